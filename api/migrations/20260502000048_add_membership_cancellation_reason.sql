@@ -0,0 +1,4 @@
+-- Record why a user canceled their membership, for churn analysis.
+-- cancellation_reason holds a fixed reason code; cancellation_feedback is optional free text.
+ALTER TABLE users ADD COLUMN cancellation_reason VARCHAR(50);
+ALTER TABLE users ADD COLUMN cancellation_feedback TEXT;
@@ -0,0 +1,4 @@
+-- Retire applications instead of deleting them, so audit history and
+-- download/pull records keep pointing at a real row.
+-- Retired applications are hidden from every listing and lookup.
+ALTER TABLE applications ADD COLUMN retired_at TIMESTAMPTZ;
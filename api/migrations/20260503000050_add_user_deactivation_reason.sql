@@ -0,0 +1,3 @@
+-- Optional admin-supplied reason recorded when an account is deactivated or deleted.
+-- Cleared when deleted_at is cleared.
+ALTER TABLE users ADD COLUMN deactivation_reason TEXT;
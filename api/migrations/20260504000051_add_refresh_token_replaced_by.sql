@@ -0,0 +1,4 @@
+-- Successor of a refresh token revoked by rotation. Lets a concurrent refresh
+-- that presents the just-rotated token be told apart from real reuse.
+ALTER TABLE refresh_tokens
+    ADD COLUMN replaced_by UUID REFERENCES refresh_tokens(id) ON DELETE SET NULL;
@@ -0,0 +1,21 @@
+-- Trigram index for admin user search (substring match on email). pg_trgm is
+-- optional: where the role may not create extensions the search falls back to
+-- a sequential scan.
+DO $$
+BEGIN
+    CREATE EXTENSION IF NOT EXISTS pg_trgm;
+EXCEPTION
+    WHEN insufficient_privilege OR undefined_file THEN
+        RAISE NOTICE 'pg_trgm unavailable; user search will not be index-backed';
+END
+$$;
+
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'pg_trgm') THEN
+        CREATE INDEX IF NOT EXISTS idx_users_email_trgm
+            ON users USING gin (LOWER(email) gin_trgm_ops)
+            WHERE deleted_at IS NULL;
+    END IF;
+END
+$$;
@@ -0,0 +1,6 @@
+-- Tokens for setting a first password on passwordless (magic link / OAuth)
+-- accounts share this table with reset tokens; the purpose keeps one kind
+-- from being redeemed through the other flow.
+ALTER TABLE password_reset_tokens
+    ADD COLUMN purpose TEXT NOT NULL DEFAULT 'reset'
+        CHECK (purpose IN ('reset', 'set_password'));
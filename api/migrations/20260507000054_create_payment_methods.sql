@@ -0,0 +1,12 @@
+-- Card shown in the dashboard for each customer, synced from Stripe webhooks.
+-- Only display metadata is kept; card numbers never leave Stripe.
+CREATE TABLE payment_methods (
+    user_id                  UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    stripe_payment_method_id TEXT NOT NULL,
+    brand                    TEXT NOT NULL,
+    last4                    TEXT NOT NULL CHECK (last4 ~ '^[0-9]{4}$'),
+    exp_month                INTEGER NOT NULL CHECK (exp_month BETWEEN 1 AND 12),
+    exp_year                 INTEGER NOT NULL,
+    created_at               TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at               TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
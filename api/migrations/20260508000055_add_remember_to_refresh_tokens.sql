@@ -0,0 +1,4 @@
+-- Whether the session was started with "remember me". Decides the token's
+-- lifetime, and is carried over when the token is rotated.
+ALTER TABLE refresh_tokens
+    ADD COLUMN remember BOOLEAN NOT NULL DEFAULT TRUE;
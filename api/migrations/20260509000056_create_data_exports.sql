@@ -0,0 +1,15 @@
+-- Asynchronous user data (GDPR) exports. The finished bundle is kept here
+-- until expires_at and handed out through short-lived signed URLs.
+CREATE TABLE data_exports (
+    id           UUID        PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id      UUID        NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status       VARCHAR(20) NOT NULL DEFAULT 'queued'
+                 CHECK (status IN ('queued', 'ready', 'failed')),
+    bundle       JSONB,
+    error        TEXT,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ,
+    expires_at   TIMESTAMPTZ
+);
+
+CREATE INDEX idx_data_exports_user_id ON data_exports(user_id);
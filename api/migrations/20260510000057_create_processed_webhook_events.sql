@@ -0,0 +1,7 @@
+-- Stripe delivers webhooks at least once. Each event ID is recorded here
+-- before its handler runs so redeliveries can be acknowledged and skipped.
+CREATE TABLE processed_webhook_events (
+    event_id     TEXT         PRIMARY KEY,
+    event_type   VARCHAR(100) NOT NULL,
+    processed_at TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
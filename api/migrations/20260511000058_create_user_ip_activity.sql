@@ -0,0 +1,12 @@
+-- IP addresses each account has signed in or refreshed from, for fraud
+-- review and support. Only the most recently seen addresses per user are kept.
+CREATE TABLE user_ip_activity (
+    user_id    UUID        NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    ip_address INET        NOT NULL,
+    first_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    count      BIGINT      NOT NULL DEFAULT 1,
+    PRIMARY KEY (user_id, ip_address)
+);
+
+CREATE INDEX idx_user_ip_activity_last_seen ON user_ip_activity(user_id, last_seen DESC);
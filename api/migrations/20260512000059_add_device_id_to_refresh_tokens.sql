@@ -0,0 +1,8 @@
+-- Client-supplied device identifier (X-Device-Id). Carried over when the
+-- token is rotated, so a new login on the same device can replace the
+-- session it already has.
+ALTER TABLE refresh_tokens
+    ADD COLUMN device_id TEXT;
+
+CREATE INDEX idx_refresh_tokens_user_device ON refresh_tokens(user_id, device_id)
+    WHERE device_id IS NOT NULL AND revoked_at IS NULL;
@@ -0,0 +1,7 @@
+-- Access tokens issued to a user at or before this time are rejected. Set
+-- when a change to the user's claims forces a token refresh, so the
+-- revocation reaches every API replica and survives a restart.
+ALTER TABLE users ADD COLUMN tokens_valid_after TIMESTAMPTZ;
+
+CREATE INDEX idx_users_tokens_valid_after ON users (tokens_valid_after)
+    WHERE tokens_valid_after IS NOT NULL;
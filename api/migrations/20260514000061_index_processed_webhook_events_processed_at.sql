@@ -0,0 +1,4 @@
+-- Event IDs are now recorded only after their handlers succeed, and the
+-- maintenance job deletes them once Stripe can no longer redeliver them.
+CREATE INDEX idx_processed_webhook_events_processed_at
+    ON processed_webhook_events (processed_at);
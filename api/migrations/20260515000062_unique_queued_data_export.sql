@@ -0,0 +1,14 @@
+-- One queued export per user. Earlier duplicates are failed so the index
+-- can be built; the newest queued job per user is kept.
+UPDATE data_exports d
+SET status = 'failed', error = 'Export was interrupted', completed_at = NOW()
+WHERE d.status = 'queued'
+  AND EXISTS (
+      SELECT 1 FROM data_exports newer
+      WHERE newer.user_id = d.user_id
+        AND newer.status = 'queued'
+        AND (newer.created_at, newer.id) > (d.created_at, d.id)
+  );
+
+CREATE UNIQUE INDEX idx_data_exports_one_queued_per_user
+    ON data_exports(user_id) WHERE status = 'queued';
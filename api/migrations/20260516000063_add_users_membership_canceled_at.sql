@@ -0,0 +1,8 @@
+-- When the user's membership last moved to 'canceled'. Only a subscription
+-- created after this time may reactivate the membership, so a replayed or
+-- late event for the old subscription is ignored.
+ALTER TABLE users ADD COLUMN membership_canceled_at TIMESTAMPTZ;
+
+-- Best available estimate for memberships canceled before this column existed
+UPDATE users SET membership_canceled_at = updated_at
+WHERE subscription_status = 'canceled';
@@ -0,0 +1,5 @@
+-- When a rotated token was last exchanged inside the grace window. A
+-- rotated token gets at most one such reissue, so replaying it cannot keep
+-- minting sessions.
+ALTER TABLE refresh_tokens
+    ADD COLUMN grace_reissued_at TIMESTAMPTZ;
@@ -0,0 +1,6 @@
+-- A retired application keeps its row but gives up its slug, so a new
+-- application can reuse it. Only applications that are not retired need
+-- unique slugs.
+ALTER TABLE applications DROP CONSTRAINT applications_slug_key;
+CREATE UNIQUE INDEX idx_applications_slug_not_retired
+    ON applications(slug) WHERE retired_at IS NULL;
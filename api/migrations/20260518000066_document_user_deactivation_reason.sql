@@ -0,0 +1,5 @@
+-- 20260503000050 says deactivation_reason is cleared along with deleted_at,
+-- but nothing clears either column: the reason stays as recorded. That file
+-- has already been applied, so the correction is recorded on the column.
+COMMENT ON COLUMN users.deactivation_reason IS
+    'Optional admin-supplied reason recorded when the account was deactivated or deleted; kept as recorded';
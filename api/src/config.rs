@@ -1,6 +1,10 @@
+use ipnetwork::IpNetwork;
+use std::collections::HashMap;
 use std::env;
 use tracing::info;
 
+use crate::models::{AuditAction, AuditSeverity, SubscriptionTier};
+
 /// Application configuration loaded from environment variables
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -14,6 +18,10 @@ pub struct Config {
     pub log_level: String,
     /// CORS allowed origin
     pub cors_origin: String,
+    /// Whether cross-origin requests may carry cookies (CORS_ALLOW_CREDENTIALS)
+    pub cors_allow_credentials: bool,
+    /// Frontend URL to land on after a magic link is verified via email click-through
+    pub magic_link_redirect_url: String,
     /// Environment (development, production)
     pub environment: String,
     /// Application name used in emails, JWT issuer, etc.
@@ -22,8 +30,85 @@ pub struct Config {
     pub email: EmailConfig,
     /// Cookie domain (e.g., ".yourdomain.com" for production, empty for localhost)
     pub cookie_domain: Option<String>,
+    /// Whether auth cookies carry the `Secure` attribute. Set via COOKIE_SECURE,
+    /// otherwise derived from whether the public base URL (APP_URL) is https.
+    pub cookie_secure: bool,
     /// Auto-ban configuration
     pub auto_ban: AutoBanConfig,
+    /// Reserved addresses and denied domains for self-service signup
+    pub signup_policy: SignupPolicyConfig,
+    /// Hosts browser redirects may point at
+    pub redirect: RedirectConfig,
+    /// Captcha verification on register and magic-link requests
+    pub captcha: CaptchaConfig,
+    /// Flag logins implying implausible travel since the previous login
+    pub impossible_travel: ImpossibleTravelConfig,
+    /// Response compression
+    pub compression: CompressionConfig,
+    /// Push alerts on 5xx responses
+    pub error_alerts: ErrorAlertConfig,
+    /// Redirect plain-HTTP requests to https
+    pub https_redirect: HttpsRedirectConfig,
+    /// Audit log masking and policy
+    pub audit: AuditConfig,
+    /// Server-side secret mixed into password hashes (PASSWORD_PEPPER)
+    pub password_pepper: Option<PasswordPepperConfig>,
+    /// File of banned passwords, one per line (BANNED_PASSWORDS_FILE); `None`
+    /// keeps the small embedded list
+    pub banned_passwords_file: Option<String>,
+    /// Argon2id cost parameters for new password hashes
+    pub argon2: Argon2Config,
+    /// Maximum active sessions (refresh tokens) per user; 0 means unlimited
+    pub max_sessions_per_user: u32,
+    /// A login carrying an `X-Device-Id` replaces the user's existing session
+    /// on that device (UNIQUE_SESSION_PER_DEVICE)
+    pub unique_session_per_device: bool,
+    /// Seconds a just-rotated refresh token is still accepted from a concurrent
+    /// refresh (REFRESH_REUSE_GRACE_SECS); 0 disables the grace window
+    pub refresh_reuse_grace_secs: u64,
+    /// Platform JWT signing algorithm and key files
+    pub jwt_signing: JwtSigningConfig,
+    /// Key from before the last signing-key rotation, still accepted when verifying
+    pub jwt_retired_key: Option<RetiredJwtKey>,
+    /// Clock skew tolerated when checking token `exp`/`nbf` (JWT_LEEWAY_SECS),
+    /// at most [`MAX_JWT_LEEWAY_SECS`]
+    pub jwt_leeway_secs: u64,
+    /// Seconds an expired access token is still accepted on safe requests
+    /// (JWT_READ_GRACE_SECS); 0 disables, at most [`MAX_JWT_READ_GRACE_SECS`]
+    pub jwt_read_grace_secs: u64,
+    /// Email the user when a replayed OIDC refresh token revokes their
+    /// session (REFRESH_REUSE_NOTIFY_USER)
+    pub refresh_reuse_notify_user: bool,
+    /// Offset cap for list endpoints
+    pub pagination: PaginationConfig,
+    /// Audit actions that force an access token refresh (TOKEN_REFRESH_ACTIONS);
+    /// `None` uses the built-in set of role, tier and membership changes
+    pub token_refresh_actions: Option<Vec<String>>,
+    /// API-wide request limit per caller, by subscription tier
+    pub api_rate_limit: ApiRateLimitConfig,
+    /// Fraction of a rate limit from which responses carry `X-RateLimit-Warning`;
+    /// 0 disables the warning (RATE_LIMIT_WARNING_THRESHOLD)
+    pub rate_limit_warning_threshold: f64,
+    /// Refuse to start when a startup preflight check fails; otherwise failures
+    /// are only logged (PREFLIGHT_STRICT, default on in production)
+    pub preflight_strict: bool,
+    /// Send a one-time welcome email on a user's first login (WELCOME_EMAIL_ON_FIRST_LOGIN)
+    pub welcome_email_on_first_login: bool,
+    /// Accept urlencoded form bodies on auth endpoints (AUTH_ACCEPT_FORM_BODIES)
+    pub auth_accept_form_bodies: bool,
+    /// Deny admin endpoints to staff roles without 2FA (REQUIRE_ADMIN_2FA)
+    pub require_admin_2fa: bool,
+    /// Changing email, disabling 2FA and deleting the account require a
+    /// sign-in at most this many seconds old; 0 disables (STEP_UP_MAX_AGE_SECS)
+    pub step_up_max_age_secs: u64,
+    /// Stripe webhook path segment under `/v1/webhooks/` (STRIPE_WEBHOOK_PATH)
+    pub stripe_webhook_path: String,
+    /// Minutes between checks for expired grace periods
+    /// (GRACE_PERIOD_CHECK_INTERVAL_MINS)
+    pub grace_period_check_interval_mins: u64,
+    /// Minutes between deletions of expired tokens, rate-limit windows and
+    /// IP bans (MAINTENANCE_INTERVAL_MINS)
+    pub maintenance_interval_mins: u64,
     /// TOTP encryption key (32 bytes) for encrypting TOTP secrets at rest
     pub totp_encryption_key: [u8; 32],
     /// Previous TOTP encryption key for rotation (optional)
@@ -174,9 +259,19 @@ pub struct AutoBanConfig {
     pub window_secs: u64,
     /// How long a ban lasts in seconds
     pub ban_duration_secs: u64,
+    /// Lowercased User-Agent patterns that count as strikes. `=agent` matches
+    /// the whole header; anything else matches a substring. Empty disables
+    /// the check.
+    pub user_agent_denylist: Vec<String>,
+    /// Lowercased User-Agent substrings never counted, even if denylisted
+    pub user_agent_allowlist: Vec<String>,
 }
 
 impl AutoBanConfig {
+    /// Scanner User-Agents flagged when AUTO_BAN_USER_AGENTS is unset
+    pub const DEFAULT_USER_AGENT_DENYLIST: &'static str =
+        "sqlmap,nikto,masscan,nmap,zgrab,nuclei,gobuster,dirbuster,wpscan,acunetix";
+
     /// Load auto-ban configuration from environment variables
     pub fn from_env() -> Self {
         Self {
@@ -195,10 +290,748 @@ impl AutoBanConfig {
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(86400),
+            user_agent_denylist: parse_lowercase_list(
+                &env::var("AUTO_BAN_USER_AGENTS")
+                    .unwrap_or_else(|_| Self::DEFAULT_USER_AGENT_DENYLIST.to_string()),
+            ),
+            user_agent_allowlist: parse_lowercase_list(
+                &env::var("AUTO_BAN_USER_AGENT_ALLOWLIST").unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+/// Self-service signup restrictions
+#[derive(Debug, Clone, Default)]
+pub struct SignupPolicyConfig {
+    /// Reserved addresses, lowercased. `support@example.com` reserves one address;
+    /// `support@` reserves that local part on every domain.
+    pub reserved_emails: Vec<String>,
+    /// Denied domains, lowercased. `example.com` matches that domain only;
+    /// `*.example.com` also matches its subdomains.
+    pub denied_domains: Vec<String>,
+}
+
+impl SignupPolicyConfig {
+    /// Load signup restrictions from RESERVED_EMAILS and DENIED_SIGNUP_DOMAINS
+    /// (comma-separated)
+    pub fn from_env() -> Self {
+        Self {
+            reserved_emails: parse_lowercase_list(&env::var("RESERVED_EMAILS").unwrap_or_default()),
+            denied_domains: parse_lowercase_list(
+                &env::var("DENIED_SIGNUP_DOMAINS").unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+/// Offset pagination limits, passed to every list handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaginationConfig {
+    /// Deepest `(page - 1) * per_page` offset list endpoints serve
+    /// (MAX_PAGINATION_OFFSET)
+    pub max_offset: i64,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            max_offset: crate::pagination::DEFAULT_MAX_OFFSET,
+        }
+    }
+}
+
+impl PaginationConfig {
+    /// Load the offset cap from MAX_PAGINATION_OFFSET, falling back to the
+    /// default when unset or not a number
+    pub fn from_env() -> Self {
+        Self {
+            max_offset: env::var("MAX_PAGINATION_OFFSET")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(crate::pagination::DEFAULT_MAX_OFFSET),
+        }
+    }
+}
+
+/// Hosts the API may redirect browsers to (see `crate::redirect`)
+#[derive(Debug, Clone, Default)]
+pub struct RedirectConfig {
+    /// Lowercased hosts. `example.com` matches that host only;
+    /// `*.example.com` also matches its subdomains.
+    pub allowed_hosts: Vec<String>,
+}
+
+impl RedirectConfig {
+    /// Load from REDIRECT_ALLOWED_HOSTS (comma-separated). When unset, the
+    /// cookie domain (or the frontend host) and its subdomains are allowed.
+    pub fn from_env(cors_origin: &str, cookie_domain: Option<&str>) -> Self {
+        Self::resolve(
+            env::var("REDIRECT_ALLOWED_HOSTS").ok().as_deref(),
+            cors_origin,
+            cookie_domain,
+        )
+    }
+
+    fn resolve(
+        override_value: Option<&str>,
+        cors_origin: &str,
+        cookie_domain: Option<&str>,
+    ) -> Self {
+        let allowed_hosts = parse_lowercase_list(override_value.unwrap_or_default());
+        if !allowed_hosts.is_empty() {
+            return Self { allowed_hosts };
+        }
+
+        let base = cookie_domain
+            .map(|d| d.trim_start_matches('.').to_lowercase())
+            .filter(|d| !d.is_empty())
+            .or_else(|| {
+                url::Url::parse(cors_origin)
+                    .ok()
+                    .and_then(|u| u.host_str().map(str::to_lowercase))
+            });
+        Self {
+            allowed_hosts: base.map(|b| format!("*.{}", b)).into_iter().collect(),
+        }
+    }
+}
+
+/// Bot-protection provider for signup forms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaProvider {
+    Turnstile,
+    HCaptcha,
+}
+
+impl CaptchaProvider {
+    /// Token verification (siteverify) endpoint
+    pub fn verify_url(&self) -> &'static str {
+        match self {
+            CaptchaProvider::Turnstile => {
+                "https://challenges.cloudflare.com/turnstile/v0/siteverify"
+            }
+            CaptchaProvider::HCaptcha => "https://api.hcaptcha.com/siteverify",
+        }
+    }
+}
+
+/// Captcha verification on register and magic-link requests
+#[derive(Debug, Clone)]
+pub struct CaptchaConfig {
+    /// Whether a captcha token is required
+    pub enabled: bool,
+    pub provider: CaptchaProvider,
+    /// Provider secret key used to verify tokens
+    pub secret_key: Option<String>,
+    /// Verification endpoint; defaults to the provider's siteverify URL
+    pub verify_url: String,
+    /// Let requests through when verification cannot be performed (missing
+    /// secret, provider unreachable). Only ever true outside production.
+    pub fail_open: bool,
+}
+
+impl CaptchaConfig {
+    /// Load captcha configuration from environment variables
+    pub fn from_env(is_production: bool) -> Self {
+        let provider = match env::var("CAPTCHA_PROVIDER").as_deref() {
+            Ok("hcaptcha") => CaptchaProvider::HCaptcha,
+            _ => CaptchaProvider::Turnstile,
+        };
+        Self {
+            enabled: env::var("CAPTCHA_ENABLED")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            provider,
+            secret_key: env::var("CAPTCHA_SECRET_KEY")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            verify_url: env::var("CAPTCHA_VERIFY_URL")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| provider.verify_url().to_string()),
+            fail_open: !is_production,
+        }
+    }
+}
+
+/// Audit log settings, passed to every `AuditLogRepository::create`
+#[derive(Debug, Clone, Default)]
+pub struct AuditConfig {
+    /// Mask actor email/IP in audit logs (AUDIT_MASK_PII)
+    pub mask_pii: bool,
+    /// HMAC key for audit email hashes: masked actor emails and the
+    /// attempted email on failed logins (AUDIT_MASK_KEY)
+    pub mask_key: String,
+    /// Which routine entries are recorded
+    pub policy: AuditPolicyConfig,
+}
+
+impl AuditConfig {
+    /// `ip` as stored alongside a user's records outside the audit log,
+    /// truncated the same way as audit entries when `mask_pii` is set
+    pub fn stored_ip(&self, ip: std::net::IpAddr) -> IpNetwork {
+        let ip = IpNetwork::from(ip);
+        if self.mask_pii {
+            crate::models::audit::mask_audit_ip(ip)
+        } else {
+            ip
+        }
+    }
+}
+
+/// Suppression and sampling of routine audit entries.
+///
+/// Admin, billing and security actions are always recorded. Any other entry below
+/// `min_severity`, or whose action is listed in `suppressed_actions`, is
+/// kept with probability `sample_rate`.
+#[derive(Debug, Clone)]
+pub struct AuditPolicyConfig {
+    pub min_severity: AuditSeverity,
+    /// Action names (e.g. `user_login`), lowercased
+    pub suppressed_actions: Vec<String>,
+    /// Fraction (0.0-1.0) of routine entries to keep anyway
+    pub sample_rate: f64,
+}
+
+impl Default for AuditPolicyConfig {
+    /// Record everything
+    fn default() -> Self {
+        Self {
+            min_severity: AuditSeverity::Info,
+            suppressed_actions: Vec::new(),
+            sample_rate: 0.0,
+        }
+    }
+}
+
+impl AuditPolicyConfig {
+    /// Load from AUDIT_MIN_SEVERITY, AUDIT_SUPPRESS_ACTIONS (comma-separated)
+    /// and AUDIT_SAMPLE_RATE
+    pub fn from_env() -> Self {
+        let min_severity = match env::var("AUDIT_MIN_SEVERITY") {
+            Ok(value) => AuditSeverity::parse(&value.trim().to_lowercase()).unwrap_or_else(|| {
+                tracing::warn!(value = %value, "Unknown AUDIT_MIN_SEVERITY, recording all severities");
+                AuditSeverity::Info
+            }),
+            Err(_) => AuditSeverity::Info,
+        };
+        Self {
+            min_severity,
+            suppressed_actions: parse_lowercase_list(
+                &env::var("AUDIT_SUPPRESS_ACTIONS").unwrap_or_default(),
+            ),
+            sample_rate: env::var("AUDIT_SAMPLE_RATE")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .unwrap_or(0.0)
+                .clamp(0.0, 1.0),
+        }
+    }
+
+    /// Whether an entry should be written. `roll` is a uniform sample in
+    /// `[0, 1)` used for sampling routine entries.
+    pub fn should_record(&self, action: &AuditAction, severity: AuditSeverity, roll: f64) -> bool {
+        if action.is_admin_action() || action.is_billing_action() || action.is_security_action() {
+            return true;
+        }
+        let routine = severity < self.min_severity
+            || self.suppressed_actions.iter().any(|a| a == action.as_str());
+        !routine || roll < self.sample_rate
+    }
+
+    /// Whether every entry is recorded, making the policy a no-op
+    pub fn records_everything(&self) -> bool {
+        self.min_severity == AuditSeverity::Info && self.suppressed_actions.is_empty()
+    }
+}
+
+/// "Impossible travel" check on password login
+#[derive(Debug, Clone)]
+pub struct ImpossibleTravelConfig {
+    /// Whether logins are compared against the previous login's location
+    pub enabled: bool,
+    /// GeoIP lookup URL containing `{ip}`. There is no default provider:
+    /// logins' IPs are only sent to a service the operator chose.
+    pub geo_url: Option<String>,
+    /// Implied speeds above this are flagged (roughly airliner cruise speed)
+    pub max_speed_kmh: f64,
+    /// Logins closer than this are never flagged, to absorb GeoIP jitter
+    pub min_distance_km: f64,
+    /// Refuse flagged password logins unless the user completes 2FA
+    pub require_step_up: bool,
+    /// Email the user when a login is flagged
+    pub notify_user: bool,
+}
+
+impl ImpossibleTravelConfig {
+    /// Load impossible-travel configuration from environment variables.
+    /// Enabling the check without IMPOSSIBLE_TRAVEL_GEO_URL is an error.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let flag = |name: &str| {
+            env::var(name)
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false)
+        };
+        let number = |name: &str, default: f64| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        let enabled = flag("IMPOSSIBLE_TRAVEL_ENABLED");
+        let geo_url = env::var("IMPOSSIBLE_TRAVEL_GEO_URL")
+            .ok()
+            .filter(|s| !s.trim().is_empty());
+        if enabled && geo_url.is_none() {
+            return Err(ConfigError::MissingEnv(
+                "IMPOSSIBLE_TRAVEL_GEO_URL".to_string(),
+            ));
+        }
+        Ok(Self {
+            enabled,
+            geo_url,
+            max_speed_kmh: number("IMPOSSIBLE_TRAVEL_MAX_SPEED_KMH", 1000.0),
+            min_distance_km: number("IMPOSSIBLE_TRAVEL_MIN_DISTANCE_KM", 500.0),
+            require_step_up: flag("IMPOSSIBLE_TRAVEL_REQUIRE_STEP_UP"),
+            notify_user: flag("IMPOSSIBLE_TRAVEL_NOTIFY_USER"),
+        })
+    }
+}
+
+/// Push alerts to an ops channel on 5xx responses
+#[derive(Debug, Clone)]
+pub struct ErrorAlertConfig {
+    /// Slack incoming webhook or any URL accepting JSON; unset disables
+    /// alerts (ERROR_ALERT_WEBHOOK_URL)
+    pub webhook_url: Option<String>,
+    /// Minimum seconds between alerts for the same error code
+    /// (ERROR_ALERT_COOLDOWN_SECS)
+    pub cooldown_secs: i64,
+}
+
+impl ErrorAlertConfig {
+    /// Load error alert configuration from environment variables
+    pub fn from_env() -> Self {
+        Self {
+            webhook_url: env::var("ERROR_ALERT_WEBHOOK_URL")
+                .ok()
+                .filter(|s| !s.trim().is_empty()),
+            cooldown_secs: env::var("ERROR_ALERT_COOLDOWN_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300)
+                .max(1),
+        }
+    }
+}
+
+/// Upgrade plain-HTTP requests (see `middleware::HttpsRedirect`)
+#[derive(Debug, Clone)]
+pub struct HttpsRedirectConfig {
+    /// Whether plain-HTTP requests are redirected (HTTPS_REDIRECT)
+    pub enabled: bool,
+    /// Peers whose `X-Forwarded-Proto` is believed; IPs or CIDRs
+    /// (HTTPS_REDIRECT_TRUSTED_PROXIES, comma-separated, default loopback)
+    pub trusted_proxies: Vec<IpNetwork>,
+}
+
+impl HttpsRedirectConfig {
+    /// Default `HTTPS_REDIRECT_TRUSTED_PROXIES`: a proxy on the same host
+    pub const DEFAULT_TRUSTED_PROXIES: &'static str = "127.0.0.1,::1";
+
+    /// Load HTTPS redirect configuration from environment variables
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(Self {
+            enabled: env::var("HTTPS_REDIRECT")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            trusted_proxies: parse_trusted_proxies(
+                &env::var("HTTPS_REDIRECT_TRUSTED_PROXIES")
+                    .unwrap_or_else(|_| Self::DEFAULT_TRUSTED_PROXIES.to_string()),
+            )?,
+        })
+    }
+}
+
+/// Parse comma-separated IPs and CIDRs; a bare IP matches that address only
+fn parse_trusted_proxies(value: &str) -> Result<Vec<IpNetwork>, ConfigError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry.parse().map_err(|_| {
+                ConfigError::InvalidValue(
+                    "HTTPS_REDIRECT_TRUSTED_PROXIES".to_string(),
+                    format!("`{}` is not an IP address or CIDR", entry),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Response compression (gzip/brotli, negotiated via `Accept-Encoding`)
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    /// Whether responses are compressed at all (COMPRESSION_ENABLED)
+    pub enabled: bool,
+    /// Responses smaller than this many bytes are sent as-is (COMPRESSION_MIN_SIZE)
+    pub min_size: usize,
+}
+
+impl CompressionConfig {
+    /// Load compression configuration from environment variables
+    pub fn from_env() -> Self {
+        Self {
+            enabled: env::var("COMPRESSION_ENABLED")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(true),
+            min_size: env::var("COMPRESSION_MIN_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1024),
+        }
+    }
+}
+
+/// API-wide request limit (see `middleware::ApiRateLimit`). Anonymous callers
+/// get `RateLimitConfig::API_UNAUTH` per IP; signed-in callers get
+/// `RateLimitConfig::API_AUTH` per user, raised for member tiers listed here.
+#[derive(Debug, Clone)]
+pub struct ApiRateLimitConfig {
+    /// Whether the limiter runs at all (API_RATE_LIMIT_ENABLED)
+    pub enabled: bool,
+    /// Requests per minute by subscription tier for users with member access
+    /// (API_RATE_LIMIT_TIERS, comma-separated `tier:limit` pairs)
+    pub tier_limits: HashMap<String, i32>,
+}
+
+impl ApiRateLimitConfig {
+    /// Default `API_RATE_LIMIT_TIERS`: paying tiers get three times the base
+    /// limit, lifetime members six. `free` is left at the base limit.
+    pub const DEFAULT_TIER_LIMITS: &'static str = "standard:300,early_adopter:300,lifetime:600";
+
+    /// Load API rate limit configuration from environment variables
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(Self {
+            enabled: env::var("API_RATE_LIMIT_ENABLED")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            tier_limits: parse_tier_limits(
+                &env::var("API_RATE_LIMIT_TIERS")
+                    .unwrap_or_else(|_| Self::DEFAULT_TIER_LIMITS.to_string()),
+            )?,
+        })
+    }
+}
+
+/// Parse `tier:limit` pairs separated by commas. Tiers must be known
+/// subscription tiers and limits positive.
+fn parse_tier_limits(value: &str) -> Result<HashMap<String, i32>, ConfigError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .split_once(':')
+                .and_then(|(tier, limit)| {
+                    let tier = tier.trim().to_lowercase();
+                    let limit: i32 = limit.trim().parse().ok()?;
+                    let known = SubscriptionTier::from(tier.as_str()).as_str() == tier;
+                    (known && limit > 0).then_some((tier, limit))
+                })
+                .ok_or_else(|| {
+                    ConfigError::InvalidValue(
+                        "API_RATE_LIMIT_TIERS".to_string(),
+                        "entries must be `tier:limit` with a known tier and a positive limit"
+                            .to_string(),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Read TIER_DEFAULT_SIGNUP (default `standard`). Accepts the self-serve
+/// tiers, `standard` and `early_adopter`, which are paid once any trial
+/// ends. `lifetime` and `free` are never paid and are handed out by slot
+/// count or by an admin, so they and unknown tiers are rejected.
+pub fn default_signup_tier_from_env() -> Result<SubscriptionTier, ConfigError> {
+    let Ok(value) = env::var("TIER_DEFAULT_SIGNUP") else {
+        return Ok(SubscriptionTier::Standard);
+    };
+    parse_default_signup_tier(&value)
+}
+
+fn parse_default_signup_tier(value: &str) -> Result<SubscriptionTier, ConfigError> {
+    let value = value.trim().to_lowercase();
+    let tier = SubscriptionTier::from(value.as_str());
+    if tier.as_str() != value {
+        return Err(ConfigError::InvalidValue(
+            "TIER_DEFAULT_SIGNUP".to_string(),
+            format!("unknown tier `{value}`"),
+        ));
+    }
+    if matches!(tier, SubscriptionTier::Lifetime | SubscriptionTier::Free) {
+        return Err(ConfigError::InvalidValue(
+            "TIER_DEFAULT_SIGNUP".to_string(),
+            format!("`{value}` is granted by slot or by an admin, not on signup"),
+        ));
+    }
+    Ok(tier)
+}
+
+/// Password pepper, applied as HMAC-SHA256(pepper, password) before Argon2.
+///
+/// New hashes record `version`, so the pepper can be rotated: bump
+/// PASSWORD_PEPPER_VERSION, set the new PASSWORD_PEPPER and move the old one
+/// to PASSWORD_PEPPER_PREVIOUS until every user has logged in again.
+#[derive(Debug, Clone)]
+pub struct PasswordPepperConfig {
+    /// Version recorded on new hashes (PASSWORD_PEPPER_VERSION, default 1)
+    pub version: u32,
+    /// Current pepper secret (PASSWORD_PEPPER)
+    pub secret: String,
+    /// Retired peppers still accepted when verifying, as `(version, secret)`
+    /// (PASSWORD_PEPPER_PREVIOUS, comma-separated `version:secret` pairs)
+    pub previous: Vec<(u32, String)>,
+}
+
+impl PasswordPepperConfig {
+    /// Load the pepper from environment variables; `None` when PASSWORD_PEPPER is unset
+    pub fn from_env() -> Result<Option<Self>, ConfigError> {
+        let Some(secret) = env::var("PASSWORD_PEPPER").ok().filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+        let version = match env::var("PASSWORD_PEPPER_VERSION") {
+            Ok(v) => v.trim().parse().map_err(|_| {
+                ConfigError::InvalidValue(
+                    "PASSWORD_PEPPER_VERSION".to_string(),
+                    "must be a non-negative integer".to_string(),
+                )
+            })?,
+            Err(_) => 1,
+        };
+        let previous =
+            parse_previous_peppers(&env::var("PASSWORD_PEPPER_PREVIOUS").unwrap_or_default())?;
+        if previous.iter().any(|(v, _)| *v == version) {
+            return Err(ConfigError::InvalidValue(
+                "PASSWORD_PEPPER_PREVIOUS".to_string(),
+                format!("must not reuse the current version {version}"),
+            ));
         }
+        Ok(Some(Self {
+            version,
+            secret,
+            previous,
+        }))
     }
 }
 
+/// Argon2id cost parameters for password hashing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Config {
+    /// Memory per hash in KiB (ARGON2_MEMORY_KIB, default 64 MiB)
+    pub memory_kib: u32,
+    /// Passes over the memory (ARGON2_ITERATIONS, default 3)
+    pub iterations: u32,
+    /// Lanes (ARGON2_PARALLELISM, default 4)
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Config {
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 4,
+        }
+    }
+}
+
+impl Argon2Config {
+    /// Load the cost parameters from environment variables, falling back to
+    /// the defaults for any that are unset
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        let read = |name: &str, default: u32| match env::var(name) {
+            Ok(v) => v.trim().parse().map_err(|_| {
+                ConfigError::InvalidValue(
+                    name.to_string(),
+                    "must be a positive integer".to_string(),
+                )
+            }),
+            Err(_) => Ok(default),
+        };
+        let config = Self {
+            memory_kib: read("ARGON2_MEMORY_KIB", defaults.memory_kib)?,
+            iterations: read("ARGON2_ITERATIONS", defaults.iterations)?,
+            parallelism: read("ARGON2_PARALLELISM", defaults.parallelism)?,
+        };
+        argon2::Params::new(
+            config.memory_kib,
+            config.iterations,
+            config.parallelism,
+            None,
+        )
+        .map_err(|e| ConfigError::InvalidValue("ARGON2_*".to_string(), e.to_string()))?;
+        Ok(config)
+    }
+}
+
+/// Parse `version:secret` pairs separated by commas.
+fn parse_previous_peppers(value: &str) -> Result<Vec<(u32, String)>, ConfigError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .split_once(':')
+                .and_then(|(version, secret)| {
+                    let version = version.trim().parse().ok()?;
+                    (!secret.is_empty()).then(|| (version, secret.to_string()))
+                })
+                .ok_or_else(|| {
+                    ConfigError::InvalidValue(
+                        "PASSWORD_PEPPER_PREVIOUS".to_string(),
+                        "entries must be `version:secret`".to_string(),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// How platform JWTs are signed (JWT_ALGORITHM). The key files are read at
+/// startup; only their paths are kept here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JwtSigningConfig {
+    /// HMAC-SHA256 with JWT_SECRET (the default)
+    Hs256,
+    /// RSA key pair from JWT_PRIVATE_KEY_PATH and JWT_PUBLIC_KEY_PATH
+    Rs256 {
+        private_key_path: String,
+        public_key_path: String,
+    },
+    /// Ed25519 key pair from JWT_PRIVATE_KEY_PATH and JWT_PUBLIC_KEY_PATH
+    EdDsa {
+        private_key_path: String,
+        public_key_path: String,
+    },
+}
+
+impl JwtSigningConfig {
+    /// Load the signing algorithm and, for RS256 and EdDSA, the key paths
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::resolve(
+            env::var("JWT_ALGORITHM").ok().as_deref(),
+            env::var("JWT_PRIVATE_KEY_PATH").ok(),
+            env::var("JWT_PUBLIC_KEY_PATH").ok(),
+        )
+    }
+
+    /// The `alg` name tokens are signed with
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Self::Hs256 => "HS256",
+            Self::Rs256 { .. } => "RS256",
+            Self::EdDsa { .. } => "EdDSA",
+        }
+    }
+
+    fn resolve(
+        algorithm: Option<&str>,
+        private_key_path: Option<String>,
+        public_key_path: Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let algorithm = algorithm
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .unwrap_or("HS256");
+        let required = |name: &str, value: Option<String>| {
+            value.filter(|v| !v.trim().is_empty()).ok_or_else(|| {
+                ConfigError::InvalidValue(
+                    name.to_string(),
+                    format!("must be set when JWT_ALGORITHM={algorithm}"),
+                )
+            })
+        };
+        match algorithm {
+            "HS256" => Ok(Self::Hs256),
+            "RS256" => Ok(Self::Rs256 {
+                private_key_path: required("JWT_PRIVATE_KEY_PATH", private_key_path)?,
+                public_key_path: required("JWT_PUBLIC_KEY_PATH", public_key_path)?,
+            }),
+            "EdDSA" => Ok(Self::EdDsa {
+                private_key_path: required("JWT_PRIVATE_KEY_PATH", private_key_path)?,
+                public_key_path: required("JWT_PUBLIC_KEY_PATH", public_key_path)?,
+            }),
+            other => Err(ConfigError::InvalidValue(
+                "JWT_ALGORITHM".to_string(),
+                format!("`{other}` is not supported; use HS256, RS256 or EdDSA"),
+            )),
+        }
+    }
+}
+
+/// Previous platform JWT key, kept after a rotation so the tokens it signed
+/// verify until they expire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetiredJwtKey {
+    /// Old HS256 secret (JWT_RETIRED_SECRET)
+    Secret(String),
+    /// Old RS256 or EdDSA public key PEM file (JWT_RETIRED_PUBLIC_KEY_PATH)
+    PublicKeyPath(String),
+}
+
+impl RetiredJwtKey {
+    /// Load the retired key matching `signing`; `None` when none is set
+    pub fn from_env(signing: &JwtSigningConfig) -> Result<Option<Self>, ConfigError> {
+        Self::resolve(
+            signing,
+            env::var("JWT_RETIRED_SECRET").ok(),
+            env::var("JWT_RETIRED_PUBLIC_KEY_PATH").ok(),
+        )
+    }
+
+    /// A retired key of the wrong kind for the signing algorithm is an error
+    /// rather than ignored, since it would silently log out every user
+    /// holding a token from before the rotation.
+    fn resolve(
+        signing: &JwtSigningConfig,
+        secret: Option<String>,
+        public_key_path: Option<String>,
+    ) -> Result<Option<Self>, ConfigError> {
+        let secret = secret.filter(|v| !v.is_empty());
+        let public_key_path = public_key_path.filter(|v| !v.trim().is_empty());
+        match (signing, secret, public_key_path) {
+            (JwtSigningConfig::Hs256, secret, None) => Ok(secret.map(Self::Secret)),
+            (JwtSigningConfig::Hs256, _, Some(_)) => Err(ConfigError::InvalidValue(
+                "JWT_RETIRED_PUBLIC_KEY_PATH".to_string(),
+                "applies to RS256 and EdDSA only; use JWT_RETIRED_SECRET for HS256".to_string(),
+            )),
+            (_, None, path) => Ok(path.map(Self::PublicKeyPath)),
+            (signing, Some(_), _) => Err(ConfigError::InvalidValue(
+                "JWT_RETIRED_SECRET".to_string(),
+                format!(
+                    "applies to HS256 only; use JWT_RETIRED_PUBLIC_KEY_PATH for {}",
+                    signing.algorithm()
+                ),
+            )),
+        }
+    }
+}
+
+/// Split a comma-separated env value into trimmed, lowercased, non-empty entries.
+fn parse_lowercase_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|v| v.trim().to_lowercase())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
 /// Membership tier threshold configuration
 #[derive(Debug, Clone)]
 pub struct TierConfig {
@@ -210,6 +1043,11 @@ pub struct TierConfig {
     pub early_adopter_trial_days: i64,
     /// Trial duration in days for standard tier
     pub standard_trial_days: i64,
+    /// Tier label set on users auto-created by magic-link sign-in.
+    /// Only the label: trials and lifetime access are not granted by it.
+    /// Limited to tiers anyone may sign up into; see
+    /// [`default_signup_tier_from_env`].
+    pub default_signup_tier: SubscriptionTier,
     /// Stripe Price ID for lifetime members ($0 recurring). Falls back to STRIPE_FREE_PRICE_ID env var.
     pub free_price_id: Option<String>,
     /// Stripe Price ID unlocked after early adopter trial ends.
@@ -244,6 +1082,9 @@ impl TierConfig {
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(30),
+            // Validated by Config::from_env at startup
+            default_signup_tier: default_signup_tier_from_env()
+                .unwrap_or(SubscriptionTier::Standard),
             free_price_id: env::var("STRIPE_FREE_PRICE_ID")
                 .ok()
                 .filter(|s| !s.is_empty()),
@@ -266,6 +1107,7 @@ impl TierConfig {
                 .early_adopter_trial_days
                 .unwrap_or(env.early_adopter_trial_days),
             standard_trial_days: row.standard_trial_days.unwrap_or(env.standard_trial_days),
+            default_signup_tier: env.default_signup_tier,
             // free_price_id: DB value takes precedence; fall back to STRIPE_FREE_PRICE_ID env var
             free_price_id: row.free_price_id.clone().or(env.free_price_id),
             early_adopter_price_id: row.early_adopter_price_id.clone(),
@@ -480,6 +1322,16 @@ impl Config {
         let cors_origin =
             env::var("CORS_ORIGIN").unwrap_or_else(|_| "http://localhost:5173".to_string());
 
+        let cors_allow_credentials = resolve_cors_credentials(
+            env::var("CORS_ALLOW_CREDENTIALS").ok().as_deref(),
+            &cors_origin,
+        )?;
+
+        let magic_link_redirect_url = env::var("MAGIC_LINK_REDIRECT_URL")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| format!("{}/dashboard", cors_origin.trim_end_matches('/')));
+
         let environment = env::var("ENVIRONMENT").unwrap_or_else(|_| "production".to_string());
         let app_name = env::var("APP_NAME").unwrap_or_else(|_| "localhost".to_string());
         let is_production = environment == "production";
@@ -489,7 +1341,95 @@ impl Config {
         // None means cookies are scoped to the exact hostname (suitable for localhost).
         let cookie_domain = env::var("COOKIE_DOMAIN").ok().filter(|s| !s.is_empty());
 
+        let cookie_secure =
+            resolve_cookie_secure(env::var("COOKIE_SECURE").ok().as_deref(), &email.base_url)?;
+        if !cookie_secure && is_production {
+            tracing::warn!(
+                base_url = %email.base_url,
+                "Auth cookies are not marked Secure in production"
+            );
+        }
+
         let auto_ban = AutoBanConfig::from_env();
+        let signup_policy = SignupPolicyConfig::from_env();
+        let redirect = RedirectConfig::from_env(&cors_origin, cookie_domain.as_deref());
+        if !crate::redirect::is_allowed(&magic_link_redirect_url, &redirect.allowed_hosts) {
+            return Err(ConfigError::InvalidValue(
+                "MAGIC_LINK_REDIRECT_URL".to_string(),
+                "host is not in REDIRECT_ALLOWED_HOSTS".to_string(),
+            ));
+        }
+        let captcha = CaptchaConfig::from_env(is_production);
+        let impossible_travel = ImpossibleTravelConfig::from_env()?;
+        let compression = CompressionConfig::from_env();
+        let error_alerts = ErrorAlertConfig::from_env();
+        let https_redirect = HttpsRedirectConfig::from_env()?;
+        let audit_mask_pii = env::var("AUDIT_MASK_PII")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        let audit_mask_key = env::var("AUDIT_MASK_KEY").unwrap_or_default();
+        if audit_mask_key.is_empty() && (audit_mask_pii || is_production) {
+            tracing::warn!("AUDIT_MASK_KEY is not set; audit email hashes are unkeyed");
+        }
+        let audit_policy = AuditPolicyConfig::from_env();
+        let password_pepper = PasswordPepperConfig::from_env()?;
+        let banned_passwords_file = env::var("BANNED_PASSWORDS_FILE")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+        let argon2 = Argon2Config::from_env()?;
+        let max_sessions_per_user = env::var("MAX_SESSIONS_PER_USER")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+        let unique_session_per_device = env::var("UNIQUE_SESSION_PER_DEVICE")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        let refresh_reuse_grace_secs = env::var("REFRESH_REUSE_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+        let jwt_signing = JwtSigningConfig::from_env()?;
+        let jwt_retired_key = RetiredJwtKey::from_env(&jwt_signing)?;
+        let jwt_leeway_secs = resolve_leeway(env::var("JWT_LEEWAY_SECS").ok().as_deref())?;
+        let jwt_read_grace_secs =
+            resolve_read_grace(env::var("JWT_READ_GRACE_SECS").ok().as_deref())?;
+        let refresh_reuse_notify_user = env::var("REFRESH_REUSE_NOTIFY_USER")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(true);
+        let pagination = PaginationConfig::from_env();
+        let token_refresh_actions =
+            resolve_token_refresh_actions(env::var("TOKEN_REFRESH_ACTIONS").ok().as_deref())?;
+        let api_rate_limit = ApiRateLimitConfig::from_env()?;
+        let rate_limit_warning_threshold =
+            resolve_warning_threshold(env::var("RATE_LIMIT_WARNING_THRESHOLD").ok().as_deref())?;
+        let preflight_strict = env::var("PREFLIGHT_STRICT")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(is_production);
+        let welcome_email_on_first_login = env::var("WELCOME_EMAIL_ON_FIRST_LOGIN")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(true);
+        let auth_accept_form_bodies = env::var("AUTH_ACCEPT_FORM_BODIES")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        let require_admin_2fa = env::var("REQUIRE_ADMIN_2FA")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        let step_up_max_age_secs = env::var("STEP_UP_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(900);
+        let grace_period_check_interval_mins = env::var("GRACE_PERIOD_CHECK_INTERVAL_MINS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(15)
+            .max(1);
+        let maintenance_interval_mins = env::var("MAINTENANCE_INTERVAL_MINS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60)
+            .max(1);
+        let stripe_webhook_path =
+            resolve_webhook_path(env::var("STRIPE_WEBHOOK_PATH").ok().as_deref())?;
 
         let totp_encryption_key = Self::load_totp_encryption_key(&environment);
         let stripe_encryption_key = Self::load_stripe_encryption_key(&environment);
@@ -506,6 +1446,7 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .unwrap_or(1);
 
+        default_signup_tier_from_env()?;
         let tier = TierConfig::from_env();
         let download = DownloadConfig::from_env();
         let oci = OciConfig::from_env();
@@ -517,11 +1458,49 @@ impl Config {
             port,
             log_level,
             cors_origin,
+            cors_allow_credentials,
+            magic_link_redirect_url,
             environment,
             app_name,
             email,
             cookie_domain,
+            cookie_secure,
             auto_ban,
+            signup_policy,
+            redirect,
+            captcha,
+            impossible_travel,
+            compression,
+            error_alerts,
+            https_redirect,
+            audit: AuditConfig {
+                mask_pii: audit_mask_pii,
+                mask_key: audit_mask_key,
+                policy: audit_policy,
+            },
+            password_pepper,
+            banned_passwords_file,
+            argon2,
+            max_sessions_per_user,
+            unique_session_per_device,
+            refresh_reuse_grace_secs,
+            jwt_signing,
+            jwt_retired_key,
+            jwt_leeway_secs,
+            jwt_read_grace_secs,
+            refresh_reuse_notify_user,
+            pagination,
+            token_refresh_actions,
+            api_rate_limit,
+            rate_limit_warning_threshold,
+            preflight_strict,
+            welcome_email_on_first_login,
+            auth_accept_form_bodies,
+            require_admin_2fa,
+            step_up_max_age_secs,
+            stripe_webhook_path,
+            grace_period_check_interval_mins,
+            maintenance_interval_mins,
             totp_encryption_key,
             totp_encryption_key_prev,
             totp_key_version,
@@ -610,6 +1589,186 @@ impl Config {
     }
 }
 
+/// Clock-skew leeway for token verification when JWT_LEEWAY_SECS is unset
+pub const DEFAULT_JWT_LEEWAY_SECS: u64 = 30;
+
+/// Largest JWT_LEEWAY_SECS accepted; beyond this, clock skew is better fixed
+/// at the host than tolerated for every token
+pub const MAX_JWT_LEEWAY_SECS: u64 = 300;
+
+/// Seconds of clock-skew leeway for token `exp`/`nbf`: [`DEFAULT_JWT_LEEWAY_SECS`]
+/// unless overridden, never above [`MAX_JWT_LEEWAY_SECS`].
+fn resolve_leeway(override_value: Option<&str>) -> Result<u64, ConfigError> {
+    let Some(value) = override_value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(DEFAULT_JWT_LEEWAY_SECS);
+    };
+    match value.parse::<u64>() {
+        Ok(secs) if secs <= MAX_JWT_LEEWAY_SECS => Ok(secs),
+        _ => Err(ConfigError::InvalidValue(
+            "JWT_LEEWAY_SECS".to_string(),
+            format!("must be a whole number of seconds between 0 and {MAX_JWT_LEEWAY_SECS}"),
+        )),
+    }
+}
+
+/// Longest JWT_READ_GRACE_SECS accepted; a leaked access token stays usable
+/// for reads this much longer
+pub const MAX_JWT_READ_GRACE_SECS: u64 = 60;
+
+/// Seconds of read grace for expired access tokens: 0 unless overridden,
+/// never above [`MAX_JWT_READ_GRACE_SECS`].
+fn resolve_read_grace(override_value: Option<&str>) -> Result<u64, ConfigError> {
+    let Some(value) = override_value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(0);
+    };
+    match value.parse::<u64>() {
+        Ok(secs) if secs <= MAX_JWT_READ_GRACE_SECS => Ok(secs),
+        _ => Err(ConfigError::InvalidValue(
+            "JWT_READ_GRACE_SECS".to_string(),
+            format!("must be a whole number of seconds between 0 and {MAX_JWT_READ_GRACE_SECS}"),
+        )),
+    }
+}
+
+/// Audit actions that force an access token refresh: `None` (the built-in
+/// list) unless overridden. Every entry must name an [`AuditAction`], so a
+/// typo fails startup instead of silently disabling the refresh.
+fn resolve_token_refresh_actions(
+    override_value: Option<&str>,
+) -> Result<Option<Vec<String>>, ConfigError> {
+    let Some(value) = override_value.filter(|v| !v.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let actions = parse_lowercase_list(value);
+    if let Some(unknown) = actions.iter().find(|a| AuditAction::parse(a).is_none()) {
+        return Err(ConfigError::InvalidValue(
+            "TOKEN_REFRESH_ACTIONS".to_string(),
+            format!("unknown audit action '{unknown}'"),
+        ));
+    }
+    Ok(Some(actions))
+}
+
+/// Fraction of a rate limit at which soft warnings start: 0.8 unless
+/// overridden, 0 to disable, never above 1.
+fn resolve_warning_threshold(override_value: Option<&str>) -> Result<f64, ConfigError> {
+    let Some(value) = override_value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(0.8);
+    };
+    match value.parse::<f64>() {
+        Ok(threshold) if (0.0..=1.0).contains(&threshold) => Ok(threshold),
+        _ => Err(ConfigError::InvalidValue(
+            "RATE_LIMIT_WARNING_THRESHOLD".to_string(),
+            "must be a number between 0 and 1".to_string(),
+        )),
+    }
+}
+
+/// The Stripe webhook path segment: `stripe` unless overridden, e.g. with a
+/// random value so the endpoint is not guessable. Only `[A-Za-z0-9_-]` is
+/// allowed, keeping it a single segment directly under `/v1/webhooks/`.
+fn resolve_webhook_path(override_value: Option<&str>) -> Result<String, ConfigError> {
+    let segment = override_value.map(str::trim).unwrap_or_default();
+    if segment.is_empty() {
+        return Ok("stripe".to_string());
+    }
+    if !segment
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ConfigError::InvalidValue(
+            "STRIPE_WEBHOOK_PATH".to_string(),
+            "must be a single path segment of letters, digits, '-' or '_'".to_string(),
+        ));
+    }
+    Ok(segment.to_string())
+}
+
+/// Decide whether CORS responses allow credentials.
+///
+/// Defaults to on, since the frontend authenticates with cookies. The API
+/// also accepts every subdomain of the `CORS_ORIGIN` host, so credentials are
+/// refused for an origin that is a wildcard, `null`, or has no host with a
+/// dot in it (e.g. `https://com`), any of which would let unrelated sites
+/// make authenticated requests.
+fn resolve_cors_credentials(
+    override_value: Option<&str>,
+    cors_origin: &str,
+) -> Result<bool, ConfigError> {
+    let allow = match override_value.map(|v| v.trim().to_lowercase()) {
+        None => true,
+        Some(v) if v.is_empty() => true,
+        Some(v) if v == "true" || v == "1" => true,
+        Some(v) if v == "false" || v == "0" => false,
+        Some(_) => {
+            return Err(ConfigError::InvalidValue(
+                "CORS_ALLOW_CREDENTIALS".to_string(),
+                "must be 'true' or 'false'".to_string(),
+            ))
+        }
+    };
+
+    if allow && is_wildcard_like_origin(cors_origin) {
+        return Err(ConfigError::InvalidValue(
+            "CORS_ALLOW_CREDENTIALS".to_string(),
+            format!(
+                "credentials cannot be allowed for CORS_ORIGIN '{cors_origin}'; set a single origin or CORS_ALLOW_CREDENTIALS=false"
+            ),
+        ));
+    }
+
+    Ok(allow)
+}
+
+/// Whether `origin` would match sites other than the one frontend
+fn is_wildcard_like_origin(origin: &str) -> bool {
+    let origin = origin.trim();
+    if origin.contains('*') || origin.eq_ignore_ascii_case("null") {
+        return true;
+    }
+    match url::Url::parse(origin)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_lowercase))
+    {
+        Some(host) => host != "localhost" && !host.trim_matches('.').contains('.'),
+        None => true,
+    }
+}
+
+/// Decide whether auth cookies get the `Secure` attribute.
+///
+/// An explicit `COOKIE_SECURE` value wins; otherwise cookies are secure exactly
+/// when the public base URL is https. Secure cookies over a plain-http base URL
+/// are rejected, since browsers would silently drop them and nobody could log in.
+fn resolve_cookie_secure(
+    override_value: Option<&str>,
+    base_url: &str,
+) -> Result<bool, ConfigError> {
+    let is_https = base_url.trim().to_lowercase().starts_with("https://");
+
+    let secure = match override_value.map(|v| v.trim().to_lowercase()) {
+        None => is_https,
+        Some(v) if v.is_empty() => is_https,
+        Some(v) if v == "true" || v == "1" => true,
+        Some(v) if v == "false" || v == "0" => false,
+        Some(_) => {
+            return Err(ConfigError::InvalidValue(
+                "COOKIE_SECURE".to_string(),
+                "must be 'true' or 'false'".to_string(),
+            ))
+        }
+    };
+
+    if secure && !is_https {
+        return Err(ConfigError::InvalidValue(
+            "COOKIE_SECURE".to_string(),
+            format!("secure cookies require an https APP_URL (got '{base_url}')"),
+        ));
+    }
+
+    Ok(secure)
+}
+
 /// Configuration errors
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
@@ -625,6 +1784,112 @@ mod tests {
     use super::*;
     use std::env;
 
+    #[test]
+    fn trusted_proxies_accept_addresses_and_cidrs() {
+        let proxies = parse_trusted_proxies(" 10.0.0.0/8, ::1,,192.168.1.5 ").unwrap();
+        assert_eq!(proxies.len(), 3);
+        assert!(proxies[0].contains("10.20.30.40".parse().unwrap()));
+        assert!(proxies[2].contains("192.168.1.5".parse().unwrap()));
+        assert!(!proxies[2].contains("192.168.1.6".parse().unwrap()));
+        assert!(parse_trusted_proxies("proxy.internal").is_err());
+    }
+
+    #[test]
+    fn parse_lowercase_list_trims_and_drops_empty() {
+        assert_eq!(
+            parse_lowercase_list(" Support@ , ,Admin@Example.com,"),
+            vec!["support@".to_string(), "admin@example.com".to_string()]
+        );
+        assert!(parse_lowercase_list("").is_empty());
+    }
+
+    #[test]
+    fn audit_policy_skips_routine_actions_below_threshold() {
+        let policy = AuditPolicyConfig {
+            min_severity: AuditSeverity::Warning,
+            suppressed_actions: vec!["user_logout".into()],
+            sample_rate: 0.0,
+        };
+        assert!(!policy.should_record(&AuditAction::UserLogin, AuditSeverity::Info, 0.0));
+        assert!(!policy.should_record(&AuditAction::UserLogout, AuditSeverity::Critical, 0.0));
+        assert!(policy.should_record(&AuditAction::UserLogin, AuditSeverity::Warning, 0.0));
+        assert!(policy.should_record(&AuditAction::PaymentFailed, AuditSeverity::Error, 0.0));
+    }
+
+    #[test]
+    fn audit_policy_always_records_admin_billing_and_security_actions() {
+        let policy = AuditPolicyConfig {
+            min_severity: AuditSeverity::Critical,
+            suppressed_actions: vec![
+                "admin_user_deleted".into(),
+                "password_changed".into(),
+                "payment_succeeded".into(),
+            ],
+            sample_rate: 0.0,
+        };
+        for action in [
+            AuditAction::AdminUserDeleted,
+            AuditAction::AdminUserImpersonated,
+            AuditAction::PasswordChanged,
+            AuditAction::UserLoginFailed,
+            AuditAction::PaymentSucceeded,
+            AuditAction::MembershipCanceled,
+            AuditAction::GracePeriodStarted,
+        ] {
+            assert!(
+                policy.should_record(&action, AuditSeverity::Info, 0.99),
+                "{}",
+                action.as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn audit_policy_samples_routine_actions() {
+        let policy = AuditPolicyConfig {
+            suppressed_actions: vec!["user_login".into()],
+            sample_rate: 0.1,
+            ..Default::default()
+        };
+        assert!(policy.should_record(&AuditAction::UserLogin, AuditSeverity::Info, 0.05));
+        assert!(!policy.should_record(&AuditAction::UserLogin, AuditSeverity::Info, 0.5));
+        assert!(!policy.records_everything());
+        assert!(AuditPolicyConfig::default().records_everything());
+    }
+
+    #[test]
+    fn stored_ip_is_truncated_only_when_masking() {
+        let ip = "203.0.113.77".parse().unwrap();
+        let masked = AuditConfig {
+            mask_pii: true,
+            ..Default::default()
+        };
+        assert_eq!(masked.stored_ip(ip).to_string(), "203.0.113.0/24");
+        assert_eq!(
+            AuditConfig::default().stored_ip(ip).to_string(),
+            "203.0.113.77/32"
+        );
+    }
+
+    #[test]
+    fn redirect_hosts_default_to_cookie_domain_then_frontend_host() {
+        let explicit = RedirectConfig::resolve(
+            Some(" App.Example.com, *.tools.example "),
+            "http://localhost:5173",
+            Some(".example.com"),
+        );
+        assert_eq!(
+            explicit.allowed_hosts,
+            vec!["app.example.com".to_string(), "*.tools.example".to_string()]
+        );
+
+        let cookie = RedirectConfig::resolve(None, "https://app.example.com", Some(".Example.com"));
+        assert_eq!(cookie.allowed_hosts, vec!["*.example.com".to_string()]);
+
+        let frontend = RedirectConfig::resolve(Some(""), "http://localhost:5173", None);
+        assert_eq!(frontend.allowed_hosts, vec!["*.localhost".to_string()]);
+    }
+
     #[test]
     fn test_config_defaults() {
         // Set required env vars
@@ -638,6 +1903,11 @@ mod tests {
         env::remove_var("SMTP_HOST");
         env::remove_var("EMAIL_ENABLED");
         env::remove_var("COOKIE_DOMAIN");
+        env::remove_var("MAGIC_LINK_REDIRECT_URL");
+        env::remove_var("REDIRECT_ALLOWED_HOSTS");
+        env::remove_var("COOKIE_SECURE");
+        env::remove_var("APP_URL");
+        env::remove_var("MAX_SESSIONS_PER_USER");
 
         let config = Config::from_env().unwrap();
 
@@ -645,10 +1915,17 @@ mod tests {
         assert_eq!(config.port, 4000);
         assert_eq!(config.log_level, "info");
         assert_eq!(config.cors_origin, "http://localhost:5173");
+        assert_eq!(
+            config.magic_link_redirect_url,
+            "http://localhost:5173/dashboard"
+        );
         assert_eq!(config.environment, "development");
+        assert_eq!(config.max_sessions_per_user, 10);
         assert!(!config.email.enabled);
         // In development mode without COOKIE_DOMAIN set, it should be None (for localhost)
         assert!(config.cookie_domain.is_none());
+        // Plain-http localhost base URL means non-secure cookies by default
+        assert!(!config.cookie_secure);
     }
 
     #[test]
@@ -659,6 +1936,229 @@ mod tests {
         assert!(err.to_string().contains("DATABASE_URL"));
     }
 
+    // ---- CORS credentials ----
+
+    #[test]
+    fn cors_credentials_default_on_for_a_single_origin() {
+        assert!(resolve_cors_credentials(None, "https://app.example.com").unwrap());
+        assert!(resolve_cors_credentials(Some(""), "http://localhost:5173").unwrap());
+        assert!(!resolve_cors_credentials(Some("false"), "https://app.example.com").unwrap());
+        assert!(resolve_cors_credentials(Some("maybe"), "https://app.example.com").is_err());
+    }
+
+    #[test]
+    fn cors_credentials_rejected_for_wildcard_like_origins() {
+        for origin in [
+            "*",
+            "https://*.example.com",
+            "null",
+            "",
+            "not a url",
+            "https://com",
+        ] {
+            assert!(
+                resolve_cors_credentials(None, origin).is_err(),
+                "credentials allowed for {origin:?}"
+            );
+            assert!(!resolve_cors_credentials(Some("false"), origin).unwrap());
+        }
+    }
+
+    // ---- Secure cookie resolution ----
+
+    #[test]
+    fn cookie_secure_defaults_to_true_for_https() {
+        assert!(resolve_cookie_secure(None, "https://app.example.com").unwrap());
+    }
+
+    #[test]
+    fn cookie_secure_defaults_to_false_for_http() {
+        assert!(!resolve_cookie_secure(None, "http://localhost:5173").unwrap());
+    }
+
+    #[test]
+    fn cookie_secure_empty_override_uses_default() {
+        assert!(resolve_cookie_secure(Some(""), "https://app.example.com").unwrap());
+        assert!(!resolve_cookie_secure(Some(""), "http://localhost:5173").unwrap());
+    }
+
+    #[test]
+    fn cookie_secure_explicit_true_over_https() {
+        assert!(resolve_cookie_secure(Some("true"), "https://staging.example.com").unwrap());
+    }
+
+    #[test]
+    fn webhook_path_defaults_to_stripe() {
+        assert_eq!(resolve_webhook_path(None).unwrap(), "stripe");
+        assert_eq!(resolve_webhook_path(Some("  ")).unwrap(), "stripe");
+        assert_eq!(
+            resolve_webhook_path(Some("stripe-4f9c_x2")).unwrap(),
+            "stripe-4f9c_x2"
+        );
+    }
+
+    #[test]
+    fn webhook_path_must_be_a_single_segment() {
+        for value in ["a/b", "../admin", "hook.php", "hook?x=1", "%2e%2e"] {
+            assert!(resolve_webhook_path(Some(value)).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn warning_threshold_defaults_and_bounds() {
+        assert_eq!(resolve_warning_threshold(None).unwrap(), 0.8);
+        assert_eq!(resolve_warning_threshold(Some(" ")).unwrap(), 0.8);
+        assert_eq!(resolve_warning_threshold(Some("0.5")).unwrap(), 0.5);
+        assert_eq!(resolve_warning_threshold(Some("0")).unwrap(), 0.0);
+        assert!(resolve_warning_threshold(Some("1.5")).is_err());
+        assert!(resolve_warning_threshold(Some("-0.1")).is_err());
+        assert!(resolve_warning_threshold(Some("most")).is_err());
+    }
+
+    #[test]
+    fn jwt_signing_requires_key_paths_for_asymmetric_algorithms() {
+        let path = |p: &str| Some(p.to_string());
+        assert_eq!(
+            JwtSigningConfig::resolve(None, None, None).unwrap(),
+            JwtSigningConfig::Hs256
+        );
+        assert_eq!(
+            JwtSigningConfig::resolve(Some("EdDSA"), path("k.pem"), path("k.pub.pem")).unwrap(),
+            JwtSigningConfig::EdDsa {
+                private_key_path: "k.pem".to_string(),
+                public_key_path: "k.pub.pem".to_string(),
+            }
+        );
+        assert!(JwtSigningConfig::resolve(Some("RS256"), path("k.pem"), None).is_err());
+        assert!(JwtSigningConfig::resolve(Some("RS256"), path(""), path("k.pub.pem")).is_err());
+        assert!(JwtSigningConfig::resolve(Some("ES256"), None, None).is_err());
+    }
+
+    #[test]
+    fn retired_jwt_key_must_match_the_signing_algorithm() {
+        let eddsa = JwtSigningConfig::EdDsa {
+            private_key_path: "k.pem".to_string(),
+            public_key_path: "k.pub.pem".to_string(),
+        };
+        let old = |v: &str| Some(v.to_string());
+
+        assert_eq!(
+            RetiredJwtKey::resolve(&JwtSigningConfig::Hs256, None, None).unwrap(),
+            None
+        );
+        assert_eq!(
+            RetiredJwtKey::resolve(&JwtSigningConfig::Hs256, old("old-secret"), None).unwrap(),
+            Some(RetiredJwtKey::Secret("old-secret".to_string()))
+        );
+        assert_eq!(
+            RetiredJwtKey::resolve(&eddsa, None, old("old.pub.pem")).unwrap(),
+            Some(RetiredJwtKey::PublicKeyPath("old.pub.pem".to_string()))
+        );
+        assert!(
+            RetiredJwtKey::resolve(&JwtSigningConfig::Hs256, None, old("old.pub.pem")).is_err()
+        );
+        assert!(RetiredJwtKey::resolve(&eddsa, old("old-secret"), None).is_err());
+    }
+
+    #[test]
+    fn leeway_defaults_and_is_capped() {
+        assert_eq!(resolve_leeway(None).unwrap(), DEFAULT_JWT_LEEWAY_SECS);
+        assert_eq!(resolve_leeway(Some("")).unwrap(), DEFAULT_JWT_LEEWAY_SECS);
+        assert_eq!(resolve_leeway(Some("0")).unwrap(), 0);
+        assert_eq!(resolve_leeway(Some("300")).unwrap(), 300);
+        assert!(resolve_leeway(Some("301")).is_err());
+        assert!(resolve_leeway(Some("thirty")).is_err());
+    }
+
+    #[test]
+    fn read_grace_defaults_off_and_is_capped() {
+        assert_eq!(resolve_read_grace(None).unwrap(), 0);
+        assert_eq!(resolve_read_grace(Some(" ")).unwrap(), 0);
+        assert_eq!(resolve_read_grace(Some("60")).unwrap(), 60);
+        assert!(resolve_read_grace(Some("61")).is_err());
+        assert!(resolve_read_grace(Some("86400")).is_err());
+        assert!(resolve_read_grace(Some("-1")).is_err());
+    }
+
+    #[test]
+    fn token_refresh_actions_reject_unknown_names() {
+        assert_eq!(resolve_token_refresh_actions(None).unwrap(), None);
+        assert_eq!(resolve_token_refresh_actions(Some(" ")).unwrap(), None);
+        assert_eq!(
+            resolve_token_refresh_actions(Some("Membership_Canceled, admin_user_role_changed"))
+                .unwrap(),
+            Some(vec![
+                "membership_canceled".to_string(),
+                "admin_user_role_changed".to_string()
+            ])
+        );
+        assert!(resolve_token_refresh_actions(Some("membership_cancelled")).is_err());
+    }
+
+    #[test]
+    fn previous_peppers_parse_version_secret_pairs() {
+        assert_eq!(
+            parse_previous_peppers(" 1:old-secret, 2:a:b ,").unwrap(),
+            vec![(1, "old-secret".to_string()), (2, "a:b".to_string())]
+        );
+        assert!(parse_previous_peppers("").unwrap().is_empty());
+        assert!(parse_previous_peppers("no-version").is_err());
+        assert!(parse_previous_peppers("x:secret").is_err());
+        assert!(parse_previous_peppers("3:").is_err());
+    }
+
+    #[test]
+    fn api_tier_limits_parse_known_tiers() {
+        let limits = parse_tier_limits(ApiRateLimitConfig::DEFAULT_TIER_LIMITS).unwrap();
+        assert_eq!(limits["lifetime"], 600);
+        assert_eq!(limits["standard"], 300);
+        assert!(!limits.contains_key("free"));
+
+        assert_eq!(parse_tier_limits(" Free:150 ,").unwrap()["free"], 150);
+        assert!(parse_tier_limits("").unwrap().is_empty());
+        assert!(parse_tier_limits("gold:1000").is_err());
+        assert!(parse_tier_limits("standard:0").is_err());
+        assert!(parse_tier_limits("standard").is_err());
+    }
+
+    #[test]
+    fn default_signup_tier_accepts_self_serve_tiers() {
+        assert_eq!(
+            parse_default_signup_tier(" Standard ").unwrap(),
+            SubscriptionTier::Standard
+        );
+        assert_eq!(
+            parse_default_signup_tier("early_adopter").unwrap(),
+            SubscriptionTier::EarlyAdopter
+        );
+        for granted in ["lifetime", "free"] {
+            assert!(parse_default_signup_tier(granted).is_err(), "{granted}");
+        }
+        assert!(parse_default_signup_tier("gold").is_err());
+        assert!(parse_default_signup_tier("").is_err());
+    }
+
+    #[test]
+    fn cookie_secure_explicit_false_over_https() {
+        assert!(!resolve_cookie_secure(Some("false"), "https://staging.example.com").unwrap());
+    }
+
+    #[test]
+    fn cookie_secure_explicit_false_over_http() {
+        assert!(!resolve_cookie_secure(Some("0"), "http://localhost:5173").unwrap());
+    }
+
+    #[test]
+    fn cookie_secure_explicit_true_over_http_is_rejected() {
+        let err = resolve_cookie_secure(Some("true"), "http://localhost:5173").unwrap_err();
+        assert!(err.to_string().contains("COOKIE_SECURE"));
+    }
+
+    #[test]
+    fn cookie_secure_invalid_value_is_rejected() {
+        assert!(resolve_cookie_secure(Some("yes please"), "https://app.example.com").is_err());
+    }
+
     #[test]
     fn test_parse_smtp_from_with_display_name() {
         let input = "a8n Tools Staging <tools-staging@a8n.run>";
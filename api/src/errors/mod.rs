@@ -7,12 +7,18 @@ use chrono::{DateTime, Utc};
 use serde::Serialize;
 
 use crate::middleware::request_id::RequestId;
+use crate::responses::ApiVersion;
 
 /// Application error type
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
     #[error("Validation error on field '{field}': {message}")]
-    ValidationError { field: String, message: String },
+    ValidationError {
+        field: String,
+        /// Stable machine-readable reason, e.g. `password_too_short`
+        code: String,
+        message: String,
+    },
 
     #[error("Invalid credentials")]
     InvalidCredentials,
@@ -26,6 +32,18 @@ pub enum AppError {
     #[error("Forbidden")]
     Forbidden,
 
+    #[error("Forbidden: requires {required} tier")]
+    TierRequired { required: String },
+
+    /// An admin without 2FA while REQUIRE_ADMIN_2FA is on
+    #[error("Forbidden: two-factor authentication required")]
+    TwoFactorRequired,
+
+    /// A sensitive action from a session that signed in longer than
+    /// `max_age_secs` ago (STEP_UP_MAX_AGE_SECS)
+    #[error("Forbidden: sign-in older than {max_age_secs} seconds")]
+    ReauthenticationRequired { max_age_secs: u64 },
+
     #[error("Resource not found: {resource}")]
     NotFound { resource: String },
 
@@ -41,8 +59,14 @@ pub enum AppError {
         retry_after_secs: Option<i64>,
     },
 
-    #[error("Upstream error: {message}")]
-    Upstream { message: String },
+    /// A dependency failed. `service` names a third-party integration
+    /// (Stripe, email, captcha); `message` is only logged, and clients see
+    /// the service name and a generic message.
+    #[error("{} error: {message}", .service.unwrap_or("Upstream"))]
+    Upstream {
+        service: Option<&'static str>,
+        message: String,
+    },
 
     #[error("Internal error: {message}")]
     InternalError { message: String },
@@ -76,6 +100,9 @@ impl AppError {
             AppError::TokenExpired => "TOKEN_EXPIRED",
             AppError::Unauthorized => "UNAUTHORIZED",
             AppError::Forbidden => "FORBIDDEN",
+            AppError::TierRequired { .. } => "FORBIDDEN",
+            AppError::TwoFactorRequired => "TWO_FACTOR_REQUIRED",
+            AppError::ReauthenticationRequired { .. } => "REAUTHENTICATION_REQUIRED",
             AppError::NotFound { .. } => "NOT_FOUND",
             AppError::Conflict { .. } => "CONFLICT",
             AppError::RateLimited { .. } => "RATE_LIMITED",
@@ -96,6 +123,10 @@ impl AppError {
     pub fn dynamic_error_code(&self) -> String {
         match self {
             AppError::RateLimitedCoded { code, .. } => code.clone(),
+            AppError::Upstream {
+                service: Some(service),
+                ..
+            } => format!("{}_ERROR", service.to_uppercase()),
             other => other.error_code().to_string(),
         }
     }
@@ -112,14 +143,36 @@ impl AppError {
     /// Create an upstream (502) error with a friendly message.
     pub fn upstream(message: impl Into<String>) -> Self {
         AppError::Upstream {
+            service: None,
+            message: message.into(),
+        }
+    }
+
+    /// Create an upstream (502) error for a named third-party integration,
+    /// e.g. `external("stripe", ...)`
+    pub fn external(service: &'static str, message: impl Into<String>) -> Self {
+        AppError::Upstream {
+            service: Some(service),
             message: message.into(),
         }
     }
 
-    /// Create a validation error
+    /// Create a validation error with the generic `<field>_invalid` code
     pub fn validation(field: impl Into<String>, message: impl Into<String>) -> Self {
+        let field = field.into();
+        let code = format!("{}_invalid", field);
+        Self::validation_coded(field, code, message)
+    }
+
+    /// Create a validation error with a specific code, e.g. `password_too_short`
+    pub fn validation_coded(
+        field: impl Into<String>,
+        code: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
         AppError::ValidationError {
             field: field.into(),
+            code: code.into(),
             message: message.into(),
         }
     }
@@ -173,6 +226,7 @@ pub struct ErrorDetails {
 pub struct ErrorMeta {
     pub request_id: String,
     pub timestamp: DateTime<Utc>,
+    pub api_version: ApiVersion,
 }
 
 impl ResponseError for AppError {
@@ -183,6 +237,9 @@ impl ResponseError for AppError {
             AppError::TokenExpired => StatusCode::UNAUTHORIZED,
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
             AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::TierRequired { .. } => StatusCode::FORBIDDEN,
+            AppError::TwoFactorRequired => StatusCode::FORBIDDEN,
+            AppError::ReauthenticationRequired { .. } => StatusCode::FORBIDDEN,
             AppError::NotFound { .. } => StatusCode::NOT_FOUND,
             AppError::Conflict { .. } => StatusCode::CONFLICT,
             AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
@@ -233,13 +290,28 @@ impl ResponseError for AppError {
         let request_id = RequestId::new().0;
 
         let details = match self {
-            AppError::ValidationError { field, .. } => Some(serde_json::json!({ "field": field })),
+            AppError::ValidationError { field, code, .. } => {
+                Some(serde_json::json!({ "field": field, "code": code }))
+            }
+            AppError::TierRequired { required } => {
+                Some(serde_json::json!({ "required_tier": required }))
+            }
+            AppError::TwoFactorRequired => {
+                Some(serde_json::json!({ "setup_url": "/v1/auth/2fa/setup" }))
+            }
+            AppError::ReauthenticationRequired { max_age_secs } => {
+                Some(serde_json::json!({ "max_age_secs": max_age_secs }))
+            }
             AppError::RateLimited { retry_after } => {
                 Some(serde_json::json!({ "retry_after": retry_after }))
             }
             AppError::RateLimitedCoded {
                 retry_after_secs, ..
             } => retry_after_secs.map(|n| serde_json::json!({ "retry_after": n })),
+            AppError::Upstream {
+                service: Some(service),
+                ..
+            } => Some(serde_json::json!({ "service": service })),
             _ => None,
         };
 
@@ -251,6 +323,15 @@ impl ResponseError for AppError {
             AppError::TokenExpired => "Your session has expired. Please log in again.".to_string(),
             AppError::Unauthorized => "You need to log in to access this.".to_string(),
             AppError::Forbidden => "You don't have permission to do this.".to_string(),
+            AppError::TierRequired { .. } => {
+                "Your membership tier doesn't include this feature.".to_string()
+            }
+            AppError::TwoFactorRequired => {
+                "Admin accounts must use two-factor authentication. Set it up in your account security settings to continue.".to_string()
+            }
+            AppError::ReauthenticationRequired { .. } => {
+                "For your security, please sign in again to continue.".to_string()
+            }
             AppError::NotFound { .. } => "The requested resource could not be found.".to_string(),
             AppError::Conflict { message } => message.clone(),
             AppError::RateLimited { retry_after } => {
@@ -293,6 +374,7 @@ impl ResponseError for AppError {
             meta: ErrorMeta {
                 request_id,
                 timestamp: Utc::now(),
+                api_version: ApiVersion::DEFAULT,
             },
         };
 
@@ -363,6 +445,21 @@ mod tests {
         assert_eq!(AppError::TokenExpired.error_code(), "TOKEN_EXPIRED");
         assert_eq!(AppError::Unauthorized.error_code(), "UNAUTHORIZED");
         assert_eq!(AppError::Forbidden.error_code(), "FORBIDDEN");
+        assert_eq!(
+            AppError::TierRequired {
+                required: "lifetime".to_string()
+            }
+            .error_code(),
+            "FORBIDDEN"
+        );
+        assert_eq!(
+            AppError::TwoFactorRequired.error_code(),
+            "TWO_FACTOR_REQUIRED"
+        );
+        assert_eq!(
+            AppError::ReauthenticationRequired { max_age_secs: 900 }.error_code(),
+            "REAUTHENTICATION_REQUIRED"
+        );
         assert_eq!(AppError::not_found("user").error_code(), "NOT_FOUND");
         assert_eq!(AppError::conflict("exists").error_code(), "CONFLICT");
         assert_eq!(
@@ -426,8 +523,13 @@ mod tests {
     #[test]
     fn test_error_constructors() {
         match AppError::validation("email", "bad format") {
-            AppError::ValidationError { field, message } => {
+            AppError::ValidationError {
+                field,
+                code,
+                message,
+            } => {
                 assert_eq!(field, "email");
+                assert_eq!(code, "email_invalid");
                 assert_eq!(message, "bad format");
             }
             _ => panic!("wrong variant"),
@@ -498,8 +600,10 @@ mod tests {
         assert_eq!(json["error"]["code"], "VALIDATION_ERROR");
         assert_eq!(json["error"]["message"], "invalid format");
         assert_eq!(json["error"]["details"]["field"], "email");
+        assert_eq!(json["error"]["details"]["code"], "email_invalid");
         assert!(json["meta"]["request_id"].is_string());
         assert!(json["meta"]["timestamp"].is_string());
+        assert_eq!(json["meta"]["api_version"], "v1");
     }
 
     #[test]
@@ -543,8 +647,34 @@ mod tests {
     #[test]
     fn upstream_error_is_502() {
         let err = AppError::upstream("forgejo timeout");
+        assert_eq!(err.dynamic_error_code(), "UPSTREAM_ERROR");
+        assert_eq!(err.to_string(), "Upstream error: forgejo timeout");
+        let resp = err.error_response();
+        assert_eq!(resp.status().as_u16(), 502);
+    }
+
+    #[test]
+    fn external_service_error_is_502_with_service_code() {
+        let err = AppError::external("stripe", "card_declined for sk_live_secret");
+        assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(err.error_code(), "UPSTREAM_ERROR");
+        assert_eq!(err.dynamic_error_code(), "STRIPE_ERROR");
+        assert_eq!(
+            err.to_string(),
+            "stripe error: card_declined for sk_live_secret"
+        );
+
         let resp = err.error_response();
         assert_eq!(resp.status().as_u16(), 502);
+        let rt = actix_web::rt::Runtime::new().unwrap();
+        let bytes = rt
+            .block_on(actix_web::body::to_bytes(resp.into_body()))
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(json["error"]["code"], "STRIPE_ERROR");
+        assert_eq!(json["error"]["details"]["service"], "stripe");
+        assert!(!String::from_utf8_lossy(&bytes).contains("sk_live_secret"));
     }
 
     #[test]
@@ -5,23 +5,28 @@
 use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio;
 
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 
-use crate::config::Config;
+use crate::config::{AuditConfig, Config, PaginationConfig};
 use crate::errors::AppError;
-use crate::middleware::{AdminUser, AuthenticatedUser};
+use crate::middleware::auto_ban::{self, AutoBanService};
+use crate::middleware::{force_token_refresh, AdminUser, AuthenticatedUser, RequirePermission};
 use crate::models::stripe::encrypt_secret;
 use crate::models::{
-    AuditAction, CreateApplication, CreateAuditLog, CreatePasswordResetToken, CreateRefreshToken,
-    DeleteApplicationRequest, MembershipStatus, StripeConfigResponse, SwapApplicationOrderRequest,
+    scopes, AuditAction, CreateApplication, CreateAuditLog, CreatePasswordResetToken, Currency,
+    DeleteApplicationRequest, MembershipStatus, NotificationType, StripeConfigResponse,
+    SwapApplicationOrderRequest, TimeseriesInterval, TimeseriesMetric, TimeseriesResponse,
     UpdateApplication, UserResponse,
 };
+use crate::pagination::{resolve_page, Page, PerPage};
 use crate::repositories::{
     ApplicationRepository, AuditLogRepository, InviteRepository, NotificationRepository,
-    StripeConfigRepository, TokenRepository, TotpRepository, UserRepository,
+    StatsRepository, StripeConfigRepository, TokenRepository, TotpRepository,
+    UserIpActivityRepository, UserRepository,
 };
 use crate::responses::{created, get_request_id, paginated, success, success_no_data};
 use crate::services::{
@@ -47,14 +52,14 @@ pub struct ListUsersQuery {
 /// List all users with pagination
 pub async fn list_users(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::UsersRead>,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
     query: web::Query<ListUsersQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
 
-    let page = query.page.unwrap_or(1).max(1);
-    let per_page = query.per_page.unwrap_or(20).min(100);
+    let (page, per_page) = resolve_page(query.page, query.per_page, 20, pagination.max_offset)?;
     let status_filter = query
         .status
         .as_ref()
@@ -74,28 +79,118 @@ pub async fn list_users(
     Ok(paginated(user_responses, total, page, per_page, request_id))
 }
 
+/// Default and largest `within_days` for the at-risk users list
+const DEFAULT_AT_RISK_WINDOW_DAYS: i64 = 7;
+const MAX_AT_RISK_WINDOW_DAYS: i64 = 90;
+
+/// Query parameters for listing users at risk of losing access
+#[derive(Debug, Deserialize)]
+pub struct AtRiskUsersQuery {
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+    /// Include users whose grace period ends within this many days
+    pub within_days: Option<i64>,
+}
+
+/// GET /v1/admin/users/at-risk
+/// Past-due and grace-period users whose grace period ends soon, soonest first
+pub async fn list_users_at_risk(
+    req: HttpRequest,
+    _admin: RequirePermission<scopes::UsersRead>,
+    pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
+    query: web::Query<AtRiskUsersQuery>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let (page, per_page) = resolve_page(query.page, query.per_page, 20, pagination.max_offset)?;
+    let within_days = query
+        .within_days
+        .unwrap_or(DEFAULT_AT_RISK_WINDOW_DAYS)
+        .clamp(1, MAX_AT_RISK_WINDOW_DAYS);
+
+    let (users, total) = UserRepository::find_in_grace_period(
+        &pool,
+        Utc::now() + Duration::days(within_days),
+        page,
+        per_page,
+    )
+    .await?;
+
+    let user_responses: Vec<UserResponse> = users.into_iter().map(UserResponse::from).collect();
+
+    Ok(paginated(user_responses, total, page, per_page, request_id))
+}
+
 /// GET /v1/admin/users/{user_id}
-/// Get a specific user
+/// Get a specific user, including deactivated ones
 pub async fn get_user(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::UsersRead>,
     pool: web::Data<PgPool>,
     path: web::Path<uuid::Uuid>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
     let user_id = path.into_inner();
 
-    let user = UserRepository::find_by_id(&pool, user_id)
+    let user = UserRepository::find_by_id_with_deleted(&pool, user_id)
         .await?
         .ok_or(AppError::not_found("User"))?;
 
     Ok(success(UserResponse::from(user), request_id))
 }
 
+/// GET /v1/admin/users/{user_id}/ip-activity
+/// IP addresses a user has signed in or refreshed from, most recent first
+pub async fn get_user_ip_activity(
+    req: HttpRequest,
+    _admin: RequirePermission<scopes::UsersRead>,
+    pool: web::Data<PgPool>,
+    path: web::Path<uuid::Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let user_id = path.into_inner();
+
+    UserRepository::find_by_id_with_deleted(&pool, user_id)
+        .await?
+        .ok_or(AppError::not_found("User"))?;
+    let activity = UserIpActivityRepository::list_for_user(&pool, user_id).await?;
+
+    Ok(success(activity, request_id))
+}
+
 /// Request body for activating/deactivating user
 #[derive(Debug, Deserialize)]
 pub struct UpdateUserStatusRequest {
     pub active: bool,
+    /// Why the account is being deactivated; stored and audited
+    pub reason: Option<String>,
+}
+
+/// Optional request body for deleting a user
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteUserRequest {
+    pub reason: Option<String>,
+}
+
+/// Longest accepted deactivation reason, in characters
+const MAX_DEACTIVATION_REASON_CHARS: usize = 500;
+
+/// Trim a deactivation reason, treating blank as absent
+fn normalize_deactivation_reason(reason: Option<&str>) -> Result<Option<String>, AppError> {
+    let Some(reason) = reason.map(str::trim).filter(|r| !r.is_empty()) else {
+        return Ok(None);
+    };
+    if reason.chars().count() > MAX_DEACTIVATION_REASON_CHARS {
+        return Err(AppError::validation(
+            "reason",
+            format!(
+                "Reason must be at most {} characters",
+                MAX_DEACTIVATION_REASON_CHARS
+            ),
+        ));
+    }
+    Ok(Some(reason.to_string()))
 }
 
 /// PUT /v1/admin/users/{user_id}/status
@@ -104,6 +199,7 @@ pub async fn update_user_status(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     oidc_provider: web::Data<Option<Arc<crate::services::oidc_provider::OidcProvider>>>,
     path: web::Path<uuid::Uuid>,
     body: web::Json<UpdateUserStatusRequest>,
@@ -119,19 +215,22 @@ pub async fn update_user_status(
             "Cannot reactivate deleted users through this endpoint",
         ));
     } else {
+        let reason = normalize_deactivation_reason(body.reason.as_deref())?;
         let target_user = UserRepository::find_by_id(&pool, user_id)
             .await?
             .ok_or(AppError::not_found("User"))?;
 
-        UserRepository::soft_delete(&pool, user_id).await?;
+        UserRepository::soft_delete(&pool, user_id, reason.as_deref()).await?;
+        force_token_refresh(&req, user_id, &AuditAction::AdminUserDeactivated).await;
 
         let audit_log = CreateAuditLog::new(AuditAction::AdminUserDeactivated)
             .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
             .with_resource("user", user_id)
             .with_metadata(serde_json::json!({
                 "target_email": target_user.email,
+                "reason": reason,
             }));
-        AuditLogRepository::create(&pool, audit_log).await?;
+        AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
         if let Some(provider) = oidc_provider.as_ref().as_ref().cloned() {
             tokio::spawn(dispatch_lifecycle_event(provider, user_id, "user.deleted"));
@@ -147,11 +246,14 @@ pub async fn delete_user(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     oidc_provider: web::Data<Option<Arc<crate::services::oidc_provider::OidcProvider>>>,
     path: web::Path<uuid::Uuid>,
+    body: Option<web::Json<DeleteUserRequest>>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
     let user_id = path.into_inner();
+    let reason = normalize_deactivation_reason(body.as_ref().and_then(|b| b.reason.as_deref()))?;
 
     // Prevent self-deletion
     if admin.0.sub == user_id {
@@ -171,7 +273,7 @@ pub async fn delete_user(
         return Err(AppError::validation("user_id", "Cannot delete admin users"));
     }
 
-    UserRepository::soft_delete(&pool, user_id).await?;
+    UserRepository::soft_delete(&pool, user_id, reason.as_deref()).await?;
 
     tracing::info!(
         admin_id = %admin.0.sub,
@@ -186,8 +288,9 @@ pub async fn delete_user(
         .with_metadata(serde_json::json!({
             "target_email": target_user.email,
             "target_role": target_user.role,
+            "reason": reason,
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     if let Some(provider) = oidc_provider.as_ref().as_ref().cloned() {
         tokio::spawn(dispatch_lifecycle_event(provider, user_id, "user.deleted"));
@@ -196,6 +299,146 @@ pub async fn delete_user(
     Ok(success_no_data(request_id))
 }
 
+/// Request body for merging a duplicate account into another
+#[derive(Debug, Deserialize)]
+pub struct MergeUsersRequest {
+    /// Duplicate account; soft-deleted once merged
+    pub source_user_id: uuid::Uuid,
+    /// Account that keeps the merged membership and history
+    pub target_user_id: uuid::Uuid,
+}
+
+/// POST /v1/admin/users/merge
+/// Merge a duplicate account into another
+pub async fn merge_users(
+    req: HttpRequest,
+    admin: AdminUser,
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
+    stripe: web::Data<Arc<StripeService>>,
+    body: web::Json<MergeUsersRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let MergeUsersRequest {
+        source_user_id,
+        target_user_id,
+    } = body.into_inner();
+
+    if source_user_id == target_user_id {
+        return Err(AppError::validation(
+            "target_user_id",
+            "Cannot merge an account into itself",
+        ));
+    }
+
+    let source = UserRepository::find_by_id(&pool, source_user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User"))?;
+    let target = UserRepository::find_by_id(&pool, target_user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User"))?;
+
+    // Merging deletes the source, so the same rule as deleting applies
+    if source.is_admin() {
+        return Err(AppError::validation(
+            "source_user_id",
+            "Cannot merge away admin users",
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    let summary = UserRepository::merge_into(&mut *tx, source.id, target.id).await?;
+
+    let audit_log = CreateAuditLog::new(AuditAction::AdminUsersMerged)
+        .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
+        .with_resource("user", target.id)
+        .with_metadata(serde_json::json!({
+            "source_user_id": source.id,
+            "source_email": source.email,
+            "target_email": target.email,
+            "membership_moved": summary.membership_moved,
+            "stripe_customer_id": summary.stripe_customer_id,
+            "payment_method_moved": summary.payment_method_moved,
+        }));
+    AuditLogRepository::create_with(&mut *tx, &audit, audit_log).await?;
+
+    // Point the Stripe customer at its new owner so webhooks resolve to the
+    // target. This runs before the commit: if Stripe refuses (after the
+    // client's retries) the merge is rolled back and the error returned, so
+    // the admin can simply retry it.
+    if let Some(customer_id) = summary.stripe_customer_id.as_deref() {
+        stripe.set_customer_user_id(customer_id, target.id).await?;
+    }
+    tx.commit().await?;
+
+    // The source is gone, so its access tokens must stop working at once
+    force_token_refresh(&req, source.id, &AuditAction::AdminUsersMerged).await;
+    if summary.membership_moved {
+        force_token_refresh(&req, target.id, &AuditAction::AdminUsersMerged).await;
+    }
+
+    tracing::info!(
+        admin_id = %admin.0.sub,
+        source_user_id = %source.id,
+        target_user_id = %target.id,
+        membership_moved = summary.membership_moved,
+        "Admin merged user accounts"
+    );
+
+    Ok(success(summary, request_id))
+}
+
+/// POST /v1/admin/users/{user_id}/reconcile-stripe
+/// Correct a user's local membership from their live Stripe subscription
+pub async fn reconcile_stripe(
+    req: HttpRequest,
+    admin: AdminUser,
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
+    stripe: web::Data<Arc<StripeService>>,
+    tier_config: web::Data<Arc<std::sync::RwLock<crate::config::TierConfig>>>,
+    path: web::Path<uuid::Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let user_id = path.into_inner();
+
+    let user = UserRepository::find_by_id(&pool, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User"))?;
+    let customer_id = user
+        .stripe_customer_id
+        .as_deref()
+        .ok_or_else(|| AppError::validation("user_id", "User has no Stripe customer"))?;
+
+    let subscription = stripe.get_customer_subscription(customer_id).await?;
+    let tc = tier_config
+        .read()
+        .expect("TierConfig lock poisoned")
+        .clone();
+    let reconciliation =
+        crate::services::membership::reconcile_membership(&pool, &tc, &user, subscription.as_ref())
+            .await?;
+
+    if reconciliation.corrected.is_empty() {
+        return Ok(success(reconciliation, request_id));
+    }
+
+    force_token_refresh(&req, user.id, &AuditAction::AdminMembershipReconciled).await;
+
+    let audit_log = CreateAuditLog::new(AuditAction::AdminMembershipReconciled)
+        .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
+        .with_resource("user", user.id)
+        .with_metadata(serde_json::json!({
+            "stripe_customer_id": customer_id,
+            "stripe_subscription_id": reconciliation.stripe_subscription_id,
+            "stripe_status": reconciliation.stripe_status,
+            "corrected": reconciliation.corrected,
+        }));
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
+
+    Ok(success(reconciliation, request_id))
+}
+
 /// Request body for updating user role
 #[derive(Debug, Deserialize)]
 pub struct UpdateUserRoleRequest {
@@ -208,6 +451,7 @@ pub async fn update_user_role(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     path: web::Path<uuid::Uuid>,
     body: web::Json<UpdateUserRoleRequest>,
 ) -> Result<HttpResponse, AppError> {
@@ -237,6 +481,7 @@ pub async fn update_user_role(
     let old_role = target_user.role.clone();
 
     let updated_user = UserRepository::update_role(&pool, user_id, &body.role).await?;
+    force_token_refresh(&req, user_id, &AuditAction::AdminUserRoleChanged).await;
 
     tracing::info!(
         admin_id = %admin.0.sub,
@@ -253,7 +498,7 @@ pub async fn update_user_role(
         .with_metadata(serde_json::json!({
             "target_email": target_user.email,
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     Ok(success(UserResponse::from(updated_user), request_id))
 }
@@ -275,8 +520,9 @@ pub struct GrantMembershipRequest {
 /// Creates a $0 Stripe subscription so the user receives invoices.
 pub async fn grant_membership(
     req: HttpRequest,
-    admin: AdminUser,
+    admin: RequirePermission<scopes::BillingWrite>,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     stripe: web::Data<Arc<StripeService>>,
     body: web::Json<GrantMembershipRequest>,
 ) -> Result<HttpResponse, AppError> {
@@ -285,6 +531,7 @@ pub async fn grant_membership(
     // Grant free tier — sets lifetime_member=true and subscription_status='active'
     let user =
         UserRepository::grant_free_membership(pool.get_ref(), body.user_id, admin.0.sub).await?;
+    force_token_refresh(&req, body.user_id, &AuditAction::AdminMembershipGranted).await;
 
     // Create $0 Stripe subscription for invoice generation
     if let Some(free_price_id) = stripe.free_price_id() {
@@ -322,7 +569,7 @@ pub async fn grant_membership(
             "price_locked": price_locked,
             "locked_price_amount": locked_amount,
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     Ok(success_no_data(request_id))
 }
@@ -331,8 +578,9 @@ pub async fn grant_membership(
 /// Revoke a membership from a user
 pub async fn revoke_membership(
     req: HttpRequest,
-    admin: AdminUser,
+    admin: RequirePermission<scopes::BillingWrite>,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     body: web::Json<GrantMembershipRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -349,11 +597,12 @@ pub async fn revoke_membership(
 
     // Clear any grace period
     UserRepository::clear_grace_period(pool.get_ref(), body.user_id).await?;
+    force_token_refresh(&req, body.user_id, &AuditAction::AdminMembershipRevoked).await;
 
     let audit_log = CreateAuditLog::new(AuditAction::AdminMembershipRevoked)
         .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
         .with_resource("user", body.user_id);
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     Ok(success_no_data(request_id))
 }
@@ -370,15 +619,15 @@ pub struct ListMembershipsQuery {
 /// List all memberships with pagination (sourced from users table)
 pub async fn list_memberships(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingRead>,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
     query: web::Query<ListMembershipsQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
 
-    let page = query.page.unwrap_or(1).max(1);
-    let per_page = query.per_page.unwrap_or(20).min(100);
-    let offset = (page - 1) * per_page;
+    let (page, per_page) = resolve_page(query.page, query.per_page, 20, pagination.max_offset)?;
+    let offset = page.offset(per_page);
 
     let (memberships, total) = if let Some(ref status) = query.status {
         let rows = sqlx::query_as::<_, crate::models::AdminMembershipResponse>(
@@ -387,6 +636,7 @@ pub async fn list_memberships(
                    subscription_status AS status,
                    COALESCE(subscription_tier, 'standard') AS subscription_tier,
                    subscription_override_by,
+                   cancellation_reason, cancellation_feedback,
                    created_at
             FROM users
             WHERE subscription_status = $3 AND deleted_at IS NULL
@@ -394,7 +644,7 @@ pub async fn list_memberships(
             LIMIT $1 OFFSET $2
             "#,
         )
-        .bind(per_page)
+        .bind(per_page.get())
         .bind(offset)
         .bind(status)
         .fetch_all(pool.get_ref())
@@ -415,6 +665,7 @@ pub async fn list_memberships(
                    subscription_status AS status,
                    COALESCE(subscription_tier, 'standard') AS subscription_tier,
                    subscription_override_by,
+                   cancellation_reason, cancellation_feedback,
                    created_at
             FROM users
             WHERE subscription_status != 'none' AND deleted_at IS NULL
@@ -422,7 +673,7 @@ pub async fn list_memberships(
             LIMIT $1 OFFSET $2
             "#,
         )
-        .bind(per_page)
+        .bind(per_page.get())
         .bind(offset)
         .fetch_all(pool.get_ref())
         .await?;
@@ -443,21 +694,29 @@ pub async fn list_memberships(
 // Application Management
 // =============================================================================
 
+/// Query parameters for listing applications
+#[derive(Debug, Deserialize)]
+pub struct ListApplicationsQuery {
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+}
+
 /// GET /v1/admin/applications
-/// List all applications (including inactive)
+/// List all applications (including inactive) with pagination
 pub async fn list_all_applications(
     req: HttpRequest,
     _admin: AdminUser,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
+    query: web::Query<ListApplicationsQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
 
-    let apps = ApplicationRepository::list_all(&pool).await?;
+    let (page, per_page) = resolve_page(query.page, query.per_page, 20, pagination.max_offset)?;
 
-    Ok(success(
-        serde_json::json!({ "applications": apps }),
-        request_id,
-    ))
+    let (apps, total) = ApplicationRepository::list_all_paginated(&pool, page, per_page).await?;
+
+    Ok(paginated(apps, total, page, per_page, request_id))
 }
 
 /// PUT /v1/admin/applications/{app_id}/swap-order
@@ -489,6 +748,7 @@ pub async fn update_application(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     path: web::Path<uuid::Uuid>,
     body: web::Json<UpdateApplication>,
     webhook_service: web::Data<Arc<WebhookService>>,
@@ -604,7 +864,7 @@ pub async fn update_application(
             "is_active": app.is_active,
             "maintenance_mode": app.maintenance_mode,
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     // Additional specific log when maintenance mode changes
     if maintenance_changed {
@@ -615,7 +875,7 @@ pub async fn update_application(
                 "application_name": app.name,
                 "maintenance_mode": app.maintenance_mode,
             }));
-        AuditLogRepository::create(&pool, maintenance_log).await?;
+        AuditLogRepository::create(&pool, &audit, maintenance_log).await?;
     }
 
     Ok(success(app, request_id))
@@ -627,26 +887,37 @@ pub async fn create_application(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     body: web::Json<CreateApplication>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
 
     // Validate required fields
     if body.name.trim().is_empty() {
-        return Err(AppError::validation("name", "Name is required"));
+        return Err(AppError::validation_coded(
+            "name",
+            "name_required",
+            "Name is required",
+        ));
     }
     if body.slug.trim().is_empty() {
-        return Err(AppError::validation("slug", "Slug is required"));
+        return Err(AppError::validation_coded(
+            "slug",
+            "slug_required",
+            "Slug is required",
+        ));
     }
     if body.display_name.trim().is_empty() {
-        return Err(AppError::validation(
+        return Err(AppError::validation_coded(
             "display_name",
+            "display_name_required",
             "Display name is required",
         ));
     }
     if body.container_name.trim().is_empty() {
-        return Err(AppError::validation(
+        return Err(AppError::validation_coded(
             "container_name",
+            "container_name_required",
             "Container name is required",
         ));
     }
@@ -658,6 +929,13 @@ pub async fn create_application(
             "Slug must contain only lowercase letters, numbers, and hyphens",
         )
     })?;
+    if validation::ValidationRules::RESERVED_APPLICATION_SLUGS.contains(&body.slug.as_str()) {
+        return Err(AppError::validation_coded(
+            "slug",
+            "slug_reserved",
+            format!("Slug `{}` is reserved", body.slug),
+        ));
+    }
 
     // Check slug uniqueness
     if ApplicationRepository::find_by_slug(&pool, &body.slug)
@@ -679,20 +957,22 @@ pub async fn create_application(
             "application_name": app.name,
             "application_slug": app.slug,
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     Ok(created(app, request_id))
 }
 
 /// DELETE /v1/admin/applications/{app_id}
-/// Delete an application (requires password + 2FA)
+/// Retire an inactive application (requires password + 2FA)
 pub async fn delete_application(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     path: web::Path<uuid::Uuid>,
     body: web::Json<DeleteApplicationRequest>,
     totp_service: web::Data<Arc<TotpService>>,
+    password_service: web::Data<Arc<PasswordService>>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
     let app_id = path.into_inner();
@@ -703,13 +983,19 @@ pub async fn delete_application(
         .ok_or(AppError::not_found("User"))?;
 
     // Verify password
-    let password_service = PasswordService::new();
-    let password_hash = admin_user
-        .password_hash
-        .as_deref()
-        .ok_or_else(|| AppError::validation("password", "Account has no password set"))?;
+    let password_hash = admin_user.password_hash.as_deref().ok_or_else(|| {
+        AppError::validation_coded(
+            "password",
+            "password_not_set",
+            "Account has no password set",
+        )
+    })?;
     if !password_service.verify(&body.password, password_hash)? {
-        return Err(AppError::validation("password", "Invalid password"));
+        return Err(AppError::validation_coded(
+            "password",
+            "password_incorrect",
+            "Invalid password",
+        ));
     }
 
     // Verify TOTP code (2FA must be enabled)
@@ -720,16 +1006,15 @@ pub async fn delete_application(
             AppError::validation("totp_code", "2FA must be enabled to delete applications")
         })?;
     if !totp_valid {
-        return Err(AppError::validation("totp_code", "Invalid 2FA code"));
+        return Err(AppError::validation_coded(
+            "totp_code",
+            "totp_code_invalid",
+            "Invalid 2FA code",
+        ));
     }
 
-    // Find the application (for audit metadata)
-    let app = ApplicationRepository::find_by_id(&pool, app_id)
-        .await?
-        .ok_or(AppError::not_found("Application"))?;
-
-    // Delete
-    ApplicationRepository::delete(&pool, app_id).await?;
+    // Retire rather than delete, so history keeps a row to point at
+    let app = ApplicationRepository::retire(&pool, app_id).await?;
 
     // Audit log
     let audit_log = CreateAuditLog::new(AuditAction::ApplicationDeleted)
@@ -739,7 +1024,7 @@ pub async fn delete_application(
             "application_name": app.name,
             "application_slug": app.slug,
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     Ok(success_no_data(request_id))
 }
@@ -762,14 +1047,14 @@ pub struct ListAuditLogsQuery {
 /// List audit logs with pagination
 pub async fn list_audit_logs(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::AuditRead>,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
     query: web::Query<ListAuditLogsQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
 
-    let page = query.page.unwrap_or(1).max(1);
-    let per_page = query.per_page.unwrap_or(50).min(100);
+    let (page, per_page) = resolve_page(query.page, query.per_page, 50, pagination.max_offset)?;
 
     let (logs, total) = AuditLogRepository::list_paginated(
         &pool,
@@ -786,6 +1071,80 @@ pub async fn list_audit_logs(
     Ok(paginated(logs, total, page, per_page, request_id))
 }
 
+// =============================================================================
+// IP Bans
+// =============================================================================
+
+/// Query parameters for listing IP bans
+#[derive(Debug, Deserialize)]
+pub struct ListIpBansQuery {
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+}
+
+/// GET /v1/admin/ip-bans
+/// List active auto-bans, most recent first
+pub async fn list_ip_bans(
+    req: HttpRequest,
+    _admin: AdminUser,
+    pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
+    query: web::Query<ListIpBansQuery>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let (page, per_page) = resolve_page(query.page, query.per_page, 50, pagination.max_offset)?;
+    let (bans, total) = auto_ban::list_active_bans(&pool, page, per_page).await?;
+
+    Ok(paginated(bans, total, page, per_page, request_id))
+}
+
+/// DELETE /v1/admin/ip-bans/{ip}
+/// Lift a ban on an IPv4 or IPv6 address. The row is deleted and this
+/// replica stops enforcing it at once; other replicas hold their own copy
+/// until it expires or they restart.
+pub async fn delete_ip_ban(
+    req: HttpRequest,
+    admin: AdminUser,
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
+    auto_ban: web::Data<Arc<AutoBanService>>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let ip: IpAddr = path
+        .trim()
+        .parse()
+        .map_err(|_| AppError::validation("ip", "Must be an IPv4 or IPv6 address"))?;
+
+    let row = auto_ban::delete_ban(&pool, &ip).await?;
+    let evicted = auto_ban.unban(&ip).await;
+    if row.is_none() && !evicted {
+        return Err(AppError::not_found("IP ban"));
+    }
+
+    tracing::info!(
+        admin_id = %admin.0.sub,
+        ip = %ip,
+        "Admin lifted IP ban"
+    );
+
+    let mut audit_log = CreateAuditLog::new(AuditAction::AdminIpUnbanned)
+        .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
+        .with_metadata(serde_json::json!({
+            "ip_address": ip.to_string(),
+            "reason": row.as_ref().map(|ban| &ban.reason),
+            "strikes": row.as_ref().map(|ban| ban.strikes),
+            "expires_at": row.as_ref().map(|ban| ban.expires_at.to_rfc3339()),
+        }));
+    if let Some(ban) = &row {
+        audit_log = audit_log.with_resource("ip_ban", ban.id);
+    }
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
+
+    Ok(success_no_data(request_id))
+}
+
 // =============================================================================
 // Dashboard Stats
 // =============================================================================
@@ -833,9 +1192,10 @@ pub async fn get_dashboard_stats(
     .fetch_one(pool.get_ref())
     .await?;
 
-    let total_applications: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM applications")
-        .fetch_one(pool.get_ref())
-        .await?;
+    let total_applications: (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM applications WHERE retired_at IS NULL")
+            .fetch_one(pool.get_ref())
+            .await?;
 
     let active_applications: (i64,) =
         sqlx::query_as("SELECT COUNT(*) FROM applications WHERE is_active = TRUE")
@@ -854,6 +1214,79 @@ pub async fn get_dashboard_stats(
     Ok(success(stats, request_id))
 }
 
+/// Longest range accepted by the time-series endpoint
+const MAX_TIMESERIES_RANGE_DAYS: i64 = 366;
+
+/// Query parameters for the stats time series
+#[derive(Debug, Deserialize)]
+pub struct TimeseriesQuery {
+    /// `signups` or `revenue`
+    pub metric: String,
+    /// `day` (default) or `week`
+    pub interval: Option<String>,
+    /// Lookback window such as `30d` or `12w` (default `30d`)
+    pub range: Option<String>,
+    /// Currency whose payments make up `revenue` (default `usd`)
+    pub currency: Option<String>,
+}
+
+/// Parse a lookback range like `30d` or `12w` into a duration.
+fn parse_timeseries_range(range: &str) -> Result<Duration, AppError> {
+    let invalid = || AppError::validation("range", "Range must look like 30d or 12w");
+    let days = if let Some(count) = range.strip_suffix('d') {
+        count.parse::<i64>().map_err(|_| invalid())?
+    } else if let Some(count) = range.strip_suffix('w') {
+        let weeks = count.parse::<i64>().map_err(|_| invalid())?;
+        weeks.checked_mul(7).ok_or_else(invalid)?
+    } else {
+        return Err(invalid());
+    };
+    if !(1..=MAX_TIMESERIES_RANGE_DAYS).contains(&days) {
+        return Err(AppError::validation(
+            "range",
+            format!(
+                "Range must be between 1 and {} days",
+                MAX_TIMESERIES_RANGE_DAYS
+            ),
+        ));
+    }
+    Ok(Duration::days(days))
+}
+
+/// GET /v1/admin/stats/timeseries
+/// Bucketed signups or revenue over a recent window
+pub async fn get_stats_timeseries(
+    req: HttpRequest,
+    _admin: AdminUser,
+    pool: web::Data<PgPool>,
+    query: web::Query<TimeseriesQuery>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let metric = TimeseriesMetric::from_str(&query.metric)
+        .ok_or_else(|| AppError::validation("metric", "Metric must be signups or revenue"))?;
+    let interval = TimeseriesInterval::from_str(query.interval.as_deref().unwrap_or("day"))
+        .ok_or_else(|| AppError::validation("interval", "Interval must be day or week"))?;
+    let range = parse_timeseries_range(query.range.as_deref().unwrap_or("30d"))?;
+    let currency = Currency::parse(query.currency.as_deref().unwrap_or("usd"))?;
+
+    let until = Utc::now();
+    let since = until - range;
+    let points =
+        StatsRepository::timeseries(&pool, metric, interval, since, until, &currency).await?;
+
+    Ok(success(
+        TimeseriesResponse {
+            metric: metric.as_str(),
+            interval: interval.as_str(),
+            since,
+            currency: (metric == TimeseriesMetric::Revenue).then(|| currency.to_string()),
+            points,
+        },
+        request_id,
+    ))
+}
+
 // =============================================================================
 // User Actions (Reset Password, Impersonate)
 // =============================================================================
@@ -864,6 +1297,7 @@ pub async fn admin_reset_password(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     jwt_service: web::Data<Arc<JwtService>>,
     email_service: web::Data<Arc<EmailService>>,
     path: web::Path<uuid::Uuid>,
@@ -906,17 +1340,20 @@ pub async fn admin_reset_password(
             "target_user_id": user_id,
             "target_email": user.email
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     Ok(success_no_data(request_id))
 }
 
 /// POST /v1/admin/users/{user_id}/impersonate
-/// Generate tokens to impersonate a user
+/// Generate an access token to impersonate a user. No refresh token is
+/// issued: a refreshed session would no longer carry the impersonating
+/// admin, so the admin impersonates again once the token expires.
 pub async fn impersonate_user(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     jwt_service: web::Data<Arc<JwtService>>,
     path: web::Path<uuid::Uuid>,
 ) -> Result<HttpResponse, AppError> {
@@ -937,24 +1374,11 @@ pub async fn impersonate_user(
         .await?
         .ok_or(AppError::not_found("User"))?;
 
-    // Generate access token for target user
-    let access_token = jwt_service.create_access_token(&target_user)?;
-
-    // Generate refresh token
-    let (refresh_token, token_hash) = jwt_service.create_refresh_token(target_user.id)?;
-    let expires_at = Utc::now() + Duration::days(30);
-
-    TokenRepository::create_refresh_token(
-        &pool,
-        CreateRefreshToken {
-            user_id: target_user.id,
-            token_hash,
-            device_info: Some("Admin impersonation".to_string()),
-            ip_address: None,
-            expires_at,
-        },
-    )
-    .await?;
+    // Generate access token for target user, tagged with the impersonating
+    // admin. Without an auth_time it never counts as a recent sign-in, so
+    // impersonation cannot pass step-up checks.
+    let access_token =
+        jwt_service.create_impersonation_token(&target_user, admin_user_id, &admin.0.email)?;
 
     // Log admin action
     let audit_log = CreateAuditLog::new(AuditAction::AdminUserImpersonated)
@@ -965,12 +1389,11 @@ pub async fn impersonate_user(
             "target_email": target_user.email,
             "admin_id": admin_user_id
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     Ok(success(
         serde_json::json!({
             "access_token": access_token,
-            "refresh_token": refresh_token,
             "user": UserResponse::from(target_user)
         }),
         request_id,
@@ -995,6 +1418,7 @@ pub async fn list_notifications(
     req: HttpRequest,
     _admin: AdminUser,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
     query: web::Query<ListNotificationsQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -1002,11 +1426,16 @@ pub async fn list_notifications(
     if query.unread.unwrap_or(false) {
         let notifications = NotificationRepository::list_unread(&pool).await?;
         let total = notifications.len() as i64;
-        return Ok(paginated(notifications, total, 1, 100, request_id));
+        return Ok(paginated(
+            notifications,
+            total,
+            Page::FIRST,
+            PerPage::MAX,
+            request_id,
+        ));
     }
 
-    let page = query.page.unwrap_or(1).max(1);
-    let per_page = query.per_page.unwrap_or(20).min(100);
+    let (page, per_page) = resolve_page(query.page, query.per_page, 20, pagination.max_offset)?;
 
     let (notifications, total) =
         NotificationRepository::list_paginated(&pool, page, per_page).await?;
@@ -1030,6 +1459,40 @@ pub async fn mark_notification_read(
     Ok(success_no_data(request_id))
 }
 
+/// Request body for marking a subset of notifications read
+#[derive(Debug, Deserialize)]
+pub struct MarkNotificationsReadRequest {
+    /// Only notifications of this type, e.g. `payment_failed`
+    #[serde(rename = "type")]
+    pub notification_type: Option<NotificationType>,
+    /// Only notifications created before this instant
+    pub older_than: Option<DateTime<Utc>>,
+}
+
+/// POST /v1/admin/notifications/read
+/// Mark unread notifications matching a type and/or age filter as read
+pub async fn mark_notifications_read(
+    req: HttpRequest,
+    admin: AdminUser,
+    pool: web::Data<PgPool>,
+    body: web::Json<MarkNotificationsReadRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let marked = NotificationRepository::mark_read_filtered(
+        &pool,
+        admin.0.sub,
+        body.notification_type.as_ref(),
+        body.older_than,
+    )
+    .await?;
+
+    Ok(success(
+        serde_json::json!({ "marked_read": marked }),
+        request_id,
+    ))
+}
+
 /// POST /v1/admin/notifications/read-all
 /// Mark all notifications as read
 pub async fn mark_all_notifications_read(
@@ -1148,12 +1611,12 @@ pub async fn list_admin_invites(
     req: HttpRequest,
     _admin: AdminUser,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
     query: web::Query<ListAdminInvitesQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
 
-    let page = query.page.unwrap_or(1).max(1);
-    let per_page = query.per_page.unwrap_or(20).min(100);
+    let (page, per_page) = resolve_page(query.page, query.per_page, 20, pagination.max_offset)?;
 
     let (invites, total) = InviteRepository::list_all(&pool, page, per_page).await?;
 
@@ -1294,8 +1757,10 @@ pub async fn update_stripe_config(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     stripe_key_set: web::Data<EncryptionKeySet>,
     stripe_service: web::Data<Arc<StripeService>>,
+    config: web::Data<Config>,
     body: web::Json<UpdateStripeConfigRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -1338,7 +1803,7 @@ pub async fn update_stripe_config(
     .await?;
 
     // Hot-reload the live StripeService so new API calls use the updated keys
-    match StripeConfig::from_db_model(&updated, &stripe_key_set) {
+    match StripeConfig::from_db_model(&updated, &stripe_key_set, &config) {
         Ok(new_config) => {
             stripe_service.reload(new_config);
             tracing::info!("Stripe service reloaded with updated config");
@@ -1357,7 +1822,7 @@ pub async fn update_stripe_config(
                 "app_tag": app_tag,
             }
         }));
-    AuditLogRepository::create(&pool, audit_log).await?;
+    AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
     Ok(success(
         StripeConfigResponse::from_db(&updated, &stripe_key_set)?,
@@ -1374,8 +1839,9 @@ pub async fn update_stripe_config(
 /// Creates a $0 Stripe subscription so the user receives invoices.
 pub async fn grant_lifetime_membership(
     req: HttpRequest,
-    admin: AdminUser,
+    admin: RequirePermission<scopes::BillingWrite>,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     stripe: web::Data<Arc<StripeService>>,
     path: web::Path<uuid::Uuid>,
 ) -> Result<HttpResponse, AppError> {
@@ -1383,6 +1849,7 @@ pub async fn grant_lifetime_membership(
     let user_id = path.into_inner();
 
     let user = UserRepository::grant_lifetime_membership(&pool, user_id, admin.0.sub).await?;
+    force_token_refresh(&req, user_id, &AuditAction::AdminMembershipGranted).await;
 
     // Create $0 Stripe subscription for invoice generation
     if let Some(free_price_id) = stripe.free_price_id() {
@@ -1401,6 +1868,7 @@ pub async fn grant_lifetime_membership(
 
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::AdminMembershipGranted)
             .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
             .with_resource("user", user_id)
@@ -1482,6 +1950,7 @@ pub async fn update_tier_config(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     auth_service: web::Data<Arc<AuthService>>,
     body: web::Json<UpdateTierConfigRequest>,
 ) -> Result<HttpResponse, AppError> {
@@ -1551,6 +2020,7 @@ pub async fn update_tier_config(
 
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::AdminTierConfigUpdated)
             .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
             .with_metadata(serde_json::json!({
@@ -1809,6 +2279,7 @@ pub async fn reencrypt_key(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     totp_service: web::Data<Arc<TotpService>>,
     stripe_key_set: web::Data<EncryptionKeySet>,
     path: web::Path<String>,
@@ -1852,7 +2323,7 @@ pub async fn reencrypt_key(
                     "total": total,
                     "new_version": current_version,
                 }));
-            AuditLogRepository::create(&pool, audit_log).await?;
+            AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
             Ok(success(
                 serde_json::json!({
@@ -1916,7 +2387,7 @@ pub async fn reencrypt_key(
                     "reencrypted": 1,
                     "new_version": current_version,
                 }));
-            AuditLogRepository::create(&pool, audit_log).await?;
+            AuditLogRepository::create(&pool, &audit, audit_log).await?;
 
             Ok(success(
                 serde_json::json!({
@@ -2069,6 +2540,262 @@ mod tests {
             .await
             .unwrap();
     }
+
+    fn admin_token(jwt: &JwtService) -> String {
+        let admin = crate::models::User {
+            email: "admin@example.com".to_string(),
+            role: "admin".into(),
+            membership_status: "none".to_string(),
+            ..crate::models::User::test_fixture()
+        };
+        jwt.create_access_token(&admin).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn list_all_applications_uses_paginated_envelope() {
+        use actix_web::{test, App};
+
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let slug = format!("paged-{}", uuid::Uuid::new_v4());
+        sqlx::query(
+            "INSERT INTO applications (name, slug, display_name, container_name) VALUES ($1, $1, $1, $1)",
+        )
+        .bind(&slug)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let jwt = Arc::new(JwtService::new(crate::services::JwtConfig::from_secret(
+            "a-very-long-secret-key-for-tests-12345",
+            "a8n",
+        )));
+        let token = admin_token(&jwt);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(PaginationConfig::default()))
+                .app_data(jwt.clone())
+                .route("/applications", web::get().to(list_all_applications)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/applications?page=1&per_page=1")
+            .insert_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let json: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(json["success"], true);
+        let data = &json["data"];
+        assert!(data["items"].is_array());
+        assert_eq!(data["items"].as_array().unwrap().len(), 1);
+        assert!(data["total"].as_i64().unwrap() >= 1);
+        assert_eq!(data["page"], 1);
+        assert_eq!(data["per_page"], 1);
+        assert_eq!(data["total_pages"], data["total"]);
+        assert!(data.get("applications").is_none());
+
+        sqlx::query("DELETE FROM applications WHERE slug = $1")
+            .bind(&slug)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn lifting_an_ip_ban_deletes_the_row_and_evicts_it_from_memory() {
+        use actix_web::{http::StatusCode, test, App};
+
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let admin = UserRepository::create(
+            &pool,
+            crate::models::CreateUser {
+                email: format!("unban-{}@example.com", uuid::Uuid::new_v4()),
+                password_hash: None,
+                role: crate::models::UserRole::Admin,
+            },
+        )
+        .await
+        .unwrap();
+        let jwt = Arc::new(JwtService::new(crate::services::JwtConfig::from_secret(
+            "a-very-long-secret-key-for-tests-12345",
+            "a8n",
+        )));
+        let token = jwt.create_access_token(&admin).unwrap();
+
+        let ip = IpAddr::from(std::net::Ipv6Addr::new(
+            0x2001,
+            0xdb8,
+            0,
+            0,
+            0,
+            0,
+            rand::random(),
+            rand::random(),
+        ));
+        sqlx::query(
+            "INSERT INTO ip_bans (ip_address, reason, strikes, expires_at) VALUES ($1, 'Suspicious path: /.env', 5, NOW() + INTERVAL '1 hour')",
+        )
+        .bind(ipnetwork::IpNetwork::from(ip))
+        .execute(&pool)
+        .await
+        .unwrap();
+        let auto_ban = Arc::new(AutoBanService::new(
+            crate::config::AutoBanConfig::from_env(),
+            pool.clone(),
+        ));
+        auto_ban
+            .load_bans(auto_ban::load_active_bans(&pool).await.unwrap())
+            .await;
+        assert!(auto_ban.is_banned(&ip).await);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(auto_ban.clone()))
+                .app_data(web::Data::new(AuditConfig::default()))
+                .app_data(web::Data::new(PaginationConfig::default()))
+                .app_data(jwt.clone())
+                .route("/ip-bans", web::get().to(list_ip_bans))
+                .route("/ip-bans/{ip}", web::delete().to(delete_ip_ban)),
+        )
+        .await;
+        let delete = |ip: String| {
+            test::TestRequest::delete()
+                .uri(&format!("/ip-bans/{ip}"))
+                .insert_header(("Authorization", format!("Bearer {token}")))
+                .to_request()
+        };
+
+        let req = test::TestRequest::get()
+            .uri("/ip-bans?per_page=100")
+            .insert_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let json: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(json["data"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|ban| ban["ip_address"] == ip.to_string()));
+
+        let res = test::call_service(&app, delete(ip.to_string())).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!auto_ban.is_banned(&ip).await);
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ip_bans WHERE ip_address = $1")
+            .bind(ipnetwork::IpNetwork::from(ip))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+        let audited: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM audit_logs WHERE action = 'admin_ip_unbanned' AND actor_id = $1 AND metadata->>'ip_address' = $2",
+        )
+        .bind(admin.id)
+        .bind(ip.to_string())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(audited, 1);
+
+        let res = test::call_service(&app, delete(ip.to_string())).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = test::call_service(&app, delete("not-an-ip".to_string())).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        sqlx::query("DELETE FROM audit_logs WHERE actor_id = $1")
+            .bind(admin.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(admin.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn parse_timeseries_range_accepts_days_and_weeks() {
+        assert_eq!(parse_timeseries_range("30d").unwrap(), Duration::days(30));
+        assert_eq!(parse_timeseries_range("12w").unwrap(), Duration::days(84));
+    }
+
+    #[test]
+    fn parse_timeseries_range_rejects_bad_input() {
+        for range in [
+            "",
+            "d",
+            "30",
+            "30m",
+            "-5d",
+            "0d",
+            "400d",
+            "9999999999999999999w",
+        ] {
+            assert!(
+                parse_timeseries_range(range).is_err(),
+                "{range} should fail"
+            );
+        }
+    }
+
+    #[test]
+    fn deactivation_reason_is_trimmed_and_bounded() {
+        assert_eq!(normalize_deactivation_reason(None).unwrap(), None);
+        assert_eq!(normalize_deactivation_reason(Some("   ")).unwrap(), None);
+        assert_eq!(
+            normalize_deactivation_reason(Some("  chargeback fraud ")).unwrap(),
+            Some("chargeback fraud".to_string())
+        );
+        let too_long = "x".repeat(MAX_DEACTIVATION_REASON_CHARS + 1);
+        assert!(normalize_deactivation_reason(Some(&too_long)).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn deactivation_reason_is_persisted_and_returned() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let user = UserRepository::create(
+            &pool,
+            crate::models::CreateUser {
+                email: format!("deactivate-{}@example.com", uuid::Uuid::new_v4()),
+                password_hash: None,
+                role: crate::models::UserRole::Subscriber,
+            },
+        )
+        .await
+        .unwrap();
+
+        UserRepository::soft_delete(&pool, user.id, Some("chargeback fraud"))
+            .await
+            .unwrap();
+        let hidden = UserRepository::find_by_id(&pool, user.id).await.unwrap();
+        let stored = UserRepository::find_by_id_with_deleted(&pool, user.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(hidden.is_none());
+        assert_eq!(
+            stored.deactivation_reason.as_deref(),
+            Some("chargeback fraud")
+        );
+        let response = serde_json::to_value(UserResponse::from(stored)).unwrap();
+        assert_eq!(response["deactivation_reason"], "chargeback fraud");
+        assert!(response["deactivated_at"].is_string());
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,353 @@
+//! Admin-only: bulk membership grants from CSV, for migrating legacy members.
+//!
+//! Each row is `user,amount,locked` where `user` is a user ID or email,
+//! `amount` is the locked price in cents and `locked` is a boolean. A header
+//! row starting with `user`, `user_id` or `email` is skipped. Rows are applied
+//! independently, each in its own transaction, so one bad row does not block
+//! the rest.
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::AuditConfig;
+use crate::errors::AppError;
+use crate::middleware::{force_token_refresh, RequirePermission};
+use crate::models::{scopes, AuditAction, CreateAuditLog};
+use crate::repositories::{AuditLogRepository, UserRepository};
+use crate::responses::{get_request_id, success};
+
+/// Most rows accepted in one upload
+const MAX_BULK_GRANT_ROWS: usize = 1000;
+
+/// Price ID recorded for prices locked by an admin grant
+const ADMIN_GRANT_PRICE_ID: &str = "price_admin_grant";
+
+/// Who a CSV row refers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrantTarget {
+    UserId(Uuid),
+    Email(String),
+}
+
+/// A validated CSV row
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrantRow {
+    pub line: usize,
+    pub target: GrantTarget,
+    pub amount: i32,
+    pub locked: bool,
+}
+
+/// Outcome of one CSV row
+#[derive(Debug, Serialize)]
+pub struct BulkGrantResult {
+    pub line: usize,
+    pub user: String,
+    pub user_id: Option<Uuid>,
+    pub granted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Summary returned by the bulk grant endpoint
+#[derive(Debug, Serialize)]
+pub struct BulkGrantResponse {
+    pub granted: usize,
+    pub failed: usize,
+    pub results: Vec<BulkGrantResult>,
+}
+
+/// POST /v1/admin/subscriptions/grant/bulk
+/// Grant memberships with locked prices to every user listed in a CSV body.
+/// Unlike the single grant, no $0 Stripe subscription is created.
+pub async fn bulk_grant_memberships(
+    req: HttpRequest,
+    admin: RequirePermission<scopes::BillingWrite>,
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
+    body: String,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let rows = parse_grant_csv(&body);
+    if rows.is_empty() {
+        return Err(AppError::validation_coded(
+            "csv",
+            "csv_empty",
+            "CSV contains no rows",
+        ));
+    }
+    if rows.len() > MAX_BULK_GRANT_ROWS {
+        return Err(AppError::validation_coded(
+            "csv",
+            "csv_too_many_rows",
+            format!("CSV may contain at most {} rows", MAX_BULK_GRANT_ROWS),
+        ));
+    }
+
+    let admin = &admin.0;
+    let mut results = Vec::with_capacity(rows.len());
+    for (line, raw_user, parsed) in rows {
+        let result = match parsed {
+            Ok(row) => apply_grant(&pool, &row, admin.sub)
+                .await
+                .map(|user_id| (user_id, row)),
+            Err(message) => Err(message),
+        };
+        match result {
+            Ok((user_id, row)) => {
+                force_token_refresh(&req, user_id, &AuditAction::AdminMembershipGranted).await;
+                let audit_log = CreateAuditLog::new(AuditAction::AdminMembershipGranted)
+                    .with_actor(admin.sub, &admin.email, &admin.role)
+                    .with_resource("user", user_id)
+                    .with_metadata(serde_json::json!({
+                        "tier": "free",
+                        "price_locked": row.locked,
+                        "locked_price_amount": row.amount,
+                        "source": "bulk_csv",
+                        "line": line,
+                    }));
+                if let Err(e) = AuditLogRepository::create(&pool, &audit, audit_log).await {
+                    tracing::error!(error = %e, user_id = %user_id, "Failed to create audit log for bulk grant");
+                }
+                results.push(BulkGrantResult {
+                    line,
+                    user: raw_user,
+                    user_id: Some(user_id),
+                    granted: true,
+                    error: None,
+                });
+            }
+            Err(message) => results.push(BulkGrantResult {
+                line,
+                user: raw_user,
+                user_id: None,
+                granted: false,
+                error: Some(message),
+            }),
+        }
+    }
+
+    let granted = results.iter().filter(|r| r.granted).count();
+    tracing::info!(
+        admin_id = %admin.sub,
+        granted,
+        failed = results.len() - granted,
+        "Applied bulk membership grants"
+    );
+
+    Ok(success(
+        BulkGrantResponse {
+            granted,
+            failed: results.len() - granted,
+            results,
+        },
+        request_id,
+    ))
+}
+
+/// Parse CSV text into `(line, raw user column, row or error)` entries.
+/// Blank lines and a leading header row are skipped.
+pub fn parse_grant_csv(csv: &str) -> Vec<(usize, String, Result<GrantRow, String>)> {
+    let mut rows = Vec::new();
+    for (index, raw) in csv.lines().enumerate() {
+        let line = index + 1;
+        let raw = raw.trim();
+        if raw.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = raw.split(',').map(|f| f.trim().trim_matches('"')).collect();
+        if rows.is_empty()
+            && matches!(
+                fields[0].to_lowercase().as_str(),
+                "user" | "user_id" | "email"
+            )
+        {
+            continue;
+        }
+        rows.push((line, fields[0].to_string(), parse_grant_row(line, &fields)));
+    }
+    rows
+}
+
+fn parse_grant_row(line: usize, fields: &[&str]) -> Result<GrantRow, String> {
+    let [user, amount, locked] = fields else {
+        return Err(format!(
+            "Expected 3 columns (user, amount, locked), got {}",
+            fields.len()
+        ));
+    };
+
+    let target = match user.parse::<Uuid>() {
+        Ok(id) => GrantTarget::UserId(id),
+        Err(_) if user.contains('@') => {
+            crate::validation::validate_email(user).map_err(|_| "Invalid email".to_string())?;
+            GrantTarget::Email(user.to_lowercase())
+        }
+        Err(_) => return Err("Not a valid user ID or email".to_string()),
+    };
+
+    let amount = amount
+        .parse::<i32>()
+        .ok()
+        .filter(|a| *a >= 0)
+        .ok_or_else(|| "Amount must be a non-negative integer (cents)".to_string())?;
+
+    let locked = match locked.to_lowercase().as_str() {
+        "true" | "1" | "yes" => true,
+        "false" | "0" | "no" | "" => false,
+        _ => return Err("Locked must be true or false".to_string()),
+    };
+
+    Ok(GrantRow {
+        line,
+        target,
+        amount,
+        locked,
+    })
+}
+
+/// Grant the membership (and lock the price if requested) in one transaction.
+/// Errors are returned as messages for the per-row result.
+async fn apply_grant(pool: &PgPool, row: &GrantRow, admin_id: Uuid) -> Result<Uuid, String> {
+    let user_id = match &row.target {
+        GrantTarget::UserId(id) => *id,
+        GrantTarget::Email(email) => {
+            UserRepository::find_by_email(pool, email)
+                .await
+                .map_err(|e| row_error(row, e))?
+                .ok_or_else(|| "User not found".to_string())?
+                .id
+        }
+    };
+
+    let result: Result<(), AppError> = async {
+        let mut tx = pool.begin().await?;
+        UserRepository::grant_free_membership(&mut *tx, user_id, admin_id).await?;
+        if row.locked {
+            UserRepository::lock_price(&mut *tx, user_id, ADMIN_GRANT_PRICE_ID, row.amount).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    result.map(|_| user_id).map_err(|e| row_error(row, e))
+}
+
+fn row_error(row: &GrantRow, error: AppError) -> String {
+    match error {
+        AppError::NotFound { .. } => "User not found".to_string(),
+        other => {
+            tracing::error!(error = %other, line = row.line, "Bulk grant row failed");
+            "Failed to apply grant".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "user,amount,locked\n\
+        3f1c1d2e-8a4b-4c5d-9e6f-0a1b2c3d4e5f,300,true\n\
+        \n\
+        Legacy@Example.com,500,false\n\
+        not-an-email,300,true\n";
+
+    #[test]
+    fn parses_rows_and_reports_the_invalid_one() {
+        let rows = parse_grant_csv(CSV);
+        assert_eq!(rows.len(), 3);
+
+        let (line, _, first) = &rows[0];
+        assert_eq!(*line, 2);
+        assert_eq!(
+            first.as_ref().unwrap(),
+            &GrantRow {
+                line: 2,
+                target: GrantTarget::UserId(
+                    "3f1c1d2e-8a4b-4c5d-9e6f-0a1b2c3d4e5f".parse().unwrap()
+                ),
+                amount: 300,
+                locked: true,
+            }
+        );
+
+        let (_, _, second) = &rows[1];
+        let second = second.as_ref().unwrap();
+        assert_eq!(second.line, 4);
+        assert_eq!(
+            second.target,
+            GrantTarget::Email("legacy@example.com".into())
+        );
+        assert!(!second.locked);
+
+        let (line, user, third) = &rows[2];
+        assert_eq!((*line, user.as_str()), (5, "not-an-email"));
+        assert!(third.is_err());
+    }
+
+    #[test]
+    fn rejects_bad_amounts_flags_and_column_counts() {
+        let rows = parse_grant_csv(
+            "a@example.com,-1,true\na@example.com,300,maybe\na@example.com,300\na@example.com,3.5,no",
+        );
+        assert_eq!(rows.len(), 4);
+        assert!(rows.iter().all(|(_, _, r)| r.is_err()));
+    }
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    #[actix_rt::test]
+    async fn applies_valid_rows_and_skips_the_invalid_one() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let admin_id = Uuid::new_v4();
+        let user_id = Uuid::new_v4();
+        let email = format!("bulk-{}@example.com", user_id);
+        for (id, email) in [
+            (admin_id, format!("{}@example.com", admin_id)),
+            (user_id, email.clone()),
+        ] {
+            sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, 'x')")
+                .bind(id)
+                .bind(email)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let csv = format!("email,amount,locked\n{},250,true\nbad@,300,true\n", email);
+        let mut outcomes = Vec::new();
+        for (_, _, parsed) in parse_grant_csv(&csv) {
+            outcomes.push(match parsed {
+                Ok(row) => apply_grant(&pool, &row, admin_id).await,
+                Err(e) => Err(e),
+            });
+        }
+
+        let user = UserRepository::find_by_id(&pool, user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = ANY($1)")
+            .bind(vec![user_id, admin_id])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0], Ok(user_id));
+        assert!(outcomes[1].is_err());
+        assert!(user.lifetime_member);
+        assert!(user.price_locked);
+        assert_eq!(user.locked_price_amount, Some(250));
+    }
+}
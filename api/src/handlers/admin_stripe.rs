@@ -1,7 +1,7 @@
 //! Admin Stripe management handlers
 //!
 //! Endpoints for managing Stripe products, prices, and webhook endpoints.
-//! All handlers require the `AdminUser` extractor.
+//! Reads require the `billing:read` scope and writes `billing:write`.
 
 use actix_web::{web, HttpRequest, HttpResponse};
 use serde::Deserialize;
@@ -9,9 +9,11 @@ use sqlx::PgPool;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::config::Config;
 use crate::errors::AppError;
-use crate::middleware::AdminUser;
+use crate::middleware::RequirePermission;
 use crate::models::stripe::encrypt_secret;
+use crate::models::{scopes, Currency};
 use crate::repositories::StripeConfigRepository;
 use crate::responses::{get_request_id, success, success_no_data};
 use crate::services::{EncryptionKeySet, StripeConfig, StripeService};
@@ -61,7 +63,7 @@ pub struct CreateStripeWebhookRequest {
 /// GET /v1/admin/stripe/products
 pub async fn list_stripe_products(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingRead>,
     stripe: web::Data<Arc<StripeService>>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -72,7 +74,7 @@ pub async fn list_stripe_products(
 /// POST /v1/admin/stripe/products
 pub async fn create_stripe_product(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingWrite>,
     stripe: web::Data<Arc<StripeService>>,
     body: web::Json<CreateStripeProductRequest>,
 ) -> Result<HttpResponse, AppError> {
@@ -90,7 +92,7 @@ pub async fn create_stripe_product(
 /// PUT /v1/admin/stripe/products/{id}
 pub async fn update_stripe_product(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingWrite>,
     stripe: web::Data<Arc<StripeService>>,
     path: web::Path<String>,
     body: web::Json<UpdateStripeProductRequest>,
@@ -112,7 +114,7 @@ pub async fn update_stripe_product(
 /// DELETE /v1/admin/stripe/products/{id}
 pub async fn archive_stripe_product(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingWrite>,
     stripe: web::Data<Arc<StripeService>>,
     path: web::Path<String>,
 ) -> Result<HttpResponse, AppError> {
@@ -129,7 +131,7 @@ pub async fn archive_stripe_product(
 /// GET /v1/admin/stripe/prices
 pub async fn list_stripe_prices(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingRead>,
     stripe: web::Data<Arc<StripeService>>,
     query: web::Query<ListStripePricesQuery>,
 ) -> Result<HttpResponse, AppError> {
@@ -141,16 +143,17 @@ pub async fn list_stripe_prices(
 /// POST /v1/admin/stripe/prices
 pub async fn create_stripe_price(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingWrite>,
     stripe: web::Data<Arc<StripeService>>,
     body: web::Json<CreateStripePriceRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
+    let currency = Currency::parse(&body.currency)?;
     let price = stripe
         .create_price(
             &body.product_id,
             body.unit_amount,
-            &body.currency,
+            &currency,
             &body.interval,
         )
         .await?;
@@ -160,7 +163,7 @@ pub async fn create_stripe_price(
 /// DELETE /v1/admin/stripe/prices/{id}
 pub async fn archive_stripe_price(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingWrite>,
     stripe: web::Data<Arc<StripeService>>,
     path: web::Path<String>,
 ) -> Result<HttpResponse, AppError> {
@@ -177,7 +180,7 @@ pub async fn archive_stripe_price(
 /// GET /v1/admin/stripe/webhooks
 pub async fn list_stripe_webhooks(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingRead>,
     stripe: web::Data<Arc<StripeService>>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -191,10 +194,11 @@ pub async fn list_stripe_webhooks(
 /// to the database (encrypted), then reloads the StripeService.
 pub async fn create_stripe_webhook(
     req: HttpRequest,
-    admin: AdminUser,
+    admin: RequirePermission<scopes::BillingWrite>,
     stripe: web::Data<Arc<StripeService>>,
     stripe_key_set: web::Data<EncryptionKeySet>,
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
     body: web::Json<CreateStripeWebhookRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -220,7 +224,7 @@ pub async fn create_stripe_webhook(
         .await?;
 
         // Hot-reload the StripeService with the new webhook secret
-        match StripeConfig::from_db_model(&updated, &stripe_key_set) {
+        match StripeConfig::from_db_model(&updated, &stripe_key_set, &config) {
             Ok(new_config) => {
                 stripe.reload(new_config);
                 tracing::info!("Stripe service reloaded with new webhook secret");
@@ -237,7 +241,7 @@ pub async fn create_stripe_webhook(
 /// DELETE /v1/admin/stripe/webhooks/{id}
 pub async fn delete_stripe_webhook(
     req: HttpRequest,
-    _admin: AdminUser,
+    _admin: RequirePermission<scopes::BillingWrite>,
     stripe: web::Data<Arc<StripeService>>,
     path: web::Path<String>,
 ) -> Result<HttpResponse, AppError> {
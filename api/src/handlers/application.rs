@@ -2,14 +2,19 @@
 //!
 //! This module contains HTTP handlers for application endpoints.
 
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL};
 use actix_web::{web, HttpRequest, HttpResponse};
 use sqlx::PgPool;
 
 use crate::errors::AppError;
-use crate::middleware::OptionalUser;
-use crate::models::ApplicationResponse;
-use crate::repositories::ApplicationRepository;
+use crate::middleware::{AuthenticatedUser, OptionalUser};
+use crate::models::{
+    AccessibleApplicationResponse, Application, ApplicationResponse, ApplicationStatusResponse,
+    MemberAccessReason,
+};
+use crate::repositories::{ApplicationRepository, UserRepository};
 use crate::responses::{get_request_id, success};
+use crate::services::AccessTokenClaims;
 
 /// GET /v1/applications
 /// List all active applications
@@ -39,6 +44,28 @@ pub async fn list_applications(
     ))
 }
 
+/// GET /v1/applications/status
+/// Maintenance status of each active application, for the public status page
+pub async fn list_application_status(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let statuses: Vec<ApplicationStatusResponse> = ApplicationRepository::list_active(&pool)
+        .await?
+        .into_iter()
+        .map(ApplicationStatusResponse::from)
+        .collect();
+
+    let mut response = success(serde_json::json!({ "applications": statuses }), request_id);
+    response.headers_mut().insert(
+        CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=60"),
+    );
+    Ok(response)
+}
+
 /// GET /v1/applications/{slug}
 /// Get a specific application by slug
 pub async fn get_application(
@@ -64,3 +91,132 @@ pub async fn get_application(
 
     Ok(success(app_response, request_id))
 }
+
+/// GET /v1/users/me/applications
+/// List the applications the current user can open right now
+///
+/// Access is evaluated against the stored user rather than token claims, so a
+/// membership change is reflected before the access token is refreshed.
+pub async fn list_my_applications(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let db_user = UserRepository::find_by_id(&pool, user.0.sub)
+        .await?
+        .ok_or(AppError::not_found("User"))?;
+
+    let reason = AccessTokenClaims::member_access_reason(
+        &db_user.role,
+        db_user.lifetime_member,
+        db_user.trial_ends_at.map(|t| t.timestamp()),
+        &db_user.membership_status,
+    );
+
+    let apps = match reason {
+        Some(_) => ApplicationRepository::list_active(&pool).await?,
+        None => Vec::new(),
+    };
+
+    Ok(success(
+        serde_json::json!({ "applications": accessible_applications(apps, reason) }),
+        request_id,
+    ))
+}
+
+/// Keep active, non-maintenance applications when the user has member access.
+fn accessible_applications(
+    apps: Vec<Application>,
+    reason: Option<MemberAccessReason>,
+) -> Vec<AccessibleApplicationResponse> {
+    let Some(reason) = reason else {
+        return Vec::new();
+    };
+
+    apps.into_iter()
+        .filter(|app| app.is_active && !app.maintenance_mode)
+        .map(|app| AccessibleApplicationResponse {
+            application: ApplicationResponse::from_application(app, true),
+            access_reason: reason,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn app(slug: &str, is_active: bool, maintenance_mode: bool) -> Application {
+        let now = Utc::now();
+        Application {
+            id: uuid::Uuid::new_v4(),
+            name: slug.to_string(),
+            slug: slug.to_string(),
+            display_name: slug.to_string(),
+            description: None,
+            icon_url: None,
+            is_active,
+            maintenance_mode,
+            maintenance_message: None,
+            subdomain: None,
+            container_name: slug.to_string(),
+            health_check_url: None,
+            webhook_url: None,
+            version: None,
+            source_code_url: None,
+            forgejo_owner: None,
+            forgejo_repo: None,
+            pinned_release_tag: None,
+            oci_image_owner: None,
+            oci_image_name: None,
+            pinned_image_tag: None,
+            sort_order: 0,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn catalog() -> Vec<Application> {
+        vec![
+            app("open", true, false),
+            app("down", true, true),
+            app("retired", false, false),
+        ]
+    }
+
+    fn reason_for(status: &str) -> Option<MemberAccessReason> {
+        AccessTokenClaims::member_access_reason("subscriber", false, None, status)
+    }
+
+    #[test]
+    fn active_member_gets_available_apps() {
+        let apps = accessible_applications(catalog(), reason_for("active"));
+
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].application.slug, "open");
+        assert!(apps[0].application.is_accessible);
+        assert_eq!(
+            apps[0].access_reason,
+            MemberAccessReason::ActiveSubscription
+        );
+    }
+
+    #[test]
+    fn grace_period_member_keeps_access_with_reason() {
+        let apps = accessible_applications(catalog(), reason_for("grace_period"));
+
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].access_reason, MemberAccessReason::GracePeriod);
+        let json = serde_json::to_value(&apps[0]).unwrap();
+        assert_eq!(json["access_reason"], "grace_period");
+        assert_eq!(json["slug"], "open");
+    }
+
+    #[test]
+    fn canceled_user_gets_no_apps() {
+        assert!(accessible_applications(catalog(), reason_for("canceled")).is_empty());
+    }
+}
@@ -2,27 +2,34 @@
 //!
 //! This module contains HTTP handlers for authentication endpoints.
 
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse, HttpResponseBuilder};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::sync::Arc;
 
+use crate::config::SignupPolicyConfig;
 use crate::errors::AppError;
 use crate::middleware::{
-    extract_client_ip, extract_device_info, AuthCookies, AuthenticatedUser, OptionalUser,
+    extract_client_ip, extract_device_id, extract_device_info, record_rate_limit_usage,
+    AuthCookies, AuthenticatedUser, JsonOrForm, OptionalUser,
 };
 use crate::models::{CreateUser, RateLimitConfig, UserResponse, UserRole};
 use crate::repositories::{RateLimitRepository, UserRepository};
-use crate::responses::{get_request_id, success};
-use crate::services::{AcceptInviteResult, AuthService, LoginResult, PasswordService};
+use crate::responses::{created, get_request_id, success};
+use crate::services::{
+    AcceptInviteResult, AuthService, AuthTokens, CaptchaService, LoginResult, MagicLinkResult,
+    PasswordService,
+};
 
 /// Check rate limit and return RateLimited error if exceeded
 async fn check_rate_limit(
+    req: &HttpRequest,
     pool: &PgPool,
     key: &str,
     config: &RateLimitConfig,
 ) -> Result<(), AppError> {
-    let (_count, exceeded) = RateLimitRepository::check_and_increment(pool, key, config).await?;
+    let (count, exceeded) = RateLimitRepository::check_and_increment(pool, key, config).await?;
+    record_rate_limit_usage(req, config, count);
     if exceeded {
         let retry_after = RateLimitRepository::get_retry_after(pool, key, config).await?;
         return Err(AppError::RateLimited { retry_after });
@@ -39,6 +46,16 @@ pub struct RegisterRequest {
     pub stripe_customer_id: Option<String>,
     /// Payment method ID returned by stripe.confirmSetup() on the frontend.
     pub payment_method_id: Option<String>,
+    /// Turnstile/hCaptcha token; required when captcha is enabled
+    pub captcha_token: Option<String>,
+    /// Log the new user in (set auth cookies) in the same request. Clients
+    /// that verify the email first send `false` and log in afterwards.
+    #[serde(default = "default_auto_login")]
+    pub auto_login: bool,
+}
+
+fn default_auto_login() -> bool {
+    true
 }
 
 /// Request body for login
@@ -50,10 +67,24 @@ pub struct LoginRequest {
     pub remember: bool,
 }
 
+/// Request body for the email availability check
+#[derive(Debug, Deserialize)]
+pub struct EmailAvailableRequest {
+    pub email: String,
+}
+
+/// Response for the email availability check
+#[derive(Debug, Serialize)]
+pub struct EmailAvailableResponse {
+    pub available: bool,
+}
+
 /// Request body for magic link request
 #[derive(Debug, Deserialize)]
 pub struct MagicLinkRequest {
     pub email: String,
+    /// Turnstile/hCaptcha token; required when captcha is enabled
+    pub captcha_token: Option<String>,
 }
 
 /// Request body for magic link verification
@@ -75,6 +106,14 @@ pub struct PasswordResetConfirmRequest {
     pub new_password: String,
 }
 
+/// Request body for setting a first password on a passwordless account
+#[derive(Debug, Deserialize)]
+pub struct SetPasswordRequest {
+    /// Token from the email sent by `POST /v1/auth/set-password/request`
+    pub token: String,
+    pub new_password: String,
+}
+
 /// Request body for initial admin setup
 #[derive(Debug, Deserialize)]
 pub struct SetupRequest {
@@ -97,15 +136,22 @@ pub struct AuthResponse {
     pub expires_in: i64,
 }
 
+/// Response for registration with `auto_login: false`
+#[derive(Debug, Serialize)]
+pub struct RegisteredResponse {
+    pub user: UserResponse,
+}
+
 /// POST /v1/auth/register
-/// Register a new user and log them in
+/// Register a new user and, unless `auto_login` is false, log them in
 pub async fn register(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     auth_service: web::Data<Arc<AuthService>>,
     email_service: web::Data<Arc<crate::services::EmailService>>,
-    body: web::Json<RegisterRequest>,
+    body: JsonOrForm<RegisterRequest>,
     config: web::Data<crate::config::Config>,
+    captcha: web::Data<Arc<CaptchaService>>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
     let ip_address = extract_client_ip(&req);
@@ -113,12 +159,20 @@ pub async fn register(
 
     // Rate limit by IP address
     let ip_key = ip_address.map(|ip| ip.to_string()).unwrap_or_default();
-    check_rate_limit(&pool, &ip_key, &RateLimitConfig::REGISTRATION).await?;
+    check_rate_limit(&req, &pool, &ip_key, &RateLimitConfig::REGISTRATION).await?;
+
+    captcha
+        .verify(
+            body.captcha_token.as_deref(),
+            ip_address.map(|ip| ip.to_string()).as_deref(),
+        )
+        .await?;
 
     // Validate email format
     crate::validation::validate_email(&body.email)?;
+    crate::validation::validate_signup_email(&body.email, &config.signup_policy)?;
 
-    auth_service
+    let registered = auth_service
         .register(
             body.email.clone(),
             body.password.clone(),
@@ -126,6 +180,32 @@ pub async fn register(
         )
         .await?;
 
+    // Store Stripe customer and payment method if card authorization was completed
+    if let (Some(customer_id), Some(payment_method_id)) =
+        (&body.stripe_customer_id, &body.payment_method_id)
+    {
+        UserRepository::update_stripe_registration_info(
+            &pool,
+            registered.id,
+            customer_id,
+            payment_method_id,
+        )
+        .await?;
+    }
+
+    // Send welcome email (in background, don't wait)
+    let email = body.email.clone();
+    let email_svc = email_service.get_ref().clone();
+    tokio::spawn(async move {
+        if let Err(e) = email_svc.send_account_created(&email).await {
+            tracing::error!(error = %e, email = %email, "Failed to send account created email");
+        }
+    });
+
+    if !body.auto_login {
+        return Ok(created(RegisteredResponse { user: registered }, request_id));
+    }
+
     // Generate tokens so the user is logged in immediately
     // (newly registered users never have 2FA, so this always returns Success)
     let result = auth_service
@@ -133,7 +213,9 @@ pub async fn register(
             body.email.clone(),
             body.password.clone(),
             device_info,
+            extract_device_id(&req),
             ip_address,
+            false,
         )
         .await?;
 
@@ -147,31 +229,9 @@ pub async fn register(
         }
     };
 
-    // Store Stripe customer and payment method if card authorization was completed
-    if let (Some(customer_id), Some(payment_method_id)) =
-        (&body.stripe_customer_id, &body.payment_method_id)
-    {
-        UserRepository::update_stripe_registration_info(
-            &pool,
-            user.id,
-            customer_id,
-            payment_method_id,
-        )
-        .await?;
-    }
-
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
-    // Send welcome email (in background, don't wait)
-    let email = body.email.clone();
-    let email_svc = email_service.get_ref().clone();
-    tokio::spawn(async move {
-        if let Err(e) = email_svc.send_account_created(&email).await {
-            tracing::error!(error = %e, email = %email, "Failed to send account created email");
-        }
-    });
-
     let response = AuthResponse {
         user,
         expires_in: tokens.expires_in,
@@ -190,7 +250,7 @@ pub async fn register(
         .cookie(AuthCookies::refresh_token(
             &tokens.refresh_token,
             secure,
-            false,
+            tokens.remember,
             cookie_domain,
         ))
         .json(crate::responses::ApiResponse {
@@ -206,22 +266,31 @@ pub async fn login(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     auth_service: web::Data<Arc<AuthService>>,
-    body: web::Json<LoginRequest>,
+    body: JsonOrForm<LoginRequest>,
     config: web::Data<crate::config::Config>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
     let ip_address = extract_client_ip(&req);
     let device_info = extract_device_info(&req);
+    let device_id = extract_device_id(&req);
 
     // Rate limit by email
-    check_rate_limit(&pool, &body.email.to_lowercase(), &RateLimitConfig::LOGIN).await?;
+    check_rate_limit(
+        &req,
+        &pool,
+        &body.email.to_lowercase(),
+        &RateLimitConfig::LOGIN,
+    )
+    .await?;
 
     let result = auth_service
         .login(
             body.email.clone(),
             body.password.clone(),
             device_info,
+            device_id,
             ip_address,
+            body.remember,
         )
         .await?;
 
@@ -231,7 +300,7 @@ pub async fn login(
             request_id,
         )),
         LoginResult::Success(tokens, user) => {
-            let secure = config.is_production();
+            let secure = config.cookie_secure;
             let cookie_domain = config.cookie_domain.as_deref();
 
             let response = AuthResponse {
@@ -253,7 +322,7 @@ pub async fn login(
                 .cookie(AuthCookies::refresh_token(
                     &tokens.refresh_token,
                     secure,
-                    body.remember,
+                    tokens.remember,
                     cookie_domain,
                 ))
                 .json(crate::responses::ApiResponse {
@@ -272,19 +341,28 @@ pub async fn request_magic_link(
     pool: web::Data<PgPool>,
     auth_service: web::Data<Arc<AuthService>>,
     email_service: web::Data<Arc<crate::services::EmailService>>,
-    body: web::Json<MagicLinkRequest>,
+    body: JsonOrForm<MagicLinkRequest>,
+    captcha: web::Data<Arc<CaptchaService>>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
     let ip_address = extract_client_ip(&req);
 
     // Rate limit by email
     check_rate_limit(
+        &req,
         &pool,
         &body.email.to_lowercase(),
         &RateLimitConfig::MAGIC_LINK,
     )
     .await?;
 
+    captcha
+        .verify(
+            body.captcha_token.as_deref(),
+            ip_address.map(|ip| ip.to_string()).as_deref(),
+        )
+        .await?;
+
     // Validate email format
     crate::validation::validate_email(&body.email)?;
 
@@ -312,6 +390,46 @@ pub async fn request_magic_link(
     )
 }
 
+/// POST /v1/auth/email-available
+/// Tell the signup form whether an email is still free to register.
+/// Addresses the signup policy rejects fail with the same error as register.
+/// Rate limited per IP and padded with a random delay so it can't be used
+/// to enumerate accounts quickly or by response timing.
+pub async fn email_available(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    signup_policy: web::Data<SignupPolicyConfig>,
+    body: JsonOrForm<EmailAvailableRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let started = std::time::Instant::now();
+
+    let ip_key = extract_client_ip(&req)
+        .map(|ip| ip.to_string())
+        .unwrap_or_default();
+    check_rate_limit(&req, &pool, &ip_key, &RateLimitConfig::EMAIL_AVAILABILITY).await?;
+
+    crate::validation::validate_email(&body.email)?;
+    crate::validation::validate_signup_email(&body.email, &signup_policy)?;
+    let available = UserRepository::find_by_email(&pool, body.email.trim())
+        .await?
+        .is_none();
+
+    // Pad to a random total so taken and free emails take the same time
+    let target = availability_delay();
+    if let Some(remaining) = target.checked_sub(started.elapsed()) {
+        tokio::time::sleep(remaining).await;
+    }
+
+    Ok(success(EmailAvailableResponse { available }, request_id))
+}
+
+/// Random response time for the availability check
+fn availability_delay() -> std::time::Duration {
+    use rand::Rng;
+    std::time::Duration::from_millis(rand::thread_rng().gen_range(100..=300))
+}
+
 /// POST /v1/auth/magic-link/verify
 /// Verify a magic link and login
 pub async fn verify_magic_link(
@@ -319,7 +437,7 @@ pub async fn verify_magic_link(
     pool: web::Data<PgPool>,
     auth_service: web::Data<Arc<AuthService>>,
     email_service: web::Data<Arc<crate::services::EmailService>>,
-    body: web::Json<VerifyMagicLinkRequest>,
+    body: JsonOrForm<VerifyMagicLinkRequest>,
     config: web::Data<crate::config::Config>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -328,14 +446,19 @@ pub async fn verify_magic_link(
 
     // Rate limit by IP address
     let ip_key = ip_address.map(|ip| ip.to_string()).unwrap_or_default();
-    check_rate_limit(&pool, &ip_key, &RateLimitConfig::LOGIN).await?;
+    check_rate_limit(&req, &pool, &ip_key, &RateLimitConfig::LOGIN).await?;
 
     let result = auth_service
-        .verify_magic_link(body.token.clone(), device_info, ip_address)
+        .verify_magic_link(
+            body.token.clone(),
+            device_info,
+            extract_device_id(&req),
+            ip_address,
+        )
         .await?;
 
     match result {
-        crate::services::MagicLinkResult::TwoFactorRequired {
+        MagicLinkResult::TwoFactorRequired {
             challenge_token,
             is_new_user,
         } => {
@@ -351,7 +474,7 @@ pub async fn verify_magic_link(
                 request_id,
             ))
         }
-        crate::services::MagicLinkResult::Success(tokens, user, is_new_user) => {
+        MagicLinkResult::Success(tokens, user, is_new_user) => {
             // Send account created email for new users (in background, don't wait)
             if is_new_user {
                 let email = user.email.clone();
@@ -363,7 +486,7 @@ pub async fn verify_magic_link(
                 });
             }
 
-            let secure = config.is_production();
+            let secure = config.cookie_secure;
             let cookie_domain = config.cookie_domain.as_deref();
 
             let response = AuthResponse {
@@ -384,7 +507,7 @@ pub async fn verify_magic_link(
                 .cookie(AuthCookies::refresh_token(
                     &tokens.refresh_token,
                     secure,
-                    true,
+                    tokens.remember,
                     cookie_domain,
                 ))
                 .json(crate::responses::ApiResponse {
@@ -396,6 +519,135 @@ pub async fn verify_magic_link(
     }
 }
 
+/// GET /v1/auth/magic-link/verify?token=...
+/// Email click-through variant of magic link verification. Sets auth cookies and
+/// redirects (302) to the configured frontend URL instead of returning JSON.
+/// Failures redirect back to the frontend magic link page with an error code.
+pub async fn verify_magic_link_redirect(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    auth_service: web::Data<Arc<AuthService>>,
+    email_service: web::Data<Arc<crate::services::EmailService>>,
+    query: web::Query<VerifyMagicLinkRequest>,
+    config: web::Data<crate::config::Config>,
+) -> Result<HttpResponse, AppError> {
+    let ip_address = extract_client_ip(&req);
+    let device_info = extract_device_info(&req);
+
+    let secure = config.cookie_secure;
+    let cookie_domain = config.cookie_domain.as_deref();
+    let frontend = config.cors_origin.trim_end_matches('/');
+
+    // Rate limit by IP address. This is a browser navigation, so a refusal
+    // lands on the magic link page like any other failure.
+    let ip_key = ip_address.map(|ip| ip.to_string()).unwrap_or_default();
+    if let Err(e) = check_rate_limit(&req, &pool, &ip_key, &RateLimitConfig::LOGIN).await {
+        tracing::info!(error = %e, "magic_link_redirect: rate limited");
+        let location = format!("{frontend}/magic-link?error={}", e.error_code());
+        return Ok(magic_link_redirect(&location, None, secure, cookie_domain));
+    }
+
+    let result = match auth_service
+        .verify_magic_link(
+            query.token.clone(),
+            device_info,
+            extract_device_id(&req),
+            ip_address,
+        )
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::info!(error = %e, "magic_link_redirect: verification failed");
+            let location = format!("{frontend}/magic-link?error={}", e.error_code());
+            return Ok(magic_link_redirect(&location, None, secure, cookie_domain));
+        }
+    };
+
+    match result {
+        MagicLinkResult::TwoFactorRequired {
+            challenge_token, ..
+        } => {
+            // The challenge travels in an httpOnly cookie rather than the URL,
+            // where history and Referer could leak it
+            let location = format!("{frontend}/login/2fa?challenge=cookie");
+            Ok(magic_link_challenge_redirect(
+                &location,
+                &challenge_token,
+                secure,
+            ))
+        }
+        MagicLinkResult::Success(tokens, user, is_new_user) => {
+            // Send account created email for new users (in background, don't wait)
+            if is_new_user {
+                let email = user.email.clone();
+                let email_svc = email_service.get_ref().clone();
+                tokio::spawn(async move {
+                    if let Err(e) = email_svc.send_account_created(&email).await {
+                        tracing::error!(error = %e, email = %email, "Failed to send account created email");
+                    }
+                });
+            }
+
+            Ok(magic_link_redirect(
+                &config.magic_link_redirect_url,
+                Some(&tokens),
+                secure,
+                cookie_domain,
+            ))
+        }
+    }
+}
+
+/// Build the 302 for a magic link click-through, optionally carrying auth cookies.
+/// The request URL contains the single-use token, so `Referrer-Policy: no-referrer`
+/// keeps it from being sent to the landing page or anything it loads.
+fn magic_link_redirect(
+    location: &str,
+    tokens: Option<&AuthTokens>,
+    secure: bool,
+    cookie_domain: Option<&str>,
+) -> HttpResponse {
+    let mut resp = HttpResponse::Found();
+    if let Some(tokens) = tokens {
+        for cookie in AuthCookies::clear_stale(secure) {
+            resp.cookie(cookie);
+        }
+        resp.cookie(AuthCookies::access_token(
+            &tokens.access_token,
+            secure,
+            cookie_domain,
+        ))
+        .cookie(AuthCookies::refresh_token(
+            &tokens.refresh_token,
+            secure,
+            tokens.remember,
+            cookie_domain,
+        ));
+    }
+    finish_magic_link_redirect(resp, location)
+}
+
+/// Build the 302 for a magic link click-through that still needs a 2FA code.
+/// The challenge token is handed to the 2FA verify endpoint in a short-lived
+/// httpOnly cookie.
+fn magic_link_challenge_redirect(
+    location: &str,
+    challenge_token: &str,
+    secure: bool,
+) -> HttpResponse {
+    let mut resp = HttpResponse::Found();
+    resp.cookie(AuthCookies::two_factor_challenge(challenge_token, secure));
+    finish_magic_link_redirect(resp, location)
+}
+
+fn finish_magic_link_redirect(mut resp: HttpResponseBuilder, location: &str) -> HttpResponse {
+    resp.insert_header(("Location", location))
+        .insert_header(("Referrer-Policy", "no-referrer"))
+        .insert_header(("Cache-Control", "no-store"))
+        .finish()
+}
+
 /// Request body for accepting an admin invite
 #[derive(Debug, Deserialize)]
 pub struct AcceptInviteRequest {
@@ -409,7 +661,7 @@ pub async fn accept_admin_invite(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     auth_service: web::Data<Arc<AuthService>>,
-    body: web::Json<AcceptInviteRequest>,
+    body: JsonOrForm<AcceptInviteRequest>,
     config: web::Data<crate::config::Config>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -418,7 +670,7 @@ pub async fn accept_admin_invite(
 
     // Rate limit by IP address
     let ip_key = ip_address.map(|ip| ip.to_string()).unwrap_or_default();
-    check_rate_limit(&pool, &ip_key, &RateLimitConfig::LOGIN).await?;
+    check_rate_limit(&req, &pool, &ip_key, &RateLimitConfig::LOGIN).await?;
 
     let result = auth_service
         .accept_admin_invite(
@@ -435,7 +687,7 @@ pub async fn accept_admin_invite(
             request_id,
         )),
         AcceptInviteResult::Success(tokens, user) => {
-            let secure = config.is_production();
+            let secure = config.cookie_secure;
             let cookie_domain = config.cookie_domain.as_deref();
 
             let response = AuthResponse {
@@ -456,7 +708,7 @@ pub async fn accept_admin_invite(
                 .cookie(AuthCookies::refresh_token(
                     &tokens.refresh_token,
                     secure,
-                    true,
+                    tokens.remember,
                     cookie_domain,
                 ))
                 .json(crate::responses::ApiResponse {
@@ -514,7 +766,7 @@ pub async fn refresh_token(
         }
     };
 
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     let mut resp = HttpResponse::Ok();
@@ -530,7 +782,7 @@ pub async fn refresh_token(
         .cookie(AuthCookies::refresh_token(
             &tokens.refresh_token,
             secure,
-            true,
+            tokens.remember,
             cookie_domain,
         ))
         .json(crate::responses::ApiResponse {
@@ -603,7 +855,7 @@ pub async fn logout(
         });
     }
 
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     // Clear cookies
@@ -633,34 +885,8 @@ pub async fn logout_redirect(
 ) -> Result<HttpResponse, AppError> {
     let target_url = &query.url;
 
-    // Validate the redirect URL is on an allowed domain
-    let allowed = match url::Url::parse(target_url) {
-        Ok(parsed) => {
-            if let Some(host) = parsed.host_str() {
-                let cors_domain = url::Url::parse(&config.cors_origin)
-                    .ok()
-                    .and_then(|u| u.host_str().map(|h| h.to_string()));
-
-                let base_domain = config
-                    .cookie_domain
-                    .as_deref()
-                    .map(|d| d.trim_start_matches('.'))
-                    .or(cors_domain.as_deref());
-
-                match base_domain {
-                    Some(domain) => host == domain || host.ends_with(&format!(".{domain}")),
-                    None => false,
-                }
-            } else {
-                false
-            }
-        }
-        Err(_) => false,
-    };
-
-    if !allowed {
-        return Err(AppError::validation("url", "Invalid redirect URL"));
-    }
+    // Only redirect to hosts on the allowlist
+    crate::redirect::ensure_allowed(target_url, &config.redirect.allowed_hosts, "url")?;
 
     // If authenticated, revoke the refresh token
     if let Some(user) = &optional_user.0 {
@@ -673,7 +899,7 @@ pub async fn logout_redirect(
         }
     }
 
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
     let clear_cookies = AuthCookies::clear(secure, cookie_domain);
 
@@ -707,7 +933,7 @@ pub async fn logout_all(
 
     auth_service.logout_all(user.0.sub, ip_address).await?;
 
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     let mut response = HttpResponse::Ok().json(crate::responses::ApiResponse::<()> {
@@ -730,13 +956,14 @@ pub async fn request_password_reset(
     pool: web::Data<PgPool>,
     auth_service: web::Data<Arc<AuthService>>,
     email_service: web::Data<Arc<crate::services::EmailService>>,
-    body: web::Json<PasswordResetRequest>,
+    body: JsonOrForm<PasswordResetRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
     let ip_address = extract_client_ip(&req);
 
     // Rate limit by email
     check_rate_limit(
+        &req,
         &pool,
         &body.email.to_lowercase(),
         &RateLimitConfig::PASSWORD_RESET,
@@ -778,14 +1005,14 @@ pub async fn confirm_password_reset(
     pool: web::Data<PgPool>,
     auth_service: web::Data<Arc<AuthService>>,
     email_service: web::Data<Arc<crate::services::EmailService>>,
-    body: web::Json<PasswordResetConfirmRequest>,
+    body: JsonOrForm<PasswordResetConfirmRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
     let ip_address = extract_client_ip(&req);
 
     // Rate limit by IP address
     let ip_key = ip_address.map(|ip| ip.to_string()).unwrap_or_default();
-    check_rate_limit(&pool, &ip_key, &RateLimitConfig::LOGIN).await?;
+    check_rate_limit(&req, &pool, &ip_key, &RateLimitConfig::LOGIN).await?;
 
     let email = auth_service
         .complete_password_reset(body.token.clone(), body.new_password.clone(), ip_address)
@@ -817,6 +1044,81 @@ pub async fn verify_password_reset_token(
     Ok(success(serde_json::json!({ "valid": true }), request_id))
 }
 
+/// POST /v1/auth/set-password/request
+/// Email a set-password link to a passwordless (magic link / OAuth) account
+pub async fn request_set_password(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<PgPool>,
+    auth_service: web::Data<Arc<AuthService>>,
+    email_service: web::Data<Arc<crate::services::EmailService>>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let ip_address = extract_client_ip(&req);
+
+    check_rate_limit(
+        &req,
+        &pool,
+        &user.0.sub.to_string(),
+        &RateLimitConfig::PASSWORD_RESET,
+    )
+    .await?;
+
+    let (email, token) = auth_service
+        .request_set_password(user.0.sub, ip_address)
+        .await?;
+
+    let email_svc = email_service.get_ref().clone();
+    tokio::spawn(async move {
+        if let Err(e) = email_svc.send_set_password(&email, &token).await {
+            tracing::error!(error = %e, email = %email, "Failed to send set password email");
+        }
+    });
+
+    Ok(
+        HttpResponse::Accepted().json(crate::responses::ApiResponse::<()> {
+            success: true,
+            data: None,
+            meta: crate::responses::ResponseMeta::new(request_id),
+        }),
+    )
+}
+
+/// POST /v1/auth/set-password
+/// Set a first password on a passwordless account with the emailed token
+pub async fn set_password(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<PgPool>,
+    auth_service: web::Data<Arc<AuthService>>,
+    email_service: web::Data<Arc<crate::services::EmailService>>,
+    body: JsonOrForm<SetPasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let ip_address = extract_client_ip(&req);
+
+    let ip_key = ip_address.map(|ip| ip.to_string()).unwrap_or_default();
+    check_rate_limit(&req, &pool, &ip_key, &RateLimitConfig::LOGIN).await?;
+
+    let email = auth_service
+        .set_initial_password(
+            user.0.sub,
+            body.token.clone(),
+            body.new_password.clone(),
+            ip_address,
+        )
+        .await?;
+
+    let email_svc = email_service.get_ref().clone();
+    tokio::spawn(async move {
+        if let Err(e) = email_svc.send_password_changed(&email).await {
+            tracing::error!(error = %e, email = %email, "Failed to send password changed email");
+        }
+    });
+
+    Ok(crate::responses::success_no_data(request_id))
+}
+
 /// Query params for redirect endpoint
 #[derive(Debug, Deserialize)]
 pub struct RedirectQuery {
@@ -846,38 +1148,8 @@ pub async fn auth_redirect(
         "auth_redirect: request received"
     );
 
-    // Validate the redirect URL is on an allowed domain
-    let allowed = match url::Url::parse(target_url) {
-        Ok(parsed) => {
-            if let Some(host) = parsed.host_str() {
-                // Extract base domain from CORS origin for comparison
-                let cors_domain = url::Url::parse(&config.cors_origin)
-                    .ok()
-                    .and_then(|u| u.host_str().map(|h| h.to_string()));
-
-                // Also allow cookie_domain subdomains
-                let base_domain = config
-                    .cookie_domain
-                    .as_deref()
-                    .map(|d| d.trim_start_matches('.'))
-                    .or(cors_domain.as_deref());
-
-                match base_domain {
-                    Some(domain) => host == domain || host.ends_with(&format!(".{domain}")),
-                    None => false,
-                }
-            } else {
-                false
-            }
-        }
-        Err(_) => false,
-    };
-
-    tracing::info!(allowed = allowed, "auth_redirect: URL validation result");
-
-    if !allowed {
-        return Err(AppError::validation("url", "Invalid redirect URL"));
-    }
+    // Only redirect to hosts on the allowlist
+    crate::redirect::ensure_allowed(target_url, &config.redirect.allowed_hosts, "url")?;
 
     let login_url = format!(
         "{}/login?redirect={}&checked=1",
@@ -907,7 +1179,7 @@ pub async fn auth_redirect(
         {
             Ok(tokens) => {
                 tracing::info!(location = %target_url, "auth_redirect: refresh succeeded, redirecting to target");
-                let secure = config.is_production();
+                let secure = config.cookie_secure;
                 let cookie_domain = config.cookie_domain.as_deref();
 
                 let mut resp = HttpResponse::Found();
@@ -923,7 +1195,7 @@ pub async fn auth_redirect(
                     .cookie(AuthCookies::refresh_token(
                         &tokens.refresh_token,
                         secure,
-                        true,
+                        tokens.remember,
                         cookie_domain,
                     ))
                     .insert_header(("Location", target_url.as_str()))
@@ -972,7 +1244,8 @@ pub async fn setup_admin(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     auth_service: web::Data<Arc<AuthService>>,
-    body: web::Json<SetupRequest>,
+    password_service: web::Data<Arc<PasswordService>>,
+    body: JsonOrForm<SetupRequest>,
     config: web::Data<crate::config::Config>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -989,7 +1262,6 @@ pub async fn setup_admin(
     crate::validation::validate_email(&body.email)?;
 
     // Validate and hash password
-    let password_service = PasswordService::new();
     password_service.validate_strength(&body.password)?;
     password_service.validate_not_contains_email(&body.password, &body.email)?;
     let password_hash = password_service.hash(&body.password)?;
@@ -1013,7 +1285,9 @@ pub async fn setup_admin(
             body.email.clone(),
             body.password.clone(),
             device_info,
+            extract_device_id(&req),
             ip_address,
+            false,
         )
         .await?;
 
@@ -1024,7 +1298,7 @@ pub async fn setup_admin(
         }
     };
 
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     let response = AuthResponse {
@@ -1045,7 +1319,7 @@ pub async fn setup_admin(
         .cookie(AuthCookies::refresh_token(
             &tokens.refresh_token,
             secure,
-            false,
+            tokens.remember,
             cookie_domain,
         ))
         .json(crate::responses::ApiResponse {
@@ -1054,3 +1328,289 @@ pub async fn setup_admin(
             meta: crate::responses::ResponseMeta::new(request_id),
         }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_tokens() -> AuthTokens {
+        AuthTokens {
+            access_token: "access-abc".to_string(),
+            refresh_token: "refresh-xyz".to_string(),
+            expires_in: 900,
+            remember: true,
+        }
+    }
+
+    fn set_cookies(resp: &HttpResponse) -> Vec<actix_web::cookie::Cookie<'_>> {
+        resp.cookies().collect()
+    }
+
+    #[test]
+    fn magic_link_redirect_sets_cookies_and_location() {
+        let tokens = test_tokens();
+        let resp = magic_link_redirect(
+            "https://app.example.com/dashboard",
+            Some(&tokens),
+            true,
+            Some(".example.com"),
+        );
+
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FOUND);
+        assert_eq!(
+            resp.headers().get("location").unwrap(),
+            "https://app.example.com/dashboard"
+        );
+
+        let cookies = set_cookies(&resp);
+        let access = cookies
+            .iter()
+            .find(|c| c.name() == "access_token" && c.value() == "access-abc")
+            .expect("access_token cookie");
+        // The cookie crate reports the domain without its leading dot
+        assert_eq!(access.domain(), Some("example.com"));
+        assert!(access.secure().unwrap_or(false));
+        assert!(access.http_only().unwrap_or(false));
+        let refresh = cookies
+            .iter()
+            .find(|c| c.name() == "refresh_token" && c.value() == "refresh-xyz")
+            .expect("refresh_token cookie");
+        assert_eq!(
+            refresh.max_age(),
+            Some(actix_web::cookie::time::Duration::days(30))
+        );
+    }
+
+    #[test]
+    fn magic_link_redirect_sets_no_referrer() {
+        let tokens = test_tokens();
+        let resp = magic_link_redirect("/dashboard", Some(&tokens), false, None);
+
+        assert_eq!(
+            resp.headers().get("referrer-policy").unwrap(),
+            "no-referrer"
+        );
+        assert_eq!(resp.headers().get("cache-control").unwrap(), "no-store");
+    }
+
+    #[test]
+    fn magic_link_redirect_without_tokens_sets_no_cookies() {
+        let resp = magic_link_redirect("/magic-link?error=INVALID_CREDENTIALS", None, false, None);
+
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FOUND);
+        assert!(set_cookies(&resp).is_empty());
+        assert_eq!(
+            resp.headers().get("referrer-policy").unwrap(),
+            "no-referrer"
+        );
+    }
+
+    #[test]
+    fn magic_link_challenge_redirect_keeps_the_token_out_of_the_url() {
+        let resp = magic_link_challenge_redirect(
+            "https://app.example.com/login/2fa?challenge=cookie",
+            "challenge-123",
+            true,
+        );
+
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FOUND);
+        let location = resp.headers().get("location").unwrap().to_str().unwrap();
+        assert!(!location.contains("challenge-123"));
+        assert_eq!(
+            resp.headers().get("referrer-policy").unwrap(),
+            "no-referrer"
+        );
+
+        let cookies = set_cookies(&resp);
+        let challenge = cookies
+            .iter()
+            .find(|c| c.name() == crate::middleware::TWO_FACTOR_CHALLENGE_COOKIE)
+            .expect("challenge cookie");
+        assert_eq!(challenge.value(), "challenge-123");
+        assert_eq!(challenge.path(), Some("/v1/auth/2fa"));
+        assert!(challenge.http_only().unwrap_or(false));
+        assert!(challenge.secure().unwrap_or(false));
+        assert!(!cookies.iter().any(|c| c.name() == "access_token"));
+    }
+
+    #[test]
+    fn availability_delay_stays_in_range() {
+        for _ in 0..50 {
+            let delay = availability_delay();
+            assert!(delay.as_millis() >= 100 && delay.as_millis() <= 300);
+        }
+    }
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    fn availability_request(ip: &str, email: &str) -> actix_web::test::TestRequest {
+        actix_web::test::TestRequest::post()
+            .uri("/email-available")
+            .insert_header(("X-Forwarded-For", ip))
+            .set_json(serde_json::json!({ "email": email }))
+    }
+
+    async fn availability(res: actix_web::dev::ServiceResponse) -> (u16, Option<bool>) {
+        let status = res.status().as_u16();
+        let body: serde_json::Value = actix_web::test::read_body_json(res).await;
+        (status, body["data"]["available"].as_bool())
+    }
+
+    #[actix_rt::test]
+    async fn email_available_reports_taken_and_is_rate_limited() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let user_id = uuid::Uuid::new_v4();
+        let taken = format!("taken-{}@example.com", user_id);
+        sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, 'x')")
+            .bind(user_id)
+            .bind(&taken)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let policy = SignupPolicyConfig {
+            reserved_emails: Vec::new(),
+            denied_domains: vec!["denied.example".to_string()],
+        };
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(policy))
+                .route("/email-available", web::post().to(email_available)),
+        )
+        .await;
+        let ip = std::net::Ipv6Addr::from(uuid::Uuid::new_v4().as_u128()).to_string();
+
+        let free = format!("free-{}@example.com", uuid::Uuid::new_v4());
+        let (app, ip_key) = (&app, ip.as_str());
+        let call = move |email: &str| {
+            actix_web::test::call_service(app, availability_request(ip_key, email).to_request())
+        };
+        let taken_result = availability(call(&taken.to_uppercase()).await).await;
+        let free_result = availability(call(&free).await).await;
+        let denied_result = availability(call("new@denied.example").await).await;
+
+        let limit = RateLimitConfig::EMAIL_AVAILABILITY.max_requests;
+        let mut statuses = Vec::new();
+        for _ in 3..=limit {
+            statuses.push(call(&free).await.status().as_u16());
+        }
+        let blocked = call(&free).await.status().as_u16();
+
+        RateLimitRepository::reset(&pool, &ip, "email_availability")
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(taken_result, (200, Some(false)));
+        assert_eq!(free_result, (200, Some(true)));
+        assert_eq!(denied_result, (400, None));
+        assert!(statuses.iter().all(|s| *s == 200));
+        assert_eq!(blocked, 429);
+    }
+
+    /// Register a fresh user through the handler, returning the status, the
+    /// auth cookies set and the response `data`
+    async fn register_via_handler(
+        pool: &PgPool,
+        auto_login: Option<bool>,
+    ) -> (u16, Vec<String>, serde_json::Value) {
+        // Development mode avoids requiring production-only keys
+        std::env::set_var("ENVIRONMENT", "development");
+        let config = crate::config::Config::from_env().unwrap();
+        let jwt = crate::services::JwtService::new(crate::services::JwtConfig::from_secret(
+            "a-very-long-secret-key-for-tests-12345",
+            "a8n",
+        ));
+        let auth = AuthService::new(
+            pool.clone(),
+            jwt,
+            Arc::new(std::sync::RwLock::new(config.tier.clone())),
+        );
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(Arc::new(auth)))
+                .app_data(web::Data::new(Arc::new(
+                    crate::services::EmailService::new_dev(),
+                )))
+                .app_data(web::Data::new(Arc::new(CaptchaService::new(
+                    config.captcha.clone(),
+                ))))
+                .app_data(web::Data::new(config))
+                .route("/register", web::post().to(register)),
+        )
+        .await;
+
+        let mut body = serde_json::json!({
+            "email": format!("register-{}@example.com", uuid::Uuid::new_v4()),
+            "password": "Correct-Horse-Battery-9!",
+        });
+        if let Some(auto_login) = auto_login {
+            body["auto_login"] = auto_login.into();
+        }
+        let ip = std::net::Ipv6Addr::from(uuid::Uuid::new_v4().as_u128()).to_string();
+        let req = actix_web::test::TestRequest::post()
+            .uri("/register")
+            .insert_header(("X-Forwarded-For", ip.as_str()))
+            .set_json(&body)
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+
+        let status = res.status().as_u16();
+        let cookies = res
+            .response()
+            .cookies()
+            .filter(|c| !c.value().is_empty())
+            .map(|c| c.name().to_string())
+            .collect();
+        let json: serde_json::Value = actix_web::test::read_body_json(res).await;
+
+        RateLimitRepository::reset(pool, &ip, "registration")
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE email = $1")
+            .bind(body["email"].as_str().unwrap())
+            .execute(pool)
+            .await
+            .unwrap();
+
+        (status, cookies, json["data"].clone())
+    }
+
+    #[actix_rt::test]
+    async fn register_logs_in_by_default() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (status, cookies, data) = register_via_handler(&pool, None).await;
+
+        assert_eq!(status, 201);
+        assert!(cookies.contains(&"access_token".to_string()));
+        assert!(cookies.contains(&"refresh_token".to_string()));
+        assert_eq!(data["expires_in"], 900);
+        assert!(data["user"]["email"].is_string());
+    }
+
+    #[actix_rt::test]
+    async fn register_without_auto_login_issues_no_tokens() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (status, cookies, data) = register_via_handler(&pool, Some(false)).await;
+
+        assert_eq!(status, 201);
+        assert!(cookies.is_empty(), "{:?}", cookies);
+        assert!(data.get("expires_in").is_none());
+        assert!(data["user"]["email"].is_string());
+    }
+}
@@ -8,8 +8,8 @@ use sqlx::PgPool;
 use std::sync::Arc;
 
 use crate::errors::AppError;
-use crate::middleware::extract_client_ip;
 use crate::middleware::AuthenticatedUser;
+use crate::middleware::{extract_client_ip, record_rate_limit_usage};
 use crate::models::RateLimitConfig;
 use crate::repositories::{RateLimitRepository, UserRepository};
 use crate::responses::{get_request_id, success};
@@ -42,9 +42,10 @@ pub async fn create_setup_intent(
 
     // Rate-limit by IP using the same budget as registration
     let ip_key = ip_address.map(|ip| ip.to_string()).unwrap_or_default();
-    let (_count, exceeded) =
+    let (count, exceeded) =
         RateLimitRepository::check_and_increment(&pool, &ip_key, &RateLimitConfig::REGISTRATION)
             .await?;
+    record_rate_limit_usage(&req, &RateLimitConfig::REGISTRATION, count);
     if exceeded {
         let retry_after =
             RateLimitRepository::get_retry_after(&pool, &ip_key, &RateLimitConfig::REGISTRATION)
@@ -116,21 +117,7 @@ pub async fn download_invoice(
         .stripe_customer_id
         .ok_or(AppError::not_found("No billing account found"))?;
 
-    let invoice = stripe.get_invoice(&invoice_id).await?;
-
-    // Verify the invoice belongs to this user's Stripe customer
-    let invoice_customer = invoice
-        .customer_id
-        .as_deref()
-        .ok_or(AppError::not_found("Invoice"))?;
-
-    if invoice_customer != customer_id {
-        return Err(AppError::not_found("Invoice"));
-    }
-
-    let pdf_url = invoice
-        .invoice_pdf
-        .ok_or(AppError::not_found("Invoice PDF"))?;
+    let pdf_url = stripe.invoice_pdf_url(&invoice_id, &customer_id).await?;
 
     Ok(HttpResponse::Found()
         .insert_header(("Location", pdf_url))
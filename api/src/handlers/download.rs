@@ -6,6 +6,7 @@ use sqlx::PgPool;
 use std::sync::Arc;
 use tokio_util::codec::{BytesCodec, FramedRead};
 
+use crate::config::AuditConfig;
 use crate::errors::AppError;
 use crate::middleware::{extract_client_ip, AdminUser, MemberUser};
 use crate::models::download::{
@@ -133,6 +134,7 @@ pub async fn download_asset(
     req: HttpRequest,
     user: MemberUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     release_cache: web::Data<Option<Arc<ReleaseCache>>>,
     download_cache: web::Data<Option<Arc<DownloadCache>>>,
     limiter: web::Data<Arc<DownloadLimiter>>,
@@ -162,6 +164,7 @@ pub async fn download_asset(
             // Audit: requested.
             AuditLogRepository::create(
                 &pool,
+                &audit,
                 CreateAuditLog::new(AuditAction::DownloadRequested)
                     .with_actor(user.0.sub, &user.0.email, &user.0.role)
                     .with_resource("application", app.id)
@@ -189,6 +192,7 @@ pub async fn download_asset(
                 Err(e) => {
                     AuditLogRepository::create(
                         &pool,
+                        &audit,
                         CreateAuditLog::new(AuditAction::DownloadFailedUpstream)
                             .with_actor(user.0.sub, &user.0.email, &user.0.role)
                             .with_resource("application", app.id)
@@ -216,6 +220,7 @@ pub async fn download_asset(
             // dropped (client abort) after an error branch already fired.
             struct AuditCtx {
                 pool: PgPool,
+                audit: AuditConfig,
                 user_id: uuid::Uuid,
                 email: String,
                 role: String,
@@ -228,6 +233,7 @@ pub async fn download_asset(
             }
             let audit = AuditCtx {
                 pool: pool.get_ref().clone(),
+                audit: audit.get_ref().clone(),
                 user_id: user.0.sub,
                 email: user.0.email.clone(),
                 role: user.0.role.clone(),
@@ -253,6 +259,7 @@ pub async fn download_asset(
                                 a.emitted = true;
                                 let _ = AuditLogRepository::create(
                                     &a.pool,
+                                    &a.audit,
                                     CreateAuditLog::new(AuditAction::DownloadFailedUpstream)
                                         .with_actor(a.user_id, &a.email, &a.role)
                                         .with_resource("application", a.app_id)
@@ -272,6 +279,7 @@ pub async fn download_asset(
                                 a.emitted = true;
                                 let _ = AuditLogRepository::create(
                                     &a.pool,
+                                    &a.audit,
                                     CreateAuditLog::new(AuditAction::DownloadCompleted)
                                         .with_actor(a.user_id, &a.email, &a.role)
                                         .with_resource("application", a.app_id)
@@ -304,6 +312,7 @@ pub async fn download_asset(
         Err(LimitDenial::Concurrency) => {
             AuditLogRepository::create(
                 &pool,
+                &audit,
                 CreateAuditLog::new(AuditAction::DownloadDeniedRateLimit)
                     .with_actor(user.0.sub, &user.0.email, &user.0.role)
                     .with_resource("application", app.id)
@@ -320,6 +329,7 @@ pub async fn download_asset(
         Err(LimitDenial::DailyCap { reset_in_secs }) => {
             AuditLogRepository::create(
                 &pool,
+                &audit,
                 CreateAuditLog::new(AuditAction::DownloadDeniedRateLimit)
                     .with_actor(user.0.sub, &user.0.email, &user.0.role)
                     .with_resource("application", app.id)
@@ -8,14 +8,15 @@ use sqlx::PgPool;
 use std::collections::HashSet;
 use std::sync::Arc;
 
-use crate::config::Config;
+use crate::config::{AuditConfig, Config, PaginationConfig};
 use crate::errors::AppError;
-use crate::middleware::{extract_client_ip, AdminUser};
+use crate::middleware::{extract_client_ip, record_rate_limit_usage, AdminUser};
 use crate::models::{
     AuditAction, CreateAdminNotification, CreateAuditLog, CreateFeedback, FeedbackStatus,
     FeedbackSubmissionResponse, NotificationType, RateLimitConfig, RespondToFeedback,
     RespondToFeedbackRequest, UpdateFeedbackStatusRequest,
 };
+use crate::pagination::resolve_page;
 use crate::repositories::{
     AuditLogRepository, FeedbackRepository, NotificationRepository, RateLimitRepository,
     UserRepository,
@@ -46,8 +47,9 @@ fn normalize_optional(value: Option<String>) -> Option<String> {
 
 fn validate_length(field: &str, value: &str, max: usize) -> Result<(), AppError> {
     if value.len() > max {
-        return Err(AppError::validation(
+        return Err(AppError::validation_coded(
             field,
+            format!("{field}_too_long"),
             format!("{field} must be at most {max} characters"),
         ));
     }
@@ -85,13 +87,18 @@ fn normalize_tags(tags: &[String]) -> Result<Vec<String>, AppError> {
     Ok(normalized)
 }
 
-async fn check_feedback_rate_limit(pool: &PgPool, key: &str) -> Result<(), AppError> {
+async fn check_feedback_rate_limit(
+    req: &HttpRequest,
+    pool: &PgPool,
+    key: &str,
+) -> Result<(), AppError> {
     let config = RateLimitConfig {
         action: "feedback_submit",
         max_requests: 5,
         window_seconds: 3600,
     };
-    let (_count, exceeded) = RateLimitRepository::check_and_increment(pool, key, &config).await?;
+    let (count, exceeded) = RateLimitRepository::check_and_increment(pool, key, &config).await?;
+    record_rate_limit_usage(req, &config, count);
     if exceeded {
         let retry_after = RateLimitRepository::get_retry_after(pool, key, &config).await?;
         return Err(AppError::RateLimited { retry_after });
@@ -111,7 +118,7 @@ pub async fn submit_feedback(
     let ip_key = ip_address
         .map(|ip| ip.to_string())
         .unwrap_or_else(|| "unknown".to_string());
-    check_feedback_rate_limit(&pool, &ip_key).await?;
+    check_feedback_rate_limit(&req, &pool, &ip_key).await?;
 
     // Parse multipart fields
     let mut name_raw: Option<String> = None;
@@ -228,7 +235,11 @@ pub async fn submit_feedback(
         }
     }
     if message.is_empty() {
-        return Err(AppError::validation("message", "Message is required"));
+        return Err(AppError::validation_coded(
+            "message",
+            "message_required",
+            "Message is required",
+        ));
     }
     validate_length("message", &message, 5000)?;
 
@@ -252,6 +263,7 @@ pub async fn submit_feedback(
 
     AuditLogRepository::create(
         &pool,
+        &config.audit,
         CreateAuditLog::new(AuditAction::FeedbackSubmitted)
             .with_resource("feedback", feedback.id)
             .with_metadata(serde_json::json!({
@@ -320,11 +332,16 @@ pub async fn list_feedback(
     req: HttpRequest,
     _admin: AdminUser,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
     query: web::Query<ListFeedbackQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
-    let page = query.page.unwrap_or(1).max(1);
-    let per_page = query.per_page.or(query.page_size).unwrap_or(20).min(100);
+    let (page, per_page) = resolve_page(
+        query.page,
+        query.per_page.or(query.page_size),
+        20,
+        pagination.max_offset,
+    )?;
 
     if let Some(status) = query.status.as_deref() {
         FeedbackStatus::from_str(status)
@@ -361,6 +378,7 @@ pub async fn respond_to_feedback(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     email_service: web::Data<Arc<EmailService>>,
     path: web::Path<uuid::Uuid>,
     body: web::Json<RespondToFeedbackRequest>,
@@ -373,7 +391,11 @@ pub async fn respond_to_feedback(
 
     let response = body.response.trim().to_string();
     if response.is_empty() {
-        return Err(AppError::validation("response", "Response is required"));
+        return Err(AppError::validation_coded(
+            "response",
+            "response_required",
+            "Response is required",
+        ));
     }
     validate_length("response", &response, 5000)?;
 
@@ -400,6 +422,7 @@ pub async fn respond_to_feedback(
 
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::FeedbackResponded)
             .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
             .with_resource("feedback", updated.id)
@@ -428,6 +451,7 @@ pub async fn update_feedback_status(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     path: web::Path<uuid::Uuid>,
     body: web::Json<UpdateFeedbackStatusRequest>,
 ) -> Result<HttpResponse, AppError> {
@@ -445,6 +469,7 @@ pub async fn update_feedback_status(
 
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::FeedbackResponded)
             .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
             .with_resource("feedback", updated.id)
@@ -463,6 +488,7 @@ pub async fn delete_feedback(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     path: web::Path<uuid::Uuid>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -472,6 +498,7 @@ pub async fn delete_feedback(
 
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::FeedbackDeleted)
             .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
             .with_resource("feedback", feedback_id),
@@ -545,11 +572,11 @@ pub async fn list_feedback_archive(
     req: HttpRequest,
     _admin: AdminUser,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
     query: web::Query<ListArchiveQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
-    let page = query.page.unwrap_or(1).max(1);
-    let per_page = query.per_page.unwrap_or(20).min(100);
+    let (page, per_page) = resolve_page(query.page, query.per_page, 20, pagination.max_offset)?;
 
     let (items, total) = FeedbackRepository::list_archived(&pool, page, per_page).await?;
 
@@ -560,6 +587,7 @@ pub async fn restore_feedback(
     req: HttpRequest,
     admin: AdminUser,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     path: web::Path<uuid::Uuid>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -569,6 +597,7 @@ pub async fn restore_feedback(
 
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::FeedbackRestored)
             .with_actor(admin.0.sub, &admin.0.email, &admin.0.role)
             .with_resource("feedback", feedback.id),
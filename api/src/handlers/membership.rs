@@ -3,24 +3,36 @@
 //! This module contains HTTP handlers for membership management endpoints.
 
 use actix_web::{web, HttpRequest, HttpResponse};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json;
 use sqlx::PgPool;
 use std::sync::Arc;
 
-use crate::config::Config;
+use crate::config::{AuditConfig, Config};
 use crate::errors::AppError;
-use crate::middleware::{AuthCookies, AuthenticatedUser};
-use crate::models::MembershipResponse;
-use crate::repositories::UserRepository;
+use crate::middleware::{extract_client_ip, AuthCookies, AuthenticatedUser};
+use crate::models::{
+    AuditAction, CancellationReason, CreateAuditLog, MembershipResponse, PaymentMethodResponse,
+    User,
+};
+use crate::repositories::{AuditLogRepository, PaymentMethodRepository, UserRepository};
 use crate::responses::{get_request_id, success};
-use crate::services::{JwtService, StripeService};
+use crate::services::{CheckoutMode, JwtService, StripeService};
 
 /// Request for creating a checkout session
 #[derive(Debug, Deserialize)]
 pub struct CheckoutRequest {
     /// The Stripe price ID to checkout with
     pub price_id: Option<String>,
+    /// Free trial length; capped at `STRIPE_MAX_TRIAL_DAYS`
+    pub trial_days: Option<u32>,
+    /// `subscription` (default) or `setup` to save a card before subscribing
+    #[serde(default)]
+    pub mode: CheckoutMode,
+    /// Stripe test clock (`clock_...`) for a newly created customer;
+    /// ignored in production
+    pub test_clock: Option<String>,
 }
 
 /// Response for checkout session creation
@@ -131,7 +143,13 @@ pub async fn create_checkout(
     let customer_id = match db_user.stripe_customer_id {
         Some(id) => id,
         None => {
-            let customer_id = stripe.create_customer(&db_user.email, db_user.id).await?;
+            let customer_id = stripe
+                .create_customer_on_test_clock(
+                    &db_user.email,
+                    db_user.id,
+                    body.test_clock.as_deref(),
+                )
+                .await?;
             UserRepository::update_stripe_customer_id(&mut *tx, db_user.id, &customer_id).await?;
             customer_id
         }
@@ -140,12 +158,19 @@ pub async fn create_checkout(
 
     // Create checkout session with the price
     let (session_id, checkout_url) = stripe
-        .create_checkout_session(&customer_id, db_user.id, &price_id)
+        .create_checkout_session(
+            &customer_id,
+            db_user.id,
+            &price_id,
+            body.trial_days,
+            body.mode,
+        )
         .await?;
 
     tracing::info!(
         user_id = %db_user.id,
         price_id = %price_id,
+        mode = ?body.mode,
         "Created checkout session for user"
     );
 
@@ -166,8 +191,11 @@ pub async fn cancel_membership(
     pool: web::Data<PgPool>,
     stripe: web::Data<Arc<StripeService>>,
     config: web::Data<Config>,
+    body: web::Bytes,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
+    let body: CancelMembershipRequest = parse_optional_body(&body, "reason")?;
+    let feedback = validate_cancellation_feedback(body.feedback.as_deref())?;
 
     // Get jwt_service from app data
     let jwt_service = req
@@ -199,6 +227,17 @@ pub async fn cancel_membership(
         UserRepository::reset_subscription_tier(pool.get_ref(), user.0.sub).await?;
     }
 
+    let ip = extract_client_ip(&req).map(ipnetwork::IpNetwork::from);
+    record_cancellation(
+        &pool,
+        &config.audit,
+        &db_user,
+        body.reason,
+        feedback.as_deref(),
+        ip,
+    )
+    .await?;
+
     // Fetch updated user
     let updated_user = UserRepository::find_by_id(&pool, user.0.sub)
         .await?
@@ -206,14 +245,18 @@ pub async fn cancel_membership(
 
     tracing::info!(
         user_id = %updated_user.id,
+        reason = body.reason.map(|r| r.as_str()).unwrap_or("none"),
         "User canceled membership"
     );
 
-    // Create new access token with updated claims
-    let access_token = jwt_service.create_access_token(&updated_user)?;
+    // Old tokens carry the previous status; hand this session a fresh one
+    jwt_service
+        .claims_changed(updated_user.id, &AuditAction::MembershipCanceled)
+        .await;
+    let access_token = jwt_service.reissue_access_token(&updated_user, &user.0)?;
 
     // Determine if we should use secure cookies
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     Ok(HttpResponse::Ok()
@@ -232,6 +275,74 @@ pub async fn cancel_membership(
         }))
 }
 
+/// Longest free-text cancellation feedback accepted
+const MAX_CANCELLATION_FEEDBACK_LEN: usize = 2000;
+
+/// Optional body for POST /v1/memberships/cancel
+#[derive(Debug, Default, Deserialize)]
+pub struct CancelMembershipRequest {
+    pub reason: Option<CancellationReason>,
+    pub feedback: Option<String>,
+}
+
+/// Parse an optional JSON body. An empty body yields the default so clients
+/// that post without a body keep working; malformed input is a validation error
+/// on `field`.
+fn parse_optional_body<T: DeserializeOwned + Default>(
+    body: &[u8],
+    field: &str,
+) -> Result<T, AppError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body).map_err(|_| AppError::validation(field, "Invalid request body"))
+}
+
+/// Trim feedback, dropping it when blank and rejecting it when too long.
+fn validate_cancellation_feedback(feedback: Option<&str>) -> Result<Option<String>, AppError> {
+    let Some(feedback) = feedback.map(str::trim).filter(|f| !f.is_empty()) else {
+        return Ok(None);
+    };
+    if feedback.chars().count() > MAX_CANCELLATION_FEEDBACK_LEN {
+        return Err(AppError::validation(
+            "feedback",
+            format!(
+                "Feedback must be at most {} characters",
+                MAX_CANCELLATION_FEEDBACK_LEN
+            ),
+        ));
+    }
+    Ok(Some(feedback.to_string()))
+}
+
+/// Store the cancellation reason on the user and write the audit entry.
+async fn record_cancellation(
+    pool: &PgPool,
+    audit: &AuditConfig,
+    user: &User,
+    reason: Option<CancellationReason>,
+    feedback: Option<&str>,
+    ip: Option<ipnetwork::IpNetwork>,
+) -> Result<(), AppError> {
+    let reason = reason.map(|r| r.as_str());
+    UserRepository::set_cancellation_reason(pool, user.id, reason, feedback).await?;
+
+    let audit_log = CreateAuditLog::new(AuditAction::MembershipCanceled)
+        .with_actor(user.id, &user.email, &user.role)
+        .with_ip(ip)
+        .with_resource("user", user.id)
+        .with_metadata(serde_json::json!({
+            "reason": reason,
+            "feedback": feedback,
+            "at_period_end": user.stripe_customer_id.is_some(),
+        }));
+    if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
+        tracing::error!(error = %e, user_id = %user.id, "Failed to create audit log for membership cancellation");
+    }
+
+    Ok(())
+}
+
 /// POST /v1/memberships/cancel-now
 /// Cancel membership immediately (for testing/development)
 pub async fn cancel_membership_immediate(
@@ -277,8 +388,11 @@ pub async fn cancel_membership_immediate(
 
     tracing::info!(user_id = %updated_user.id, "User canceled membership immediately");
 
-    let access_token = jwt_service.create_access_token(&updated_user)?;
-    let secure = config.is_production();
+    jwt_service
+        .claims_changed(updated_user.id, &AuditAction::MembershipCanceled)
+        .await;
+    let access_token = jwt_service.reissue_access_token(&updated_user, &user.0)?;
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     Ok(HttpResponse::Ok()
@@ -334,6 +448,58 @@ pub async fn reactivate_membership(
     Ok(crate::responses::success_no_data(request_id))
 }
 
+/// Request for POST /v1/memberships/change-tier/preview
+#[derive(Debug, Deserialize)]
+pub struct ChangeTierPreviewRequest {
+    /// Stripe price ID of the tier to move to
+    pub price_id: String,
+}
+
+/// POST /v1/memberships/change-tier/preview
+/// Estimate what switching to another price would charge, without switching
+pub async fn preview_tier_change(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<PgPool>,
+    stripe: web::Data<Arc<StripeService>>,
+    body: web::Json<ChangeTierPreviewRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let price_id = body.price_id.trim();
+    if price_id.is_empty() {
+        return Err(AppError::validation("price_id", "Price ID is required"));
+    }
+
+    let db_user = UserRepository::find_by_id(&pool, user.0.sub)
+        .await?
+        .ok_or(AppError::not_found("User"))?;
+
+    let customer_id = db_user
+        .stripe_customer_id
+        .ok_or(AppError::not_found("No billing account found"))?;
+
+    let sub = stripe
+        .get_customer_subscription(&customer_id)
+        .await?
+        .ok_or(AppError::not_found("Subscription"))?;
+
+    if sub.items.iter().any(|item| item.price_id == price_id) {
+        return Err(AppError::conflict("Already subscribed to this price"));
+    }
+
+    let preview = stripe.preview_proration(&sub.id, price_id).await?;
+
+    Ok(success(preview, request_id))
+}
+
+/// Optional body for POST /v1/memberships/billing-portal
+#[derive(Debug, Default, Deserialize)]
+pub struct PortalRequest {
+    /// Where Stripe sends the user back to; must be on an allowed host
+    pub return_url: Option<String>,
+}
+
 /// POST /v1/memberships/billing-portal
 /// Get a link to the Stripe billing portal
 pub async fn billing_portal(
@@ -341,8 +507,10 @@ pub async fn billing_portal(
     user: AuthenticatedUser,
     pool: web::Data<PgPool>,
     stripe: web::Data<Arc<StripeService>>,
+    body: web::Bytes,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
+    let body: PortalRequest = parse_optional_body(&body, "return_url")?;
 
     // Get user from database
     let db_user = UserRepository::find_by_id(&pool, user.0.sub)
@@ -353,7 +521,9 @@ pub async fn billing_portal(
         .stripe_customer_id
         .ok_or(AppError::not_found("No billing account found"))?;
 
-    let url = stripe.create_billing_portal_session(&customer_id).await?;
+    let url = stripe
+        .create_billing_portal_session(&customer_id, body.return_url.as_deref())
+        .await?;
 
     Ok(success(PortalResponse { url }, request_id))
 }
@@ -365,9 +535,10 @@ pub async fn get_payment_history(
     user: AuthenticatedUser,
     pool: web::Data<PgPool>,
     stripe: web::Data<Arc<StripeService>>,
-    query: web::Query<PaginationQuery>,
+    query: web::Query<PaymentHistoryQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
+    let (since, until) = payment_history_range(query.since, query.until)?;
 
     let db_user = UserRepository::find_by_id(&pool, user.0.sub)
         .await?
@@ -375,9 +546,12 @@ pub async fn get_payment_history(
 
     let payments = if let Some(ref customer_id) = db_user.stripe_customer_id {
         let limit = query.per_page.map(|p| p.min(100).max(1) as u64);
-        let invoices = stripe.list_customer_invoices(customer_id, limit).await?;
+        let invoices = stripe
+            .list_customer_invoices_in_range(customer_id, limit, since, until)
+            .await?;
         invoices
             .into_iter()
+            .filter(|inv| created_in_range(inv.created, since, until))
             .map(|inv| StripePaymentResponse {
                 id: inv.id,
                 amount: inv.amount_paid,
@@ -394,10 +568,76 @@ pub async fn get_payment_history(
     Ok(success(payments, request_id))
 }
 
+/// GET /v1/memberships/payment-method
+/// Get the card on file (brand, last4, expiry), or `null` if there is none
+pub async fn get_payment_method(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let payment_method = PaymentMethodRepository::find_by_user_id(&pool, user.0.sub)
+        .await?
+        .map(PaymentMethodResponse::from);
+
+    Ok(success(payment_method, request_id))
+}
+
+/// GET /v1/memberships/payments/{payment_id}/invoice
+/// Redirect to the Stripe-hosted PDF invoice for one of the caller's payments
+pub async fn download_payment_invoice(
+    user: AuthenticatedUser,
+    pool: web::Data<PgPool>,
+    stripe: web::Data<Arc<StripeService>>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let payment_id = path.into_inner();
+
+    let db_user = UserRepository::find_by_id(&pool, user.0.sub)
+        .await?
+        .ok_or(AppError::not_found("User"))?;
+
+    // Payments are Stripe invoices, so the payment ID is the invoice ID
+    let customer_id = db_user
+        .stripe_customer_id
+        .ok_or(AppError::not_found("Invoice"))?;
+    let pdf_url = stripe.invoice_pdf_url(&payment_id, &customer_id).await?;
+
+    Ok(HttpResponse::Found()
+        .insert_header(("Location", pdf_url))
+        .finish())
+}
+
 #[derive(Debug, Deserialize)]
-pub struct PaginationQuery {
+pub struct PaymentHistoryQuery {
     pub page: Option<i32>,
     pub per_page: Option<i32>,
+    /// Only include payments created at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only include payments created at or before this time
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Validate the requested range and convert it to unix timestamps.
+fn payment_history_range(
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<(Option<i64>, Option<i64>), AppError> {
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            return Err(AppError::validation(
+                "since",
+                "since must be before or equal to until",
+            ));
+        }
+    }
+    Ok((since.map(|t| t.timestamp()), until.map(|t| t.timestamp())))
+}
+
+/// Whether a payment's `created` timestamp falls within the inclusive range.
+fn created_in_range(created: i64, since: Option<i64>, until: Option<i64>) -> bool {
+    since.map_or(true, |s| created >= s) && until.map_or(true, |u| created <= u)
 }
 
 /// Response for subscription activation
@@ -430,11 +670,14 @@ pub async fn subscribe(
         "User subscribed to membership"
     );
 
-    // Create new access token with updated claims
-    let access_token = jwt_service.create_access_token(&updated_user)?;
+    // Create new access token with updated claims, retiring the old ones
+    jwt_service
+        .claims_changed(updated_user.id, &AuditAction::MembershipCreated)
+        .await;
+    let access_token = jwt_service.reissue_access_token(&updated_user, &user.0)?;
 
     // Determine if we should use secure cookies
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     // Build response with the cookie
@@ -455,3 +698,153 @@ pub async fn subscribe(
             meta: crate::responses::ResponseMeta::new(request_id),
         }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    #[test]
+    fn payment_history_range_rejects_inverted_bounds() {
+        let since = Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+        let err = payment_history_range(Some(since), Some(until)).unwrap_err();
+        assert_eq!(err.error_code(), "VALIDATION_ERROR");
+    }
+
+    #[test]
+    fn payment_history_range_allows_open_and_equal_bounds() {
+        let t = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            payment_history_range(Some(t), Some(t)).unwrap(),
+            (Some(t.timestamp()), Some(t.timestamp()))
+        );
+        assert_eq!(payment_history_range(None, None).unwrap(), (None, None));
+    }
+
+    #[test]
+    fn bounded_range_keeps_only_in_range_payments() {
+        let since = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2025, 2, 28, 23, 59, 59).unwrap();
+        let (since, until) = payment_history_range(Some(since), Some(until)).unwrap();
+
+        let jan = Utc
+            .with_ymd_and_hms(2025, 1, 15, 0, 0, 0)
+            .unwrap()
+            .timestamp();
+        let feb = Utc
+            .with_ymd_and_hms(2025, 2, 15, 0, 0, 0)
+            .unwrap()
+            .timestamp();
+        let mar = Utc
+            .with_ymd_and_hms(2025, 3, 15, 0, 0, 0)
+            .unwrap()
+            .timestamp();
+
+        let kept: Vec<i64> = [jan, feb, mar]
+            .into_iter()
+            .filter(|c| created_in_range(*c, since, until))
+            .collect();
+        assert_eq!(kept, vec![feb]);
+        assert!(created_in_range(since.unwrap(), since, until));
+        assert!(created_in_range(until.unwrap(), since, until));
+    }
+
+    #[test]
+    fn cancel_request_body_parses_reason_codes() {
+        let body = parse_optional_body::<CancelMembershipRequest>(
+            br#"{"reason":"too_expensive","feedback":"pricey"}"#,
+            "reason",
+        )
+        .unwrap();
+        assert_eq!(body.reason, Some(CancellationReason::TooExpensive));
+        assert_eq!(body.feedback.as_deref(), Some("pricey"));
+
+        let empty = parse_optional_body::<CancelMembershipRequest>(b"", "reason").unwrap();
+        assert!(empty.reason.is_none() && empty.feedback.is_none());
+
+        let err =
+            parse_optional_body::<CancelMembershipRequest>(br#"{"reason":"bored"}"#, "reason")
+                .unwrap_err();
+        assert_eq!(err.error_code(), "VALIDATION_ERROR");
+    }
+
+    #[test]
+    fn cancellation_feedback_is_trimmed_and_bounded() {
+        assert_eq!(validate_cancellation_feedback(None).unwrap(), None);
+        assert_eq!(validate_cancellation_feedback(Some("   ")).unwrap(), None);
+        assert_eq!(
+            validate_cancellation_feedback(Some("  too slow ")).unwrap(),
+            Some("too slow".to_string())
+        );
+        let long = "x".repeat(MAX_CANCELLATION_FEEDBACK_LEN + 1);
+        assert!(validate_cancellation_feedback(Some(&long)).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn record_cancellation_persists_reason_and_audits() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let id = uuid::Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO users (id, email, password_hash, subscription_status) VALUES ($1, $2, 'x', 'active')",
+        )
+        .bind(id)
+        .bind(format!("cancel-{}@example.com", id))
+        .execute(&pool)
+        .await
+        .unwrap();
+        let user = UserRepository::find_by_id(&pool, id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        record_cancellation(
+            &pool,
+            &AuditConfig::default(),
+            &user,
+            Some(CancellationReason::MissingFeatures),
+            Some("needs SSO"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let row: (Option<String>, Option<String>) = sqlx::query_as(
+            "SELECT cancellation_reason, cancellation_feedback FROM users WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(row.0.as_deref(), Some("missing_features"));
+        assert_eq!(row.1.as_deref(), Some("needs SSO"));
+
+        let audit: (serde_json::Value,) = sqlx::query_as(
+            "SELECT metadata FROM audit_logs WHERE action = 'membership_canceled' AND resource_id = $1",
+        )
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(audit.0["reason"], "missing_features");
+        assert_eq!(audit.0["feedback"], "needs SSO");
+
+        sqlx::query("DELETE FROM audit_logs WHERE resource_id = $1")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
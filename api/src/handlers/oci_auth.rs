@@ -9,8 +9,9 @@ use chrono::Utc;
 use ipnetwork::IpNetwork;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::sync::{Arc, OnceLock};
+use std::sync::Arc;
 
+use crate::config::AuditConfig;
 use crate::errors::OciError;
 use crate::middleware::extract_client_ip;
 use crate::models::{AuditAction, CreateAuditLog, RateLimitConfig, User};
@@ -45,23 +46,14 @@ fn has_member_access(user: &User) -> bool {
         || user.membership_status == "grace_period"
 }
 
-/// A pre-hashed Argon2id string used to perform constant-time verification
-/// on the "user not found" branch, preventing email enumeration via timing.
-fn dummy_hash() -> &'static str {
-    static DUMMY: OnceLock<String> = OnceLock::new();
-    DUMMY.get_or_init(|| {
-        PasswordService::new()
-            .hash("unused-password-for-timing-mitigation")
-            .expect("failed to compute dummy hash")
-    })
-}
-
 /// GET /auth/token
 pub async fn issue_token(
     req: HttpRequest,
     query: web::Query<TokenQuery>,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     token_svc: web::Data<Arc<OciTokenService>>,
+    password_service: web::Data<Arc<PasswordService>>,
 ) -> Result<HttpResponse, OciError> {
     let ip = extract_client_ip(&req).map(IpNetwork::from);
     let (email, password) = parse_basic_auth(&req).ok_or(OciError::Unauthorized)?;
@@ -85,7 +77,7 @@ pub async fn issue_token(
         )
         .await
         .unwrap_or(60);
-        audit_failed(pool.get_ref(), &email, ip, "rate_limited").await;
+        audit_failed(pool.get_ref(), &audit, &email, ip, "rate_limited").await;
         return Err(OciError::TooManyRequests {
             retry_after_secs: Some(retry_after as u64),
         });
@@ -100,26 +92,23 @@ pub async fn issue_token(
         None => {
             // Perform dummy verification on the "user not found" path to mitigate
             // email enumeration attacks via response-time analysis.
-            let password_service = PasswordService::new();
-            let _ = password_service.verify(&password, dummy_hash());
-            audit_failed(pool.get_ref(), &email, ip, "user_not_found").await;
+            password_service.verify_dummy(&password);
+            audit_failed(pool.get_ref(), &audit, &email, ip, "user_not_found").await;
             return Err(OciError::Unauthorized);
         }
     };
 
     if user.deleted_at.is_some() {
-        audit_failed(pool.get_ref(), &email, ip, "inactive_user").await;
+        audit_failed(pool.get_ref(), &audit, &email, ip, "inactive_user").await;
         return Err(OciError::Unauthorized);
     }
 
-    let password_service = PasswordService::new();
-
     // Passwordless accounts (magic-link only) cannot use the registry. Still
     // perform a dummy verify to keep timing indistinguishable from the
     // password-check branch.
     let Some(password_hash) = user.password_hash.as_ref() else {
-        let _ = password_service.verify(&password, dummy_hash());
-        audit_failed(pool.get_ref(), &email, ip, "no_password").await;
+        password_service.verify_dummy(&password);
+        audit_failed(pool.get_ref(), &audit, &email, ip, "no_password").await;
         return Err(OciError::Unauthorized);
     };
 
@@ -127,12 +116,12 @@ pub async fn issue_token(
         .verify(&password, password_hash)
         .map_err(|_| OciError::Internal)?;
     if !password_ok {
-        audit_failed(pool.get_ref(), &email, ip, "bad_password").await;
+        audit_failed(pool.get_ref(), &audit, &email, ip, "bad_password").await;
         return Err(OciError::Unauthorized);
     }
 
     if !has_member_access(&user) {
-        audit_failed(pool.get_ref(), &email, ip, "no_active_membership").await;
+        audit_failed(pool.get_ref(), &audit, &email, ip, "no_active_membership").await;
         return Err(OciError::Unauthorized);
     }
 
@@ -157,7 +146,7 @@ pub async fn issue_token(
         .with_actor(user.id, &user.email, &user.role)
         .with_ip(ip)
         .with_metadata(serde_json::json!({ "scope": scope_str }));
-    if let Err(e) = AuditLogRepository::create(pool.get_ref(), log).await {
+    if let Err(e) = AuditLogRepository::create(pool.get_ref(), &audit, log).await {
         tracing::warn!(?e, "oci audit log write failed");
     }
 
@@ -196,11 +185,17 @@ fn parse_repository_pull_scope(scope: &str) -> Option<String> {
     Some(slug.to_string())
 }
 
-async fn audit_failed(pool: &PgPool, email: &str, ip: Option<IpNetwork>, reason: &str) {
+async fn audit_failed(
+    pool: &PgPool,
+    audit: &AuditConfig,
+    email: &str,
+    ip: Option<IpNetwork>,
+    reason: &str,
+) {
     let log = CreateAuditLog::new(AuditAction::OciLoginFailed)
         .with_ip(ip)
         .with_metadata(serde_json::json!({ "email": email, "reason": reason }));
-    if let Err(e) = AuditLogRepository::create(pool, log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, log).await {
         tracing::warn!(?e, "oci audit log write failed");
     }
 }
@@ -9,6 +9,7 @@ use std::sync::Arc;
 use tokio_util::codec::{BytesCodec, FramedRead};
 use uuid::Uuid;
 
+use crate::config::AuditConfig;
 use crate::errors::{AppError, OciError};
 use crate::middleware::{extract_client_ip, OciBearerUser};
 use crate::models::oci::CachedManifest;
@@ -35,13 +36,14 @@ pub async fn get_manifest(
     user: OciBearerUser,
     path: web::Path<(String, String)>,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     client: web::Data<Option<Arc<ForgejoRegistryClient>>>,
     manifest_cache: web::Data<Option<Arc<ManifestCache>>>,
     limiter: web::Data<Arc<OciLimiter>>,
 ) -> Result<HttpResponse, OciError> {
     let (slug, reference) = path.into_inner();
     if user.assert_scope(&slug).is_err() {
-        audit_denied_scope(pool.get_ref(), &req, &user, &slug).await;
+        audit_denied_scope(pool.get_ref(), &audit, &req, &user, &slug).await;
         return Err(OciError::Denied);
     }
 
@@ -83,7 +85,16 @@ pub async fn get_manifest(
     {
         Ok(g) => g,
         Err(OciLimitDenial::Concurrency) => {
-            audit_denied(pool.get_ref(), &req, &user, &app.id, "concurrency", None).await;
+            audit_denied(
+                pool.get_ref(),
+                &audit,
+                &req,
+                &user,
+                &app.id,
+                "concurrency",
+                None,
+            )
+            .await;
             return Err(OciError::TooManyRequests {
                 retry_after_secs: None,
             });
@@ -92,6 +103,7 @@ pub async fn get_manifest(
             let secs_u64 = reset_in_secs.max(0) as u64;
             audit_denied(
                 pool.get_ref(),
+                &audit,
                 &req,
                 &user,
                 &app.id,
@@ -105,7 +117,7 @@ pub async fn get_manifest(
         }
     };
 
-    audit_requested(pool.get_ref(), &req, &user, &app.id, &reference).await;
+    audit_requested(pool.get_ref(), &audit, &req, &user, &app.id, &reference).await;
 
     let accept = req
         .headers()
@@ -129,6 +141,7 @@ pub async fn get_manifest(
                 if matches!(mapped, OciError::Upstream) {
                     audit_failed_upstream(
                         pool.get_ref(),
+                        &audit,
                         &req,
                         &user,
                         &app.id,
@@ -161,6 +174,7 @@ pub async fn get_manifest(
 
     audit_completed(
         pool.get_ref(),
+        &audit,
         &req,
         &user,
         &app.id,
@@ -188,11 +202,12 @@ pub async fn get_blob(
     user: OciBearerUser,
     path: web::Path<(String, String)>,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     blob_cache: web::Data<Option<Arc<BlobCache>>>,
 ) -> Result<HttpResponse, OciError> {
     let (slug, digest) = path.into_inner();
     if user.assert_scope(&slug).is_err() {
-        audit_denied_scope(pool.get_ref(), &req, &user, &slug).await;
+        audit_denied_scope(pool.get_ref(), &audit, &req, &user, &slug).await;
         return Err(OciError::Denied);
     }
     let blob_cache = blob_cache
@@ -225,6 +240,7 @@ pub async fn get_blob(
             if matches!(mapped, OciError::Upstream) {
                 audit_failed_upstream(
                     pool.get_ref(),
+                    &audit,
                     &req,
                     &user,
                     &app.id,
@@ -273,6 +289,7 @@ fn map_reg_err(e: &RegistryError) -> OciError {
 
 async fn audit_requested(
     pool: &PgPool,
+    audit: &AuditConfig,
     req: &HttpRequest,
     user: &OciBearerUser,
     app_id: &Uuid,
@@ -283,13 +300,14 @@ async fn audit_requested(
         .with_ip(extract_client_ip(req).map(IpNetwork::from))
         .with_resource("application", *app_id)
         .with_metadata(serde_json::json!({ "reference": reference }));
-    if let Err(e) = AuditLogRepository::create(pool, log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, log).await {
         tracing::warn!(?e, "oci pull_requested audit log failed");
     }
 }
 
 async fn audit_completed(
     pool: &PgPool,
+    audit: &AuditConfig,
     req: &HttpRequest,
     user: &OciBearerUser,
     app_id: &Uuid,
@@ -301,13 +319,14 @@ async fn audit_completed(
         .with_ip(extract_client_ip(req).map(IpNetwork::from))
         .with_resource("application", *app_id)
         .with_metadata(serde_json::json!({ "reference": reference, "digest": digest }));
-    if let Err(e) = AuditLogRepository::create(pool, log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, log).await {
         tracing::warn!(?e, "oci pull_completed audit log failed");
     }
 }
 
 async fn audit_denied(
     pool: &PgPool,
+    audit: &AuditConfig,
     req: &HttpRequest,
     user: &OciBearerUser,
     app_id: &Uuid,
@@ -319,13 +338,14 @@ async fn audit_denied(
         .with_ip(extract_client_ip(req).map(IpNetwork::from))
         .with_resource("application", *app_id)
         .with_metadata(serde_json::json!({ "reason": reason, "reset_in_secs": reset_in_secs }));
-    if let Err(e) = AuditLogRepository::create(pool, log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, log).await {
         tracing::warn!(?e, "oci pull_denied audit log failed");
     }
 }
 
 async fn audit_denied_scope(
     pool: &PgPool,
+    audit: &AuditConfig,
     req: &HttpRequest,
     user: &OciBearerUser,
     requested_slug: &str,
@@ -337,13 +357,14 @@ async fn audit_denied_scope(
             "requested_slug": requested_slug,
             "token_scope": user.claims.scope,
         }));
-    if let Err(e) = AuditLogRepository::create(pool, log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, log).await {
         tracing::warn!(?e, "oci pull_denied_scope audit log failed");
     }
 }
 
 async fn audit_failed_upstream(
     pool: &PgPool,
+    audit: &AuditConfig,
     req: &HttpRequest,
     user: &OciBearerUser,
     app_id: &Uuid,
@@ -360,7 +381,7 @@ async fn audit_failed_upstream(
             "reference": reference,
             "error": error,
         }));
-    if let Err(e) = AuditLogRepository::create(pool, log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, log).await {
         tracing::warn!(?e, "oci pull_failed_upstream audit log failed");
     }
 }
@@ -501,6 +522,7 @@ mod integration {
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(AuditConfig::default()))
                 .app_data(web::Data::new(Some(client.clone())))
                 .app_data(web::Data::new(Some(manifest_cache.clone())))
                 .app_data(web::Data::new(Some(blob_cache.clone())))
@@ -564,6 +586,7 @@ mod integration {
     > {
         App::new()
             .app_data(web::Data::new(pool))
+            .app_data(web::Data::new(AuditConfig::default()))
             .app_data(web::Data::new(Some(client)))
             .app_data(web::Data::new(Some(manifest_cache)))
             .app_data(web::Data::new(Some(blob_cache)))
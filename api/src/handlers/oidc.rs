@@ -18,8 +18,11 @@ use uuid::Uuid;
 
 use crate::errors::AppError;
 use crate::middleware::auth::{AuthenticatedUser, OptionalUser};
-use crate::repositories::UserRepository;
+use crate::middleware::extract_client_ip;
+use crate::repositories::ip_activity::MAX_TRACKED_IPS_PER_USER;
+use crate::repositories::{UserIpActivityRepository, UserRepository};
 use crate::services::oidc_provider::{OAuthClient, OidcProvider};
+use crate::services::JwtService;
 
 /// Unwrap `Option<Arc<OidcProvider>>` from `web::Data`, returning 404 when absent.
 /// Yields `&OidcProvider`.
@@ -108,13 +111,37 @@ pub async fn discovery(provider: web::Data<Option<Arc<OidcProvider>>>) -> HttpRe
 // ── JWKS ──────────────────────────────────────────────────────────────────────
 
 /// GET /.well-known/jwks.json
-pub async fn jwks(provider: web::Data<Option<Arc<OidcProvider>>>) -> HttpResponse {
-    let provider = require_provider!(provider, plain);
+///
+/// Serves the OIDC id_token keys together with the platform access token
+/// keys, so apps verify both from one document. 404 when there is nothing to
+/// publish (OIDC disabled and access tokens signed with a shared secret).
+pub async fn jwks(
+    req: HttpRequest,
+    provider: web::Data<Option<Arc<OidcProvider>>>,
+) -> HttpResponse {
+    let mut keys: Vec<serde_json::Value> = match provider.as_ref().as_ref() {
+        Some(p) => p
+            .keys
+            .jwks
+            .keys
+            .iter()
+            .filter_map(|k| serde_json::to_value(k).ok())
+            .collect(),
+        None => Vec::new(),
+    };
+    if let Some(jwt) = req.app_data::<Arc<JwtService>>() {
+        if let Some(platform) = jwt.jwks()["keys"].as_array() {
+            keys.extend(platform.iter().cloned());
+        }
+    }
+    if keys.is_empty() {
+        return HttpResponse::NotFound().finish();
+    }
 
     HttpResponse::Ok()
         .content_type("application/json")
         .append_header(("Cache-Control", "public, max-age=300"))
-        .json(&provider.keys.jwks)
+        .json(serde_json::json!({ "keys": keys }))
 }
 
 // ── Authorization endpoint ────────────────────────────────────────────────────
@@ -259,6 +286,20 @@ pub async fn authorize(
 
     let auth_time = chrono::Utc::now();
 
+    // Signing in to a relying party counts as IP activity like any other login
+    if let Some(client_ip) = extract_client_ip(&req) {
+        if let Err(e) = UserIpActivityRepository::record(
+            &pool,
+            user.0.sub,
+            config.audit.stored_ip(client_ip),
+            MAX_TRACKED_IPS_PER_USER,
+        )
+        .await
+        {
+            tracing::error!(error = %e, user_id = %user.0.sub, "Failed to record IP activity");
+        }
+    }
+
     // Issue authorization code
     let code = provider
         .issue_authorization_code(
@@ -689,7 +730,7 @@ pub async fn logout(
         }
     }
 
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
     let mut response = HttpResponse::Found();
     response.append_header(("Location", redirect));
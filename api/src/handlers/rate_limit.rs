@@ -0,0 +1,145 @@
+//! Rate limit handlers
+//!
+//! Lets clients see their remaining quota without consuming it.
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+use crate::errors::AppError;
+use crate::middleware::{extract_client_ip, record_rate_limit_usage, AuthenticatedUser};
+use crate::models::{RateLimitConfig, RateLimitStatus};
+use crate::repositories::RateLimitRepository;
+use crate::responses::{get_request_id, success};
+
+/// Auth limits keyed by the client IP
+const IP_LIMITS: [RateLimitConfig; 3] = [
+    RateLimitConfig::LOGIN,
+    RateLimitConfig::REGISTRATION,
+    RateLimitConfig::EMAIL_AVAILABILITY,
+];
+
+/// Auth limits keyed by the (lowercased) email address
+const EMAIL_LIMITS: [RateLimitConfig; 3] = [
+    RateLimitConfig::LOGIN,
+    RateLimitConfig::MAGIC_LINK,
+    RateLimitConfig::PASSWORD_RESET,
+];
+
+/// GET /v1/rate-limit/status
+/// Report count, limit and reset for the auth rate limits keyed by the
+/// caller's IP and their own account email
+pub async fn rate_limit_status(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+
+    let config = RateLimitConfig::RATE_LIMIT_STATUS;
+    let user_key = user.0.sub.to_string();
+    let (count, exceeded) =
+        RateLimitRepository::check_and_increment(&pool, &user_key, &config).await?;
+    record_rate_limit_usage(&req, &config, count);
+    if exceeded {
+        let retry_after = RateLimitRepository::get_retry_after(&pool, &user_key, &config).await?;
+        return Err(AppError::RateLimited { retry_after });
+    }
+
+    let ip_key = extract_client_ip(&req)
+        .map(|ip| ip.to_string())
+        .unwrap_or_default();
+    // The auth handlers key email limits by the lowercased address
+    let email = user.0.email.to_lowercase();
+
+    let statuses = collect_statuses(&pool, &ip_key, &email).await?;
+
+    Ok(success(statuses, request_id))
+}
+
+async fn collect_statuses(
+    pool: &PgPool,
+    ip_key: &str,
+    email: &str,
+) -> Result<Vec<RateLimitStatus>, AppError> {
+    let keyed = IP_LIMITS
+        .iter()
+        .map(|c| (c, "ip", ip_key))
+        .chain(EMAIL_LIMITS.iter().map(|c| (c, "email", email)));
+
+    let mut statuses = Vec::with_capacity(IP_LIMITS.len() + EMAIL_LIMITS.len());
+    for (config, key_type, key) in keyed {
+        let (count, reset_in_secs) = RateLimitRepository::status(pool, key, config).await?;
+        statuses.push(RateLimitStatus::new(config, key_type, count, reset_in_secs));
+    }
+
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    //! DB-backed integration tests. Skipped when DATABASE_URL is unset.
+    use super::*;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    #[actix_rt::test]
+    async fn reports_consumption_without_incrementing() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let ip = format!("rl-status-{}", uuid::Uuid::new_v4());
+        let email = format!("{}@example.com", uuid::Uuid::new_v4());
+        for _ in 0..2 {
+            RateLimitRepository::check_and_increment(&pool, &email, &RateLimitConfig::LOGIN)
+                .await
+                .unwrap();
+        }
+        RateLimitRepository::check_and_increment(&pool, &ip, &RateLimitConfig::REGISTRATION)
+            .await
+            .unwrap();
+
+        let first = collect_statuses(&pool, &ip, &email).await.unwrap();
+        let second = collect_statuses(&pool, &ip, &email).await.unwrap();
+
+        for action in ["login", "registration"] {
+            RateLimitRepository::reset(&pool, &ip, action)
+                .await
+                .unwrap();
+        }
+        RateLimitRepository::reset(&pool, &email, "login")
+            .await
+            .unwrap();
+
+        let find = |key_type: &str, action: &str| {
+            first
+                .iter()
+                .find(|s| s.key_type == key_type && s.action == action)
+                .cloned()
+                .unwrap()
+        };
+
+        let login = find("email", "login");
+        assert_eq!((login.count, login.limit, login.remaining), (2, 5, 3));
+        assert!(login.reset_in_secs > 0 && login.reset_in_secs <= 60);
+
+        let registration = find("ip", "registration");
+        assert_eq!(registration.count, 1);
+        assert_eq!(registration.remaining, 2);
+
+        let magic = find("email", "magic_link");
+        assert_eq!((magic.count, magic.reset_in_secs), (0, 0));
+
+        assert_eq!(find("ip", "login").count, 0);
+        assert_eq!(first.len(), 6);
+
+        // Reading the status must not consume quota
+        assert_eq!(
+            first.iter().map(|s| s.count).collect::<Vec<_>>(),
+            second.iter().map(|s| s.count).collect::<Vec<_>>()
+        );
+    }
+}
@@ -5,8 +5,13 @@ use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::sync::Arc;
 
+use crate::config::AuditConfig;
 use crate::errors::AppError;
-use crate::middleware::{extract_client_ip, extract_device_info, AuthCookies, AuthenticatedUser};
+use crate::middleware::{
+    extract_client_ip, extract_device_id, extract_device_info, force_token_refresh,
+    record_rate_limit_usage, AuthCookies, AuthenticatedUser, RecentlyAuthenticatedUser,
+    TWO_FACTOR_CHALLENGE_COOKIE,
+};
 use crate::models::{AuditAction, CreateAuditLog, RateLimitConfig};
 use crate::repositories::{AuditLogRepository, RateLimitRepository, UserRepository};
 use crate::responses::{get_request_id, success};
@@ -14,11 +19,13 @@ use crate::services::{AuthService, PasswordService, TotpService};
 
 /// Check rate limit and return RateLimited error if exceeded
 async fn check_rate_limit(
+    req: &HttpRequest,
     pool: &PgPool,
     key: &str,
     config: &RateLimitConfig,
 ) -> Result<(), AppError> {
-    let (_count, exceeded) = RateLimitRepository::check_and_increment(pool, key, config).await?;
+    let (count, exceeded) = RateLimitRepository::check_and_increment(pool, key, config).await?;
+    record_rate_limit_usage(req, config, count);
     if exceeded {
         let retry_after = RateLimitRepository::get_retry_after(pool, key, config).await?;
         return Err(AppError::RateLimited { retry_after });
@@ -35,7 +42,10 @@ pub struct ConfirmSetupRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct Verify2FARequest {
-    pub challenge_token: String,
+    /// Omitted when a magic link redirect left the challenge in the
+    /// `two_factor_challenge` cookie instead
+    #[serde(default)]
+    pub challenge_token: Option<String>,
     pub code: String,
 }
 
@@ -94,6 +104,7 @@ pub async fn setup_2fa(
 pub async fn confirm_2fa(
     req: HttpRequest,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     user: AuthenticatedUser,
     totp_service: web::Data<Arc<TotpService>>,
     body: web::Json<ConfirmSetupRequest>,
@@ -102,11 +113,13 @@ pub async fn confirm_2fa(
     let ip_address = extract_client_ip(&req);
 
     let codes = totp_service.confirm_setup(user.0.sub, &body.code).await?;
+    force_token_refresh(&req, user.0.sub, &AuditAction::TwoFactorEnabled).await;
 
     // Audit log
     let ip = ip_address.map(ipnetwork::IpNetwork::from);
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::TwoFactorEnabled)
             .with_actor(user.0.sub, &user.0.email, &user.0.role)
             .with_ip(ip),
@@ -133,6 +146,7 @@ pub async fn verify_2fa(
     // Rate limit by IP
     let ip_key = ip_address.map(|ip| ip.to_string()).unwrap_or_default();
     check_rate_limit(
+        &req,
         &pool,
         &format!("2fa_verify:{}", ip_key),
         &RateLimitConfig::LOGIN,
@@ -143,7 +157,16 @@ pub async fn verify_2fa(
     let jwt_service = req
         .app_data::<Arc<crate::services::JwtService>>()
         .ok_or(AppError::internal("JWT service not available"))?;
-    let claims = jwt_service.verify_2fa_challenge_token(&body.challenge_token)?;
+    let challenge_token = body
+        .challenge_token
+        .clone()
+        .or_else(|| {
+            req.cookie(TWO_FACTOR_CHALLENGE_COOKIE)
+                .map(|c| c.value().to_string())
+        })
+        .filter(|token| !token.is_empty())
+        .ok_or(AppError::InvalidCredentials)?;
+    let claims = jwt_service.verify_2fa_challenge_token(&challenge_token)?;
     let user_id = claims.sub;
 
     // Try TOTP code first, then recovery code
@@ -159,7 +182,11 @@ pub async fn verify_2fa(
     };
 
     if !verified {
-        return Err(AppError::validation("code", "Invalid verification code"));
+        return Err(AppError::validation_coded(
+            "code",
+            "totp_code_invalid",
+            "Invalid verification code",
+        ));
     }
 
     // Audit the verification
@@ -171,6 +198,7 @@ pub async fn verify_2fa(
     if is_recovery {
         AuditLogRepository::create(
             &pool,
+            &config.audit,
             CreateAuditLog::new(AuditAction::TwoFactorRecoveryCodeUsed)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip),
@@ -179,6 +207,7 @@ pub async fn verify_2fa(
     } else {
         AuditLogRepository::create(
             &pool,
+            &config.audit,
             CreateAuditLog::new(AuditAction::TwoFactorVerified)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip),
@@ -188,10 +217,15 @@ pub async fn verify_2fa(
 
     // Complete login
     let (tokens, user_response) = auth_service
-        .complete_2fa_login(&body.challenge_token, device_info, ip_address)
+        .complete_2fa_login(
+            &challenge_token,
+            device_info,
+            extract_device_id(&req),
+            ip_address,
+        )
         .await?;
 
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     let response = AuthResponse {
@@ -204,6 +238,7 @@ pub async fn verify_2fa(
         resp.cookie(cookie);
     }
     Ok(resp
+        .cookie(AuthCookies::clear_two_factor_challenge(secure))
         .cookie(AuthCookies::access_token(
             &tokens.access_token,
             secure,
@@ -212,7 +247,7 @@ pub async fn verify_2fa(
         .cookie(AuthCookies::refresh_token(
             &tokens.refresh_token,
             secure,
-            true,
+            tokens.remember,
             cookie_domain,
         ))
         .json(crate::responses::ApiResponse {
@@ -223,12 +258,14 @@ pub async fn verify_2fa(
 }
 
 /// DELETE /v1/auth/2fa
-/// Disable 2FA (authenticated, requires password, blocked for admins)
+/// Disable 2FA (recent sign-in, requires password, blocked for admins)
 pub async fn disable_2fa(
     req: HttpRequest,
     pool: web::Data<PgPool>,
-    user: AuthenticatedUser,
+    audit: web::Data<AuditConfig>,
+    user: RecentlyAuthenticatedUser,
     totp_service: web::Data<Arc<TotpService>>,
+    password_service: web::Data<Arc<PasswordService>>,
     body: web::Json<PasswordConfirmRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -244,22 +281,31 @@ pub async fn disable_2fa(
         .await?
         .ok_or(AppError::not_found("User"))?;
 
-    let password_hash = db_user.password_hash.as_ref().ok_or(AppError::validation(
-        "password",
-        "No password set for this account",
-    ))?;
+    let password_hash = db_user
+        .password_hash
+        .as_ref()
+        .ok_or(AppError::validation_coded(
+            "password",
+            "password_not_set",
+            "No password set for this account",
+        ))?;
 
-    let password_service = PasswordService::new();
     if !password_service.verify(&body.password, password_hash)? {
-        return Err(AppError::validation("password", "Invalid password"));
+        return Err(AppError::validation_coded(
+            "password",
+            "password_incorrect",
+            "Invalid password",
+        ));
     }
 
     totp_service.disable(user.0.sub).await?;
+    force_token_refresh(&req, user.0.sub, &AuditAction::TwoFactorDisabled).await;
 
     // Audit log
     let ip = ip_address.map(ipnetwork::IpNetwork::from);
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::TwoFactorDisabled)
             .with_actor(user.0.sub, &user.0.email, &user.0.role)
             .with_ip(ip),
@@ -274,8 +320,10 @@ pub async fn disable_2fa(
 pub async fn regenerate_recovery_codes(
     req: HttpRequest,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     user: AuthenticatedUser,
     totp_service: web::Data<Arc<TotpService>>,
+    password_service: web::Data<Arc<PasswordService>>,
     body: web::Json<PasswordConfirmRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -286,14 +334,21 @@ pub async fn regenerate_recovery_codes(
         .await?
         .ok_or(AppError::not_found("User"))?;
 
-    let password_hash = db_user.password_hash.as_ref().ok_or(AppError::validation(
-        "password",
-        "No password set for this account",
-    ))?;
+    let password_hash = db_user
+        .password_hash
+        .as_ref()
+        .ok_or(AppError::validation_coded(
+            "password",
+            "password_not_set",
+            "No password set for this account",
+        ))?;
 
-    let password_service = PasswordService::new();
     if !password_service.verify(&body.password, password_hash)? {
-        return Err(AppError::validation("password", "Invalid password"));
+        return Err(AppError::validation_coded(
+            "password",
+            "password_incorrect",
+            "Invalid password",
+        ));
     }
 
     let codes = totp_service.regenerate_recovery_codes(user.0.sub).await?;
@@ -302,6 +357,7 @@ pub async fn regenerate_recovery_codes(
     let ip = ip_address.map(ipnetwork::IpNetwork::from);
     AuditLogRepository::create(
         &pool,
+        &audit,
         CreateAuditLog::new(AuditAction::TwoFactorRecoveryCodesRegenerated)
             .with_actor(user.0.sub, &user.0.email, &user.0.role)
             .with_ip(ip),
@@ -8,12 +8,26 @@ use sqlx::PgPool;
 use std::sync::Arc;
 use tokio;
 
+use crate::config::{AuditConfig, PaginationConfig};
 use crate::errors::AppError;
-use crate::middleware::{extract_client_ip, AuthCookies, AuthenticatedUser};
-use crate::models::{AuditAction, CreateAuditLog, SubscriptionTier, UserResponse};
-use crate::repositories::{AuditLogRepository, TokenRepository, UserRepository};
-use crate::responses::{get_request_id, success, success_no_data};
-use crate::services::{AuthService, EmailService, PasswordService, StripeService, TotpService};
+use crate::middleware::{
+    extract_client_ip, record_rate_limit_usage, AuthCookies, AuthenticatedUser,
+    RecentlyAuthenticatedUser,
+};
+use crate::models::{
+    AuditAction, CreateAuditLog, RateLimitConfig, RefreshToken, SessionInfo, SubscriptionTier,
+    UserResponse,
+};
+use crate::pagination::resolve_page;
+use crate::repositories::{
+    AuditLogRepository, RateLimitRepository, TokenRepository, UserRepository,
+};
+use crate::responses::{created, get_request_id, paginated, success, success_no_data};
+use crate::services::data_export::collect_user_data;
+use crate::services::{
+    AuthService, DataExportService, EmailService, JwtService, PasswordService, StripeService,
+    TotpService,
+};
 use crate::validation::validate_email;
 
 /// Request body for deleting account
@@ -49,6 +63,13 @@ pub struct ConfirmEmailVerificationBody {
     pub token: String,
 }
 
+/// Query string of a signed export download URL
+#[derive(Debug, Deserialize)]
+pub struct ExportDownloadQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
 /// GET /v1/users/me
 /// Get current user profile
 pub async fn get_current_user(
@@ -59,11 +80,14 @@ pub async fn get_current_user(
     let request_id = get_request_id(&req);
 
     // Get fresh user data from database
-    let user = UserRepository::find_by_id(&pool, user.0.sub)
+    let current = UserRepository::find_by_id(&pool, user.0.sub)
         .await?
         .ok_or(AppError::not_found("User"))?;
 
-    Ok(success(UserResponse::from(user), request_id))
+    let mut response = UserResponse::from(current);
+    response.impersonated_by = user.0.impersonator_email.clone();
+
+    Ok(success(response, request_id))
 }
 
 /// PUT /v1/users/me/password
@@ -99,35 +123,75 @@ pub async fn change_password(
     Ok(success_no_data(request_id))
 }
 
+/// Query parameters for listing sessions
+#[derive(Debug, Deserialize)]
+pub struct ListSessionsQuery {
+    pub page: Option<i32>,
+    pub per_page: Option<i32>,
+    /// Case-insensitive substring of the session's device info (user agent)
+    pub device: Option<String>,
+    /// IP address or CIDR range the session was created from
+    pub ip: Option<String>,
+}
+
 /// GET /v1/users/me/sessions
-/// List active sessions for current user
+/// List active sessions for current user with pagination
 pub async fn list_sessions(
     req: HttpRequest,
     user: AuthenticatedUser,
     pool: web::Data<PgPool>,
+    pagination: web::Data<PaginationConfig>,
+    query: web::Query<ListSessionsQuery>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
 
-    let tokens = TokenRepository::find_active_refresh_tokens_for_user(&pool, user.0.sub).await?;
+    let (page, per_page) = resolve_page(query.page, query.per_page, 20, pagination.max_offset)?;
+    let device = query
+        .device
+        .as_deref()
+        .map(str::trim)
+        .filter(|d| !d.is_empty());
+    let ip = match query
+        .ip
+        .as_deref()
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+    {
+        Some(ip) => Some(
+            ip.parse::<ipnetwork::IpNetwork>()
+                .map_err(|_| AppError::validation("ip", "Invalid IP address or CIDR range"))?,
+        ),
+        None => None,
+    };
+
+    let (tokens, total) = TokenRepository::find_active_refresh_tokens_paginated(
+        &pool, user.0.sub, page, per_page, device, ip,
+    )
+    .await?;
+
+    // The session making this request is the one whose refresh cookie it carries
+    let current_hash = req
+        .cookie("refresh_token")
+        .zip(req.app_data::<Arc<JwtService>>())
+        .map(|(cookie, jwt)| jwt.hash_token(cookie.value()));
+
+    let sessions = session_infos(tokens, current_hash.as_deref());
+
+    Ok(paginated(sessions, total, page, per_page, request_id))
+}
 
-    // Map to response format (hide sensitive fields)
-    let sessions: Vec<_> = tokens
+/// Convert refresh tokens to `SessionInfo`, flagging the one hashing to `current_hash`
+fn session_infos(tokens: Vec<RefreshToken>, current_hash: Option<&str>) -> Vec<SessionInfo> {
+    tokens
         .into_iter()
-        .map(|t| {
-            serde_json::json!({
-                "id": t.id,
-                "device_info": t.device_info,
-                "ip_address": t.ip_address.map(|ip| ip.to_string()),
-                "created_at": t.created_at,
-                "last_used_at": t.last_used_at,
-            })
+        .map(|token| {
+            let is_current = current_hash == Some(token.token_hash.as_str());
+            SessionInfo {
+                is_current,
+                ..SessionInfo::from(token)
+            }
         })
-        .collect();
-
-    Ok(success(
-        serde_json::json!({ "sessions": sessions }),
-        request_id,
-    ))
+        .collect()
 }
 
 /// DELETE /v1/users/me/sessions/{session_id}
@@ -157,10 +221,10 @@ pub async fn revoke_session(
 }
 
 /// POST /v1/users/me/email
-/// Request email change
+/// Request email change (recent sign-in required)
 pub async fn request_email_change(
     req: HttpRequest,
-    user: AuthenticatedUser,
+    user: RecentlyAuthenticatedUser,
     auth_service: web::Data<Arc<AuthService>>,
     email_service: web::Data<Arc<EmailService>>,
     body: web::Json<RequestEmailChangeBody>,
@@ -319,15 +383,16 @@ pub async fn confirm_email_verification(
 }
 
 /// DELETE /v1/users/me
-/// Delete current user's account (soft delete)
+/// Delete current user's account (soft delete, recent sign-in required)
 pub async fn delete_account(
     req: HttpRequest,
-    user: AuthenticatedUser,
+    user: RecentlyAuthenticatedUser,
     pool: web::Data<PgPool>,
     config: web::Data<crate::config::Config>,
     totp_service: web::Data<Arc<TotpService>>,
     stripe_service: web::Data<Arc<StripeService>>,
     oidc_provider: web::Data<Option<Arc<crate::services::oidc_provider::OidcProvider>>>,
+    password_service: web::Data<Arc<PasswordService>>,
     body: web::Json<DeleteAccountRequest>,
 ) -> Result<HttpResponse, AppError> {
     let request_id = get_request_id(&req);
@@ -339,33 +404,46 @@ pub async fn delete_account(
         .ok_or(AppError::not_found("User"))?;
 
     // Verify password
-    let password_hash = db_user.password_hash.as_ref().ok_or(AppError::validation(
-        "password",
-        "No password set for this account",
-    ))?;
+    let password_hash = db_user
+        .password_hash
+        .as_ref()
+        .ok_or(AppError::validation_coded(
+            "password",
+            "password_not_set",
+            "No password set for this account",
+        ))?;
 
-    let password_service = PasswordService::new();
     if !password_service.verify(&body.password, password_hash)? {
-        return Err(AppError::validation("password", "Invalid password"));
+        return Err(AppError::validation_coded(
+            "password",
+            "password_incorrect",
+            "Invalid password",
+        ));
     }
 
     // If 2FA is enabled, require and verify TOTP code
     if db_user.two_factor_enabled {
         let totp_code = body.totp_code.as_deref().ok_or_else(|| {
-            AppError::validation("totp_code", "Two-factor authentication code is required")
+            AppError::validation_coded(
+                "totp_code",
+                "totp_code_required",
+                "Two-factor authentication code is required",
+            )
         })?;
 
         if totp_code.is_empty() {
-            return Err(AppError::validation(
+            return Err(AppError::validation_coded(
                 "totp_code",
+                "totp_code_required",
                 "Two-factor authentication code is required",
             ));
         }
 
         let valid = totp_service.verify_code(user.0.sub, totp_code).await?;
         if !valid {
-            return Err(AppError::validation(
+            return Err(AppError::validation_coded(
                 "totp_code",
+                "totp_code_invalid",
                 "Invalid two-factor authentication code",
             ));
         }
@@ -388,15 +466,16 @@ pub async fn delete_account(
     }
 
     // Soft-delete the user
-    UserRepository::soft_delete(&pool, user.0.sub).await?;
+    UserRepository::soft_delete(&pool, user.0.sub, None).await?;
 
     // Revoke all refresh tokens
-    TokenRepository::revoke_all_user_refresh_tokens(&pool, user.0.sub).await?;
+    TokenRepository::revoke_all_user_refresh_tokens(pool.get_ref(), user.0.sub).await?;
 
     // Audit log
     let ip = ip_address.map(ipnetwork::IpNetwork::from);
     AuditLogRepository::create(
         &pool,
+        &config.audit,
         CreateAuditLog::new(AuditAction::UserAccountDeleted)
             .with_actor(user.0.sub, &user.0.email, &user.0.role)
             .with_resource("user", user.0.sub)
@@ -420,7 +499,7 @@ pub async fn delete_account(
     }
 
     // Clear auth cookies and return success
-    let secure = config.is_production();
+    let secure = config.cookie_secure;
     let cookie_domain = config.cookie_domain.as_deref();
 
     let mut response = HttpResponse::Ok().json(crate::responses::ApiResponse::<()> {
@@ -435,3 +514,150 @@ pub async fn delete_account(
 
     Ok(response)
 }
+
+/// POST /v1/users/me/export
+/// Queue an export of everything held about the current user. While an
+/// export is already being built, its status is returned instead.
+pub async fn request_data_export(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
+    exports: web::Data<Arc<DataExportService>>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let user_id = user.0.sub;
+
+    let limit = RateLimitConfig::DATA_EXPORT;
+    let user_key = user_id.to_string();
+    let (count, exceeded) =
+        RateLimitRepository::check_and_increment(&pool, &user_key, &limit).await?;
+    record_rate_limit_usage(&req, &limit, count);
+    if exceeded {
+        let retry_after = RateLimitRepository::get_retry_after(&pool, &user_key, &limit).await?;
+        return Err(AppError::RateLimited { retry_after });
+    }
+
+    let (job, queued) = exports.queue(user_id).await?;
+    if !queued {
+        let status = exports.status(user_id, job.id).await?;
+        return Ok(success(status, request_id));
+    }
+
+    // Build in the background; the client polls the status endpoint
+    let build_pool = pool.get_ref().clone();
+    let build_exports = exports.get_ref().clone();
+    tokio::spawn(async move {
+        let bundle = collect_user_data(&build_pool, user_id).await;
+        if let Err(e) = build_exports.finish(job.id, bundle).await {
+            tracing::error!(error = %e, export_id = %job.id, "Failed to record data export result");
+        }
+    });
+
+    let ip = extract_client_ip(&req).map(ipnetwork::IpNetwork::from);
+    AuditLogRepository::create(
+        &pool,
+        &audit,
+        CreateAuditLog::new(AuditAction::UserDataExportRequested)
+            .with_actor(user_id, &user.0.email, &user.0.role)
+            .with_resource("data_export", job.id)
+            .with_ip(ip),
+    )
+    .await?;
+
+    let status = exports.status(user_id, job.id).await?;
+    Ok(created(status, request_id))
+}
+
+/// GET /v1/users/me/export/status/{job_id}
+/// Status of an export, with a short-lived download URL once it is ready
+pub async fn get_data_export_status(
+    req: HttpRequest,
+    user: AuthenticatedUser,
+    exports: web::Data<Arc<DataExportService>>,
+    path: web::Path<uuid::Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = get_request_id(&req);
+    let status = exports.status(user.0.sub, path.into_inner()).await?;
+    Ok(success(status, request_id))
+}
+
+/// GET /v1/users/me/export/download/{job_id}?expires=&signature=
+/// Download a ready export. The signature is the credential, so no session is needed.
+pub async fn download_data_export(
+    exports: web::Data<Arc<DataExportService>>,
+    path: web::Path<uuid::Uuid>,
+    query: web::Query<ExportDownloadQuery>,
+) -> Result<HttpResponse, AppError> {
+    let job_id = path.into_inner();
+    let bundle = exports
+        .download(job_id, query.expires, &query.signature)
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"a8n-export-{}.json\"", job_id),
+        ))
+        .insert_header(("Cache-Control", "no-store"))
+        .json(bundle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pagination::{Page, PerPage};
+    use chrono::{Duration, Utc};
+
+    fn token(hash: &str, device_info: Option<&str>) -> RefreshToken {
+        RefreshToken {
+            id: uuid::Uuid::new_v4(),
+            user_id: uuid::Uuid::new_v4(),
+            token_hash: hash.to_string(),
+            device_info: device_info.map(String::from),
+            ip_address: Some("203.0.113.7".parse().unwrap()),
+            expires_at: Utc::now() + Duration::days(7),
+            created_at: Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+            replaced_by: None,
+            remember: true,
+            device_id: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn sessions_response_deserializes_into_session_info() {
+        let tokens = vec![
+            token(
+                "hash-current",
+                Some("Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0"),
+            ),
+            token("hash-other", None),
+        ];
+        let ids: Vec<_> = tokens.iter().map(|t| t.id).collect();
+
+        let resp = paginated(
+            session_infos(tokens, Some("hash-current")),
+            2,
+            Page::FIRST,
+            PerPage::new(20).unwrap(),
+            "req-1".to_string(),
+        );
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert!(json["data"]["items"][0].get("token_hash").is_none());
+        let items: Vec<SessionInfo> =
+            serde_json::from_value(json["data"]["items"].clone()).unwrap();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].id, ids[0]);
+        assert!(items[0].is_current);
+        assert_eq!(items[0].browser.as_deref(), Some("Firefox"));
+        assert_eq!(items[0].os.as_deref(), Some("Linux"));
+        assert_eq!(items[0].ip_address.as_deref(), Some("203.0.113.7/32"));
+        assert!(!items[1].is_current);
+        assert_eq!(items[1].browser, None);
+    }
+}
@@ -3,24 +3,28 @@
 //! This module contains HTTP handlers for external webhooks (Stripe, etc.)
 
 use actix_web::{web, HttpRequest, HttpResponse};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use sqlx::PgPool;
 use std::sync::Arc;
 
-use crate::config::TierConfig;
+use crate::config::{AuditConfig, TierConfig};
 use crate::errors::AppError;
 use crate::models::{
-    AuditAction, AuditSeverity, CreateAuditLog, MembershipStatus, SubscriptionTier,
+    AuditAction, AuditSeverity, CreateAuditLog, Currency, MembershipStatus, PaymentCard,
 };
-use crate::repositories::{AuditLogRepository, UserRepository};
-use crate::services::{EmailService, StripeService};
+use crate::repositories::{
+    AuditLogRepository, PaymentMethodRepository, UserRepository, WebhookEventRepository,
+};
+use crate::services::membership::{membership_status_for_subscription, resolve_tier_for_product};
+use crate::services::{EmailService, JwtService, StripeService};
 
-/// POST /v1/webhooks/stripe
+/// POST /v1/webhooks/{STRIPE_WEBHOOK_PATH} (default `stripe`)
 /// Handle Stripe webhook events
 pub async fn stripe_webhook(
     req: HttpRequest,
     body: web::Bytes,
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditConfig>,
     stripe: web::Data<Arc<StripeService>>,
     email: web::Data<Arc<EmailService>>,
     tier_config: web::Data<Arc<std::sync::RwLock<TierConfig>>>,
@@ -46,7 +50,25 @@ pub async fn stripe_webhook(
         .as_str()
         .ok_or(AppError::validation("type", "Missing event type"))?;
 
-    tracing::info!(event_type = %event_type, "Processing Stripe webhook");
+    let event_id = event["id"]
+        .as_str()
+        .ok_or(AppError::validation("id", "Missing event ID"))?;
+
+    // Stripe may deliver the same event more than once; acknowledge repeats
+    // without running the handlers again. A concurrent redelivery waits here
+    // until this one finishes.
+    let Some(claim) = WebhookEventRepository::claim(&pool, event_id).await? else {
+        tracing::info!(
+            event_id = %event_id,
+            event_type = %event_type,
+            "Skipping already processed Stripe webhook"
+        );
+        return Ok(HttpResponse::Ok().finish());
+    };
+
+    tracing::info!(event_id = %event_id, event_type = %event_type, "Processing Stripe webhook");
+
+    let jwt = req.app_data::<Arc<JwtService>>().map(|jwt| jwt.as_ref());
 
     let tc = tier_config
         .read()
@@ -54,29 +76,37 @@ pub async fn stripe_webhook(
         .clone();
 
     // Route to appropriate handler
-    match event_type {
+    let result = match event_type {
         "checkout.session.completed" => {
-            handle_checkout_completed(&event, &pool, &email).await?;
+            handle_checkout_completed(&event, &pool, &audit, &stripe, &email, jwt).await
         }
         "customer.subscription.created" => {
-            handle_subscription_created(&event, &pool, &tc).await?;
+            handle_subscription_created(&event, &pool, &audit, &tc, jwt).await
         }
         "customer.subscription.updated" => {
-            handle_subscription_updated(&event, &pool, &tc).await?;
+            handle_subscription_updated(&event, &pool, &audit, &tc, jwt).await
         }
         "customer.subscription.deleted" => {
-            handle_subscription_deleted(&event, &pool, &email).await?;
+            handle_subscription_deleted(&event, &pool, &audit, &email, jwt).await
         }
         "invoice.payment_succeeded" => {
-            handle_payment_succeeded(&event, &pool, &email).await?;
-        }
-        "invoice.payment_failed" => {
-            handle_payment_failed(&event, &pool, &email).await?;
+            handle_payment_succeeded(&event, &pool, &audit, &email, jwt).await
         }
+        "invoice.payment_failed" => handle_payment_failed(&event, &pool, &audit, &email, jwt).await,
+        "payment_method.attached" => handle_payment_method_attached(&event, &pool, &stripe).await,
+        "payment_method.updated" => handle_payment_method_updated(&event, &pool).await,
+        "payment_method.detached" => handle_payment_method_detached(&event, &pool).await,
+        "customer.updated" => handle_customer_updated(&event, &pool, &stripe).await,
         _ => {
             tracing::debug!(event_type = %event_type, "Unhandled Stripe event type");
+            Ok(())
         }
-    }
+    };
+
+    // Only a successful run is recorded; on failure the claim is dropped
+    // unrecorded so Stripe's retry processes the event again
+    result?;
+    WebhookEventRepository::complete(claim, event_type).await?;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -84,10 +114,17 @@ pub async fn stripe_webhook(
 async fn handle_checkout_completed(
     event: &serde_json::Value,
     pool: &PgPool,
+    audit: &AuditConfig,
+    stripe: &StripeService,
     email: &EmailService,
+    jwt: Option<&JwtService>,
 ) -> Result<(), AppError> {
     let session = &event["data"]["object"];
 
+    if session["mode"].as_str() == Some("setup") {
+        return handle_setup_checkout_completed(session, stripe).await;
+    }
+
     // Get user ID from metadata
     let user_id_str = session["metadata"]["user_id"]
         .as_str()
@@ -107,7 +144,17 @@ async fn handle_checkout_completed(
     };
 
     // Update user membership status and lock price
-    UserRepository::update_membership_status(pool, user_id, MembershipStatus::Active).await?;
+    if !apply_membership_status(
+        pool,
+        user_id,
+        MembershipStatus::Active,
+        stripe_created(session),
+        "checkout.session.completed",
+    )
+    .await?
+    {
+        return Ok(());
+    }
 
     // Lock the price for life
     let price_id = session["subscription"]
@@ -118,6 +165,7 @@ async fn handle_checkout_completed(
     UserRepository::lock_price(pool, user_id, &price_id, amount).await?;
 
     tracing::info!(user_id = %user_id, "Checkout completed, membership activated");
+    force_refresh(jwt, user_id, &AuditAction::MembershipCreated).await;
 
     // Send welcome email and audit log
     if let Ok(Some(user)) = UserRepository::find_by_id(pool, user_id).await {
@@ -132,7 +180,7 @@ async fn handle_checkout_completed(
                 "source": "stripe_checkout",
                 "amount": amount,
             }));
-        if let Err(e) = AuditLogRepository::create(pool, audit_log).await {
+        if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
             tracing::error!(error = %e, user_id = %user_id, "Failed to create audit log for checkout");
         }
     }
@@ -140,10 +188,57 @@ async fn handle_checkout_completed(
     Ok(())
 }
 
+/// A setup-mode checkout only saved a card: attach it and start the
+/// subscription. Membership status then follows from the resulting
+/// `customer.subscription.created` event like any other subscription.
+async fn handle_setup_checkout_completed(
+    session: &serde_json::Value,
+    stripe: &StripeService,
+) -> Result<(), AppError> {
+    let user_id: uuid::Uuid = session["metadata"]["user_id"]
+        .as_str()
+        .ok_or(AppError::validation("metadata", "Missing user_id"))?
+        .parse()
+        .map_err(|_| AppError::validation("user_id", "Invalid UUID"))?;
+
+    let price_id = session["metadata"]["price_id"]
+        .as_str()
+        .ok_or(AppError::validation("metadata", "Missing price_id"))?;
+
+    let customer_id = session["customer"]
+        .as_str()
+        .ok_or(AppError::validation("customer", "Missing customer ID"))?;
+
+    let setup_intent_id = session["setup_intent"]
+        .as_str()
+        .ok_or(AppError::validation("setup_intent", "Missing setup intent"))?;
+
+    let trial_days = session["metadata"]["trial_days"]
+        .as_str()
+        .and_then(|d| d.parse().ok());
+
+    let subscription_id = stripe
+        .subscribe_with_setup_intent(customer_id, setup_intent_id, price_id, trial_days, user_id)
+        .await?;
+
+    tracing::info!(
+        user_id = %user_id,
+        subscription_id = %subscription_id,
+        "Setup checkout completed, subscription created"
+    );
+
+    Ok(())
+}
+
+/// Subscription rows are not stored locally (see the stripe_overhaul
+/// migration), so a redelivered `customer.subscription.created` only rewrites
+/// the same status/tier columns on the user and cannot duplicate memberships.
 async fn handle_subscription_created(
     event: &serde_json::Value,
     pool: &PgPool,
+    audit: &AuditConfig,
     tc: &TierConfig,
+    jwt: Option<&JwtService>,
 ) -> Result<(), AppError> {
     let subscription = &event["data"]["object"];
 
@@ -172,14 +267,33 @@ async fn handle_subscription_created(
         .as_i64()
         .unwrap_or(300) as i32;
 
+    let status = subscription["status"].as_str().unwrap_or("active");
+    let trial_end = subscription_trial_end(subscription);
+
     // Resolve tier from product ID mapping (None means no match — leave tier unchanged)
     let resolved_tier = resolve_tier_for_product(product_id, tc);
 
+    let user_status = membership_status_for_subscription(status, trial_end, Utc::now());
+
     let mut tx = pool.begin().await?;
-    UserRepository::update_membership_status(&mut *tx, user.id, MembershipStatus::Active).await?;
+    if !apply_membership_status(
+        &mut *tx,
+        user.id,
+        user_status,
+        stripe_created(subscription),
+        "customer.subscription.created",
+    )
+    .await?
+    {
+        return Ok(());
+    }
     if let Some(ref tier) = resolved_tier {
         UserRepository::upgrade_subscription_tier(&mut *tx, user.id, tier).await?;
     }
+    // Tier upgrade clears the trial; a Stripe trial sets it back to trial_end
+    if status == "trialing" {
+        UserRepository::set_trial_ends_at(&mut *tx, user.id, trial_end).await?;
+    }
     tx.commit().await?;
 
     tracing::info!(
@@ -188,6 +302,7 @@ async fn handle_subscription_created(
         resolved_tier = ?resolved_tier,
         "Subscription created"
     );
+    force_refresh(jwt, user.id, &AuditAction::MembershipCreated).await;
 
     let audit_log = CreateAuditLog::new(AuditAction::MembershipCreated)
         .with_actor(user.id, &user.email, &user.role)
@@ -197,9 +312,11 @@ async fn handle_subscription_created(
             "stripe_price_id": price_id,
             "stripe_product_id": product_id,
             "amount": amount,
+            "status": status,
+            "trial_end": trial_end,
             "resolved_tier": resolved_tier.as_ref().map(|t| t.as_str()),
         }));
-    if let Err(e) = AuditLogRepository::create(pool, audit_log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
         tracing::error!(error = %e, user_id = %user.id, "Failed to create audit log for subscription created");
     }
 
@@ -209,7 +326,9 @@ async fn handle_subscription_created(
 async fn handle_subscription_updated(
     event: &serde_json::Value,
     pool: &PgPool,
+    audit: &AuditConfig,
     tc: &TierConfig,
+    jwt: Option<&JwtService>,
 ) -> Result<(), AppError> {
     let subscription = &event["data"]["object"];
 
@@ -222,6 +341,7 @@ async fn handle_subscription_updated(
         .ok_or(AppError::validation("customer", "Missing customer ID"))?;
 
     let status = subscription["status"].as_str().unwrap_or("active");
+    let trial_end = subscription_trial_end(subscription);
 
     let cancel_at_period_end = subscription["cancel_at_period_end"]
         .as_bool()
@@ -237,20 +357,28 @@ async fn handle_subscription_updated(
 
     // Find user by customer ID
     if let Some(user) = UserRepository::find_by_stripe_customer_id(pool, customer_id).await? {
-        let user_status = match status {
-            "active" => MembershipStatus::Active,
-            "past_due" => MembershipStatus::PastDue,
-            "canceled" => MembershipStatus::Canceled,
-            _ => MembershipStatus::Active,
-        };
+        let user_status = membership_status_for_subscription(status, trial_end, Utc::now());
 
         let resolved_tier = resolve_tier_for_product(product_id, tc);
 
         let mut tx = pool.begin().await?;
-        UserRepository::update_membership_status(&mut *tx, user.id, user_status).await?;
+        if !apply_membership_status(
+            &mut *tx,
+            user.id,
+            user_status,
+            stripe_created(subscription),
+            "customer.subscription.updated",
+        )
+        .await?
+        {
+            return Ok(());
+        }
         if let Some(ref tier) = resolved_tier {
             UserRepository::upgrade_subscription_tier(&mut *tx, user.id, tier).await?;
         }
+        if status == "trialing" {
+            UserRepository::set_trial_ends_at(&mut *tx, user.id, trial_end).await?;
+        }
         tx.commit().await?;
 
         tracing::info!(
@@ -262,11 +390,12 @@ async fn handle_subscription_updated(
         // Audit log
         let action = if cancel_at_period_end {
             AuditAction::MembershipCanceled
-        } else if status == "active" {
+        } else if matches!(status, "active" | "trialing") {
             AuditAction::MembershipReactivated
         } else {
             AuditAction::MembershipCanceled
         };
+        force_refresh(jwt, user.id, &action).await;
 
         let audit_log = CreateAuditLog::new(action)
             .with_actor(user.id, &user.email, &user.role)
@@ -274,12 +403,13 @@ async fn handle_subscription_updated(
             .with_metadata(serde_json::json!({
                 "stripe_subscription_id": stripe_subscription_id,
                 "status": status,
+                "trial_end": trial_end,
                 "cancel_at_period_end": cancel_at_period_end,
                 "stripe_price_id": price_id,
                 "stripe_product_id": product_id,
                 "resolved_tier": resolved_tier.as_ref().map(|t| t.as_str()),
             }));
-        if let Err(e) = AuditLogRepository::create(pool, audit_log).await {
+        if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
             tracing::error!(error = %e, "Failed to create audit log for subscription update");
         }
     }
@@ -290,7 +420,9 @@ async fn handle_subscription_updated(
 async fn handle_subscription_deleted(
     event: &serde_json::Value,
     pool: &PgPool,
+    audit: &AuditConfig,
     email: &EmailService,
+    jwt: Option<&JwtService>,
 ) -> Result<(), AppError> {
     let subscription = &event["data"]["object"];
 
@@ -319,6 +451,7 @@ async fn handle_subscription_deleted(
         UserRepository::reset_subscription_tier(&mut *tx, user.id).await?;
         UserRepository::clear_grace_period(&mut *tx, user.id).await?;
         tx.commit().await?;
+        force_refresh(jwt, user.id, &AuditAction::MembershipCanceled).await;
 
         tracing::info!(
             user_id = %user.id,
@@ -341,7 +474,7 @@ async fn handle_subscription_deleted(
                 "source": "stripe_subscription_deleted",
                 "stripe_subscription_id": stripe_subscription_id,
             }));
-        if let Err(e) = AuditLogRepository::create(pool, audit_log).await {
+        if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
             tracing::error!(error = %e, user_id = %user.id, "Failed to create audit log for subscription deleted");
         }
     }
@@ -352,7 +485,9 @@ async fn handle_subscription_deleted(
 async fn handle_payment_succeeded(
     event: &serde_json::Value,
     pool: &PgPool,
+    audit: &AuditConfig,
     email: &EmailService,
+    jwt: Option<&JwtService>,
 ) -> Result<(), AppError> {
     let invoice = &event["data"]["object"];
 
@@ -370,15 +505,27 @@ async fn handle_payment_succeeded(
     };
 
     let amount = invoice["amount_paid"].as_i64().unwrap_or(0) as i32;
+    let currency = Currency::normalize_lenient(invoice["currency"].as_str().unwrap_or("usd"));
 
-    // Clear any grace period if exists
-    let had_grace_period = user.grace_period_start.is_some();
-    if had_grace_period {
+    // End any grace period, unless the membership can no longer become
+    // active (e.g. a late invoice for a canceled subscription)
+    let mut grace_period_ended = false;
+    if user.grace_period_start.is_some() {
         let mut tx = pool.begin().await?;
-        UserRepository::clear_grace_period(&mut *tx, user.id).await?;
-        UserRepository::update_membership_status(&mut *tx, user.id, MembershipStatus::Active)
-            .await?;
-        tx.commit().await?;
+        if apply_membership_status(
+            &mut *tx,
+            user.id,
+            MembershipStatus::Active,
+            None,
+            "invoice.payment_succeeded",
+        )
+        .await?
+        {
+            UserRepository::clear_grace_period(&mut *tx, user.id).await?;
+            tx.commit().await?;
+            force_refresh(jwt, user.id, &AuditAction::GracePeriodEnded).await;
+            grace_period_ended = true;
+        }
     }
 
     tracing::info!(
@@ -393,18 +540,18 @@ async fn handle_payment_succeeded(
         .with_resource("user", user.id)
         .with_metadata(serde_json::json!({
             "amount": amount,
-            "currency": "usd",
+            "currency": currency,
         }));
-    if let Err(e) = AuditLogRepository::create(pool, audit_log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
         tracing::error!(error = %e, user_id = %user.id, "Failed to create audit log for payment succeeded");
     }
 
     // Audit log for grace period ended
-    if had_grace_period {
+    if grace_period_ended {
         let audit_log = CreateAuditLog::new(AuditAction::GracePeriodEnded)
             .with_actor(user.id, &user.email, &user.role)
             .with_resource("user", user.id);
-        if let Err(e) = AuditLogRepository::create(pool, audit_log).await {
+        if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
             tracing::error!(error = %e, user_id = %user.id, "Failed to create audit log for grace period ended");
         }
     }
@@ -420,7 +567,9 @@ async fn handle_payment_succeeded(
 async fn handle_payment_failed(
     event: &serde_json::Value,
     pool: &PgPool,
+    audit: &AuditConfig,
     email: &EmailService,
+    jwt: Option<&JwtService>,
 ) -> Result<(), AppError> {
     let invoice = &event["data"]["object"];
 
@@ -438,6 +587,7 @@ async fn handle_payment_failed(
     };
 
     let amount = invoice["amount_due"].as_i64().unwrap_or(0) as i32;
+    let currency = Currency::normalize_lenient(invoice["currency"].as_str().unwrap_or("usd"));
 
     // Audit log for payment failure
     let audit_log = CreateAuditLog::new(AuditAction::PaymentFailed)
@@ -446,22 +596,33 @@ async fn handle_payment_failed(
         .with_severity(AuditSeverity::Warning)
         .with_metadata(serde_json::json!({
             "amount": amount,
-            "currency": "usd",
+            "currency": currency,
         }));
-    if let Err(e) = AuditLogRepository::create(pool, audit_log).await {
+    if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
         tracing::error!(error = %e, user_id = %user.id, "Failed to create audit log for payment failed");
     }
 
-    // Start grace period if not already started
+    // Start grace period if not already started (and the membership is not
+    // already canceled)
     if user.grace_period_start.is_none() {
         let now = Utc::now();
         let grace_end = now + Duration::days(30);
 
         let mut tx = pool.begin().await?;
+        if !apply_membership_status(
+            &mut *tx,
+            user.id,
+            MembershipStatus::GracePeriod,
+            None,
+            "invoice.payment_failed",
+        )
+        .await?
+        {
+            return Ok(());
+        }
         UserRepository::set_grace_period(&mut *tx, user.id, now, grace_end).await?;
-        UserRepository::update_membership_status(&mut *tx, user.id, MembershipStatus::GracePeriod)
-            .await?;
         tx.commit().await?;
+        force_refresh(jwt, user.id, &AuditAction::GracePeriodStarted).await;
 
         tracing::info!(
             user_id = %user.id,
@@ -477,7 +638,7 @@ async fn handle_payment_failed(
             .with_metadata(serde_json::json!({
                 "grace_period_end": grace_end.to_rfc3339(),
             }));
-        if let Err(e) = AuditLogRepository::create(pool, audit_log).await {
+        if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
             tracing::error!(error = %e, user_id = %user.id, "Failed to create audit log for grace period started");
         }
     }
@@ -490,18 +651,339 @@ async fn handle_payment_failed(
     Ok(())
 }
 
-/// Map a Stripe product ID to its corresponding `SubscriptionTier` using the current tier config.
-/// Returns `None` if the product ID does not match any configured mapping, meaning tier is left
-/// unchanged and only `subscription_status` is updated by the caller.
-fn resolve_tier_for_product(product_id: &str, tc: &TierConfig) -> Option<SubscriptionTier> {
-    if tc.lifetime_product_id.as_deref() == Some(product_id) {
-        return Some(SubscriptionTier::Lifetime);
+/// Show a newly attached card in the dashboard if it is the customer's default
+async fn handle_payment_method_attached(
+    event: &serde_json::Value,
+    pool: &PgPool,
+    stripe: &StripeService,
+) -> Result<(), AppError> {
+    let payment_method = &event["data"]["object"];
+
+    let customer_id = payment_method["customer"]
+        .as_str()
+        .ok_or(AppError::validation("customer", "Missing customer ID"))?;
+
+    let Some(card) = PaymentCard::from_stripe(payment_method) else {
+        tracing::debug!(customer_id = %customer_id, "Attached payment method is not a card");
+        return Ok(());
+    };
+
+    let Some(user) = UserRepository::find_by_stripe_customer_id(pool, customer_id).await? else {
+        tracing::warn!(customer_id = %customer_id, "User not found for payment method");
+        return Ok(());
+    };
+
+    // Only the default card is shown; customer.updated switches to a card
+    // that becomes the default later
+    let default_pm = stripe
+        .get_customer_default_payment_method(customer_id)
+        .await?;
+    if default_pm.as_deref() != Some(card.stripe_payment_method_id.as_str()) {
+        tracing::debug!(user_id = %user.id, "Attached payment method is not the default");
+        return Ok(());
     }
-    if tc.early_adopter_product_id.as_deref() == Some(product_id) {
-        return Some(SubscriptionTier::EarlyAdopter);
+
+    PaymentMethodRepository::upsert(pool, user.id, &card).await?;
+    tracing::info!(user_id = %user.id, brand = %card.brand, "Stored payment method");
+
+    Ok(())
+}
+
+/// Stop showing a card once it is removed from the customer. Stripe has
+/// already cleared `customer` on the detached object, so the stored row is
+/// found by payment method id.
+async fn handle_payment_method_detached(
+    event: &serde_json::Value,
+    pool: &PgPool,
+) -> Result<(), AppError> {
+    let payment_method_id = event["data"]["object"]["id"]
+        .as_str()
+        .ok_or(AppError::validation("id", "Missing payment method ID"))?;
+
+    if PaymentMethodRepository::delete_by_payment_method_id(pool, payment_method_id).await? {
+        tracing::info!(payment_method_id = %payment_method_id, "Removed detached payment method");
     }
-    if tc.standard_product_id.as_deref() == Some(product_id) {
-        return Some(SubscriptionTier::Standard);
+
+    Ok(())
+}
+
+/// Refresh the stored card's details (e.g. a new expiry from the card network)
+async fn handle_payment_method_updated(
+    event: &serde_json::Value,
+    pool: &PgPool,
+) -> Result<(), AppError> {
+    let payment_method = &event["data"]["object"];
+
+    let (Some(customer_id), Some(card)) = (
+        payment_method["customer"].as_str(),
+        PaymentCard::from_stripe(payment_method),
+    ) else {
+        return Ok(());
+    };
+
+    if let Some(user) = UserRepository::find_by_stripe_customer_id(pool, customer_id).await? {
+        // Updates to cards other than the one on file are not shown anywhere
+        PaymentMethodRepository::update_if_current(pool, user.id, &card).await?;
+    }
+
+    Ok(())
+}
+
+/// Show the customer's new default card when it changes
+async fn handle_customer_updated(
+    event: &serde_json::Value,
+    pool: &PgPool,
+    stripe: &StripeService,
+) -> Result<(), AppError> {
+    let customer = &event["data"]["object"];
+
+    let customer_id = customer["id"]
+        .as_str()
+        .ok_or(AppError::validation("id", "Missing customer ID"))?;
+
+    // Unexpanded this is just the ID; the card has to be fetched separately
+    let default_pm = &customer["invoice_settings"]["default_payment_method"];
+    let Some(payment_method_id) = default_pm.as_str().or(default_pm["id"].as_str()) else {
+        return Ok(());
+    };
+
+    let Some(user) = UserRepository::find_by_stripe_customer_id(pool, customer_id).await? else {
+        return Ok(());
+    };
+
+    let current = PaymentMethodRepository::find_by_user_id(pool, user.id).await?;
+    if current.is_some_and(|pm| pm.stripe_payment_method_id == payment_method_id) {
+        return Ok(());
+    }
+
+    let payment_method = if default_pm.is_object() {
+        default_pm.clone()
+    } else {
+        stripe.get_payment_method(payment_method_id).await?
+    };
+    if let Some(card) = PaymentCard::from_stripe(&payment_method) {
+        PaymentMethodRepository::upsert(pool, user.id, &card).await?;
+        tracing::info!(user_id = %user.id, brand = %card.brand, "Stored default payment method");
+    }
+
+    Ok(())
+}
+
+/// Move the user to `status` if that is a legal transition from their current
+/// status. Stripe can deliver and replay events out of order, so an illegal
+/// transition (e.g. an `active` event for a subscription created before the
+/// membership was canceled) is logged and ignored. `subscription_created` is
+/// the Stripe `created` time of the subscription (or checkout) behind the
+/// event, when the event carries one. Returns whether the status was applied.
+async fn apply_membership_status<'e, E>(
+    executor: E,
+    user_id: uuid::Uuid,
+    status: MembershipStatus,
+    subscription_created: Option<DateTime<Utc>>,
+    event_type: &str,
+) -> Result<bool, AppError>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let target = status.as_str();
+    let applied = UserRepository::transition_membership_status(
+        executor,
+        user_id,
+        status,
+        subscription_created,
+    )
+    .await?;
+    if !applied {
+        tracing::warn!(
+            user_id = %user_id,
+            target_status = %target,
+            event_type = %event_type,
+            "Ignoring illegal membership status transition"
+        );
+    }
+    Ok(applied)
+}
+
+/// Make the user's current access tokens refresh when `action` changes their
+/// claims; the webhook caller is Stripe, so there is no cookie to replace.
+async fn force_refresh(jwt: Option<&JwtService>, user_id: uuid::Uuid, action: &AuditAction) {
+    if let Some(jwt) = jwt {
+        jwt.claims_changed(user_id, action).await;
+    }
+}
+
+/// Stripe's `created` (unix seconds) on a subscription or checkout session.
+fn stripe_created(object: &serde_json::Value) -> Option<DateTime<Utc>> {
+    object["created"]
+        .as_i64()
+        .and_then(|ts| DateTime::from_timestamp(ts, 0))
+}
+
+/// Stripe's `trial_end` (unix seconds) on a subscription object, if any.
+fn subscription_trial_end(subscription: &serde_json::Value) -> Option<DateTime<Utc>> {
+    subscription["trial_end"]
+        .as_i64()
+        .and_then(|ts| DateTime::from_timestamp(ts, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    /// A Stripe client whose API calls go to `server`
+    fn mock_stripe(server: &wiremock::MockServer) -> StripeService {
+        StripeService::new(crate::services::StripeConfig::for_tests(&server.uri()))
+    }
+
+    /// Answer `GET /v1/customers/{customer_id}` with `default_pm` as the
+    /// customer's default payment method
+    async fn mock_customer(server: &wiremock::MockServer, customer_id: &str, default_pm: &str) {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        Mock::given(method("GET"))
+            .and(path(format!("/v1/customers/{}", customer_id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": customer_id,
+                "object": "customer",
+                "invoice_settings": { "default_payment_method": default_pm }
+            })))
+            .mount(server)
+            .await;
+    }
+
+    fn card_event(
+        event_type: &str,
+        pm_id: &str,
+        customer_id: Option<&str>,
+        exp_year: i64,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "type": event_type,
+            "data": { "object": {
+                "id": pm_id,
+                "object": "payment_method",
+                "customer": customer_id,
+                "type": "card",
+                "card": { "brand": "visa", "last4": "4242", "exp_month": 12, "exp_year": exp_year }
+            }}
+        })
+    }
+
+    async fn insert_customer(pool: &PgPool, customer_id: &str) -> uuid::Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO users (email, password_hash, stripe_customer_id) VALUES ($1, 'x', $2) RETURNING id",
+        )
+        .bind(format!("{}@example.com", customer_id))
+        .bind(customer_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn delete_user(pool: &PgPool, user_id: uuid::Uuid) {
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn attached_card_is_stored_and_read_back() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let customer_id = format!("cus_{}", uuid::Uuid::new_v4().simple());
+        let user_id = insert_customer(&pool, &customer_id).await;
+        let server = wiremock::MockServer::start().await;
+        mock_customer(&server, &customer_id, "pm_card_visa").await;
+        let stripe = mock_stripe(&server);
+
+        let attached = card_event(
+            "payment_method.attached",
+            "pm_card_visa",
+            Some(&customer_id),
+            2030,
+        );
+        handle_payment_method_attached(&attached, &pool, &stripe)
+            .await
+            .unwrap();
+        let updated = card_event(
+            "payment_method.updated",
+            "pm_card_visa",
+            Some(&customer_id),
+            2032,
+        );
+        handle_payment_method_updated(&updated, &pool)
+            .await
+            .unwrap();
+
+        let stored = PaymentMethodRepository::find_by_user_id(&pool, user_id)
+            .await
+            .unwrap();
+        delete_user(&pool, user_id).await;
+
+        let stored = stored.expect("card stored");
+        assert_eq!(stored.stripe_payment_method_id, "pm_card_visa");
+        assert_eq!(stored.brand, "visa");
+        assert_eq!(stored.last4, "4242");
+        assert_eq!((stored.exp_month, stored.exp_year), (12, 2032));
+    }
+
+    #[actix_rt::test]
+    async fn non_default_card_does_not_replace_the_shown_one_and_detach_removes_it() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let customer_id = format!("cus_{}", uuid::Uuid::new_v4().simple());
+        let user_id = insert_customer(&pool, &customer_id).await;
+        let server = wiremock::MockServer::start().await;
+        mock_customer(&server, &customer_id, "pm_default").await;
+        let stripe = mock_stripe(&server);
+
+        for pm_id in ["pm_default", "pm_spare"] {
+            let attached = card_event("payment_method.attached", pm_id, Some(&customer_id), 2030);
+            handle_payment_method_attached(&attached, &pool, &stripe)
+                .await
+                .unwrap();
+        }
+        let shown = PaymentMethodRepository::find_by_user_id(&pool, user_id)
+            .await
+            .unwrap();
+
+        // Stripe clears `customer` on a detached payment method
+        let detached = card_event("payment_method.detached", "pm_default", None, 2030);
+        handle_payment_method_detached(&detached, &pool)
+            .await
+            .unwrap();
+        let after_detach = PaymentMethodRepository::find_by_user_id(&pool, user_id)
+            .await
+            .unwrap();
+        delete_user(&pool, user_id).await;
+
+        assert_eq!(
+            shown.expect("default card stored").stripe_payment_method_id,
+            "pm_default"
+        );
+        assert!(after_detach.is_none());
+    }
+
+    #[test]
+    fn trial_end_is_read_from_subscription() {
+        let sub = serde_json::json!({ "status": "trialing", "trial_end": 1_700_000_000 });
+        assert_eq!(
+            subscription_trial_end(&sub),
+            DateTime::from_timestamp(1_700_000_000, 0)
+        );
+        assert_eq!(
+            subscription_trial_end(&serde_json::json!({ "trial_end": null })),
+            None
+        );
     }
-    None
 }
@@ -0,0 +1,238 @@
+//! Retrying HTTP client for external APIs (Stripe, etc.)
+
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+/// Header that makes a non-idempotent request (e.g. a Stripe POST) safe to retry
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// When and how often to retry a failed request
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled on each further retry
+    pub base_delay: Duration,
+    /// Upper bound on a single delay
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (0-based)
+    fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+}
+
+/// `reqwest::Client` with shared timeouts/pooling and a retry policy.
+///
+/// Only idempotent requests (GET, HEAD, PUT, DELETE, OPTIONS, or anything
+/// carrying an `Idempotency-Key` header) are retried, and only on timeouts,
+/// connection errors, 429 and 5xx responses.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    policy: RetryPolicy,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new(RetryPolicy::default())
+    }
+}
+
+impl HttpClient {
+    pub fn new(policy: RetryPolicy) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(20))
+            .pool_idle_timeout(Duration::from_secs(90))
+            .pool_max_idle_per_host(8)
+            .build()
+            .expect("Failed to build outbound HTTP client");
+
+        Self { client, policy }
+    }
+
+    /// The underlying client, for building requests
+    pub fn inner(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Send a request, retrying transient failures per the policy.
+    ///
+    /// After the last retry the final response (even a 5xx) or error is
+    /// returned, so callers keep their existing status handling.
+    pub async fn send(&self, builder: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let request = builder.build()?;
+        let retryable = request.method().is_idempotent()
+            || request.headers().contains_key(IDEMPOTENCY_KEY_HEADER);
+
+        let mut retry = 0;
+        loop {
+            // Streaming bodies can't be cloned; such requests get a single attempt.
+            let attempt = match request.try_clone() {
+                Some(clone) if retryable && retry < self.policy.max_retries => clone,
+                _ => return self.client.execute(request).await,
+            };
+
+            let outcome = self.client.execute(attempt).await;
+            let transient = match &outcome {
+                Ok(response) => is_transient_status(response.status()),
+                Err(e) => e.is_timeout() || e.is_connect(),
+            };
+            if !transient {
+                return outcome;
+            }
+
+            let delay = self.policy.delay(retry);
+            tracing::warn!(
+                method = %request.method(),
+                url = %request.url(),
+                status = ?outcome.as_ref().ok().map(|r| r.status()),
+                retry = retry + 1,
+                delay_ms = delay.as_millis() as u64,
+                "Retrying outbound HTTP request"
+            );
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn fast_client(max_retries: u32) -> HttpClient {
+        HttpClient::new(RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        })
+    }
+
+    #[actix_rt::test]
+    async fn retries_get_on_5xx_until_success() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let http = fast_client(2);
+        let resp = http
+            .send(http.inner().get(format!("{}/flaky", server.uri())))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn returns_last_5xx_when_retries_exhausted() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let http = fast_client(2);
+        let resp = http
+            .send(http.inner().delete(format!("{}/thing", server.uri())))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[actix_rt::test]
+    async fn post_is_retried_only_with_idempotency_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/plain"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/keyed"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let http = fast_client(2);
+        let plain = http
+            .send(http.inner().post(format!("{}/plain", server.uri())))
+            .await
+            .unwrap();
+        assert_eq!(plain.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let keyed = http
+            .send(
+                http.inner()
+                    .post(format!("{}/keyed", server.uri()))
+                    .header(IDEMPOTENCY_KEY_HEADER, "abc"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(keyed.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_rt::test]
+    async fn client_errors_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let http = fast_client(2);
+        let resp = http
+            .send(http.inner().get(format!("{}/missing", server.uri())))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn backoff_doubles_and_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(2), Duration::from_millis(300));
+        assert_eq!(policy.delay(10), Duration::from_millis(300));
+    }
+}
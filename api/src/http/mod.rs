@@ -0,0 +1,7 @@
+//! Outbound HTTP
+//!
+//! Shared client configuration for calls to external services.
+
+pub mod client;
+
+pub use client::{HttpClient, RetryPolicy};
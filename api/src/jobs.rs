@@ -0,0 +1,119 @@
+//! Scheduled background jobs
+//!
+//! Every replica spawns the same periodic jobs, so each tick's database work
+//! runs under a session-level advisory lock: the replica that wins the lock
+//! does the work and the others skip that tick.
+
+use sqlx::PgPool;
+use std::future::Future;
+
+/// Advisory lock key for the expired token, rate-limit and IP ban cleanup
+pub const MAINTENANCE_LOCK: i64 = 7_100_001;
+/// Advisory lock key for the closed feedback archive/purge
+pub const FEEDBACK_PURGE_LOCK: i64 = 7_100_003;
+/// Advisory lock key for dropping expired data export bundles
+pub const DATA_EXPORT_PURGE_LOCK: i64 = 7_100_004;
+/// Advisory lock key for canceling memberships whose grace period ran out
+pub const GRACE_PERIOD_EXPIRY_LOCK: i64 = 7_100_005;
+
+/// Run `job` only if `pg_try_advisory_lock(key)` succeeds, releasing the lock
+/// afterwards. Returns `Ok(None)` without running `job` when another session
+/// holds the lock.
+pub async fn with_advisory_lock<F, T, E>(pool: &PgPool, key: i64, job: F) -> Result<Option<T>, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: From<sqlx::Error>,
+{
+    // Session-level locks belong to a connection, so take and unlock on one
+    let mut conn = pool.acquire().await?;
+
+    let (acquired,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_lock($1)")
+        .bind(key)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !acquired {
+        tracing::debug!(key, "Advisory lock held elsewhere, skipping job");
+        return Ok(None);
+    }
+
+    let result = job.await;
+
+    let unlocked = sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(key)
+        .execute(&mut *conn)
+        .await;
+    if let Err(e) = unlocked {
+        // Closing the session is the only other way to drop the lock
+        tracing::error!(error = %e, key, "Failed to release advisory lock");
+        let _ = conn.close().await;
+    }
+
+    result.map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    //! DB-backed integration tests. Skipped when DATABASE_URL is unset.
+    use super::*;
+    use tokio::sync::oneshot;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    fn random_key() -> i64 {
+        rand::random::<i64>().abs()
+    }
+
+    #[actix_rt::test]
+    async fn concurrent_attempt_skips_while_lock_is_held() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let key = random_key();
+
+        let (started_tx, started_rx) = oneshot::channel::<()>();
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+
+        let first = with_advisory_lock(&pool, key, async {
+            let _ = started_tx.send(());
+            let _ = release_rx.await;
+            Ok::<_, sqlx::Error>("first")
+        });
+        let second = async {
+            let _ = started_rx.await;
+            let result =
+                with_advisory_lock(&pool, key, async { Ok::<_, sqlx::Error>("second") }).await;
+            let _ = release_tx.send(());
+            result
+        };
+
+        let (first, second) = tokio::join!(first, second);
+        assert_eq!(first.unwrap(), Some("first"));
+        assert_eq!(second.unwrap(), None);
+
+        // Released after the first job finished
+        let third = with_advisory_lock(&pool, key, async { Ok::<_, sqlx::Error>("third") })
+            .await
+            .unwrap();
+        assert_eq!(third, Some("third"));
+    }
+
+    #[actix_rt::test]
+    async fn lock_is_released_when_the_job_fails() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let key = random_key();
+
+        let failed: Result<Option<()>, sqlx::Error> =
+            with_advisory_lock(&pool, key, async { Err(sqlx::Error::RowNotFound) }).await;
+        assert!(failed.is_err());
+
+        let retried = with_advisory_lock(&pool, key, async { Ok::<_, sqlx::Error>(1) })
+            .await
+            .unwrap();
+        assert_eq!(retried, Some(1));
+    }
+}
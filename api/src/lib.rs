@@ -3,11 +3,20 @@
 //! This crate provides the core API functionality for the platform,
 //! including authentication, membership management, and application access.
 
+// Full Stripe object fixtures in the tests nest deeper than `json!` allows by default
+#![recursion_limit = "256"]
+
+pub mod clock;
 pub mod config;
 pub mod errors;
 pub mod handlers;
+pub mod http;
+pub mod jobs;
 pub mod middleware;
 pub mod models;
+pub mod pagination;
+pub mod preflight;
+pub mod redirect;
 pub mod repositories;
 pub mod responses;
 pub mod routes;
@@ -17,4 +26,4 @@ pub mod validation;
 // Re-export commonly used types
 pub use config::Config;
 pub use errors::AppError;
-pub use responses::{ApiResponse, ResponseMeta};
+pub use responses::{ApiResponse, ApiVersion, ResponseMeta};
@@ -3,8 +3,12 @@
 //! This is the entry point for the backend API server.
 
 use actix_cors::Cors;
-use actix_web::{middleware::Logger, web, App, HttpServer};
+use actix_web::{
+    middleware::{Compress, Condition, Logger},
+    web, App, HttpServer,
+};
 use sqlx::postgres::PgPoolOptions;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{error, info};
@@ -12,21 +16,28 @@ use tracing_actix_web::TracingLogger;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 use a8n_api::{
-    config::{Config, TierConfig},
+    config::{Argon2Config, Config, JwtSigningConfig, RetiredJwtKey, TierConfig},
+    jobs::{self, with_advisory_lock},
     middleware::{
         auto_ban::{self, AutoBanService},
         request_id::RequestIdMiddleware,
-        AutoBanMiddleware, SecurityHeaders,
+        AdminTwoFactorPolicy, ApiRateLimit, AutoBanMiddleware, CompressionPolicy, ErrorAlerts,
+        HttpsRedirect, ImpersonationHeader, JsonOrFormConfig, RateLimitWarning, SecurityHeaders,
+        StepUpPolicy,
     },
-    models::{CreateUser, UserRole},
-    repositories::{FeedbackRepository, RateLimitRepository, UserRepository},
+    models::{CreateUser, RateLimitConfig, TieredRateLimit, UserRole},
+    preflight,
+    repositories::{DataExportRepository, FeedbackRepository, UserRepository},
     routes,
     services::{
-        oidc_keys::OidcKeySet, oidc_provider::OidcProvider, AuthService, BlobCache, DownloadCache,
-        DownloadLimiter, EmailService, EncryptionKeySet, ForgejoClient, ForgejoRegistryClient,
+        oidc_keys::OidcKeySet, oidc_provider::OidcProvider, scheduler, AuthService, BlobCache,
+        CaptchaService, DataExportService, DownloadCache, DownloadLimiter, EmailService,
+        EncryptionKeySet, ErrorAlerter, ForgejoClient, ForgejoRegistryClient, HttpGeoResolver,
         JwtConfig, JwtService, ManifestCache, OciLimiter, OciTokenService, PasswordService,
-        ReleaseCache, StripeConfig, StripeService, TotpService, WebhookService,
+        PgExportStore, ReleaseCache, StripeConfig, StripeService, TotpService, WebhookAlertSink,
+        WebhookService,
     },
+    validation,
 };
 
 #[tokio::main]
@@ -68,6 +79,30 @@ async fn main() -> anyhow::Result<()> {
 
     info!("Database migrations completed successfully");
 
+    if let Some(pepper) = config.password_pepper.as_ref() {
+        info!(version = pepper.version, "Password pepper enabled");
+    }
+
+    if config.argon2 != Argon2Config::default() {
+        info!(
+            memory_kib = config.argon2.memory_kib,
+            iterations = config.argon2.iterations,
+            parallelism = config.argon2.parallelism,
+            "Custom Argon2 parameters"
+        );
+    }
+    let mut password_service =
+        PasswordService::with_pepper(config.password_pepper.clone()).with_params(config.argon2)?;
+    if let Some(path) = config.banned_passwords_file.as_deref() {
+        let banned = validation::load_banned_passwords(Path::new(path)).map_err(|e| {
+            error!(error = %e, path = %path, "Failed to read banned password list");
+            e
+        })?;
+        info!(count = banned.len(), "Banned password list loaded");
+        password_service = password_service.with_banned_passwords(banned);
+    }
+    let password_service = Arc::new(password_service);
+
     // Seed default admin if SETUP_DEFAULT_ADMIN is set and no admin exists
     if let Ok(setup_admin) = std::env::var("SETUP_DEFAULT_ADMIN") {
         let admin_emails = UserRepository::find_admin_emails(&pool).await?;
@@ -79,7 +114,6 @@ async fn main() -> anyhow::Result<()> {
             let email = email.trim();
             let password = password.trim();
 
-            let password_service = PasswordService::new();
             let password_hash = password_service.hash(password)?;
 
             let user = UserRepository::create(
@@ -98,25 +132,72 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
-    // Test database connection
-    sqlx::query("SELECT 1").execute(&pool).await.map_err(|e| {
-        error!(error = %e, "Database health check failed");
-        e
-    })?;
-
-    info!("Database health check passed");
-
-    // Initialize JWT service
+    // Initialize JWT service. JWT_SECRET also keys webhook and export
+    // signatures, so it is required whichever algorithm signs tokens.
     let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| {
         if config.is_production() {
             panic!("JWT_SECRET must be set in production");
         }
         "development-secret-key-min-32-chars-long!".to_string()
     });
-    let jwt_config = JwtConfig::from_secret(&jwt_secret, &config.app_name);
-    let jwt_service = Arc::new(JwtService::new(jwt_config.clone()));
+    // HS256 signs with the shared secret; RS256 and EdDSA sign with a private
+    // key so other services only need the public one to verify
+    let read_pem = |var: &str, path: &str| -> anyhow::Result<Vec<u8>> {
+        std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {var} {path}: {e}"))
+    };
+    let jwt_algorithm = config.jwt_signing.algorithm();
+    let jwt_config = match &config.jwt_signing {
+        JwtSigningConfig::Hs256 => JwtConfig::from_secret(&jwt_secret, &config.app_name),
+        JwtSigningConfig::Rs256 {
+            private_key_path,
+            public_key_path,
+        } => JwtConfig::from_rsa_pem(
+            &read_pem("JWT_PRIVATE_KEY_PATH", private_key_path)?,
+            &read_pem("JWT_PUBLIC_KEY_PATH", public_key_path)?,
+            &config.app_name,
+        )
+        .map_err(|e| anyhow::anyhow!("{}", e))?,
+        JwtSigningConfig::EdDsa {
+            private_key_path,
+            public_key_path,
+        } => JwtConfig::from_ed25519_pem(
+            &read_pem("JWT_PRIVATE_KEY_PATH", private_key_path)?,
+            &read_pem("JWT_PUBLIC_KEY_PATH", public_key_path)?,
+            &config.app_name,
+        )
+        .map_err(|e| anyhow::anyhow!("{}", e))?,
+    };
+    // After a rotation the previous key keeps verifying the tokens it signed
+    // until they expire: the old secret for HS256, the old public key otherwise
+    let retired_key = match &config.jwt_retired_key {
+        Some(RetiredJwtKey::Secret(secret)) => Some(secret.clone().into_bytes()),
+        Some(RetiredJwtKey::PublicKeyPath(path)) => {
+            Some(read_pem("JWT_RETIRED_PUBLIC_KEY_PATH", path)?)
+        }
+        None => None,
+    };
+    let jwt_config = match retired_key {
+        Some(key) => jwt_config
+            .with_retired_key(&key)
+            .map_err(|e| anyhow::anyhow!("{}", e))?,
+        None => jwt_config,
+    };
+    let mut jwt_config = jwt_config
+        .with_leeway(config.jwt_leeway_secs)
+        .with_read_grace(config.jwt_read_grace_secs);
+    if let Some(actions) = config.token_refresh_actions.clone() {
+        jwt_config = jwt_config.with_refresh_actions(actions);
+    }
+    let jwt_service = Arc::new(JwtService::new(jwt_config.clone()).with_pool(pool.clone()));
+    // Revocations made before this replica started still apply to it
+    jwt_service.sync_revocations().await?;
 
-    info!("JWT service initialized");
+    info!(
+        algorithm = %jwt_algorithm,
+        kid = %jwt_config.signing_key.kid,
+        retired_keys = jwt_config.retired_keys.len(),
+        "JWT service initialized"
+    );
 
     // Initialize tier config — prefer DB overrides, fall back to env vars
     let tier_config = {
@@ -134,15 +215,6 @@ async fn main() -> anyhow::Result<()> {
     };
     let tier_config = Arc::new(std::sync::RwLock::new(tier_config));
 
-    // Initialize Auth service
-    let auth_service = Arc::new(AuthService::new(
-        pool.clone(),
-        (*jwt_service).clone(),
-        tier_config.clone(),
-    ));
-
-    info!("Auth service initialized");
-
     // Initialize Email service
     let email_service = Arc::new(EmailService::new(config.email.clone()).unwrap_or_else(|e| {
         tracing::warn!(error = %e, "Failed to initialize email service, using dev mode");
@@ -151,6 +223,29 @@ async fn main() -> anyhow::Result<()> {
 
     info!(enabled = config.email.enabled, "Email service initialized");
 
+    // Initialize Auth service
+    let mut auth_service =
+        AuthService::new(pool.clone(), (*jwt_service).clone(), tier_config.clone())
+            .with_max_sessions_per_user(config.max_sessions_per_user)
+            .with_unique_session_per_device(config.unique_session_per_device)
+            .with_refresh_reuse_grace(config.refresh_reuse_grace_secs)
+            .with_password_service((*password_service).clone())
+            .with_audit(config.audit.clone())
+            .with_signup_policy(config.signup_policy.clone());
+    if let Some(geo_url) = &config.impossible_travel.geo_url {
+        auth_service = auth_service.with_impossible_travel(
+            config.impossible_travel.clone(),
+            Arc::new(HttpGeoResolver::new(geo_url)),
+            email_service.clone(),
+        );
+    }
+    if config.welcome_email_on_first_login {
+        auth_service = auth_service.with_welcome_email(email_service.clone());
+    }
+    let auth_service = Arc::new(auth_service);
+
+    info!("Auth service initialized");
+
     // Build encryption key sets for key rotation support
     let totp_key_set = EncryptionKeySet {
         current: config.totp_encryption_key,
@@ -168,20 +263,34 @@ async fn main() -> anyhow::Result<()> {
         use a8n_api::repositories::StripeConfigRepository;
         match StripeConfigRepository::get(&pool).await {
             Ok(db_config) if db_config.secret_key.is_some() => {
-                match StripeConfig::from_db_model(&db_config, &stripe_key_set) {
+                match StripeConfig::from_db_model(&db_config, &stripe_key_set, &config) {
                     Ok(cfg) => {
                         info!("Stripe service initialized from database config");
                         cfg
                     }
                     Err(e) => {
                         tracing::warn!(error = %e, "Failed to decrypt DB Stripe config, falling back to env vars");
-                        StripeConfig::from_env()?
+                        StripeConfig::from_env(&config)?
                     }
                 }
             }
-            _ => StripeConfig::from_env()?,
+            _ => StripeConfig::from_env(&config)?,
         }
     };
+
+    // Validate critical config and external dependencies before serving traffic
+    let report = preflight::run(
+        &config,
+        &pool,
+        &jwt_service,
+        &jwt_secret,
+        &stripe_config,
+        &email_service,
+    )
+    .await;
+    report.log();
+    report.enforce(config.preflight_strict)?;
+
     let stripe_service = Arc::new(StripeService::new(stripe_config));
 
     info!("Stripe service initialized");
@@ -280,6 +389,47 @@ async fn main() -> anyhow::Result<()> {
 
     info!("Webhook service initialized");
 
+    // User data exports, with download URLs signed by a key derived from the JWT secret
+    let data_export_service = Arc::new(DataExportService::new(
+        Arc::new(PgExportStore::new(pool.clone())),
+        &jwt_secret,
+    ));
+    // Builds run in-process, so jobs left queued by a previous run never finish
+    let interrupted = DataExportRepository::fail_stale(&pool).await?;
+    if interrupted > 0 {
+        info!(interrupted, "Failed data exports interrupted by a restart");
+    }
+
+    if config.audit.mask_pii {
+        info!("Audit log actor masking enabled");
+    }
+    if !config.audit.policy.records_everything() {
+        info!(
+            min_severity = config.audit.policy.min_severity.as_str(),
+            suppressed = ?config.audit.policy.suppressed_actions,
+            sample_rate = config.audit.policy.sample_rate,
+            "Audit log policy enabled"
+        );
+    }
+
+    // Initialize captcha verification (no-op unless CAPTCHA_ENABLED)
+    let captcha_service = Arc::new(CaptchaService::new(config.captcha.clone()));
+    if config.captcha.enabled {
+        info!(provider = ?config.captcha.provider, "Captcha verification enabled");
+    }
+
+    // Push alerts on 5xx responses (optional — only when ERROR_ALERT_WEBHOOK_URL is set)
+    let error_alerter = config.error_alerts.webhook_url.as_deref().map(|url| {
+        info!(
+            cooldown_secs = config.error_alerts.cooldown_secs,
+            "Server error alerts enabled"
+        );
+        Arc::new(ErrorAlerter::new(
+            Arc::new(WebhookAlertSink::new(url)),
+            chrono::Duration::seconds(config.error_alerts.cooldown_secs),
+        ))
+    });
+
     // Initialize OIDC provider (optional — only when OIDC_ISSUER is set)
     let oidc_provider: Option<Arc<OidcProvider>> = if config.oidc.enabled() {
         let key_set = OidcKeySet::load(
@@ -291,11 +441,12 @@ async fn main() -> anyhow::Result<()> {
             error!(error = %e, "Failed to load OIDC key set");
             anyhow::anyhow!("{}", e)
         })?;
-        let provider = Arc::new(OidcProvider::new(
-            config.oidc.clone(),
-            Arc::new(key_set),
-            pool.clone(),
-        ));
+        let mut provider = OidcProvider::new(config.oidc.clone(), Arc::new(key_set), pool.clone())
+            .with_audit(config.audit.clone());
+        if config.refresh_reuse_notify_user {
+            provider = provider.with_reuse_alert_email(email_service.clone());
+        }
+        let provider = Arc::new(provider);
         info!(
             issuer = %config.oidc.issuer.as_deref().unwrap_or("(none)"),
             active_kid = %config.oidc.jwt_active_kid,
@@ -309,7 +460,10 @@ async fn main() -> anyhow::Result<()> {
     };
 
     // Initialize auto-ban service
-    let auto_ban_service = Arc::new(AutoBanService::new(config.auto_ban.clone(), pool.clone()));
+    let auto_ban_service = Arc::new(
+        AutoBanService::new(config.auto_ban.clone(), pool.clone())
+            .with_exempt_path(format!("/v1/webhooks/{}", config.stripe_webhook_path)),
+    );
 
     // Load existing bans from DB
     match auto_ban::load_active_bans(&pool).await {
@@ -346,71 +500,74 @@ async fn main() -> anyhow::Result<()> {
 
     let config_data = config.clone();
 
-    // Spawn rate limit cleanup background task
-    let cleanup_pool = pool.clone();
-    tokio::spawn(async move {
-        info!("Rate limit cleanup task started");
-        let mut interval = tokio::time::interval(Duration::from_secs(3600));
-        loop {
-            interval.tick().await;
-            match RateLimitRepository::cleanup_expired(&cleanup_pool).await {
-                Ok(deleted) => {
-                    if deleted > 0 {
-                        info!(deleted, "Cleaned up expired rate limit entries");
-                    }
-                }
-                Err(e) => {
-                    error!(error = %e, "Failed to cleanup expired rate limit entries");
-                }
-            }
-        }
-    });
+    // Spawn expired token/rate limit/IP ban cleanup background task
+    scheduler::spawn_maintenance(
+        pool.clone(),
+        Duration::from_secs(config.maintenance_interval_mins * 60),
+    );
+    // In-memory bans and strikes are pruned on their own, shorter tick
+    scheduler::spawn_auto_ban_prune(auto_ban_service.clone(), scheduler::AUTO_BAN_PRUNE_INTERVAL);
 
-    // Spawn auto-ban cleanup background task (every 5 minutes)
-    let ban_cleanup_pool = pool.clone();
-    let ban_cleanup_service = auto_ban_service.clone();
+    // Spawn feedback archive+purge background task (every 24h)
+    // Archives closed feedback older than 90 days into feedback_archive, then hard-deletes it
+    let feedback_purge_pool = pool.clone();
     tokio::spawn(async move {
-        info!("Auto-ban cleanup task started");
-        let mut interval = tokio::time::interval(Duration::from_secs(300));
+        info!("Feedback archive/purge task started");
+        let mut interval = tokio::time::interval(Duration::from_secs(86400));
         loop {
             interval.tick().await;
-            // Clean in-memory state
-            ban_cleanup_service.cleanup_expired().await;
-            // Clean database
-            match auto_ban::cleanup_expired_bans(&ban_cleanup_pool).await {
-                Ok(deleted) => {
-                    if deleted > 0 {
-                        info!(deleted, "Cleaned up expired IP bans");
+            let purge = FeedbackRepository::archive_and_purge_closed(&feedback_purge_pool);
+            match with_advisory_lock(&feedback_purge_pool, jobs::FEEDBACK_PURGE_LOCK, purge).await {
+                Ok(None) => {}
+                Ok(Some(purged)) => {
+                    if purged > 0 {
+                        info!(purged, "Archived and purged closed feedback records");
                     }
                 }
                 Err(e) => {
-                    error!(error = %e, "Failed to cleanup expired IP bans");
+                    error!(error = %e, "Failed to archive/purge closed feedback");
                 }
             }
         }
     });
 
-    // Spawn feedback archive+purge background task (every 24h)
-    // Archives closed feedback older than 90 days into feedback_archive, then hard-deletes it
-    let feedback_purge_pool = pool.clone();
+    // Spawn data export bundle purge and stale job background task (every hour)
+    let export_purge_pool = pool.clone();
     tokio::spawn(async move {
-        info!("Feedback archive/purge task started");
-        let mut interval = tokio::time::interval(Duration::from_secs(86400));
+        info!("Data export purge task started");
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
         loop {
             interval.tick().await;
-            match FeedbackRepository::archive_and_purge_closed(&feedback_purge_pool).await {
-                Ok(purged) => {
+            let purge = async {
+                DataExportRepository::fail_stale(&export_purge_pool).await?;
+                DataExportRepository::purge_expired_bundles(&export_purge_pool).await
+            };
+            match with_advisory_lock(&export_purge_pool, jobs::DATA_EXPORT_PURGE_LOCK, purge).await
+            {
+                Ok(None) => {}
+                Ok(Some(purged)) => {
                     if purged > 0 {
-                        info!(purged, "Archived and purged closed feedback records");
+                        info!(purged, "Dropped expired data export bundles");
                     }
                 }
                 Err(e) => {
-                    error!(error = %e, "Failed to archive/purge closed feedback");
+                    error!(error = %e, "Failed to drop expired data export bundles");
                 }
             }
         }
     });
 
+    // Spawn access token revocation sync background task
+    scheduler::spawn_revocation_sync(jwt_service.clone(), scheduler::REVOCATION_SYNC_INTERVAL);
+
+    // Spawn grace period expiry background task
+    scheduler::spawn_grace_period_expiry(
+        pool.clone(),
+        config.audit.clone(),
+        Some(jwt_service.clone()),
+        Duration::from_secs(config.grace_period_check_interval_mins * 60),
+    );
+
     info!(address = %server_addr, "Starting HTTP server");
 
     // Pre-clone OCI handles for the OCI server (primary closure moves the originals)
@@ -421,11 +578,13 @@ async fn main() -> anyhow::Result<()> {
     let forgejo_registry_client_oci = forgejo_registry_client.clone();
     let pool_oci_server = pool.clone();
     let cfg_oci_server = config_data.oci.clone();
+    let audit_oci_server = config_data.audit.clone();
+    let password_service_oci_server = password_service.clone();
 
     let primary = HttpServer::new(move || {
         // Configure CORS
         let domain = cors_domain.clone();
-        let cors = Cors::default()
+        let mut cors = Cors::default()
             .allowed_origin(&cors_origin)
             .allowed_origin_fn(move |origin, _req_head| {
                 let origin = origin.as_bytes();
@@ -445,33 +604,81 @@ async fn main() -> anyhow::Result<()> {
                 actix_web::http::header::ACCEPT,
                 actix_web::http::header::CONTENT_TYPE,
                 actix_web::http::header::COOKIE,
+                actix_web::http::header::HeaderName::from_static(
+                    a8n_api::middleware::auth::DEVICE_ID_HEADER,
+                ),
+            ])
+            .expose_headers(vec![
+                actix_web::http::header::SET_COOKIE,
+                actix_web::http::header::HeaderName::from_static(
+                    a8n_api::middleware::impersonation::IMPERSONATING_HEADER,
+                ),
+                actix_web::http::header::HeaderName::from_static(
+                    a8n_api::middleware::rate_limit_warning::RATE_LIMIT_WARNING_HEADER,
+                ),
             ])
-            .expose_headers(vec![actix_web::http::header::SET_COOKIE])
-            .supports_credentials()
             .max_age(3600);
+        if config_data.cors_allow_credentials {
+            cors = cors.supports_credentials();
+        }
 
         App::new()
             // Add middleware (order matters - executed in reverse order)
+            .wrap(Condition::new(
+                config_data.compression.enabled,
+                CompressionPolicy::new(config_data.compression.min_size),
+            ))
+            .wrap(Condition::new(
+                config_data.compression.enabled,
+                Compress::default(),
+            ))
             .wrap(TracingLogger::default())
             .wrap(Logger::default())
+            .wrap(ImpersonationHeader)
+            .wrap(Condition::new(
+                config_data.api_rate_limit.enabled,
+                ApiRateLimit::new(TieredRateLimit::new(
+                    RateLimitConfig::API_AUTH,
+                    config_data.api_rate_limit.tier_limits.clone(),
+                )),
+            ))
+            .wrap(RateLimitWarning::new(
+                config_data.rate_limit_warning_threshold,
+            ))
             .wrap(SecurityHeaders)
+            // Inside RequestIdMiddleware so alerts carry the request id
+            .wrap(ErrorAlerts::new(error_alerter.clone()))
             .wrap(RequestIdMiddleware)
             .wrap(cors)
+            .wrap(Condition::new(
+                config_data.https_redirect.enabled,
+                HttpsRedirect::new(config_data.https_redirect.trusted_proxies.clone()),
+            ))
             // Auto-ban runs outermost — rejects banned IPs before CORS processing
             .wrap(AutoBanMiddleware::new(auto_ban_service.clone()))
             // Explicit JSON body size limit (32 KB)
             .app_data(web::JsonConfig::default().limit(32_768))
+            .app_data(JsonOrFormConfig::new(config_data.auth_accept_form_bodies))
+            .app_data(AdminTwoFactorPolicy::new(config_data.require_admin_2fa))
+            .app_data(StepUpPolicy::new(config_data.step_up_max_age_secs))
             // Add database pool to app state
             .app_data(web::Data::new(pool.clone()))
             // Add services to app state
             .app_data(jwt_service.clone())
             .app_data(web::Data::new(auth_service.clone()))
+            .app_data(web::Data::new(password_service.clone()))
             .app_data(web::Data::new(email_service.clone()))
             .app_data(web::Data::new(stripe_service.clone()))
             .app_data(web::Data::new(totp_service.clone()))
             .app_data(web::Data::new(webhook_service.clone()))
+            .app_data(web::Data::new(data_export_service.clone()))
+            .app_data(web::Data::new(captcha_service.clone()))
+            .app_data(web::Data::new(auto_ban_service.clone()))
             .app_data(web::Data::new(stripe_key_set.clone()))
             .app_data(web::Data::new(config_data.clone()))
+            .app_data(web::Data::new(config_data.audit.clone()))
+            .app_data(web::Data::new(config_data.signup_policy.clone()))
+            .app_data(web::Data::new(config_data.pagination))
             .app_data(web::Data::new(download_limiter.clone()))
             .app_data(web::Data::new(release_cache.clone()))
             .app_data(web::Data::new(download_cache.clone()))
@@ -485,7 +692,7 @@ async fn main() -> anyhow::Result<()> {
             .app_data(web::Data::new(oidc_provider.clone()))
             .app_data(web::Data::new(tier_config.clone()))
             // Configure routes
-            .configure(routes::configure)
+            .configure(|cfg| routes::configure(cfg, &config_data))
     })
     .bind(&server_addr)?
     .shutdown_timeout(30)
@@ -508,6 +715,8 @@ async fn main() -> anyhow::Result<()> {
         let cfg_oci = cfg_oci_server;
         let frc = forgejo_registry_client_oci;
         let pool_oci = pool_oci_server;
+        let audit_oci = audit_oci_server;
+        let password_oci = password_service_oci_server;
 
         info!(address = %oci_addr, "Starting OCI registry server");
 
@@ -521,6 +730,8 @@ async fn main() -> anyhow::Result<()> {
                     cfg: std::sync::Arc::new(cfg_oci.clone()),
                 })
                 .app_data(web::Data::new(pool_oci.clone()))
+                .app_data(web::Data::new(audit_oci.clone()))
+                .app_data(web::Data::new(password_oci.clone()))
                 // Raw Arc for the OciBearerUser extractor
                 .app_data(ots.clone())
                 // web::Data for the issue_token handler
@@ -0,0 +1,169 @@
+//! API-wide rate limit middleware
+//!
+//! Counts every `/v1` request against a per-caller limit. Signed-in callers
+//! are keyed by user ID and get the limit for their subscription tier (see
+//! [`TieredRateLimit`]); anonymous callers are keyed by IP and get
+//! `RateLimitConfig::API_UNAUTH`. Stripe webhooks are exempt.
+//!
+//! Wrap it inside `RateLimitWarning` so its usage feeds the warning header.
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error,
+};
+use sqlx::PgPool;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::errors::AppError;
+use crate::middleware::auth::{extract_client_ip, extract_token};
+use crate::middleware::record_rate_limit_usage;
+use crate::models::{RateLimitConfig, TieredRateLimit};
+use crate::repositories::RateLimitRepository;
+use crate::services::{AccessTokenClaims, JwtService};
+
+/// API-wide rate limit middleware
+pub struct ApiRateLimit {
+    limits: Rc<TieredRateLimit>,
+}
+
+impl ApiRateLimit {
+    pub fn new(limits: TieredRateLimit) -> Self {
+        Self {
+            limits: Rc::new(limits),
+        }
+    }
+}
+
+/// Whether requests to `path` count against the API limit
+fn is_limited_path(path: &str) -> bool {
+    path.starts_with("/v1/") && !path.starts_with("/v1/webhooks/")
+}
+
+/// Limit for a signed-in caller: their tier's, if they have member access
+fn limit_for_claims(limits: &TieredRateLimit, claims: &AccessTokenClaims) -> RateLimitConfig {
+    let tier = claims.tier();
+    limits.for_tier(claims.has_member_access().then(|| tier.as_str()))
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiRateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ApiRateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiRateLimitMiddleware {
+            service: Rc::new(service),
+            limits: self.limits.clone(),
+        }))
+    }
+}
+
+pub struct ApiRateLimitMiddleware<S> {
+    service: Rc<S>,
+    limits: Rc<TieredRateLimit>,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiRateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let limits = self.limits.clone();
+
+        Box::pin(async move {
+            let pool = req.app_data::<web::Data<PgPool>>().cloned();
+            let Some(pool) = pool.filter(|_| is_limited_path(req.path())) else {
+                return service.call(req).await;
+            };
+
+            let claims = req
+                .app_data::<Arc<JwtService>>()
+                .zip(extract_token(req.request()))
+                .and_then(|(jwt, token)| jwt.verify_access_token_for(&token, req.method()).ok());
+            let (key, config) = match &claims {
+                Some(claims) => (claims.sub.to_string(), limit_for_claims(&limits, claims)),
+                None => (
+                    extract_client_ip(req.request())
+                        .map(|ip| ip.to_string())
+                        .unwrap_or_default(),
+                    RateLimitConfig::API_UNAUTH,
+                ),
+            };
+
+            // Fail open: a database hiccup should not take the whole API down
+            match RateLimitRepository::check_and_increment(&pool, &key, &config).await {
+                Ok((count, exceeded)) => {
+                    record_rate_limit_usage(req.request(), &config, count);
+                    if exceeded {
+                        let retry_after =
+                            RateLimitRepository::get_retry_after(&pool, &key, &config)
+                                .await
+                                .unwrap_or(config.window_seconds as u64);
+                        return Err(AppError::RateLimited { retry_after }.into());
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "API rate limit check failed; allowing request");
+                }
+            }
+
+            service.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn claims(tier: &str, membership_status: &str) -> AccessTokenClaims {
+        AccessTokenClaims {
+            subscription_tier: tier.to_string(),
+            membership_status: membership_status.to_string(),
+            ..AccessTokenClaims::test_fixture()
+        }
+    }
+
+    #[test]
+    fn only_api_paths_outside_webhooks_are_limited() {
+        assert!(is_limited_path("/v1/users/me"));
+        assert!(!is_limited_path("/v1/webhooks/stripe"));
+        assert!(!is_limited_path("/health"));
+    }
+
+    #[test]
+    fn members_get_their_tier_limit() {
+        let limits = TieredRateLimit::new(
+            RateLimitConfig::API_AUTH,
+            HashMap::from([("lifetime".to_string(), 600)]),
+        );
+
+        let member = limit_for_claims(&limits, &claims("lifetime", "active"));
+        let lapsed = limit_for_claims(&limits, &claims("lifetime", "canceled"));
+
+        assert_eq!(member.max_requests, 600);
+        // Without member access the tier does not apply
+        assert_eq!(lapsed.max_requests, RateLimitConfig::API_AUTH.max_requests);
+        assert_eq!(member.action, lapsed.action);
+    }
+}
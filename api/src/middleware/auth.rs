@@ -4,6 +4,7 @@
 //! for securing API endpoints.
 
 use crate::errors::AppError;
+use crate::models::{AuditAction, Permission, PermissionScope, RefreshToken, SubscriptionTier};
 use crate::services::{AccessTokenClaims, JwtService};
 use actix_web::{
     cookie::{Cookie, SameSite},
@@ -12,6 +13,7 @@ use actix_web::{
     FromRequest, HttpMessage, HttpRequest,
 };
 use std::future::{ready, Ready};
+use std::marker::PhantomData;
 use std::sync::Arc;
 
 /// Key for storing authenticated user claims in request extensions
@@ -42,7 +44,7 @@ impl FromRequest for AuthenticatedUser {
         let token = extract_token(req);
 
         match token {
-            Some(token) => match jwt_service.verify_access_token(&token) {
+            Some(token) => match verify_request_token(&jwt_service, &token, req) {
                 Ok(claims) => {
                     // Store claims in request extensions for later use
                     req.extensions_mut()
@@ -78,7 +80,7 @@ impl FromRequest for OptionalUser {
         let token = extract_token(req);
 
         match token {
-            Some(token) => match jwt_service.verify_access_token(&token) {
+            Some(token) => match verify_request_token(&jwt_service, &token, req) {
                 Ok(claims) => {
                     req.extensions_mut()
                         .insert(AuthenticatedClaims(claims.clone()));
@@ -121,11 +123,14 @@ impl FromRequest for AdminUser {
         let token = extract_token(req);
 
         match token {
-            Some(token) => match jwt_service.verify_access_token(&token) {
+            Some(token) => match verify_request_token(&jwt_service, &token, req) {
                 Ok(claims) => {
                     if claims.role != "admin" {
                         return ready(Err(AppError::Forbidden));
                     }
+                    if let Err(e) = check_admin_two_factor(req, &claims) {
+                        return ready(Err(e));
+                    }
                     req.extensions_mut()
                         .insert(AuthenticatedClaims(claims.clone()));
                     ready(Ok(AdminUser(claims)))
@@ -137,6 +142,112 @@ impl FromRequest for AdminUser {
     }
 }
 
+/// App data marking a resource whose safe-method requests issue credentials,
+/// such as `GET /oauth2/authorize` minting authorization codes. Extractors
+/// verify tokens there without the JWT_READ_GRACE_SECS read grace.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IssuesCredentials;
+
+/// Verify the request's access token, applying the read grace unless the
+/// resource is marked [`IssuesCredentials`]
+fn verify_request_token(
+    jwt_service: &JwtService,
+    token: &str,
+    req: &HttpRequest,
+) -> Result<AccessTokenClaims, AppError> {
+    if req.app_data::<IssuesCredentials>().is_some() {
+        jwt_service.verify_access_token(token)
+    } else {
+        jwt_service.verify_access_token_for(token, req.method())
+    }
+}
+
+/// App data making admin extractors reject admins without 2FA
+/// (REQUIRE_ADMIN_2FA). Absent means 2FA is not required.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AdminTwoFactorPolicy {
+    pub required: bool,
+}
+
+impl AdminTwoFactorPolicy {
+    pub fn new(required: bool) -> Self {
+        Self { required }
+    }
+}
+
+/// Reject a token without 2FA for any role holding admin-scope permissions
+/// (admin, support, finance) when the policy requires it. The 2FA enrollment
+/// endpoints only need `AuthenticatedUser`, so staff can still reach them to
+/// enroll.
+fn check_admin_two_factor(req: &HttpRequest, claims: &AccessTokenClaims) -> Result<(), AppError> {
+    let required = req
+        .app_data::<AdminTwoFactorPolicy>()
+        .is_some_and(|p| p.required);
+    let staff = !Permission::for_role(&claims.role).is_empty();
+    if required && staff && !claims.two_factor_enabled {
+        tracing::debug!(user_id = %claims.sub, path = %req.path(), "Admin without 2FA denied");
+        return Err(AppError::TwoFactorRequired);
+    }
+    Ok(())
+}
+
+/// App data making [`RecentlyAuthenticatedUser`] reject sessions that signed
+/// in more than `max_age_secs` ago (STEP_UP_MAX_AGE_SECS). Absent or 0 means
+/// any session may perform sensitive actions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StepUpPolicy {
+    pub max_age_secs: u64,
+}
+
+impl StepUpPolicy {
+    pub fn new(max_age_secs: u64) -> Self {
+        Self { max_age_secs }
+    }
+}
+
+/// Extractor for sensitive actions (changing email, disabling 2FA, deleting
+/// the account) - returns 403 `REAUTHENTICATION_REQUIRED` unless the session
+/// signed in recently enough under [`StepUpPolicy`]. Impersonation sessions
+/// carry no sign-in time, so they never qualify.
+#[derive(Debug, Clone)]
+pub struct RecentlyAuthenticatedUser(pub AccessTokenClaims);
+
+impl FromRequest for RecentlyAuthenticatedUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let jwt_service = match req.app_data::<Arc<JwtService>>() {
+            Some(service) => service.clone(),
+            None => {
+                tracing::error!("JwtService not found in app data");
+                return ready(Err(AppError::internal(
+                    "Authentication service not available",
+                )));
+            }
+        };
+
+        let token = extract_token(req);
+
+        match token {
+            Some(token) => match verify_request_token(&jwt_service, &token, req) {
+                Ok(claims) => {
+                    let max_age_secs = req.app_data::<StepUpPolicy>().map_or(0, |p| p.max_age_secs);
+                    if max_age_secs > 0 && !jwt_service.is_recent_sign_in(&claims, max_age_secs) {
+                        tracing::debug!(user_id = %claims.sub, path = %req.path(), "Stale sign-in denied sensitive action");
+                        return ready(Err(AppError::ReauthenticationRequired { max_age_secs }));
+                    }
+                    req.extensions_mut()
+                        .insert(AuthenticatedClaims(claims.clone()));
+                    ready(Ok(RecentlyAuthenticatedUser(claims)))
+                }
+                Err(e) => ready(Err(e)),
+            },
+            None => ready(Err(AppError::Unauthorized)),
+        }
+    }
+}
+
 /// Extractor for users with active membership - returns 403 if not a member
 #[derive(Debug, Clone)]
 pub struct MemberUser(pub AccessTokenClaims);
@@ -159,9 +270,9 @@ impl FromRequest for MemberUser {
         let token = extract_token(req);
 
         match token {
-            Some(token) => match jwt_service.verify_access_token(&token) {
+            Some(token) => match verify_request_token(&jwt_service, &token, req) {
                 Ok(claims) => {
-                    if !claims.has_member_access() {
+                    if !jwt_service.has_member_access(&claims) {
                         return ready(Err(AppError::Forbidden));
                     }
 
@@ -176,9 +287,72 @@ impl FromRequest for MemberUser {
     }
 }
 
+/// Extractor for users whose role grants scope `P` - returns 403 otherwise
+///
+/// e.g. `admin: RequirePermission<scopes::BillingWrite>`; claims are in `.0`
+/// as with `AdminUser`.
+#[derive(Debug, Clone)]
+pub struct RequirePermission<P: PermissionScope>(pub AccessTokenClaims, PhantomData<P>);
+
+impl<P: PermissionScope> FromRequest for RequirePermission<P> {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let jwt_service = match req.app_data::<Arc<JwtService>>() {
+            Some(service) => service.clone(),
+            None => {
+                tracing::error!("JwtService not found in app data");
+                return ready(Err(AppError::internal(
+                    "Authentication service not available",
+                )));
+            }
+        };
+
+        let token = extract_token(req);
+
+        match token {
+            Some(token) => match verify_request_token(&jwt_service, &token, req) {
+                Ok(claims) => {
+                    if !claims.has_permission(P::PERMISSION) {
+                        tracing::debug!(
+                            role = %claims.role,
+                            permission = P::PERMISSION.as_str(),
+                            path = %req.path(),
+                            "Permission denied"
+                        );
+                        return ready(Err(AppError::Forbidden));
+                    }
+                    if let Err(e) = check_admin_two_factor(req, &claims) {
+                        return ready(Err(e));
+                    }
+                    req.extensions_mut()
+                        .insert(AuthenticatedClaims(claims.clone()));
+                    ready(Ok(RequirePermission(claims, PhantomData)))
+                }
+                Err(e) => ready(Err(e)),
+            },
+            None => ready(Err(AppError::Unauthorized)),
+        }
+    }
+}
+
+/// Require the caller's subscription tier to be at least `min`.
+///
+/// Admins always pass. Use after an auth extractor, e.g.
+/// `require_tier(&member.0, SubscriptionTier::EarlyAdopter)?`.
+pub fn require_tier(claims: &AccessTokenClaims, min: SubscriptionTier) -> Result<(), AppError> {
+    if claims.role == "admin" || claims.tier() >= min {
+        return Ok(());
+    }
+    Err(AppError::TierRequired {
+        required: min.as_str().to_string(),
+    })
+}
+
 /// Extract JWT token from request
 /// Checks cookie first (access_token), then Authorization header
-fn extract_token(req: &HttpRequest) -> Option<String> {
+pub(crate) fn extract_token(req: &HttpRequest) -> Option<String> {
     // Try cookie first
     if let Some(cookie) = req.cookie("access_token") {
         return Some(cookie.value().to_string());
@@ -196,6 +370,24 @@ fn extract_token(req: &HttpRequest) -> Option<String> {
     None
 }
 
+/// Make `user_id`'s outstanding access tokens refresh when `action` changes
+/// their claims (see [`JwtService::claims_changed`]). Returns whether it did.
+pub async fn force_token_refresh(
+    req: &HttpRequest,
+    user_id: uuid::Uuid,
+    action: &AuditAction,
+) -> bool {
+    match req.app_data::<Arc<JwtService>>() {
+        Some(jwt) => jwt.claims_changed(user_id, action).await,
+        None => false,
+    }
+}
+
+/// Cookie carrying a 2FA challenge token handed off by a magic link redirect
+pub const TWO_FACTOR_CHALLENGE_COOKIE: &str = "two_factor_challenge";
+
+const TWO_FACTOR_CHALLENGE_PATH: &str = "/v1/auth/2fa";
+
 /// Cookie configuration for auth tokens
 pub struct AuthCookies;
 
@@ -223,11 +415,10 @@ impl AuthCookies {
         remember: bool,
         cookie_domain: Option<&str>,
     ) -> Cookie<'static> {
-        let max_age = if remember {
-            actix_web::cookie::time::Duration::days(30)
-        } else {
-            actix_web::cookie::time::Duration::days(7)
-        };
+        // Matches the lifetime of the refresh token stored in the database
+        let max_age = actix_web::cookie::time::Duration::seconds(
+            RefreshToken::lifetime(remember).num_seconds(),
+        );
 
         let mut builder = Cookie::build("refresh_token", token.to_owned())
             .path("/")
@@ -243,6 +434,30 @@ impl AuthCookies {
         builder.finish()
     }
 
+    /// Create the cookie holding a pending 2FA challenge from a magic link
+    /// click-through, so the challenge token never appears in a URL. Scoped
+    /// to the 2FA endpoints and to the challenge's 5 minute lifetime.
+    pub fn two_factor_challenge(token: &str, secure: bool) -> Cookie<'static> {
+        Cookie::build(TWO_FACTOR_CHALLENGE_COOKIE, token.to_owned())
+            .path(TWO_FACTOR_CHALLENGE_PATH)
+            .http_only(true)
+            .secure(secure)
+            .same_site(SameSite::Lax)
+            .max_age(actix_web::cookie::time::Duration::minutes(5))
+            .finish()
+    }
+
+    /// Create a cookie clearing the pending 2FA challenge
+    pub fn clear_two_factor_challenge(secure: bool) -> Cookie<'static> {
+        Cookie::build(TWO_FACTOR_CHALLENGE_COOKIE, "")
+            .path(TWO_FACTOR_CHALLENGE_PATH)
+            .http_only(true)
+            .secure(secure)
+            .same_site(SameSite::Lax)
+            .max_age(actix_web::cookie::time::Duration::seconds(0))
+            .finish()
+    }
+
     /// Create cookies to clear stale hostname-scoped tokens.
     /// When COOKIE_DOMAIN is set (e.g. `.example.com`), any old cookies set
     /// without a domain attribute (scoped to the exact hostname like `api.example.com`)
@@ -341,10 +556,321 @@ pub fn extract_device_info(req: &HttpRequest) -> Option<String> {
         })
 }
 
+/// Request header carrying a client-generated device identifier
+pub const DEVICE_ID_HEADER: &str = "x-device-id";
+
+/// Longest `X-Device-Id` kept; longer values are ignored
+const MAX_DEVICE_ID_LEN: usize = 128;
+
+/// Extract the client-supplied device identifier from the `X-Device-Id` header
+pub fn extract_device_id(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(DEVICE_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_DEVICE_ID_LEN)
+        .map(String::from)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn claims_with(role: &str, tier: &str) -> AccessTokenClaims {
+        AccessTokenClaims {
+            role: role.to_string(),
+            subscription_tier: tier.to_string(),
+            ..AccessTokenClaims::test_fixture()
+        }
+    }
+
+    #[test]
+    fn require_tier_passes_at_or_above_minimum() {
+        let claims = claims_with("subscriber", "free");
+        assert!(require_tier(&claims, SubscriptionTier::Standard).is_ok());
+        assert!(require_tier(&claims, SubscriptionTier::EarlyAdopter).is_ok());
+        assert!(require_tier(&claims, SubscriptionTier::Free).is_ok());
+    }
+
+    #[test]
+    fn require_tier_rejects_lower_tier_with_required_tier() {
+        let claims = claims_with("subscriber", "early_adopter");
+        match require_tier(&claims, SubscriptionTier::Lifetime) {
+            Err(AppError::TierRequired { required }) => assert_eq!(required, "lifetime"),
+            other => panic!("expected TierRequired, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn require_tier_treats_missing_tier_as_standard_and_lets_admins_through() {
+        let mut claims = claims_with("subscriber", "standard");
+        claims.subscription_tier = String::new();
+        assert!(require_tier(&claims, SubscriptionTier::Standard).is_ok());
+        assert!(require_tier(&claims, SubscriptionTier::EarlyAdopter).is_err());
+
+        let admin = claims_with("admin", "standard");
+        assert!(require_tier(&admin, SubscriptionTier::Lifetime).is_ok());
+    }
+
+    fn user_with_role(role: &str) -> crate::models::User {
+        crate::models::User {
+            email: format!("{}@example.com", role),
+            role: role.to_string(),
+            membership_status: "none".to_string(),
+            ..crate::models::User::test_fixture()
+        }
+    }
+
+    #[actix_rt::test]
+    async fn require_permission_allows_granted_scope_and_denies_others() {
+        use crate::models::scopes;
+        use crate::services::JwtConfig;
+        use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+
+        async fn refund(_: RequirePermission<scopes::BillingWrite>) -> HttpResponse {
+            HttpResponse::Ok().finish()
+        }
+
+        let jwt = Arc::new(JwtService::new(JwtConfig::from_secret(
+            "test-secret-key-12345",
+            "localhost",
+        )));
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt.clone())
+                .route("/refunds", web::post().to(refund)),
+        )
+        .await;
+
+        let status_for = |role: &str| {
+            let token = jwt.create_access_token(&user_with_role(role)).unwrap();
+            test::TestRequest::post()
+                .uri("/refunds")
+                .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        for (role, expected) in [
+            ("finance", StatusCode::OK),
+            ("admin", StatusCode::OK),
+            ("support", StatusCode::FORBIDDEN),
+            ("subscriber", StatusCode::FORBIDDEN),
+        ] {
+            let res = test::call_service(&app, status_for(role)).await;
+            assert_eq!(res.status(), expected, "role {}", role);
+        }
+
+        let anonymous = test::TestRequest::post().uri("/refunds").to_request();
+        let res = test::call_service(&app, anonymous).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn admin_without_2fa_is_sent_to_enroll_when_required() {
+        use crate::services::JwtConfig;
+        use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+
+        async fn dashboard(_: AdminUser) -> HttpResponse {
+            HttpResponse::Ok().finish()
+        }
+
+        let jwt = Arc::new(JwtService::new(JwtConfig::from_secret(
+            "test-secret-key-12345",
+            "localhost",
+        )));
+        let admin_token = |two_factor_enabled: bool| {
+            let mut admin = user_with_role("admin");
+            admin.two_factor_enabled = two_factor_enabled;
+            jwt.create_access_token(&admin).unwrap()
+        };
+
+        for (required, two_factor_enabled, expected) in [
+            (true, false, StatusCode::FORBIDDEN),
+            (true, true, StatusCode::OK),
+            (false, false, StatusCode::OK),
+        ] {
+            let app = test::init_service(
+                App::new()
+                    .app_data(jwt.clone())
+                    .app_data(AdminTwoFactorPolicy::new(required))
+                    .route("/admin", web::get().to(dashboard)),
+            )
+            .await;
+            let req = test::TestRequest::get()
+                .uri("/admin")
+                .insert_header((
+                    header::AUTHORIZATION,
+                    format!("Bearer {}", admin_token(two_factor_enabled)),
+                ))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(
+                res.status(),
+                expected,
+                "required={required} 2fa={two_factor_enabled}"
+            );
+
+            if expected == StatusCode::FORBIDDEN {
+                let body: serde_json::Value = test::read_body_json(res).await;
+                assert_eq!(body["error"]["code"], "TWO_FACTOR_REQUIRED");
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn narrower_staff_roles_also_need_2fa_when_required() {
+        use crate::models::scopes;
+        use crate::services::JwtConfig;
+        use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+
+        async fn users(_: RequirePermission<scopes::UsersRead>) -> HttpResponse {
+            HttpResponse::Ok().finish()
+        }
+
+        let jwt = Arc::new(JwtService::new(JwtConfig::from_secret(
+            "test-secret-key-12345",
+            "localhost",
+        )));
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt.clone())
+                .app_data(AdminTwoFactorPolicy::new(true))
+                .route("/users", web::get().to(users)),
+        )
+        .await;
+
+        for role in ["support", "finance"] {
+            for (two_factor_enabled, expected) in
+                [(false, StatusCode::FORBIDDEN), (true, StatusCode::OK)]
+            {
+                let mut staff = user_with_role(role);
+                staff.two_factor_enabled = two_factor_enabled;
+                let req = test::TestRequest::get()
+                    .uri("/users")
+                    .insert_header((
+                        header::AUTHORIZATION,
+                        format!("Bearer {}", jwt.create_access_token(&staff).unwrap()),
+                    ))
+                    .to_request();
+                let res = test::call_service(&app, req).await;
+                assert_eq!(
+                    res.status(),
+                    expected,
+                    "role={role} 2fa={two_factor_enabled}"
+                );
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn credential_issuing_resources_get_no_read_grace() {
+        use crate::clock::MockClock;
+        use crate::services::JwtConfig;
+        use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+
+        async fn page(user: OptionalUser) -> HttpResponse {
+            if user.0.is_some() {
+                HttpResponse::Ok().finish()
+            } else {
+                HttpResponse::Found().finish()
+            }
+        }
+
+        let clock = MockClock::default();
+        let jwt = Arc::new(
+            JwtService::new(
+                JwtConfig::from_secret("test-secret-key-12345", "localhost")
+                    .with_leeway(0)
+                    .with_read_grace(60),
+            )
+            .with_clock(Arc::new(clock.clone())),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt.clone())
+                .route("/profile", web::get().to(page))
+                .service(
+                    web::resource("/authorize")
+                        .app_data(IssuesCredentials)
+                        .route(web::get().to(page)),
+                ),
+        )
+        .await;
+        let token = jwt
+            .create_access_token(&user_with_role("subscriber"))
+            .unwrap();
+        let get = |uri: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        clock.advance(chrono::Duration::minutes(15) + chrono::Duration::seconds(30));
+        let res = test::call_service(&app, get("/profile")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, get("/authorize")).await;
+        assert_eq!(res.status(), StatusCode::FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn stale_sign_in_must_reauthenticate_for_sensitive_actions() {
+        use crate::clock::MockClock;
+        use crate::services::JwtConfig;
+        use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+
+        async fn delete_account(_: RecentlyAuthenticatedUser) -> HttpResponse {
+            HttpResponse::Ok().finish()
+        }
+
+        let clock = MockClock::default();
+        let jwt = Arc::new(
+            JwtService::new(JwtConfig::from_secret("test-secret-key-12345", "localhost"))
+                .with_clock(Arc::new(clock.clone())),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt.clone())
+                .app_data(StepUpPolicy::new(600))
+                .route("/users/me", web::delete().to(delete_account)),
+        )
+        .await;
+        let request = |token: &str| {
+            test::TestRequest::delete()
+                .uri("/users/me")
+                .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        let user = user_with_role("subscriber");
+        let signed_in = jwt.create_access_token(&user).unwrap();
+        let (refresh, _) = jwt.create_refresh_token(user.id).unwrap();
+        let res = test::call_service(&app, request(&signed_in)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // Refreshing keeps the original sign-in time, so the session goes stale
+        clock.advance(chrono::Duration::minutes(11));
+        let auth_time = jwt.verify_refresh_token(&refresh).unwrap().auth_time;
+        let refreshed = jwt.create_access_token_since(&user, auth_time).unwrap();
+        let res = test::call_service(&app, request(&refreshed)).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "REAUTHENTICATION_REQUIRED");
+        assert_eq!(body["error"]["details"]["max_age_secs"], 600);
+
+        // Signing in again satisfies the step-up
+        let fresh = jwt.create_access_token(&user).unwrap();
+        let res = test::call_service(&app, request(&fresh)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // Impersonation tokens never count as a recent sign-in
+        let impersonated = jwt
+            .create_impersonation_token(&user, uuid::Uuid::new_v4(), "admin@example.com")
+            .unwrap();
+        let res = test::call_service(&app, request(&impersonated)).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
     #[test]
     fn test_auth_cookies_clear() {
         let cookies = AuthCookies::clear(false, None);
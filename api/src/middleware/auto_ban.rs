@@ -4,14 +4,17 @@
 //! a configurable threshold. Inspired by Stalwart's auto-ban approach.
 //!
 //! Suspicious patterns are matched by string prefix/suffix/exact checks (no regex needed).
+//! Requests from known scanner User-Agents (sqlmap, nikto, ...) also count as strikes.
 //! Bans are held in-memory for fast O(1) lookups and persisted to PostgreSQL asynchronously.
 
 use actix_web::{
     body::EitherBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
     Error, HttpResponse,
 };
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 use sqlx::{FromRow, PgPool};
 use std::{
     collections::{HashMap, HashSet},
@@ -23,9 +26,11 @@ use std::{
 };
 use tokio::sync::RwLock;
 use tracing::{info, warn};
+use uuid::Uuid;
 
 use crate::config::AutoBanConfig;
 use crate::middleware::auth::extract_client_ip;
+use crate::pagination::{Page, PerPage};
 
 // ── Pattern matching ────────────────────────────────────────────────────────
 
@@ -144,6 +149,43 @@ impl SuspiciousPatterns {
     }
 }
 
+/// User-Agent denylist with an allowlist that takes precedence.
+pub struct SuspiciousUserAgents {
+    exact: HashSet<String>,
+    contains: Vec<String>,
+    allow: Vec<String>,
+}
+
+impl SuspiciousUserAgents {
+    /// Build from lowercased config lists (see `AutoBanConfig::user_agent_denylist`).
+    pub fn new(denylist: &[String], allowlist: &[String]) -> Self {
+        let mut exact = HashSet::new();
+        let mut contains = Vec::new();
+        for pattern in denylist {
+            match pattern.strip_prefix('=') {
+                Some(agent) => {
+                    exact.insert(agent.to_string());
+                }
+                None => contains.push(pattern.clone()),
+            }
+        }
+        Self {
+            exact,
+            contains,
+            allow: allowlist.to_vec(),
+        }
+    }
+
+    /// Returns `true` if the User-Agent is denylisted and not allowlisted.
+    pub fn matches(&self, user_agent: &str) -> bool {
+        let lower = user_agent.trim().to_ascii_lowercase();
+        if lower.is_empty() || self.allow.iter().any(|a| lower.contains(a.as_str())) {
+            return false;
+        }
+        self.exact.contains(&lower) || self.contains.iter().any(|c| lower.contains(c.as_str()))
+    }
+}
+
 // ── In-memory state ─────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -167,6 +209,9 @@ pub struct AutoBanService {
     banned: RwLock<HashMap<IpAddr, BanEntry>>,
     strikes: RwLock<HashMap<IpAddr, StrikeEntry>>,
     patterns: SuspiciousPatterns,
+    user_agents: SuspiciousUserAgents,
+    /// Paths never treated as suspicious (e.g. the configured webhook path)
+    exempt_paths: HashSet<String>,
     config: AutoBanConfig,
     pool: PgPool,
 }
@@ -178,11 +223,22 @@ impl AutoBanService {
             banned: RwLock::new(HashMap::new()),
             strikes: RwLock::new(HashMap::new()),
             patterns: SuspiciousPatterns::default_patterns(),
+            user_agents: SuspiciousUserAgents::new(
+                &config.user_agent_denylist,
+                &config.user_agent_allowlist,
+            ),
+            exempt_paths: HashSet::new(),
             config,
             pool,
         }
     }
 
+    /// Never flag requests to `path` (exact match).
+    pub fn with_exempt_path(mut self, path: impl Into<String>) -> Self {
+        self.exempt_paths.insert(path.into());
+        self
+    }
+
     /// Returns `true` if the given IP is currently banned.
     pub async fn is_banned(&self, ip: &IpAddr) -> bool {
         let map = self.banned.read().await;
@@ -196,7 +252,13 @@ impl AutoBanService {
 
     /// Returns `true` if the path matches suspicious patterns.
     pub fn is_suspicious(&self, path: &str) -> bool {
-        self.patterns.matches(path)
+        !self.exempt_paths.contains(path) && self.patterns.matches(path)
+    }
+
+    /// Returns `true` if a request to `path` with this User-Agent is from a
+    /// known scanner. Exempt paths are never flagged.
+    pub fn is_suspicious_user_agent(&self, path: &str, user_agent: &str) -> bool {
+        !self.exempt_paths.contains(path) && self.user_agents.matches(user_agent)
     }
 
     /// Record a strike for the IP. Returns `true` if the IP was **newly** banned.
@@ -282,6 +344,15 @@ impl AutoBanService {
         }
     }
 
+    /// Lift the ban on `ip` and forget its strikes. Returns `true` if the IP
+    /// was banned in memory. Only this replica's map is touched; the caller
+    /// deletes the row with [`delete_ban`].
+    pub async fn unban(&self, ip: &IpAddr) -> bool {
+        self.strikes.write().await.remove(ip);
+        let removed = self.banned.write().await.remove(ip);
+        removed.is_some()
+    }
+
     /// Populate in-memory ban map from database rows.
     pub async fn load_bans(&self, bans: Vec<IpBanRow>) {
         let mut map = self.banned.write().await;
@@ -312,6 +383,17 @@ pub struct IpBanRow {
     pub expires_at: DateTime<Utc>,
 }
 
+/// A ban as listed to admins, with the address rendered without a prefix.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct IpBan {
+    pub id: Uuid,
+    pub ip_address: String,
+    pub reason: String,
+    pub strikes: i32,
+    pub banned_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
 /// Persist a ban to the database (upsert).
 async fn persist_ban(
     pool: &PgPool,
@@ -359,6 +441,47 @@ pub async fn load_active_bans(pool: &PgPool) -> Result<Vec<IpBanRow>, sqlx::Erro
     Ok(rows)
 }
 
+/// List unexpired bans, most recent first, with the total count.
+pub async fn list_active_bans(
+    pool: &PgPool,
+    page: Page,
+    per_page: PerPage,
+) -> Result<(Vec<IpBan>, i64), sqlx::Error> {
+    let bans = sqlx::query_as::<_, IpBan>(
+        r#"
+        SELECT id, host(ip_address) AS ip_address, reason, strikes, banned_at, expires_at
+        FROM ip_bans
+        WHERE expires_at > NOW()
+        ORDER BY banned_at DESC, id
+        LIMIT $1 OFFSET $2
+        "#,
+    )
+    .bind(per_page.get())
+    .bind(page.offset(per_page))
+    .fetch_all(pool)
+    .await?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ip_bans WHERE expires_at > NOW()")
+        .fetch_one(pool)
+        .await?;
+
+    Ok((bans, total))
+}
+
+/// Delete the ban on `ip`, returning the deleted row if there was one.
+pub async fn delete_ban(pool: &PgPool, ip: &IpAddr) -> Result<Option<IpBan>, sqlx::Error> {
+    sqlx::query_as::<_, IpBan>(
+        r#"
+        DELETE FROM ip_bans
+        WHERE ip_address = $1
+        RETURNING id, host(ip_address) AS ip_address, reason, strikes, banned_at, expires_at
+        "#,
+    )
+    .bind(ipnetwork::IpNetwork::from(*ip))
+    .fetch_optional(pool)
+    .await
+}
+
 // ── Actix middleware ────────────────────────────────────────────────────────
 
 /// Actix middleware factory for auto-banning.
@@ -421,6 +544,12 @@ where
 
         let ip = extract_client_ip(req.request());
         let path = req.path().to_string();
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
 
         Box::pin(async move {
             if let Some(ref ip) = ip {
@@ -430,13 +559,15 @@ where
                     return Ok(req.into_response(res).map_into_right_body());
                 }
 
-                // Check if the path is suspicious
-                if auto_ban.is_suspicious(&path) {
+                // Check if the path or the client is suspicious
+                if auto_ban.is_suspicious(&path)
+                    || auto_ban.is_suspicious_user_agent(&path, &user_agent)
+                {
                     let newly_banned = auto_ban.record_strike(ip, &path).await;
                     if newly_banned {
-                        info!(ip = %ip, path = %path, "Suspicious request triggered auto-ban");
+                        info!(ip = %ip, path = %path, user_agent = %user_agent, "Suspicious request triggered auto-ban");
                     } else {
-                        info!(ip = %ip, path = %path, "Suspicious request recorded as strike");
+                        info!(ip = %ip, path = %path, user_agent = %user_agent, "Suspicious request recorded as strike");
                     }
                     let res = HttpResponse::Forbidden().finish();
                     return Ok(req.into_response(res).map_into_right_body());
@@ -598,10 +729,178 @@ mod tests {
             threshold: 10,
             window_secs: 600,
             ban_duration_secs: 7200,
+            user_agent_denylist: Vec::new(),
+            user_agent_allowlist: Vec::new(),
         };
         assert!(!config.enabled);
         assert_eq!(config.threshold, 10);
         assert_eq!(config.window_secs, 600);
         assert_eq!(config.ban_duration_secs, 7200);
     }
+
+    #[actix_rt::test]
+    async fn exempt_paths_are_never_suspicious() {
+        let pool = PgPool::connect_lazy("postgres://127.0.0.1:1/unused").unwrap();
+        let service = AutoBanService::new(AutoBanConfig::from_env(), pool)
+            .with_exempt_path("/v1/webhooks/stripe_hook.bak");
+
+        assert!(!service.is_suspicious("/v1/webhooks/stripe_hook.bak"));
+        assert!(service.is_suspicious("/v1/webhooks/other.bak"));
+        assert!(!service.is_suspicious("/v1/webhooks/stripe"));
+    }
+
+    #[test]
+    fn suspicious_user_agents_respect_allowlist() {
+        let deny = vec!["sqlmap".to_string(), "=curl/7.0".to_string()];
+        let allow = vec!["nikto-internal".to_string()];
+        let agents = SuspiciousUserAgents::new(&deny, &allow);
+
+        assert!(agents.matches("sqlmap/1.7.2#stable (https://sqlmap.org)"));
+        assert!(agents.matches("curl/7.0"));
+        assert!(!agents.matches("curl/7.88.1"));
+        assert!(!agents.matches(""));
+
+        let with_allow = SuspiciousUserAgents::new(&["nikto".to_string()], &allow);
+        assert!(with_allow.matches("Mozilla/5.00 (Nikto/2.5.0)"));
+        assert!(!with_allow.matches("nikto-internal/1.0"));
+    }
+
+    #[actix_rt::test]
+    async fn scanner_user_agent_accrues_a_strike() {
+        use actix_web::{test, web, App};
+
+        let pool = PgPool::connect_lazy("postgres://127.0.0.1:1/unused").unwrap();
+        let config = AutoBanConfig {
+            enabled: true,
+            threshold: 1,
+            window_secs: 600,
+            ban_duration_secs: 600,
+            user_agent_denylist: vec!["sqlmap".to_string()],
+            user_agent_allowlist: Vec::new(),
+        };
+        let auto_ban = Arc::new(AutoBanService::new(config, pool));
+        let app = test::init_service(
+            App::new()
+                .wrap(AutoBanMiddleware::new(auto_ban.clone()))
+                .route("/v1/users/me", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let request = |ip: &str, user_agent: &str| {
+            test::TestRequest::get()
+                .uri("/v1/users/me")
+                .insert_header(("X-Real-IP", ip.to_string()))
+                .insert_header((header::USER_AGENT, user_agent.to_string()))
+                .to_request()
+        };
+
+        let browser = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+        let res = test::call_service(&app, request("192.0.2.10", browser)).await;
+        assert!(res.status().is_success());
+        assert!(!auto_ban.is_banned(&"192.0.2.10".parse().unwrap()).await);
+
+        // With a threshold of one, the first strike bans
+        let res = test::call_service(&app, request("192.0.2.11", "sqlmap/1.7.2#stable")).await;
+        assert_eq!(res.status(), actix_web::http::StatusCode::FORBIDDEN);
+        assert!(auto_ban.is_banned(&"192.0.2.11".parse().unwrap()).await);
+    }
+
+    #[actix_rt::test]
+    async fn unban_evicts_the_in_memory_ban() {
+        use actix_web::{test, web, App};
+
+        let pool = PgPool::connect_lazy("postgres://127.0.0.1:1/unused").unwrap();
+        let config = AutoBanConfig {
+            enabled: true,
+            threshold: 1,
+            window_secs: 600,
+            ban_duration_secs: 600,
+            user_agent_denylist: Vec::new(),
+            user_agent_allowlist: Vec::new(),
+        };
+        let auto_ban = Arc::new(AutoBanService::new(config, pool));
+        let app = test::init_service(
+            App::new()
+                .wrap(AutoBanMiddleware::new(auto_ban.clone()))
+                .route("/v1/users/me", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let request = |ip: &str| {
+            test::TestRequest::get()
+                .uri("/v1/users/me")
+                .insert_header(("X-Real-IP", ip.to_string()))
+                .to_request()
+        };
+
+        for ip in ["192.0.2.20", "2001:db8::20"] {
+            let addr: IpAddr = ip.parse().unwrap();
+            assert!(auto_ban.record_strike(&addr, "/.env").await);
+            let res = test::call_service(&app, request(ip)).await;
+            assert_eq!(res.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+            assert!(auto_ban.unban(&addr).await);
+            assert!(!auto_ban.is_banned(&addr).await);
+            let res = test::call_service(&app, request(ip)).await;
+            assert!(res.status().is_success(), "{ip} should be let back in");
+
+            // Nothing left to lift
+            assert!(!auto_ban.unban(&addr).await);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn persisted_bans_are_enforced_after_a_restart() {
+        use actix_web::{test, web, App};
+
+        let Ok(url) = std::env::var("DATABASE_URL") else {
+            return;
+        };
+        let Ok(pool) = PgPool::connect(&url).await else {
+            return;
+        };
+        let ip = IpAddr::from(std::net::Ipv6Addr::new(
+            0x2001,
+            0xdb8,
+            0,
+            0,
+            0,
+            0,
+            rand::random(),
+            rand::random(),
+        ));
+        persist_ban(
+            &pool,
+            &ip,
+            "Suspicious path: /.env",
+            5,
+            Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+
+        // What main does on boot, with empty in-memory state
+        let auto_ban = Arc::new(AutoBanService::new(AutoBanConfig::from_env(), pool.clone()));
+        auto_ban
+            .load_bans(load_active_bans(&pool).await.unwrap())
+            .await;
+        let app = test::init_service(
+            App::new()
+                .wrap(AutoBanMiddleware::new(auto_ban.clone()))
+                .route("/v1/users/me", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/v1/users/me")
+            .insert_header(("X-Real-IP", ip.to_string()))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        sqlx::query("DELETE FROM ip_bans WHERE ip_address = $1")
+            .bind(ipnetwork::IpNetwork::from(ip))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
 }
@@ -0,0 +1,183 @@
+//! Compression policy middleware
+//!
+//! Actix's `Compress` encodes every response the client will accept. This
+//! middleware sits inside it and decides which responses are worth encoding:
+//!
+//! - bodies below the configured minimum size are marked
+//!   `Content-Encoding: identity`, which `Compress` leaves alone
+//! - streaming bodies (file downloads, registry blobs) are left unencoded too,
+//!   since they set their own `Content-Length` and are usually compressed already
+//! - a strong `ETag` on a response that will be encoded is weakened, because
+//!   the encoded bytes no longer match the validator
+//!
+//! Wrap it before `Compress` so it sees responses first:
+//! `.wrap(CompressionPolicy::new(min_size)).wrap(Compress::default())`.
+
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderValue, CONTENT_ENCODING, ETAG},
+    Error,
+};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+
+/// Decides which responses `Compress` may encode
+pub struct CompressionPolicy {
+    min_size: usize,
+}
+
+impl CompressionPolicy {
+    pub fn new(min_size: usize) -> Self {
+        Self { min_size }
+    }
+}
+
+/// Whether a body of this size should be compressed
+fn worth_compressing(size: BodySize, min_size: usize) -> bool {
+    match size {
+        BodySize::Sized(len) => len >= min_size as u64,
+        BodySize::None | BodySize::Stream => false,
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CompressionPolicy
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CompressionPolicyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CompressionPolicyMiddleware {
+            service,
+            min_size: self.min_size,
+        }))
+    }
+}
+
+pub struct CompressionPolicyMiddleware<S> {
+    service: S,
+    min_size: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for CompressionPolicyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let min_size = self.min_size;
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if res.headers().contains_key(CONTENT_ENCODING) {
+                return Ok(res);
+            }
+
+            if !worth_compressing(res.response().body().size(), min_size) {
+                res.headers_mut()
+                    .insert(CONTENT_ENCODING, HeaderValue::from_static("identity"));
+                return Ok(res);
+            }
+
+            let weakened = res
+                .headers()
+                .get(ETAG)
+                .and_then(|v| v.to_str().ok())
+                .filter(|etag| !etag.starts_with("W/"))
+                .and_then(|etag| HeaderValue::from_str(&format!("W/{}", etag)).ok());
+            if let Some(etag) = weakened {
+                res.headers_mut().insert(ETAG, etag);
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        http::header::ACCEPT_ENCODING, middleware::Compress, test, web, App, HttpResponse,
+    };
+
+    async fn large() -> HttpResponse {
+        let rows: Vec<_> = (0..500)
+            .map(|i| serde_json::json!({ "id": i, "email": format!("user{}@example.com", i) }))
+            .collect();
+        HttpResponse::Ok()
+            .insert_header((ETAG, "\"v1\""))
+            .json(rows)
+    }
+
+    async fn small() -> HttpResponse {
+        HttpResponse::Ok().json(serde_json::json!({ "ok": true }))
+    }
+
+    async fn streamed() -> HttpResponse {
+        let chunks =
+            futures_util::stream::iter(vec![Ok::<_, Error>(web::Bytes::from(vec![b'a'; 4096]))]);
+        HttpResponse::Ok().streaming(chunks)
+    }
+
+    async fn encoding_of(path: &str) -> (Option<String>, Option<String>) {
+        let app = test::init_service(
+            App::new()
+                .wrap(CompressionPolicy::new(1024))
+                .wrap(Compress::default())
+                .route("/large", web::get().to(large))
+                .route("/small", web::get().to(small))
+                .route("/streamed", web::get().to(streamed)),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri(path)
+            .insert_header((ACCEPT_ENCODING, "gzip"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        let header = |name: actix_web::http::header::HeaderName| {
+            res.headers()
+                .get(name)
+                .map(|v| v.to_str().unwrap().to_string())
+        };
+        (header(CONTENT_ENCODING), header(ETAG))
+    }
+
+    #[actix_rt::test]
+    async fn large_responses_are_gzipped_with_a_weak_etag() {
+        let (encoding, etag) = encoding_of("/large").await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        assert_eq!(etag.as_deref(), Some("W/\"v1\""));
+    }
+
+    #[actix_rt::test]
+    async fn small_and_streamed_responses_are_not_compressed() {
+        assert_eq!(encoding_of("/small").await.0.as_deref(), Some("identity"));
+        assert_eq!(
+            encoding_of("/streamed").await.0.as_deref(),
+            Some("identity")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn threshold_applies_to_sized_bodies_only() {
+        assert!(worth_compressing(BodySize::Sized(1024), 1024));
+        assert!(!worth_compressing(BodySize::Sized(1023), 1024));
+        assert!(!worth_compressing(BodySize::Stream, 0));
+        assert!(!worth_compressing(BodySize::None, 0));
+    }
+}
@@ -0,0 +1,173 @@
+//! Server error alert middleware
+//!
+//! Reports every 5xx response to the [`ErrorAlerter`], which decides whether
+//! it is worth a push alert. Must sit inside `RequestIdMiddleware` so the
+//! request id is available.
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::errors::AppError;
+use crate::middleware::request_id::RequestId;
+use crate::services::ErrorAlerter;
+
+/// Server error alert middleware; a no-op without an alerter
+pub struct ErrorAlerts {
+    alerter: Option<Arc<ErrorAlerter>>,
+}
+
+impl ErrorAlerts {
+    pub fn new(alerter: Option<Arc<ErrorAlerter>>) -> Self {
+        Self { alerter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ErrorAlerts
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ErrorAlertsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ErrorAlertsMiddleware {
+            service,
+            alerter: self.alerter.clone(),
+        }))
+    }
+}
+
+pub struct ErrorAlertsMiddleware<S> {
+    service: S,
+    alerter: Option<Arc<ErrorAlerter>>,
+}
+
+impl<S, B> Service<ServiceRequest> for ErrorAlertsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let alerter = self.alerter.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            let Some(alerter) = alerter else {
+                return Ok(res);
+            };
+            let status = res.status();
+            if !status.is_server_error() {
+                return Ok(res);
+            }
+
+            // The code only, never the message: internal errors carry detail
+            // that belongs in the logs
+            let code = res
+                .response()
+                .error()
+                .and_then(|e| e.as_error::<AppError>())
+                .map(AppError::dynamic_error_code)
+                .unwrap_or_else(|| format!("HTTP_{}", status.as_u16()));
+            let request_id = res
+                .request()
+                .extensions()
+                .get::<RequestId>()
+                .map(|id| id.0.clone())
+                .unwrap_or_else(|| "unknown".to_string());
+            let route = res
+                .request()
+                .match_pattern()
+                .unwrap_or_else(|| "unmatched".to_string());
+
+            alerter.report(&request_id, status.as_u16(), &code, &route);
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::request_id::RequestIdMiddleware;
+    use crate::services::error_alert::{AlertSink, ServerErrorAlert};
+    use actix_web::{test, web, App, HttpResponse};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<ServerErrorAlert>>);
+
+    impl AlertSink for RecordingSink {
+        fn send(&self, alert: ServerErrorAlert) {
+            self.0.lock().unwrap().push(alert);
+        }
+    }
+
+    async fn fail() -> Result<HttpResponse, AppError> {
+        Err(AppError::DatabaseError {
+            message: "relation \"users\" does not exist".to_string(),
+        })
+    }
+
+    async fn missing() -> Result<HttpResponse, AppError> {
+        Err(AppError::not_found("User"))
+    }
+
+    #[actix_rt::test]
+    async fn repeated_5xxs_alert_once_with_the_code_only() {
+        let sink = Arc::new(RecordingSink::default());
+        let alerter = Arc::new(ErrorAlerter::new(
+            sink.clone(),
+            chrono::Duration::minutes(5),
+        ));
+        let app = test::init_service(
+            App::new()
+                .wrap(ErrorAlerts::new(Some(alerter)))
+                .wrap(RequestIdMiddleware)
+                .route("/users/{user_id}", web::get().to(fail))
+                .route("/missing", web::get().to(missing)),
+        )
+        .await;
+
+        let mut request_ids = Vec::new();
+        for _ in 0..5 {
+            let req = test::TestRequest::get().uri("/users/42").to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), 500);
+            request_ids.push(
+                res.headers()
+                    .get("x-request-id")
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            );
+        }
+        let req = test::TestRequest::get().uri("/missing").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+        let alerts = sink.0.lock().unwrap();
+        assert_eq!(alerts.len(), 1, "later 5xxs are inside the cooldown");
+        assert_eq!(alerts[0].code, "DATABASE_ERROR");
+        assert_eq!(alerts[0].route, "/users/{user_id}");
+        assert_eq!(alerts[0].request_id, request_ids[0]);
+        assert!(!format!("{:?}", alerts[0]).contains("relation"));
+    }
+}
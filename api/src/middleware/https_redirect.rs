@@ -0,0 +1,193 @@
+//! HTTPS redirect middleware
+//!
+//! Answers plain-HTTP requests with a 308 to the same URL over https, for
+//! deployments whose TLS-terminating proxy forwards HTTP instead of
+//! redirecting it. The scheme is read from `X-Forwarded-Proto`, believed only
+//! from a trusted proxy; anything else could send the header to bounce
+//! requests around. Health checks are exempt, since load balancers probe them
+//! over plain HTTP.
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error, HttpResponse,
+};
+use ipnetwork::IpNetwork;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// HTTPS redirect middleware
+pub struct HttpsRedirect {
+    trusted_proxies: Rc<Vec<IpNetwork>>,
+}
+
+impl HttpsRedirect {
+    /// Redirect requests that a proxy in `trusted_proxies` reports as HTTP
+    pub fn new(trusted_proxies: Vec<IpNetwork>) -> Self {
+        Self {
+            trusted_proxies: Rc::new(trusted_proxies),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HttpsRedirect
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = HttpsRedirectMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpsRedirectMiddleware {
+            service,
+            trusted_proxies: Rc::clone(&self.trusted_proxies),
+        }))
+    }
+}
+
+pub struct HttpsRedirectMiddleware<S> {
+    service: S,
+    trusted_proxies: Rc<Vec<IpNetwork>>,
+}
+
+impl<S> HttpsRedirectMiddleware<S> {
+    /// Whether a trusted proxy says `req` arrived over plain HTTP
+    fn forwarded_over_http(&self, req: &ServiceRequest) -> bool {
+        let trusted = req.peer_addr().is_some_and(|peer| {
+            self.trusted_proxies
+                .iter()
+                .any(|net| net.contains(peer.ip()))
+        });
+        if !trusted {
+            return false;
+        }
+
+        // A proxy chain appends; the first entry is the client-facing hop
+        req.headers()
+            .get("X-Forwarded-Proto")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("http"))
+    }
+}
+
+/// Load balancer probes, served over whatever scheme they arrive on
+fn is_health_check(path: &str) -> bool {
+    path == "/health" || path.starts_with("/health/") || path == "/v1/health"
+}
+
+impl<S, B> Service<ServiceRequest> for HttpsRedirectMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if is_health_check(req.path()) || !self.forwarded_over_http(&req) {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(|res| res.map_into_left_body()) });
+        }
+
+        let path_and_query = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        let location = format!("https://{}{}", req.connection_info().host(), path_and_query);
+        let res = HttpResponse::PermanentRedirect()
+            .insert_header((header::LOCATION, location))
+            .finish();
+        Box::pin(async move { Ok(req.into_response(res).map_into_right_body()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+
+    const PROXY: &str = "10.0.0.7:40000";
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    macro_rules! app {
+        () => {
+            test::init_service(
+                App::new()
+                    .wrap(HttpsRedirect::new(vec!["10.0.0.0/24".parse().unwrap()]))
+                    .route("/v1/users/me", web::post().to(ok))
+                    .route("/health", web::get().to(ok)),
+            )
+            .await
+        };
+    }
+
+    #[actix_rt::test]
+    async fn http_from_a_trusted_proxy_is_redirected_to_https() {
+        let app = app!();
+        let req = test::TestRequest::post()
+            .uri("/v1/users/me?tab=billing")
+            .peer_addr(PROXY.parse().unwrap())
+            .insert_header(("Host", "api.example.com"))
+            .insert_header(("X-Forwarded-Proto", "http"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), 308, "308 keeps the method and body");
+        assert_eq!(
+            res.headers().get(header::LOCATION).unwrap(),
+            "https://api.example.com/v1/users/me?tab=billing"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn https_and_health_checks_pass_through() {
+        let app = app!();
+        let req = test::TestRequest::post()
+            .uri("/v1/users/me")
+            .peer_addr(PROXY.parse().unwrap())
+            .insert_header(("X-Forwarded-Proto", "https"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        let req = test::TestRequest::get()
+            .uri("/health")
+            .peer_addr(PROXY.parse().unwrap())
+            .insert_header(("X-Forwarded-Proto", "http"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+    }
+
+    #[actix_rt::test]
+    async fn forwarded_proto_from_an_untrusted_peer_is_ignored() {
+        let app = app!();
+        let req = test::TestRequest::post()
+            .uri("/v1/users/me")
+            .peer_addr("203.0.113.9:40000".parse().unwrap())
+            .insert_header(("X-Forwarded-Proto", "http"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        // Without a peer address there is nothing to trust
+        let req = test::TestRequest::post()
+            .uri("/v1/users/me")
+            .insert_header(("X-Forwarded-Proto", "http"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+    }
+}
@@ -0,0 +1,138 @@
+//! Impersonation indicator middleware
+//!
+//! Adds an `X-Impersonating: <admin_email>` header to responses for requests
+//! authenticated with an impersonation token, so the frontend can warn the
+//! admin that they are acting as another user.
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    Error, HttpMessage,
+};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+
+use super::auth::AuthenticatedClaims;
+
+/// Response header naming the impersonating admin
+pub const IMPERSONATING_HEADER: &str = "x-impersonating";
+
+/// Impersonation indicator middleware
+///
+/// Claims are stashed in request extensions by the auth extractors, so the
+/// header is added after the handler has run.
+pub struct ImpersonationHeader;
+
+impl<S, B> Transform<S, ServiceRequest> for ImpersonationHeader
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ImpersonationHeaderMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ImpersonationHeaderMiddleware { service }))
+    }
+}
+
+pub struct ImpersonationHeaderMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ImpersonationHeaderMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let impersonator = res
+                .request()
+                .extensions()
+                .get::<AuthenticatedClaims>()
+                .and_then(|claims| claims.0.impersonator_email.clone());
+
+            if let Some(value) = impersonator.and_then(|e| HeaderValue::from_str(&e).ok()) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(IMPERSONATING_HEADER), value);
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::AuthenticatedUser;
+    use crate::models::User;
+    use crate::services::{JwtConfig, JwtService};
+    use actix_web::{http::header, test, web, App, HttpResponse};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn test_user() -> User {
+        User {
+            email: "member@example.com".to_string(),
+            ..User::test_fixture()
+        }
+    }
+
+    async fn ok(_user: AuthenticatedUser) -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_rt::test]
+    async fn header_set_only_for_impersonation_tokens() {
+        let jwt = Arc::new(JwtService::new(JwtConfig::from_secret(
+            "test-secret-key-12345",
+            "localhost",
+        )));
+        let user = test_user();
+        let normal = jwt.create_access_token(&user).unwrap();
+        let impersonated = jwt
+            .create_impersonation_token(&user, Uuid::new_v4(), "admin@example.com")
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .wrap(ImpersonationHeader)
+                .app_data(jwt.clone())
+                .route("/me", web::get().to(ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/me")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", impersonated)))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get(IMPERSONATING_HEADER).unwrap(),
+            "admin@example.com"
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/me")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", normal)))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(res.status().is_success());
+        assert!(res.headers().get(IMPERSONATING_HEADER).is_none());
+    }
+}
@@ -0,0 +1,136 @@
+//! Body extractor accepting JSON or, when enabled, urlencoded forms
+//!
+//! Auth endpoints use `JsonOrForm<T>` in place of `web::Json<T>` so
+//! integrators that POST `application/x-www-form-urlencoded` can log in.
+//! Form bodies are only accepted when a `JsonOrFormConfig` with
+//! `accept_form` set is registered as app data (AUTH_ACCEPT_FORM_BODIES).
+
+use actix_web::{dev::Payload, web, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+
+/// App data toggling form-body support for `JsonOrForm`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonOrFormConfig {
+    pub accept_form: bool,
+}
+
+impl JsonOrFormConfig {
+    pub fn new(accept_form: bool) -> Self {
+        Self { accept_form }
+    }
+}
+
+/// A request body parsed from JSON, or from a urlencoded form when allowed.
+///
+/// Anything that is not an allowed form body goes through `web::Json`, so
+/// JSON-only behavior (and its errors) is unchanged when the toggle is off.
+#[derive(Debug)]
+pub struct JsonOrForm<T>(pub T);
+
+impl<T> JsonOrForm<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for JsonOrForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for JsonOrForm<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let accept_form = req
+            .app_data::<JsonOrFormConfig>()
+            .is_some_and(|c| c.accept_form);
+
+        if accept_form && req.content_type() == "application/x-www-form-urlencoded" {
+            let form = web::Form::<T>::from_request(req, payload);
+            Box::pin(async move { form.await.map(|f| JsonOrForm(f.into_inner())) })
+        } else {
+            let json = web::Json::<T>::from_request(req, payload);
+            Box::pin(async move { json.await.map(|j| JsonOrForm(j.into_inner())) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::auth::LoginRequest;
+    use actix_web::{test, App, HttpResponse};
+
+    async fn echo_login(body: JsonOrForm<LoginRequest>) -> HttpResponse {
+        HttpResponse::Ok().json(serde_json::json!({
+            "email": body.email,
+            "password": body.password,
+            "remember": body.remember,
+        }))
+    }
+
+    async fn post_login(accept_form: bool, req: test::TestRequest) -> (u16, serde_json::Value) {
+        let app = test::init_service(
+            App::new()
+                .app_data(JsonOrFormConfig::new(accept_form))
+                .route("/login", web::post().to(echo_login)),
+        )
+        .await;
+        let resp = test::call_service(&app, req.uri("/login").to_request()).await;
+        let status = resp.status().as_u16();
+        let body = if status == 200 {
+            test::read_body_json(resp).await
+        } else {
+            serde_json::Value::Null
+        };
+        (status, body)
+    }
+
+    fn expected() -> serde_json::Value {
+        serde_json::json!({
+            "email": "user@example.com",
+            "password": "Correct horse 1!",
+            "remember": true,
+        })
+    }
+
+    #[actix_rt::test]
+    async fn same_login_parses_as_json_and_as_form() {
+        let json = test::TestRequest::post().set_json(serde_json::json!({
+            "email": "user@example.com",
+            "password": "Correct horse 1!",
+            "remember": true,
+        }));
+        assert_eq!(post_login(true, json).await, (200, expected()));
+
+        let form = test::TestRequest::post().set_form([
+            ("email", "user@example.com"),
+            ("password", "Correct horse 1!"),
+            ("remember", "true"),
+        ]);
+        assert_eq!(post_login(true, form).await, (200, expected()));
+    }
+
+    #[actix_rt::test]
+    async fn form_is_rejected_when_disabled() {
+        let form =
+            test::TestRequest::post().set_form([("email", "user@example.com"), ("password", "pw")]);
+        let (status, _) = post_login(false, form).await;
+        assert!((400..500).contains(&status), "got {}", status);
+
+        // JSON still works with the toggle off
+        let json = test::TestRequest::post().set_json(serde_json::json!({
+            "email": "user@example.com",
+            "password": "Correct horse 1!",
+            "remember": true,
+        }));
+        assert_eq!(post_login(false, json).await, (200, expected()));
+    }
+}
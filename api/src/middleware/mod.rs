@@ -2,19 +2,35 @@
 //!
 //! This module contains custom Actix-Web middleware.
 
+pub mod api_rate_limit;
 pub mod auth;
 pub mod auto_ban;
+pub mod compression;
+pub mod error_alerts;
+pub mod https_redirect;
+pub mod impersonation;
+pub mod json_or_form;
 pub mod oci_auth;
 pub mod oci_www_authenticate;
+pub mod rate_limit_warning;
 pub mod request_id;
 pub mod security_headers;
 
 // Re-export commonly used items
+pub use api_rate_limit::ApiRateLimit;
 pub use auth::{
-    extract_client_ip, extract_device_info, AdminUser, AuthCookies, AuthenticatedUser, MemberUser,
-    OptionalUser,
+    extract_client_ip, extract_device_id, extract_device_info, force_token_refresh, require_tier,
+    AdminTwoFactorPolicy, AdminUser, AuthCookies, AuthenticatedUser, IssuesCredentials, MemberUser,
+    OptionalUser, RecentlyAuthenticatedUser, RequirePermission, StepUpPolicy,
+    TWO_FACTOR_CHALLENGE_COOKIE,
 };
 pub use auto_ban::{AutoBanMiddleware, AutoBanService};
+pub use compression::CompressionPolicy;
+pub use error_alerts::ErrorAlerts;
+pub use https_redirect::HttpsRedirect;
+pub use impersonation::ImpersonationHeader;
+pub use json_or_form::{JsonOrForm, JsonOrFormConfig};
 pub use oci_auth::OciBearerUser;
 pub use oci_www_authenticate::OciWwwAuthenticate;
+pub use rate_limit_warning::{record_rate_limit_usage, RateLimitWarning};
 pub use security_headers::SecurityHeaders;
@@ -0,0 +1,202 @@
+//! Soft rate-limit warning middleware
+//!
+//! Handlers record how much of a rate limit a request has used (see
+//! [`record_rate_limit_usage`]). Once that crosses the configured fraction of
+//! the limit, responses carry `X-RateLimit-Warning: <action>; used=N; limit=M`
+//! so clients can slow down before they start getting 429s.
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    Error, HttpMessage, HttpRequest,
+};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+
+use crate::models::RateLimitConfig;
+
+/// Response header carrying the soft rate-limit warning
+pub const RATE_LIMIT_WARNING_HEADER: &str = "x-ratelimit-warning";
+
+/// Rate-limit usage of the current request, stashed in request extensions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitUsage {
+    pub action: &'static str,
+    pub count: i32,
+    pub limit: i32,
+}
+
+impl RateLimitUsage {
+    fn fraction(&self) -> f64 {
+        if self.limit <= 0 {
+            return 1.0;
+        }
+        self.count as f64 / self.limit as f64
+    }
+
+    /// Header value when `count` is in the warning band: at or above
+    /// `threshold` of the limit but not yet over it
+    pub fn warning(&self, threshold: f64) -> Option<String> {
+        if threshold <= 0.0 || self.count > self.limit || self.fraction() < threshold {
+            return None;
+        }
+        Some(format!(
+            "{}; used={}; limit={}",
+            self.action, self.count, self.limit
+        ))
+    }
+}
+
+/// Record the counter returned by `RateLimitRepository` for this request.
+/// When several limits are checked, the one closest to its limit is kept.
+pub fn record_rate_limit_usage(req: &HttpRequest, config: &RateLimitConfig, count: i32) {
+    let usage = RateLimitUsage {
+        action: config.action,
+        count,
+        limit: config.max_requests,
+    };
+    let mut extensions = req.extensions_mut();
+    let closer = extensions
+        .get::<RateLimitUsage>()
+        .map_or(true, |existing| usage.fraction() > existing.fraction());
+    if closer {
+        extensions.insert(usage);
+    }
+}
+
+/// Soft rate-limit warning middleware
+///
+/// `threshold` is the fraction of a limit (e.g. 0.8) from which the warning
+/// header is sent; 0 disables it.
+pub struct RateLimitWarning {
+    threshold: f64,
+}
+
+impl RateLimitWarning {
+    pub fn new(threshold: f64) -> Self {
+        Self { threshold }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitWarning
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimitWarningMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitWarningMiddleware {
+            service,
+            threshold: self.threshold,
+        }))
+    }
+}
+
+pub struct RateLimitWarningMiddleware<S> {
+    service: S,
+    threshold: f64,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitWarningMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let threshold = self.threshold;
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let warning = res
+                .request()
+                .extensions()
+                .get::<RateLimitUsage>()
+                .and_then(|usage| usage.warning(threshold));
+
+            if let Some(value) = warning.and_then(|w| HeaderValue::from_str(&w).ok()) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(RATE_LIMIT_WARNING_HEADER), value);
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn used(req: HttpRequest, path: web::Path<i32>) -> HttpResponse {
+        record_rate_limit_usage(&req, &RateLimitConfig::LOGIN, path.into_inner());
+        HttpResponse::Ok().finish()
+    }
+
+    async fn warning_for(count: i32) -> Option<String> {
+        let app = test::init_service(
+            App::new()
+                .wrap(RateLimitWarning::new(0.8))
+                .route("/used/{count}", web::get().to(used)),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri(&format!("/used/{}", count))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        res.headers()
+            .get(RATE_LIMIT_WARNING_HEADER)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[actix_rt::test]
+    async fn header_appears_only_in_the_warning_band() {
+        // LOGIN allows 5 per window; 80% of that is 4
+        assert_eq!(warning_for(1).await, None);
+        assert_eq!(warning_for(3).await, None);
+        assert_eq!(
+            warning_for(4).await.as_deref(),
+            Some("login; used=4; limit=5")
+        );
+        assert_eq!(
+            warning_for(5).await.as_deref(),
+            Some("login; used=5; limit=5")
+        );
+        // Over the limit the request is blocked instead
+        assert_eq!(warning_for(6).await, None);
+    }
+
+    #[actix_rt::test]
+    async fn zero_threshold_disables_warnings() {
+        let usage = RateLimitUsage {
+            action: "login",
+            count: 5,
+            limit: 5,
+        };
+        assert!(usage.warning(0.0).is_none());
+        assert!(usage.warning(1.0).is_some());
+    }
+
+    #[actix_rt::test]
+    async fn the_limit_closest_to_exhaustion_is_reported() {
+        let req = test::TestRequest::default().to_http_request();
+        record_rate_limit_usage(&req, &RateLimitConfig::LOGIN, 2);
+        record_rate_limit_usage(&req, &RateLimitConfig::MAGIC_LINK, 3);
+        record_rate_limit_usage(&req, &RateLimitConfig::API_UNAUTH, 1);
+        let usage = *req.extensions().get::<RateLimitUsage>().unwrap();
+        assert_eq!(usage.action, "magic_link");
+    }
+}
@@ -4,7 +4,7 @@
 
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    http::header::{HeaderName, HeaderValue},
+    http::header::{HeaderName, HeaderValue, REFERRER_POLICY},
     Error,
 };
 use std::future::{ready, Future, Ready};
@@ -86,11 +86,14 @@ fn add_security_headers(headers: &mut actix_web::http::header::HeaderMap) {
         HeaderValue::from_static("1; mode=block"),
     );
 
-    // Referrer policy - only send origin for cross-origin requests
-    headers.insert(
-        HeaderName::from_static("referrer-policy"),
-        HeaderValue::from_static("strict-origin-when-cross-origin"),
-    );
+    // Referrer policy - only send origin for cross-origin requests.
+    // Handlers may set a stricter policy (e.g. no-referrer on token-bearing URLs).
+    if !headers.contains_key(REFERRER_POLICY) {
+        headers.insert(
+            REFERRER_POLICY,
+            HeaderValue::from_static("strict-origin-when-cross-origin"),
+        );
+    }
 
     // HSTS - enforce HTTPS with preload
     // max-age=31536000 = 1 year
@@ -172,4 +175,16 @@ mod tests {
         let value = headers.get("x-frame-options").unwrap();
         assert_eq!(value, "DENY");
     }
+
+    #[test]
+    fn test_referrer_policy_preserves_handler_value() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static("referrer-policy"),
+            HeaderValue::from_static("no-referrer"),
+        );
+        add_security_headers(&mut headers);
+
+        assert_eq!(headers.get("referrer-policy").unwrap(), "no-referrer");
+    }
 }
@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::models::MemberAccessReason;
+
 /// Application database model
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Application {
@@ -73,6 +75,38 @@ impl ApplicationResponse {
     }
 }
 
+/// Per-application availability for the public status page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplicationStatusResponse {
+    pub slug: String,
+    pub display_name: String,
+    pub maintenance_mode: bool,
+    pub maintenance_message: Option<String>,
+}
+
+impl From<Application> for ApplicationStatusResponse {
+    fn from(app: Application) -> Self {
+        Self {
+            slug: app.slug,
+            display_name: app.display_name,
+            maintenance_mode: app.maintenance_mode,
+            maintenance_message: if app.maintenance_mode {
+                app.maintenance_message
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// An application the current user may open, with the rule that grants access
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessibleApplicationResponse {
+    #[serde(flatten)]
+    pub application: ApplicationResponse,
+    pub access_reason: MemberAccessReason,
+}
+
 impl Application {
     pub fn is_downloadable(&self) -> bool {
         self.forgejo_owner.is_some()
@@ -190,6 +224,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn status_reflects_maintenance_mode() {
+        let mut app = test_app();
+        app.maintenance_message = Some("Upgrading the database".to_string());
+        let up = ApplicationStatusResponse::from(app.clone());
+        assert!(!up.maintenance_mode);
+        assert_eq!(up.maintenance_message, None);
+
+        app.maintenance_mode = true;
+        let down = ApplicationStatusResponse::from(app);
+        assert!(down.maintenance_mode);
+        assert_eq!(
+            down.maintenance_message.as_deref(),
+            Some("Upgrading the database")
+        );
+    }
+
     #[test]
     fn application_is_downloadable_when_all_forgejo_fields_set() {
         let mut app = test_app();
@@ -12,13 +12,18 @@ use uuid::Uuid;
 #[serde(rename_all = "snake_case")]
 pub enum AuditAction {
     UserLogin,
+    UserLoginFailed,
+    UserLoginImpossibleTravel,
     UserLogout,
     UserRegistered,
+    RefreshTokenReuseDetected,
     MagicLinkRequested,
     MagicLinkUsed,
     PasswordResetRequested,
     PasswordResetCompleted,
     PasswordChanged,
+    PasswordSetRequested,
+    PasswordSet,
     MembershipCreated,
     MembershipCanceled,
     MembershipReactivated,
@@ -57,7 +62,11 @@ pub enum AuditAction {
     AdminStripeConfigUpdated,
     AdminTierConfigUpdated,
     AdminKeyRotation,
+    AdminUsersMerged,
+    AdminMembershipReconciled,
+    AdminIpUnbanned,
     UserAccountDeleted,
+    UserDataExportRequested,
     DownloadRequested,
     DownloadCompleted,
     DownloadDeniedMembership,
@@ -76,13 +85,18 @@ impl AuditAction {
     pub fn as_str(&self) -> &'static str {
         match self {
             AuditAction::UserLogin => "user_login",
+            AuditAction::UserLoginFailed => "user_login_failed",
+            AuditAction::UserLoginImpossibleTravel => "user_login_impossible_travel",
             AuditAction::UserLogout => "user_logout",
             AuditAction::UserRegistered => "user_registered",
+            AuditAction::RefreshTokenReuseDetected => "refresh_token_reuse_detected",
             AuditAction::MagicLinkRequested => "magic_link_requested",
             AuditAction::MagicLinkUsed => "magic_link_used",
             AuditAction::PasswordResetRequested => "password_reset_requested",
             AuditAction::PasswordResetCompleted => "password_reset_completed",
             AuditAction::PasswordChanged => "password_changed",
+            AuditAction::PasswordSetRequested => "password_set_requested",
+            AuditAction::PasswordSet => "password_set",
             AuditAction::MembershipCreated => "membership_created",
             AuditAction::MembershipCanceled => "membership_canceled",
             AuditAction::MembershipReactivated => "membership_reactivated",
@@ -123,7 +137,11 @@ impl AuditAction {
             AuditAction::AdminStripeConfigUpdated => "admin_stripe_config_updated",
             AuditAction::AdminTierConfigUpdated => "admin_tier_config_updated",
             AuditAction::AdminKeyRotation => "admin_key_rotation",
+            AuditAction::AdminUsersMerged => "admin_users_merged",
+            AuditAction::AdminMembershipReconciled => "admin_membership_reconciled",
+            AuditAction::AdminIpUnbanned => "admin_ip_unbanned",
             AuditAction::UserAccountDeleted => "user_account_deleted",
+            AuditAction::UserDataExportRequested => "user_data_export_requested",
             AuditAction::DownloadRequested => "download_requested",
             AuditAction::DownloadCompleted => "download_completed",
             AuditAction::DownloadDeniedMembership => "download_denied_membership",
@@ -139,6 +157,12 @@ impl AuditAction {
         }
     }
 
+    /// The action named by `value`, which uses the same names as
+    /// [`AuditAction::as_str`]
+    pub fn parse(value: &str) -> Option<Self> {
+        serde_json::from_value(JsonValue::String(value.to_string())).ok()
+    }
+
     pub fn is_admin_action(&self) -> bool {
         matches!(
             self,
@@ -163,12 +187,74 @@ impl AuditAction {
                 | AuditAction::AdminStripeConfigUpdated
                 | AuditAction::AdminTierConfigUpdated
                 | AuditAction::AdminKeyRotation
+                | AuditAction::AdminUsersMerged
+                | AuditAction::AdminMembershipReconciled
+                | AuditAction::AdminIpUnbanned
+        )
+    }
+
+    /// Changes to role, tier, membership status or 2FA, which make outstanding
+    /// access tokens carry stale claims. Used unless TOKEN_REFRESH_ACTIONS
+    /// overrides the list.
+    pub fn affects_token_claims(&self) -> bool {
+        matches!(
+            self,
+            AuditAction::MembershipCreated
+                | AuditAction::MembershipCanceled
+                | AuditAction::MembershipReactivated
+                | AuditAction::GracePeriodStarted
+                | AuditAction::GracePeriodEnded
+                | AuditAction::AdminMembershipGranted
+                | AuditAction::AdminMembershipRevoked
+                | AuditAction::AdminUserDeactivated
+                | AuditAction::AdminUserRoleChanged
+                | AuditAction::AdminUsersMerged
+                | AuditAction::AdminMembershipReconciled
+                | AuditAction::TwoFactorEnabled
+                | AuditAction::TwoFactorDisabled
+        )
+    }
+
+    /// Membership and payment events; revenue stats and membership history are
+    /// derived from these, so they are always recorded regardless of the
+    /// audit policy
+    pub fn is_billing_action(&self) -> bool {
+        matches!(
+            self,
+            AuditAction::MembershipCreated
+                | AuditAction::MembershipCanceled
+                | AuditAction::MembershipReactivated
+                | AuditAction::PaymentSucceeded
+                | AuditAction::PaymentFailed
+                | AuditAction::GracePeriodStarted
+                | AuditAction::GracePeriodEnded
+        )
+    }
+
+    /// Account-security events; like admin actions, these are always recorded
+    /// regardless of the audit policy
+    pub fn is_security_action(&self) -> bool {
+        matches!(
+            self,
+            AuditAction::UserLoginFailed
+                | AuditAction::UserLoginImpossibleTravel
+                | AuditAction::RefreshTokenReuseDetected
+                | AuditAction::PasswordResetCompleted
+                | AuditAction::PasswordChanged
+                | AuditAction::PasswordSet
+                | AuditAction::EmailChangeCompleted
+                | AuditAction::TwoFactorEnabled
+                | AuditAction::TwoFactorDisabled
+                | AuditAction::TwoFactorRecoveryCodeUsed
+                | AuditAction::TwoFactorRecoveryCodesRegenerated
+                | AuditAction::UserAccountDeleted
+                | AuditAction::OciLoginFailed
         )
     }
 }
 
-/// Audit severity levels
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Audit severity levels, in increasing order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum AuditSeverity {
     Info,
@@ -186,6 +272,16 @@ impl AuditSeverity {
             AuditSeverity::Critical => "critical",
         }
     }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "info" => Some(AuditSeverity::Info),
+            "warning" => Some(AuditSeverity::Warning),
+            "error" => Some(AuditSeverity::Error),
+            "critical" => Some(AuditSeverity::Critical),
+            _ => None,
+        }
+    }
 }
 
 impl Default for AuditSeverity {
@@ -283,6 +379,59 @@ impl CreateAuditLog {
         self.severity = severity;
         self
     }
+
+    /// Privacy-masked copy of the actor email and IP.
+    ///
+    /// The email becomes `a***@example.com#<hash>`, where the hash is a keyed
+    /// HMAC of the normalized address so entries for one actor still correlate.
+    /// The IP is truncated to its /24 (IPv4) or /48 (IPv6) network.
+    pub fn masked(mut self, hash_key: &[u8]) -> Self {
+        self.actor_email = self
+            .actor_email
+            .map(|email| mask_audit_email(&email, hash_key));
+        self.actor_ip_address = self.actor_ip_address.map(mask_audit_ip);
+        self
+    }
+}
+
+fn mask_audit_email(email: &str, hash_key: &[u8]) -> String {
+    let digest = audit_email_hash(email, hash_key);
+
+    format!(
+        "{}#{}",
+        crate::models::Feedback::mask_email(&email.trim().to_lowercase()),
+        &digest[..16]
+    )
+}
+
+/// Hex HMAC-SHA256 of the normalized `email`, keyed by `hash_key`
+/// (AUDIT_MASK_KEY), for correlating audit entries without the address
+pub(crate) fn audit_email_hash(email: &str, hash_key: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    let normalized = email.trim().to_lowercase();
+    let mut mac = Hmac::<Sha256>::new_from_slice(hash_key).expect("HMAC accepts any key size");
+    mac.update(normalized.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Truncate an address to its /24 (IPv4) or /48 (IPv6) network
+pub(crate) fn mask_audit_ip(ip: IpNetwork) -> IpNetwork {
+    let masked = match ip.ip() {
+        std::net::IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            IpNetwork::new(std::net::Ipv4Addr::new(a, b, c, 0).into(), 24)
+        }
+        std::net::IpAddr::V6(v6) => {
+            let s = v6.segments();
+            IpNetwork::new(
+                std::net::Ipv6Addr::new(s[0], s[1], s[2], 0, 0, 0, 0, 0).into(),
+                48,
+            )
+        }
+    };
+    masked.expect("prefix length is within range")
 }
 
 /// Admin notification types
@@ -346,6 +495,7 @@ mod tests {
     fn audit_action_as_str_covers_all_variants() {
         // Spot-check a few
         assert_eq!(AuditAction::UserLogin.as_str(), "user_login");
+        assert_eq!(AuditAction::UserLoginFailed.as_str(), "user_login_failed");
         assert_eq!(AuditAction::UserRegistered.as_str(), "user_registered");
         assert_eq!(
             AuditAction::AdminUserImpersonated.as_str(),
@@ -364,6 +514,12 @@ mod tests {
             "admin_user_role_changed"
         );
         assert_eq!(AuditAction::AdminUserDeleted.as_str(), "admin_user_deleted");
+        assert_eq!(AuditAction::AdminUsersMerged.as_str(), "admin_users_merged");
+        assert_eq!(
+            AuditAction::AdminMembershipReconciled.as_str(),
+            "admin_membership_reconciled"
+        );
+        assert_eq!(AuditAction::AdminIpUnbanned.as_str(), "admin_ip_unbanned");
         assert_eq!(
             AuditAction::ApplicationUpdated.as_str(),
             "application_updated"
@@ -509,4 +665,39 @@ mod tests {
         assert!(log.metadata.is_none());
         assert_eq!(log.severity.as_str(), "info");
     }
+
+    // -- Masking --
+
+    #[test]
+    fn masked_hides_email_but_keeps_correlation() {
+        let ip: IpNetwork = "203.0.113.77".parse().unwrap();
+        let log = CreateAuditLog::new(AuditAction::UserLogin)
+            .with_actor(Uuid::new_v4(), "Alice@Example.com", "subscriber")
+            .with_ip(Some(ip))
+            .masked(b"key");
+
+        let email = log.actor_email.unwrap();
+        assert!(email.starts_with("a***@example.com#"), "{email}");
+        assert_eq!(email.len(), "a***@example.com#".len() + 16);
+        assert!(!email.contains("alice"));
+        assert_eq!(log.actor_ip_address.unwrap().to_string(), "203.0.113.0/24");
+
+        // Same address, any casing -> same masked value; different key -> different hash
+        assert_eq!(mask_audit_email(" alice@example.com", b"key"), email);
+        assert_ne!(mask_audit_email("alice@example.com", b"other"), email);
+        assert_ne!(mask_audit_email("bob@example.com", b"key"), email);
+    }
+
+    #[test]
+    fn masked_truncates_ipv6_to_48() {
+        let ip: IpNetwork = "2001:db8:abcd:12::1".parse().unwrap();
+        assert_eq!(mask_audit_ip(ip).to_string(), "2001:db8:abcd::/48");
+    }
+
+    #[test]
+    fn masked_leaves_missing_actor_fields_alone() {
+        let log = CreateAuditLog::new(AuditAction::UserLogout).masked(b"key");
+        assert!(log.actor_email.is_none());
+        assert!(log.actor_ip_address.is_none());
+    }
 }
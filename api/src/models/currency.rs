@@ -0,0 +1,107 @@
+//! Currency codes
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::errors::AppError;
+
+/// A validated ISO 4217 currency code that Stripe accepts, stored lowercase
+/// (Stripe's own representation, e.g. `usd`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency(String);
+
+impl Currency {
+    /// Parse a currency code, accepting any case and surrounding whitespace.
+    pub fn parse(code: &str) -> Result<Self, AppError> {
+        let normalized = code.trim().to_lowercase();
+        let known = normalized.len() == 3 && normalized.parse::<stripe::Currency>().is_ok();
+        if !known {
+            return Err(AppError::validation(
+                "currency",
+                "Currency must be a supported ISO 4217 code",
+            ));
+        }
+        Ok(Self(normalized))
+    }
+
+    /// Normalize a code received from Stripe. Unknown codes are kept (lowercased)
+    /// rather than dropped, since the payment already happened.
+    pub fn normalize_lenient(code: &str) -> String {
+        match Self::parse(code) {
+            Ok(currency) => currency.0,
+            Err(_) => {
+                tracing::warn!(currency = %code, "Unrecognized currency code from Stripe");
+                code.trim().to_lowercase()
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The equivalent `stripe::Currency`
+    pub fn to_stripe(&self) -> stripe::Currency {
+        // Parse cannot fail: the code was validated against this enum.
+        self.0.parse().unwrap_or(stripe::Currency::USD)
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = AppError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_and_normalizes_known_codes() {
+        assert_eq!(Currency::parse("usd").unwrap().as_str(), "usd");
+        assert_eq!(Currency::parse(" EUR ").unwrap().as_str(), "eur");
+        assert_eq!(Currency::parse("Jpy").unwrap().to_string(), "jpy");
+        assert_eq!(
+            Currency::parse("gbp").unwrap().to_stripe(),
+            stripe::Currency::GBP
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unknown_codes() {
+        for code in ["", "us", "usdd", "xyz", "dollars", "12$"] {
+            let err = Currency::parse(code).unwrap_err();
+            assert_eq!(err.error_code(), "VALIDATION_ERROR", "{code}");
+        }
+    }
+
+    #[test]
+    fn normalize_lenient_keeps_unknown_codes_lowercased() {
+        assert_eq!(Currency::normalize_lenient("USD"), "usd");
+        assert_eq!(Currency::normalize_lenient("ZZZ"), "zzz");
+    }
+
+    #[test]
+    fn deserialize_validates() {
+        let c: Currency = serde_json::from_str(r#""CAD""#).unwrap();
+        assert_eq!(c.as_str(), "cad");
+        assert!(serde_json::from_str::<Currency>(r#""nope""#).is_err());
+        assert_eq!(serde_json::to_string(&c).unwrap(), r#""cad""#);
+    }
+}
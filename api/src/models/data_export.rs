@@ -0,0 +1,108 @@
+//! User data export models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Where an export job is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataExportStatus {
+    /// Accepted; the bundle is still being built
+    Queued,
+    /// The bundle can be downloaded
+    Ready,
+    /// Building the bundle failed; request a new export
+    Failed,
+    /// The bundle was ready but is past its retention window
+    Expired,
+}
+
+impl DataExportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataExportStatus::Queued => "queued",
+            DataExportStatus::Ready => "ready",
+            DataExportStatus::Failed => "failed",
+            DataExportStatus::Expired => "expired",
+        }
+    }
+}
+
+/// Data export job database model (the bundle itself is fetched separately)
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DataExport {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Stored state: 'queued', 'ready' or 'failed'
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When a ready bundle stops being downloadable
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl DataExport {
+    /// Lifecycle state as of `now`; a ready bundle past `expires_at` is expired
+    pub fn status_at(&self, now: DateTime<Utc>) -> DataExportStatus {
+        match self.status.as_str() {
+            "ready" if self.expires_at.map_or(true, |at| at <= now) => DataExportStatus::Expired,
+            "ready" => DataExportStatus::Ready,
+            "failed" => DataExportStatus::Failed,
+            _ => DataExportStatus::Queued,
+        }
+    }
+}
+
+/// Export job status for API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataExportResponse {
+    pub job_id: Uuid,
+    pub status: DataExportStatus,
+    pub created_at: DateTime<Utc>,
+    /// When the bundle stops being downloadable; set once ready
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Short-lived signed URL for the bundle; only while ready
+    pub download_url: Option<String>,
+    /// When `download_url` stops working; ask for the status again for a fresh one
+    pub download_url_expires_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn export(status: &str, expires_at: Option<DateTime<Utc>>) -> DataExport {
+        DataExport {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            status: status.to_string(),
+            error: None,
+            created_at: Utc::now(),
+            completed_at: None,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn ready_export_expires_with_its_bundle() {
+        let now = Utc::now();
+        let ready = export("ready", Some(now + Duration::hours(1)));
+        assert_eq!(ready.status_at(now), DataExportStatus::Ready);
+        assert_eq!(
+            ready.status_at(now + Duration::hours(1)),
+            DataExportStatus::Expired
+        );
+        assert_eq!(
+            export("queued", None).status_at(now),
+            DataExportStatus::Queued
+        );
+        assert_eq!(
+            export("failed", None).status_at(now),
+            DataExportStatus::Failed
+        );
+    }
+}
@@ -0,0 +1,19 @@
+//! Per-user IP activity models
+
+use chrono::{DateTime, Utc};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// An IP address a user has signed in or refreshed tokens from
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserIpActivity {
+    pub user_id: Uuid,
+    /// A single address, or its /24 (/48) network when AUDIT_MASK_PII is on
+    pub ip_address: IpNetwork,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Logins and refreshes seen from this address
+    pub count: i64,
+}
@@ -61,6 +61,62 @@ pub struct MembershipResponse {
     pub grace_period_end: Option<DateTime<Utc>>,
 }
 
+/// A local membership field that disagreed with Stripe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MembershipDrift {
+    pub field: String,
+    pub local: Option<String>,
+    pub stripe: Option<String>,
+}
+
+/// Outcome of reconciling a user's membership with their live Stripe subscription
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MembershipReconciliation {
+    pub user_id: Uuid,
+    /// None when the customer has no live subscription
+    pub stripe_subscription_id: Option<String>,
+    pub stripe_status: Option<String>,
+    pub current_period_end: Option<DateTime<Utc>>,
+    /// Fields that were corrected; empty when nothing had drifted
+    pub corrected: Vec<MembershipDrift>,
+}
+
+/// Reason a user gave when canceling their membership
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancellationReason {
+    TooExpensive,
+    NotUsing,
+    MissingFeatures,
+    TechnicalIssues,
+    SwitchingService,
+    Other,
+}
+
+impl CancellationReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CancellationReason::TooExpensive => "too_expensive",
+            CancellationReason::NotUsing => "not_using",
+            CancellationReason::MissingFeatures => "missing_features",
+            CancellationReason::TechnicalIssues => "technical_issues",
+            CancellationReason::SwitchingService => "switching_service",
+            CancellationReason::Other => "other",
+        }
+    }
+}
+
+/// The rule that granted a user member access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberAccessReason {
+    Admin,
+    Lifetime,
+    Trial,
+    ActiveSubscription,
+    GracePeriod,
+}
+
 /// Admin membership response (sourced from users table, Stripe data fetched on demand)
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct AdminMembershipResponse {
@@ -70,6 +126,8 @@ pub struct AdminMembershipResponse {
     pub status: String,
     pub subscription_tier: String,
     pub subscription_override_by: Option<Uuid>,
+    pub cancellation_reason: Option<String>,
+    pub cancellation_feedback: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -106,6 +164,77 @@ impl From<String> for PaymentStatus {
     }
 }
 
+/// Card metadata for a customer's saved payment method.
+///
+/// Only what the dashboard displays is kept; Stripe holds the card itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct PaymentMethod {
+    pub user_id: Uuid,
+    pub stripe_payment_method_id: String,
+    pub brand: String,
+    pub last4: String,
+    pub exp_month: i32,
+    pub exp_year: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Card fields read from a Stripe `payment_method` object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentCard {
+    pub stripe_payment_method_id: String,
+    pub brand: String,
+    pub last4: String,
+    pub exp_month: i32,
+    pub exp_year: i32,
+}
+
+impl PaymentCard {
+    /// Read the card from a Stripe `payment_method` object.
+    ///
+    /// Returns `None` for non-card payment methods and for anything whose
+    /// `last4` is not exactly four digits, so a full card number can never
+    /// end up in the database.
+    pub fn from_stripe(payment_method: &serde_json::Value) -> Option<Self> {
+        let card = &payment_method["card"];
+        let last4 = card["last4"].as_str()?;
+        if last4.len() != 4 || !last4.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let exp_month = i32::try_from(card["exp_month"].as_i64()?).ok()?;
+        if !(1..=12).contains(&exp_month) {
+            return None;
+        }
+
+        Some(Self {
+            stripe_payment_method_id: payment_method["id"].as_str()?.to_string(),
+            brand: card["brand"].as_str().unwrap_or("unknown").to_string(),
+            last4: last4.to_string(),
+            exp_month,
+            exp_year: i32::try_from(card["exp_year"].as_i64()?).ok()?,
+        })
+    }
+}
+
+/// Payment method response for API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentMethodResponse {
+    pub brand: String,
+    pub last4: String,
+    pub exp_month: i32,
+    pub exp_year: i32,
+}
+
+impl From<PaymentMethod> for PaymentMethodResponse {
+    fn from(pm: PaymentMethod) -> Self {
+        Self {
+            brand: pm.brand,
+            last4: pm.last4,
+            exp_month: pm.exp_month,
+            exp_year: pm.exp_year,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,6 +277,41 @@ mod tests {
         assert_eq!(PaymentStatus::Refunded.as_str(), "refunded");
     }
 
+    #[test]
+    fn card_is_read_from_stripe_payment_method() {
+        let pm = serde_json::json!({
+            "id": "pm_123",
+            "type": "card",
+            "card": { "brand": "visa", "last4": "4242", "exp_month": 8, "exp_year": 2030 }
+        });
+        assert_eq!(
+            PaymentCard::from_stripe(&pm),
+            Some(PaymentCard {
+                stripe_payment_method_id: "pm_123".to_string(),
+                brand: "visa".to_string(),
+                last4: "4242".to_string(),
+                exp_month: 8,
+                exp_year: 2030,
+            })
+        );
+    }
+
+    #[test]
+    fn card_rejects_anything_but_last4() {
+        let with_last4 = |last4: &str| {
+            serde_json::json!({
+                "id": "pm_123",
+                "card": { "brand": "visa", "last4": last4, "exp_month": 8, "exp_year": 2030 }
+            })
+        };
+        assert!(PaymentCard::from_stripe(&with_last4("4242424242424242")).is_none());
+        assert!(PaymentCard::from_stripe(&with_last4("42a2")).is_none());
+        assert!(PaymentCard::from_stripe(
+            &serde_json::json!({ "id": "pm_1", "type": "sepa_debit" })
+        )
+        .is_none());
+    }
+
     #[test]
     fn payment_status_from_string() {
         assert_eq!(
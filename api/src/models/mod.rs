@@ -4,11 +4,16 @@
 
 pub mod application;
 pub mod audit;
+pub mod currency;
+pub mod data_export;
 pub mod download;
 pub mod feedback;
+pub mod ip_activity;
 pub mod membership;
 pub mod oci;
+pub mod permission;
 pub mod rate_limit;
+pub mod stats;
 pub mod stripe;
 pub mod tier;
 pub mod token;
@@ -17,13 +22,15 @@ pub mod user;
 
 // Re-export commonly used types
 pub use application::{
-    Application, ApplicationResponse, CreateApplication, DeleteApplicationRequest,
-    SwapApplicationOrderRequest, UpdateApplication,
+    AccessibleApplicationResponse, Application, ApplicationResponse, ApplicationStatusResponse,
+    CreateApplication, DeleteApplicationRequest, SwapApplicationOrderRequest, UpdateApplication,
 };
 pub use audit::{
     AdminNotification, AuditAction, AuditLog, AuditSeverity, CreateAdminNotification,
     CreateAuditLog, NotificationType,
 };
+pub use currency::Currency;
+pub use data_export::{DataExport, DataExportResponse, DataExportStatus};
 pub use download::{
     AppDownloadGroup, AppDownloadsResponse, DownloadAsset, DownloadCacheRow, ReleaseAsset,
     ReleaseMetadata,
@@ -34,14 +41,19 @@ pub use feedback::{
     FeedbackSubmissionResponse, RespondToFeedback, RespondToFeedbackRequest,
     UpdateFeedbackStatusRequest,
 };
+pub use ip_activity::UserIpActivity;
 pub use membership::{
-    AdminMembershipResponse, MembershipResponse, PaymentStatus, StripeSubscriptionStatus,
+    AdminMembershipResponse, CancellationReason, MemberAccessReason, MembershipDrift,
+    MembershipReconciliation, MembershipResponse, PaymentCard, PaymentMethod,
+    PaymentMethodResponse, PaymentStatus, StripeSubscriptionStatus,
 };
-pub use rate_limit::{RateLimit, RateLimitConfig};
+pub use permission::{scopes, Permission, PermissionScope};
+pub use rate_limit::{RateLimit, RateLimitConfig, RateLimitStatus, TieredRateLimit};
+pub use stats::{TimeseriesInterval, TimeseriesMetric, TimeseriesPoint, TimeseriesResponse};
 pub use stripe::{
     StripeConfig, StripeConfigResponse, StripeInvoiceResponse, StripePriceResponse,
-    StripeProductResponse, StripeSubscriptionItemResponse, StripeSubscriptionResponse,
-    StripeWebhookEndpointResponse,
+    StripeProductResponse, StripeProrationPreview, StripeSubscriptionItemResponse,
+    StripeSubscriptionResponse, StripeWebhookEndpointResponse,
 };
 pub use tier::{TierConfigResponse, TierConfigRow};
 pub use token::{
@@ -50,4 +62,7 @@ pub use token::{
     EmailVerificationToken, MagicLinkToken, PasswordResetToken, RefreshToken, SessionInfo,
 };
 pub use totp::{RecoveryCode, UserTotp};
-pub use user::{CreateUser, MembershipStatus, SubscriptionTier, User, UserResponse, UserRole};
+pub use user::{
+    AccountMergeSummary, CreateUser, MembershipStatus, SubscriptionTier, User, UserResponse,
+    UserRole,
+};
@@ -0,0 +1,125 @@
+//! Permission scopes
+//!
+//! Scopes are derived from the `role` column rather than stored, so granting
+//! a role is still a single column update. `admin` holds every scope; the
+//! narrower `support` and `finance` roles hold the subset their work needs.
+
+use serde::Serialize;
+
+/// A single permission scope, rendered as `resource:action`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Permission {
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+    #[serde(rename = "billing:read")]
+    BillingRead,
+    #[serde(rename = "billing:write")]
+    BillingWrite,
+    #[serde(rename = "audit:read")]
+    AuditRead,
+}
+
+impl Permission {
+    /// Every scope; held by `admin`
+    pub const ALL: [Permission; 5] = [
+        Permission::UsersRead,
+        Permission::UsersWrite,
+        Permission::BillingRead,
+        Permission::BillingWrite,
+        Permission::AuditRead,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::UsersRead => "users:read",
+            Permission::UsersWrite => "users:write",
+            Permission::BillingRead => "billing:read",
+            Permission::BillingWrite => "billing:write",
+            Permission::AuditRead => "audit:read",
+        }
+    }
+
+    /// Scopes granted to a role. Unknown roles (including `subscriber`) get none.
+    pub fn for_role(role: &str) -> &'static [Permission] {
+        match role {
+            "admin" => &Self::ALL,
+            "support" => &[Permission::UsersRead, Permission::AuditRead],
+            "finance" => &[
+                Permission::UsersRead,
+                Permission::BillingRead,
+                Permission::BillingWrite,
+            ],
+            _ => &[],
+        }
+    }
+
+    /// Whether `role` holds this scope
+    pub fn granted_to(self, role: &str) -> bool {
+        Self::for_role(role).contains(&self)
+    }
+}
+
+/// Type-level scope for the `RequirePermission` extractor
+pub trait PermissionScope {
+    const PERMISSION: Permission;
+}
+
+/// Marker types naming each scope, e.g. `RequirePermission<scopes::BillingWrite>`
+pub mod scopes {
+    use super::{Permission, PermissionScope};
+
+    macro_rules! scope {
+        ($name:ident) => {
+            #[derive(Debug, Clone, Copy)]
+            pub struct $name;
+
+            impl PermissionScope for $name {
+                const PERMISSION: Permission = Permission::$name;
+            }
+        };
+    }
+
+    scope!(UsersRead);
+    scope!(UsersWrite);
+    scope!(BillingRead);
+    scope!(BillingWrite);
+    scope!(AuditRead);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_holds_every_scope() {
+        for permission in Permission::ALL {
+            assert!(permission.granted_to("admin"), "{}", permission.as_str());
+        }
+    }
+
+    #[test]
+    fn subscriber_and_unknown_roles_hold_nothing() {
+        assert!(Permission::for_role("subscriber").is_empty());
+        assert!(Permission::for_role("").is_empty());
+        assert!(!Permission::UsersRead.granted_to("Admin"));
+    }
+
+    #[test]
+    fn narrower_roles_hold_only_their_scopes() {
+        assert!(Permission::BillingWrite.granted_to("finance"));
+        assert!(!Permission::BillingWrite.granted_to("support"));
+        assert!(Permission::AuditRead.granted_to("support"));
+        assert!(!Permission::UsersWrite.granted_to("finance"));
+    }
+
+    #[test]
+    fn scopes_serialize_as_resource_action() {
+        assert_eq!(
+            serde_json::to_value(Permission::BillingWrite).unwrap(),
+            "billing:write"
+        );
+        assert_eq!(Permission::BillingWrite.as_str(), "billing:write");
+    }
+}
@@ -1,7 +1,9 @@
 //! Rate limiting models
 
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 use sqlx::FromRow;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 /// Rate limit database model
@@ -64,4 +66,133 @@ impl RateLimitConfig {
         max_requests: 3,
         window_seconds: 3600,
     };
+
+    /// Email availability checks: 10 requests per hour per IP
+    pub const EMAIL_AVAILABILITY: Self = Self {
+        action: "email_availability",
+        max_requests: 10,
+        window_seconds: 3600,
+    };
+
+    /// Data export requests: 5 per day per user
+    pub const DATA_EXPORT: Self = Self {
+        action: "data_export",
+        max_requests: 5,
+        window_seconds: 86400,
+    };
+
+    /// Rate limit status reads: 10 requests per minute per user
+    pub const RATE_LIMIT_STATUS: Self = Self {
+        action: "rate_limit_status",
+        max_requests: 10,
+        window_seconds: 60,
+    };
+}
+
+/// One rate limit whose `max_requests` depends on the caller's subscription tier
+#[derive(Debug, Clone)]
+pub struct TieredRateLimit {
+    /// Limit for callers without a listed tier (including no member access)
+    pub base: RateLimitConfig,
+    /// `max_requests` by subscription tier name (e.g. `lifetime`)
+    pub tier_limits: HashMap<String, i32>,
+}
+
+impl TieredRateLimit {
+    pub fn new(base: RateLimitConfig, tier_limits: HashMap<String, i32>) -> Self {
+        Self { base, tier_limits }
+    }
+
+    /// Effective limit for a caller on `tier`; `None` gets the base limit.
+    /// The action and window are shared, so a caller's count carries over
+    /// when their tier changes.
+    pub fn for_tier(&self, tier: Option<&str>) -> RateLimitConfig {
+        match tier.and_then(|t| self.tier_limits.get(t)) {
+            Some(&max_requests) => RateLimitConfig {
+                max_requests,
+                ..self.base
+            },
+            None => self.base,
+        }
+    }
+}
+
+/// Current usage of one rate limit for the caller
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub action: &'static str,
+    /// What the limit is keyed by: `ip` or `email`
+    pub key_type: &'static str,
+    pub count: i32,
+    pub limit: i32,
+    pub remaining: i32,
+    /// Seconds until the window resets; 0 when nothing has been consumed
+    pub reset_in_secs: u64,
+}
+
+impl RateLimitStatus {
+    pub fn new(
+        config: &RateLimitConfig,
+        key_type: &'static str,
+        count: i32,
+        reset_in_secs: u64,
+    ) -> Self {
+        Self {
+            action: config.action,
+            key_type,
+            count,
+            limit: config.max_requests,
+            remaining: (config.max_requests - count).max(0),
+            reset_in_secs: if count == 0 { 0 } else { reset_in_secs },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_reports_remaining_quota() {
+        let status = RateLimitStatus::new(&RateLimitConfig::LOGIN, "email", 2, 41);
+        assert_eq!(status.action, "login");
+        assert_eq!(status.limit, 5);
+        assert_eq!(status.remaining, 3);
+        assert_eq!(status.reset_in_secs, 41);
+    }
+
+    #[test]
+    fn status_never_reports_negative_remaining() {
+        let status = RateLimitStatus::new(&RateLimitConfig::MAGIC_LINK, "email", 7, 300);
+        assert_eq!(status.remaining, 0);
+    }
+
+    #[test]
+    fn unused_limit_has_no_reset() {
+        let status = RateLimitStatus::new(&RateLimitConfig::REGISTRATION, "ip", 0, 1200);
+        assert_eq!(status.remaining, 3);
+        assert_eq!(status.reset_in_secs, 0);
+    }
+
+    #[test]
+    fn higher_tiers_get_higher_limits_on_the_same_action() {
+        let limits = TieredRateLimit::new(
+            RateLimitConfig::API_AUTH,
+            HashMap::from([("standard".to_string(), 300), ("lifetime".to_string(), 600)]),
+        );
+
+        let none = limits.for_tier(None);
+        let free = limits.for_tier(Some("free"));
+        let standard = limits.for_tier(Some("standard"));
+        let lifetime = limits.for_tier(Some("lifetime"));
+
+        assert_eq!(none.max_requests, 100);
+        assert_eq!(free.max_requests, 100);
+        assert_eq!(standard.max_requests, 300);
+        assert_eq!(lifetime.max_requests, 600);
+        for config in [free, standard, lifetime] {
+            assert_eq!(config.action, none.action);
+            assert_eq!(config.window_seconds, none.window_seconds);
+        }
+    }
 }
@@ -0,0 +1,76 @@
+//! Admin statistics models
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+
+/// Metric aggregated by the admin time-series endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeseriesMetric {
+    /// New user registrations (count)
+    Signups,
+    /// Successful payments (sum of amounts in cents)
+    Revenue,
+}
+
+impl TimeseriesMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeseriesMetric::Signups => "signups",
+            TimeseriesMetric::Revenue => "revenue",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "signups" => Some(Self::Signups),
+            "revenue" => Some(Self::Revenue),
+            _ => None,
+        }
+    }
+}
+
+/// Bucket width for the admin time-series endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeseriesInterval {
+    Day,
+    Week,
+}
+
+impl TimeseriesInterval {
+    /// Postgres `date_trunc` field name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeseriesInterval::Day => "day",
+            TimeseriesInterval::Week => "week",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "day" => Some(Self::Day),
+            "week" => Some(Self::Week),
+            _ => None,
+        }
+    }
+}
+
+/// A single bucket in a time series
+#[derive(Debug, Clone, Serialize, FromRow, PartialEq, Eq)]
+pub struct TimeseriesPoint {
+    /// Start of the bucket (UTC)
+    pub bucket: DateTime<Utc>,
+    pub value: i64,
+}
+
+/// Response for GET /v1/admin/stats/timeseries
+#[derive(Debug, Serialize)]
+pub struct TimeseriesResponse {
+    pub metric: &'static str,
+    pub interval: &'static str,
+    pub since: DateTime<Utc>,
+    /// Currency of the revenue amounts; absent for signups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    pub points: Vec<TimeseriesPoint>,
+}
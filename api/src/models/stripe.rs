@@ -57,9 +57,23 @@ pub struct StripeSubscriptionResponse {
     pub current_period_start: i64,
     pub current_period_end: i64,
     pub cancel_at_period_end: bool,
+    /// End of the trial (unix seconds), if the subscription has one
+    pub trial_end: Option<i64>,
     pub items: Vec<StripeSubscriptionItemResponse>,
 }
 
+/// Estimated charges for moving a subscription to another price
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StripeProrationPreview {
+    /// Prorated difference charged as soon as the change is made (cents; negative is a credit)
+    pub immediate_amount: i64,
+    /// Amount of the first full billing cycle on the new price (cents)
+    pub next_cycle_amount: i64,
+    pub currency: String,
+    /// When the next cycle is charged (unix seconds)
+    pub next_payment_at: Option<i64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StripeInvoiceResponse {
     pub id: String,
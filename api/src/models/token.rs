@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::clock::{Clock, SystemClock};
+
 /// Refresh token database model
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct RefreshToken {
@@ -19,12 +21,32 @@ pub struct RefreshToken {
     pub created_at: DateTime<Utc>,
     pub last_used_at: Option<DateTime<Utc>>,
     pub revoked_at: Option<DateTime<Utc>>,
+    /// Token issued in place of this one when it was rotated
+    pub replaced_by: Option<Uuid>,
+    /// Whether the session was started with "remember me"
+    pub remember: bool,
+    /// Client-supplied device identifier (X-Device-Id)
+    pub device_id: Option<String>,
 }
 
 impl RefreshToken {
+    /// How long a refresh token, and the cookie carrying it, lasts
+    pub fn lifetime(remember: bool) -> chrono::Duration {
+        if remember {
+            chrono::Duration::days(30)
+        } else {
+            chrono::Duration::days(7)
+        }
+    }
+
     /// Check if the token is expired
     pub fn is_expired(&self) -> bool {
-        self.expires_at < Utc::now()
+        self.is_expired_on(&SystemClock)
+    }
+
+    /// Check if the token is expired as of `clock`
+    pub fn is_expired_on(&self, clock: &dyn Clock) -> bool {
+        self.expires_at < clock.now()
     }
 
     /// Check if the token is revoked
@@ -34,7 +56,25 @@ impl RefreshToken {
 
     /// Check if the token is valid (not expired and not revoked)
     pub fn is_valid(&self) -> bool {
-        !self.is_expired() && !self.is_revoked()
+        self.is_valid_on(&SystemClock)
+    }
+
+    /// Check if the token is valid as of `clock`
+    pub fn is_valid_on(&self, clock: &dyn Clock) -> bool {
+        !self.is_expired_on(clock) && !self.is_revoked()
+    }
+
+    /// Whether the token was revoked by rotation less than `grace` ago
+    pub fn rotated_within(&self, grace: chrono::Duration) -> bool {
+        self.rotated_within_on(grace, &SystemClock)
+    }
+
+    /// [`RefreshToken::rotated_within`] as of `clock`
+    pub fn rotated_within_on(&self, grace: chrono::Duration, clock: &dyn Clock) -> bool {
+        match (self.replaced_by, self.revoked_at) {
+            (Some(_), Some(revoked_at)) => revoked_at > clock.now() - grace,
+            _ => false,
+        }
     }
 }
 
@@ -46,13 +86,20 @@ pub struct CreateRefreshToken {
     pub device_info: Option<String>,
     pub ip_address: Option<IpNetwork>,
     pub expires_at: DateTime<Utc>,
+    pub remember: bool,
+    pub device_id: Option<String>,
 }
 
 /// Session info for display to users
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionInfo {
     pub id: Uuid,
+    /// Raw user agent the session was created with
     pub device_info: Option<String>,
+    /// Browser family parsed from `device_info`, e.g. `Firefox`
+    pub browser: Option<String>,
+    /// Operating system parsed from `device_info`, e.g. `macOS`
+    pub os: Option<String>,
     pub ip_address: Option<String>,
     pub created_at: DateTime<Utc>,
     pub last_used_at: Option<DateTime<Utc>>,
@@ -61,9 +108,16 @@ pub struct SessionInfo {
 
 impl From<RefreshToken> for SessionInfo {
     fn from(token: RefreshToken) -> Self {
+        let (browser, os) = token
+            .device_info
+            .as_deref()
+            .map(parse_user_agent)
+            .unwrap_or_default();
         Self {
             id: token.id,
             device_info: token.device_info,
+            browser: browser.map(String::from),
+            os: os.map(String::from),
             ip_address: token.ip_address.map(|ip| ip.to_string()),
             created_at: token.created_at,
             last_used_at: token.last_used_at,
@@ -72,6 +126,41 @@ impl From<RefreshToken> for SessionInfo {
     }
 }
 
+/// Best-effort (browser, OS) from a user agent string.
+///
+/// Order matters: Edge and Opera UAs also claim Chrome and Safari, Android
+/// UAs claim Linux and iOS UAs claim Mac OS X.
+pub fn parse_user_agent(ua: &str) -> (Option<&'static str>, Option<&'static str>) {
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("FxiOS/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("CriOS/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+    ]
+    .into_iter()
+    .find(|(needle, _)| ua.contains(needle))
+    .map(|(_, name)| name);
+
+    let os = [
+        ("Windows", "Windows"),
+        ("iPhone", "iOS"),
+        ("iPad", "iOS"),
+        ("Android", "Android"),
+        ("CrOS", "ChromeOS"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .into_iter()
+    .find(|(needle, _)| ua.contains(needle))
+    .map(|(_, name)| name);
+
+    (browser, os)
+}
+
 /// Magic link token database model
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct MagicLinkToken {
@@ -88,7 +177,12 @@ pub struct MagicLinkToken {
 impl MagicLinkToken {
     /// Check if the token is expired
     pub fn is_expired(&self) -> bool {
-        self.expires_at < Utc::now()
+        self.is_expired_on(&SystemClock)
+    }
+
+    /// Check if the token is expired as of `clock`
+    pub fn is_expired_on(&self, clock: &dyn Clock) -> bool {
+        self.expires_at < clock.now()
     }
 
     /// Check if the token has been used
@@ -98,7 +192,12 @@ impl MagicLinkToken {
 
     /// Check if the token is valid (not expired and not used)
     pub fn is_valid(&self) -> bool {
-        !self.is_expired() && !self.is_used()
+        self.is_valid_on(&SystemClock)
+    }
+
+    /// Check if the token is valid as of `clock`
+    pub fn is_valid_on(&self, clock: &dyn Clock) -> bool {
+        !self.is_expired_on(clock) && !self.is_used()
     }
 }
 
@@ -127,7 +226,12 @@ pub struct PasswordResetToken {
 impl PasswordResetToken {
     /// Check if the token is expired
     pub fn is_expired(&self) -> bool {
-        self.expires_at < Utc::now()
+        self.is_expired_on(&SystemClock)
+    }
+
+    /// Check if the token is expired as of `clock`
+    pub fn is_expired_on(&self, clock: &dyn Clock) -> bool {
+        self.expires_at < clock.now()
     }
 
     /// Check if the token has been used
@@ -137,7 +241,12 @@ impl PasswordResetToken {
 
     /// Check if the token is valid (not expired and not used)
     pub fn is_valid(&self) -> bool {
-        !self.is_expired() && !self.is_used()
+        self.is_valid_on(&SystemClock)
+    }
+
+    /// Check if the token is valid as of `clock`
+    pub fn is_valid_on(&self, clock: &dyn Clock) -> bool {
+        !self.is_expired_on(clock) && !self.is_used()
     }
 }
 
@@ -168,7 +277,12 @@ pub struct EmailChangeRequest {
 impl EmailChangeRequest {
     /// Check if the request is expired
     pub fn is_expired(&self) -> bool {
-        self.expires_at < Utc::now()
+        self.is_expired_on(&SystemClock)
+    }
+
+    /// Check if the request is expired as of `clock`
+    pub fn is_expired_on(&self, clock: &dyn Clock) -> bool {
+        self.expires_at < clock.now()
     }
 
     /// Check if the request has been confirmed
@@ -183,7 +297,12 @@ impl EmailChangeRequest {
 
     /// Check if the request is valid (not expired, confirmed, or canceled)
     pub fn is_valid(&self) -> bool {
-        !self.is_expired() && !self.is_confirmed() && !self.is_canceled()
+        self.is_valid_on(&SystemClock)
+    }
+
+    /// Check if the request is valid as of `clock`
+    pub fn is_valid_on(&self, clock: &dyn Clock) -> bool {
+        !self.is_expired_on(clock) && !self.is_confirmed() && !self.is_canceled()
     }
 }
 
@@ -213,7 +332,12 @@ pub struct EmailVerificationToken {
 impl EmailVerificationToken {
     /// Check if the token is expired
     pub fn is_expired(&self) -> bool {
-        self.expires_at < Utc::now()
+        self.is_expired_on(&SystemClock)
+    }
+
+    /// Check if the token is expired as of `clock`
+    pub fn is_expired_on(&self, clock: &dyn Clock) -> bool {
+        self.expires_at < clock.now()
     }
 
     /// Check if the token has been used
@@ -223,7 +347,12 @@ impl EmailVerificationToken {
 
     /// Check if the token is valid (not expired and not used)
     pub fn is_valid(&self) -> bool {
-        !self.is_expired() && !self.is_used()
+        self.is_valid_on(&SystemClock)
+    }
+
+    /// Check if the token is valid as of `clock`
+    pub fn is_valid_on(&self, clock: &dyn Clock) -> bool {
+        !self.is_expired_on(clock) && !self.is_used()
     }
 }
 
@@ -254,7 +383,12 @@ pub struct AdminInvite {
 impl AdminInvite {
     /// Check if the invite is expired
     pub fn is_expired(&self) -> bool {
-        self.expires_at < Utc::now()
+        self.is_expired_on(&SystemClock)
+    }
+
+    /// Check if the invite is expired as of `clock`
+    pub fn is_expired_on(&self, clock: &dyn Clock) -> bool {
+        self.expires_at < clock.now()
     }
 
     /// Check if the invite has been accepted
@@ -269,7 +403,12 @@ impl AdminInvite {
 
     /// Check if the invite is valid (not expired, accepted, or revoked)
     pub fn is_valid(&self) -> bool {
-        !self.is_expired() && !self.is_accepted() && !self.is_revoked()
+        self.is_valid_on(&SystemClock)
+    }
+
+    /// Check if the invite is valid as of `clock`
+    pub fn is_valid_on(&self, clock: &dyn Clock) -> bool {
+        !self.is_expired_on(clock) && !self.is_accepted() && !self.is_revoked()
     }
 }
 
@@ -286,6 +425,7 @@ pub struct CreateAdminInvite {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
     use chrono::Duration;
 
     // -- RefreshToken --
@@ -304,6 +444,9 @@ mod tests {
             created_at: Utc::now(),
             last_used_at: None,
             revoked_at,
+            replaced_by: None,
+            remember: true,
+            device_id: None,
         }
     }
 
@@ -329,6 +472,48 @@ mod tests {
         assert!(!token.is_valid());
     }
 
+    #[test]
+    fn refresh_token_rotated_within_grace() {
+        let expires = Utc::now() + Duration::hours(1);
+        let grace = Duration::seconds(10);
+
+        let mut rotated = make_refresh_token(expires, Some(Utc::now() - Duration::seconds(2)));
+        rotated.replaced_by = Some(Uuid::new_v4());
+        assert!(rotated.rotated_within(grace));
+
+        rotated.revoked_at = Some(Utc::now() - Duration::seconds(30));
+        assert!(!rotated.rotated_within(grace));
+
+        // Revoked by logout, not rotation
+        let logged_out = make_refresh_token(expires, Some(Utc::now()));
+        assert!(!logged_out.rotated_within(grace));
+    }
+
+    #[test]
+    fn refresh_token_expires_as_the_clock_advances() {
+        let clock = MockClock::default();
+        let token = make_refresh_token(clock.now() + Duration::hours(1), None);
+        assert!(token.is_valid_on(&clock));
+
+        clock.advance(Duration::minutes(59));
+        assert!(token.is_valid_on(&clock));
+
+        clock.advance(Duration::minutes(2));
+        assert!(token.is_expired_on(&clock));
+        assert!(!token.is_valid_on(&clock));
+    }
+
+    #[test]
+    fn rotation_grace_ends_as_the_clock_advances() {
+        let clock = MockClock::default();
+        let mut rotated = make_refresh_token(clock.now() + Duration::hours(1), Some(clock.now()));
+        rotated.replaced_by = Some(Uuid::new_v4());
+        assert!(rotated.rotated_within_on(Duration::seconds(10), &clock));
+
+        clock.advance(Duration::seconds(11));
+        assert!(!rotated.rotated_within_on(Duration::seconds(10), &clock));
+    }
+
     #[test]
     fn session_info_from_refresh_token() {
         let token = make_refresh_token(Utc::now() + Duration::hours(1), None);
@@ -338,6 +523,36 @@ mod tests {
         assert!(!info.is_current); // default false
     }
 
+    #[test]
+    fn user_agents_parse_to_browser_and_os() {
+        for (ua, expected) in [
+            (
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_4) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Safari/605.1.15",
+                (Some("Safari"), Some("macOS")),
+            ),
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Safari/537.36 Edg/124.0",
+                (Some("Edge"), Some("Windows")),
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Mobile Safari/537.36",
+                (Some("Chrome"), Some("Android")),
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1",
+                (Some("Safari"), Some("iOS")),
+            ),
+            (
+                "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0",
+                (Some("Firefox"), Some("Linux")),
+            ),
+            ("curl/8.5.0", (Some("curl"), None)),
+            ("something else", (None, None)),
+        ] {
+            assert_eq!(parse_user_agent(ua), expected, "{}", ua);
+        }
+    }
+
     // -- MagicLinkToken --
 
     fn make_magic_link(
@@ -375,6 +590,16 @@ mod tests {
         assert!(!token.is_valid());
     }
 
+    #[test]
+    fn magic_link_expires_as_the_clock_advances() {
+        let clock = MockClock::default();
+        let token = make_magic_link(clock.now() + Duration::minutes(15), None);
+        assert!(token.is_valid_on(&clock));
+
+        clock.advance(Duration::minutes(16));
+        assert!(!token.is_valid_on(&clock));
+    }
+
     // -- PasswordResetToken --
 
     fn make_reset_token(
@@ -69,6 +69,41 @@ impl MembershipStatus {
             MembershipStatus::Active | MembershipStatus::GracePeriod
         )
     }
+
+    pub const ALL: [MembershipStatus; 5] = [
+        MembershipStatus::None,
+        MembershipStatus::Active,
+        MembershipStatus::PastDue,
+        MembershipStatus::Canceled,
+        MembershipStatus::GracePeriod,
+    ];
+
+    /// Whether a user may move from this status to `next`.
+    ///
+    /// Cancellation is terminal for a subscription: leaving `Canceled` needs a
+    /// new one (`new_subscription`: created after the membership was
+    /// canceled), so a late event for the old subscription cannot reactivate
+    /// the user. Moving back to `None` is never allowed.
+    /// Staying in the same status is always allowed.
+    pub fn can_transition_to(&self, next: &MembershipStatus, new_subscription: bool) -> bool {
+        use MembershipStatus::*;
+        match (self, next) {
+            (from, to) if from == to => true,
+            (_, None) => false,
+            (_, Canceled) => true,
+            (Canceled, _) => new_subscription,
+            (None | Active | PastDue | GracePeriod, Active | PastDue | GracePeriod) => true,
+        }
+    }
+
+    /// Statuses from which `next` may be reached
+    pub fn allowed_sources(next: &MembershipStatus, new_subscription: bool) -> Vec<&'static str> {
+        Self::ALL
+            .iter()
+            .filter(|from| from.can_transition_to(next, new_subscription))
+            .map(|from| from.as_str())
+            .collect()
+    }
 }
 
 impl From<String> for MembershipStatus {
@@ -118,6 +153,29 @@ impl SubscriptionTier {
             SubscriptionTier::Standard => "standard",
         }
     }
+
+    /// Position in the tier ordering, lowest first:
+    /// standard < early_adopter < free < lifetime
+    fn rank(&self) -> u8 {
+        match self {
+            SubscriptionTier::Standard => 0,
+            SubscriptionTier::EarlyAdopter => 1,
+            SubscriptionTier::Free => 2,
+            SubscriptionTier::Lifetime => 3,
+        }
+    }
+}
+
+impl Ord for SubscriptionTier {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for SubscriptionTier {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl From<&str> for SubscriptionTier {
@@ -169,6 +227,8 @@ pub struct User {
     pub lifetime_member: bool,
     /// Set when an admin manually granted lifetime membership
     pub subscription_override_by: Option<Uuid>,
+    /// Admin-supplied reason recorded with `deleted_at`
+    pub deactivation_reason: Option<String>,
 }
 
 impl User {
@@ -225,6 +285,50 @@ impl User {
     }
 }
 
+#[cfg(test)]
+impl User {
+    /// An active, verified standard subscriber with no password or billing
+    /// history, for tests to override field by field
+    pub(crate) fn test_fixture() -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            email: "test@example.com".to_string(),
+            email_verified: true,
+            password_hash: None,
+            role: "subscriber".to_string(),
+            stripe_customer_id: None,
+            stripe_payment_method_id: None,
+            membership_status: "active".to_string(),
+            price_locked: false,
+            locked_price_id: None,
+            locked_price_amount: None,
+            grace_period_start: None,
+            grace_period_end: None,
+            two_factor_enabled: false,
+            created_at: now,
+            updated_at: now,
+            last_login_at: None,
+            deleted_at: None,
+            subscription_tier: "standard".to_string(),
+            trial_ends_at: None,
+            lifetime_member: false,
+            subscription_override_by: None,
+            deactivation_reason: None,
+        }
+    }
+}
+
+/// What an admin account merge moved from the source user to the target
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountMergeSummary {
+    /// Membership and billing fields were taken from the source
+    pub membership_moved: bool,
+    /// Stripe customer now attached to the target, if it came from the source
+    pub stripe_customer_id: Option<String>,
+    pub payment_method_moved: bool,
+}
+
 /// Data for creating a new user
 #[derive(Debug, Clone)]
 pub struct CreateUser {
@@ -250,6 +354,14 @@ pub struct UserResponse {
     pub subscription_tier: String,
     pub trial_ends_at: Option<DateTime<Utc>>,
     pub lifetime_member: bool,
+    /// Email of the admin impersonating this user; only set on `/users/me`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<String>,
+    /// When the account was deactivated; only visible to admins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deactivated_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deactivation_reason: Option<String>,
 }
 
 impl From<User> for UserResponse {
@@ -269,6 +381,9 @@ impl From<User> for UserResponse {
             subscription_tier: user.subscription_tier,
             trial_ends_at: user.trial_ends_at,
             lifetime_member: user.lifetime_member,
+            impersonated_by: None,
+            deactivated_at: user.deleted_at,
+            deactivation_reason: user.deactivation_reason,
         }
     }
 }
@@ -280,28 +395,8 @@ mod tests {
 
     fn test_user() -> User {
         User {
-            id: Uuid::new_v4(),
-            email: "test@example.com".to_string(),
-            email_verified: true,
             password_hash: Some("hash".to_string()),
-            role: "subscriber".to_string(),
-            stripe_customer_id: None,
-            stripe_payment_method_id: None,
-            membership_status: "active".to_string(),
-            price_locked: false,
-            locked_price_id: None,
-            locked_price_amount: None,
-            grace_period_start: None,
-            grace_period_end: None,
-            two_factor_enabled: false,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-            last_login_at: None,
-            deleted_at: None,
-            subscription_tier: "standard".to_string(),
-            trial_ends_at: None,
-            lifetime_member: false,
-            subscription_override_by: None,
+            ..User::test_fixture()
         }
     }
 
@@ -425,6 +520,17 @@ mod tests {
         assert!(deleted.is_deleted());
     }
 
+    #[test]
+    fn subscription_tier_total_ordering() {
+        use SubscriptionTier::*;
+        let mut tiers = vec![Lifetime, Standard, Free, EarlyAdopter];
+        tiers.sort();
+        assert_eq!(tiers, vec![Standard, EarlyAdopter, Free, Lifetime]);
+        assert!(Standard < EarlyAdopter);
+        assert!(Lifetime >= Lifetime);
+        assert_eq!(Free.max(EarlyAdopter), Free);
+    }
+
     #[test]
     fn user_response_from_user() {
         let user = test_user();
@@ -433,6 +539,18 @@ mod tests {
         assert_eq!(response.id, id);
         assert_eq!(response.email, "test@example.com");
         assert_eq!(response.role, "subscriber");
+        assert_eq!(response.impersonated_by, None);
+    }
+
+    #[test]
+    fn user_response_omits_impersonated_by_unless_set() {
+        let mut response = UserResponse::from(test_user());
+        let json = serde_json::to_value(&response).unwrap();
+        assert!(json.get("impersonated_by").is_none());
+
+        response.impersonated_by = Some("admin@example.com".to_string());
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["impersonated_by"], "admin@example.com");
     }
 
     // -- SubscriptionTier --
@@ -589,4 +707,33 @@ mod tests {
         // 100 standard users exist but 0 lifetime assigned — lifetime still available
         assert_eq!(tier_for_counts(0, 0, 5, 5), SubscriptionTier::Lifetime);
     }
+
+    #[test]
+    fn legal_membership_transitions() {
+        use MembershipStatus::*;
+        assert!(None.can_transition_to(&Active, true));
+        assert!(Active.can_transition_to(&PastDue, false));
+        assert!(PastDue.can_transition_to(&GracePeriod, false));
+        assert!(GracePeriod.can_transition_to(&Active, false));
+        assert!(Active.can_transition_to(&Canceled, false));
+        assert!(Canceled.can_transition_to(&Canceled, false));
+        // Resubscribing after a cancellation
+        assert!(Canceled.can_transition_to(&Active, true));
+    }
+
+    #[test]
+    fn illegal_membership_transitions() {
+        use MembershipStatus::*;
+        // Late events for a canceled subscription
+        assert!(!Canceled.can_transition_to(&Active, false));
+        assert!(!Canceled.can_transition_to(&PastDue, false));
+        assert!(!Canceled.can_transition_to(&GracePeriod, false));
+        assert!(!Active.can_transition_to(&None, true));
+
+        assert_eq!(
+            MembershipStatus::allowed_sources(&Active, false),
+            vec!["none", "active", "past_due", "grace_period"]
+        );
+        assert_eq!(MembershipStatus::allowed_sources(&Active, true).len(), 5);
+    }
 }
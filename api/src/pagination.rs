@@ -0,0 +1,155 @@
+//! Shared `page`/`per_page` handling for list endpoints
+//!
+//! Offset pagination gets slower the deeper it goes, so the reachable offset
+//! is capped (MAX_PAGINATION_OFFSET). Requests past it are rejected rather than
+//! left to scan the table.
+//!
+//! Repositories take [`Page`] and [`PerPage`] rather than bare integers, so an
+//! out-of-range value is rejected where it is built instead of reaching SQL
+//! as a negative offset.
+
+use crate::errors::AppError;
+
+/// Largest `per_page` any list endpoint serves
+pub const MAX_PER_PAGE: i32 = 100;
+
+/// Default deepest offset when MAX_PAGINATION_OFFSET is unset
+pub const DEFAULT_MAX_OFFSET: i64 = 10_000;
+
+/// 1-based page number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page(i32);
+
+impl Page {
+    pub const FIRST: Page = Page(1);
+
+    /// Reject pages below 1
+    pub fn new(page: i32) -> Result<Self, AppError> {
+        if page < 1 {
+            return Err(AppError::validation("page", "Page must be at least 1"));
+        }
+        Ok(Self(page))
+    }
+
+    pub fn get(self) -> i32 {
+        self.0
+    }
+
+    /// Rows to skip to reach this page; never negative
+    pub fn offset(self, per_page: PerPage) -> i64 {
+        i64::from(self.0 - 1) * i64::from(per_page.0)
+    }
+}
+
+/// Rows per page, between 1 and [`MAX_PER_PAGE`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerPage(i32);
+
+impl PerPage {
+    pub const MAX: PerPage = PerPage(MAX_PER_PAGE);
+
+    /// Reject sizes outside 1..=MAX_PER_PAGE
+    pub fn new(per_page: i32) -> Result<Self, AppError> {
+        if !(1..=MAX_PER_PAGE).contains(&per_page) {
+            return Err(AppError::validation(
+                "per_page",
+                format!("per_page must be between 1 and {}", MAX_PER_PAGE),
+            ));
+        }
+        Ok(Self(per_page))
+    }
+
+    pub fn get(self) -> i32 {
+        self.0
+    }
+}
+
+/// Resolve `page` (default 1) and `per_page` (default `default_per_page`,
+/// clamped to 1..=100), rejecting pages whose offset is past `max_offset`
+/// (`PaginationConfig.max_offset`).
+pub fn resolve_page(
+    page: Option<i32>,
+    per_page: Option<i32>,
+    default_per_page: i32,
+    max_offset: i64,
+) -> Result<(Page, PerPage), AppError> {
+    let page = Page(page.unwrap_or(1).max(1));
+    let per_page = PerPage(per_page.unwrap_or(default_per_page).clamp(1, MAX_PER_PAGE));
+
+    if page.offset(per_page) > max_offset {
+        return Err(AppError::validation(
+            "page",
+            format!(
+                "Pages beyond offset {} are not available; narrow the query with filters",
+                max_offset
+            ),
+        ));
+    }
+
+    Ok((page, per_page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(
+        page: Option<i32>,
+        per_page: Option<i32>,
+        default_per_page: i32,
+        max_offset: i64,
+    ) -> Result<(i32, i32), AppError> {
+        resolve_page(page, per_page, default_per_page, max_offset)
+            .map(|(page, per_page)| (page.get(), per_page.get()))
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected_at_construction() {
+        assert!(matches!(
+            Page::new(0),
+            Err(AppError::ValidationError { .. })
+        ));
+        assert!(Page::new(-1).is_err());
+        assert!(PerPage::new(0).is_err());
+        assert!(PerPage::new(-20).is_err());
+        assert!(PerPage::new(MAX_PER_PAGE + 1).is_err());
+
+        assert_eq!(Page::new(1).unwrap(), Page::FIRST);
+        assert_eq!(PerPage::new(MAX_PER_PAGE).unwrap(), PerPage::MAX);
+    }
+
+    #[test]
+    fn offsets_start_at_zero() {
+        let per_page = PerPage::new(20).unwrap();
+        assert_eq!(Page::FIRST.offset(per_page), 0);
+        assert_eq!(Page::new(3).unwrap().offset(per_page), 40);
+        // Computed in i64, so the largest page cannot overflow
+        assert_eq!(
+            Page::new(i32::MAX).unwrap().offset(PerPage::MAX),
+            i64::from(i32::MAX - 1) * 100
+        );
+    }
+
+    #[test]
+    fn defaults_and_clamps_values() {
+        assert_eq!(resolved(None, None, 20, 10_000).unwrap(), (1, 20));
+        assert_eq!(resolved(Some(0), Some(500), 20, 10_000).unwrap(), (1, 100));
+        assert_eq!(resolved(Some(-3), Some(0), 20, 10_000).unwrap(), (1, 1));
+    }
+
+    #[test]
+    fn rejects_pages_past_the_offset_cap() {
+        // Offset 10_000 is the last one allowed
+        assert_eq!(
+            resolved(Some(101), Some(100), 20, 10_000).unwrap(),
+            (101, 100)
+        );
+        assert!(matches!(
+            resolved(Some(102), Some(100), 20, 10_000),
+            Err(AppError::ValidationError { .. })
+        ));
+        assert!(resolved(Some(100_000), Some(20), 20, 10_000).is_err());
+        // No overflow on absurd pages
+        assert!(resolved(Some(i32::MAX), Some(100), 20, 10_000).is_err());
+    }
+}
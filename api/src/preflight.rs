@@ -0,0 +1,302 @@
+//! Startup preflight checks
+//!
+//! Misconfigured keys and unreachable dependencies otherwise only surface on
+//! the first request that needs them. [`run`] checks each critical dependency
+//! once at boot and collects every result into a [`PreflightReport`], which is
+//! logged and then enforced according to PREFLIGHT_STRICT.
+
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::{Config, ImpossibleTravelConfig};
+use crate::services::{EmailService, JwtService, StripeConfig};
+
+/// Shortest accepted HS256 signing secret, in bytes
+const MIN_JWT_SECRET_LEN: usize = 32;
+
+/// Result of a single check
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckOutcome {
+    Passed,
+    /// Not applicable with the current configuration (feature disabled)
+    Skipped(String),
+    Failed(String),
+}
+
+/// A named check and its outcome
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+}
+
+/// Every check run at startup, in order
+#[derive(Debug, Default)]
+pub struct PreflightReport {
+    pub checks: Vec<CheckResult>,
+}
+
+/// Raised in strict mode when any check failed
+#[derive(Debug, thiserror::Error)]
+#[error("Startup preflight failed: {}", .0.join(", "))]
+pub struct PreflightError(pub Vec<String>);
+
+impl PreflightReport {
+    pub fn record(&mut self, name: &'static str, outcome: CheckOutcome) {
+        self.checks.push(CheckResult { name, outcome });
+    }
+
+    /// Checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks
+            .iter()
+            .filter(|c| matches!(c.outcome, CheckOutcome::Failed(_)))
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Log one line per check plus a summary
+    pub fn log(&self) {
+        for check in &self.checks {
+            match &check.outcome {
+                CheckOutcome::Passed => info!(check = check.name, status = "passed", "Preflight"),
+                CheckOutcome::Skipped(reason) => {
+                    info!(check = check.name, status = "skipped", reason = %reason, "Preflight")
+                }
+                CheckOutcome::Failed(reason) => {
+                    error!(check = check.name, status = "failed", reason = %reason, "Preflight")
+                }
+            }
+        }
+        let failed = self.failures().count();
+        info!(
+            total = self.checks.len(),
+            failed, "Preflight checks completed"
+        );
+    }
+
+    /// In strict mode, fail with every failed check's `name: reason`.
+    /// Otherwise failures are only warned about.
+    pub fn enforce(&self, strict: bool) -> Result<(), PreflightError> {
+        let failures: Vec<String> = self
+            .checks
+            .iter()
+            .filter_map(|c| match &c.outcome {
+                CheckOutcome::Failed(reason) => Some(format!("{}: {}", c.name, reason)),
+                _ => None,
+            })
+            .collect();
+        if failures.is_empty() {
+            return Ok(());
+        }
+        if strict {
+            return Err(PreflightError(failures));
+        }
+        warn!(
+            failures = ?failures,
+            "Preflight checks failed; starting anyway because PREFLIGHT_STRICT is off"
+        );
+        Ok(())
+    }
+}
+
+/// Run every startup check and collect the results
+pub async fn run(
+    config: &Config,
+    pool: &PgPool,
+    jwt: &JwtService,
+    jwt_secret: &str,
+    stripe: &StripeConfig,
+    email: &EmailService,
+) -> PreflightReport {
+    let mut report = PreflightReport::default();
+    report.record("database", check_database(pool).await);
+    report.record("jwt", check_jwt(jwt, jwt_secret));
+    report.record("stripe", check_stripe(stripe));
+    report.record("email", check_email(email, config.email.enabled).await);
+    report.record("geoip", check_geo(&config.impossible_travel));
+    report
+}
+
+async fn check_database(pool: &PgPool) -> CheckOutcome {
+    match sqlx::query("SELECT 1").execute(pool).await {
+        Ok(_) => CheckOutcome::Passed,
+        Err(e) => CheckOutcome::Failed(e.to_string()),
+    }
+}
+
+/// The secret is long enough and a token signed with it verifies
+pub fn check_jwt(jwt: &JwtService, secret: &str) -> CheckOutcome {
+    if secret.len() < MIN_JWT_SECRET_LEN {
+        return CheckOutcome::Failed(format!(
+            "JWT_SECRET must be at least {} bytes",
+            MIN_JWT_SECRET_LEN
+        ));
+    }
+    let round_trip = jwt
+        .create_2fa_challenge_token(Uuid::nil(), false)
+        .and_then(|token| jwt.verify_2fa_challenge_token(&token));
+    match round_trip {
+        Ok(_) => CheckOutcome::Passed,
+        Err(e) => CheckOutcome::Failed(format!("signing key unusable: {}", e)),
+    }
+}
+
+/// Both Stripe secrets are well-formed. With no secret key (only the built-in
+/// placeholder) the check is skipped: Stripe can still be configured at
+/// runtime from the admin Stripe settings, which take precedence over env.
+pub fn check_stripe(config: &StripeConfig) -> CheckOutcome {
+    let unset = |key: &str| key.is_empty() || key.ends_with("_placeholder");
+    if unset(&config.secret_key) {
+        return CheckOutcome::Skipped(
+            "Stripe is not configured in env or the admin Stripe settings".to_string(),
+        );
+    }
+    if !config.secret_key.starts_with("sk_") && !config.secret_key.starts_with("rk_") {
+        return CheckOutcome::Failed("STRIPE_SECRET_KEY must start with sk_ or rk_".to_string());
+    }
+    if unset(&config.webhook_secret) {
+        return CheckOutcome::Failed("STRIPE_WEBHOOK_SECRET is not set".to_string());
+    }
+    if !config.webhook_secret.starts_with("whsec_") {
+        return CheckOutcome::Failed("STRIPE_WEBHOOK_SECRET must start with whsec_".to_string());
+    }
+    CheckOutcome::Passed
+}
+
+async fn check_email(email: &EmailService, enabled: bool) -> CheckOutcome {
+    if !enabled {
+        return CheckOutcome::Skipped("EMAIL_ENABLED is off".to_string());
+    }
+    match email.test_connection().await {
+        Ok(true) => CheckOutcome::Passed,
+        // A transport that failed to build leaves the service in dev mode
+        Ok(false) => CheckOutcome::Failed("SMTP transport could not be created".to_string()),
+        Err(e) => CheckOutcome::Failed(e.to_string()),
+    }
+}
+
+/// The GeoIP lookup URL is a valid http(s) template with an `{ip}` placeholder
+pub fn check_geo(config: &ImpossibleTravelConfig) -> CheckOutcome {
+    if !config.enabled {
+        return CheckOutcome::Skipped("IMPOSSIBLE_TRAVEL_ENABLED is off".to_string());
+    }
+    let Some(geo_url) = config.geo_url.as_deref() else {
+        return CheckOutcome::Failed("IMPOSSIBLE_TRAVEL_GEO_URL is not set".to_string());
+    };
+    if !geo_url.contains("{ip}") {
+        return CheckOutcome::Failed("IMPOSSIBLE_TRAVEL_GEO_URL must contain {ip}".to_string());
+    }
+    match url::Url::parse(&geo_url.replace("{ip}", "192.0.2.1")) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => CheckOutcome::Passed,
+        _ => CheckOutcome::Failed("IMPOSSIBLE_TRAVEL_GEO_URL is not an http(s) URL".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::JwtConfig;
+
+    const SECRET: &str = "test-secret-key-at-least-32-bytes-long";
+
+    fn stripe(secret_key: &str, webhook_secret: &str) -> StripeConfig {
+        StripeConfig {
+            secret_key: secret_key.to_string(),
+            webhook_secret: webhook_secret.to_string(),
+            ..StripeConfig::for_tests("http://localhost")
+        }
+    }
+
+    fn geo(enabled: bool, geo_url: &str) -> ImpossibleTravelConfig {
+        ImpossibleTravelConfig {
+            enabled,
+            geo_url: Some(geo_url.to_string()).filter(|url| !url.is_empty()),
+            max_speed_kmh: 1000.0,
+            min_distance_km: 500.0,
+            require_step_up: false,
+            notify_user: false,
+        }
+    }
+
+    #[test]
+    fn individual_checks() {
+        let jwt = JwtService::new(JwtConfig::from_secret(SECRET, "test"));
+        assert_eq!(check_jwt(&jwt, SECRET), CheckOutcome::Passed);
+        assert!(matches!(check_jwt(&jwt, "short"), CheckOutcome::Failed(_)));
+
+        assert_eq!(
+            check_stripe(&stripe("sk_live_abc", "whsec_abc")),
+            CheckOutcome::Passed
+        );
+        assert!(matches!(
+            check_stripe(&stripe("sk_test_placeholder", "whsec_placeholder")),
+            CheckOutcome::Skipped(_)
+        ));
+        assert!(matches!(
+            check_stripe(&stripe("", "")),
+            CheckOutcome::Skipped(_)
+        ));
+        assert!(matches!(
+            check_stripe(&stripe("pk_live_abc", "whsec_abc")),
+            CheckOutcome::Failed(_)
+        ));
+        assert!(matches!(
+            check_stripe(&stripe("sk_live_abc", "whsec_placeholder")),
+            CheckOutcome::Failed(_)
+        ));
+
+        assert!(matches!(
+            check_geo(&geo(false, "")),
+            CheckOutcome::Skipped(_)
+        ));
+        assert!(matches!(check_geo(&geo(true, "")), CheckOutcome::Failed(_)));
+        assert_eq!(
+            check_geo(&geo(true, "https://ipapi.co/{ip}/json/")),
+            CheckOutcome::Passed
+        );
+        assert!(matches!(
+            check_geo(&geo(true, "https://ipapi.co/json/")),
+            CheckOutcome::Failed(_)
+        ));
+        assert!(matches!(
+            check_geo(&geo(true, "ftp://geo/{ip}")),
+            CheckOutcome::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn report_aggregates_every_failure() {
+        let jwt = JwtService::new(JwtConfig::from_secret(SECRET, "test"));
+        let mut report = PreflightReport::default();
+        report.record("jwt", check_jwt(&jwt, "short"));
+        report.record("stripe", check_stripe(&stripe("pk_live_abc", "whsec_abc")));
+        report.record("email", CheckOutcome::Skipped("off".to_string()));
+        report.record("geoip", check_geo(&geo(true, "not a url")));
+        report.record("database", CheckOutcome::Passed);
+
+        assert!(!report.is_ok());
+        let failed: Vec<_> = report.failures().map(|c| c.name).collect();
+        assert_eq!(failed, vec!["jwt", "stripe", "geoip"]);
+
+        let err = report.enforce(true).unwrap_err();
+        assert_eq!(err.0.len(), 3);
+        assert!(err.0[1].starts_with("stripe: STRIPE_SECRET_KEY"));
+        assert!(err.to_string().contains("geoip: IMPOSSIBLE_TRAVEL_GEO_URL"));
+
+        // Non-strict mode only warns
+        assert!(report.enforce(false).is_ok());
+    }
+
+    #[test]
+    fn skipped_checks_do_not_fail_the_report() {
+        let mut report = PreflightReport::default();
+        report.record("database", CheckOutcome::Passed);
+        report.record("email", CheckOutcome::Skipped("off".to_string()));
+        assert!(report.is_ok());
+        assert!(report.enforce(true).is_ok());
+    }
+}
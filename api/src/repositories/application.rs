@@ -5,6 +5,7 @@ use uuid::Uuid;
 
 use crate::errors::AppError;
 use crate::models::{Application, CreateApplication, UpdateApplication};
+use crate::pagination::{Page, PerPage};
 
 pub struct ApplicationRepository;
 
@@ -14,7 +15,7 @@ impl ApplicationRepository {
         let apps = sqlx::query_as::<_, Application>(
             r#"
             SELECT * FROM applications
-            WHERE is_active = TRUE
+            WHERE is_active = TRUE AND retired_at IS NULL
             ORDER BY sort_order ASC, display_name ASC
             "#,
         )
@@ -28,7 +29,7 @@ impl ApplicationRepository {
     pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Application>, AppError> {
         let app = sqlx::query_as::<_, Application>(
             r#"
-            SELECT * FROM applications WHERE id = $1
+            SELECT * FROM applications WHERE id = $1 AND retired_at IS NULL
             "#,
         )
         .bind(id)
@@ -42,7 +43,7 @@ impl ApplicationRepository {
     pub async fn find_by_slug(pool: &PgPool, slug: &str) -> Result<Option<Application>, AppError> {
         let app = sqlx::query_as::<_, Application>(
             r#"
-            SELECT * FROM applications WHERE slug = $1
+            SELECT * FROM applications WHERE slug = $1 AND retired_at IS NULL
             "#,
         )
         .bind(slug)
@@ -59,7 +60,8 @@ impl ApplicationRepository {
     ) -> Result<Option<Application>, AppError> {
         let app = sqlx::query_as::<_, Application>(
             r#"
-            SELECT * FROM applications WHERE slug = $1 AND is_active = TRUE
+            SELECT * FROM applications
+            WHERE slug = $1 AND is_active = TRUE AND retired_at IS NULL
             "#,
         )
         .bind(slug)
@@ -158,7 +160,7 @@ impl ApplicationRepository {
                 oci_image_name      = COALESCE($17, oci_image_name),
                 pinned_image_tag    = COALESCE($18, pinned_image_tag),
                 updated_at          = NOW()
-            WHERE id = $19
+            WHERE id = $19 AND retired_at IS NULL
             RETURNING *
             "#,
         )
@@ -224,18 +226,33 @@ impl ApplicationRepository {
         Ok(app)
     }
 
-    /// Delete an application by ID (admin)
-    pub async fn delete(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
-        let result = sqlx::query("DELETE FROM applications WHERE id = $1")
-            .bind(id)
-            .execute(pool)
-            .await?;
+    /// Retire (soft-delete) an application by ID (admin). Its slug is freed
+    /// for a new application.
+    ///
+    /// Refuses while the application is still active, i.e. still offered to
+    /// members; it has to be deactivated first.
+    pub async fn retire(pool: &PgPool, id: Uuid) -> Result<Application, AppError> {
+        let retired = sqlx::query_as::<_, Application>(
+            r#"
+            UPDATE applications
+            SET retired_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND retired_at IS NULL AND is_active = FALSE
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
 
-        if result.rows_affected() == 0 {
-            return Err(AppError::not_found("Application"));
+        match retired {
+            Some(app) => Ok(app),
+            None => match Self::find_by_id(pool, id).await? {
+                Some(_) => Err(AppError::conflict(
+                    "Application is still active; deactivate it before deleting",
+                )),
+                None => Err(AppError::not_found("Application")),
+            },
         }
-
-        Ok(())
     }
 
     /// Swap sort_order between two applications (admin)
@@ -265,7 +282,9 @@ impl ApplicationRepository {
     pub async fn list_all(pool: &PgPool) -> Result<Vec<Application>, AppError> {
         let apps = sqlx::query_as::<_, Application>(
             r#"
-            SELECT * FROM applications ORDER BY sort_order ASC, display_name ASC
+            SELECT * FROM applications
+            WHERE retired_at IS NULL
+            ORDER BY sort_order ASC, display_name ASC
             "#,
         )
         .fetch_all(pool)
@@ -273,6 +292,36 @@ impl ApplicationRepository {
 
         Ok(apps)
     }
+
+    /// List all applications with pagination (admin)
+    pub async fn list_all_paginated(
+        pool: &PgPool,
+        page: Page,
+        per_page: PerPage,
+    ) -> Result<(Vec<Application>, i64), AppError> {
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
+
+        let apps = sqlx::query_as::<_, Application>(
+            r#"
+            SELECT * FROM applications
+            WHERE retired_at IS NULL
+            ORDER BY sort_order ASC, display_name ASC
+            LIMIT $1 OFFSET $2
+            "#,
+        )
+        .bind(per_page)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        let total: (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM applications WHERE retired_at IS NULL")
+                .fetch_one(pool)
+                .await?;
+
+        Ok((apps, total.0))
+    }
 }
 
 #[cfg(test)]
@@ -349,4 +398,116 @@ mod tests {
             .await
             .unwrap();
     }
+
+    async fn insert_app(pool: &PgPool, is_active: bool) -> Uuid {
+        let slug = format!("test-retire-{}", uuid::Uuid::new_v4());
+        let row: (Uuid,) = sqlx::query_as(
+            r#"
+            INSERT INTO applications (name, slug, display_name, container_name, is_active)
+            VALUES ($1, $1, $1, $1, $2)
+            RETURNING id
+            "#,
+        )
+        .bind(&slug)
+        .bind(is_active)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        row.0
+    }
+
+    #[actix_rt::test]
+    async fn retire_refuses_active_application() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let id = insert_app(&pool, true).await;
+
+        let result = ApplicationRepository::retire(&pool, id).await;
+        let still_listed = ApplicationRepository::find_by_id(&pool, id)
+            .await
+            .unwrap()
+            .is_some();
+
+        sqlx::query("DELETE FROM applications WHERE id = $1")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(result, Err(AppError::Conflict { .. })));
+        assert!(still_listed);
+    }
+
+    #[actix_rt::test]
+    async fn retire_hides_inactive_application_but_keeps_row() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let id = insert_app(&pool, false).await;
+
+        let retired = ApplicationRepository::retire(&pool, id).await.unwrap();
+        let found = ApplicationRepository::find_by_id(&pool, id).await.unwrap();
+        let listed = ApplicationRepository::list_all(&pool)
+            .await
+            .unwrap()
+            .iter()
+            .any(|a| a.id == id);
+        let again = ApplicationRepository::retire(&pool, id).await;
+        let row: (Option<chrono::DateTime<chrono::Utc>>,) =
+            sqlx::query_as("SELECT retired_at FROM applications WHERE id = $1")
+                .bind(id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+        sqlx::query("DELETE FROM applications WHERE id = $1")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(retired.id, id);
+        assert!(found.is_none());
+        assert!(!listed);
+        assert!(matches!(again, Err(AppError::NotFound { .. })));
+        assert!(row.0.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn retired_slug_can_be_reused() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let id = insert_app(&pool, false).await;
+        let (slug,): (String,) = sqlx::query_as("SELECT slug FROM applications WHERE id = $1")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        ApplicationRepository::retire(&pool, id).await.unwrap();
+
+        let reused: Result<(Uuid,), _> = sqlx::query_as(
+            r#"
+            INSERT INTO applications (name, slug, display_name, container_name)
+            VALUES ($1, $1, $1, $1)
+            RETURNING id
+            "#,
+        )
+        .bind(&slug)
+        .fetch_one(&pool)
+        .await;
+        let found = ApplicationRepository::find_by_slug(&pool, &slug)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM applications WHERE slug = $1")
+            .bind(&slug)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (new_id,) = reused.expect("slug of a retired application is free");
+        assert_eq!(found.map(|a| a.id), Some(new_id));
+    }
 }
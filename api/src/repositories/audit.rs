@@ -1,19 +1,75 @@
 //! Audit log repository
 
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use serde_json::Value as JsonValue;
+use sqlx::{PgPool, Postgres};
 use uuid::Uuid;
 
+use crate::config::AuditConfig;
 use crate::errors::AppError;
 use crate::models::{AuditLog, CreateAuditLog};
+use crate::pagination::{Page, PerPage};
+use crate::repositories::timed;
+
+/// Maximum serialized size of each JSON column on an audit entry
+pub const MAX_AUDIT_JSON_BYTES: usize = 16 * 1024;
+
+/// Bytes of the original JSON kept in the truncation marker
+const TRUNCATED_PREVIEW_BYTES: usize = 1024;
 
 pub struct AuditLogRepository;
 
 impl AuditLogRepository {
     /// Create a new audit log entry
-    pub async fn create(pool: &PgPool, data: CreateAuditLog) -> Result<AuditLog, AppError> {
-        let log = sqlx::query_as::<_, AuditLog>(
-            r#"
+    ///
+    /// Returns `None` when `audit.policy` suppresses the entry. Actor
+    /// email/IP are masked when `audit.mask_pii` is set (see
+    /// [`CreateAuditLog::masked`]).
+    /// `old_values`, `new_values` and `metadata` larger than
+    /// [`MAX_AUDIT_JSON_BYTES`] are replaced with a truncation marker.
+    pub async fn create(
+        pool: &PgPool,
+        audit: &AuditConfig,
+        data: CreateAuditLog,
+    ) -> Result<Option<AuditLog>, AppError> {
+        Self::create_with(pool, audit, data).await
+    }
+
+    /// [`Self::create`] on any executor, so an entry can be written in the
+    /// same transaction as the change it records
+    #[tracing::instrument(name = "create", level = "debug", skip_all, fields(action = data.action.as_str(), duration_ms = tracing::field::Empty))]
+    pub async fn create_with<'e, E>(
+        executor: E,
+        audit: &AuditConfig,
+        data: CreateAuditLog,
+    ) -> Result<Option<AuditLog>, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        if !audit
+            .policy
+            .should_record(&data.action, data.severity, rand::random::<f64>())
+        {
+            return Ok(None);
+        }
+
+        let data = if audit.mask_pii {
+            data.masked(audit.mask_key.as_bytes())
+        } else {
+            data
+        };
+        let action = data.action.as_str();
+        let data = CreateAuditLog {
+            old_values: cap_json_size(action, "old_values", data.old_values),
+            new_values: cap_json_size(action, "new_values", data.new_values),
+            metadata: cap_json_size(action, "metadata", data.metadata),
+            ..data
+        };
+
+        let log = timed(
+            "create",
+            sqlx::query_as::<_, AuditLog>(
+                r#"
             INSERT INTO audit_logs (
                 actor_id, actor_email, actor_role, actor_ip_address, action,
                 resource_type, resource_id, old_values, new_values, metadata,
@@ -22,37 +78,39 @@ impl AuditLogRepository {
             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
             RETURNING *
             "#,
+            )
+            .bind(data.actor_id)
+            .bind(&data.actor_email)
+            .bind(&data.actor_role)
+            .bind(data.actor_ip_address)
+            .bind(data.action.as_str())
+            .bind(&data.resource_type)
+            .bind(data.resource_id)
+            .bind(&data.old_values)
+            .bind(&data.new_values)
+            .bind(&data.metadata)
+            .bind(data.action.is_admin_action())
+            .bind(data.severity.as_str())
+            .fetch_one(executor),
         )
-        .bind(data.actor_id)
-        .bind(&data.actor_email)
-        .bind(&data.actor_role)
-        .bind(data.actor_ip_address)
-        .bind(data.action.as_str())
-        .bind(&data.resource_type)
-        .bind(data.resource_id)
-        .bind(&data.old_values)
-        .bind(&data.new_values)
-        .bind(&data.metadata)
-        .bind(data.action.is_admin_action())
-        .bind(data.severity.as_str())
-        .fetch_one(pool)
         .await?;
 
-        Ok(log)
+        Ok(Some(log))
     }
 
     /// List audit logs with pagination and filters
     pub async fn list_paginated(
         pool: &PgPool,
-        page: i32,
-        per_page: i32,
+        page: Page,
+        per_page: PerPage,
         actor_id: Option<Uuid>,
         action: Option<&str>,
         admin_only: bool,
         start_date: Option<DateTime<Utc>>,
         end_date: Option<DateTime<Utc>>,
     ) -> Result<(Vec<AuditLog>, i64), AppError> {
-        let offset = (page - 1) * per_page;
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
 
         // Build query dynamically based on filters
         let mut conditions = Vec::new();
@@ -131,10 +189,11 @@ impl AuditLogRepository {
     /// List admin actions
     pub async fn list_admin_actions(
         pool: &PgPool,
-        page: i32,
-        per_page: i32,
+        page: Page,
+        per_page: PerPage,
     ) -> Result<(Vec<AuditLog>, i64), AppError> {
-        let offset = (page - 1) * per_page;
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
 
         let logs = sqlx::query_as::<_, AuditLog>(
             r#"
@@ -165,7 +224,7 @@ impl AuditLogRepository {
         let logs = sqlx::query_as::<_, AuditLog>(
             r#"
             SELECT * FROM audit_logs
-            WHERE action IN ('user_login', 'user_logout', 'password_changed', 'password_reset_completed', 'admin_user_impersonated')
+            WHERE action IN ('user_login', 'user_logout', 'password_changed', 'password_reset_completed', 'admin_user_impersonated', 'user_login_impossible_travel')
             ORDER BY created_at DESC
             LIMIT $1
             "#,
@@ -177,3 +236,64 @@ impl AuditLogRepository {
         Ok(logs)
     }
 }
+
+/// Replace `value` with a small marker object if it serializes to more than
+/// [`MAX_AUDIT_JSON_BYTES`]. The marker keeps the original size and a prefix
+/// of the serialized JSON so the entry is still useful when investigating.
+fn cap_json_size(action: &str, field: &str, value: Option<JsonValue>) -> Option<JsonValue> {
+    let value = value?;
+    let serialized = value.to_string();
+    if serialized.len() <= MAX_AUDIT_JSON_BYTES {
+        return Some(value);
+    }
+
+    tracing::warn!(
+        action = %action,
+        field = %field,
+        size = serialized.len(),
+        limit = MAX_AUDIT_JSON_BYTES,
+        "Truncating oversized audit log JSON"
+    );
+
+    let mut end = TRUNCATED_PREVIEW_BYTES;
+    while !serialized.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    Some(serde_json::json!({
+        "_truncated": true,
+        "original_size": serialized.len(),
+        "preview": &serialized[..end],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn small_json_is_untouched() {
+        let value = json!({ "amount": 1000, "currency": "usd" });
+        assert_eq!(
+            cap_json_size("payment_succeeded", "metadata", Some(value.clone())),
+            Some(value)
+        );
+        assert_eq!(cap_json_size("payment_succeeded", "metadata", None), None);
+    }
+
+    #[test]
+    fn oversized_json_is_replaced_with_marker() {
+        let value = json!({ "payload": "é".repeat(MAX_AUDIT_JSON_BYTES) });
+        let original_size = value.to_string().len();
+
+        let capped = cap_json_size("webhook_received", "metadata", Some(value)).unwrap();
+
+        assert_eq!(capped["_truncated"], true);
+        assert_eq!(capped["original_size"], original_size);
+        let preview = capped["preview"].as_str().unwrap();
+        assert!(preview.len() <= TRUNCATED_PREVIEW_BYTES);
+        assert!(preview.starts_with(r#"{"payload":"é"#));
+        assert!(capped.to_string().len() <= MAX_AUDIT_JSON_BYTES);
+    }
+}
@@ -0,0 +1,152 @@
+//! User data export repository
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::models::DataExport;
+
+/// A job still queued this long after it was created was lost with the
+/// process building it
+pub const STALE_EXPORT_MINUTES: i64 = 15;
+
+pub struct DataExportRepository;
+
+impl DataExportRepository {
+    /// Queue a new export for `user_id`, or return the one it already has
+    /// queued. The flag is true when a new job was created.
+    pub async fn create(pool: &PgPool, user_id: Uuid) -> Result<(DataExport, bool), AppError> {
+        loop {
+            let created = sqlx::query_as::<_, DataExport>(
+                r#"
+                INSERT INTO data_exports (user_id)
+                VALUES ($1)
+                ON CONFLICT (user_id) WHERE status = 'queued' DO NOTHING
+                RETURNING id, user_id, status, error, created_at, completed_at, expires_at
+                "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+            if let Some(export) = created {
+                return Ok((export, true));
+            }
+
+            let pending = sqlx::query_as::<_, DataExport>(
+                r#"
+                SELECT id, user_id, status, error, created_at, completed_at, expires_at
+                FROM data_exports
+                WHERE user_id = $1 AND status = 'queued'
+                "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+            if let Some(export) = pending {
+                return Ok((export, false));
+            }
+            // The queued job finished in between; try again
+        }
+    }
+
+    /// Find an export job by ID
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<DataExport>, AppError> {
+        let export = sqlx::query_as::<_, DataExport>(
+            r#"
+            SELECT id, user_id, status, error, created_at, completed_at, expires_at
+            FROM data_exports
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(export)
+    }
+
+    /// Store the finished bundle and mark the job ready until `expires_at`
+    pub async fn mark_ready(
+        pool: &PgPool,
+        id: Uuid,
+        bundle: &serde_json::Value,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE data_exports
+            SET status = 'ready', bundle = $2, completed_at = NOW(), expires_at = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(bundle)
+        .bind(expires_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark the job failed with `error`
+    pub async fn mark_failed(pool: &PgPool, id: Uuid, error: &str) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE data_exports
+            SET status = 'failed', error = $2, completed_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The stored bundle of a ready export
+    pub async fn find_bundle(
+        pool: &PgPool,
+        id: Uuid,
+    ) -> Result<Option<serde_json::Value>, AppError> {
+        let bundle: Option<Option<serde_json::Value>> = sqlx::query_scalar(
+            "SELECT bundle FROM data_exports WHERE id = $1 AND status = 'ready'",
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(bundle.flatten())
+    }
+
+    /// Fail jobs queued more than [`STALE_EXPORT_MINUTES`] ago, so a user
+    /// whose build died with a restart can request a new export
+    pub async fn fail_stale(pool: &PgPool) -> Result<u64, AppError> {
+        let cutoff = Utc::now() - Duration::minutes(STALE_EXPORT_MINUTES);
+        let result = sqlx::query(
+            r#"
+            UPDATE data_exports
+            SET status = 'failed', error = 'Export was interrupted', completed_at = NOW()
+            WHERE status = 'queued' AND created_at < $1
+            "#,
+        )
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Drop bundles past their retention window, keeping the job rows
+    pub async fn purge_expired_bundles(pool: &PgPool) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            "UPDATE data_exports SET bundle = NULL WHERE bundle IS NOT NULL AND expires_at < NOW()",
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -8,6 +8,7 @@ use crate::models::{
     ArchivedFeedbackItem, CreateFeedback, Feedback, FeedbackAttachmentMeta, FeedbackStatus,
     RespondToFeedback,
 };
+use crate::pagination::{Page, PerPage};
 
 pub struct FeedbackRepository;
 
@@ -44,11 +45,12 @@ impl FeedbackRepository {
 
     pub async fn list_paginated(
         pool: &PgPool,
-        page: i32,
-        per_page: i32,
+        page: Page,
+        per_page: PerPage,
         status: Option<&str>,
     ) -> Result<(Vec<Feedback>, i64), AppError> {
-        let offset = (page - 1) * per_page;
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
 
         let mut query = QueryBuilder::new("SELECT * FROM feedback");
         let mut count_query = QueryBuilder::new("SELECT COUNT(*)::BIGINT FROM feedback");
@@ -99,10 +101,11 @@ impl FeedbackRepository {
 
     pub async fn list_archived(
         pool: &PgPool,
-        page: i32,
-        per_page: i32,
+        page: Page,
+        per_page: PerPage,
     ) -> Result<(Vec<ArchivedFeedbackItem>, i64), AppError> {
-        let offset = (page - 1) * per_page;
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
 
         let items = sqlx::query_as::<_, ArchivedFeedbackItem>(
             r#"
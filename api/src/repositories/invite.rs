@@ -5,6 +5,7 @@ use uuid::Uuid;
 
 use crate::errors::AppError;
 use crate::models::token::{AdminInvite, CreateAdminInvite};
+use crate::pagination::{Page, PerPage};
 
 pub struct InviteRepository;
 
@@ -122,10 +123,11 @@ impl InviteRepository {
     /// List all invites with pagination
     pub async fn list_all(
         pool: &PgPool,
-        page: i32,
-        per_page: i32,
+        page: Page,
+        per_page: PerPage,
     ) -> Result<(Vec<AdminInvite>, i64), AppError> {
-        let offset = (page - 1) * per_page;
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
 
         let invites = sqlx::query_as::<_, AdminInvite>(
             r#"
@@ -135,7 +137,7 @@ impl InviteRepository {
             "#,
         )
         .bind(per_page as i64)
-        .bind(offset as i64)
+        .bind(offset)
         .fetch_all(pool)
         .await?;
 
@@ -0,0 +1,176 @@
+//! Per-user IP activity repository
+
+use ipnetwork::IpNetwork;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::models::UserIpActivity;
+
+/// Distinct addresses kept per user; the least recently seen are dropped
+pub const MAX_TRACKED_IPS_PER_USER: i64 = 50;
+
+pub struct UserIpActivityRepository;
+
+impl UserIpActivityRepository {
+    /// Record a login or refresh from `ip`, then drop the user's least
+    /// recently seen addresses beyond `max_ips`
+    pub async fn record(
+        pool: &PgPool,
+        user_id: Uuid,
+        ip: IpNetwork,
+        max_ips: i64,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_ip_activity (user_id, ip_address)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, ip_address) DO UPDATE SET
+                last_seen = NOW(),
+                count = user_ip_activity.count + 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(ip)
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM user_ip_activity
+            WHERE user_id = $1
+              AND ip_address NOT IN (
+                  SELECT ip_address FROM user_ip_activity
+                  WHERE user_id = $1
+                  ORDER BY last_seen DESC
+                  LIMIT $2
+              )
+            "#,
+        )
+        .bind(user_id)
+        .bind(max_ips)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// A user's addresses, most recently seen first
+    pub async fn list_for_user(
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> Result<Vec<UserIpActivity>, AppError> {
+        let activity = sqlx::query_as::<_, UserIpActivity>(
+            r#"
+            SELECT user_id, ip_address, first_seen, last_seen, count
+            FROM user_ip_activity
+            WHERE user_id = $1
+            ORDER BY last_seen DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(activity)
+    }
+
+    /// The address a user was most recently seen from
+    pub async fn find_latest(
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> Result<Option<UserIpActivity>, AppError> {
+        let activity = sqlx::query_as::<_, UserIpActivity>(
+            r#"
+            SELECT user_id, ip_address, first_seen, last_seen, count
+            FROM user_ip_activity
+            WHERE user_id = $1
+            ORDER BY last_seen DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(activity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    async fn create_user(pool: &PgPool) -> Uuid {
+        sqlx::query_scalar("INSERT INTO users (email, password_hash) VALUES ($1, 'x') RETURNING id")
+            .bind(format!("{}@example.com", Uuid::new_v4()))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn repeat_ip_updates_the_row_and_new_ip_adds_one() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let user_id = create_user(&pool).await;
+        let home: IpNetwork = "203.0.113.10".parse().unwrap();
+        let travel: IpNetwork = "198.51.100.20".parse().unwrap();
+
+        UserIpActivityRepository::record(&pool, user_id, home, MAX_TRACKED_IPS_PER_USER)
+            .await
+            .unwrap();
+        let first = UserIpActivityRepository::list_for_user(&pool, user_id)
+            .await
+            .unwrap();
+        UserIpActivityRepository::record(&pool, user_id, home, MAX_TRACKED_IPS_PER_USER)
+            .await
+            .unwrap();
+
+        let activity = UserIpActivityRepository::list_for_user(&pool, user_id)
+            .await
+            .unwrap();
+        assert_eq!(activity.len(), 1);
+        assert_eq!(activity[0].count, 2);
+        assert_eq!(activity[0].first_seen, first[0].first_seen);
+        assert!(activity[0].last_seen > first[0].last_seen);
+
+        UserIpActivityRepository::record(&pool, user_id, travel, MAX_TRACKED_IPS_PER_USER)
+            .await
+            .unwrap();
+        let activity = UserIpActivityRepository::list_for_user(&pool, user_id)
+            .await
+            .unwrap();
+        assert_eq!(activity.len(), 2);
+        assert_eq!(activity[0].ip_address, travel);
+        assert_eq!(activity[0].count, 1);
+    }
+
+    #[actix_rt::test]
+    async fn least_recently_seen_ips_are_dropped_past_the_cap() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let user_id = create_user(&pool).await;
+        for last_octet in 1..=3 {
+            let ip: IpNetwork = format!("192.0.2.{last_octet}").parse().unwrap();
+            UserIpActivityRepository::record(&pool, user_id, ip, 2)
+                .await
+                .unwrap();
+        }
+
+        let kept: Vec<String> = UserIpActivityRepository::list_for_user(&pool, user_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|a| a.ip_address.ip().to_string())
+            .collect();
+        assert_eq!(kept, vec!["192.0.2.3", "192.0.2.2"]);
+    }
+}
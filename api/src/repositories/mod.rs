@@ -1,36 +1,173 @@
 //! Database repository layer
 //!
 //! This module contains all database access logic organized by domain.
+//!
+//! Hot repository methods carry a DEBUG-level `#[tracing::instrument]` span
+//! with an empty `duration_ms` field, and await their query through
+//! [`timed`], which fills it in. Calls slower than [`SLOW_QUERY_THRESHOLD`]
+//! are also logged at WARN so they surface without DEBUG tracing enabled;
+//! that warning names the method itself, since the DEBUG span is disabled
+//! at INFO.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
 
 pub mod application;
 pub mod audit;
+pub mod data_export;
 pub mod download_cache;
 pub mod download_daily_count;
 pub mod feedback;
 pub mod invite;
+pub mod ip_activity;
 pub mod notification;
 pub mod oci_blob_cache;
 pub mod oci_pull_daily_counts;
+pub mod payment_method;
 pub mod rate_limit;
+pub mod stats;
 pub mod stripe;
 pub mod tier;
 pub mod token;
 pub mod totp;
 pub mod user;
+pub mod webhook_event;
 
 // Re-export repositories
 pub use application::ApplicationRepository;
 pub use audit::AuditLogRepository;
+pub use data_export::DataExportRepository;
 pub use download_cache::DownloadCacheRepository;
 pub use download_daily_count::DownloadDailyCountRepository;
 pub use feedback::FeedbackRepository;
 pub use invite::InviteRepository;
+pub use ip_activity::UserIpActivityRepository;
 pub use notification::NotificationRepository;
 pub use oci_blob_cache::OciBlobCacheRepository;
 pub use oci_pull_daily_counts::OciPullDailyCountRepository;
+pub use payment_method::PaymentMethodRepository;
 pub use rate_limit::RateLimitRepository;
+pub use stats::StatsRepository;
 pub use stripe::StripeConfigRepository;
 pub use tier::TierConfigRepository;
 pub use token::TokenRepository;
 pub use totp::TotpRepository;
 pub use user::UserRepository;
+pub use webhook_event::WebhookEventRepository;
+
+/// Repository calls slower than this are logged at WARN
+pub const SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// Await a repository query, recording its latency as `duration_ms` on the
+/// current (instrumented) span and warning, as `method`, when it is slow.
+pub(crate) async fn timed<F: Future>(method: &'static str, query: F) -> F::Output {
+    let started = Instant::now();
+    let output = query.await;
+    let elapsed = started.elapsed();
+
+    let span = tracing::Span::current();
+    span.record("duration_ms", elapsed.as_millis() as u64);
+    if elapsed >= SLOW_QUERY_THRESHOLD {
+        tracing::warn!(
+            method,
+            duration_ms = elapsed.as_millis() as u64,
+            "Slow repository call"
+        );
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::PgPool;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    /// Test layer capturing each span's name and recorded `duration_ms`
+    #[derive(Clone, Default)]
+    struct SpanCapture(Arc<Mutex<Vec<(u64, &'static str, Option<u64>)>>>);
+
+    impl SpanCapture {
+        fn duration_of(&self, name: &str) -> Option<u64> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(_, n, _)| *n == name)
+                .and_then(|(_, _, ms)| *ms)
+        }
+    }
+
+    struct DurationVisitor(Option<u64>);
+
+    impl Visit for DurationVisitor {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "duration_ms" {
+                self.0 = Some(value);
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for SpanCapture {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((id.into_u64(), attrs.metadata().name(), None));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            let mut visitor = DurationVisitor(None);
+            values.record(&mut visitor);
+            if let Some(ms) = visitor.0 {
+                for span in self.0.lock().unwrap().iter_mut() {
+                    if span.0 == id.into_u64() {
+                        span.2 = Some(ms);
+                    }
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "debug", fields(duration_ms = tracing::field::Empty))]
+    async fn slow_lookup() -> u32 {
+        timed("slow_lookup", async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            7
+        })
+        .await
+    }
+
+    #[actix_rt::test]
+    async fn timed_records_duration_on_the_instrumented_span() {
+        let capture = SpanCapture::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+
+        assert_eq!(slow_lookup().await, 7);
+        assert!(capture.duration_of("slow_lookup").unwrap() >= 20);
+    }
+
+    #[actix_rt::test]
+    async fn repository_calls_emit_a_timed_span() {
+        let Some(url) = std::env::var("DATABASE_URL").ok() else {
+            return;
+        };
+        let Ok(pool) = PgPool::connect(&url).await else {
+            return;
+        };
+        let capture = SpanCapture::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+
+        UserRepository::find_by_id(&pool, uuid::Uuid::new_v4())
+            .await
+            .unwrap();
+        assert!(capture.duration_of("find_by_id").is_some());
+    }
+}
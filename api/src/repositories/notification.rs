@@ -1,10 +1,12 @@
 //! Admin notification repository
 
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::errors::AppError;
-use crate::models::{AdminNotification, CreateAdminNotification};
+use crate::models::{AdminNotification, CreateAdminNotification, NotificationType};
+use crate::pagination::{Page, PerPage};
 
 pub struct NotificationRepository;
 
@@ -50,10 +52,11 @@ impl NotificationRepository {
     /// List all notifications with pagination
     pub async fn list_paginated(
         pool: &PgPool,
-        page: i32,
-        per_page: i32,
+        page: Page,
+        per_page: PerPage,
     ) -> Result<(Vec<AdminNotification>, i64), AppError> {
-        let offset = (page - 1) * per_page;
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
 
         let notifications = sqlx::query_as::<_, AdminNotification>(
             r#"
@@ -111,6 +114,34 @@ impl NotificationRepository {
         Ok(())
     }
 
+    /// Mark unread notifications matching every given filter as read.
+    ///
+    /// `None` filters match everything, so passing neither behaves like
+    /// `mark_all_as_read`. Returns the number of notifications marked.
+    pub async fn mark_read_filtered(
+        pool: &PgPool,
+        admin_id: Uuid,
+        notification_type: Option<&NotificationType>,
+        older_than: Option<DateTime<Utc>>,
+    ) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE admin_notifications
+            SET is_read = TRUE, read_by = $1, read_at = NOW()
+            WHERE is_read = FALSE
+              AND ($2::text IS NULL OR type = $2)
+              AND ($3::timestamptz IS NULL OR created_at < $3)
+            "#,
+        )
+        .bind(admin_id)
+        .bind(notification_type.map(NotificationType::as_str))
+        .bind(older_than)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// Count unread notifications
     pub async fn count_unread(pool: &PgPool) -> Result<i64, AppError> {
         let count: (i64,) =
@@ -136,3 +167,107 @@ impl NotificationRepository {
         Ok(result.rows_affected())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    //! DB-backed integration tests. Skipped when DATABASE_URL is unset.
+    use super::*;
+    use chrono::Duration;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    async fn insert_notification(
+        pool: &PgPool,
+        notification_type: NotificationType,
+        created_at: DateTime<Utc>,
+    ) -> Uuid {
+        let row: (Uuid,) = sqlx::query_as(
+            r#"
+            INSERT INTO admin_notifications (type, title, message, created_at)
+            VALUES ($1, 'test', 'test', $2)
+            RETURNING id
+            "#,
+        )
+        .bind(notification_type.as_str())
+        .bind(created_at)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        row.0
+    }
+
+    async fn is_read(pool: &PgPool, id: Uuid) -> bool {
+        let row: (bool,) = sqlx::query_as("SELECT is_read FROM admin_notifications WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        row.0
+    }
+
+    #[actix_rt::test]
+    async fn mark_read_filtered_only_touches_matching_notifications() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let admin_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, 'x')")
+            .bind(admin_id)
+            .bind(format!("{}@example.com", admin_id))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let old_failed = insert_notification(
+            &pool,
+            NotificationType::PaymentFailed,
+            now - Duration::days(40),
+        )
+        .await;
+        let new_failed = insert_notification(&pool, NotificationType::PaymentFailed, now).await;
+        let old_signup =
+            insert_notification(&pool, NotificationType::NewSignup, now - Duration::days(40)).await;
+
+        // Type and age together: only the old payment failure
+        let cutoff = now - Duration::days(30);
+        let marked = NotificationRepository::mark_read_filtered(
+            &pool,
+            admin_id,
+            Some(&NotificationType::PaymentFailed),
+            Some(cutoff),
+        )
+        .await
+        .unwrap();
+        assert!(marked >= 1);
+        assert!(is_read(&pool, old_failed).await);
+        assert!(!is_read(&pool, new_failed).await);
+        assert!(!is_read(&pool, old_signup).await);
+
+        // Type alone: the remaining payment failure, signups untouched
+        NotificationRepository::mark_read_filtered(
+            &pool,
+            admin_id,
+            Some(&NotificationType::PaymentFailed),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(is_read(&pool, new_failed).await);
+        assert!(!is_read(&pool, old_signup).await);
+
+        sqlx::query("DELETE FROM admin_notifications WHERE id = ANY($1)")
+            .bind(vec![old_failed, new_failed, old_signup])
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(admin_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+//! Saved payment method repository
+
+use sqlx::{PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::models::{PaymentCard, PaymentMethod};
+
+pub struct PaymentMethodRepository;
+
+impl PaymentMethodRepository {
+    /// The card currently shown for `user_id`, if Stripe has told us about one
+    pub async fn find_by_user_id(
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> Result<Option<PaymentMethod>, AppError> {
+        let pm = sqlx::query_as::<_, PaymentMethod>(
+            r#"
+            SELECT user_id, stripe_payment_method_id, brand, last4, exp_month, exp_year, updated_at
+            FROM payment_methods
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(pm)
+    }
+
+    /// Make `card` the card shown for `user_id`, replacing any previous one
+    pub async fn upsert<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        card: &PaymentCard,
+    ) -> Result<PaymentMethod, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let pm = sqlx::query_as::<_, PaymentMethod>(
+            r#"
+            INSERT INTO payment_methods
+                (user_id, stripe_payment_method_id, brand, last4, exp_month, exp_year)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (user_id) DO UPDATE SET
+                stripe_payment_method_id = EXCLUDED.stripe_payment_method_id,
+                brand = EXCLUDED.brand,
+                last4 = EXCLUDED.last4,
+                exp_month = EXCLUDED.exp_month,
+                exp_year = EXCLUDED.exp_year,
+                updated_at = NOW()
+            RETURNING user_id, stripe_payment_method_id, brand, last4, exp_month, exp_year, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(&card.stripe_payment_method_id)
+        .bind(&card.brand)
+        .bind(&card.last4)
+        .bind(card.exp_month)
+        .bind(card.exp_year)
+        .fetch_one(executor)
+        .await?;
+
+        Ok(pm)
+    }
+
+    /// Refresh the stored card only if it is still `card`'s payment method.
+    ///
+    /// Returns whether a row was updated.
+    pub async fn update_if_current(
+        pool: &PgPool,
+        user_id: Uuid,
+        card: &PaymentCard,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE payment_methods
+            SET brand = $3, last4 = $4, exp_month = $5, exp_year = $6, updated_at = NOW()
+            WHERE user_id = $1 AND stripe_payment_method_id = $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(&card.stripe_payment_method_id)
+        .bind(&card.brand)
+        .bind(&card.last4)
+        .bind(card.exp_month)
+        .bind(card.exp_year)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Stop showing `payment_method_id` for whichever user it was stored for.
+    ///
+    /// Returns whether a row was deleted.
+    pub async fn delete_by_payment_method_id(
+        pool: &PgPool,
+        payment_method_id: &str,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM payment_methods WHERE stripe_payment_method_id = $1")
+            .bind(payment_method_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! DB-backed integration tests. Skipped when DATABASE_URL is unset.
+    use super::*;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    fn card(id: &str, last4: &str) -> PaymentCard {
+        PaymentCard {
+            stripe_payment_method_id: id.to_string(),
+            brand: "visa".to_string(),
+            last4: last4.to_string(),
+            exp_month: 8,
+            exp_year: 2030,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn upsert_replaces_the_previous_card() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let user_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO users (email, password_hash) VALUES ($1, 'x') RETURNING id",
+        )
+        .bind(format!("card.{}@example.com", Uuid::new_v4().simple()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        PaymentMethodRepository::upsert(&pool, user_id, &card("pm_old", "1111"))
+            .await
+            .unwrap();
+        PaymentMethodRepository::upsert(&pool, user_id, &card("pm_new", "4242"))
+            .await
+            .unwrap();
+
+        // An update for the replaced card must not overwrite the new one
+        let stale =
+            PaymentMethodRepository::update_if_current(&pool, user_id, &card("pm_old", "9999"))
+                .await
+                .unwrap();
+        assert!(!stale);
+
+        let stored = PaymentMethodRepository::find_by_user_id(&pool, user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.stripe_payment_method_id, "pm_new");
+        assert_eq!(stored.last4, "4242");
+    }
+}
@@ -5,12 +5,14 @@ use sqlx::PgPool;
 
 use crate::errors::AppError;
 use crate::models::RateLimitConfig;
+use crate::repositories::timed;
 
 pub struct RateLimitRepository;
 
 impl RateLimitRepository {
     /// Check if rate limit is exceeded and increment counter
     /// Returns the current count and whether the limit is exceeded
+    #[tracing::instrument(level = "debug", skip_all, fields(action = config.action, duration_ms = tracing::field::Empty))]
     pub async fn check_and_increment(
         pool: &PgPool,
         key: &str,
@@ -19,8 +21,10 @@ impl RateLimitRepository {
         let window_start = Utc::now() - Duration::seconds(config.window_seconds);
 
         // Try to insert or update the rate limit entry
-        let result = sqlx::query_as::<_, (i32,)>(
-            r#"
+        let result = timed(
+            "check_and_increment",
+            sqlx::query_as::<_, (i32,)>(
+                r#"
             INSERT INTO rate_limits (key, action, count, window_start)
             VALUES ($1, $2, 1, NOW())
             ON CONFLICT (key, action)
@@ -35,11 +39,12 @@ impl RateLimitRepository {
                 END
             RETURNING count
             "#,
+            )
+            .bind(key)
+            .bind(config.action)
+            .bind(window_start)
+            .fetch_one(pool),
         )
-        .bind(key)
-        .bind(config.action)
-        .bind(window_start)
-        .fetch_one(pool)
         .await?;
 
         let count = result.0;
@@ -77,6 +82,37 @@ impl RateLimitRepository {
         Ok((count, exceeded))
     }
 
+    /// Current count and seconds until the window resets, without
+    /// incrementing. `(0, 0)` when nothing has been consumed in the window.
+    pub async fn status(
+        pool: &PgPool,
+        key: &str,
+        config: &RateLimitConfig,
+    ) -> Result<(i32, u64), AppError> {
+        let now = Utc::now();
+        let window_start = now - Duration::seconds(config.window_seconds);
+
+        let result = sqlx::query_as::<_, (i32, chrono::DateTime<Utc>)>(
+            r#"
+            SELECT count, window_start FROM rate_limits
+            WHERE key = $1 AND action = $2 AND window_start >= $3
+            "#,
+        )
+        .bind(key)
+        .bind(config.action)
+        .bind(window_start)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(match result {
+            Some((count, started)) => {
+                let reset_at = started + Duration::seconds(config.window_seconds);
+                (count, (reset_at - now).num_seconds().max(0) as u64)
+            }
+            None => (0, 0),
+        })
+    }
+
     /// Reset rate limit for a specific key and action
     pub async fn reset(pool: &PgPool, key: &str, action: &str) -> Result<(), AppError> {
         sqlx::query(
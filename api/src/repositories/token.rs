@@ -1,6 +1,7 @@
 //! Token repository for refresh tokens, magic links, and password resets
 
 use chrono::{DateTime, Utc};
+use ipnetwork::IpNetwork;
 use sqlx::postgres::Postgres;
 use sqlx::PgPool;
 use uuid::Uuid;
@@ -11,6 +12,8 @@ use crate::models::{
     CreatePasswordResetToken, CreateRefreshToken, EmailChangeRequest, EmailVerificationToken,
     MagicLinkToken, PasswordResetToken, RefreshToken,
 };
+use crate::pagination::{Page, PerPage};
+use crate::repositories::timed;
 
 pub struct TokenRepository;
 
@@ -20,41 +23,53 @@ impl TokenRepository {
     // =====================
 
     /// Create a new refresh token
-    pub async fn create_refresh_token(
-        pool: &PgPool,
+    #[tracing::instrument(level = "debug", skip_all, fields(user_id = %data.user_id, duration_ms = tracing::field::Empty))]
+    pub async fn create_refresh_token<'e, E>(
+        executor: E,
         data: CreateRefreshToken,
-    ) -> Result<RefreshToken, AppError> {
-        let token = sqlx::query_as::<_, RefreshToken>(
-            r#"
-            INSERT INTO refresh_tokens (user_id, token_hash, device_info, ip_address, expires_at)
-            VALUES ($1, $2, $3, $4, $5)
+    ) -> Result<RefreshToken, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let token = timed("create_refresh_token", 
+            sqlx::query_as::<_, RefreshToken>(
+                r#"
+            INSERT INTO refresh_tokens (user_id, token_hash, device_info, ip_address, expires_at, remember, device_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             RETURNING *
             "#,
+            )
+            .bind(data.user_id)
+            .bind(&data.token_hash)
+            .bind(&data.device_info)
+            .bind(data.ip_address)
+            .bind(data.expires_at)
+            .bind(data.remember)
+            .bind(&data.device_id)
+            .fetch_one(executor),
         )
-        .bind(data.user_id)
-        .bind(&data.token_hash)
-        .bind(&data.device_info)
-        .bind(data.ip_address)
-        .bind(data.expires_at)
-        .fetch_one(pool)
         .await?;
 
         Ok(token)
     }
 
     /// Find refresh token by hash
+    #[tracing::instrument(level = "debug", skip_all, fields(duration_ms = tracing::field::Empty))]
     pub async fn find_refresh_token_by_hash(
         pool: &PgPool,
         token_hash: &str,
     ) -> Result<Option<RefreshToken>, AppError> {
-        let token = sqlx::query_as::<_, RefreshToken>(
-            r#"
+        let token = timed(
+            "find_refresh_token_by_hash",
+            sqlx::query_as::<_, RefreshToken>(
+                r#"
             SELECT * FROM refresh_tokens
             WHERE token_hash = $1 AND revoked_at IS NULL AND expires_at > NOW()
             "#,
+            )
+            .bind(token_hash)
+            .fetch_optional(pool),
         )
-        .bind(token_hash)
-        .fetch_optional(pool)
         .await?;
 
         Ok(token)
@@ -105,6 +120,58 @@ impl TokenRepository {
         Self::find_user_refresh_tokens(pool, user_id).await
     }
 
+    /// Page through a user's active refresh tokens, newest first.
+    ///
+    /// `device` matches a case-insensitive substring of `device_info` (e.g.
+    /// "iPhone", "Firefox"); `ip` matches addresses inside the network, so a
+    /// bare address or a CIDR range both work.
+    pub async fn find_active_refresh_tokens_paginated(
+        pool: &PgPool,
+        user_id: Uuid,
+        page: Page,
+        per_page: PerPage,
+        device: Option<&str>,
+        ip: Option<IpNetwork>,
+    ) -> Result<(Vec<RefreshToken>, i64), AppError> {
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
+        let device_pattern = device.map(|d| format!("%{}%", d));
+
+        let tokens = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT * FROM refresh_tokens
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+              AND ($4::text IS NULL OR device_info ILIKE $4)
+              AND ($5::inet IS NULL OR ip_address <<= $5)
+            ORDER BY created_at DESC, id
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(per_page)
+        .bind(offset)
+        .bind(&device_pattern)
+        .bind(ip)
+        .fetch_all(pool)
+        .await?;
+
+        let total: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM refresh_tokens
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+              AND ($2::text IS NULL OR device_info ILIKE $2)
+              AND ($3::inet IS NULL OR ip_address <<= $3)
+            "#,
+        )
+        .bind(user_id)
+        .bind(&device_pattern)
+        .bind(ip)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((tokens, total.0))
+    }
+
     /// Find refresh token by ID
     pub async fn find_refresh_token_by_id(
         pool: &PgPool,
@@ -153,6 +220,78 @@ impl TokenRepository {
         Ok(())
     }
 
+    /// Revoke an active refresh token so it can be rotated. The row stays
+    /// locked until the caller's transaction ends, so of several concurrent
+    /// refreshes presenting the same token only one gets it back.
+    #[tracing::instrument(level = "debug", skip_all, fields(duration_ms = tracing::field::Empty))]
+    pub async fn claim_refresh_token_for_rotation<'e, E>(
+        executor: E,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let token = timed(
+            "claim_refresh_token_for_rotation",
+            sqlx::query_as::<_, RefreshToken>(
+                r#"
+            UPDATE refresh_tokens SET revoked_at = NOW(), last_used_at = NOW()
+            WHERE token_hash = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            RETURNING *
+            "#,
+            )
+            .bind(token_hash)
+            .fetch_optional(executor),
+        )
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Record the token that replaced a rotated one
+    pub async fn set_refresh_token_successor<'e, E>(
+        executor: E,
+        token_id: Uuid,
+        successor_hash: &str,
+    ) -> Result<(), AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET replaced_by = (SELECT id FROM refresh_tokens WHERE token_hash = $2)
+            WHERE id = $1
+            "#,
+        )
+        .bind(token_id)
+        .bind(successor_hash)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claim the single grace-window reissue of a rotated refresh token.
+    /// Returns whether this caller got it; concurrent or later replays of the
+    /// same token find it taken.
+    pub async fn claim_grace_reissue<'e, E>(executor: E, token_id: Uuid) -> Result<bool, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let claimed = sqlx::query(
+            r#"
+            UPDATE refresh_tokens SET grace_reissued_at = NOW()
+            WHERE id = $1 AND grace_reissued_at IS NULL
+            "#,
+        )
+        .bind(token_id)
+        .execute(executor)
+        .await?;
+
+        Ok(claimed.rows_affected() == 1)
+    }
+
     /// Revoke refresh token by hash
     pub async fn revoke_refresh_token_by_hash(
         pool: &PgPool,
@@ -171,10 +310,13 @@ impl TokenRepository {
     }
 
     /// Revoke all refresh tokens for a user
-    pub async fn revoke_all_user_refresh_tokens(
-        pool: &PgPool,
+    pub async fn revoke_all_user_refresh_tokens<'e, E>(
+        executor: E,
         user_id: Uuid,
-    ) -> Result<(), AppError> {
+    ) -> Result<(), AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
         sqlx::query(
             r#"
             UPDATE refresh_tokens SET revoked_at = NOW()
@@ -182,12 +324,65 @@ impl TokenRepository {
             "#,
         )
         .bind(user_id)
-        .execute(pool)
+        .execute(executor)
         .await?;
 
         Ok(())
     }
 
+    /// Revoke a user's active refresh tokens beyond the newest `keep`, oldest first.
+    /// Returns the number of sessions revoked.
+    pub async fn revoke_oldest_for_user<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        keep: i64,
+    ) -> Result<u64, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query(
+            r#"
+            UPDATE refresh_tokens SET revoked_at = NOW()
+            WHERE id IN (
+                SELECT id FROM refresh_tokens
+                WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+                ORDER BY created_at DESC, id DESC
+                OFFSET $2
+            )
+            "#,
+        )
+        .bind(user_id)
+        .bind(keep.max(0))
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Revoke a user's active sessions on one device. Returns how many
+    /// were revoked.
+    pub async fn revoke_for_device<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        device_id: &str,
+    ) -> Result<u64, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query(
+            r#"
+            UPDATE refresh_tokens SET revoked_at = NOW()
+            WHERE user_id = $1 AND device_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(device_id)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     // =====================
     // Magic Link Tokens
     // =====================
@@ -300,7 +495,8 @@ impl TokenRepository {
         let token = sqlx::query_as::<_, PasswordResetToken>(
             r#"
             SELECT * FROM password_reset_tokens
-            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            WHERE token_hash = $1 AND purpose = 'reset'
+              AND used_at IS NULL AND expires_at > NOW()
             "#,
         )
         .bind(token_hash)
@@ -310,21 +506,73 @@ impl TokenRepository {
         Ok(token)
     }
 
-    /// Mark password reset token as used
-    pub async fn mark_password_reset_token_used(
-        pool: &PgPool,
+    /// Mark a password reset token as used, if it still is unused and
+    /// unexpired. Returns false when a concurrent request consumed it first.
+    pub async fn mark_password_reset_token_used<'e, E>(
+        executor: E,
         token_id: Uuid,
-    ) -> Result<(), AppError> {
-        sqlx::query(
+    ) -> Result<bool, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query(
             r#"
-            UPDATE password_reset_tokens SET used_at = NOW() WHERE id = $1
+            UPDATE password_reset_tokens SET used_at = NOW()
+            WHERE id = $1 AND used_at IS NULL AND expires_at > NOW()
             "#,
         )
         .bind(token_id)
-        .execute(pool)
+        .execute(executor)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Create a token for setting a first password on a passwordless account
+    pub async fn create_set_password_token(
+        pool: &PgPool,
+        data: CreatePasswordResetToken,
+    ) -> Result<PasswordResetToken, AppError> {
+        let token = sqlx::query_as::<_, PasswordResetToken>(
+            r#"
+            INSERT INTO password_reset_tokens (user_id, token_hash, expires_at, ip_address, purpose)
+            VALUES ($1, $2, $3, $4, 'set_password')
+            RETURNING *
+            "#,
+        )
+        .bind(data.user_id)
+        .bind(&data.token_hash)
+        .bind(data.expires_at)
+        .bind(data.ip_address)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Consume a user's unused, unexpired set-password token
+    pub async fn claim_set_password_token<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        token_hash: &str,
+    ) -> Result<Option<PasswordResetToken>, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let token = sqlx::query_as::<_, PasswordResetToken>(
+            r#"
+            UPDATE password_reset_tokens SET used_at = NOW()
+            WHERE token_hash = $1 AND user_id = $2 AND purpose = 'set_password'
+              AND used_at IS NULL AND expires_at > NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(token)
     }
 
     /// Count recent password reset tokens for a user (for rate limiting)
@@ -491,24 +739,26 @@ impl TokenRepository {
         Ok(token)
     }
 
-    /// Mark email verification token as used
+    /// Mark email verification token as used. Returns false when it was
+    /// already used, so a token can only be redeemed once.
     pub async fn mark_email_verification_token_used<'e, E>(
         executor: E,
         token_id: Uuid,
-    ) -> Result<(), AppError>
+    ) -> Result<bool, AppError>
     where
         E: sqlx::Executor<'e, Database = Postgres>,
     {
-        sqlx::query(
+        let result = sqlx::query(
             r#"
-            UPDATE email_verification_tokens SET used_at = NOW() WHERE id = $1
+            UPDATE email_verification_tokens SET used_at = NOW()
+            WHERE id = $1 AND used_at IS NULL
             "#,
         )
         .bind(token_id)
         .execute(executor)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() == 1)
     }
 
     /// Count recent email verification tokens for a user (for rate limiting)
@@ -592,3 +842,161 @@ impl TokenRepository {
         Ok(total)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    //! DB-backed integration tests. Skipped when DATABASE_URL is unset.
+    use super::*;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    #[actix_rt::test]
+    async fn revoke_oldest_for_user_keeps_newest_sessions() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let user_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, 'x')")
+            .bind(user_id)
+            .bind(format!("sessions-{}@example.com", user_id))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut ids = Vec::new();
+        for age_minutes in [30, 20, 10] {
+            let token = TokenRepository::create_refresh_token(
+                &pool,
+                CreateRefreshToken {
+                    user_id,
+                    token_hash: format!("cap-test-{}", Uuid::new_v4()),
+                    device_info: None,
+                    ip_address: None,
+                    expires_at: Utc::now() + chrono::Duration::days(1),
+                    remember: true,
+                    device_id: None,
+                },
+            )
+            .await
+            .unwrap();
+            sqlx::query(
+                "UPDATE refresh_tokens SET created_at = NOW() - make_interval(mins => $1) WHERE id = $2",
+            )
+            .bind(age_minutes)
+            .bind(token.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+            ids.push(token.id);
+        }
+
+        let revoked = TokenRepository::revoke_oldest_for_user(&pool, user_id, 2)
+            .await
+            .unwrap();
+        let active: Vec<Uuid> = TokenRepository::find_user_refresh_tokens(&pool, user_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(revoked, 1);
+        assert_eq!(active, vec![ids[2], ids[1]]);
+    }
+
+    #[actix_rt::test]
+    async fn paginated_sessions_span_pages_and_filter() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let user_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, 'x')")
+            .bind(user_id)
+            .bind(format!("sessions-{}@example.com", user_id))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let sessions = [
+            ("Mozilla/5.0 (iPhone) Safari", "203.0.113.10"),
+            ("Mozilla/5.0 (Windows) Firefox", "203.0.113.11"),
+            ("Mozilla/5.0 (iPhone) Safari", "198.51.100.7"),
+            ("Mozilla/5.0 (Macintosh) Firefox", "198.51.100.8"),
+            ("curl/8.0", "192.0.2.1"),
+        ];
+        let mut ids = Vec::new();
+        for (age_minutes, (device, ip)) in sessions.iter().enumerate() {
+            let token = TokenRepository::create_refresh_token(
+                &pool,
+                CreateRefreshToken {
+                    user_id,
+                    token_hash: format!("page-test-{}", Uuid::new_v4()),
+                    device_info: Some(device.to_string()),
+                    ip_address: Some(ip.parse().unwrap()),
+                    expires_at: Utc::now() + chrono::Duration::days(1),
+                    remember: true,
+                    device_id: None,
+                },
+            )
+            .await
+            .unwrap();
+            sqlx::query(
+                "UPDATE refresh_tokens SET created_at = NOW() - make_interval(mins => $1) WHERE id = $2",
+            )
+            .bind(age_minutes as i32)
+            .bind(token.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+            ids.push(token.id);
+        }
+
+        let page = |n, device: Option<&'static str>, ip: Option<&'static str>| {
+            let pool = pool.clone();
+            async move {
+                let (tokens, total) = TokenRepository::find_active_refresh_tokens_paginated(
+                    &pool,
+                    user_id,
+                    Page::new(n).unwrap(),
+                    PerPage::new(2).unwrap(),
+                    device,
+                    ip.map(|ip| ip.parse().unwrap()),
+                )
+                .await
+                .unwrap();
+                (tokens.into_iter().map(|t| t.id).collect::<Vec<_>>(), total)
+            }
+        };
+
+        let pages = [
+            page(1, None, None).await,
+            page(2, None, None).await,
+            page(3, None, None).await,
+        ];
+        let iphone = page(1, Some("iphone"), None).await;
+        let subnet = page(1, None, Some("198.51.100.0/24")).await;
+        let both = page(1, Some("firefox"), Some("203.0.113.0/24")).await;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(pages[0], (vec![ids[0], ids[1]], 5));
+        assert_eq!(pages[1], (vec![ids[2], ids[3]], 5));
+        assert_eq!(pages[2], (vec![ids[4]], 5));
+        assert_eq!(iphone, (vec![ids[0], ids[2]], 2));
+        assert_eq!(subnet, (vec![ids[2], ids[3]], 2));
+        assert_eq!(both, (vec![ids[1]], 1));
+    }
+}
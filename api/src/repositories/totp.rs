@@ -164,13 +164,19 @@ impl TotpRepository {
         .await
     }
 
-    /// Mark a recovery code as used
-    pub async fn mark_recovery_code_used(pool: &PgPool, code_id: Uuid) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE recovery_codes SET used_at = NOW() WHERE id = $1")
-            .bind(code_id)
-            .execute(pool)
-            .await?;
-        Ok(())
+    /// Mark a recovery code as used. Returns false when it was already used,
+    /// so two concurrent logins cannot both spend the same code.
+    pub async fn mark_recovery_code_used(
+        pool: &PgPool,
+        code_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE recovery_codes SET used_at = NOW() WHERE id = $1 AND used_at IS NULL",
+        )
+        .bind(code_id)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
     }
 
     /// Count unused recovery codes for a user
@@ -6,7 +6,9 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::errors::AppError;
-use crate::models::{CreateUser, MembershipStatus, SubscriptionTier, User};
+use crate::models::{AccountMergeSummary, CreateUser, MembershipStatus, SubscriptionTier, User};
+use crate::pagination::{Page, PerPage};
+use crate::repositories::timed;
 
 pub struct UserRepository;
 
@@ -30,10 +32,31 @@ impl UserRepository {
     }
 
     /// Find user by ID
+    #[tracing::instrument(level = "debug", skip(pool), fields(duration_ms = tracing::field::Empty))]
     pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<User>, AppError> {
+        let user = timed(
+            "find_by_id",
+            sqlx::query_as::<_, User>(
+                r#"
+            SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL
+            "#,
+            )
+            .bind(id)
+            .fetch_optional(pool),
+        )
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Find user by ID, including soft-deleted users (admin views)
+    pub async fn find_by_id_with_deleted(
+        pool: &PgPool,
+        id: Uuid,
+    ) -> Result<Option<User>, AppError> {
         let user = sqlx::query_as::<_, User>(
             r#"
-            SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL
+            SELECT * FROM users WHERE id = $1
             "#,
         )
         .bind(id)
@@ -44,42 +67,53 @@ impl UserRepository {
     }
 
     /// Find user by email
+    #[tracing::instrument(level = "debug", skip_all, fields(duration_ms = tracing::field::Empty))]
     pub async fn find_by_email(pool: &PgPool, email: &str) -> Result<Option<User>, AppError> {
-        let user = sqlx::query_as::<_, User>(
-            r#"
+        let user = timed(
+            "find_by_email",
+            sqlx::query_as::<_, User>(
+                r#"
             SELECT * FROM users WHERE LOWER(email) = LOWER($1) AND deleted_at IS NULL
             "#,
+            )
+            .bind(email)
+            .fetch_optional(pool),
         )
-        .bind(email)
-        .fetch_optional(pool)
         .await?;
 
         Ok(user)
     }
 
     /// Find user by Stripe customer ID
+    #[tracing::instrument(level = "debug", skip(pool), fields(duration_ms = tracing::field::Empty))]
     pub async fn find_by_stripe_customer_id(
         pool: &PgPool,
         customer_id: &str,
     ) -> Result<Option<User>, AppError> {
-        let user = sqlx::query_as::<_, User>(
-            r#"
+        let user = timed(
+            "find_by_stripe_customer_id",
+            sqlx::query_as::<_, User>(
+                r#"
             SELECT * FROM users WHERE stripe_customer_id = $1 AND deleted_at IS NULL
             "#,
+            )
+            .bind(customer_id)
+            .fetch_optional(pool),
         )
-        .bind(customer_id)
-        .fetch_optional(pool)
         .await?;
 
         Ok(user)
     }
 
     /// Update user's password hash
-    pub async fn update_password(
-        pool: &PgPool,
+    pub async fn update_password<'e, E>(
+        executor: E,
         user_id: Uuid,
         password_hash: &str,
-    ) -> Result<(), AppError> {
+    ) -> Result<(), AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
         sqlx::query(
             r#"
             UPDATE users
@@ -89,12 +123,36 @@ impl UserRepository {
         )
         .bind(password_hash)
         .bind(user_id)
-        .execute(pool)
+        .execute(executor)
         .await?;
 
         Ok(())
     }
 
+    /// Replace `old_hash` with `new_hash`, unless the password changed since
+    /// `old_hash` was read. Returns whether the row was updated.
+    pub async fn rehash_password_if_unchanged(
+        pool: &PgPool,
+        user_id: Uuid,
+        old_hash: &str,
+        new_hash: &str,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = $1, updated_at = NOW()
+            WHERE id = $2 AND password_hash = $3
+            "#,
+        )
+        .bind(new_hash)
+        .bind(user_id)
+        .bind(old_hash)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Update email verified status
     pub async fn set_email_verified(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
         sqlx::query(
@@ -123,7 +181,12 @@ impl UserRepository {
         sqlx::query(
             r#"
             UPDATE users
-            SET subscription_status = $1, updated_at = NOW()
+            SET subscription_status = $1,
+                membership_canceled_at = CASE
+                    WHEN $1 = 'canceled' AND subscription_status <> 'canceled' THEN NOW()
+                    ELSE membership_canceled_at
+                END,
+                updated_at = NOW()
             WHERE id = $2
             "#,
         )
@@ -135,6 +198,76 @@ impl UserRepository {
         Ok(())
     }
 
+    /// Update membership status only if the user's current status may legally
+    /// move to `status` (see [`MembershipStatus::can_transition_to`]). Returns
+    /// false, leaving the row unchanged, when the transition is not allowed.
+    ///
+    /// `subscription_created` is when the subscription behind the change was
+    /// created; it counts as a new subscription, and may leave `Canceled`,
+    /// only if that is after the membership was canceled.
+    pub async fn transition_membership_status<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        status: MembershipStatus,
+        subscription_created: Option<DateTime<Utc>>,
+    ) -> Result<bool, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let sources = MembershipStatus::allowed_sources(&status, false);
+        let reopens = MembershipStatus::Canceled.can_transition_to(&status, true);
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET subscription_status = $1,
+                membership_canceled_at = CASE
+                    WHEN $1 = 'canceled' AND subscription_status <> 'canceled' THEN NOW()
+                    ELSE membership_canceled_at
+                END,
+                updated_at = NOW()
+            WHERE id = $2
+              AND (
+                  subscription_status = ANY($3)
+                  OR (
+                      subscription_status = 'canceled'
+                      AND $4 > COALESCE(membership_canceled_at, '-infinity')
+                  )
+              )
+            "#,
+        )
+        .bind(status.as_str())
+        .bind(user_id)
+        .bind(&sources)
+        .bind(subscription_created.filter(|_| reopens))
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Record why a user canceled their membership
+    pub async fn set_cancellation_reason(
+        pool: &PgPool,
+        user_id: Uuid,
+        reason: Option<&str>,
+        feedback: Option<&str>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET cancellation_reason = $1, cancellation_feedback = $2, updated_at = NOW()
+            WHERE id = $3
+            "#,
+        )
+        .bind(reason)
+        .bind(feedback)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
     /// Activate membership (set subscription_status to 'active')
     pub async fn activate_membership(pool: &PgPool, user_id: Uuid) -> Result<User, AppError> {
         let user = sqlx::query_as::<_, User>(
@@ -202,12 +335,15 @@ impl UserRepository {
     }
 
     /// Lock price for user
-    pub async fn lock_price(
-        pool: &PgPool,
+    pub async fn lock_price<'e, E>(
+        executor: E,
         user_id: Uuid,
         price_id: &str,
         amount: i32,
-    ) -> Result<(), AppError> {
+    ) -> Result<(), AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
         sqlx::query(
             r#"
             UPDATE users
@@ -218,7 +354,7 @@ impl UserRepository {
         .bind(price_id)
         .bind(amount)
         .bind(user_id)
-        .execute(pool)
+        .execute(executor)
         .await?;
 
         Ok(())
@@ -250,6 +386,42 @@ impl UserRepository {
         Ok(())
     }
 
+    /// Set or clear the trial end, e.g. from a Stripe `trialing` subscription.
+    pub async fn set_trial_ends_at<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        trial_ends_at: Option<DateTime<Utc>>,
+    ) -> Result<(), AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        sqlx::query("UPDATE users SET trial_ends_at = $1, updated_at = NOW() WHERE id = $2")
+            .bind(trial_ends_at)
+            .bind(user_id)
+            .execute(executor)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Set only the subscription tier label, leaving trial and lifetime flags alone
+    pub async fn set_subscription_tier<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        tier: &SubscriptionTier,
+    ) -> Result<(), AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        sqlx::query("UPDATE users SET subscription_tier = $1, updated_at = NOW() WHERE id = $2")
+            .bind(tier.as_str())
+            .bind(user_id)
+            .execute(executor)
+            .await?;
+
+        Ok(())
+    }
+
     /// Reset subscription tier to standard when a membership is revoked/canceled.
     /// This frees the lifetime or early_adopter slot so it can be assigned to the next user.
     pub async fn reset_subscription_tier<'e, E>(executor: E, user_id: Uuid) -> Result<(), AppError>
@@ -323,6 +495,38 @@ impl UserRepository {
         Ok(())
     }
 
+    /// Cancel `user_id`'s membership and clear the grace period, provided the
+    /// grace period is still running out as of `now`. Returns false, leaving
+    /// the row unchanged, when a payment or cancellation got there first.
+    pub async fn expire_grace_period<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<bool, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET subscription_status = 'canceled',
+                membership_canceled_at = NOW(),
+                grace_period_start = NULL,
+                grace_period_end = NULL,
+                updated_at = NOW()
+            WHERE id = $1
+            AND subscription_status IN ('past_due', 'grace_period')
+            AND grace_period_end <= $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(now)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
     /// Update user's email address
     pub async fn update_email(
         pool: &PgPool,
@@ -346,36 +550,169 @@ impl UserRepository {
         Ok(())
     }
 
-    /// Update last login timestamp
-    pub async fn update_last_login(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    /// Update last login timestamp.
+    ///
+    /// Returns true when this was the user's first login. The previous value is
+    /// read under a row lock, so concurrent logins see it as NULL at most once.
+    pub async fn update_last_login(pool: &PgPool, user_id: Uuid) -> Result<bool, AppError> {
+        let first_login: Option<bool> = sqlx::query_scalar(
+            r#"
+            UPDATE users u
+            SET last_login_at = NOW(), updated_at = NOW()
+            FROM (SELECT id, last_login_at FROM users WHERE id = $1 FOR UPDATE) prev
+            WHERE u.id = prev.id
+            RETURNING prev.last_login_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(first_login.unwrap_or(false))
+    }
+
+    /// Soft delete user, recording an optional reason
+    pub async fn soft_delete(
+        pool: &PgPool,
+        user_id: Uuid,
+        reason: Option<&str>,
+    ) -> Result<(), AppError> {
         sqlx::query(
             r#"
             UPDATE users
-            SET last_login_at = NOW(), updated_at = NOW()
+            SET deleted_at = NOW(), deactivation_reason = $2, updated_at = NOW()
             WHERE id = $1
             "#,
         )
         .bind(user_id)
+        .bind(reason)
         .execute(pool)
         .await?;
 
         Ok(())
     }
 
-    /// Soft delete user
-    pub async fn soft_delete(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    /// Merge `source_id` into `target_id`: move the source's membership and
+    /// saved card to the target (if it has the one worth keeping), then
+    /// soft-delete the source and revoke its sessions and access tokens.
+    /// Audit history is left under the account that made it.
+    ///
+    /// Run inside a transaction. Both users are locked first; either being
+    /// missing or deleted is `NotFound`, and two Stripe customers cannot be
+    /// combined, so that is a `Conflict`.
+    pub async fn merge_into(
+        conn: &mut sqlx::PgConnection,
+        source_id: Uuid,
+        target_id: Uuid,
+    ) -> Result<AccountMergeSummary, AppError> {
+        let users = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE id = ANY($1) AND deleted_at IS NULL ORDER BY id FOR UPDATE",
+        )
+        .bind(vec![source_id, target_id])
+        .fetch_all(&mut *conn)
+        .await?;
+        let find = |id: Uuid| users.iter().find(|u| u.id == id).cloned();
+        let (Some(source), Some(target)) = (find(source_id), find(target_id)) else {
+            return Err(AppError::not_found("User"));
+        };
+        if source.stripe_customer_id.is_some() && target.stripe_customer_id.is_some() {
+            return Err(AppError::conflict(
+                "Both accounts have a billing account; merge them in Stripe first",
+            ));
+        }
+
+        // The target keeps its own membership unless the source's is an
+        // active paid (Stripe-backed) one or a lifetime one
+        let paid_and_active = source.stripe_customer_id.is_some()
+            && source.membership_status_enum() == MembershipStatus::Active;
+        let mut summary = AccountMergeSummary {
+            membership_moved: paid_and_active
+                || (source.lifetime_member && !target.lifetime_member),
+            ..Default::default()
+        };
+        if summary.membership_moved {
+            // Free the unique Stripe customer ID before the target takes it
+            sqlx::query(
+                "UPDATE users SET stripe_customer_id = NULL, stripe_payment_method_id = NULL WHERE id = $1",
+            )
+            .bind(source.id)
+            .execute(&mut *conn)
+            .await?;
+            sqlx::query(
+                r#"
+                UPDATE users SET
+                    stripe_customer_id = COALESCE($2, stripe_customer_id),
+                    stripe_payment_method_id = COALESCE($3, stripe_payment_method_id),
+                    subscription_status = $4,
+                    price_locked = $5,
+                    locked_price_id = $6,
+                    locked_price_amount = $7,
+                    grace_period_start = $8,
+                    grace_period_end = $9,
+                    subscription_tier = $10,
+                    trial_ends_at = $11,
+                    lifetime_member = lifetime_member OR $12,
+                    subscription_override_by = COALESCE($13, subscription_override_by),
+                    updated_at = NOW()
+                WHERE id = $1
+                "#,
+            )
+            .bind(target.id)
+            .bind(&source.stripe_customer_id)
+            .bind(&source.stripe_payment_method_id)
+            .bind(&source.membership_status)
+            .bind(source.price_locked)
+            .bind(&source.locked_price_id)
+            .bind(source.locked_price_amount)
+            .bind(source.grace_period_start)
+            .bind(source.grace_period_end)
+            .bind(&source.subscription_tier)
+            .bind(source.trial_ends_at)
+            .bind(source.lifetime_member)
+            .bind(source.subscription_override_by)
+            .execute(&mut *conn)
+            .await?;
+            summary.stripe_customer_id = source.stripe_customer_id.clone();
+
+            // The card on file follows the billing account
+            if source.stripe_customer_id.is_some() {
+                sqlx::query(
+                    r#"
+                    DELETE FROM payment_methods
+                    WHERE user_id = $2 AND EXISTS (SELECT 1 FROM payment_methods WHERE user_id = $1)
+                    "#,
+                )
+                .bind(source.id)
+                .bind(target.id)
+                .execute(&mut *conn)
+                .await?;
+                summary.payment_method_moved =
+                    sqlx::query("UPDATE payment_methods SET user_id = $2 WHERE user_id = $1")
+                        .bind(source.id)
+                        .bind(target.id)
+                        .execute(&mut *conn)
+                        .await?
+                        .rows_affected()
+                        > 0;
+            }
+        }
+
         sqlx::query(
             r#"
             UPDATE users
-            SET deleted_at = NOW(), updated_at = NOW()
+            SET deleted_at = NOW(), deactivation_reason = $2, updated_at = NOW(),
+                tokens_valid_after = GREATEST(COALESCE(tokens_valid_after, NOW()), NOW())
             WHERE id = $1
             "#,
         )
-        .bind(user_id)
-        .execute(pool)
+        .bind(source.id)
+        .bind(format!("Merged into {}", target.id))
+        .execute(&mut *conn)
         .await?;
+        crate::repositories::TokenRepository::revoke_all_user_refresh_tokens(&mut *conn, source.id)
+            .await?;
 
-        Ok(())
+        Ok(summary)
     }
 
     /// Set two_factor_enabled flag on a user
@@ -395,6 +732,43 @@ impl UserRepository {
         Ok(())
     }
 
+    /// Reject the user's access tokens issued at or before `cutoff`. Never
+    /// moves an existing cutoff back.
+    pub async fn set_tokens_valid_after(
+        pool: &PgPool,
+        user_id: Uuid,
+        cutoff: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET tokens_valid_after = GREATEST(COALESCE(tokens_valid_after, $2), $2)
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Token cutoffs set after `since`, as (user id, cutoff)
+    pub async fn find_token_cutoffs_since(
+        pool: &PgPool,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(Uuid, DateTime<Utc>)>, AppError> {
+        let cutoffs = sqlx::query_as::<_, (Uuid, DateTime<Utc>)>(
+            "SELECT id, tokens_valid_after FROM users WHERE tokens_valid_after > $1",
+        )
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(cutoffs)
+    }
+
     /// Update user role
     pub async fn update_role(pool: &PgPool, user_id: Uuid, role: &str) -> Result<User, AppError> {
         let user = sqlx::query_as::<_, User>(
@@ -414,100 +788,131 @@ impl UserRepository {
         Ok(user)
     }
 
-    /// List users with pagination
+    /// List users with pagination, optionally filtered by an email search
+    /// term and membership status
+    #[tracing::instrument(level = "debug", skip(pool, search), fields(duration_ms = tracing::field::Empty))]
     pub async fn list_paginated(
         pool: &PgPool,
-        page: i32,
-        per_page: i32,
+        page: Page,
+        per_page: PerPage,
         search: Option<&str>,
         status_filter: Option<MembershipStatus>,
     ) -> Result<(Vec<User>, i64), AppError> {
-        let offset = (page - 1) * per_page;
-
-        // Build dynamic query based on filters
-        let mut conditions = vec!["deleted_at IS NULL".to_string()];
-
-        if search.is_some() {
-            conditions.push("LOWER(email) LIKE LOWER($3)".to_string());
+        if let Some(term) = search {
+            return Self::search_by_email(pool, term, status_filter, page, per_page).await;
         }
 
-        if let Some(_status) = &status_filter {
-            let idx = if search.is_some() { 4 } else { 3 };
-            conditions.push(format!("subscription_status = ${}", idx));
-        }
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
+        let status = status_filter.as_ref().map(|s| s.as_str());
 
-        let where_clause = conditions.join(" AND ");
-        let query = format!(
-            "SELECT * FROM users WHERE {} ORDER BY created_at DESC LIMIT $1 OFFSET $2",
-            where_clause
-        );
-        let count_query = format!("SELECT COUNT(*) FROM users WHERE {}", where_clause);
-
-        // Execute queries based on filters
-        let (users, total): (Vec<User>, i64) = match (search, &status_filter) {
-            (Some(s), Some(status)) => {
-                let search_pattern = format!("%{}%", s);
-                let users = sqlx::query_as::<_, User>(&query)
-                    .bind(per_page)
-                    .bind(offset)
-                    .bind(&search_pattern)
-                    .bind(status.as_str())
-                    .fetch_all(pool)
-                    .await?;
-
-                let total: (i64,) = sqlx::query_as(&count_query)
-                    .bind(&search_pattern)
-                    .bind(status.as_str())
-                    .fetch_one(pool)
-                    .await?;
-
-                (users, total.0)
-            }
-            (Some(s), None) => {
-                let search_pattern = format!("%{}%", s);
-                let users = sqlx::query_as::<_, User>(&query)
-                    .bind(per_page)
-                    .bind(offset)
-                    .bind(&search_pattern)
-                    .fetch_all(pool)
-                    .await?;
-
-                let total: (i64,) = sqlx::query_as(&count_query)
-                    .bind(&search_pattern)
-                    .fetch_one(pool)
-                    .await?;
-
-                (users, total.0)
-            }
-            (None, Some(status)) => {
-                let users = sqlx::query_as::<_, User>(&query)
-                    .bind(per_page)
-                    .bind(offset)
-                    .bind(status.as_str())
-                    .fetch_all(pool)
-                    .await?;
-
-                let total: (i64,) = sqlx::query_as(&count_query)
-                    .bind(status.as_str())
-                    .fetch_one(pool)
-                    .await?;
-
-                (users, total.0)
-            }
-            (None, None) => {
-                let users = sqlx::query_as::<_, User>(&query)
-                    .bind(per_page)
-                    .bind(offset)
-                    .fetch_all(pool)
-                    .await?;
+        let users = timed(
+            "list_paginated",
+            sqlx::query_as::<_, User>(
+                r#"
+            SELECT * FROM users
+            WHERE deleted_at IS NULL
+              AND ($3::text IS NULL OR subscription_status = $3)
+            ORDER BY created_at DESC
+            LIMIT $1 OFFSET $2
+            "#,
+            )
+            .bind(per_page)
+            .bind(offset)
+            .bind(status)
+            .fetch_all(pool),
+        )
+        .await?;
 
-                let total: (i64,) = sqlx::query_as(&count_query).fetch_one(pool).await?;
+        let total: (i64,) = timed(
+            "list_paginated",
+            sqlx::query_as(
+                r#"
+            SELECT COUNT(*) FROM users
+            WHERE deleted_at IS NULL
+              AND ($1::text IS NULL OR subscription_status = $1)
+            "#,
+            )
+            .bind(status)
+            .fetch_one(pool),
+        )
+        .await?;
 
-                (users, total.0)
-            }
+        Ok((users, total.0))
+    }
+
+    /// Search users by email substring (case-insensitive).
+    ///
+    /// The predicate is served by the `pg_trgm` GIN index on `LOWER(email)`
+    /// when the extension is installed, and results are ranked by trigram
+    /// similarity. Without the extension the same query still works as a
+    /// sequential scan ordered by newest first.
+    #[tracing::instrument(level = "debug", skip(pool, term), fields(duration_ms = tracing::field::Empty))]
+    pub async fn search_by_email(
+        pool: &PgPool,
+        term: &str,
+        status_filter: Option<MembershipStatus>,
+        page: Page,
+        per_page: PerPage,
+    ) -> Result<(Vec<User>, i64), AppError> {
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
+        let needle = escape_like(&term.to_lowercase());
+        let status = status_filter.as_ref().map(|s| s.as_str());
+
+        let order = if Self::trigram_search_available(pool).await? {
+            "similarity(LOWER(email), $1) DESC, created_at DESC"
+        } else {
+            "created_at DESC"
         };
+        let users = timed(
+            "search_by_email",
+            sqlx::query_as::<_, User>(&format!(
+                r#"
+            SELECT * FROM users
+            WHERE deleted_at IS NULL
+              AND LOWER(email) LIKE '%' || $1 || '%'
+              AND ($2::text IS NULL OR subscription_status = $2)
+            ORDER BY {}
+            LIMIT $3 OFFSET $4
+            "#,
+                order
+            ))
+            .bind(&needle)
+            .bind(status)
+            .bind(per_page)
+            .bind(offset)
+            .fetch_all(pool),
+        )
+        .await?;
 
-        Ok((users, total))
+        let total: (i64,) = timed(
+            "search_by_email",
+            sqlx::query_as(
+                r#"
+            SELECT COUNT(*) FROM users
+            WHERE deleted_at IS NULL
+              AND LOWER(email) LIKE '%' || $1 || '%'
+              AND ($2::text IS NULL OR subscription_status = $2)
+            "#,
+            )
+            .bind(&needle)
+            .bind(status)
+            .fetch_one(pool),
+        )
+        .await?;
+
+        Ok((users, total.0))
+    }
+
+    /// Whether the `pg_trgm` extension is installed. It is optional: the
+    /// migration skips it where the database role cannot create extensions.
+    async fn trigram_search_available(pool: &PgPool) -> Result<bool, AppError> {
+        let row: (bool,) =
+            sqlx::query_as("SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'pg_trgm')")
+                .fetch_one(pool)
+                .await?;
+        Ok(row.0)
     }
 
     /// Atomically assign a subscription tier to a user.
@@ -611,11 +1016,14 @@ impl UserRepository {
     }
 
     /// Grant free membership to a user (admin override, not tied to signup count).
-    pub async fn grant_free_membership(
-        pool: &PgPool,
+    pub async fn grant_free_membership<'e, E>(
+        executor: E,
         user_id: Uuid,
         granted_by: Uuid,
-    ) -> Result<User, AppError> {
+    ) -> Result<User, AppError>
+    where
+        E: sqlx::Executor<'e, Database = Postgres>,
+    {
         let user = sqlx::query_as::<_, User>(
             r#"
             UPDATE users
@@ -631,7 +1039,7 @@ impl UserRepository {
         )
         .bind(user_id)
         .bind(granted_by)
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?
         .ok_or_else(|| AppError::not_found("User"))?;
 
@@ -649,20 +1057,414 @@ impl UserRepository {
         Ok(rows.into_iter().map(|(email,)| email).collect())
     }
 
-    /// Find users in grace period
-    pub async fn find_in_grace_period(pool: &PgPool) -> Result<Vec<User>, AppError> {
+    /// Users in `past_due` or `grace_period` whose grace period ends before
+    /// `ends_before`, soonest first
+    pub async fn find_in_grace_period(
+        pool: &PgPool,
+        ends_before: DateTime<Utc>,
+        page: Page,
+        per_page: PerPage,
+    ) -> Result<(Vec<User>, i64), AppError> {
+        let offset = page.offset(per_page);
+        let per_page = per_page.get();
+
         let users = sqlx::query_as::<_, User>(
             r#"
             SELECT * FROM users
-            WHERE subscription_status = 'grace_period'
+            WHERE subscription_status IN ('past_due', 'grace_period')
             AND grace_period_end IS NOT NULL
+            AND grace_period_end <= $1
             AND deleted_at IS NULL
-            ORDER BY grace_period_end ASC
+            ORDER BY grace_period_end ASC, id
+            LIMIT $2 OFFSET $3
             "#,
         )
+        .bind(ends_before)
+        .bind(per_page)
+        .bind(offset)
         .fetch_all(pool)
         .await?;
 
-        Ok(users)
+        let total: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM users
+            WHERE subscription_status IN ('past_due', 'grace_period')
+            AND grace_period_end IS NOT NULL
+            AND grace_period_end <= $1
+            AND deleted_at IS NULL
+            "#,
+        )
+        .bind(ends_before)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((users, total.0))
+    }
+}
+
+/// Escape `LIKE` wildcards so a search term only matches literally
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    //! DB-backed integration tests. Skipped when DATABASE_URL is unset.
+    use super::*;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    async fn insert_merge_user(
+        pool: &PgPool,
+        email: String,
+        customer: Option<String>,
+        status: &str,
+    ) -> Uuid {
+        sqlx::query_scalar(
+            r#"
+            INSERT INTO users (email, password_hash, stripe_customer_id, subscription_status, subscription_tier)
+            VALUES ($1, 'x', $2, $3, 'standard')
+            RETURNING id
+            "#,
+        )
+        .bind(email)
+        .bind(customer)
+        .bind(status)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn delete_merge_users(pool: &PgPool, ids: &[Uuid]) {
+        sqlx::query("DELETE FROM payment_methods WHERE user_id = ANY($1)")
+            .bind(ids)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM audit_logs WHERE actor_id = ANY($1)")
+            .bind(ids)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn merge_moves_billing_then_deletes_source() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let tag = Uuid::new_v4().simple().to_string();
+        let customer_id = format!("cus_merge_{}", tag);
+        let source = insert_merge_user(
+            &pool,
+            format!("dup.{}@example.com", tag),
+            Some(customer_id.clone()),
+            "active",
+        )
+        .await;
+        let target =
+            insert_merge_user(&pool, format!("main.{}@example.com", tag), None, "none").await;
+        sqlx::query(
+            "INSERT INTO payment_methods (user_id, stripe_payment_method_id, brand, last4, exp_month, exp_year) VALUES ($1, $2, 'visa', '4242', 1, 2030)",
+        )
+        .bind(source)
+        .bind(format!("pm_merge_{}", tag))
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO audit_logs (actor_id, action, resource_type, resource_id) VALUES ($1, 'user_login', 'user', $1)",
+        )
+        .bind(source)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut tx = pool.begin().await.unwrap();
+        let summary = UserRepository::merge_into(&mut *tx, source, target)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        let target_user = UserRepository::find_by_id(&pool, target)
+            .await
+            .unwrap()
+            .unwrap();
+        let source_user = UserRepository::find_by_id_with_deleted(&pool, source)
+            .await
+            .unwrap()
+            .unwrap();
+        let source_visible = UserRepository::find_by_id(&pool, source).await.unwrap();
+        let source_tokens_valid_after: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT tokens_valid_after FROM users WHERE id = $1")
+                .bind(source)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let card_owner: Uuid = sqlx::query_scalar(
+            "SELECT user_id FROM payment_methods WHERE stripe_payment_method_id = $1",
+        )
+        .bind(format!("pm_merge_{}", tag))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let source_history: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM audit_logs WHERE actor_id = $1")
+                .bind(source)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+        // A second merge of the now-deleted source finds nothing to merge
+        let mut tx = pool.begin().await.unwrap();
+        let again = UserRepository::merge_into(&mut *tx, source, target).await;
+        drop(tx);
+
+        delete_merge_users(&pool, &[source, target]).await;
+
+        assert!(summary.membership_moved);
+        assert!(summary.payment_method_moved);
+        assert_eq!(
+            summary.stripe_customer_id.as_deref(),
+            Some(customer_id.as_str())
+        );
+        assert_eq!(
+            target_user.stripe_customer_id.as_deref(),
+            Some(customer_id.as_str())
+        );
+        assert_eq!(target_user.membership_status, "active");
+        assert!(source_user.is_deleted());
+        assert_eq!(source_user.stripe_customer_id, None);
+        assert!(source_visible.is_none());
+        // The source's outstanding access tokens are cut off
+        assert!(source_tokens_valid_after.is_some());
+        assert_eq!(card_owner, target);
+        // History stays with the account that made it
+        assert_eq!(source_history, 1);
+        assert!(matches!(again, Err(AppError::NotFound { .. })));
+    }
+
+    #[actix_rt::test]
+    async fn merge_keeps_the_target_membership_over_a_canceled_one() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let tag = Uuid::new_v4().simple().to_string();
+        let source = insert_merge_user(
+            &pool,
+            format!("dup.{}@example.com", tag),
+            Some(format!("cus_merge_{}", tag)),
+            "canceled",
+        )
+        .await;
+        let target =
+            insert_merge_user(&pool, format!("main.{}@example.com", tag), None, "active").await;
+
+        let mut tx = pool.begin().await.unwrap();
+        let summary = UserRepository::merge_into(&mut *tx, source, target)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        let target_user = UserRepository::find_by_id(&pool, target)
+            .await
+            .unwrap()
+            .unwrap();
+
+        delete_merge_users(&pool, &[source, target]).await;
+
+        assert!(!summary.membership_moved);
+        assert_eq!(summary.stripe_customer_id, None);
+        assert_eq!(target_user.membership_status, "active");
+        assert_eq!(target_user.stripe_customer_id, None);
+    }
+
+    #[actix_rt::test]
+    async fn grace_period_window_excludes_later_and_healthy_users() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let tag = Uuid::new_v4().simple().to_string();
+        let now = Utc::now();
+        let insert = |name: &str, status: &str, grace_end: DateTime<Utc>| {
+            let pool = pool.clone();
+            let email = format!("{}.{}@example.com", name, tag);
+            let status = status.to_string();
+            async move {
+                sqlx::query_scalar::<_, Uuid>(
+                    r#"
+                    INSERT INTO users (email, password_hash, subscription_status, grace_period_end)
+                    VALUES ($1, 'x', $2, $3)
+                    RETURNING id
+                    "#,
+                )
+                .bind(email)
+                .bind(status)
+                .bind(grace_end)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+            }
+        };
+        let soonest = insert("soonest", "past_due", now + chrono::Duration::hours(6)).await;
+        let soon = insert("soon", "grace_period", now + chrono::Duration::days(2)).await;
+        let later = insert("later", "grace_period", now + chrono::Duration::days(10)).await;
+        let healthy = insert("healthy", "active", now + chrono::Duration::days(1)).await;
+
+        let (users, total) = UserRepository::find_in_grace_period(
+            &pool,
+            now + chrono::Duration::days(3),
+            Page::FIRST,
+            PerPage::MAX,
+        )
+        .await
+        .unwrap();
+
+        sqlx::query("DELETE FROM users WHERE id = ANY($1)")
+            .bind(vec![soonest, soon, later, healthy])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let ours: Vec<Uuid> = users
+            .iter()
+            .map(|u| u.id)
+            .filter(|id| [soonest, soon, later, healthy].contains(id))
+            .collect();
+        assert_eq!(ours, vec![soonest, soon]);
+        assert!(total >= 2);
+    }
+
+    #[test]
+    fn escape_like_neutralises_wildcards() {
+        assert_eq!(escape_like("a_b%c\\d"), "a\\_b\\%c\\\\d");
+        assert_eq!(escape_like("plain"), "plain");
+    }
+
+    #[actix_rt::test]
+    async fn search_by_email_matches_substrings_only() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let tag = Uuid::new_v4().simple().to_string();
+        let emails = [
+            format!("alice.{}@example.com", tag),
+            format!("ALICIA.{}@example.com", tag),
+            format!("bob.{}@example.com", tag),
+            format!("bob_x{}@example.com", tag),
+        ];
+        for email in &emails {
+            sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'x')")
+                .bind(email)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let found = |users: Vec<User>| {
+            let mut found: Vec<String> = users.into_iter().map(|u| u.email).collect();
+            found.sort();
+            found
+        };
+
+        // Case-insensitive substring match
+        let (users, total) =
+            UserRepository::search_by_email(&pool, "ALI", None, Page::FIRST, PerPage::MAX)
+                .await
+                .unwrap();
+        let users: Vec<User> = users
+            .into_iter()
+            .filter(|u| u.email.contains(&tag))
+            .collect();
+        assert!(total >= 2);
+        assert_eq!(found(users), vec![emails[1].clone(), emails[0].clone()]);
+
+        let (users, total) = UserRepository::list_paginated(
+            &pool,
+            Page::FIRST,
+            PerPage::MAX,
+            Some(&format!("bob.{}", tag)),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(found(users), vec![emails[2].clone()]);
+
+        // `_` is literal, not a single-character wildcard
+        let (users, _) = UserRepository::search_by_email(
+            &pool,
+            &format!("_x{}", tag),
+            None,
+            Page::FIRST,
+            PerPage::MAX,
+        )
+        .await
+        .unwrap();
+        assert_eq!(found(users), vec![emails[3].clone()]);
+
+        sqlx::query("DELETE FROM users WHERE email = ANY($1)")
+            .bind(&emails[..])
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn transition_membership_status_rejects_illegal_moves() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (user_id,): (Uuid,) = sqlx::query_as(
+            "INSERT INTO users (email, password_hash, subscription_status, membership_canceled_at) VALUES ($1, 'x', 'canceled', NOW()) RETURNING id",
+        )
+        .bind(format!("transition-{}@example.com", Uuid::new_v4()))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let status = |pool: PgPool| async move {
+            UserRepository::find_by_id(&pool, user_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .membership_status
+        };
+        let transition = |pool: PgPool, created: Option<DateTime<Utc>>| async move {
+            UserRepository::transition_membership_status(
+                &pool,
+                user_id,
+                MembershipStatus::Active,
+                created,
+            )
+            .await
+            .unwrap()
+        };
+
+        // A late update, or a replayed creation, for the canceled
+        // subscription is ignored
+        assert!(!transition(pool.clone(), None).await);
+        let old = Utc::now() - chrono::Duration::days(30);
+        assert!(!transition(pool.clone(), Some(old)).await);
+        assert_eq!(status(pool.clone()).await, "canceled");
+
+        // A subscription created after the cancellation reactivates
+        let new = Utc::now() + chrono::Duration::minutes(1);
+        assert!(transition(pool.clone(), Some(new)).await);
+        assert_eq!(status(pool.clone()).await, "active");
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
     }
 }
@@ -0,0 +1,186 @@
+//! Processed webhook event repository
+
+use chrono::{Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::errors::AppError;
+
+/// First key of the two-key advisory lock taken per event id, keeping these
+/// locks apart from the single-key job locks in [`crate::jobs`]
+const EVENT_LOCK_NAMESPACE: i32 = 7_101;
+
+/// How long processed event ids are kept. Stripe stops retrying a delivery
+/// after three days, so older ids can no longer come back.
+pub const PROCESSED_EVENT_RETENTION_DAYS: i64 = 30;
+
+/// A delivery claimed by [`WebhookEventRepository::claim`]. Holds a
+/// transaction-scoped lock on the event id until it is completed or dropped.
+pub struct WebhookEventClaim {
+    tx: Transaction<'static, Postgres>,
+    event_id: String,
+}
+
+pub struct WebhookEventRepository;
+
+impl WebhookEventRepository {
+    /// Claim `event_id` for processing. Waits while another delivery of the
+    /// same event is being processed, then returns `None` if that delivery
+    /// (or an earlier one) recorded it as processed.
+    pub async fn claim(
+        pool: &PgPool,
+        event_id: &str,
+    ) -> Result<Option<WebhookEventClaim>, AppError> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+            .bind(EVENT_LOCK_NAMESPACE)
+            .bind(event_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let (processed,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM processed_webhook_events WHERE event_id = $1)",
+        )
+        .bind(event_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if processed {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        Ok(Some(WebhookEventClaim {
+            tx,
+            event_id: event_id.to_string(),
+        }))
+    }
+
+    /// Record a claimed event as processed and release its lock. Call only
+    /// once the handlers have succeeded: dropping the claim instead (or the
+    /// process dying) records nothing, so Stripe's retry processes it again.
+    pub async fn complete(claim: WebhookEventClaim, event_type: &str) -> Result<(), AppError> {
+        let WebhookEventClaim { mut tx, event_id } = claim;
+        sqlx::query(
+            r#"
+            INSERT INTO processed_webhook_events (event_id, event_type)
+            VALUES ($1, $2)
+            ON CONFLICT (event_id) DO NOTHING
+            "#,
+        )
+        .bind(&event_id)
+        .bind(event_type)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Delete event ids processed more than
+    /// [`PROCESSED_EVENT_RETENTION_DAYS`] ago
+    pub async fn cleanup_expired(pool: &PgPool) -> Result<u64, AppError> {
+        let cutoff = Utc::now() - Duration::days(PROCESSED_EVENT_RETENTION_DAYS);
+        let result = sqlx::query("DELETE FROM processed_webhook_events WHERE processed_at < $1")
+            .bind(cutoff)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    async fn delete_event(pool: &PgPool, event_id: &str) {
+        sqlx::query("DELETE FROM processed_webhook_events WHERE event_id = $1")
+            .bind(event_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn completed_delivery_is_not_claimed_again() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let event_id = format!("evt_{}", uuid::Uuid::new_v4().simple());
+
+        let claim = WebhookEventRepository::claim(&pool, &event_id)
+            .await
+            .unwrap()
+            .unwrap();
+        WebhookEventRepository::complete(claim, "invoice.paid")
+            .await
+            .unwrap();
+        let again = WebhookEventRepository::claim(&pool, &event_id)
+            .await
+            .unwrap();
+
+        delete_event(&pool, &event_id).await;
+        assert!(again.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn dropped_claim_leaves_the_event_unprocessed() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let event_id = format!("evt_{}", uuid::Uuid::new_v4().simple());
+
+        let claim = WebhookEventRepository::claim(&pool, &event_id)
+            .await
+            .unwrap();
+        assert!(claim.is_some());
+        drop(claim);
+
+        let retry = WebhookEventRepository::claim(&pool, &event_id)
+            .await
+            .unwrap();
+        assert!(retry.is_some());
+        drop(retry);
+
+        let (recorded,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM processed_webhook_events WHERE event_id = $1")
+                .bind(&event_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(recorded, 0);
+    }
+
+    #[actix_rt::test]
+    async fn concurrent_delivery_waits_for_the_first_to_finish() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let event_id = format!("evt_{}", uuid::Uuid::new_v4().simple());
+
+        let first = WebhookEventRepository::claim(&pool, &event_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let second = tokio::spawn({
+            let pool = pool.clone();
+            let event_id = event_id.clone();
+            async move { WebhookEventRepository::claim(&pool, &event_id).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!second.is_finished());
+
+        WebhookEventRepository::complete(first, "invoice.paid")
+            .await
+            .unwrap();
+        let second = second.await.unwrap().unwrap();
+
+        delete_event(&pool, &event_id).await;
+        assert!(second.is_none());
+    }
+}
@@ -1,12 +1,64 @@
 //! Standardized API response types
 //!
 //! This module provides consistent response formatting across all API endpoints.
+//!
+//! Every envelope reports the [`ApiVersion`] it was rendered for in
+//! `meta.api_version`. Handlers build the same `ApiResponse` whatever the
+//! version; when a v2 envelope is introduced, it will be rendered from that
+//! shared representation rather than by handlers directly.
 
 use actix_web::{HttpMessage, HttpRequest, HttpResponse};
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
 use crate::middleware::request_id::RequestId;
+use crate::pagination::{Page, PerPage};
+
+/// Version header clients can send to pick an envelope version
+pub const ACCEPT_VERSION_HEADER: &str = "Accept-Version";
+
+/// Response envelope version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ApiVersion {
+    #[serde(rename = "v1")]
+    V1,
+}
+
+impl ApiVersion {
+    /// Version used when the client asks for none, or for one we do not serve
+    pub const DEFAULT: Self = ApiVersion::V1;
+
+    /// Parse `v1` or `1`; `None` for versions this server does not serve
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().trim_start_matches(['v', 'V']) {
+            "1" => Some(ApiVersion::V1),
+            _ => None,
+        }
+    }
+
+    /// Pick the envelope version for `req`.
+    ///
+    /// An `Accept-Version` header wins over the `/vN/` path prefix. Unknown
+    /// versions fall back to [`ApiVersion::DEFAULT`] instead of failing.
+    pub fn negotiate(req: &HttpRequest) -> Self {
+        let from_header = req
+            .headers()
+            .get(ACCEPT_VERSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(Self::parse);
+        let from_path = || {
+            req.path()
+                .trim_start_matches('/')
+                .split('/')
+                .next()
+                .and_then(Self::parse)
+        };
+
+        from_header
+            .unwrap_or_else(from_path)
+            .unwrap_or(Self::DEFAULT)
+    }
+}
 
 /// Generic API response wrapper
 #[derive(Debug, Serialize)]
@@ -22,14 +74,16 @@ pub struct ApiResponse<T: Serialize> {
 pub struct ResponseMeta {
     pub request_id: String,
     pub timestamp: DateTime<Utc>,
+    pub api_version: ApiVersion,
 }
 
 impl ResponseMeta {
-    /// Create new response metadata
+    /// Create new response metadata for the default API version
     pub fn new(request_id: String) -> Self {
         Self {
             request_id,
             timestamp: Utc::now(),
+            api_version: ApiVersion::DEFAULT,
         }
     }
 
@@ -41,7 +95,10 @@ impl ResponseMeta {
             .map(|id| id.0.clone())
             .unwrap_or_else(|| RequestId::new().0);
 
-        Self::new(request_id)
+        Self {
+            api_version: ApiVersion::negotiate(req),
+            ..Self::new(request_id)
+        }
     }
 }
 
@@ -100,11 +157,11 @@ pub fn created<T: Serialize>(data: T, request_id: String) -> HttpResponse {
 pub fn paginated<T: Serialize>(
     items: Vec<T>,
     total: i64,
-    page: i32,
-    per_page: i32,
+    page: Page,
+    per_page: PerPage,
     request_id: String,
 ) -> HttpResponse {
-    let paginated = PaginatedResponse::new(items, total, page, per_page);
+    let paginated = PaginatedResponse::new(items, total, page.get(), per_page.get());
     HttpResponse::Ok().json(ApiResponse {
         success: true,
         data: Some(paginated),
@@ -170,6 +227,50 @@ mod tests {
         assert_eq!(paginated.total_pages, 3); // 25 / 10 = 2.5, ceil = 3
     }
 
+    #[test]
+    fn test_api_version_appears_in_meta() {
+        let meta = ResponseMeta::new("req_123".to_string());
+        let json = serde_json::to_value(&meta).unwrap();
+        assert_eq!(json["api_version"], "v1");
+    }
+
+    #[test]
+    fn test_api_version_negotiation() {
+        use actix_web::test::TestRequest;
+
+        let negotiate = |req: TestRequest| ApiVersion::negotiate(&req.to_http_request());
+
+        assert_eq!(
+            negotiate(TestRequest::get().uri("/v1/users/me")),
+            ApiVersion::V1
+        );
+        assert_eq!(
+            negotiate(
+                TestRequest::get()
+                    .uri("/v1/users/me")
+                    .insert_header((ACCEPT_VERSION_HEADER, "1"))
+            ),
+            ApiVersion::V1
+        );
+        // Unknown versions fall back to the default rather than failing
+        assert_eq!(
+            negotiate(
+                TestRequest::get()
+                    .uri("/v1/users/me")
+                    .insert_header((ACCEPT_VERSION_HEADER, "v9"))
+            ),
+            ApiVersion::DEFAULT
+        );
+        assert_eq!(
+            negotiate(TestRequest::get().uri("/v7/users/me")),
+            ApiVersion::DEFAULT
+        );
+        assert_eq!(
+            negotiate(TestRequest::get().uri("/health")),
+            ApiVersion::DEFAULT
+        );
+    }
+
     #[test]
     fn test_response_meta_timestamp() {
         let before = Utc::now();
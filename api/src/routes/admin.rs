@@ -10,6 +10,10 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         web::scope("/admin")
             // Dashboard stats
             .route("/stats", web::get().to(handlers::get_dashboard_stats))
+            .route(
+                "/stats/timeseries",
+                web::get().to(handlers::get_stats_timeseries),
+            )
             // System health
             .route("/health", web::get().to(handlers::get_system_health))
             .route("/key-health", web::get().to(handlers::get_key_health))
@@ -19,8 +23,21 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             )
             // User management
             .route("/users", web::get().to(handlers::list_users))
+            .route("/users/merge", web::post().to(handlers::merge_users))
+            .route(
+                "/users/at-risk",
+                web::get().to(handlers::list_users_at_risk),
+            )
             .route("/users/{user_id}", web::get().to(handlers::get_user))
             .route("/users/{user_id}", web::delete().to(handlers::delete_user))
+            .route(
+                "/users/{user_id}/ip-activity",
+                web::get().to(handlers::get_user_ip_activity),
+            )
+            .route(
+                "/users/{user_id}/reconcile-stripe",
+                web::post().to(handlers::reconcile_stripe),
+            )
             .route(
                 "/users/{user_id}/status",
                 web::put().to(handlers::update_user_status),
@@ -51,6 +68,10 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                 "/memberships/revoke",
                 web::post().to(handlers::revoke_membership),
             )
+            .route(
+                "/subscriptions/grant/bulk",
+                web::post().to(handlers::bulk_grant_memberships),
+            )
             // Application management
             .route(
                 "/applications",
@@ -82,6 +103,9 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             )
             // Audit logs
             .route("/audit-logs", web::get().to(handlers::list_audit_logs))
+            // IP bans
+            .route("/ip-bans", web::get().to(handlers::list_ip_bans))
+            .route("/ip-bans/{ip}", web::delete().to(handlers::delete_ip_ban))
             // Feedback
             .route("/feedback", web::get().to(handlers::list_feedback))
             .route("/feedback/export", web::get().to(handlers::export_feedback))
@@ -180,6 +204,10 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                 "/notifications/{notification_id}/read",
                 web::post().to(handlers::mark_notification_read),
             )
+            .route(
+                "/notifications/read",
+                web::post().to(handlers::mark_notifications_read),
+            )
             .route(
                 "/notifications/read-all",
                 web::post().to(handlers::mark_all_notifications_read),
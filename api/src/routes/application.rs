@@ -9,6 +9,7 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/applications")
             .route("", web::get().to(handlers::list_applications))
+            .route("/status", web::get().to(handlers::list_application_status))
             .route("/{slug}", web::get().to(handlers::get_application))
             .route(
                 "/{slug}/downloads",
@@ -15,10 +15,18 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/logout-all", web::post().to(handlers::logout_all))
             .route("/refresh", web::post().to(handlers::refresh_token))
             .route("/magic-link", web::post().to(handlers::request_magic_link))
+            .route(
+                "/email-available",
+                web::post().to(handlers::email_available),
+            )
             .route(
                 "/magic-link/verify",
                 web::post().to(handlers::verify_magic_link),
             )
+            .route(
+                "/magic-link/verify",
+                web::get().to(handlers::verify_magic_link_redirect),
+            )
             .route(
                 "/password-reset",
                 web::post().to(handlers::request_password_reset),
@@ -31,6 +39,11 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                 "/password-reset/confirm",
                 web::post().to(handlers::confirm_password_reset),
             )
+            .route("/set-password", web::post().to(handlers::set_password))
+            .route(
+                "/set-password/request",
+                web::post().to(handlers::request_set_password),
+            )
             .route("/2fa/setup", web::post().to(handlers::setup_2fa))
             .route("/2fa/confirm", web::post().to(handlers::confirm_2fa))
             .route("/2fa/verify", web::post().to(handlers::verify_2fa))
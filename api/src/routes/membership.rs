@@ -20,7 +20,19 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                 "/reactivate",
                 web::post().to(handlers::reactivate_membership),
             )
+            .route(
+                "/change-tier/preview",
+                web::post().to(handlers::preview_tier_change),
+            )
             .route("/billing-portal", web::post().to(handlers::billing_portal))
-            .route("/payments", web::get().to(handlers::get_payment_history)),
+            .route(
+                "/payment-method",
+                web::get().to(handlers::get_payment_method),
+            )
+            .route("/payments", web::get().to(handlers::get_payment_history))
+            .route(
+                "/payments/{payment_id}/invoice",
+                web::get().to(handlers::download_payment_invoice),
+            ),
     );
 }
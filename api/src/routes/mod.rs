@@ -12,13 +12,16 @@ pub mod health;
 pub mod membership;
 pub mod oci;
 pub mod oidc;
+pub mod rate_limit;
 pub mod user;
 pub mod webhook;
 
 use actix_web::web;
 
+use crate::config::Config;
+
 /// Configure all application routes
-pub fn configure(cfg: &mut web::ServiceConfig) {
+pub fn configure(cfg: &mut web::ServiceConfig, config: &Config) {
     // V1 API routes
     cfg.service(
         web::scope("/v1")
@@ -30,13 +33,15 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .configure(billing::configure)
             .configure(feedback::configure)
             .configure(membership::configure)
-            .configure(webhook::configure)
+            .configure(rate_limit::configure)
+            .configure(|cfg| webhook::configure(cfg, &config.stripe_webhook_path))
             .configure(admin::configure),
     );
 
     // Root-level endpoints
     cfg.service(health::root_status);
     cfg.service(health::health_check);
+    cfg.service(health::readiness_check);
 
     // OIDC / OAuth 2.1 endpoints (root-level, outside /v1)
     oidc::configure_well_known(cfg);
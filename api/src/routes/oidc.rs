@@ -6,6 +6,7 @@
 use actix_web::web;
 
 use crate::handlers::oidc::{authorize, discovery, jwks, logout, revoke, token, userinfo};
+use crate::middleware::IssuesCredentials;
 
 /// Configure OIDC well-known discovery and JWKS endpoints.
 /// These are registered at the root level (not under `/v1`).
@@ -21,7 +22,12 @@ pub fn configure_well_known(cfg: &mut web::ServiceConfig) {
 pub fn configure_oauth2(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/oauth2")
-            .route("/authorize", web::get().to(authorize))
+            // Mints authorization codes, so no expired-token read grace
+            .service(
+                web::resource("/authorize")
+                    .app_data(IssuesCredentials)
+                    .route(web::get().to(authorize)),
+            )
             .route("/token", web::post().to(token))
             .route("/userinfo", web::get().to(userinfo))
             .route("/revoke", web::post().to(revoke))
@@ -0,0 +1,11 @@
+//! Rate limit routes
+
+use actix_web::web;
+
+use crate::handlers;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/rate-limit").route("/status", web::get().to(handlers::rate_limit_status)),
+    );
+}
@@ -23,7 +23,20 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                 "/me/email/verify/confirm",
                 web::post().to(handlers::confirm_email_verification),
             )
+            .route(
+                "/me/applications",
+                web::get().to(handlers::list_my_applications),
+            )
             .route("/me/sessions", web::get().to(handlers::list_sessions))
+            .route("/me/export", web::post().to(handlers::request_data_export))
+            .route(
+                "/me/export/status/{job_id}",
+                web::get().to(handlers::get_data_export_status),
+            )
+            .route(
+                "/me/export/download/{job_id}",
+                web::get().to(handlers::download_data_export),
+            )
             .route("/me", web::delete().to(handlers::delete_account))
             .route(
                 "/me/sessions/{session_id}",
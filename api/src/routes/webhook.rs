@@ -4,7 +4,93 @@ use actix_web::web;
 
 use crate::handlers;
 
-/// Configure webhook routes
-pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/webhooks").route("/stripe", web::post().to(handlers::stripe_webhook)));
+/// Configure webhook routes. The Stripe endpoint lives at
+/// `/webhooks/{stripe_path}` (STRIPE_WEBHOOK_PATH, default `stripe`).
+pub fn configure(cfg: &mut web::ServiceConfig, stripe_path: &str) {
+    cfg.service(web::scope("/webhooks").route(
+        &format!("/{}", stripe_path),
+        web::post().to(handlers::stripe_webhook),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use sqlx::PgPool;
+    use std::sync::{Arc, RwLock};
+
+    use crate::config::{AuditConfig, TierConfig};
+    use crate::services::{EmailService, StripeConfig, StripeService};
+
+    fn stripe_config() -> StripeConfig {
+        StripeConfig::for_tests("http://127.0.0.1:1")
+    }
+
+    fn signature(payload: &str) -> String {
+        let timestamp = chrono::Utc::now().timestamp();
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(stripe_config().webhook_secret.as_bytes()).unwrap();
+        mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+        format!(
+            "t={},v1={}",
+            timestamp,
+            hex::encode(mac.finalize().into_bytes())
+        )
+    }
+
+    /// POST `payload` to `uri` on an app whose Stripe webhook is mounted at
+    /// `stripe_path`, with the app data the real handler extracts
+    async fn post(stripe_path: &str, uri: &str, payload: &str, signed: bool) -> u16 {
+        let path = stripe_path.to_string();
+        let pool = PgPool::connect_lazy("postgres://127.0.0.1:1/unused").unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .app_data(web::Data::new(AuditConfig::default()))
+                .app_data(web::Data::new(Arc::new(
+                    StripeService::new(stripe_config()),
+                )))
+                .app_data(web::Data::new(Arc::new(EmailService::new_dev())))
+                .app_data(web::Data::new(Arc::new(
+                    RwLock::new(TierConfig::from_env()),
+                )))
+                .service(web::scope("/v1").configure(|cfg| configure(cfg, &path))),
+        )
+        .await;
+        let mut req = test::TestRequest::post()
+            .uri(uri)
+            .set_payload(payload.to_string());
+        if signed {
+            req = req.insert_header(("Stripe-Signature", signature(payload)));
+        }
+        test::call_service(&app, req.to_request())
+            .await
+            .status()
+            .as_u16()
+    }
+
+    #[actix_rt::test]
+    async fn configured_path_routes_to_the_handler() {
+        // A signed body that is not JSON is rejected by the handler itself,
+        // after it has checked the signature and before it touches the database
+        assert_eq!(
+            post("hook-7f3a", "/v1/webhooks/hook-7f3a", "not json", true).await,
+            400
+        );
+        assert_eq!(
+            post("hook-7f3a", "/v1/webhooks/hook-7f3a", "not json", false).await,
+            401
+        );
+        assert_eq!(
+            post("hook-7f3a", "/v1/webhooks/stripe", "not json", true).await,
+            404
+        );
+        assert_eq!(
+            post("stripe", "/v1/webhooks/stripe", "not json", true).await,
+            400
+        );
+    }
 }
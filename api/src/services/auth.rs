@@ -1,6 +1,6 @@
 //! Authentication service
 
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use ipnetwork::IpNetwork;
 use rand::RngCore;
 use sqlx::PgPool;
@@ -9,17 +9,23 @@ use uuid::Uuid;
 
 use std::sync::{Arc, RwLock};
 
-use crate::config::TierConfig;
+use crate::config::{AuditConfig, ImpossibleTravelConfig, SignupPolicyConfig, TierConfig};
 use crate::errors::AppError;
 use crate::models::{
-    AuditAction, CreateAdminInvite, CreateAuditLog, CreateEmailChangeRequest,
+    AuditAction, AuditSeverity, CreateAdminInvite, CreateAuditLog, CreateEmailChangeRequest,
     CreateEmailVerificationToken, CreateMagicLinkToken, CreatePasswordResetToken,
-    CreateRefreshToken, CreateUser, SubscriptionTier, User, UserResponse, UserRole,
+    CreateRefreshToken, CreateUser, RefreshToken, SubscriptionTier, User, UserResponse, UserRole,
 };
+use crate::repositories::ip_activity::MAX_TRACKED_IPS_PER_USER;
 use crate::repositories::{
-    AuditLogRepository, InviteRepository, TokenRepository, TotpRepository, UserRepository,
+    AuditLogRepository, InviteRepository, TokenRepository, TotpRepository,
+    UserIpActivityRepository, UserRepository,
 };
-use crate::services::{JwtService, PasswordService};
+use crate::services::geo::{assess_travel, GeoResolver};
+use crate::services::{EmailService, JwtService, PasswordService, RefreshTokenClaims};
+
+/// Default window in which a just-rotated refresh token is still accepted
+pub const DEFAULT_REFRESH_REUSE_GRACE_SECS: i64 = 10;
 
 /// Authentication tokens returned after login
 #[derive(Debug, Clone)]
@@ -27,6 +33,9 @@ pub struct AuthTokens {
     pub access_token: String,
     pub refresh_token: String,
     pub expires_in: i64,
+    /// Whether the refresh token was issued for a "remember me" session;
+    /// sizes the refresh cookie to match the stored token's lifetime
+    pub remember: bool,
 }
 
 /// Result of a login attempt — either full success or 2FA challenge
@@ -56,6 +65,99 @@ pub struct AuthService {
     jwt: JwtService,
     password: PasswordService,
     tier_config: Arc<RwLock<TierConfig>>,
+    /// Maximum active sessions per user; 0 disables the cap
+    max_sessions_per_user: u32,
+    /// How long a just-rotated refresh token is still accepted from a
+    /// concurrent refresh
+    refresh_reuse_grace: Duration,
+    /// Sends the one-time welcome email on first login when set
+    welcome_email: Option<Arc<EmailService>>,
+    /// Impossible-travel check on password login when set
+    travel_check: Option<TravelCheck>,
+    /// A password login with a device id replaces that device's session
+    unique_session_per_device: bool,
+    /// Audit log policy applied to every entry this service writes
+    audit: AuditConfig,
+    /// Reserved addresses and denied domains for magic-link signups
+    signup_policy: SignupPolicyConfig,
+}
+
+/// Wiring for the impossible-travel login check
+#[derive(Clone)]
+struct TravelCheck {
+    config: ImpossibleTravelConfig,
+    resolver: Arc<dyn GeoResolver>,
+    email: Arc<EmailService>,
+}
+
+impl TravelCheck {
+    /// Locate both addresses and compare them. When the implied speed is
+    /// implausible, writes a warning audit entry, emails the user if
+    /// configured, and returns true.
+    async fn assess(
+        self,
+        pool: PgPool,
+        audit: AuditConfig,
+        user: User,
+        ip: IpAddr,
+        (previous_ip, previous_seen_at): (IpAddr, DateTime<Utc>),
+    ) -> bool {
+        let (from, to) = futures_util::join!(
+            self.resolver.resolve(previous_ip),
+            self.resolver.resolve(ip)
+        );
+        let (Some(from), Some(to)) = (from, to) else {
+            return false;
+        };
+
+        let now = Utc::now();
+        let assessment = assess_travel(
+            (from, previous_seen_at),
+            (to, now),
+            self.config.max_speed_kmh,
+            self.config.min_distance_km,
+        );
+        if !assessment.impossible {
+            return false;
+        }
+
+        tracing::warn!(
+            user_id = %user.id,
+            distance_km = assessment.distance_km.round(),
+            speed_kmh = assessment.speed_kmh.round(),
+            "Impossible travel detected on login"
+        );
+        let log = CreateAuditLog::new(AuditAction::UserLoginImpossibleTravel)
+            .with_actor(user.id, &user.email, &user.role)
+            .with_ip(Some(IpNetwork::from(ip)))
+            .with_severity(AuditSeverity::Warning)
+            .with_metadata(serde_json::json!({
+                "previous_ip": previous_ip.to_string(),
+                "previous_seen_at": previous_seen_at,
+                "elapsed_secs": (now - previous_seen_at).num_seconds(),
+                "distance_km": assessment.distance_km.round(),
+                // Infinite for simultaneous logins, which serializes as null
+                "speed_kmh": assessment.speed_kmh.round(),
+            }));
+        if let Err(e) = AuditLogRepository::create(&pool, &audit, log).await {
+            tracing::error!(error = %e, "Failed to create audit log for impossible travel");
+        }
+
+        if self.config.notify_user {
+            let email_service = self.email.clone();
+            let distance_km = assessment.distance_km.round() as i64;
+            tokio::spawn(async move {
+                if let Err(e) = email_service
+                    .send_unusual_login_alert(&user.email, &ip.to_string(), distance_km)
+                    .await
+                {
+                    tracing::warn!(error = %e, "Failed to send unusual login alert");
+                }
+            });
+        }
+
+        true
+    }
 }
 
 impl AuthService {
@@ -65,9 +167,98 @@ impl AuthService {
             jwt,
             password: PasswordService::new(),
             tier_config,
+            max_sessions_per_user: 0,
+            refresh_reuse_grace: Duration::seconds(DEFAULT_REFRESH_REUSE_GRACE_SECS),
+            welcome_email: None,
+            travel_check: None,
+            unique_session_per_device: false,
+            audit: AuditConfig::default(),
+            signup_policy: SignupPolicyConfig::default(),
         }
     }
 
+    /// Cap the number of active sessions per user. When a new session would
+    /// exceed the cap, the oldest sessions are revoked. 0 disables the cap.
+    pub fn with_max_sessions_per_user(mut self, max_sessions: u32) -> Self {
+        self.max_sessions_per_user = max_sessions;
+        self
+    }
+
+    /// Accept a refresh token rotated less than `secs` ago when its successor
+    /// is still active, so two tabs refreshing at once both succeed. 0 disables.
+    pub fn with_refresh_reuse_grace(mut self, secs: u64) -> Self {
+        self.refresh_reuse_grace = Duration::seconds(secs as i64);
+        self
+    }
+
+    /// Revoke a user's existing session on a device when they log in on it
+    /// again with the same `X-Device-Id`, for kiosks and shared devices.
+    pub fn with_unique_session_per_device(mut self, enabled: bool) -> Self {
+        self.unique_session_per_device = enabled;
+        self
+    }
+
+    /// Hash and verify passwords with `password` (pepper and Argon2 costs).
+    pub fn with_password_service(mut self, password: PasswordService) -> Self {
+        self.password = password;
+        self
+    }
+
+    /// Apply the configured audit log policy to this service's entries.
+    pub fn with_audit(mut self, audit: AuditConfig) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Apply the signup email policy to accounts created by magic link.
+    pub fn with_signup_policy(mut self, policy: SignupPolicyConfig) -> Self {
+        self.signup_policy = policy;
+        self
+    }
+
+    /// Send a welcome email after a user's first successful login.
+    pub fn with_welcome_email(mut self, email_service: Arc<EmailService>) -> Self {
+        self.welcome_email = Some(email_service);
+        self
+    }
+
+    /// Compare each password login's location with the previous login's and
+    /// flag implausible travel. Disabled unless `config.enabled`.
+    pub fn with_impossible_travel(
+        mut self,
+        config: ImpossibleTravelConfig,
+        resolver: Arc<dyn GeoResolver>,
+        email_service: Arc<EmailService>,
+    ) -> Self {
+        if config.enabled {
+            self.travel_check = Some(TravelCheck {
+                config,
+                resolver,
+                email: email_service,
+            });
+        }
+        self
+    }
+
+    /// Stamp `last_login_at` and, on the first login, fire the welcome email.
+    ///
+    /// The send is spawned and its failure only logged, so it never affects
+    /// the login. Returns whether the welcome email was dispatched.
+    async fn record_login(&self, user_id: Uuid, email: &str) -> Result<bool, AppError> {
+        let first_login = UserRepository::update_last_login(&self.pool, user_id).await?;
+        let Some(email_service) = self.welcome_email.clone().filter(|_| first_login) else {
+            return Ok(false);
+        };
+
+        let email = email.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = email_service.send_first_login_welcome(&email).await {
+                tracing::warn!(user_id = %user_id, error = %e, "Failed to send welcome email");
+            }
+        });
+        Ok(true)
+    }
+
     /// Hot-reload the tier configuration (e.g. after admin update).
     pub fn reload_tier_config(&self, config: TierConfig) {
         let mut tc = self.tier_config.write().expect("TierConfig lock poisoned");
@@ -112,6 +303,7 @@ impl AuthService {
         let ip = ip_address.map(|ip| IpNetwork::from(ip));
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::UserRegistered)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip)
@@ -122,33 +314,56 @@ impl AuthService {
         Ok(UserResponse::from(user))
     }
 
-    /// Login with email and password
+    /// Login with email and password. `remember` picks the session lifetime
+    /// (see `RefreshToken::lifetime`).
     pub async fn login(
         &self,
         email: String,
         password: String,
         device_info: Option<String>,
+        device_id: Option<String>,
         ip_address: Option<IpAddr>,
+        remember: bool,
     ) -> Result<LoginResult, AppError> {
-        // Find user
-        let user = UserRepository::find_by_email(&self.pool, &email)
-            .await?
-            .ok_or(AppError::InvalidCredentials)?;
-
-        // Check if user is deleted
-        if user.is_deleted() {
-            return Err(AppError::InvalidCredentials);
-        }
+        // Find user. Every failure below returns the same InvalidCredentials
+        // error; only the audit entry records which check failed. Failures
+        // without a real hash to check still run a dummy Argon2 verify, so
+        // response time does not reveal whether the email is registered.
+        let user = match UserRepository::find_by_email(&self.pool, &email).await? {
+            Some(user) if !user.is_deleted() => user,
+            Some(user) => {
+                self.password.verify_dummy(&password);
+                self.audit_failed_login(&email, Some(user.id), ip_address, "account_deleted")
+                    .await;
+                return Err(AppError::InvalidCredentials);
+            }
+            None => {
+                self.password.verify_dummy(&password);
+                self.audit_failed_login(&email, None, ip_address, "unknown_email")
+                    .await;
+                return Err(AppError::InvalidCredentials);
+            }
+        };
 
         // Verify password
-        let password_hash = user
-            .password_hash
-            .as_ref()
-            .ok_or(AppError::InvalidCredentials)?;
+        let Some(password_hash) = user.password_hash.as_ref() else {
+            self.password.verify_dummy(&password);
+            self.audit_failed_login(&email, Some(user.id), ip_address, "no_password")
+                .await;
+            return Err(AppError::InvalidCredentials);
+        };
 
         if !self.password.verify(&password, password_hash)? {
+            self.audit_failed_login(&email, Some(user.id), ip_address, "wrong_password")
+                .await;
             return Err(AppError::InvalidCredentials);
         }
+        if self.password.needs_rehash(password_hash) {
+            self.rehash_password(user.id, &password, password_hash)
+                .await;
+        }
+
+        let travel_flagged = self.check_impossible_travel(&user, ip_address).await;
 
         // Check if 2FA is enabled AND actually configured
         if user.two_factor_enabled {
@@ -156,7 +371,7 @@ impl AuthService {
             let has_verified_totp = totp_record.map(|r| r.verified).unwrap_or(false);
 
             if has_verified_totp {
-                let challenge_token = self.jwt.create_2fa_challenge_token(user.id)?;
+                let challenge_token = self.jwt.create_2fa_challenge_token(user.id, remember)?;
                 return Ok(LoginResult::TwoFactorRequired { challenge_token });
             }
 
@@ -165,18 +380,35 @@ impl AuthService {
             UserRepository::set_two_factor_enabled(&self.pool, user.id, false).await?;
         }
 
+        // A flagged login without 2FA to step up to must prove mailbox access
+        // instead; magic-link sign-in skips this check.
+        if travel_flagged
+            && self
+                .travel_check
+                .as_ref()
+                .is_some_and(|c| c.config.require_step_up)
+        {
+            return Err(AppError::bad_request(
+                "Sign-in from an unusual location. Use a magic link sent to your email to continue.",
+            ));
+        }
+
         // Create tokens
+        self.replace_device_session(user.id, device_id.as_deref())
+            .await?;
         let tokens = self
-            .create_tokens(&user, device_info.clone(), ip_address)
+            .create_tokens(&user, device_info.clone(), device_id, ip_address, remember)
             .await?;
 
         // Update last login
-        UserRepository::update_last_login(&self.pool, user.id).await?;
+        self.record_login(user.id, &user.email).await?;
+        self.record_ip_activity(user.id, ip_address).await;
 
         // Create audit log
         let ip = ip_address.map(|ip| IpNetwork::from(ip));
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::UserLogin)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip)
@@ -187,6 +419,101 @@ impl AuthService {
         Ok(LoginResult::Success(tokens, UserResponse::from(user)))
     }
 
+    /// Replace `verified_hash`, the hash `password` was just checked against,
+    /// with one using the current Argon2 parameters. Skipped if the password
+    /// was changed or reset in the meantime, so the old password is never
+    /// written back. Failures are logged; the old hash keeps working.
+    async fn rehash_password(&self, user_id: Uuid, password: &str, verified_hash: &str) {
+        let result = match self.password.hash(password) {
+            Ok(hash) => {
+                UserRepository::rehash_password_if_unchanged(
+                    &self.pool,
+                    user_id,
+                    verified_hash,
+                    &hash,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(true) => {
+                tracing::info!(user_id = %user_id, "Rehashed password with current Argon2 parameters")
+            }
+            Ok(false) => {
+                tracing::debug!(user_id = %user_id, "Password changed since login; skipping rehash")
+            }
+            Err(e) => tracing::warn!(error = %e, user_id = %user_id, "Failed to rehash password"),
+        }
+    }
+
+    /// Compare this login's location with where the user was last seen.
+    ///
+    /// The previous address comes from IP activity, which the audit policy
+    /// cannot suppress or mask. When a flag would block the login
+    /// (`require_step_up`) the lookups run inline, bounded by the resolver's
+    /// short timeout; otherwise they are spawned and this returns false
+    /// straight away. Lookup failures are logged and treated as "not flagged".
+    async fn check_impossible_travel(&self, user: &User, ip_address: Option<IpAddr>) -> bool {
+        let (Some(check), Some(ip)) = (self.travel_check.as_ref(), ip_address) else {
+            return false;
+        };
+
+        let previous = match UserIpActivityRepository::find_latest(&self.pool, user.id).await {
+            Ok(Some(activity)) => activity,
+            Ok(None) => return false,
+            Err(e) => {
+                tracing::warn!(user_id = %user.id, error = %e, "Impossible travel check skipped");
+                return false;
+            }
+        };
+        // A masked address is stored as its network, which covers `ip` when
+        // the user has not moved
+        if previous.ip_address.contains(ip) {
+            return false;
+        }
+        let previous = (previous.ip_address.ip(), previous.last_seen);
+
+        let assess = check.clone().assess(
+            self.pool.clone(),
+            self.audit.clone(),
+            user.clone(),
+            ip,
+            previous,
+        );
+        if check.config.require_step_up {
+            assess.await
+        } else {
+            tokio::spawn(assess);
+            false
+        }
+    }
+
+    /// Write a warning-severity audit entry for a failed password login.
+    /// Failures to write are logged and never change the login response.
+    async fn audit_failed_login(
+        &self,
+        email: &str,
+        user_id: Option<Uuid>,
+        ip_address: Option<IpAddr>,
+        reason: &str,
+    ) {
+        let mut log = CreateAuditLog::new(AuditAction::UserLoginFailed)
+            .with_ip(ip_address.map(IpNetwork::from))
+            .with_severity(AuditSeverity::Warning)
+            .with_metadata(failed_login_metadata(
+                email,
+                reason,
+                self.audit.mask_key.as_bytes(),
+            ));
+        if let Some(user_id) = user_id {
+            log = log.with_resource("user", user_id);
+        }
+        if let Err(e) = AuditLogRepository::create(&self.pool, &self.audit, log).await {
+            tracing::error!(error = %e, "Failed to create audit log for failed login");
+        }
+    }
+
     /// Refresh tokens
     pub async fn refresh_tokens(
         &self,
@@ -209,49 +536,145 @@ impl AuthService {
         // Hash token to find in database
         let token_hash = self.jwt.hash_token(&refresh_token);
 
-        // Find token in database
-        let stored_token =
-            match TokenRepository::find_refresh_token_by_hash(&self.pool, &token_hash).await? {
-                Some(token) => token,
-                None => {
-                    // Diagnostic: check if the token exists at all (revoked/expired)
-                    match TokenRepository::find_refresh_token_by_hash_any(&self.pool, &token_hash)
-                        .await
-                    {
-                        Ok(Some(stale)) => {
-                            tracing::warn!(
-                                user_id = %claims.sub,
-                                token_id = %claims.jti,
-                                hash_prefix = %&token_hash[..8],
-                                revoked_at = ?stale.revoked_at,
-                                expires_at = %stale.expires_at,
-                                created_at = %stale.created_at,
-                                "token_refresh: token exists in DB but is revoked or expired"
-                            );
-                        }
-                        Ok(None) => {
-                            tracing::warn!(
-                                user_id = %claims.sub,
-                                token_id = %claims.jti,
-                                hash_prefix = %&token_hash[..8],
-                                "token_refresh: token hash does not exist in DB at all"
-                            );
-                        }
-                        Err(e) => {
-                            tracing::warn!(
-                                user_id = %claims.sub,
-                                token_id = %claims.jti,
-                                error = %e,
-                                "token_refresh: diagnostic query failed"
-                            );
-                        }
-                    }
+        // Claim the token for rotation. Its row stays locked until commit, so a
+        // concurrent refresh with the same token waits for us and then finds
+        // it rotated, with the successor recorded.
+        let mut tx = self.pool.begin().await?;
+        let Some(stored_token) =
+            TokenRepository::claim_refresh_token_for_rotation(&mut *tx, &token_hash).await?
+        else {
+            tx.rollback().await?;
+            return self
+                .refresh_rotated_token(&claims, &token_hash, device_info, ip_address)
+                .await;
+        };
+
+        // Get user
+        let user = UserRepository::find_by_id(&self.pool, claims.sub)
+            .await?
+            .ok_or(AppError::InvalidCredentials)?;
+
+        // Create new tokens and link the old one to its successor
+        let tokens = self
+            .create_tokens_on(
+                &mut *tx,
+                &user,
+                device_info,
+                stored_token.device_id.clone(),
+                ip_address,
+                stored_token.remember,
+                claims.auth_time,
+            )
+            .await?;
+        TokenRepository::set_refresh_token_successor(
+            &mut *tx,
+            stored_token.id,
+            &self.jwt.hash_token(&tokens.refresh_token),
+        )
+        .await?;
+        tx.commit().await?;
+
+        self.record_ip_activity(user.id, ip_address).await;
+
+        Ok(tokens)
+    }
+
+    /// Note the address a user signed in or refreshed from, masked when
+    /// `audit.mask_pii` is set. Failures are logged; they never fail the login.
+    async fn record_ip_activity(&self, user_id: Uuid, ip_address: Option<IpAddr>) {
+        let Some(ip) = ip_address else {
+            return;
+        };
+        if let Err(e) = UserIpActivityRepository::record(
+            &self.pool,
+            user_id,
+            self.audit.stored_ip(ip),
+            MAX_TRACKED_IPS_PER_USER,
+        )
+        .await
+        {
+            tracing::error!(error = %e, user_id = %user_id, "Failed to record IP activity");
+        }
+    }
+
+    /// Handle a refresh token that is no longer active. If it was rotated
+    /// within the grace window (another tab refreshed with the same cookie)
+    /// and the session it rotated into is still alive, issue fresh tokens
+    /// once; further replays are treated as reuse. An expired token is
+    /// `TokenExpired`, anything else a revoked or reused token.
+    async fn refresh_rotated_token(
+        &self,
+        claims: &RefreshTokenClaims,
+        token_hash: &str,
+        device_info: Option<String>,
+        ip_address: Option<IpAddr>,
+    ) -> Result<AuthTokens, AppError> {
+        let stale =
+            match TokenRepository::find_refresh_token_by_hash_any(&self.pool, token_hash).await {
+                Ok(Some(stale)) => stale,
+                Ok(None) => {
+                    tracing::warn!(
+                        user_id = %claims.sub,
+                        token_id = %claims.jti,
+                        hash_prefix = %&token_hash[..8],
+                        "token_refresh: token hash does not exist in DB at all"
+                    );
+                    return Err(AppError::InvalidCredentials);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        user_id = %claims.sub,
+                        token_id = %claims.jti,
+                        error = %e,
+                        "token_refresh: diagnostic query failed"
+                    );
                     return Err(AppError::InvalidCredentials);
                 }
             };
 
-        // Check if token is valid
-        if !stored_token.is_valid() {
+        if stale.rotated_within(self.refresh_reuse_grace) {
+            let successor_alive = match stale.replaced_by {
+                Some(id) => TokenRepository::find_refresh_token_by_id(&self.pool, id)
+                    .await?
+                    .is_some_and(|successor| successor.is_valid()),
+                None => false,
+            };
+            if successor_alive {
+                let user = UserRepository::find_by_id(&self.pool, claims.sub)
+                    .await?
+                    .ok_or(AppError::InvalidCredentials)?;
+                let mut tx = self.pool.begin().await?;
+                if TokenRepository::claim_grace_reissue(&mut *tx, stale.id).await? {
+                    tracing::info!(
+                        user_id = %claims.sub,
+                        token_id = %claims.jti,
+                        "token_refresh: concurrent refresh within grace window"
+                    );
+                    let tokens = self
+                        .create_tokens_on(
+                            &mut *tx,
+                            &user,
+                            device_info,
+                            stale.device_id.clone(),
+                            ip_address,
+                            stale.remember,
+                            claims.auth_time,
+                        )
+                        .await?;
+                    tx.commit().await?;
+                    return Ok(tokens);
+                }
+                tx.rollback().await?;
+                tracing::warn!(
+                    user_id = %claims.sub,
+                    token_id = %claims.jti,
+                    "token_refresh: rotated token replayed after its grace reissue"
+                );
+                return Err(AppError::InvalidCredentials);
+            }
+        }
+
+        if !stale.is_revoked() && stale.is_expired() {
             tracing::warn!(
                 user_id = %claims.sub,
                 token_id = %claims.jti,
@@ -260,18 +683,17 @@ impl AuthService {
             return Err(AppError::TokenExpired);
         }
 
-        // Get user
-        let user = UserRepository::find_by_id(&self.pool, claims.sub)
-            .await?
-            .ok_or(AppError::InvalidCredentials)?;
-
-        // Revoke old token
-        TokenRepository::revoke_refresh_token(&self.pool, stored_token.id).await?;
-
-        // Create new tokens
-        let tokens = self.create_tokens(&user, device_info, ip_address).await?;
-
-        Ok(tokens)
+        tracing::warn!(
+            user_id = %claims.sub,
+            token_id = %claims.jti,
+            hash_prefix = %&token_hash[..8],
+            revoked_at = ?stale.revoked_at,
+            replaced_by = ?stale.replaced_by,
+            expires_at = %stale.expires_at,
+            created_at = %stale.created_at,
+            "token_refresh: token exists in DB but is revoked or expired"
+        );
+        Err(AppError::InvalidCredentials)
     }
 
     /// Logout (revoke refresh token)
@@ -292,6 +714,7 @@ impl AuthService {
             let ip = ip_address.map(|ip| IpNetwork::from(ip));
             AuditLogRepository::create(
                 &self.pool,
+                &self.audit,
                 CreateAuditLog::new(AuditAction::UserLogout)
                     .with_actor(user.id, &user.email, &user.role)
                     .with_ip(ip),
@@ -315,6 +738,7 @@ impl AuthService {
             let ip = ip_address.map(|ip| IpNetwork::from(ip));
             AuditLogRepository::create(
                 &self.pool,
+                &self.audit,
                 CreateAuditLog::new(AuditAction::UserLogout)
                     .with_actor(user.id, &user.email, &user.role)
                     .with_ip(ip)
@@ -367,7 +791,7 @@ impl AuthService {
                     .with_metadata(serde_json::json!({ "email_known": false, "email": email }))
             };
         // Non-critical — don't fail the request if audit logging fails
-        if let Err(e) = AuditLogRepository::create(&self.pool, audit_log).await {
+        if let Err(e) = AuditLogRepository::create(&self.pool, &self.audit, audit_log).await {
             tracing::error!(error = %e, "Failed to create audit log for magic link request");
         }
 
@@ -382,6 +806,7 @@ impl AuthService {
         &self,
         token: String,
         device_info: Option<String>,
+        device_id: Option<String>,
         ip_address: Option<IpAddr>,
     ) -> Result<MagicLinkResult, AppError> {
         let token_hash = self.jwt.hash_token(&token);
@@ -410,6 +835,10 @@ impl AuthService {
                     (user, false)
                 }
                 None => {
+                    crate::validation::validate_signup_email(
+                        &magic_token.email,
+                        &self.signup_policy,
+                    )?;
                     // Create new user (passwordless)
                     let user = UserRepository::create(
                         &self.pool,
@@ -422,6 +851,15 @@ impl AuthService {
                     .await?;
                     // Set email as verified since they proved ownership via magic link
                     UserRepository::set_email_verified(&self.pool, user.id).await?;
+                    // Record the tier explicitly rather than relying on the column default
+                    let default_tier = self
+                        .tier_config
+                        .read()
+                        .expect("TierConfig lock poisoned")
+                        .default_signup_tier
+                        .clone();
+                    UserRepository::set_subscription_tier(&self.pool, user.id, &default_tier)
+                        .await?;
                     let user = UserRepository::find_by_id(&self.pool, user.id)
                         .await?
                         .ok_or(AppError::not_found("User"))?;
@@ -435,7 +873,7 @@ impl AuthService {
             let has_verified_totp = totp_record.map(|r| r.verified).unwrap_or(false);
 
             if has_verified_totp {
-                let challenge_token = self.jwt.create_2fa_challenge_token(user.id)?;
+                let challenge_token = self.jwt.create_2fa_challenge_token(user.id, true)?;
                 return Ok(MagicLinkResult::TwoFactorRequired {
                     challenge_token,
                     is_new_user,
@@ -447,15 +885,21 @@ impl AuthService {
         }
 
         // Create tokens
-        let tokens = self.create_tokens(&user, device_info, ip_address).await?;
+        self.replace_device_session(user.id, device_id.as_deref())
+            .await?;
+        let tokens = self
+            .create_tokens(&user, device_info, device_id, ip_address, true)
+            .await?;
 
         // Update last login
-        UserRepository::update_last_login(&self.pool, user.id).await?;
+        self.record_login(user.id, &user.email).await?;
+        self.record_ip_activity(user.id, ip_address).await;
 
         // Audit log
         let ip = ip_address.map(|ip| IpNetwork::from(ip));
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::MagicLinkUsed)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip),
@@ -474,6 +918,7 @@ impl AuthService {
         &self,
         challenge_token: &str,
         device_info: Option<String>,
+        device_id: Option<String>,
         ip_address: Option<IpAddr>,
     ) -> Result<(AuthTokens, UserResponse), AppError> {
         // Verify challenge token
@@ -489,18 +934,28 @@ impl AuthService {
             return Err(AppError::InvalidCredentials);
         }
 
-        // Create tokens
+        // Create tokens, keeping the remember-me choice made before the challenge
+        self.replace_device_session(user.id, device_id.as_deref())
+            .await?;
         let tokens = self
-            .create_tokens(&user, device_info.clone(), ip_address)
+            .create_tokens(
+                &user,
+                device_info.clone(),
+                device_id,
+                ip_address,
+                claims.remember,
+            )
             .await?;
 
         // Update last login
-        UserRepository::update_last_login(&self.pool, user.id).await?;
+        self.record_login(user.id, &user.email).await?;
+        self.record_ip_activity(user.id, ip_address).await;
 
         // Audit log
         let ip = ip_address.map(|ip| IpNetwork::from(ip));
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::UserLogin)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip)
@@ -550,6 +1005,7 @@ impl AuthService {
         // Audit log
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::PasswordResetRequested)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip),
@@ -612,19 +1068,23 @@ impl AuthService {
         // Hash new password
         let password_hash = self.password.hash(&new_password)?;
 
-        // Update password
-        UserRepository::update_password(&self.pool, user.id, &password_hash).await?;
-
-        // Mark token as used
-        TokenRepository::mark_password_reset_token_used(&self.pool, reset_token.id).await?;
+        // Consume the token and change the password together. The conditional
+        // update means only one of two concurrent completions can claim it.
+        let mut tx = self.pool.begin().await?;
+        if !TokenRepository::mark_password_reset_token_used(&mut *tx, reset_token.id).await? {
+            return Err(AppError::InvalidCredentials);
+        }
+        UserRepository::update_password(&mut *tx, user.id, &password_hash).await?;
 
         // Revoke all refresh tokens (logout everywhere)
-        TokenRepository::revoke_all_user_refresh_tokens(&self.pool, user.id).await?;
+        TokenRepository::revoke_all_user_refresh_tokens(&mut *tx, user.id).await?;
+        tx.commit().await?;
 
         // Audit log
         let ip = ip_address.map(|ip| IpNetwork::from(ip));
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::PasswordResetCompleted)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip),
@@ -634,6 +1094,92 @@ impl AuthService {
         Ok(user.email)
     }
 
+    /// Start setting a first password on a passwordless (magic link / OAuth)
+    /// account. Issues a single-use token to be emailed to the account's
+    /// address, so a hijacked session alone cannot add a password.
+    ///
+    /// Returns the email to send it to and the token.
+    pub async fn request_set_password(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<IpAddr>,
+    ) -> Result<(String, String), AppError> {
+        let user = UserRepository::find_by_id(&self.pool, user_id)
+            .await?
+            .ok_or(AppError::not_found("User"))?;
+        if user.password_hash.is_some() {
+            return Err(password_already_set());
+        }
+
+        let token = generate_secure_token(32);
+        let ip = ip_address.map(IpNetwork::from);
+        TokenRepository::create_set_password_token(
+            &self.pool,
+            CreatePasswordResetToken {
+                user_id: user.id,
+                token_hash: self.jwt.hash_token(&token),
+                expires_at: Utc::now() + Duration::hours(1),
+                ip_address: ip,
+            },
+        )
+        .await?;
+
+        AuditLogRepository::create(
+            &self.pool,
+            &self.audit,
+            CreateAuditLog::new(AuditAction::PasswordSetRequested)
+                .with_actor(user.id, &user.email, &user.role)
+                .with_ip(ip),
+        )
+        .await?;
+
+        Ok((user.email, token))
+    }
+
+    /// Set a first password on a passwordless account using a token from
+    /// [`request_set_password`](Self::request_set_password). Accounts that
+    /// already have a password must use change password or reset instead.
+    ///
+    /// Returns the user's email so the caller can send a
+    /// password-changed notification.
+    pub async fn set_initial_password(
+        &self,
+        user_id: Uuid,
+        token: String,
+        new_password: String,
+        ip_address: Option<IpAddr>,
+    ) -> Result<String, AppError> {
+        let user = UserRepository::find_by_id(&self.pool, user_id)
+            .await?
+            .ok_or(AppError::not_found("User"))?;
+        if user.password_hash.is_some() {
+            return Err(password_already_set());
+        }
+
+        self.password.validate_strength(&new_password)?;
+        self.password
+            .validate_not_contains_email(&new_password, &user.email)?;
+        let password_hash = self.password.hash(&new_password)?;
+
+        let mut tx = self.pool.begin().await?;
+        TokenRepository::claim_set_password_token(&mut *tx, user.id, &self.jwt.hash_token(&token))
+            .await?
+            .ok_or(AppError::InvalidCredentials)?;
+        UserRepository::update_password(&mut *tx, user.id, &password_hash).await?;
+        tx.commit().await?;
+
+        AuditLogRepository::create(
+            &self.pool,
+            &self.audit,
+            CreateAuditLog::new(AuditAction::PasswordSet)
+                .with_actor(user.id, &user.email, &user.role)
+                .with_ip(ip_address.map(IpNetwork::from)),
+        )
+        .await?;
+
+        Ok(user.email)
+    }
+
     /// Change password (for logged-in users)
     pub async fn change_password(
         &self,
@@ -647,14 +1193,19 @@ impl AuthService {
             .ok_or(AppError::not_found("User"))?;
 
         // Verify current password
-        let password_hash = user.password_hash.as_ref().ok_or(AppError::validation(
-            "password",
-            "No password set for this account",
-        ))?;
+        let password_hash = user
+            .password_hash
+            .as_ref()
+            .ok_or(AppError::validation_coded(
+                "password",
+                "password_not_set",
+                "No password set for this account",
+            ))?;
 
         if !self.password.verify(&current_password, password_hash)? {
-            return Err(AppError::validation(
+            return Err(AppError::validation_coded(
                 "current_password",
+                "current_password_incorrect",
                 "Current password is incorrect",
             ));
         }
@@ -672,6 +1223,7 @@ impl AuthService {
         let ip = ip_address.map(|ip| IpNetwork::from(ip));
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::PasswordChanged)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip),
@@ -702,8 +1254,9 @@ impl AuthService {
 
         // Check if new email is same as current
         if user.email.to_lowercase() == new_email.to_lowercase() {
-            return Err(AppError::validation(
+            return Err(AppError::validation_coded(
                 "email",
+                "email_unchanged",
                 "New email must be different from current email",
             ));
         }
@@ -718,14 +1271,16 @@ impl AuthService {
 
         // If user has a password, require it for verification
         if user.password_hash.is_some() {
-            let password = current_password.ok_or(AppError::validation(
+            let password = current_password.ok_or(AppError::validation_coded(
                 "current_password",
+                "current_password_required",
                 "Password is required to change email",
             ))?;
             let password_hash = user.password_hash.as_ref().unwrap();
             if !self.password.verify(&password, password_hash)? {
-                return Err(AppError::validation(
+                return Err(AppError::validation_coded(
                     "current_password",
+                    "current_password_incorrect",
                     "Current password is incorrect",
                 ));
             }
@@ -767,6 +1322,7 @@ impl AuthService {
             // Audit log
             AuditLogRepository::create(
                 &self.pool,
+                &self.audit,
                 CreateAuditLog::new(AuditAction::EmailChangeRequested)
                     .with_actor(user.id, &user.email, &user.role)
                     .with_ip(ip),
@@ -814,6 +1370,7 @@ impl AuthService {
             // Audit log (outside transaction, non-critical)
             AuditLogRepository::create(
                 &self.pool,
+                &self.audit,
                 CreateAuditLog::new(AuditAction::EmailChangeCompleted)
                     .with_actor(user.id, &user.email, &user.role)
                     .with_ip(ip)
@@ -899,6 +1456,7 @@ impl AuthService {
         // Audit log (outside transaction, non-critical)
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::EmailChangeCompleted)
                 .with_actor(user.id, &old_email, &user.role)
                 .with_ip(ip)
@@ -927,7 +1485,11 @@ impl AuthService {
             .ok_or(AppError::not_found("User"))?;
 
         if user.email_verified {
-            return Err(AppError::validation("email", "Email is already verified"));
+            return Err(AppError::validation_coded(
+                "email",
+                "email_already_verified",
+                "Email is already verified",
+            ));
         }
 
         if !user.two_factor_enabled {
@@ -966,6 +1528,7 @@ impl AuthService {
         // Audit log
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::EmailVerificationRequested)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip),
@@ -1032,9 +1595,13 @@ impl AuthService {
             SubscriptionTier::Standard
         };
 
-        // Mark token as used
-        TokenRepository::mark_email_verification_token_used(&mut *tx, verification_token.id)
-            .await?;
+        // Mark token as used; a concurrent confirmation may have claimed it
+        // after the lookup above
+        if !TokenRepository::mark_email_verification_token_used(&mut *tx, verification_token.id)
+            .await?
+        {
+            return Err(AppError::InvalidCredentials);
+        }
 
         // Set email_verified and assign tier in the same transaction
         sqlx::query("UPDATE users SET email_verified = TRUE, updated_at = NOW() WHERE id = $1")
@@ -1056,6 +1623,7 @@ impl AuthService {
         // Audit log (outside transaction — non-critical)
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::EmailVerified)
                 .with_actor(user.id, &user.email, &user.role)
                 .with_ip(ip)
@@ -1108,6 +1676,7 @@ impl AuthService {
         // Audit log
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::AdminInviteCreated)
                 .with_actor(admin_id, admin_email, admin_role)
                 .with_ip(ip)
@@ -1154,13 +1723,15 @@ impl AuthService {
 
                 // Create auth tokens
                 let tokens = self
-                    .create_tokens(&updated_user, device_info, ip_address)
+                    .create_tokens(&updated_user, device_info, None, ip_address, true)
                     .await?;
-                UserRepository::update_last_login(&self.pool, user.id).await?;
+                self.record_login(user.id, &user.email).await?;
+                self.record_ip_activity(user.id, ip_address).await;
 
                 // Audit log
                 AuditLogRepository::create(
                     &self.pool,
+                    &self.audit,
                     CreateAuditLog::new(AuditAction::AdminInviteAccepted)
                         .with_actor(user.id, &user.email, "admin")
                         .with_ip(ip)
@@ -1214,12 +1785,16 @@ impl AuthService {
                 InviteRepository::mark_accepted(&self.pool, invite.id).await?;
 
                 // Create auth tokens
-                let tokens = self.create_tokens(&user, device_info, ip_address).await?;
-                UserRepository::update_last_login(&self.pool, user.id).await?;
+                let tokens = self
+                    .create_tokens(&user, device_info, None, ip_address, true)
+                    .await?;
+                self.record_login(user.id, &user.email).await?;
+                self.record_ip_activity(user.id, ip_address).await;
 
                 // Audit log
                 AuditLogRepository::create(
                     &self.pool,
+                    &self.audit,
                     CreateAuditLog::new(AuditAction::AdminInviteAccepted)
                         .with_actor(user.id, &invite.email, "admin")
                         .with_ip(ip)
@@ -1256,6 +1831,7 @@ impl AuthService {
         // Audit log
         AuditLogRepository::create(
             &self.pool,
+            &self.audit,
             CreateAuditLog::new(AuditAction::AdminInviteRevoked)
                 .with_actor(admin_id, admin_email, admin_role)
                 .with_resource("admin_invite", invite_id),
@@ -1265,36 +1841,108 @@ impl AuthService {
         Ok(())
     }
 
-    /// Helper to create auth tokens
+    /// With unique sessions per device enabled, revoke the user's existing
+    /// sessions on `device_id` before a login issues a new one. Refreshes
+    /// never do this, so two tabs on one device can still refresh together.
+    async fn replace_device_session(
+        &self,
+        user_id: Uuid,
+        device_id: Option<&str>,
+    ) -> Result<(), AppError> {
+        let Some(device_id) = device_id.filter(|_| self.unique_session_per_device) else {
+            return Ok(());
+        };
+        let revoked = TokenRepository::revoke_for_device(&self.pool, user_id, device_id).await?;
+        if revoked > 0 {
+            tracing::info!(
+                user_id = %user_id,
+                revoked,
+                "Replaced existing session on device"
+            );
+        }
+        Ok(())
+    }
+
+    /// Helper to create auth tokens for a user who has just signed in
     async fn create_tokens(
         &self,
         user: &User,
         device_info: Option<String>,
+        device_id: Option<String>,
         ip_address: Option<IpAddr>,
+        remember: bool,
     ) -> Result<AuthTokens, AppError> {
-        let access_token = self.jwt.create_access_token(user)?;
-        let (refresh_token, token_hash) = self.jwt.create_refresh_token(user.id)?;
+        let mut conn = self.pool.acquire().await?;
+        self.create_tokens_on(
+            &mut conn,
+            user,
+            device_info,
+            device_id,
+            ip_address,
+            remember,
+            Some(Utc::now().timestamp()),
+        )
+        .await
+    }
+
+    /// `create_tokens` on a given connection, so a refresh can issue the new
+    /// session inside the transaction that rotates the old one. `auth_time`
+    /// is when the session signed in, carried over from the refreshed token.
+    #[allow(clippy::too_many_arguments)]
+    async fn create_tokens_on(
+        &self,
+        conn: &mut sqlx::PgConnection,
+        user: &User,
+        device_info: Option<String>,
+        device_id: Option<String>,
+        ip_address: Option<IpAddr>,
+        remember: bool,
+        auth_time: Option<i64>,
+    ) -> Result<AuthTokens, AppError> {
+        let access_token = self.jwt.create_access_token_since(user, auth_time)?;
+        let (refresh_token, token_hash) =
+            self.jwt.create_refresh_token_since(user.id, auth_time)?;
 
         let ip = ip_address.map(|ip| IpNetwork::from(ip));
-        let expires_at = Utc::now() + Duration::days(30);
+        let expires_at = Utc::now() + RefreshToken::lifetime(remember);
 
         // Store refresh token
         TokenRepository::create_refresh_token(
-            &self.pool,
+            &mut *conn,
             CreateRefreshToken {
                 user_id: user.id,
                 token_hash,
                 device_info,
                 ip_address: ip,
                 expires_at,
+                remember,
+                device_id,
             },
         )
         .await?;
 
+        if self.max_sessions_per_user > 0 {
+            let revoked = TokenRepository::revoke_oldest_for_user(
+                &mut *conn,
+                user.id,
+                i64::from(self.max_sessions_per_user),
+            )
+            .await?;
+            if revoked > 0 {
+                tracing::info!(
+                    user_id = %user.id,
+                    revoked,
+                    cap = self.max_sessions_per_user,
+                    "Session cap reached, revoked oldest sessions"
+                );
+            }
+        }
+
         Ok(AuthTokens {
             access_token,
             refresh_token,
             expires_in: 900, // 15 minutes in seconds
+            remember,
         })
     }
 }
@@ -1306,9 +1954,37 @@ pub(crate) fn generate_secure_token(length: usize) -> String {
     base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, &bytes)
 }
 
+/// Error for set-password attempts on accounts that already have a password
+fn password_already_set() -> AppError {
+    AppError::validation_coded(
+        "password",
+        "password_already_set",
+        "This account already has a password; change or reset it instead",
+    )
+}
+
+/// Audit metadata for a failed login. The attempted email is stored masked for
+/// readability plus an HMAC of its normalized form keyed by `hash_key`
+/// (AUDIT_MASK_KEY), so repeated attempts against one address can be
+/// correlated without keeping it in clear text or reversible by dictionary.
+fn failed_login_metadata(email: &str, reason: &str, hash_key: &[u8]) -> serde_json::Value {
+    let normalized = email.trim().to_lowercase();
+    serde_json::json!({
+        "email_masked": crate::models::Feedback::mask_email(&normalized),
+        "email_hash": crate::models::audit::audit_email_hash(&normalized, hash_key),
+        "reason": reason,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::Ordering;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
 
     #[test]
     fn generate_secure_token_correct_length() {
@@ -1345,4 +2021,963 @@ mod tests {
             assert_eq!(decoded.len(), len);
         }
     }
+
+    #[test]
+    fn failed_login_metadata_masks_and_hashes_email() {
+        let meta = failed_login_metadata(" Alice@Example.com ", "wrong_password", b"key");
+
+        assert_eq!(meta["email_masked"], "a***@example.com");
+        assert_eq!(meta["reason"], "wrong_password");
+        let hash = meta["email_hash"].as_str().unwrap();
+        assert_eq!(hash.len(), 64);
+        assert!(!meta.to_string().contains("alice@example.com"));
+        assert_eq!(
+            failed_login_metadata("alice@example.com", "unknown_email", b"key")["email_hash"],
+            hash
+        );
+        // Without the key the hash cannot be recomputed from a guessed email
+        assert_ne!(
+            failed_login_metadata("alice@example.com", "unknown_email", b"other")["email_hash"],
+            hash
+        );
+    }
+
+    #[actix_rt::test]
+    async fn failed_login_writes_warning_audit_and_returns_invalid_credentials() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        );
+        let email = format!("nobody-{}@example.com", Uuid::new_v4());
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+
+        let dummy_checks = crate::services::password::DUMMY_VERIFICATIONS.load(Ordering::Relaxed);
+        let result = service
+            .login(
+                email.clone(),
+                "wrong-password".into(),
+                None,
+                None,
+                Some(ip),
+                true,
+            )
+            .await;
+        assert!(matches!(result, Err(AppError::InvalidCredentials)));
+        // The unknown email still paid for a password verify
+        assert!(
+            crate::services::password::DUMMY_VERIFICATIONS.load(Ordering::Relaxed) > dummy_checks
+        );
+
+        let hash = failed_login_metadata(&email, "", b"")["email_hash"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let row: (String, Option<IpNetwork>, serde_json::Value) = sqlx::query_as(
+            r#"
+            SELECT severity, actor_ip_address, metadata FROM audit_logs
+            WHERE action = 'user_login_failed' AND metadata->>'email_hash' = $1
+            "#,
+        )
+        .bind(&hash)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(row.0, "warning");
+        assert_eq!(row.1, Some(IpNetwork::from(ip)));
+        assert_eq!(row.2["reason"], "unknown_email");
+
+        sqlx::query("DELETE FROM audit_logs WHERE metadata->>'email_hash' = $1")
+            .bind(&hash)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn magic_link_signup_gets_configured_default_tier() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let mut tier_config = TierConfig::from_env();
+        tier_config.default_signup_tier = SubscriptionTier::EarlyAdopter;
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(tier_config)),
+        );
+
+        let email = format!("magic-tier-{}@example.com", Uuid::new_v4());
+        let token = service
+            .request_magic_link(email.clone(), None)
+            .await
+            .unwrap();
+        let result = service
+            .verify_magic_link(token, None, None, None)
+            .await
+            .unwrap();
+
+        let user = UserRepository::find_by_email(&pool, &email)
+            .await
+            .unwrap()
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(user.subscription_tier, "early_adopter");
+        match result {
+            MagicLinkResult::Success(_, response, is_new_user) => {
+                assert!(is_new_user);
+                assert_eq!(response.subscription_tier, "early_adopter");
+            }
+            MagicLinkResult::TwoFactorRequired { .. } => panic!("new user has no 2FA"),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn magic_link_signup_applies_the_signup_policy() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        )
+        .with_signup_policy(SignupPolicyConfig {
+            reserved_emails: Vec::new(),
+            denied_domains: vec!["denied.example".to_string()],
+        });
+
+        let email = format!("magic-{}@denied.example", Uuid::new_v4());
+        let token = service
+            .request_magic_link(email.clone(), None)
+            .await
+            .unwrap();
+        let result = service.verify_magic_link(token, None, None, None).await;
+        let created = UserRepository::find_by_email(&pool, &email).await.unwrap();
+
+        sqlx::query("DELETE FROM magic_link_tokens WHERE email = $1")
+            .bind(&email)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(result, Err(AppError::ValidationError { .. })));
+        assert!(created.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn welcome_email_fires_only_without_prior_login() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let jwt = JwtService::new(crate::services::JwtConfig::from_secret(
+            "a-very-long-secret-key-for-tests-12345",
+            "a8n",
+        ));
+        let tiers = Arc::new(RwLock::new(TierConfig::from_env()));
+        let service = AuthService::new(pool.clone(), jwt.clone(), tiers.clone())
+            .with_welcome_email(Arc::new(EmailService::new_dev()));
+        let user = UserRepository::create(
+            &pool,
+            CreateUser {
+                email: format!("welcome-{}@example.com", Uuid::new_v4()),
+                password_hash: None,
+                role: UserRole::Subscriber,
+            },
+        )
+        .await
+        .unwrap();
+
+        let first = service.record_login(user.id, &user.email).await.unwrap();
+        let second = service.record_login(user.id, &user.email).await.unwrap();
+
+        // Without a configured email service nothing is sent, even on first login
+        let disabled = AuthService::new(pool.clone(), jwt, tiers);
+        sqlx::query("UPDATE users SET last_login_at = NULL WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let unconfigured = disabled.record_login(user.id, &user.email).await.unwrap();
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(first);
+        assert!(!second);
+        assert!(!unconfigured);
+    }
+    struct TableResolver(std::collections::HashMap<IpAddr, crate::services::GeoPoint>);
+
+    impl GeoResolver for TableResolver {
+        fn resolve(
+            &self,
+            ip: IpAddr,
+        ) -> futures_util::future::BoxFuture<'_, Option<crate::services::GeoPoint>> {
+            let point = self.0.get(&ip).copied();
+            Box::pin(async move { point })
+        }
+    }
+
+    #[actix_rt::test]
+    async fn impossible_travel_is_audited_and_requires_step_up() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let london: IpAddr = "203.0.113.10".parse().unwrap();
+        let new_york: IpAddr = "198.51.100.20".parse().unwrap();
+        let resolver = TableResolver(
+            [
+                (
+                    london,
+                    crate::services::GeoPoint {
+                        latitude: 51.5074,
+                        longitude: -0.1278,
+                    },
+                ),
+                (
+                    new_york,
+                    crate::services::GeoPoint {
+                        latitude: 40.7128,
+                        longitude: -74.0060,
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        )
+        .with_impossible_travel(
+            ImpossibleTravelConfig {
+                enabled: true,
+                geo_url: None,
+                max_speed_kmh: 1000.0,
+                min_distance_km: 500.0,
+                require_step_up: true,
+                notify_user: false,
+            },
+            Arc::new(resolver),
+            Arc::new(EmailService::new_dev()),
+        );
+
+        let password = "Correct-Horse-Battery-9";
+        let user = UserRepository::create(
+            &pool,
+            CreateUser {
+                email: format!("travel-{}@example.com", Uuid::new_v4()),
+                password_hash: Some(PasswordService::new().hash(password).unwrap()),
+                role: UserRole::Subscriber,
+            },
+        )
+        .await
+        .unwrap();
+        UserIpActivityRepository::record(
+            &pool,
+            user.id,
+            IpNetwork::from(london),
+            MAX_TRACKED_IPS_PER_USER,
+        )
+        .await
+        .unwrap();
+        sqlx::query(
+            "UPDATE user_ip_activity SET last_seen = NOW() - INTERVAL '10 minutes' WHERE user_id = $1",
+        )
+        .bind(user.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let result = service
+            .login(
+                user.email.clone(),
+                password.into(),
+                None,
+                None,
+                Some(new_york),
+                true,
+            )
+            .await;
+        let flagged: Option<serde_json::Value> = sqlx::query_scalar(
+            "SELECT metadata FROM audit_logs WHERE actor_id = $1 AND action = 'user_login_impossible_travel'",
+        )
+        .bind(user.id)
+        .fetch_optional(&pool)
+        .await
+        .unwrap();
+
+        // Same IP as the previous login is never flagged
+        let same_ip = service.check_impossible_travel(&user, Some(london)).await;
+
+        sqlx::query("DELETE FROM audit_logs WHERE actor_id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        let metadata = flagged.expect("impossible travel audit entry");
+        assert_eq!(metadata["previous_ip"], "203.0.113.10");
+        assert!(metadata["distance_km"].as_f64().unwrap() > 5000.0);
+        assert!(!same_ip);
+    }
+
+    async fn refresh_fixture(pool: &PgPool, grace_secs: u64) -> (AuthService, User) {
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        )
+        .with_refresh_reuse_grace(grace_secs);
+        let user = UserRepository::create(
+            pool,
+            CreateUser {
+                email: format!("refresh-race-{}@example.com", Uuid::new_v4()),
+                password_hash: None,
+                role: UserRole::Subscriber,
+            },
+        )
+        .await
+        .unwrap();
+        (service, user)
+    }
+
+    #[actix_rt::test]
+    async fn login_on_the_same_device_replaces_its_session() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let password = "Correct-Horse-Battery-9";
+        let user = UserRepository::create(
+            &pool,
+            CreateUser {
+                email: format!("kiosk-{}@example.com", Uuid::new_v4()),
+                password_hash: Some(PasswordService::new().hash(password).unwrap()),
+                role: UserRole::Subscriber,
+            },
+        )
+        .await
+        .unwrap();
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        )
+        .with_unique_session_per_device(true);
+        let login = |device_id: &str| {
+            service.login(
+                user.email.clone(),
+                password.into(),
+                None,
+                Some(device_id.to_string()),
+                None,
+                true,
+            )
+        };
+
+        let devices = || async {
+            let mut devices: Vec<Option<String>> =
+                TokenRepository::find_user_refresh_tokens(&pool, user.id)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|token| token.device_id)
+                    .collect();
+            devices.sort();
+            devices
+        };
+
+        login("kiosk-1").await.unwrap();
+        login("kiosk-1").await.unwrap();
+        let same_device = devices().await;
+        login("kiosk-2").await.unwrap();
+        let other_device = devices().await;
+        let magic = service
+            .request_magic_link(user.email.clone(), None)
+            .await
+            .unwrap();
+        service
+            .verify_magic_link(magic, None, Some("kiosk-2".to_string()), None)
+            .await
+            .unwrap();
+        let magic_link_device = devices().await;
+
+        sqlx::query("DELETE FROM magic_link_tokens WHERE email = $1")
+            .bind(&user.email)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM audit_logs WHERE actor_id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(same_device, vec![Some("kiosk-1".to_string())]);
+        assert_eq!(
+            other_device,
+            vec![Some("kiosk-1".to_string()), Some("kiosk-2".to_string())]
+        );
+        assert_eq!(magic_link_device, other_device);
+    }
+
+    #[actix_rt::test]
+    async fn email_verification_tokens_expire_and_are_single_use() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (service, user) = refresh_fixture(&pool, 0).await;
+        let issue = |raw: &'static str, expires_at: chrono::DateTime<Utc>| {
+            TokenRepository::create_email_verification_token(
+                &pool,
+                CreateEmailVerificationToken {
+                    user_id: user.id,
+                    token_hash: service.jwt.hash_token(raw),
+                    expires_at,
+                    ip_address: None,
+                },
+            )
+        };
+        let expired_raw = "expired-verification-token";
+        let valid_raw = "valid-verification-token";
+        issue(expired_raw, Utc::now() - Duration::minutes(1))
+            .await
+            .unwrap();
+        issue(valid_raw, Utc::now() + Duration::hours(1))
+            .await
+            .unwrap();
+
+        let expired = service
+            .confirm_email_verification(expired_raw.to_string(), None)
+            .await;
+        let first = service
+            .confirm_email_verification(valid_raw.to_string(), None)
+            .await;
+        let reused = service
+            .confirm_email_verification(valid_raw.to_string(), None)
+            .await;
+        let verified = UserRepository::find_by_id(&pool, user.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .email_verified;
+
+        sqlx::query("DELETE FROM audit_logs WHERE actor_id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(expired, Err(AppError::InvalidCredentials)));
+        assert_eq!(first.unwrap().0, user.id);
+        assert!(matches!(reused, Err(AppError::InvalidCredentials)));
+        assert!(verified);
+    }
+
+    #[actix_rt::test]
+    async fn concurrent_refreshes_with_the_same_token_both_succeed() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (service, user) = refresh_fixture(&pool, 10).await;
+        let initial = service
+            .create_tokens(&user, None, None, None, true)
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(
+            service.refresh_tokens(initial.refresh_token.clone(), None, None),
+            service.refresh_tokens(initial.refresh_token.clone(), None, None),
+        );
+
+        let old = TokenRepository::find_refresh_token_by_hash_any(
+            &pool,
+            &service.jwt.hash_token(&initial.refresh_token),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_ne!(first.refresh_token, second.refresh_token);
+        assert!(old.is_revoked());
+        assert!(old.replaced_by.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn rotated_token_is_reissued_at_most_once_within_grace() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (service, user) = refresh_fixture(&pool, 10).await;
+        let initial = service
+            .create_tokens(&user, None, None, None, true)
+            .await
+            .unwrap();
+
+        let rotated = service
+            .refresh_tokens(initial.refresh_token.clone(), None, None)
+            .await;
+        let reissued = service
+            .refresh_tokens(initial.refresh_token.clone(), None, None)
+            .await;
+        let replayed = service
+            .refresh_tokens(initial.refresh_token.clone(), None, None)
+            .await;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(rotated.is_ok());
+        assert!(reissued.is_ok());
+        assert!(matches!(replayed, Err(AppError::InvalidCredentials)));
+    }
+
+    #[actix_rt::test]
+    async fn expired_refresh_token_is_reported_as_expired() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (service, user) = refresh_fixture(&pool, 10).await;
+        let initial = service
+            .create_tokens(&user, None, None, None, true)
+            .await
+            .unwrap();
+        sqlx::query(
+            "UPDATE refresh_tokens SET expires_at = NOW() - INTERVAL '1 minute' WHERE user_id = $1",
+        )
+        .bind(user.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let result = service
+            .refresh_tokens(initial.refresh_token.clone(), None, None)
+            .await;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(result, Err(AppError::TokenExpired)));
+    }
+
+    #[actix_rt::test]
+    async fn without_grace_only_one_concurrent_refresh_succeeds() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (service, user) = refresh_fixture(&pool, 0).await;
+        let initial = service
+            .create_tokens(&user, None, None, None, true)
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(
+            service.refresh_tokens(initial.refresh_token.clone(), None, None),
+            service.refresh_tokens(initial.refresh_token.clone(), None, None),
+        );
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            [first.is_ok(), second.is_ok()]
+                .iter()
+                .filter(|ok| **ok)
+                .count(),
+            1
+        );
+    }
+
+    #[actix_rt::test]
+    async fn rotated_token_is_rejected_once_its_successor_is_revoked() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (service, user) = refresh_fixture(&pool, 10).await;
+        let initial = service
+            .create_tokens(&user, None, None, None, true)
+            .await
+            .unwrap();
+
+        let rotated = service
+            .refresh_tokens(initial.refresh_token.clone(), None, None)
+            .await
+            .unwrap();
+        service
+            .logout(rotated.refresh_token, user.id, None)
+            .await
+            .unwrap();
+        let reused = service
+            .refresh_tokens(initial.refresh_token.clone(), None, None)
+            .await;
+
+        sqlx::query("DELETE FROM audit_logs WHERE actor_id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(reused, Err(AppError::InvalidCredentials)));
+    }
+
+    #[actix_rt::test]
+    async fn refresh_token_expiry_follows_remember_mode() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let (service, user) = refresh_fixture(&pool, 0).await;
+
+        let stored = |tokens: AuthTokens| {
+            let hash = service.jwt.hash_token(&tokens.refresh_token);
+            let pool = pool.clone();
+            async move {
+                TokenRepository::find_refresh_token_by_hash(&pool, &hash)
+                    .await
+                    .unwrap()
+                    .expect("refresh token stored")
+            }
+        };
+        let short = service
+            .create_tokens(&user, None, None, None, false)
+            .await
+            .unwrap();
+        assert!(!short.remember);
+        let short_refresh = short.refresh_token.clone();
+        let short = stored(short).await;
+        let long = stored(
+            service
+                .create_tokens(&user, None, None, None, true)
+                .await
+                .unwrap(),
+        )
+        .await;
+
+        // Rotation keeps the session's remember mode
+        let rotated = service
+            .refresh_tokens(short_refresh, None, None)
+            .await
+            .unwrap();
+        assert!(!rotated.remember);
+        let rotated = stored(rotated).await;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let lifetime = |token: &RefreshToken| token.expires_at - token.created_at;
+        let close_to =
+            |actual: Duration, expected: Duration| (actual - expected).num_seconds().abs() < 60;
+        assert!(!short.remember && !rotated.remember && long.remember);
+        assert!(close_to(lifetime(&short), RefreshToken::lifetime(false)));
+        assert!(close_to(lifetime(&rotated), RefreshToken::lifetime(false)));
+        assert!(close_to(lifetime(&long), RefreshToken::lifetime(true)));
+    }
+
+    #[actix_rt::test]
+    async fn rehash_does_not_overwrite_a_password_changed_after_verify() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let old_hash = PasswordService::new()
+            .hash("Correct-Horse-Battery-9")
+            .unwrap();
+        let user = UserRepository::create(
+            &pool,
+            CreateUser {
+                email: format!("rehash-{}@example.com", Uuid::new_v4()),
+                password_hash: Some(old_hash.clone()),
+                role: UserRole::Subscriber,
+            },
+        )
+        .await
+        .unwrap();
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        );
+        async fn stored(pool: &PgPool, user_id: Uuid) -> String {
+            let (hash,): (String,) =
+                sqlx::query_as("SELECT password_hash FROM users WHERE id = $1")
+                    .bind(user_id)
+                    .fetch_one(pool)
+                    .await
+                    .unwrap();
+            hash
+        }
+
+        // A reset commits between the login's verify and its rehash
+        let new_hash = PasswordService::new()
+            .hash("Staple-Grape-Lantern-4!")
+            .unwrap();
+        UserRepository::update_password(&pool, user.id, &new_hash)
+            .await
+            .unwrap();
+        service
+            .rehash_password(user.id, "Correct-Horse-Battery-9", &old_hash)
+            .await;
+        let after_race = stored(&pool, user.id).await;
+
+        // Without a concurrent change the rehash goes through
+        service
+            .rehash_password(user.id, "Staple-Grape-Lantern-4!", &new_hash)
+            .await;
+        let after_rehash = stored(&pool, user.id).await;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(after_race, new_hash);
+        assert_ne!(after_rehash, new_hash);
+        assert!(PasswordService::new()
+            .verify("Staple-Grape-Lantern-4!", &after_rehash)
+            .unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn concurrent_password_reset_completions_succeed_once() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        );
+        let email = format!("reset-race-{}@example.com", Uuid::new_v4());
+        let user = service
+            .register(email.clone(), "Correct-Horse-Battery-9!".into(), None)
+            .await
+            .unwrap();
+        let token = service
+            .request_password_reset(email.clone(), None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (first, second) = futures_util::join!(
+            service.complete_password_reset(token.clone(), "Staple-Grape-Lantern-4!".into(), None),
+            service.complete_password_reset(token.clone(), "Other-Pebble-Window-7?".into(), None),
+        );
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let succeeded = [&first, &second].iter().filter(|r| r.is_ok()).count();
+        assert_eq!(succeeded, 1, "{:?} / {:?}", first, second);
+        assert!(
+            matches!(first, Err(AppError::InvalidCredentials))
+                || matches!(second, Err(AppError::InvalidCredentials))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn passwordless_user_can_set_a_password_with_the_emailed_token() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        );
+        let email = format!("set-password-{}@example.com", Uuid::new_v4());
+        let magic = service
+            .request_magic_link(email.clone(), None)
+            .await
+            .unwrap();
+        service
+            .verify_magic_link(magic, None, None, None)
+            .await
+            .unwrap();
+        let user = UserRepository::find_by_email(&pool, &email)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(user.password_hash.is_none());
+
+        let (sent_to, token) = service.request_set_password(user.id, None).await.unwrap();
+        assert_eq!(sent_to, email);
+
+        // Set-password tokens cannot be redeemed through password reset
+        assert!(matches!(
+            service
+                .complete_password_reset(token.clone(), "Staple-Grape-Lantern-4!".into(), None)
+                .await,
+            Err(AppError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            service
+                .set_initial_password(
+                    user.id,
+                    "wrong".into(),
+                    "Staple-Grape-Lantern-4!".into(),
+                    None
+                )
+                .await,
+            Err(AppError::InvalidCredentials)
+        ));
+
+        service
+            .set_initial_password(
+                user.id,
+                token.clone(),
+                "Staple-Grape-Lantern-4!".into(),
+                None,
+            )
+            .await
+            .unwrap();
+        let user = UserRepository::find_by_id(&pool, user.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(user.password_hash.is_some());
+
+        // The account now has a password, so the flow is closed
+        let again = service
+            .set_initial_password(user.id, token, "Other-Pebble-Window-7?".into(), None)
+            .await;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(again, Err(AppError::ValidationError { .. })));
+    }
+
+    #[actix_rt::test]
+    async fn users_with_a_password_cannot_use_set_password() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let service = AuthService::new(
+            pool.clone(),
+            JwtService::new(crate::services::JwtConfig::from_secret(
+                "a-very-long-secret-key-for-tests-12345",
+                "a8n",
+            )),
+            Arc::new(RwLock::new(TierConfig::from_env())),
+        );
+        let user = service
+            .register(
+                format!("has-password-{}@example.com", Uuid::new_v4()),
+                "Correct-Horse-Battery-9!".into(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let requested = service.request_set_password(user.id, None).await;
+        let set = service
+            .set_initial_password(
+                user.id,
+                "any".into(),
+                "Staple-Grape-Lantern-4!".into(),
+                None,
+            )
+            .await;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(requested, Err(AppError::ValidationError { .. })));
+        assert!(matches!(set, Err(AppError::ValidationError { .. })));
+    }
 }
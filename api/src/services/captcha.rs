@@ -0,0 +1,206 @@
+//! Captcha token verification (Cloudflare Turnstile / hCaptcha)
+//!
+//! Both providers expose the same siteverify contract: a form POST with
+//! `secret`, `response` and optional `remoteip`, answered by JSON with a
+//! `success` flag.
+
+use serde::Deserialize;
+
+use crate::config::CaptchaConfig;
+use crate::errors::AppError;
+
+pub struct CaptchaService {
+    client: reqwest::Client,
+    config: CaptchaConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+    #[serde(default, rename = "error-codes")]
+    error_codes: Vec<String>,
+}
+
+impl CaptchaService {
+    pub fn new(config: CaptchaConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(5))
+            .build()
+            .expect("Failed to build captcha HTTP client");
+
+        Self { client, config }
+    }
+
+    /// Verify a client-supplied captcha token.
+    ///
+    /// Does nothing when captcha is disabled. A missing or rejected token is
+    /// always a validation error; an unreachable provider or missing secret
+    /// is only tolerated when `fail_open` (non-production).
+    pub async fn verify(
+        &self,
+        token: Option<&str>,
+        remote_ip: Option<&str>,
+    ) -> Result<(), AppError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let token = token
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .ok_or_else(|| {
+                AppError::validation_coded(
+                    "captcha",
+                    "captcha_required",
+                    "Captcha token is required",
+                )
+            })?;
+
+        let Some(secret) = self.config.secret_key.as_deref() else {
+            return self.unavailable("CAPTCHA_SECRET_KEY is not set");
+        };
+
+        let mut form = vec![("secret", secret), ("response", token)];
+        if let Some(ip) = remote_ip {
+            form.push(("remoteip", ip));
+        }
+
+        let response = match self
+            .client
+            .post(&self.config.verify_url)
+            .form(&form)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => return self.unavailable(&e.to_string()),
+        };
+
+        let body = match response.json::<SiteVerifyResponse>().await {
+            Ok(body) => body,
+            Err(e) => return self.unavailable(&e.to_string()),
+        };
+
+        if !body.success {
+            tracing::info!(error_codes = ?body.error_codes, "Captcha verification rejected");
+            return Err(AppError::validation_coded(
+                "captcha",
+                "captcha_rejected",
+                "Captcha verification failed",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn unavailable(&self, reason: &str) -> Result<(), AppError> {
+        if self.config.fail_open {
+            tracing::warn!(reason = %reason, "Captcha verification skipped");
+            return Ok(());
+        }
+        tracing::error!(reason = %reason, "Captcha verification unavailable");
+        Err(AppError::external("captcha", reason))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CaptchaProvider;
+    use actix_web::ResponseError;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(verify_url: String, fail_open: bool) -> CaptchaConfig {
+        CaptchaConfig {
+            enabled: true,
+            provider: CaptchaProvider::Turnstile,
+            secret_key: Some("test-secret".to_string()),
+            verify_url,
+            fail_open,
+        }
+    }
+
+    async fn mock_siteverify(success: bool) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/siteverify"))
+            .and(body_string_contains("secret=test-secret"))
+            .and(body_string_contains("response=tok"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "success": success,
+                "error-codes": if success { vec![] } else { vec!["invalid-input-response"] },
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn assert_captcha_error(result: Result<(), AppError>, expected_code: &str) {
+        match result {
+            Err(AppError::ValidationError { field, code, .. }) => {
+                assert_eq!(field, "captcha");
+                assert_eq!(code, expected_code);
+            }
+            other => panic!("expected captcha validation error, got {:?}", other),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn accepts_token_verified_by_provider() {
+        let server = mock_siteverify(true).await;
+        let service = CaptchaService::new(config(format!("{}/siteverify", server.uri()), false));
+
+        assert!(service
+            .verify(Some("tok"), Some("203.0.113.7"))
+            .await
+            .is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn rejects_token_refused_by_provider() {
+        let server = mock_siteverify(false).await;
+        let service = CaptchaService::new(config(format!("{}/siteverify", server.uri()), true));
+
+        assert_captcha_error(service.verify(Some("tok"), None).await, "captcha_rejected");
+    }
+
+    #[actix_rt::test]
+    async fn missing_token_is_rejected_even_when_failing_open() {
+        let service = CaptchaService::new(config("http://127.0.0.1:9/siteverify".into(), true));
+
+        assert_captcha_error(service.verify(None, None).await, "captcha_required");
+        assert_captcha_error(service.verify(Some("  "), None).await, "captcha_required");
+    }
+
+    #[actix_rt::test]
+    async fn unreachable_provider_fails_closed_in_production() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let url = format!("{}/siteverify", server.uri());
+
+        let prod = CaptchaService::new(config(url.clone(), false));
+        match prod.verify(Some("tok"), None).await {
+            Err(err @ AppError::Upstream { .. }) => {
+                assert_eq!(err.dynamic_error_code(), "CAPTCHA_ERROR");
+                assert_eq!(err.status_code().as_u16(), 502);
+            }
+            other => panic!("expected upstream error, got {:?}", other),
+        }
+
+        let dev = CaptchaService::new(config(url, true));
+        assert!(dev.verify(Some("tok"), None).await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn disabled_captcha_skips_verification() {
+        let mut cfg = config("http://127.0.0.1:9/siteverify".into(), false);
+        cfg.enabled = false;
+        let service = CaptchaService::new(cfg);
+
+        assert!(service.verify(None, None).await.is_ok());
+    }
+}
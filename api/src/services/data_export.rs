@@ -0,0 +1,473 @@
+//! Asynchronous user data exports
+//!
+//! Requesting an export queues a job and returns at once; the bundle is
+//! built in the background and stored (see [`ExportStore`]) until it
+//! expires. A user has at most one queued job at a time. While it is ready,
+//! the status endpoint hands out a short-lived URL signed with HMAC-SHA256,
+//! so the bundle can be fetched without holding a request open for the
+//! whole build.
+
+use chrono::{DateTime, Duration, Utc};
+use futures_util::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::clock::{Clock, SystemClock};
+use crate::errors::AppError;
+use crate::models::{
+    DataExport, DataExportResponse, DataExportStatus, PaymentMethodResponse, SessionInfo,
+    UserResponse,
+};
+use crate::repositories::{
+    AuditLogRepository, DataExportRepository, PaymentMethodRepository, TokenRepository,
+    UserIpActivityRepository, UserRepository,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long a finished bundle stays downloadable
+const BUNDLE_TTL_HOURS: i64 = 24;
+/// How long one signed download URL stays valid
+const DOWNLOAD_URL_TTL_MINUTES: i64 = 15;
+/// Most recent audit entries included in a bundle
+const EXPORT_AUDIT_LOG_LIMIT: i32 = 1000;
+/// Context mixed into the master secret to derive the URL signing key
+const SIGNING_KEY_CONTEXT: &[u8] = b"a8n data export download url v1";
+
+/// Where export jobs and their bundles are kept
+pub trait ExportStore: Send + Sync {
+    /// Queue a new job for `user_id`, or return the one it already has
+    /// queued. The flag is true when a new job was created.
+    fn create(&self, user_id: Uuid) -> BoxFuture<'_, Result<(DataExport, bool), AppError>>;
+    fn find(&self, id: Uuid) -> BoxFuture<'_, Result<Option<DataExport>, AppError>>;
+    /// Store the bundle and mark the job ready until `expires_at`
+    fn mark_ready(
+        &self,
+        id: Uuid,
+        bundle: serde_json::Value,
+        expires_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<(), AppError>>;
+    fn mark_failed(&self, id: Uuid, error: String) -> BoxFuture<'_, Result<(), AppError>>;
+    /// The bundle of a ready job
+    fn bundle(&self, id: Uuid) -> BoxFuture<'_, Result<Option<serde_json::Value>, AppError>>;
+}
+
+/// Store backed by the `data_exports` table
+pub struct PgExportStore {
+    pool: PgPool,
+}
+
+impl PgExportStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl ExportStore for PgExportStore {
+    fn create(&self, user_id: Uuid) -> BoxFuture<'_, Result<(DataExport, bool), AppError>> {
+        Box::pin(DataExportRepository::create(&self.pool, user_id))
+    }
+
+    fn find(&self, id: Uuid) -> BoxFuture<'_, Result<Option<DataExport>, AppError>> {
+        Box::pin(DataExportRepository::find_by_id(&self.pool, id))
+    }
+
+    fn mark_ready(
+        &self,
+        id: Uuid,
+        bundle: serde_json::Value,
+        expires_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<(), AppError>> {
+        Box::pin(async move {
+            DataExportRepository::mark_ready(&self.pool, id, &bundle, expires_at).await
+        })
+    }
+
+    fn mark_failed(&self, id: Uuid, error: String) -> BoxFuture<'_, Result<(), AppError>> {
+        Box::pin(async move { DataExportRepository::mark_failed(&self.pool, id, &error).await })
+    }
+
+    fn bundle(&self, id: Uuid) -> BoxFuture<'_, Result<Option<serde_json::Value>, AppError>> {
+        Box::pin(DataExportRepository::find_bundle(&self.pool, id))
+    }
+}
+
+/// Queues export jobs and signs their download URLs
+pub struct DataExportService {
+    store: Arc<dyn ExportStore>,
+    signing_key: Vec<u8>,
+    clock: Arc<dyn Clock>,
+}
+
+impl DataExportService {
+    /// Sign download URLs with a key derived from `master_secret`, so a
+    /// signature can never stand in for anything else signed with it
+    pub fn new(store: Arc<dyn ExportStore>, master_secret: &str) -> Self {
+        let mut mac = HmacSha256::new_from_slice(master_secret.as_bytes())
+            .expect("HMAC accepts any key size");
+        mac.update(SIGNING_KEY_CONTEXT);
+        Self {
+            store,
+            signing_key: mac.finalize().into_bytes().to_vec(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Expire bundles and URLs against `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Queue an export for `user_id`. While one is already queued, that job
+    /// is returned instead, flagged `false` so it isn't built twice.
+    pub async fn queue(&self, user_id: Uuid) -> Result<(DataExport, bool), AppError> {
+        self.store.create(user_id).await
+    }
+
+    /// Record a finished build: the bundle on success, the reason on failure
+    pub async fn finish(
+        &self,
+        id: Uuid,
+        bundle: Result<serde_json::Value, AppError>,
+    ) -> Result<(), AppError> {
+        match bundle {
+            Ok(bundle) => {
+                let expires_at = self.clock.now() + Duration::hours(BUNDLE_TTL_HOURS);
+                self.store.mark_ready(id, bundle, expires_at).await
+            }
+            Err(e) => {
+                tracing::error!(error = %e, export_id = %id, "Data export failed");
+                self.store.mark_failed(id, e.to_string()).await
+            }
+        }
+    }
+
+    /// Status of `user_id`'s export `id`, with a fresh download URL while it is ready.
+    /// Other users' jobs are reported as not found.
+    pub async fn status(&self, user_id: Uuid, id: Uuid) -> Result<DataExportResponse, AppError> {
+        let export = self
+            .store
+            .find(id)
+            .await?
+            .filter(|e| e.user_id == user_id)
+            .ok_or_else(|| AppError::not_found("Export"))?;
+
+        let now = self.clock.now();
+        let status = export.status_at(now);
+        let (download_url, download_url_expires_at) = if status == DataExportStatus::Ready {
+            // Never outlive the bundle itself
+            let url_expires = (now + Duration::minutes(DOWNLOAD_URL_TTL_MINUTES))
+                .min(export.expires_at.unwrap_or(now));
+            (
+                Some(self.download_url(id, url_expires.timestamp())),
+                Some(url_expires),
+            )
+        } else {
+            (None, None)
+        };
+
+        Ok(DataExportResponse {
+            job_id: export.id,
+            status,
+            created_at: export.created_at,
+            expires_at: export.expires_at,
+            download_url,
+            download_url_expires_at,
+        })
+    }
+
+    /// The bundle behind a signed URL. A bad signature, an expired URL or an
+    /// expired bundle all look the same to the caller.
+    pub async fn download(
+        &self,
+        id: Uuid,
+        expires: i64,
+        signature: &str,
+    ) -> Result<serde_json::Value, AppError> {
+        if !self.verify(id, expires, signature) || expires < self.clock.now().timestamp() {
+            return Err(AppError::not_found("Export"));
+        }
+
+        let export = self
+            .store
+            .find(id)
+            .await?
+            .ok_or_else(|| AppError::not_found("Export"))?;
+        if export.status_at(self.clock.now()) != DataExportStatus::Ready {
+            return Err(AppError::not_found("Export"));
+        }
+        self.store
+            .bundle(id)
+            .await?
+            .ok_or_else(|| AppError::not_found("Export"))
+    }
+
+    fn download_url(&self, id: Uuid, expires: i64) -> String {
+        format!(
+            "/v1/users/me/export/download/{}?expires={}&signature={}",
+            id,
+            expires,
+            self.sign(id, expires)
+        )
+    }
+
+    fn mac(&self, id: Uuid, expires: i64) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.signing_key).expect("HMAC accepts any key size");
+        mac.update(format!("data-export:{}:{}", id, expires).as_bytes());
+        mac
+    }
+
+    fn sign(&self, id: Uuid, expires: i64) -> String {
+        hex::encode(self.mac(id, expires).finalize().into_bytes())
+    }
+
+    /// Constant-time check of a hex signature
+    fn verify(&self, id: Uuid, expires: i64, signature: &str) -> bool {
+        hex::decode(signature).is_ok_and(|given| self.mac(id, expires).verify_slice(&given).is_ok())
+    }
+}
+
+/// Gather everything held about `user_id` into one JSON bundle
+pub async fn collect_user_data(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<serde_json::Value, AppError> {
+    let user = UserRepository::find_by_id(pool, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User"))?;
+    let sessions: Vec<SessionInfo> = TokenRepository::find_user_refresh_tokens(pool, user_id)
+        .await?
+        .into_iter()
+        .map(SessionInfo::from)
+        .collect();
+    let payment_method = PaymentMethodRepository::find_by_user_id(pool, user_id)
+        .await?
+        .map(PaymentMethodResponse::from);
+    let activity = AuditLogRepository::list_by_actor(pool, user_id, EXPORT_AUDIT_LOG_LIMIT).await?;
+    let ip_activity = UserIpActivityRepository::list_for_user(pool, user_id).await?;
+
+    Ok(serde_json::json!({
+        "generated_at": Utc::now(),
+        "profile": UserResponse::from(user),
+        "sessions": sessions,
+        "payment_method": payment_method,
+        "activity": activity,
+        "ip_activity": ip_activity,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// In-memory store for exercising the job lifecycle without a database
+    #[derive(Default)]
+    struct MemoryStore {
+        jobs: Mutex<HashMap<Uuid, (DataExport, Option<serde_json::Value>)>>,
+    }
+
+    impl ExportStore for MemoryStore {
+        fn create(&self, user_id: Uuid) -> BoxFuture<'_, Result<(DataExport, bool), AppError>> {
+            let mut jobs = self.jobs.lock().unwrap();
+            let pending = jobs
+                .values()
+                .map(|(e, _)| e)
+                .find(|e| e.user_id == user_id && e.status == "queued");
+            if let Some(export) = pending.cloned() {
+                return Box::pin(async move { Ok((export, false)) });
+            }
+            let export = DataExport {
+                id: Uuid::new_v4(),
+                user_id,
+                status: "queued".to_string(),
+                error: None,
+                created_at: Utc::now(),
+                completed_at: None,
+                expires_at: None,
+            };
+            jobs.insert(export.id, (export.clone(), None));
+            Box::pin(async move { Ok((export, true)) })
+        }
+
+        fn find(&self, id: Uuid) -> BoxFuture<'_, Result<Option<DataExport>, AppError>> {
+            let export = self.jobs.lock().unwrap().get(&id).map(|(e, _)| e.clone());
+            Box::pin(async move { Ok(export) })
+        }
+
+        fn mark_ready(
+            &self,
+            id: Uuid,
+            bundle: serde_json::Value,
+            expires_at: DateTime<Utc>,
+        ) -> BoxFuture<'_, Result<(), AppError>> {
+            if let Some((export, stored)) = self.jobs.lock().unwrap().get_mut(&id) {
+                export.status = "ready".to_string();
+                export.expires_at = Some(expires_at);
+                *stored = Some(bundle);
+            }
+            Box::pin(async { Ok(()) })
+        }
+
+        fn mark_failed(&self, id: Uuid, error: String) -> BoxFuture<'_, Result<(), AppError>> {
+            if let Some((export, _)) = self.jobs.lock().unwrap().get_mut(&id) {
+                export.status = "failed".to_string();
+                export.error = Some(error);
+            }
+            Box::pin(async { Ok(()) })
+        }
+
+        fn bundle(&self, id: Uuid) -> BoxFuture<'_, Result<Option<serde_json::Value>, AppError>> {
+            let bundle = self
+                .jobs
+                .lock()
+                .unwrap()
+                .get(&id)
+                .and_then(|(_, b)| b.clone());
+            Box::pin(async move { Ok(bundle) })
+        }
+    }
+
+    fn service(clock: &MockClock) -> DataExportService {
+        DataExportService::new(Arc::new(MemoryStore::default()), "test-secret")
+            .with_clock(Arc::new(clock.clone()))
+    }
+
+    /// Split a signed download URL into (expires, signature)
+    fn url_parts(url: &str) -> (i64, String) {
+        let query = url.split_once('?').unwrap().1;
+        let params: HashMap<_, _> = query.split('&').filter_map(|p| p.split_once('=')).collect();
+        (
+            params["expires"].parse().unwrap(),
+            params["signature"].to_string(),
+        )
+    }
+
+    #[actix_rt::test]
+    async fn export_goes_from_queued_to_ready_to_expired() {
+        let clock = MockClock::default();
+        let service = service(&clock);
+        let user_id = Uuid::new_v4();
+
+        let (job, _) = service.queue(user_id).await.unwrap();
+        let status = service.status(user_id, job.id).await.unwrap();
+        assert_eq!(status.status, DataExportStatus::Queued);
+        assert!(status.download_url.is_none());
+
+        let bundle = serde_json::json!({ "profile": { "email": "user@example.com" } });
+        service.finish(job.id, Ok(bundle.clone())).await.unwrap();
+        let status = service.status(user_id, job.id).await.unwrap();
+        assert_eq!(status.status, DataExportStatus::Ready);
+        let url = status.download_url.unwrap();
+        assert!(url.starts_with(&format!("/v1/users/me/export/download/{}?", job.id)));
+
+        let (expires, signature) = url_parts(&url);
+        assert_eq!(
+            service.download(job.id, expires, &signature).await.unwrap(),
+            bundle
+        );
+
+        // The bundle outlives one URL; a fresh status hands out a new one
+        clock.advance(Duration::minutes(DOWNLOAD_URL_TTL_MINUTES + 1));
+        assert!(service.download(job.id, expires, &signature).await.is_err());
+        let (expires, signature) = url_parts(
+            &service
+                .status(user_id, job.id)
+                .await
+                .unwrap()
+                .download_url
+                .unwrap(),
+        );
+        assert!(service.download(job.id, expires, &signature).await.is_ok());
+
+        clock.advance(Duration::hours(BUNDLE_TTL_HOURS));
+        let status = service.status(user_id, job.id).await.unwrap();
+        assert_eq!(status.status, DataExportStatus::Expired);
+        assert!(status.download_url.is_none());
+        assert!(service.download(job.id, expires, &signature).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn one_export_is_queued_at_a_time() {
+        let clock = MockClock::default();
+        let service = service(&clock);
+        let user_id = Uuid::new_v4();
+
+        let (job, created) = service.queue(user_id).await.unwrap();
+        assert!(created);
+        let (again, created) = service.queue(user_id).await.unwrap();
+        assert!(!created);
+        assert_eq!(again.id, job.id);
+
+        // Another user's export is independent
+        assert!(service.queue(Uuid::new_v4()).await.unwrap().1);
+
+        service
+            .finish(job.id, Ok(serde_json::json!({})))
+            .await
+            .unwrap();
+        let (next, created) = service.queue(user_id).await.unwrap();
+        assert!(created);
+        assert_ne!(next.id, job.id);
+    }
+
+    #[actix_rt::test]
+    async fn failed_build_is_reported_without_a_url() {
+        let clock = MockClock::default();
+        let service = service(&clock);
+        let user_id = Uuid::new_v4();
+
+        let (job, _) = service.queue(user_id).await.unwrap();
+        service
+            .finish(job.id, Err(AppError::internal("boom")))
+            .await
+            .unwrap();
+
+        let status = service.status(user_id, job.id).await.unwrap();
+        assert_eq!(status.status, DataExportStatus::Failed);
+        assert!(status.download_url.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn exports_are_private_and_urls_tamper_proof() {
+        let clock = MockClock::default();
+        let service = service(&clock);
+        let owner = Uuid::new_v4();
+
+        let (job, _) = service.queue(owner).await.unwrap();
+        service
+            .finish(job.id, Ok(serde_json::json!({})))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service.status(Uuid::new_v4(), job.id).await,
+            Err(AppError::NotFound { .. })
+        ));
+
+        let url = service
+            .status(owner, job.id)
+            .await
+            .unwrap()
+            .download_url
+            .unwrap();
+        let (expires, signature) = url_parts(&url);
+        // Stretching the expiry invalidates the signature
+        assert!(service
+            .download(job.id, expires + 3600, &signature)
+            .await
+            .is_err());
+        assert!(service.download(job.id, expires, "00ff").await.is_err());
+        assert!(service
+            .download(Uuid::new_v4(), expires, &signature)
+            .await
+            .is_err());
+    }
+}
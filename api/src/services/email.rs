@@ -12,6 +12,10 @@ use crate::errors::AppError;
 use crate::models::Feedback;
 
 /// Email service for sending transactional emails
+///
+/// Sends are attempted immediately. Maintenance mode is per application
+/// (it gates the child app, not this API), and there is no persistent email
+/// queue, so nothing is deferred while an app is in maintenance.
 pub struct EmailService {
     /// SMTP transport for sending emails
     transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
@@ -93,6 +97,19 @@ impl EmailService {
             )
             .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
 
+        templates
+            .add_raw_template(
+                "set_password.html",
+                include_str!("../../templates/emails/set_password.html"),
+            )
+            .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
+        templates
+            .add_raw_template(
+                "set_password.txt",
+                include_str!("../../templates/emails/set_password.txt"),
+            )
+            .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
+
         templates
             .add_raw_template(
                 "welcome.html",
@@ -106,6 +123,45 @@ impl EmailService {
             )
             .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
 
+        templates
+            .add_raw_template(
+                "first_login.html",
+                include_str!("../../templates/emails/first_login.html"),
+            )
+            .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
+        templates
+            .add_raw_template(
+                "first_login.txt",
+                include_str!("../../templates/emails/first_login.txt"),
+            )
+            .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
+
+        templates
+            .add_raw_template(
+                "unusual_login.html",
+                include_str!("../../templates/emails/unusual_login.html"),
+            )
+            .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
+        templates
+            .add_raw_template(
+                "unusual_login.txt",
+                include_str!("../../templates/emails/unusual_login.txt"),
+            )
+            .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
+
+        templates
+            .add_raw_template(
+                "session_revoked.html",
+                include_str!("../../templates/emails/session_revoked.html"),
+            )
+            .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
+        templates
+            .add_raw_template(
+                "session_revoked.txt",
+                include_str!("../../templates/emails/session_revoked.txt"),
+            )
+            .map_err(|e| AppError::internal(format!("Template error: {}", e)))?;
+
         templates
             .add_raw_template(
                 "account_created.html",
@@ -293,6 +349,18 @@ impl EmailService {
         }
     }
 
+    /// Connect to the SMTP relay and check it accepts the session.
+    /// Returns `Ok(false)` without connecting when sending is disabled.
+    pub async fn test_connection(&self) -> Result<bool, AppError> {
+        let Some(transport) = &self.transport else {
+            return Ok(false);
+        };
+        transport
+            .test_connection()
+            .await
+            .map_err(|e| AppError::internal(format!("SMTP connection error: {}", e)))
+    }
+
     /// Send an email
     async fn send_email(
         &self,
@@ -331,7 +399,7 @@ impl EmailService {
             transport
                 .send(email)
                 .await
-                .map_err(|e| AppError::internal(format!("Email send error: {}", e)))?;
+                .map_err(|e| AppError::external("email", format!("Email send error: {}", e)))?;
 
             tracing::info!(to = %to, subject = %subject, "Email sent successfully");
         } else {
@@ -433,6 +501,35 @@ impl EmailService {
         .await
     }
 
+    /// Send the link for setting a first password on a passwordless account
+    pub async fn send_set_password(&self, email: &str, token: &str) -> Result<(), AppError> {
+        let set_password_url = format!(
+            "{}/settings/set-password?token={}",
+            self.config.base_url, token
+        );
+
+        if !self.config.enabled {
+            tracing::info!(
+                email = %email,
+                link = %set_password_url,
+                "Set password link (dev mode - not sending email)"
+            );
+            return Ok(());
+        }
+
+        let mut context = self.base_context();
+        context.insert("set_password_url", &set_password_url);
+
+        let (html, text) = self.render_template("set_password", &context)?;
+        self.send_email(
+            email,
+            &format!("Set a password for your {} account", self.config.app_name),
+            html,
+            text,
+        )
+        .await
+    }
+
     /// Send account creation email
     pub async fn send_account_created(&self, email: &str) -> Result<(), AppError> {
         if !self.config.enabled {
@@ -479,6 +576,76 @@ impl EmailService {
         .await
     }
 
+    /// Alert a user to a sign-in implying implausible travel
+    pub async fn send_unusual_login_alert(
+        &self,
+        email: &str,
+        ip_address: &str,
+        distance_km: i64,
+    ) -> Result<(), AppError> {
+        if !self.config.enabled {
+            tracing::info!(
+                email = %email,
+                ip = %ip_address,
+                distance_km = distance_km,
+                "Unusual login alert email (dev mode - not sending)"
+            );
+            return Ok(());
+        }
+
+        let mut context = self.base_context();
+        context.insert("ip_address", ip_address);
+        context.insert("distance_km", &distance_km);
+        context.insert(
+            "security_url",
+            &format!("{}/settings", self.config.base_url),
+        );
+
+        let (html, text) = self.render_template("unusual_login", &context)?;
+        self.send_email(
+            email,
+            &format!("Unusual sign-in to your {} account", self.config.app_name),
+            html,
+            text,
+        )
+        .await
+    }
+
+    /// Tell a user a session was revoked because its refresh token was replayed
+    pub async fn send_session_revoked_alert(
+        &self,
+        email: &str,
+        ip_address: Option<&str>,
+    ) -> Result<(), AppError> {
+        if !self.config.enabled {
+            tracing::info!(
+                email = %email,
+                ip = ?ip_address,
+                "Session revoked alert email (dev mode - not sending)"
+            );
+            return Ok(());
+        }
+
+        let mut context = self.base_context();
+        context.insert("ip_address", &ip_address);
+        context.insert(
+            "security_url",
+            &format!("{}/settings", self.config.base_url),
+        );
+
+        let (html, text) = self.render_template("session_revoked", &context)?;
+        self.send_email(
+            email,
+            &format!(
+                "A session on your {} account was revoked",
+                self.config.app_name
+            ),
+            html,
+            text,
+        )
+        .await
+    }
+
     /// Send welcome email after membership activation
     pub async fn send_welcome(&self, email: &str, price_cents: i32) -> Result<(), AppError> {
         if !self.config.enabled {
@@ -503,6 +670,29 @@ impl EmailService {
         .await
     }
 
+    /// Send the one-time welcome email after a user's first login
+    pub async fn send_first_login_welcome(&self, email: &str) -> Result<(), AppError> {
+        if !self.config.enabled {
+            tracing::info!(email = %email, "First login welcome email (dev mode)");
+            return Ok(());
+        }
+
+        let mut context = self.base_context();
+        context.insert(
+            "dashboard_url",
+            &format!("{}/dashboard", self.config.base_url),
+        );
+
+        let (html, text) = self.render_template("first_login", &context)?;
+        self.send_email(
+            email,
+            &format!("Welcome to {}!", self.config.app_name),
+            html,
+            text,
+        )
+        .await
+    }
+
     /// Send payment failed email
     pub async fn send_payment_failed(
         &self,
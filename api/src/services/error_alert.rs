@@ -0,0 +1,248 @@
+//! Push alerts to an ops channel on server errors
+//!
+//! The [`ErrorAlerts`](crate::middleware::ErrorAlerts) middleware reports 5xx
+//! responses here. Alerts carry the request id, status, error code and route
+//! pattern only: error messages can hold SQL or upstream detail and stay in
+//! the logs, where the request id finds them. Each error code alerts at most
+//! once per cooldown; repeats in between are counted and reported with the
+//! next alert, so an outage produces a handful of messages rather than one
+//! per failing request.
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::clock::{Clock, SystemClock};
+use crate::http::HttpClient;
+
+/// A 5xx response worth telling someone about
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerErrorAlert {
+    pub request_id: String,
+    pub status: u16,
+    /// Machine-readable code, e.g. `DATABASE_ERROR`
+    pub code: String,
+    /// Matched route pattern (`/v1/users/{user_id}`), never the raw path
+    pub route: String,
+    /// Errors with this code dropped by the cooldown since the last alert
+    pub suppressed: u64,
+    pub at: DateTime<Utc>,
+}
+
+/// Where alerts are delivered
+pub trait AlertSink: Send + Sync {
+    /// Deliver `alert` without blocking the request that raised it
+    fn send(&self, alert: ServerErrorAlert);
+}
+
+/// Posts alerts to a webhook: a Slack incoming webhook gets a `text`
+/// message, any other URL the alert as JSON
+pub struct WebhookAlertSink {
+    http: HttpClient,
+    url: String,
+    slack: bool,
+}
+
+impl WebhookAlertSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        let url = url.into();
+        let slack = url::Url::parse(&url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h == "hooks.slack.com"))
+            .unwrap_or(false);
+        Self {
+            http: HttpClient::default(),
+            url,
+            slack,
+        }
+    }
+
+    fn payload(&self, alert: &ServerErrorAlert) -> serde_json::Value {
+        if self.slack {
+            let mut text = format!(
+                ":rotating_light: {} {} on `{}` (request `{}`)",
+                alert.status, alert.code, alert.route, alert.request_id
+            );
+            if alert.suppressed > 0 {
+                text.push_str(&format!(", {} more since the last alert", alert.suppressed));
+            }
+            json!({ "text": text })
+        } else {
+            json!({
+                "event": "server_error",
+                "request_id": alert.request_id,
+                "status": alert.status,
+                "code": alert.code,
+                "route": alert.route,
+                "suppressed": alert.suppressed,
+                "timestamp": alert.at,
+            })
+        }
+    }
+}
+
+impl AlertSink for WebhookAlertSink {
+    fn send(&self, alert: ServerErrorAlert) {
+        let request = self
+            .http
+            .inner()
+            .post(&self.url)
+            .json(&self.payload(&alert));
+        let http = self.http.clone();
+        tokio::spawn(async move {
+            match http.send(request).await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => {
+                    tracing::warn!(status = %response.status(), "Error alert webhook rejected the alert");
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to deliver error alert"),
+            }
+        });
+    }
+}
+
+/// Rate-limits server error alerts per error code
+pub struct ErrorAlerter {
+    sink: Arc<dyn AlertSink>,
+    cooldown: Duration,
+    clock: Arc<dyn Clock>,
+    /// Per code: when it last alerted and how many were dropped since
+    last_sent: Mutex<HashMap<String, (DateTime<Utc>, u64)>>,
+}
+
+impl ErrorAlerter {
+    pub fn new(sink: Arc<dyn AlertSink>, cooldown: Duration) -> Self {
+        Self {
+            sink,
+            cooldown,
+            clock: Arc::new(SystemClock),
+            last_sent: Mutex::default(),
+        }
+    }
+
+    /// Apply the cooldown against `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Report a 5xx. Returns whether an alert was dispatched; `false` means
+    /// the code is cooling down and the error was counted instead.
+    pub fn report(&self, request_id: &str, status: u16, code: &str, route: &str) -> bool {
+        let now = self.clock.now();
+        let suppressed = {
+            let mut last_sent = self.last_sent.lock().expect("error alert lock poisoned");
+            match last_sent.get_mut(code) {
+                Some((sent_at, dropped)) if now - *sent_at < self.cooldown => {
+                    *dropped += 1;
+                    return false;
+                }
+                Some((sent_at, dropped)) => {
+                    *sent_at = now;
+                    std::mem::take(dropped)
+                }
+                None => {
+                    last_sent.insert(code.to_string(), (now, 0));
+                    0
+                }
+            }
+        };
+
+        self.sink.send(ServerErrorAlert {
+            request_id: request_id.to_string(),
+            status,
+            code: code.to_string(),
+            route: route.to_string(),
+            suppressed,
+            at: now,
+        });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<ServerErrorAlert>>);
+
+    impl AlertSink for RecordingSink {
+        fn send(&self, alert: ServerErrorAlert) {
+            self.0.lock().unwrap().push(alert);
+        }
+    }
+
+    fn alerter() -> (ErrorAlerter, Arc<RecordingSink>, MockClock) {
+        let sink = Arc::new(RecordingSink::default());
+        let clock = MockClock::default();
+        let alerter = ErrorAlerter::new(sink.clone(), Duration::minutes(5))
+            .with_clock(Arc::new(clock.clone()));
+        (alerter, sink, clock)
+    }
+
+    #[test]
+    fn repeated_errors_alert_once_per_cooldown() {
+        let (alerter, sink, clock) = alerter();
+
+        assert!(alerter.report("req_1", 500, "DATABASE_ERROR", "/v1/users/me"));
+        for n in 2..=10 {
+            assert!(!alerter.report(&format!("req_{n}"), 500, "DATABASE_ERROR", "/v1/users/me"));
+        }
+        assert_eq!(sink.0.lock().unwrap().len(), 1);
+
+        clock.advance(Duration::minutes(5));
+        assert!(alerter.report("req_11", 500, "DATABASE_ERROR", "/v1/users/me"));
+
+        let alerts = sink.0.lock().unwrap();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].request_id, "req_1");
+        assert_eq!(alerts[0].suppressed, 0);
+        assert_eq!(alerts[1].request_id, "req_11");
+        assert_eq!(alerts[1].suppressed, 9, "dropped repeats are reported");
+    }
+
+    #[test]
+    fn each_error_code_has_its_own_cooldown() {
+        let (alerter, sink, _clock) = alerter();
+
+        assert!(alerter.report("req_1", 500, "DATABASE_ERROR", "/v1/users/me"));
+        assert!(alerter.report("req_2", 500, "INTERNAL_ERROR", "/v1/users/me"));
+        assert!(alerter.report("req_3", 502, "STRIPE_ERROR", "/v1/membership"));
+        assert!(!alerter.report("req_4", 500, "INTERNAL_ERROR", "/v1/admin/users"));
+
+        let codes: Vec<_> = sink
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|a| a.code.clone())
+            .collect();
+        assert_eq!(codes, ["DATABASE_ERROR", "INTERNAL_ERROR", "STRIPE_ERROR"]);
+    }
+
+    #[test]
+    fn slack_webhooks_get_a_text_message() {
+        let alert = ServerErrorAlert {
+            request_id: "req_1".to_string(),
+            status: 500,
+            code: "DATABASE_ERROR".to_string(),
+            route: "/v1/users/me".to_string(),
+            suppressed: 3,
+            at: Utc::now(),
+        };
+
+        let slack = WebhookAlertSink::new("https://hooks.slack.com/services/T0/B0/x");
+        let text = slack.payload(&alert)["text"].as_str().unwrap().to_string();
+        assert!(text.contains("DATABASE_ERROR") && text.contains("req_1"));
+        assert!(text.contains("3 more"));
+
+        let generic = WebhookAlertSink::new("https://alerts.example.com/hook");
+        let body = generic.payload(&alert);
+        assert_eq!(body["code"], "DATABASE_ERROR");
+        assert_eq!(body["request_id"], "req_1");
+        assert!(body.get("text").is_none());
+    }
+}
@@ -0,0 +1,227 @@
+//! IP geolocation and travel-speed helpers for login anomaly checks
+//!
+//! Lookups go through the `GeoResolver` trait so the login path can be tested
+//! with a fixed table instead of a live GeoIP provider.
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::http::{HttpClient, RetryPolicy};
+
+/// Mean Earth radius used by the haversine formula
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Upper bound on one lookup. A slow provider counts as "location unknown"
+/// rather than holding up a login.
+const LOOKUP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A resolved location in decimal degrees
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct GeoPoint {
+    #[serde(alias = "lat")]
+    pub latitude: f64,
+    #[serde(alias = "lon")]
+    pub longitude: f64,
+}
+
+/// Resolves an IP address to an approximate location
+pub trait GeoResolver: Send + Sync {
+    /// `None` when the address cannot be located (private range, lookup failure)
+    fn resolve(&self, ip: IpAddr) -> BoxFuture<'_, Option<GeoPoint>>;
+}
+
+/// Resolver backed by an HTTP GeoIP API.
+///
+/// `url_template` must contain `{ip}`; the JSON response must carry
+/// `latitude`/`longitude` (or `lat`/`lon`), as ipapi.co and ip-api.com do.
+/// Each lookup is a single attempt capped at [`LOOKUP_TIMEOUT`].
+pub struct HttpGeoResolver {
+    http: HttpClient,
+    url_template: String,
+}
+
+impl HttpGeoResolver {
+    pub fn new(url_template: impl Into<String>) -> Self {
+        Self {
+            http: HttpClient::new(RetryPolicy {
+                max_retries: 0,
+                ..RetryPolicy::default()
+            }),
+            url_template: url_template.into(),
+        }
+    }
+}
+
+impl GeoResolver for HttpGeoResolver {
+    fn resolve(&self, ip: IpAddr) -> BoxFuture<'_, Option<GeoPoint>> {
+        Box::pin(async move {
+            if !is_public(ip) {
+                return None;
+            }
+            let url = self.url_template.replace("{ip}", &ip.to_string());
+            let response = match self
+                .http
+                .send(self.http.inner().get(&url).timeout(LOOKUP_TIMEOUT))
+                .await
+            {
+                Ok(response) if response.status().is_success() => response,
+                Ok(response) => {
+                    tracing::debug!(status = %response.status(), "GeoIP lookup rejected");
+                    return None;
+                }
+                Err(e) => {
+                    tracing::debug!(error = %e, "GeoIP lookup failed");
+                    return None;
+                }
+            };
+            response.json::<GeoPoint>().await.ok()
+        })
+    }
+}
+
+/// Private, loopback and link-local addresses have no meaningful location
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified())
+        }
+        IpAddr::V6(v6) => {
+            let unique_local = (v6.segments()[0] & 0xfe00) == 0xfc00;
+            let link_local = (v6.segments()[0] & 0xffc0) == 0xfe80;
+            !(v6.is_loopback() || v6.is_unspecified() || unique_local || link_local)
+        }
+    }
+}
+
+/// Great-circle distance between two points
+pub fn haversine_km(a: GeoPoint, b: GeoPoint) -> f64 {
+    let (lat1, lat2) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+/// Result of comparing two logins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TravelAssessment {
+    pub distance_km: f64,
+    /// Implied speed; infinite when the logins share a timestamp
+    pub speed_kmh: f64,
+    pub impossible: bool,
+}
+
+/// Compare a login against the previous one.
+///
+/// Logins closer than `min_distance_km` are never flagged, which absorbs
+/// GeoIP jitter between nearby cities and mobile carrier gateways.
+pub fn assess_travel(
+    previous: (GeoPoint, DateTime<Utc>),
+    current: (GeoPoint, DateTime<Utc>),
+    max_speed_kmh: f64,
+    min_distance_km: f64,
+) -> TravelAssessment {
+    let distance_km = haversine_km(previous.0, current.0);
+    let hours = (current.1 - previous.1).num_seconds().abs() as f64 / 3600.0;
+    let speed_kmh = if hours > 0.0 {
+        distance_km / hours
+    } else if distance_km > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    };
+
+    TravelAssessment {
+        distance_km,
+        speed_kmh,
+        impossible: distance_km >= min_distance_km && speed_kmh > max_speed_kmh,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const LONDON: GeoPoint = GeoPoint {
+        latitude: 51.5074,
+        longitude: -0.1278,
+    };
+    const NEW_YORK: GeoPoint = GeoPoint {
+        latitude: 40.7128,
+        longitude: -74.0060,
+    };
+    const OXFORD: GeoPoint = GeoPoint {
+        latitude: 51.7520,
+        longitude: -1.2577,
+    };
+
+    #[test]
+    fn haversine_matches_known_distance() {
+        let d = haversine_km(LONDON, NEW_YORK);
+        assert!((d - 5570.0).abs() < 15.0, "got {}", d);
+        assert_eq!(haversine_km(LONDON, LONDON), 0.0);
+    }
+
+    #[test]
+    fn transatlantic_hop_in_minutes_is_impossible() {
+        let t0 = Utc::now();
+        let a = assess_travel(
+            (LONDON, t0),
+            (NEW_YORK, t0 + Duration::minutes(30)),
+            1000.0,
+            500.0,
+        );
+        assert!(a.impossible);
+        assert!(a.speed_kmh > 10_000.0);
+    }
+
+    #[test]
+    fn transatlantic_hop_after_a_flight_is_plausible() {
+        let t0 = Utc::now();
+        let a = assess_travel(
+            (LONDON, t0),
+            (NEW_YORK, t0 + Duration::hours(8)),
+            1000.0,
+            500.0,
+        );
+        assert!(!a.impossible);
+        assert!((a.speed_kmh - a.distance_km / 8.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn nearby_logins_are_never_flagged() {
+        let t0 = Utc::now();
+        let a = assess_travel(
+            (LONDON, t0),
+            (OXFORD, t0 + Duration::seconds(10)),
+            1000.0,
+            500.0,
+        );
+        assert!(a.speed_kmh > 1000.0);
+        assert!(!a.impossible);
+    }
+
+    #[test]
+    fn simultaneous_logins_have_infinite_speed() {
+        let t0 = Utc::now();
+        let far = assess_travel((LONDON, t0), (NEW_YORK, t0), 1000.0, 500.0);
+        assert!(far.speed_kmh.is_infinite() && far.impossible);
+
+        let same = assess_travel((LONDON, t0), (LONDON, t0), 1000.0, 500.0);
+        assert_eq!(same.speed_kmh, 0.0);
+        assert!(!same.impossible);
+    }
+
+    #[test]
+    fn private_addresses_are_not_public() {
+        assert!(!is_public("10.0.0.1".parse().unwrap()));
+        assert!(!is_public("127.0.0.1".parse().unwrap()));
+        assert!(!is_public("fd00::1".parse().unwrap()));
+        assert!(is_public("203.0.113.7".parse().unwrap()));
+    }
+}
@@ -0,0 +1,274 @@
+//! Membership state derived from Stripe subscriptions
+//!
+//! Shared by the Stripe webhooks, which apply one event at a time, and the
+//! admin reconcile action, which resets a member from their live
+//! subscription.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::config::TierConfig;
+use crate::errors::AppError;
+use crate::models::{
+    MembershipDrift, MembershipReconciliation, MembershipStatus, StripeSubscriptionResponse,
+    SubscriptionTier, User,
+};
+use crate::repositories::UserRepository;
+
+/// Map a Stripe subscription status to the user's membership status.
+///
+/// `trialing` grants access only while `trial_end` is in the future; once the
+/// trial converts Stripe moves the subscription to `active` or `past_due`.
+pub fn membership_status_for_subscription(
+    status: &str,
+    trial_end: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> MembershipStatus {
+    match status {
+        "active" => MembershipStatus::Active,
+        "trialing" => match trial_end {
+            Some(end) if end > now => MembershipStatus::Active,
+            _ => MembershipStatus::PastDue,
+        },
+        "past_due" => MembershipStatus::PastDue,
+        "canceled" | "incomplete_expired" => MembershipStatus::Canceled,
+        _ => MembershipStatus::Active,
+    }
+}
+
+/// Map a Stripe product ID to its corresponding `SubscriptionTier` using the current tier config.
+/// Returns `None` if the product ID does not match any configured mapping, meaning tier is left
+/// unchanged and only `subscription_status` is updated by the caller.
+pub fn resolve_tier_for_product(product_id: &str, tc: &TierConfig) -> Option<SubscriptionTier> {
+    if tc.lifetime_product_id.as_deref() == Some(product_id) {
+        return Some(SubscriptionTier::Lifetime);
+    }
+    if tc.early_adopter_product_id.as_deref() == Some(product_id) {
+        return Some(SubscriptionTier::EarlyAdopter);
+    }
+    if tc.standard_product_id.as_deref() == Some(product_id) {
+        return Some(SubscriptionTier::Standard);
+    }
+    None
+}
+
+/// Bring `user`'s local membership back in line with `subscription`, the
+/// Stripe subscription that decides it (see
+/// [`crate::services::StripeService::get_customer_subscription`]; `None` if
+/// the customer has none at all), for when a missed webhook has let the two drift apart.
+///
+/// Stripe is authoritative here, so the status is set directly rather than
+/// through the transition rules webhooks follow. Each corrected field is
+/// logged and returned.
+pub async fn reconcile_membership(
+    pool: &PgPool,
+    tc: &TierConfig,
+    user: &User,
+    subscription: Option<&StripeSubscriptionResponse>,
+) -> Result<MembershipReconciliation, AppError> {
+    let local_status = user.membership_status_enum();
+
+    let (status, tier, trial_end) = match subscription {
+        Some(sub) => {
+            let trial_end = sub.trial_end.and_then(|ts| DateTime::from_timestamp(ts, 0));
+            let status = membership_status_for_subscription(&sub.status, trial_end, Utc::now());
+            if status == MembershipStatus::Canceled {
+                // An ended subscription grants nothing; lifetime access
+                // never depended on it
+                let status = if user.lifetime_member {
+                    local_status.clone()
+                } else {
+                    status
+                };
+                (status, None, None)
+            } else {
+                let tier = sub
+                    .items
+                    .first()
+                    .and_then(|item| resolve_tier_for_product(&item.product_id, tc));
+                let trial_end = (sub.status == "trialing").then_some(trial_end).flatten();
+                (status, tier, trial_end)
+            }
+        }
+        // A paying member whose customer has no subscription at all missed
+        // the cancellation. Lifetime members never had one.
+        None if !user.lifetime_member
+            && matches!(
+                local_status,
+                MembershipStatus::Active | MembershipStatus::PastDue
+            ) =>
+        {
+            (MembershipStatus::Canceled, None, None)
+        }
+        None => (local_status.clone(), None, None),
+    };
+    let tier = tier.filter(|t| t.as_str() != user.subscription_tier);
+    // A tier change clears the trial; otherwise only a trialing subscription sets it
+    let trial_ends_at = if trial_end.is_some() || tier.is_some() {
+        trial_end
+    } else {
+        user.trial_ends_at
+    };
+
+    let mut corrected = Vec::new();
+    let mut tx = pool.begin().await?;
+    if status != local_status {
+        UserRepository::update_membership_status(&mut *tx, user.id, status.clone()).await?;
+        corrected.push(MembershipDrift {
+            field: "membership_status".to_string(),
+            local: Some(user.membership_status.clone()),
+            stripe: Some(status.as_str().to_string()),
+        });
+    }
+    if let Some(ref tier) = tier {
+        UserRepository::upgrade_subscription_tier(&mut *tx, user.id, tier).await?;
+        corrected.push(MembershipDrift {
+            field: "subscription_tier".to_string(),
+            local: Some(user.subscription_tier.clone()),
+            stripe: Some(tier.as_str().to_string()),
+        });
+    }
+    if trial_ends_at != user.trial_ends_at {
+        UserRepository::set_trial_ends_at(&mut *tx, user.id, trial_ends_at).await?;
+        corrected.push(MembershipDrift {
+            field: "trial_ends_at".to_string(),
+            local: user.trial_ends_at.map(|t| t.to_rfc3339()),
+            stripe: trial_ends_at.map(|t| t.to_rfc3339()),
+        });
+    }
+    tx.commit().await?;
+
+    for drift in &corrected {
+        tracing::info!(
+            user_id = %user.id,
+            field = %drift.field,
+            local = ?drift.local,
+            stripe = ?drift.stripe,
+            "Corrected membership drift from Stripe"
+        );
+    }
+
+    Ok(MembershipReconciliation {
+        user_id: user.id,
+        stripe_subscription_id: subscription.map(|sub| sub.id.clone()),
+        stripe_status: subscription.map(|sub| sub.status.clone()),
+        current_period_end: subscription
+            .and_then(|sub| DateTime::from_timestamp(sub.current_period_end, 0)),
+        corrected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn trialing_grants_access_until_trial_end() {
+        let now = Utc::now();
+        assert_eq!(
+            membership_status_for_subscription("trialing", Some(now + Duration::days(14)), now),
+            MembershipStatus::Active
+        );
+        assert_eq!(
+            membership_status_for_subscription("trialing", Some(now - Duration::seconds(1)), now),
+            MembershipStatus::PastDue
+        );
+        assert_eq!(
+            membership_status_for_subscription("trialing", None, now),
+            MembershipStatus::PastDue
+        );
+    }
+
+    #[test]
+    fn other_statuses_map_directly() {
+        let now = Utc::now();
+        for (status, expected) in [
+            ("active", MembershipStatus::Active),
+            ("past_due", MembershipStatus::PastDue),
+            ("canceled", MembershipStatus::Canceled),
+            ("incomplete_expired", MembershipStatus::Canceled),
+            ("incomplete", MembershipStatus::Active),
+        ] {
+            assert_eq!(
+                membership_status_for_subscription(status, None, now),
+                expected,
+                "{status}"
+            );
+        }
+    }
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    #[actix_rt::test]
+    async fn reconcile_corrects_drifted_status_from_stripe() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let customer_id = format!("cus_{}", uuid::Uuid::new_v4().simple());
+        let user_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO users (email, password_hash, stripe_customer_id, subscription_status) \
+             VALUES ($1, 'x', $2, 'canceled') RETURNING id",
+        )
+        .bind(format!("{}@example.com", customer_id))
+        .bind(&customer_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let user = UserRepository::find_by_id(&pool, user_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Stripe still has the subscription active; the cancel was never real
+        let live = StripeSubscriptionResponse {
+            id: "sub_live".to_string(),
+            status: "active".to_string(),
+            current_period_start: 1_700_000_000,
+            current_period_end: 1_702_592_000,
+            cancel_at_period_end: false,
+            trial_end: None,
+            items: Vec::new(),
+        };
+        let tc = TierConfig::from_env();
+        let reconciliation = reconcile_membership(&pool, &tc, &user, Some(&live))
+            .await
+            .unwrap();
+        assert_eq!(
+            reconciliation.corrected,
+            vec![MembershipDrift {
+                field: "membership_status".to_string(),
+                local: Some("canceled".to_string()),
+                stripe: Some("active".to_string()),
+            }]
+        );
+        let user = UserRepository::find_by_id(&pool, user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.membership_status, "active");
+
+        // In sync now, so a second pass changes nothing
+        let again = reconcile_membership(&pool, &tc, &user, Some(&live))
+            .await
+            .unwrap();
+        assert!(again.corrected.is_empty());
+
+        // Without any subscription the paying member is canceled
+        let gone = reconcile_membership(&pool, &tc, &user, None).await;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            gone.unwrap().corrected[0].stripe.as_deref(),
+            Some("canceled")
+        );
+    }
+}
@@ -4,20 +4,26 @@
 
 pub mod auth;
 pub mod blob_cache;
+pub mod captcha;
+pub mod data_export;
 pub mod download_cache;
 pub mod download_limiter;
 pub mod email;
 pub mod encryption;
+pub mod error_alert;
 pub mod forgejo;
 pub mod forgejo_registry;
+pub mod geo;
 pub mod jwt;
 pub mod manifest_cache;
+pub mod membership;
 pub mod oci_limiter;
 pub mod oci_token;
 pub mod oidc_keys;
 pub mod oidc_provider;
 pub mod password;
 pub mod release_cache;
+pub mod scheduler;
 pub mod stripe;
 pub mod totp;
 pub mod webhook;
@@ -25,20 +31,25 @@ pub mod webhook;
 // Re-export service types
 pub use auth::{AcceptInviteResult, AuthService, AuthTokens, LoginResult, MagicLinkResult};
 pub use blob_cache::{BlobCache, BlobHandle};
+pub use captcha::CaptchaService;
+pub use data_export::{DataExportService, ExportStore, PgExportStore};
 pub use download_cache::{DownloadCache, DownloadCacheError};
 pub use download_limiter::{DownloadGuard, DownloadLimiter, LimitDenial};
 pub use email::EmailService;
 pub use encryption::EncryptionKeySet;
+pub use error_alert::{AlertSink, ErrorAlerter, WebhookAlertSink};
 pub use forgejo::{ForgejoClient, ForgejoError};
 pub use forgejo_registry::{ForgejoRegistryClient, RegistryError};
+pub use geo::{GeoPoint, GeoResolver, HttpGeoResolver};
 pub use jwt::{
-    AccessTokenClaims, JwtConfig, JwtService, RefreshTokenClaims, TwoFactorChallengeClaims,
+    AccessTokenClaims, JwtConfig, JwtService, RefreshTokenClaims, RetiredKey, SigningKey,
+    TwoFactorChallengeClaims,
 };
 pub use manifest_cache::ManifestCache;
 pub use oci_limiter::{OciLimitDenial, OciLimiter, OciPullGuard};
 pub use oci_token::{OciTokenService, RegistryTokenClaims, REGISTRY_AUDIENCE};
 pub use password::PasswordService;
 pub use release_cache::ReleaseCache;
-pub use stripe::{StripeConfig, StripeService};
+pub use stripe::{CheckoutMode, StripeConfig, StripeService};
 pub use totp::TotpService;
 pub use webhook::WebhookService;
@@ -29,6 +29,7 @@ pub struct RegistryTokenClaims {
 pub struct OciTokenService {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
+    algorithm: Algorithm,
     issuer: String,
     ttl: Duration,
 }
@@ -36,8 +37,9 @@ pub struct OciTokenService {
 impl OciTokenService {
     pub fn new(jwt_config: &JwtConfig, ttl_secs: u64) -> Self {
         Self {
-            encoding_key: jwt_config.encoding_key.clone(),
-            decoding_key: jwt_config.decoding_key.clone(),
+            encoding_key: jwt_config.signing_key.encoding_key.clone(),
+            decoding_key: jwt_config.signing_key.decoding_key.clone(),
+            algorithm: jwt_config.signing_key.algorithm,
             issuer: jwt_config.issuer.clone(),
             ttl: Duration::seconds(ttl_secs as i64),
         }
@@ -53,12 +55,12 @@ impl OciTokenService {
             exp: (now + self.ttl).timestamp(),
             iss: self.issuer.clone(),
         };
-        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)
+        encode(&Header::new(self.algorithm), &claims, &self.encoding_key)
             .map_err(|_| OciError::Internal)
     }
 
     pub fn verify(&self, token: &str) -> Result<RegistryTokenClaims, OciError> {
-        let mut validation = Validation::new(Algorithm::HS256);
+        let mut validation = Validation::new(self.algorithm);
         validation.set_audience(&[REGISTRY_AUDIENCE]);
         validation.set_issuer(&[&self.issuer]);
         validation.leeway = 0;
@@ -107,7 +109,7 @@ mod tests {
         let bad = encode(
             &Header::new(Algorithm::HS256),
             &bad_claims,
-            &cfg.encoding_key,
+            &cfg.signing_key.encoding_key,
         )
         .unwrap();
 
@@ -127,7 +129,12 @@ mod tests {
             exp: past.timestamp(),
             iss: "a8n".into(),
         };
-        let token = encode(&Header::new(Algorithm::HS256), &claims, &cfg.encoding_key).unwrap();
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &cfg.signing_key.encoding_key,
+        )
+        .unwrap();
 
         let svc = svc();
         assert!(matches!(svc.verify(&token), Err(OciError::Unauthorized)));
@@ -155,17 +155,7 @@ impl OidcKeySet {
 ///     03 21 00              — BIT STRING (33 bytes, 0 unused bits)
 ///       <32 bytes>          — raw public key
 pub fn ed25519_public_key_x(pub_pem: &[u8]) -> Result<String, String> {
-    let pem_str = std::str::from_utf8(pub_pem)
-        .map_err(|_| "public key PEM is not valid UTF-8".to_string())?;
-
-    let b64_body: String = pem_str
-        .lines()
-        .filter(|l| !l.starts_with("-----"))
-        .collect();
-
-    let der = base64::engine::general_purpose::STANDARD
-        .decode(b64_body.trim())
-        .map_err(|e| format!("base64 decode failed: {e}"))?;
+    let der = pem_der(pub_pem)?;
 
     if der.len() < 44 {
         return Err(format!(
@@ -192,6 +182,94 @@ pub fn ed25519_public_key_x(pub_pem: &[u8]) -> Result<String, String> {
     Ok(URL_SAFE_NO_PAD.encode(key_bytes))
 }
 
+/// Extract the base64url-encoded modulus `n` and exponent `e` from an RSA
+/// public key PEM, either SubjectPublicKeyInfo (`BEGIN PUBLIC KEY`) or
+/// PKCS#1 (`BEGIN RSA PUBLIC KEY`).
+///
+/// SPKI wraps the PKCS#1 key in a BIT STRING:
+///   30 ..                   — SEQUENCE
+///     30 0D 06 09 2A 86 48 86 F7 0D 01 01 01 05 00 — rsaEncryption, NULL
+///     03 .. 00              — BIT STRING (0 unused bits)
+///       30 .. 02 .. <n> 02 .. <e> — RSAPublicKey
+pub fn rsa_public_key_components(pub_pem: &[u8]) -> Result<(String, String), String> {
+    let der = pem_der(pub_pem)?;
+    let (tag, outer, _) = der_tlv(&der, 0)?;
+    if tag != 0x30 {
+        return Err(format!("expected a DER SEQUENCE, found tag {tag:02X}"));
+    }
+
+    let (first_tag, algorithm, next) = der_tlv(outer, 0)?;
+    let rsa_key = if first_tag == 0x02 {
+        // PKCS#1: the outer SEQUENCE is already { n, e }
+        outer
+    } else {
+        const RSA_ENCRYPTION_OID: [u8; 11] = [
+            0x06, 0x09, 0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01,
+        ];
+        if first_tag != 0x30 || !algorithm.starts_with(&RSA_ENCRYPTION_OID) {
+            return Err("public key is not an RSA key".to_string());
+        }
+        let (bit_tag, bits, _) = der_tlv(outer, next)?;
+        if bit_tag != 0x03 || bits.first() != Some(&0) {
+            return Err("malformed RSA SPKI BIT STRING".to_string());
+        }
+        let (seq_tag, inner, _) = der_tlv(&bits[1..], 0)?;
+        if seq_tag != 0x30 {
+            return Err("malformed RSAPublicKey".to_string());
+        }
+        inner
+    };
+
+    let (n_tag, n, next) = der_tlv(rsa_key, 0)?;
+    let (e_tag, e, _) = der_tlv(rsa_key, next)?;
+    if n_tag != 0x02 || e_tag != 0x02 {
+        return Err("malformed RSAPublicKey integers".to_string());
+    }
+    // DER integers carry a leading zero when the high bit is set; JWK does not
+    let unsigned = |bytes: &[u8]| -> String {
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        URL_SAFE_NO_PAD.encode(&bytes[start..])
+    };
+    Ok((unsigned(n), unsigned(e)))
+}
+
+/// Decode the base64 body of a PEM block into DER bytes.
+pub fn pem_der(pem: &[u8]) -> Result<Vec<u8>, String> {
+    let pem_str =
+        std::str::from_utf8(pem).map_err(|_| "public key PEM is not valid UTF-8".to_string())?;
+
+    let b64_body: String = pem_str
+        .lines()
+        .filter(|l| !l.starts_with("-----"))
+        .collect();
+
+    base64::engine::general_purpose::STANDARD
+        .decode(b64_body.trim())
+        .map_err(|e| format!("base64 decode failed: {e}"))
+}
+
+/// Read the DER element starting at `pos`, returning its tag, its contents
+/// and the position just past it.
+fn der_tlv(der: &[u8], pos: usize) -> Result<(u8, &[u8], usize), String> {
+    let truncated = || "truncated DER".to_string();
+    let tag = *der.get(pos).ok_or_else(truncated)?;
+    let first = *der.get(pos + 1).ok_or_else(truncated)?;
+    let (len, header) = if first & 0x80 == 0 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7F) as usize;
+        if count == 0 || count > 4 {
+            return Err(format!("unsupported DER length of {count} bytes"));
+        }
+        let bytes = der.get(pos + 2..pos + 2 + count).ok_or_else(truncated)?;
+        let len = bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + count)
+    };
+    let start = pos + header;
+    let contents = der.get(start..start + len).ok_or_else(truncated)?;
+    Ok((tag, contents, start + len))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
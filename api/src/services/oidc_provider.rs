@@ -11,6 +11,7 @@
 
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use chrono::{DateTime, Duration, Utc};
+use ipnetwork::IpNetwork;
 use jsonwebtoken::{Algorithm, Header};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
@@ -19,10 +20,12 @@ use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::config::OidcConfig;
+use crate::config::{AuditConfig, OidcConfig};
 use crate::errors::AppError;
-use crate::models::User;
+use crate::models::{AuditAction, AuditSeverity, CreateAuditLog, User};
+use crate::repositories::{AuditLogRepository, UserRepository};
 use crate::services::oidc_keys::OidcKeySet;
+use crate::services::EmailService;
 
 // ── Access token claims (RFC 9068) ────────────────────────────────────────────
 
@@ -118,11 +121,33 @@ pub struct OidcProvider {
     pub config: OidcConfig,
     pub keys: Arc<OidcKeySet>,
     pub pool: PgPool,
+    /// Emails the user when refresh token reuse revokes their session
+    reuse_alert_email: Option<Arc<EmailService>>,
+    /// Audit log policy for reuse reports
+    audit: AuditConfig,
 }
 
 impl OidcProvider {
     pub fn new(config: OidcConfig, keys: Arc<OidcKeySet>, pool: PgPool) -> Self {
-        Self { config, keys, pool }
+        Self {
+            config,
+            keys,
+            pool,
+            reuse_alert_email: None,
+            audit: AuditConfig::default(),
+        }
+    }
+
+    /// Apply the configured audit log policy to reuse reports.
+    pub fn with_audit(mut self, audit: AuditConfig) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Email the user when a replayed refresh token revokes their session.
+    pub fn with_reuse_alert_email(mut self, email_service: Arc<EmailService>) -> Self {
+        self.reuse_alert_email = Some(email_service);
+        self
     }
 
     /// Whether the OIDC feature is enabled.
@@ -548,7 +573,16 @@ impl OidcProvider {
             .ok(); // best-effort; don't mask the primary error
             tx.commit().await.ok();
 
-            // TODO: emit audit event auth.refresh_reuse_detected
+            report_refresh_reuse(
+                &self.pool,
+                &self.audit,
+                self.reuse_alert_email.as_ref(),
+                old.user_id,
+                old.client_id,
+                old.family_id,
+                ip,
+            )
+            .await;
             return Err(AppError::OidcInvalidGrant(
                 "refresh token already used — possible replay attack".into(),
             ));
@@ -986,6 +1020,66 @@ pub struct RotatedTokens {
     pub client: OAuthClient,
 }
 
+// ── Reuse reporting ──────────────────────────────────────────────────────────
+
+/// Audit a replayed refresh token whose family was just revoked and, when
+/// `email` is set, tell the user a session was revoked.
+///
+/// Best-effort: failures are logged and never change the token response.
+/// Returns whether the alert email was dispatched.
+async fn report_refresh_reuse(
+    pool: &PgPool,
+    audit: &AuditConfig,
+    email: Option<&Arc<EmailService>>,
+    user_id: Uuid,
+    client_id: Uuid,
+    family_id: Uuid,
+    ip: Option<std::net::IpAddr>,
+) -> bool {
+    tracing::warn!(
+        user_id = %user_id,
+        client_id = %client_id,
+        family_id = %family_id,
+        "Refresh token reuse detected; token family revoked"
+    );
+
+    let user = match UserRepository::find_by_id(pool, user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return false,
+        Err(e) => {
+            tracing::error!(user_id = %user_id, error = %e, "Failed to load user for refresh reuse");
+            return false;
+        }
+    };
+
+    let log = CreateAuditLog::new(AuditAction::RefreshTokenReuseDetected)
+        .with_actor(user.id, &user.email, &user.role)
+        .with_ip(ip.map(IpNetwork::from))
+        .with_severity(AuditSeverity::Warning)
+        .with_metadata(serde_json::json!({
+            "client_id": client_id,
+            "family_id": family_id,
+        }));
+    if let Err(e) = AuditLogRepository::create(pool, audit, log).await {
+        tracing::error!(error = %e, "Failed to create audit log for refresh reuse");
+    }
+
+    let Some(email_service) = email.cloned() else {
+        return false;
+    };
+    let address = user.email;
+    tokio::spawn(async move {
+        let ip = ip.map(|ip| ip.to_string());
+        if let Err(e) = email_service
+            .send_session_revoked_alert(&address, ip.as_deref())
+            .await
+        {
+            tracing::warn!(user_id = %user_id, error = %e, "Failed to send session revoked alert");
+        }
+    });
+    true
+}
+
 // ── Crypto helpers ────────────────────────────────────────────────────────────
 
 /// Generate a cryptographically random opaque token, base64url-encoded.
@@ -1010,3 +1104,78 @@ fn user_has_member_access(user: &User) -> bool {
         || user.membership_status == "active"
         || user.membership_status == "grace_period"
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateUser, UserRole};
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    #[actix_rt::test]
+    async fn refresh_reuse_is_audited_and_emails_the_user() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let user = UserRepository::create(
+            &pool,
+            CreateUser {
+                email: format!("reuse-{}@example.com", Uuid::new_v4()),
+                password_hash: None,
+                role: UserRole::Subscriber,
+            },
+        )
+        .await
+        .unwrap();
+        let ip: std::net::IpAddr = "203.0.113.7".parse().unwrap();
+        let email = Arc::new(EmailService::new_dev());
+
+        let audit = AuditConfig::default();
+        let notified = report_refresh_reuse(
+            &pool,
+            &audit,
+            Some(&email),
+            user.id,
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Some(ip),
+        )
+        .await;
+        let silent = report_refresh_reuse(
+            &pool,
+            &audit,
+            None,
+            user.id,
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            None,
+        )
+        .await;
+        let audited: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM audit_logs WHERE actor_id = $1 AND action = 'refresh_token_reuse_detected'",
+        )
+        .bind(user.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        sqlx::query("DELETE FROM audit_logs WHERE actor_id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(notified);
+        assert!(!silent);
+        assert_eq!(audited, 2);
+    }
+}
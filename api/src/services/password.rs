@@ -1,67 +1,177 @@
 //! Password hashing service
+//!
+//! With a pepper configured, the password is first run through
+//! HMAC-SHA256 keyed by the pepper and the stored hash is prefixed with
+//! `$pepper-v{version}`, e.g. `$pepper-v1$argon2id$v=19$...`. Hashes
+//! without the prefix were created before the pepper and still verify.
 
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2, Params,
 };
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
 
+use crate::config::{Argon2Config, PasswordPepperConfig};
 use crate::errors::AppError;
-use crate::validation::validate_password_strength;
+use crate::validation::check_password_strength;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Marks a peppered hash; followed by the pepper version and the PHC string
+const PEPPER_PREFIX: &str = "$pepper-v";
+
+/// How often the dummy hash was checked, so tests can see the path was taken
+#[cfg(test)]
+pub(crate) static DUMMY_VERIFICATIONS: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
 
 /// Password service for hashing and verification
+#[derive(Clone)]
 pub struct PasswordService {
     argon2: Argon2<'static>,
+    pepper: Option<PasswordPepperConfig>,
+    banned: Option<Arc<HashSet<String>>>,
+    /// Hash checked by [`PasswordService::verify_dummy`], made on first use
+    /// with this service's parameters
+    dummy_hash: Arc<OnceLock<String>>,
 }
 
 impl PasswordService {
-    /// Create a new password service with recommended Argon2id parameters
+    /// Create a new password service with the default Argon2id costs
+    /// (64 MiB, 3 iterations, 4 lanes) and no pepper
     pub fn new() -> Self {
-        // Recommended parameters for Argon2id
-        // Memory: 64 MiB, Iterations: 3, Parallelism: 4
-        let params = Params::new(
-            64 * 1024, // 64 MiB memory
-            3,         // 3 iterations
-            4,         // 4 parallelism
-            None,      // default output length
-        )
-        .expect("Invalid Argon2 parameters");
+        Self::with_pepper(None)
+    }
 
+    /// Create a password service with the default Argon2id costs that
+    /// peppers with `pepper` (`Config.password_pepper`)
+    pub fn with_pepper(pepper: Option<PasswordPepperConfig>) -> Self {
         Self {
-            argon2: Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params),
+            argon2: build_argon2(Argon2Config::default())
+                .expect("default Argon2 parameters are valid"),
+            pepper,
+            banned: None,
+            dummy_hash: Arc::default(),
         }
     }
 
+    /// Hash new passwords with the given Argon2id costs (`Config.argon2`).
+    /// Fails if Argon2 rejects the parameters.
+    pub fn with_params(mut self, params: Argon2Config) -> Result<Self, AppError> {
+        self.argon2 = build_argon2(params)?;
+        self.dummy_hash = Arc::default();
+        Ok(self)
+    }
+
+    /// Reject passwords on `banned` (see [`crate::validation::load_banned_passwords`])
+    /// instead of the embedded common-password list
+    pub fn with_banned_passwords(mut self, banned: HashSet<String>) -> Self {
+        self.banned = Some(Arc::new(banned));
+        self
+    }
+
     /// Hash a password
     pub fn hash(&self, password: &str) -> Result<String, AppError> {
         let salt = SaltString::generate(&mut OsRng);
 
+        let (input, prefix) = match &self.pepper {
+            Some(pepper) => (
+                apply_pepper(&pepper.secret, password),
+                format!("{}{}", PEPPER_PREFIX, pepper.version),
+            ),
+            None => (password.as_bytes().to_vec(), String::new()),
+        };
+
         let hash = self
             .argon2
-            .hash_password(password.as_bytes(), &salt)
+            .hash_password(&input, &salt)
             .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?;
 
-        Ok(hash.to_string())
+        Ok(format!("{}{}", prefix, hash))
     }
 
     /// Verify a password against a hash
+    ///
+    /// A peppered hash whose pepper version is not configured never verifies.
     pub fn verify(&self, password: &str, hash: &str) -> Result<bool, AppError> {
-        let parsed_hash = PasswordHash::new(hash)
+        let (input, phc) = match split_pepper_version(hash)? {
+            Some((version, phc)) => match self.pepper_secret(version) {
+                Some(secret) => (apply_pepper(secret, password), phc),
+                None => {
+                    tracing::error!(version, "Password hash uses an unconfigured pepper version");
+                    return Ok(false);
+                }
+            },
+            None => (password.as_bytes().to_vec(), hash),
+        };
+
+        let parsed_hash = PasswordHash::new(phc)
             .map_err(|e| AppError::internal(format!("Invalid password hash format: {}", e)))?;
 
-        Ok(self
-            .argon2
-            .verify_password(password.as_bytes(), &parsed_hash)
-            .is_ok())
+        Ok(self.argon2.verify_password(&input, &parsed_hash).is_ok())
+    }
+
+    /// Whether `hash` was made with Argon2 costs or a pepper version other
+    /// than this service's, so it should be replaced after the next
+    /// successful verify. Hashes that cannot be parsed are left alone.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let (version, phc) = match split_pepper_version(hash) {
+            Ok(Some((version, phc))) => (Some(version), phc),
+            Ok(None) => (None, hash),
+            Err(_) => return false,
+        };
+        if version != self.pepper.as_ref().map(|p| p.version) {
+            return true;
+        }
+        let Some(stored) = PasswordHash::new(phc)
+            .ok()
+            .and_then(|parsed| Params::try_from(&parsed).ok())
+        else {
+            return false;
+        };
+
+        let current = self.argon2.params();
+        (stored.m_cost(), stored.t_cost(), stored.p_cost())
+            != (current.m_cost(), current.t_cost(), current.p_cost())
+    }
+
+    /// Do the same Argon2 work as [`PasswordService::verify`] when there is
+    /// no real hash to check, e.g. for an unknown email at login, so those
+    /// requests are not answered measurably faster. Always `false`.
+    pub fn verify_dummy(&self, password: &str) -> bool {
+        let hash = self
+            .dummy_hash
+            .get_or_init(|| self.hash("dummy-password-for-timing").unwrap_or_default());
+        #[cfg(test)]
+        DUMMY_VERIFICATIONS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let _ = self.verify(password, hash);
+        false
+    }
+
+    /// The current or a previous pepper secret for `version`
+    fn pepper_secret(&self, version: u32) -> Option<&str> {
+        let pepper = self.pepper.as_ref()?;
+        if pepper.version == version {
+            return Some(&pepper.secret);
+        }
+        pepper
+            .previous
+            .iter()
+            .find(|(v, _)| *v == version)
+            .map(|(_, secret)| secret.as_str())
     }
 
     /// Validate password strength
     pub fn validate_strength(&self, password: &str) -> Result<(), AppError> {
-        validate_password_strength(password).map_err(|e| {
+        check_password_strength(password, self.banned.as_deref()).map_err(|e| {
             let message = e
                 .message
                 .map(|m| m.to_string())
                 .unwrap_or_else(|| "Password does not meet strength requirements".to_string());
-            AppError::validation("password", message)
+            AppError::validation_coded("password", e.code, message)
         })
     }
 
@@ -70,8 +180,9 @@ impl PasswordService {
         let email_parts: Vec<&str> = email.split('@').collect();
         if let Some(username) = email_parts.first() {
             if username.len() >= 4 && password.to_lowercase().contains(&username.to_lowercase()) {
-                return Err(AppError::validation(
+                return Err(AppError::validation_coded(
                     "password",
+                    "password_contains_email",
                     "Password cannot contain your email address",
                 ));
             }
@@ -86,10 +197,57 @@ impl Default for PasswordService {
     }
 }
 
+/// An Argon2id hasher with the given costs and the default output length
+fn build_argon2(config: Argon2Config) -> Result<Argon2<'static>, AppError> {
+    let params = Params::new(
+        config.memory_kib,
+        config.iterations,
+        config.parallelism,
+        None,
+    )
+    .map_err(|e| AppError::internal(format!("Invalid Argon2 parameters: {}", e)))?;
+    Ok(Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        params,
+    ))
+}
+
+/// HMAC-SHA256 of the password keyed by the pepper
+fn apply_pepper(secret: &str, password: &str) -> Vec<u8> {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(password.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Split `$pepper-v{version}$argon2id$...` into the version and PHC string;
+/// `None` for an unpeppered hash.
+fn split_pepper_version(hash: &str) -> Result<Option<(u32, &str)>, AppError> {
+    let Some(rest) = hash.strip_prefix(PEPPER_PREFIX) else {
+        return Ok(None);
+    };
+    let split = rest.find('$').unwrap_or(rest.len());
+    let version = rest[..split]
+        .parse()
+        .map_err(|_| AppError::internal("Invalid password hash format: bad pepper version"))?;
+    Ok(Some((version, &rest[split..])))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn cheap_service(memory_kib: u32, iterations: u32) -> PasswordService {
+        PasswordService::new()
+            .with_params(Argon2Config {
+                memory_kib,
+                iterations,
+                parallelism: 1,
+            })
+            .unwrap()
+    }
+
     #[test]
     fn test_hash_and_verify() {
         let service = PasswordService::new();
@@ -100,6 +258,42 @@ mod tests {
         assert!(!service.verify("wrong-password", &hash).unwrap());
     }
 
+    #[test]
+    fn cheap_params_round_trip() {
+        let service = cheap_service(8, 1);
+        let hash = service.hash("SecurePassword123!").unwrap();
+
+        assert!(hash.contains("m=8,t=1,p=1"));
+        assert!(service.verify("SecurePassword123!", &hash).unwrap());
+        assert!(!service.verify("wrong-password", &hash).unwrap());
+        // Verification follows the hash's own parameters
+        assert!(PasswordService::new()
+            .verify("SecurePassword123!", &hash)
+            .unwrap());
+    }
+
+    #[test]
+    fn weaker_hash_needs_rehash() {
+        let weak = cheap_service(8, 1);
+        let strong = cheap_service(16, 2);
+        let hash = weak.hash("SecurePassword123!").unwrap();
+
+        assert!(strong.needs_rehash(&hash));
+        assert!(!weak.needs_rehash(&hash));
+        assert!(!strong.needs_rehash(&strong.hash("SecurePassword123!").unwrap()));
+        assert!(!strong.needs_rehash("not-a-phc-string"));
+    }
+
+    #[test]
+    fn invalid_params_are_an_error() {
+        let params = Argon2Config {
+            memory_kib: 1,
+            iterations: 0,
+            parallelism: 1,
+        };
+        assert!(PasswordService::new().with_params(params).is_err());
+    }
+
     #[test]
     fn test_hash_uniqueness() {
         let service = PasswordService::new();
@@ -116,6 +310,26 @@ mod tests {
         assert!(service.verify(password, &hash2).unwrap());
     }
 
+    #[test]
+    fn dummy_verify_never_matches() {
+        let service = PasswordService::new();
+        assert!(!service.verify_dummy("dummy-password-for-timing"));
+        assert!(!service.verify_dummy("anything-else"));
+        assert!(service
+            .dummy_hash
+            .get()
+            .is_some_and(|h| h.contains("$argon2id$")));
+
+        // The dummy hash uses the service's own costs, so it takes as long
+        // to check as a real one
+        let cheap = cheap_service(8, 1);
+        assert!(!cheap.verify_dummy("anything-else"));
+        assert!(cheap
+            .dummy_hash
+            .get()
+            .is_some_and(|h| h.contains("m=8,t=1,p=1")));
+    }
+
     #[test]
     fn test_validate_strength() {
         let service = PasswordService::new();
@@ -124,6 +338,18 @@ mod tests {
         assert!(service.validate_strength("weak").is_err());
     }
 
+    #[test]
+    fn banned_list_is_checked_by_the_service() {
+        let banned = HashSet::from(["correcthorse1!A".to_lowercase()]);
+        let service = PasswordService::new().with_banned_passwords(banned);
+
+        let (_, code) = field_and_code(service.validate_strength("CorrectHorse1!A").unwrap_err());
+        assert_eq!(code, "password_too_common");
+        assert!(PasswordService::new()
+            .validate_strength("CorrectHorse1!A")
+            .is_ok());
+    }
+
     #[test]
     fn test_validate_not_contains_email() {
         let service = PasswordService::new();
@@ -135,4 +361,105 @@ mod tests {
             .validate_not_contains_email("userPassword123!", "user@example.com")
             .is_err());
     }
+
+    fn pepper(version: u32, secret: &str, previous: &[(u32, &str)]) -> PasswordPepperConfig {
+        PasswordPepperConfig {
+            version,
+            secret: secret.to_string(),
+            previous: previous.iter().map(|(v, s)| (*v, s.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_hash_and_verify_with_pepper() {
+        let service = PasswordService::with_pepper(Some(pepper(1, "pepper-one", &[])));
+        let password = "SecurePassword123!";
+
+        let hash = service.hash(password).unwrap();
+        assert!(hash.starts_with("$pepper-v1$argon2id$"));
+        assert!(service.verify(password, &hash).unwrap());
+        assert!(!service.verify("wrong-password", &hash).unwrap());
+    }
+
+    #[test]
+    fn test_peppered_hash_fails_without_the_pepper() {
+        let password = "SecurePassword123!";
+        let hash = PasswordService::with_pepper(Some(pepper(1, "pepper-one", &[])))
+            .hash(password)
+            .unwrap();
+
+        let unpeppered = PasswordService::with_pepper(None);
+        assert!(!unpeppered.verify(password, &hash).unwrap());
+
+        // Same version, different secret
+        let wrong = PasswordService::with_pepper(Some(pepper(1, "pepper-two", &[])));
+        assert!(!wrong.verify(password, &hash).unwrap());
+
+        // The bare PHC string is not the plain password's hash either
+        let phc = hash.strip_prefix("$pepper-v1").unwrap();
+        assert!(!unpeppered.verify(password, phc).unwrap());
+    }
+
+    #[test]
+    fn test_pepper_rotation_keeps_old_hashes_valid() {
+        let password = "SecurePassword123!";
+        let legacy = PasswordService::with_pepper(None).hash(password).unwrap();
+        let old = PasswordService::with_pepper(Some(pepper(1, "pepper-one", &[])))
+            .hash(password)
+            .unwrap();
+
+        let rotated =
+            PasswordService::with_pepper(Some(pepper(2, "pepper-two", &[(1, "pepper-one")])));
+        assert!(rotated.verify(password, &legacy).unwrap());
+        assert!(rotated.verify(password, &old).unwrap());
+        assert!(rotated.hash(password).unwrap().starts_with("$pepper-v2$"));
+
+        // Logging in moves old-pepper and legacy hashes to the current pepper
+        assert!(rotated.needs_rehash(&legacy));
+        assert!(rotated.needs_rehash(&old));
+        assert!(!rotated.needs_rehash(&rotated.hash(password).unwrap()));
+
+        // Once the old pepper is dropped its hashes no longer verify
+        let dropped = PasswordService::with_pepper(Some(pepper(2, "pepper-two", &[])));
+        assert!(!dropped.verify(password, &old).unwrap());
+    }
+
+    #[test]
+    fn test_malformed_pepper_version_is_an_error() {
+        let service = PasswordService::with_pepper(Some(pepper(1, "pepper-one", &[])));
+        assert!(service.verify("x", "$pepper-vX$argon2id$v=19").is_err());
+    }
+
+    fn field_and_code(err: AppError) -> (String, String) {
+        match err {
+            AppError::ValidationError { field, code, .. } => (field, code),
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validation_errors_carry_stable_codes() {
+        let service = PasswordService::new();
+        let cases = [
+            ("Short1!", "password_too_short"),
+            ("alllowercase123!", "password_no_uppercase"),
+            ("NoDigitsHere!!", "password_no_digit"),
+        ];
+        for (password, expected) in cases {
+            let (field, code) = field_and_code(service.validate_strength(password).unwrap_err());
+            assert_eq!(field, "password");
+            assert_eq!(code, expected, "{}", password);
+        }
+
+        let err = service
+            .validate_not_contains_email("userPassword123!", "user@example.com")
+            .unwrap_err();
+        assert_eq!(
+            field_and_code(err),
+            (
+                "password".to_string(),
+                "password_contains_email".to_string()
+            )
+        );
+    }
 }
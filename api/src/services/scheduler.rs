@@ -0,0 +1,396 @@
+//! Periodic housekeeping tasks
+//!
+//! A failed payment puts a member into a grace period (see the Stripe
+//! webhook handler); a later successful payment clears it. When neither
+//! happens, nothing else would end it, so one task cancels memberships whose
+//! grace period has run out. Another deletes expired tokens, rate-limit
+//! windows and IP bans. Each replica also prunes its in-memory bans and
+//! strikes, and keeps its cache of access token revocations in step with the
+//! database.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::AuditConfig;
+use crate::errors::AppError;
+use crate::jobs::{self, with_advisory_lock};
+use crate::middleware::auto_ban::{self, AutoBanService};
+use crate::models::{
+    AuditAction, AuditSeverity, CreateAdminNotification, CreateAuditLog, NotificationType,
+};
+use crate::pagination::{Page, PerPage};
+use crate::repositories::{
+    AuditLogRepository, NotificationRepository, RateLimitRepository, TokenRepository,
+    UserRepository, WebhookEventRepository,
+};
+use crate::services::JwtService;
+
+/// Cancel every membership whose grace period ended before `now`, returning
+/// how many were canceled. Each user gets a `GracePeriodEnded` audit entry and
+/// an admin notification.
+pub async fn end_expired_grace_periods(
+    pool: &PgPool,
+    audit: &AuditConfig,
+    now: DateTime<Utc>,
+    jwt: Option<&JwtService>,
+) -> Result<u64, AppError> {
+    let mut ended = 0;
+    loop {
+        // Canceled users drop out of the window, so the first page is always
+        // the next batch
+        let (users, _) =
+            UserRepository::find_in_grace_period(pool, now, Page::FIRST, PerPage::MAX).await?;
+        let mut batch_ended = 0;
+        for user in &users {
+            if !UserRepository::expire_grace_period(pool, user.id, now).await? {
+                continue;
+            }
+            batch_ended += 1;
+            if let Some(jwt) = jwt {
+                jwt.claims_changed(user.id, &AuditAction::GracePeriodEnded)
+                    .await;
+            }
+
+            tracing::info!(user_id = %user.id, "Grace period ended, membership canceled");
+
+            let grace_period_end = user.grace_period_end.map(|end| end.to_rfc3339());
+            let audit_log = CreateAuditLog::new(AuditAction::GracePeriodEnded)
+                .with_resource("user", user.id)
+                .with_severity(AuditSeverity::Warning)
+                .with_metadata(serde_json::json!({
+                    "grace_period_end": grace_period_end,
+                    "membership_status": "canceled",
+                }));
+            if let Err(e) = AuditLogRepository::create(pool, audit, audit_log).await {
+                tracing::error!(error = %e, user_id = %user.id, "Failed to create audit log for grace period ended");
+            }
+
+            let notification = CreateAdminNotification {
+                notification_type: NotificationType::GracePeriodExpiring,
+                title: "Grace period expired".to_string(),
+                message: format!(
+                    "{}'s grace period ended without payment; their membership was canceled.",
+                    user.email
+                ),
+                metadata: Some(serde_json::json!({
+                    "grace_period_end": grace_period_end,
+                })),
+                user_id: Some(user.id),
+            };
+            if let Err(e) = NotificationRepository::create(pool, notification).await {
+                tracing::error!(error = %e, user_id = %user.id, "Failed to create notification for grace period ended");
+            }
+        }
+
+        ended += batch_ended;
+        if batch_ended == 0 || users.len() < PerPage::MAX.get() as usize {
+            return Ok(ended);
+        }
+    }
+}
+
+/// Spawn the task that runs [`end_expired_grace_periods`] every `interval`,
+/// on one replica per tick
+pub fn spawn_grace_period_expiry(
+    pool: PgPool,
+    audit: AuditConfig,
+    jwt: Option<Arc<JwtService>>,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        tracing::info!(
+            interval_secs = interval.as_secs(),
+            "Grace period expiry task started"
+        );
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            let expire = end_expired_grace_periods(&pool, &audit, Utc::now(), jwt.as_deref());
+            match with_advisory_lock(&pool, jobs::GRACE_PERIOD_EXPIRY_LOCK, expire).await {
+                Ok(None) => {}
+                Ok(Some(ended)) => {
+                    if ended > 0 {
+                        tracing::info!(ended, "Canceled memberships with expired grace periods");
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to end expired grace periods");
+                }
+            }
+        }
+    });
+}
+
+/// Rows deleted by one [`run_maintenance`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// Expired refresh, magic link, password reset and similar tokens
+    pub tokens: u64,
+    pub rate_limits: u64,
+    pub ip_bans: u64,
+    /// Processed Stripe event ids past their retention
+    pub webhook_events: u64,
+}
+
+/// Delete expired tokens, rate-limit windows, IP bans and processed webhook
+/// event ids. Runs on one replica at a time; `Ok(None)` means another replica
+/// is already running it.
+pub async fn run_maintenance(pool: &PgPool) -> Result<Option<MaintenanceReport>, AppError> {
+    let cleanup = async {
+        Ok::<_, AppError>(MaintenanceReport {
+            tokens: TokenRepository::cleanup_expired_tokens(pool).await?,
+            rate_limits: RateLimitRepository::cleanup_expired(pool).await?,
+            ip_bans: auto_ban::cleanup_expired_bans(pool).await?,
+            webhook_events: WebhookEventRepository::cleanup_expired(pool).await?,
+        })
+    };
+    with_advisory_lock(pool, jobs::MAINTENANCE_LOCK, cleanup).await
+}
+
+/// Spawn the task that runs [`run_maintenance`] every `interval`. A run that
+/// overruns its interval delays the next one rather than stacking up behind it.
+pub fn spawn_maintenance(pool: PgPool, interval: Duration) {
+    tokio::spawn(async move {
+        tracing::info!(
+            interval_secs = interval.as_secs(),
+            "Maintenance task started"
+        );
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match run_maintenance(&pool).await {
+                Ok(None) => {}
+                Ok(Some(report)) => {
+                    if report != MaintenanceReport::default() {
+                        tracing::info!(
+                            tokens = report.tokens,
+                            rate_limits = report.rate_limits,
+                            ip_bans = report.ip_bans,
+                            webhook_events = report.webhook_events,
+                            "Cleaned up expired rows"
+                        );
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to clean up expired rows");
+                }
+            }
+        }
+    });
+}
+
+/// How often each replica prunes expired bans and stale strikes from its
+/// in-memory auto-ban state.
+pub const AUTO_BAN_PRUNE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Spawn the task that runs [`AutoBanService::cleanup_expired`] every
+/// `interval`. Every replica runs it; the maps are per replica.
+pub fn spawn_auto_ban_prune(auto_ban: Arc<AutoBanService>, interval: Duration) {
+    tokio::spawn(async move {
+        tracing::info!(
+            interval_secs = interval.as_secs(),
+            "Auto-ban prune task started"
+        );
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            auto_ban.cleanup_expired().await;
+        }
+    });
+}
+
+/// How often each replica picks up access token revocations made elsewhere.
+/// Bounds how long a revoked token keeps working on another replica.
+pub const REVOCATION_SYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Spawn the task that runs [`JwtService::sync_revocations`] every
+/// `interval`. Every replica runs it; it only reads.
+pub fn spawn_revocation_sync(jwt: Arc<JwtService>, interval: Duration) {
+    tokio::spawn(async move {
+        tracing::info!(
+            interval_secs = interval.as_secs(),
+            "Token revocation sync task started"
+        );
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match jwt.sync_revocations().await {
+                Ok(0) => {}
+                Ok(moved) => {
+                    tracing::debug!(moved, "Picked up access token revocations");
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to sync access token revocations");
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    //! DB-backed integration tests. Skipped when DATABASE_URL is unset.
+    use super::*;
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    async fn insert_user(pool: &PgPool, email: String, status: &str, end: DateTime<Utc>) -> Uuid {
+        sqlx::query_scalar(
+            r#"
+            INSERT INTO users (email, password_hash, subscription_status, grace_period_start, grace_period_end)
+            VALUES ($1, 'x', $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(email)
+        .bind(status)
+        .bind(end - Duration::days(30))
+        .bind(end)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn expired_grace_periods_are_canceled_and_recorded() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let tag = Uuid::new_v4().simple().to_string();
+        let now = Utc::now();
+        let expired = insert_user(
+            &pool,
+            format!("expired.{tag}@example.com"),
+            "grace_period",
+            now - Duration::hours(1),
+        )
+        .await;
+        let running = insert_user(
+            &pool,
+            format!("running.{tag}@example.com"),
+            "grace_period",
+            now + Duration::days(3),
+        )
+        .await;
+
+        let ended = end_expired_grace_periods(&pool, &AuditConfig::default(), now, None)
+            .await
+            .unwrap();
+        assert!(ended >= 1);
+
+        let user = UserRepository::find_by_id(&pool, expired)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.membership_status, "canceled");
+        assert!(user.grace_period_start.is_none() && user.grace_period_end.is_none());
+        let user = UserRepository::find_by_id(&pool, running)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.membership_status, "grace_period");
+        assert!(user.grace_period_end.is_some());
+
+        let audited: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM audit_logs WHERE action = 'grace_period_ended' AND resource_id = $1",
+        )
+        .bind(expired)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(audited, 1);
+        let notified: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM admin_notifications WHERE type = 'grace_period_expiring' AND user_id = $1",
+        )
+        .bind(expired)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(notified, 1);
+
+        // A second run finds nothing left to cancel for this user
+        end_expired_grace_periods(&pool, &AuditConfig::default(), now, None)
+            .await
+            .unwrap();
+        let audited: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM audit_logs WHERE action = 'grace_period_ended' AND resource_id = $1",
+        )
+        .bind(expired)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(audited, 1);
+    }
+
+    #[actix_rt::test]
+    async fn one_maintenance_run_deletes_expired_rows() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+        let tag = Uuid::new_v4().simple().to_string();
+        sqlx::query(
+            "INSERT INTO magic_link_tokens (email, token_hash, expires_at) VALUES ($1, $2, NOW() - INTERVAL '1 minute')",
+        )
+        .bind(format!("expired.{tag}@example.com"))
+        .bind(format!("hash_{tag}"))
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO rate_limits (key, action, window_start) VALUES ($1, 'login', NOW() - INTERVAL '2 hours')",
+        )
+        .bind(&tag)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let ip: ipnetwork::IpNetwork = std::net::IpAddr::from(std::net::Ipv6Addr::new(
+            0x2001,
+            0xdb8,
+            0,
+            0,
+            0,
+            0,
+            rand::random(),
+            rand::random(),
+        ))
+        .into();
+        sqlx::query(
+            "INSERT INTO ip_bans (ip_address, reason, strikes, expires_at) VALUES ($1, 'test', 5, NOW() - INTERVAL '1 minute')",
+        )
+        .bind(ip)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let report = run_maintenance(&pool)
+            .await
+            .unwrap()
+            .expect("no other session holds the maintenance lock");
+        assert!(report.tokens >= 1 && report.rate_limits >= 1 && report.ip_bans >= 1);
+
+        let left: i64 = sqlx::query_scalar(
+            r#"
+            SELECT (SELECT COUNT(*) FROM magic_link_tokens WHERE token_hash = $1)
+                 + (SELECT COUNT(*) FROM rate_limits WHERE key = $2)
+                 + (SELECT COUNT(*) FROM ip_bans WHERE ip_address = $3)
+            "#,
+        )
+        .bind(format!("hash_{tag}"))
+        .bind(&tag)
+        .bind(ip)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(left, 0);
+    }
+}
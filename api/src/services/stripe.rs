@@ -4,13 +4,19 @@
 //! proxy methods for products, prices, subscriptions, invoices, and webhook
 //! endpoints. No local database tables are used for Stripe state.
 
+use crate::config::Config;
 use crate::errors::AppError;
+use crate::http::client::IDEMPOTENCY_KEY_HEADER;
+use crate::http::HttpClient;
 use crate::models::stripe::{
     decrypt_secret, StripeInvoiceResponse, StripePriceResponse, StripeProductResponse,
-    StripeSubscriptionItemResponse, StripeSubscriptionResponse, StripeWebhookEndpointResponse,
+    StripeProrationPreview, StripeSubscriptionItemResponse, StripeSubscriptionResponse,
+    StripeWebhookEndpointResponse,
 };
+use crate::models::Currency;
 use crate::services::encryption::EncryptionKeySet;
 use hmac::{Hmac, Mac};
+use serde::Deserialize;
 use sha2::Sha256;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
@@ -21,6 +27,27 @@ type HmacSha256 = Hmac<Sha256>;
 /// Metadata key used to tag Stripe products belonging to this application.
 const APP_TAG_KEY: &str = "app";
 
+/// Default cap on checkout free-trial length
+const DEFAULT_MAX_TRIAL_DAYS: u32 = 14;
+
+/// Stripe API origin used when `STRIPE_API_BASE` is unset
+const DEFAULT_API_BASE: &str = "https://api.stripe.com";
+
+/// Default age, in seconds, past which a webhook signature is treated as a replay
+const DEFAULT_WEBHOOK_TOLERANCE_SECS: u64 = 300;
+
+/// What a checkout session collects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckoutMode {
+    /// Pay in Checkout; Stripe starts the subscription itself
+    #[default]
+    Subscription,
+    /// Only save a payment method. The subscription is created from the
+    /// `checkout.session.completed` webhook (`subscribe_with_setup_intent`).
+    Setup,
+}
+
 /// Stripe configuration
 #[derive(Clone, Debug)]
 pub struct StripeConfig {
@@ -32,15 +59,32 @@ pub struct StripeConfig {
     pub free_price_id: Option<String>,
     /// Application tag stored in product metadata to filter shared Stripe accounts
     pub app_tag: String,
+    /// Customer portal configuration (`bpc_...`) controlling which features are shown.
+    /// `None` uses the account's default portal configuration.
+    pub portal_configuration_id: Option<String>,
+    /// Where the customer portal sends users back to when no return URL is requested
+    pub portal_return_url: String,
+    /// Hosts a requested portal return URL may point at (lowercased)
+    pub portal_allowed_hosts: Vec<String>,
+    /// Longest free trial a checkout may request; 0 disables trials
+    pub max_trial_days: u32,
+    /// Stripe API origin, overridable to point tests at a mock backend
+    pub api_base: String,
+    /// Whether new customers may be attached to a Stripe test clock.
+    /// Only ever true outside production.
+    pub allow_test_clocks: bool,
+    /// How far a webhook's signed timestamp may be from now, in seconds
+    /// (STRIPE_WEBHOOK_TOLERANCE_SECS)
+    pub webhook_tolerance_secs: u64,
 }
 
 impl StripeConfig {
-    pub fn from_env() -> Result<Self, AppError> {
-        let frontend_origin =
-            std::env::var("CORS_ORIGIN").unwrap_or_else(|_| "http://localhost:5173".to_string());
-        let base = frontend_origin.trim_end_matches('/');
+    /// Load Stripe settings from the environment. The frontend origin,
+    /// environment and redirect allowlist come from the already-loaded `app` config.
+    pub fn from_env(app: &Config) -> Result<Self, AppError> {
+        let base = app.cors_origin.trim_end_matches('/');
 
-        Ok(Self {
+        let config = Self {
             secret_key: std::env::var("STRIPE_SECRET_KEY")
                 .unwrap_or_else(|_| "sk_test_placeholder".to_string()),
             webhook_secret: std::env::var("STRIPE_WEBHOOK_SECRET")
@@ -54,7 +98,78 @@ impl StripeConfig {
                 .ok()
                 .filter(|s| !s.is_empty())
                 .unwrap_or_else(|| "a8n-tools".to_string()),
-        })
+            portal_configuration_id: std::env::var("STRIPE_PORTAL_CONFIGURATION_ID")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            portal_return_url: std::env::var("STRIPE_PORTAL_RETURN_URL")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| format!("{base}/billing")),
+            portal_allowed_hosts: portal_allowed_hosts(
+                std::env::var("STRIPE_PORTAL_ALLOWED_HOSTS").ok().as_deref(),
+                base,
+            ),
+            max_trial_days: std::env::var("STRIPE_MAX_TRIAL_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_TRIAL_DAYS),
+            api_base: std::env::var("STRIPE_API_BASE")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| DEFAULT_API_BASE.to_string()),
+            allow_test_clocks: !app.is_production(),
+            webhook_tolerance_secs: std::env::var("STRIPE_WEBHOOK_TOLERANCE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_WEBHOOK_TOLERANCE_SECS),
+        };
+
+        // Checkout redirects are config-derived, so reject them once at load
+        for (name, url) in [
+            ("STRIPE_SUCCESS_URL", &config.success_url),
+            ("STRIPE_CANCEL_URL", &config.cancel_url),
+        ] {
+            if !crate::redirect::is_allowed(url, &app.redirect.allowed_hosts) {
+                return Err(AppError::internal(format!(
+                    "{} host is not in REDIRECT_ALLOWED_HOSTS",
+                    name
+                )));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Trial length to request from Stripe: capped at `max_trial_days`,
+    /// `None` when no trial was requested or trials are disabled.
+    pub fn trial_days(&self, requested: Option<u32>) -> Option<u32> {
+        requested
+            .map(|days| days.min(self.max_trial_days))
+            .filter(|days| *days > 0)
+    }
+
+    /// Test clock to attach a new customer to: the requested `clock_...` id
+    /// outside production, `None` otherwise.
+    pub fn test_clock<'a>(&self, requested: Option<&'a str>) -> Option<&'a str> {
+        let clock = requested.filter(|id| id.starts_with("clock_"))?;
+        if !self.allow_test_clocks {
+            tracing::warn!(test_clock = %clock, "Ignoring Stripe test clock in production");
+            return None;
+        }
+        Some(clock)
+    }
+
+    /// Check that a requested portal return URL is http(s) and on an allowed host.
+    pub fn validate_portal_return_url(&self, return_url: &str) -> Result<(), AppError> {
+        if !crate::redirect::is_allowed(return_url, &self.portal_allowed_hosts) {
+            return Err(AppError::validation_coded(
+                "return_url",
+                "redirect_not_allowed",
+                "Return URL is not allowed",
+            ));
+        }
+        Ok(())
     }
 
     /// Build a `StripeConfig` from the DB model, decrypting secrets.
@@ -62,8 +177,9 @@ impl StripeConfig {
     pub fn from_db_model(
         db: &crate::models::stripe::StripeConfig,
         key_set: &EncryptionKeySet,
+        app: &Config,
     ) -> Result<Self, AppError> {
-        let env_config = Self::from_env()?;
+        let env_config = Self::from_env(app)?;
 
         let secret_key = match (&db.secret_key, &db.secret_key_nonce) {
             (Some(ct), Some(nonce)) => decrypt_secret(key_set, ct, nonce, db.key_version)?,
@@ -83,10 +199,64 @@ impl StripeConfig {
             cancel_url: env_config.cancel_url,
             free_price_id: env_config.free_price_id,
             app_tag,
+            portal_configuration_id: env_config.portal_configuration_id,
+            portal_return_url: env_config.portal_return_url,
+            portal_allowed_hosts: env_config.portal_allowed_hosts,
+            max_trial_days: env_config.max_trial_days,
+            api_base: env_config.api_base,
+            allow_test_clocks: env_config.allow_test_clocks,
+            webhook_tolerance_secs: env_config.webhook_tolerance_secs,
         })
     }
 }
 
+#[cfg(test)]
+impl StripeConfig {
+    /// Settings for tests: placeholder keys, localhost redirects, and Stripe
+    /// API calls sent to `api_base`
+    pub(crate) fn for_tests(api_base: &str) -> Self {
+        Self {
+            secret_key: "sk_test_xxx".to_string(),
+            webhook_secret: "whsec_test_secret".to_string(),
+            success_url: "http://localhost/checkout/success".to_string(),
+            cancel_url: "http://localhost/cancel".to_string(),
+            free_price_id: None,
+            app_tag: "a8n-tools".to_string(),
+            portal_configuration_id: None,
+            portal_return_url: "http://localhost/billing".to_string(),
+            portal_allowed_hosts: vec!["localhost".to_string()],
+            max_trial_days: DEFAULT_MAX_TRIAL_DAYS,
+            api_base: api_base.to_string(),
+            allow_test_clocks: false,
+            webhook_tolerance_secs: DEFAULT_WEBHOOK_TOLERANCE_SECS,
+        }
+    }
+}
+
+/// Allowed portal return hosts: the comma-separated override if set,
+/// otherwise the frontend origin's host.
+fn portal_allowed_hosts(override_value: Option<&str>, frontend_origin: &str) -> Vec<String> {
+    let hosts: Vec<String> = override_value
+        .unwrap_or_default()
+        .split(',')
+        .map(|h| h.trim().to_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect();
+    if !hosts.is_empty() {
+        return hosts;
+    }
+    url::Url::parse(frontend_origin)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_lowercase))
+        .into_iter()
+        .collect()
+}
+
+/// Stripe client for `config`'s secret key and API origin
+fn build_client(config: &StripeConfig) -> stripe::Client {
+    stripe::Client::from_url(config.api_base.as_str(), &config.secret_key)
+}
+
 /// Inner state that can be swapped when admin updates Stripe config.
 struct StripeServiceInner {
     config: StripeConfig,
@@ -99,23 +269,26 @@ struct StripeServiceInner {
 /// when an admin updates Stripe keys via the dashboard.
 pub struct StripeService {
     inner: RwLock<StripeServiceInner>,
+    /// Client for Stripe endpoints not covered by async-stripe
+    http: HttpClient,
 }
 
 impl StripeService {
     pub fn new(config: StripeConfig) -> Self {
-        let client = stripe::Client::new(&config.secret_key);
+        let client = build_client(&config);
         Self {
             inner: RwLock::new(StripeServiceInner {
                 config,
                 client: Arc::new(client),
             }),
+            http: HttpClient::default(),
         }
     }
 
     /// Hot-reload the service with a new config (e.g. after admin update).
     /// Builds a new Stripe client with the updated secret key.
     pub fn reload(&self, config: StripeConfig) {
-        let client = stripe::Client::new(&config.secret_key);
+        let client = build_client(&config);
         let mut inner = self.inner.write().expect("StripeService lock poisoned");
         inner.config = config;
         inner.client = Arc::new(client);
@@ -167,7 +340,7 @@ impl StripeService {
                         product in the Stripe dashboard.",
                 "Failed to list Stripe products"
             );
-            AppError::internal("Failed to load products from Stripe")
+            AppError::external("stripe", "Failed to load products from Stripe")
         })?;
 
         Ok(products
@@ -213,7 +386,7 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to create Stripe product");
-                AppError::internal("Failed to create product")
+                AppError::external("stripe", "Failed to create product")
             })?;
 
         tracing::info!(product_id = %product.id, name = %name, "Created Stripe product");
@@ -261,7 +434,7 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, product_id = %product_id, "Failed to update Stripe product");
-                AppError::internal("Failed to update product")
+                AppError::external("stripe", "Failed to update product")
             })?;
 
         Ok(StripeProductResponse {
@@ -289,7 +462,7 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, product_id = %product_id, "Failed to archive Stripe product");
-                AppError::internal("Failed to archive product")
+                AppError::external("stripe", "Failed to archive product")
             })?;
 
         tracing::info!(product_id = %product_id, "Archived Stripe product");
@@ -327,7 +500,7 @@ impl StripeService {
                         Stripe dashboard.",
                 "Failed to list Stripe prices"
             );
-            AppError::internal("Failed to load prices from Stripe")
+            AppError::external("stripe", "Failed to load prices from Stripe")
         })?;
 
         // When listing all prices (no product_id filter), restrict to app-tagged products
@@ -377,12 +550,12 @@ impl StripeService {
         &self,
         product_id: &str,
         unit_amount: i64,
-        currency: &str,
+        currency: &Currency,
         interval: &str,
     ) -> Result<StripePriceResponse, AppError> {
         let (_config, client) = self.snapshot();
 
-        let cur: stripe::Currency = currency.parse().unwrap_or(stripe::Currency::USD);
+        let cur = currency.to_stripe();
         let recurring_interval = match interval {
             "year" => stripe::CreatePriceRecurringInterval::Year,
             "week" => stripe::CreatePriceRecurringInterval::Week,
@@ -400,7 +573,7 @@ impl StripeService {
 
         let price = stripe::Price::create(&client, params).await.map_err(|e| {
             tracing::error!(error = %e, "Failed to create Stripe price");
-            AppError::internal("Failed to create price")
+            AppError::external("stripe", "Failed to create price")
         })?;
 
         tracing::info!(price_id = %price.id, product_id = %product_id, "Created Stripe price");
@@ -439,7 +612,7 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, price_id = %price_id, "Failed to archive Stripe price");
-                AppError::internal("Failed to archive price")
+                AppError::external("stripe", "Failed to archive price")
             })?;
 
         tracing::info!(price_id = %price_id, "Archived Stripe price");
@@ -448,7 +621,12 @@ impl StripeService {
 
     // ─── Subscriptions ───────────────────────────────────────
 
-    /// Get the active subscription for a customer from Stripe
+    /// The customer's subscription that decides their membership.
+    ///
+    /// Lists every subscription, ended ones included, and prefers a live one
+    /// (active or trialing, then past_due, then incomplete), so a customer
+    /// with an incomplete and an active subscription is reconciled against
+    /// the active one. Ties go to the most recently created.
     pub async fn get_customer_subscription(
         &self,
         customer_id: &str,
@@ -461,53 +639,63 @@ impl StripeService {
 
         let mut params = stripe::ListSubscriptions::new();
         params.customer = Some(cid);
-        params.limit = Some(1);
+        params.status = Some(stripe::SubscriptionStatusFilter::All);
+        params.limit = Some(100);
 
         let subscriptions =
             stripe::Subscription::list(&client, &params)
                 .await
                 .map_err(|e| {
                     tracing::error!(error = %e, customer_id = %customer_id, "Failed to list subscriptions");
-                    AppError::internal("Failed to fetch subscription")
+                    AppError::external("stripe", "Failed to fetch subscription")
                 })?;
 
-        Ok(subscriptions.data.into_iter().next().map(|sub| {
-            let items: Vec<StripeSubscriptionItemResponse> = sub
-                .items
+        Ok(preferred_subscription(
+            subscriptions
                 .data
-                .iter()
-                .map(|item| {
-                    let price_id = item
-                        .price
-                        .as_ref()
-                        .map(|p| p.id.to_string())
-                        .unwrap_or_default();
-                    let product_id = item
-                        .price
-                        .as_ref()
-                        .and_then(|p| p.product.as_ref())
-                        .map(|prod| match prod {
-                            stripe::Expandable::Id(id) => id.to_string(),
-                            stripe::Expandable::Object(obj) => obj.id.to_string(),
-                        })
-                        .unwrap_or_default();
-                    StripeSubscriptionItemResponse {
-                        price_id,
-                        product_id,
-                        quantity: item.quantity.map(|q| q as u64),
-                    }
-                })
-                .collect();
-
-            StripeSubscriptionResponse {
-                id: sub.id.to_string(),
-                status: format!("{:?}", sub.status).to_lowercase(),
-                current_period_start: sub.current_period_start,
-                current_period_end: sub.current_period_end,
-                cancel_at_period_end: sub.cancel_at_period_end,
-                items,
-            }
-        }))
+                .into_iter()
+                .map(|sub| (sub.created, subscription_response(sub)))
+                .collect(),
+        ))
+    }
+
+    /// Preview moving `subscription_id` to `new_price_id` without changing it.
+    ///
+    /// Asks Stripe for the upcoming invoice as if the change were made now
+    /// and invoiced immediately: proration lines are the immediate charge,
+    /// the remaining lines the next cycle.
+    pub async fn preview_proration(
+        &self,
+        subscription_id: &str,
+        new_price_id: &str,
+    ) -> Result<StripeProrationPreview, AppError> {
+        let (config, _client) = self.snapshot();
+
+        let subscription = self
+            .api_get(
+                &config,
+                &format!("/v1/subscriptions/{}", urlencoding::encode(subscription_id)),
+            )
+            .await?;
+        let item_id = subscription["items"]["data"][0]["id"]
+            .as_str()
+            .ok_or_else(|| AppError::external("stripe", "Subscription has no items"))?;
+
+        let query = [
+            ("subscription", subscription_id),
+            ("subscription_items[0][id]", item_id),
+            ("subscription_items[0][price]", new_price_id),
+            ("subscription_proration_behavior", "always_invoice"),
+        ]
+        .iter()
+        .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+        let invoice = self
+            .api_get(&config, &format!("/v1/invoices/upcoming?{}", query))
+            .await?;
+
+        Ok(proration_preview_from_invoice(&invoice))
     }
 
     // ─── Invoices ────────────────────────────────────────────
@@ -517,6 +705,18 @@ impl StripeService {
         &self,
         customer_id: &str,
         limit: Option<u64>,
+    ) -> Result<Vec<StripeInvoiceResponse>, AppError> {
+        self.list_customer_invoices_in_range(customer_id, limit, None, None)
+            .await
+    }
+
+    /// List invoices for a customer created within `[since, until]` (unix seconds, inclusive)
+    pub async fn list_customer_invoices_in_range(
+        &self,
+        customer_id: &str,
+        limit: Option<u64>,
+        since: Option<i64>,
+        until: Option<i64>,
     ) -> Result<Vec<StripeInvoiceResponse>, AppError> {
         let (_config, client) = self.snapshot();
 
@@ -527,10 +727,18 @@ impl StripeService {
         let mut params = stripe::ListInvoices::new();
         params.customer = Some(cid);
         params.limit = Some(limit.unwrap_or(50));
+        if since.is_some() || until.is_some() {
+            params.created = Some(stripe::RangeQuery::Bounds(stripe::RangeBounds {
+                gt: None,
+                gte: since,
+                lt: None,
+                lte: until,
+            }));
+        }
 
         let invoices = stripe::Invoice::list(&client, &params).await.map_err(|e| {
             tracing::error!(error = %e, customer_id = %customer_id, "Failed to list invoices");
-            AppError::internal("Failed to list invoices")
+            AppError::external("stripe", "Failed to list invoices")
         })?;
 
         Ok(invoices
@@ -597,6 +805,88 @@ impl StripeService {
         })
     }
 
+    /// Stripe-hosted PDF link for `invoice_id`, if it belongs to `customer_id`.
+    ///
+    /// Unknown invoices and other customers' invoices are both reported as
+    /// not found, so invoice IDs cannot be probed.
+    pub async fn invoice_pdf_url(
+        &self,
+        invoice_id: &str,
+        customer_id: &str,
+    ) -> Result<String, AppError> {
+        let (config, _client) = self.snapshot();
+
+        let valid_id = invoice_id.starts_with("in_")
+            && invoice_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_id {
+            return Err(AppError::not_found("Invoice"));
+        }
+
+        let path = format!("/v1/invoices/{}", invoice_id);
+        let request = self
+            .http
+            .inner()
+            .get(format!("{}{}", config.api_base, path))
+            .bearer_auth(&config.secret_key);
+        let invoice = self
+            .api_send_optional(request, &path)
+            .await?
+            .ok_or_else(|| AppError::not_found("Invoice"))?;
+
+        let owner = invoice["customer"]
+            .as_str()
+            .or_else(|| invoice["customer"]["id"].as_str());
+        if owner != Some(customer_id) {
+            tracing::warn!(invoice_id = %invoice_id, "Invoice requested by a different customer");
+            return Err(AppError::not_found("Invoice"));
+        }
+
+        invoice["invoice_pdf"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| AppError::not_found("Invoice PDF"))
+    }
+
+    // ─── Payment Methods ─────────────────────────────────────
+
+    /// Fetch a payment method as raw JSON, for reading its card details
+    pub async fn get_payment_method(
+        &self,
+        payment_method_id: &str,
+    ) -> Result<serde_json::Value, AppError> {
+        let (config, _client) = self.snapshot();
+        self.api_get(
+            &config,
+            &format!(
+                "/v1/payment_methods/{}",
+                urlencoding::encode(payment_method_id)
+            ),
+        )
+        .await
+    }
+
+    /// The customer's default payment method id
+    /// (`invoice_settings.default_payment_method`), if one is set
+    pub async fn get_customer_default_payment_method(
+        &self,
+        customer_id: &str,
+    ) -> Result<Option<String>, AppError> {
+        let (config, _client) = self.snapshot();
+        let customer = self
+            .api_get(
+                &config,
+                &format!("/v1/customers/{}", urlencoding::encode(customer_id)),
+            )
+            .await?;
+        let default_pm = &customer["invoice_settings"]["default_payment_method"];
+        Ok(default_pm
+            .as_str()
+            .or(default_pm["id"].as_str())
+            .map(str::to_string))
+    }
+
     // ─── Webhook Endpoints ───────────────────────────────────
 
     /// List all webhook endpoints from Stripe
@@ -606,20 +896,19 @@ impl StripeService {
         let (config, _client) = self.snapshot();
 
         // Use raw reqwest — async-stripe may not expose WebhookEndpoint in current features
-        let url = "https://api.stripe.com/v1/webhook_endpoints?limit=100";
-        let resp = reqwest::Client::new()
-            .get(url)
-            .bearer_auth(&config.secret_key)
-            .send()
+        let url = format!("{}/v1/webhook_endpoints?limit=100", config.api_base);
+        let resp = self
+            .http
+            .send(self.http.inner().get(&url).bearer_auth(&config.secret_key))
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to list webhook endpoints");
-                AppError::internal("Failed to list webhook endpoints")
+                AppError::external("stripe", "Failed to list webhook endpoints")
             })?;
 
         let body: serde_json::Value = resp.json().await.map_err(|e| {
             tracing::error!(error = %e, "Failed to parse webhook endpoints response");
-            AppError::internal("Failed to list webhook endpoints")
+            AppError::external("stripe", "Failed to list webhook endpoints")
         })?;
 
         let endpoints = body["data"]
@@ -657,27 +946,32 @@ impl StripeService {
             form_params.push((format!("enabled_events[{}]", i), event.clone()));
         }
 
-        let resp = reqwest::Client::new()
-            .post("https://api.stripe.com/v1/webhook_endpoints")
+        // The idempotency key lets the client retry without creating duplicates
+        let request = self
+            .http
+            .inner()
+            .post(format!("{}/v1/webhook_endpoints", config.api_base))
             .bearer_auth(&config.secret_key)
-            .form(&form_params)
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!(error = %e, "Failed to create webhook endpoint");
-                AppError::internal("Failed to create webhook endpoint")
-            })?;
+            .header(IDEMPOTENCY_KEY_HEADER, Uuid::new_v4().to_string())
+            .form(&form_params);
+        let resp = self.http.send(request).await.map_err(|e| {
+            tracing::error!(error = %e, "Failed to create webhook endpoint");
+            AppError::external("stripe", "Failed to create webhook endpoint")
+        })?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let body = resp.text().await.unwrap_or_default();
             tracing::error!(status = %status, body = %body, "Stripe webhook endpoint creation failed");
-            return Err(AppError::internal("Failed to create webhook endpoint"));
+            return Err(AppError::external(
+                "stripe",
+                "Failed to create webhook endpoint",
+            ));
         }
 
         let body: serde_json::Value = resp.json().await.map_err(|e| {
             tracing::error!(error = %e, "Failed to parse webhook endpoint response");
-            AppError::internal("Failed to create webhook endpoint")
+            AppError::external("stripe", "Failed to create webhook endpoint")
         })?;
 
         let endpoint = StripeWebhookEndpointResponse {
@@ -706,26 +1000,25 @@ impl StripeService {
     pub async fn delete_webhook_endpoint(&self, endpoint_id: &str) -> Result<(), AppError> {
         let (config, _client) = self.snapshot();
 
-        let url = format!(
-            "https://api.stripe.com/v1/webhook_endpoints/{}",
-            endpoint_id
-        );
+        let url = format!("{}/v1/webhook_endpoints/{}", config.api_base, endpoint_id);
 
-        let resp = reqwest::Client::new()
-            .delete(&url)
-            .bearer_auth(&config.secret_key)
-            .send()
+        let resp = self
+            .http
+            .send(self.http.inner().delete(&url).bearer_auth(&config.secret_key))
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, endpoint_id = %endpoint_id, "Failed to delete webhook endpoint");
-                AppError::internal("Failed to delete webhook endpoint")
+                AppError::external("stripe", "Failed to delete webhook endpoint")
             })?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let body = resp.text().await.unwrap_or_default();
             tracing::error!(status = %status, body = %body, "Stripe webhook endpoint deletion failed");
-            return Err(AppError::internal("Failed to delete webhook endpoint"));
+            return Err(AppError::external(
+                "stripe",
+                "Failed to delete webhook endpoint",
+            ));
         }
 
         tracing::info!(endpoint_id = %endpoint_id, "Deleted Stripe webhook endpoint");
@@ -736,7 +1029,21 @@ impl StripeService {
 
     /// Create a Stripe customer linked to a platform user
     pub async fn create_customer(&self, email: &str, user_id: Uuid) -> Result<String, AppError> {
-        let (_config, client) = self.snapshot();
+        self.create_customer_on_test_clock(email, user_id, None)
+            .await
+    }
+
+    /// Create a Stripe customer attached to a test clock, so QA can advance
+    /// billing time for it. The clock is dropped in production
+    /// (see [`StripeConfig::test_clock`]).
+    pub async fn create_customer_on_test_clock(
+        &self,
+        email: &str,
+        user_id: Uuid,
+        test_clock: Option<&str>,
+    ) -> Result<String, AppError> {
+        let (config, client) = self.snapshot();
+        let test_clock = config.test_clock(test_clock);
 
         let mut metadata = HashMap::new();
         metadata.insert("user_id".to_string(), user_id.to_string());
@@ -744,6 +1051,7 @@ impl StripeService {
         let params = stripe::CreateCustomer {
             email: Some(email),
             metadata: Some(metadata),
+            test_clock,
             ..Default::default()
         };
 
@@ -751,26 +1059,51 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, email = %email, "Failed to create Stripe customer");
-                AppError::internal("Failed to create payment customer")
+                AppError::external("stripe", "Failed to create payment customer")
             })?;
 
         tracing::info!(
             customer_id = %customer.id,
             user_id = %user_id,
+            test_clock = ?test_clock,
             "Created Stripe customer"
         );
 
         Ok(customer.id.to_string())
     }
 
+    /// Point a Stripe customer's `user_id` metadata at another user, e.g.
+    /// after an admin merged the account that created it into another.
+    pub async fn set_customer_user_id(
+        &self,
+        customer_id: &str,
+        user_id: Uuid,
+    ) -> Result<(), AppError> {
+        let (config, _client) = self.snapshot();
+        self.api_post(
+            &config,
+            &format!("/v1/customers/{}", urlencoding::encode(customer_id)),
+            &[("metadata[user_id]".to_string(), user_id.to_string())],
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
     /// Create a checkout session with a specific price.
+    ///
+    /// In `Setup` mode nothing is charged: the price and trial are carried in
+    /// the session metadata until the webhook creates the subscription.
     pub async fn create_checkout_session(
         &self,
         customer_id: &str,
         user_id: Uuid,
         price_id: &str,
+        trial_days: Option<u32>,
+        mode: CheckoutMode,
     ) -> Result<(String, String), AppError> {
         let (config, client) = self.snapshot();
+        let trial_days = config.trial_days(trial_days);
 
         let mut metadata = HashMap::new();
         metadata.insert("user_id".to_string(), user_id.to_string());
@@ -780,45 +1113,234 @@ impl StripeService {
             AppError::internal("Invalid customer ID")
         })?;
 
-        let params = stripe::CreateCheckoutSession {
-            mode: Some(stripe::CheckoutSessionMode::Subscription),
-            customer: Some(customer_id),
-            line_items: Some(vec![stripe::CreateCheckoutSessionLineItems {
-                price: Some(price_id.to_string()),
-                quantity: Some(1),
-                ..Default::default()
-            }]),
-            success_url: Some(&config.success_url),
-            cancel_url: Some(&config.cancel_url),
-            metadata: Some(metadata.clone()),
-            subscription_data: Some(stripe::CreateCheckoutSessionSubscriptionData {
-                metadata: Some(metadata),
+        let params = match mode {
+            CheckoutMode::Subscription => stripe::CreateCheckoutSession {
+                mode: Some(stripe::CheckoutSessionMode::Subscription),
+                customer: Some(customer_id),
+                line_items: Some(vec![stripe::CreateCheckoutSessionLineItems {
+                    price: Some(price_id.to_string()),
+                    quantity: Some(1),
+                    ..Default::default()
+                }]),
+                success_url: Some(&config.success_url),
+                cancel_url: Some(&config.cancel_url),
+                metadata: Some(metadata.clone()),
+                subscription_data: Some(stripe::CreateCheckoutSessionSubscriptionData {
+                    metadata: Some(metadata),
+                    trial_period_days: trial_days,
+                    ..Default::default()
+                }),
                 ..Default::default()
-            }),
-            ..Default::default()
+            },
+            CheckoutMode::Setup => {
+                metadata.insert("price_id".to_string(), price_id.to_string());
+                if let Some(days) = trial_days {
+                    metadata.insert("trial_days".to_string(), days.to_string());
+                }
+                stripe::CreateCheckoutSession {
+                    mode: Some(stripe::CheckoutSessionMode::Setup),
+                    customer: Some(customer_id),
+                    payment_method_types: Some(vec![
+                        stripe::CreateCheckoutSessionPaymentMethodTypes::Card,
+                    ]),
+                    success_url: Some(&config.success_url),
+                    cancel_url: Some(&config.cancel_url),
+                    metadata: Some(metadata),
+                    ..Default::default()
+                }
+            }
         };
 
         let session = stripe::CheckoutSession::create(&client, params)
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to create Stripe checkout session");
-                AppError::internal("Failed to create checkout session")
+                // Keep Stripe's own reason (e.g. "No such price") with the error
+                let reason = match &e {
+                    stripe::StripeError::Stripe(err) => err.message.as_deref(),
+                    _ => None,
+                };
+                match reason {
+                    Some(reason) => AppError::external(
+                        "stripe",
+                        format!("Failed to create checkout session: {}", reason),
+                    ),
+                    None => AppError::external("stripe", "Failed to create checkout session"),
+                }
             })?;
 
         let session_id = session.id.to_string();
         let checkout_url = session
             .url
-            .ok_or_else(|| AppError::internal("Checkout session missing URL"))?;
+            .ok_or_else(|| AppError::external("stripe", "Checkout session missing URL"))?;
 
         tracing::info!(
             session_id = %session_id,
             price_id = %price_id,
+            trial_days = ?trial_days,
+            mode = ?mode,
             "Created Stripe checkout session"
         );
 
         Ok((session_id, checkout_url))
     }
 
+    /// Finish a setup-mode checkout: attach the payment method the session
+    /// collected, make it the customer's default and subscribe to `price_id`.
+    ///
+    /// The subscription request's idempotency key is derived from the
+    /// SetupIntent, so a redelivered webhook cannot subscribe twice.
+    pub async fn subscribe_with_setup_intent(
+        &self,
+        customer_id: &str,
+        setup_intent_id: &str,
+        price_id: &str,
+        trial_days: Option<u32>,
+        user_id: Uuid,
+    ) -> Result<String, AppError> {
+        let (config, _client) = self.snapshot();
+
+        let intent = self
+            .api_get(&config, &format!("/v1/setup_intents/{}", setup_intent_id))
+            .await?;
+        let payment_method = intent["payment_method"]
+            .as_str()
+            .or_else(|| intent["payment_method"]["id"].as_str())
+            .ok_or_else(|| {
+                tracing::error!(setup_intent_id = %setup_intent_id, "SetupIntent has no payment method");
+                AppError::external("stripe", "Setup intent missing payment method")
+            })?
+            .to_string();
+
+        self.api_post(
+            &config,
+            &format!("/v1/payment_methods/{}/attach", payment_method),
+            &[("customer".to_string(), customer_id.to_string())],
+            None,
+        )
+        .await?;
+
+        self.api_post(
+            &config,
+            &format!("/v1/customers/{}", customer_id),
+            &[(
+                "invoice_settings[default_payment_method]".to_string(),
+                payment_method.clone(),
+            )],
+            None,
+        )
+        .await?;
+
+        let mut form = vec![
+            ("customer".to_string(), customer_id.to_string()),
+            ("items[0][price]".to_string(), price_id.to_string()),
+            ("items[0][quantity]".to_string(), "1".to_string()),
+            ("default_payment_method".to_string(), payment_method),
+            ("metadata[user_id]".to_string(), user_id.to_string()),
+        ];
+        if let Some(days) = config.trial_days(trial_days) {
+            form.push(("trial_period_days".to_string(), days.to_string()));
+        }
+        let subscription = self
+            .api_post(
+                &config,
+                "/v1/subscriptions",
+                &form,
+                Some(format!("setup-subscribe-{}", setup_intent_id)),
+            )
+            .await?;
+
+        let subscription_id = subscription["id"]
+            .as_str()
+            .ok_or_else(|| AppError::external("stripe", "Subscription response missing ID"))?
+            .to_string();
+
+        tracing::info!(
+            subscription_id = %subscription_id,
+            customer_id = %customer_id,
+            setup_intent_id = %setup_intent_id,
+            "Created subscription from setup-mode checkout"
+        );
+
+        Ok(subscription_id)
+    }
+
+    /// GET a raw Stripe API path, failing on non-2xx responses
+    async fn api_get(
+        &self,
+        config: &StripeConfig,
+        path: &str,
+    ) -> Result<serde_json::Value, AppError> {
+        let request = self
+            .http
+            .inner()
+            .get(format!("{}{}", config.api_base, path))
+            .bearer_auth(&config.secret_key);
+        self.api_send(request, path).await
+    }
+
+    /// POST a form to a raw Stripe API path, failing on non-2xx responses
+    async fn api_post(
+        &self,
+        config: &StripeConfig,
+        path: &str,
+        form: &[(String, String)],
+        idempotency_key: Option<String>,
+    ) -> Result<serde_json::Value, AppError> {
+        let request = self
+            .http
+            .inner()
+            .post(format!("{}{}", config.api_base, path))
+            .bearer_auth(&config.secret_key)
+            .header(
+                IDEMPOTENCY_KEY_HEADER,
+                idempotency_key.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            )
+            .form(form);
+        self.api_send(request, path).await
+    }
+
+    async fn api_send(
+        &self,
+        request: reqwest::RequestBuilder,
+        path: &str,
+    ) -> Result<serde_json::Value, AppError> {
+        self.api_send_optional(request, path).await?.ok_or_else(|| {
+            tracing::error!(path = %path, "Stripe object not found");
+            AppError::external("stripe", "Payment provider request failed")
+        })
+    }
+
+    /// Like `api_send`, but a 404 from Stripe is `Ok(None)`
+    async fn api_send_optional(
+        &self,
+        request: reqwest::RequestBuilder,
+        path: &str,
+    ) -> Result<Option<serde_json::Value>, AppError> {
+        let resp = self.http.send(request).await.map_err(|e| {
+            tracing::error!(error = %e, path = %path, "Stripe request failed");
+            AppError::external("stripe", "Payment provider request failed")
+        })?;
+
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            tracing::error!(status = %status, body = %body, path = %path, "Stripe request rejected");
+            return Err(AppError::external(
+                "stripe",
+                "Payment provider request failed",
+            ));
+        }
+
+        resp.json().await.map(Some).map_err(|e| {
+            tracing::error!(error = %e, path = %path, "Failed to parse Stripe response");
+            AppError::external("stripe", "Payment provider request failed")
+        })
+    }
+
     /// Create a $0 subscription for a free/lifetime member so they receive invoices.
     ///
     /// `price_id` must be a recurring Stripe price with unit_amount = 0.
@@ -848,7 +1370,7 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to create free subscription");
-                AppError::internal("Failed to create free subscription")
+                AppError::external("stripe", "Failed to create free subscription")
             })?;
 
         tracing::info!(
@@ -882,14 +1404,14 @@ impl StripeService {
                 .await
                 .map_err(|e| {
                     tracing::error!(error = %e, "Failed to schedule subscription cancellation");
-                    AppError::internal("Failed to cancel subscription")
+                    AppError::external("stripe", "Failed to cancel subscription")
                 })?;
         } else {
             stripe::Subscription::cancel(&client, &sub_id, stripe::CancelSubscription::default())
                 .await
                 .map_err(|e| {
                     tracing::error!(error = %e, "Failed to cancel subscription immediately");
-                    AppError::internal("Failed to cancel subscription")
+                    AppError::external("stripe", "Failed to cancel subscription")
                 })?;
         }
 
@@ -920,7 +1442,7 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to reactivate subscription");
-                AppError::internal("Failed to reactivate subscription")
+                AppError::external("stripe", "Failed to reactivate subscription")
             })?;
 
         tracing::info!(subscription_id = %subscription_id, "Reactivated Stripe subscription");
@@ -932,22 +1454,28 @@ impl StripeService {
     pub async fn create_billing_portal_session(
         &self,
         customer_id: &str,
+        return_url: Option<&str>,
     ) -> Result<String, AppError> {
         let (config, client) = self.snapshot();
 
+        if let Some(url) = return_url {
+            config.validate_portal_return_url(url)?;
+        }
+
         let customer_id: stripe::CustomerId = customer_id.parse().map_err(|_| {
             tracing::error!(customer_id = %customer_id, "Invalid customer ID format");
             AppError::internal("Invalid customer ID")
         })?;
 
         let mut params = stripe::CreateBillingPortalSession::new(customer_id);
-        params.return_url = Some(&config.success_url);
+        params.return_url = Some(return_url.unwrap_or(&config.portal_return_url));
+        params.configuration = config.portal_configuration_id.as_deref();
 
         let session = stripe::BillingPortalSession::create(&client, params)
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to create billing portal session");
-                AppError::internal("Failed to create billing portal session")
+                AppError::external("stripe", "Failed to create billing portal session")
             })?;
 
         Ok(session.url)
@@ -983,30 +1511,32 @@ impl StripeService {
         let ts: i64 = timestamp
             .parse()
             .map_err(|_| AppError::validation("signature", "Invalid timestamp"))?;
+        let (config, _) = self.snapshot();
         let now = chrono::Utc::now().timestamp();
-        if (now - ts).abs() > 300 {
+        if now.abs_diff(ts) > config.webhook_tolerance_secs {
+            // A validly signed but stale event is a replay
             tracing::warn!(
                 timestamp = ts,
                 now = now,
+                tolerance_secs = config.webhook_tolerance_secs,
                 "Webhook timestamp outside tolerance window"
             );
-            return Err(AppError::validation(
-                "signature",
-                "Webhook timestamp too old",
-            ));
+            return Err(AppError::Unauthorized);
         }
 
         let payload_str = std::str::from_utf8(payload)
             .map_err(|_| AppError::validation("body", "Invalid UTF-8 in webhook payload"))?;
         let signed_payload = format!("{}.{}", timestamp, payload_str);
 
-        let (config, _) = self.snapshot();
         let mut mac = HmacSha256::new_from_slice(config.webhook_secret.as_bytes())
             .map_err(|_| AppError::internal("Invalid webhook secret key"))?;
         mac.update(signed_payload.as_bytes());
-        let expected = hex::encode(mac.finalize().into_bytes());
 
-        if signatures.iter().any(|sig| sig == &expected) {
+        // verify_slice compares in constant time
+        let matches = signatures
+            .iter()
+            .any(|sig| hex::decode(sig).is_ok_and(|sig| mac.clone().verify_slice(&sig).is_ok()));
+        if matches {
             Ok(())
         } else {
             tracing::warn!("Webhook signature verification failed");
@@ -1027,14 +1557,12 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, email = %email, "Failed to create Stripe customer for signup");
-                AppError::internal("Failed to initialize payment")
+                AppError::external("stripe", "Failed to initialize payment")
             })?;
 
-        let customer_id: stripe::CustomerId = customer
-            .id
-            .to_string()
-            .parse()
-            .map_err(|_| AppError::internal("Invalid customer ID returned from Stripe"))?;
+        let customer_id: stripe::CustomerId = customer.id.to_string().parse().map_err(|_| {
+            AppError::external("stripe", "Invalid customer ID returned from Stripe")
+        })?;
 
         let intent_params = stripe::CreateSetupIntent {
             customer: Some(customer_id),
@@ -1045,12 +1573,12 @@ impl StripeService {
             .await
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to create SetupIntent for signup");
-                AppError::internal("Failed to initialize payment")
+                AppError::external("stripe", "Failed to initialize payment")
             })?;
 
         let client_secret = setup_intent
             .client_secret
-            .ok_or_else(|| AppError::internal("SetupIntent missing client_secret"))?;
+            .ok_or_else(|| AppError::external("stripe", "SetupIntent missing client_secret"))?;
 
         tracing::info!(
             customer_id = %customer.id,
@@ -1061,19 +1589,96 @@ impl StripeService {
     }
 }
 
+/// Split an upcoming-invoice preview into the prorated charge and the next cycle
+fn proration_preview_from_invoice(invoice: &serde_json::Value) -> StripeProrationPreview {
+    let (mut immediate_amount, mut next_cycle_amount) = (0, 0);
+    for line in invoice["lines"]["data"].as_array().into_iter().flatten() {
+        let amount = line["amount"].as_i64().unwrap_or(0);
+        if line["proration"].as_bool().unwrap_or(false) {
+            immediate_amount += amount;
+        } else {
+            next_cycle_amount += amount;
+        }
+    }
+
+    StripeProrationPreview {
+        immediate_amount,
+        next_cycle_amount,
+        currency: invoice["currency"].as_str().unwrap_or("usd").to_string(),
+        next_payment_at: invoice["next_payment_attempt"].as_i64(),
+    }
+}
+
+/// Convert a listed Stripe subscription to the API response shape
+fn subscription_response(sub: stripe::Subscription) -> StripeSubscriptionResponse {
+    let items: Vec<StripeSubscriptionItemResponse> = sub
+        .items
+        .data
+        .iter()
+        .map(|item| {
+            let price_id = item
+                .price
+                .as_ref()
+                .map(|p| p.id.to_string())
+                .unwrap_or_default();
+            let product_id = item
+                .price
+                .as_ref()
+                .and_then(|p| p.product.as_ref())
+                .map(|prod| match prod {
+                    stripe::Expandable::Id(id) => id.to_string(),
+                    stripe::Expandable::Object(obj) => obj.id.to_string(),
+                })
+                .unwrap_or_default();
+            StripeSubscriptionItemResponse {
+                price_id,
+                product_id,
+                quantity: item.quantity.map(|q| q as u64),
+            }
+        })
+        .collect();
+
+    StripeSubscriptionResponse {
+        id: sub.id.to_string(),
+        status: sub.status.as_str().to_string(),
+        current_period_start: sub.current_period_start,
+        current_period_end: sub.current_period_end,
+        cancel_at_period_end: sub.cancel_at_period_end,
+        trial_end: sub.trial_end,
+        items,
+    }
+}
+
+/// Pick the subscription that decides a customer's membership from
+/// `(created, subscription)` pairs: active or trialing first, then past_due
+/// or unpaid, then incomplete or paused, then ended ones. Ties go to the most
+/// recently created, then the highest ID, so the choice is deterministic.
+fn preferred_subscription(
+    subscriptions: Vec<(i64, StripeSubscriptionResponse)>,
+) -> Option<StripeSubscriptionResponse> {
+    let rank = |status: &str| match status {
+        "active" | "trialing" => 0,
+        "past_due" | "unpaid" => 1,
+        "incomplete" | "paused" => 2,
+        _ => 3,
+    };
+    subscriptions
+        .into_iter()
+        .min_by(|(a_created, a), (b_created, b)| {
+            rank(&a.status)
+                .cmp(&rank(&b.status))
+                .then(b_created.cmp(a_created))
+                .then(b.id.cmp(&a.id))
+        })
+        .map(|(_, sub)| sub)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn test_config() -> StripeConfig {
-        StripeConfig {
-            secret_key: "sk_test_xxx".to_string(),
-            webhook_secret: "whsec_test_secret".to_string(),
-            success_url: "http://localhost/checkout/success".to_string(),
-            cancel_url: "http://localhost/cancel".to_string(),
-            free_price_id: None,
-            app_tag: "a8n-tools".to_string(),
-        }
+        StripeConfig::for_tests(DEFAULT_API_BASE)
     }
 
     fn test_service() -> StripeService {
@@ -1137,6 +1742,729 @@ mod tests {
         let sig = hex::encode(mac.finalize().into_bytes());
 
         let header = format!("t={},v1={}", old_ts, sig);
-        assert!(service.verify_webhook_signature(payload, &header).is_err());
+        assert!(matches!(
+            service.verify_webhook_signature(payload, &header),
+            Err(AppError::Unauthorized)
+        ));
+
+        // A wider tolerance accepts the same delivery
+        let lenient = StripeService::new(StripeConfig {
+            webhook_tolerance_secs: 900,
+            ..test_config()
+        });
+        assert!(lenient.verify_webhook_signature(payload, &header).is_ok());
+    }
+
+    #[test]
+    fn verify_webhook_signature_known_vector() {
+        // Far-past timestamp, so only an unbounded tolerance lets it through
+        let service = StripeService::new(StripeConfig {
+            webhook_tolerance_secs: u64::MAX,
+            ..test_config()
+        });
+        let payload = br#"{"id":"evt_test","type":"invoice.paid"}"#;
+        let sig = "93479c45979609e8919a6bd4267568cd8dc11114383a80abb317e0cf9afd7aa9";
+
+        // Extra schemes and older v1 signatures alongside the valid one are ignored
+        let header = format!("t=1700000000,v0=deadbeef,v1=00ff,v1={}", sig);
+        assert!(service.verify_webhook_signature(payload, &header).is_ok());
+
+        let tampered = br#"{"id":"evt_test","type":"invoice.void"}"#;
+        assert!(matches!(
+            service.verify_webhook_signature(tampered, &header),
+            Err(AppError::Unauthorized)
+        ));
+    }
+
+    // -- Billing portal return URL --
+
+    #[test]
+    fn portal_return_url_on_allowed_host_is_accepted() {
+        let config = test_config();
+        assert!(config
+            .validate_portal_return_url("http://localhost/billing?from=portal")
+            .is_ok());
+        assert!(config
+            .validate_portal_return_url("https://LOCALHOST:5173/membership")
+            .is_ok());
+    }
+
+    #[test]
+    fn portal_return_url_on_other_host_is_rejected() {
+        let config = test_config();
+        for url in [
+            "https://evil.example/billing",
+            "https://localhost.evil.example/",
+            "https://evil.example@/",
+            "javascript:alert(1)",
+            "/billing",
+            "not a url",
+        ] {
+            assert!(
+                config.validate_portal_return_url(url).is_err(),
+                "{url} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn trial_days_are_capped_at_configured_maximum() {
+        let config = test_config();
+        assert_eq!(config.trial_days(None), None);
+        assert_eq!(config.trial_days(Some(7)), Some(7));
+        assert_eq!(config.trial_days(Some(14)), Some(14));
+        assert_eq!(config.trial_days(Some(90)), Some(14));
+        assert_eq!(config.trial_days(Some(0)), None);
+    }
+
+    #[test]
+    fn trial_days_disabled_when_maximum_is_zero() {
+        let config = StripeConfig {
+            max_trial_days: 0,
+            ..test_config()
+        };
+        assert_eq!(config.trial_days(Some(14)), None);
+    }
+
+    #[test]
+    fn portal_allowed_hosts_defaults_to_frontend_host() {
+        assert_eq!(
+            portal_allowed_hosts(None, "https://App.Example.com"),
+            vec!["app.example.com".to_string()]
+        );
+        assert_eq!(
+            portal_allowed_hosts(Some(" a.example , B.example "), "https://app.example.com"),
+            vec!["a.example".to_string(), "b.example".to_string()]
+        );
+        assert_eq!(
+            portal_allowed_hosts(Some(""), "http://localhost:5173"),
+            vec!["localhost".to_string()]
+        );
+    }
+
+    // -- Setup-mode checkout (against a mock Stripe backend) --
+
+    fn mock_service(server: &wiremock::MockServer) -> StripeService {
+        StripeService::new(StripeConfig {
+            api_base: server.uri(),
+            ..test_config()
+        })
+    }
+
+    /// A setup-mode session as the Stripe API returns it
+    fn setup_session_fixture() -> serde_json::Value {
+        serde_json::json!({
+            "id": "cs_test_setup",
+            "object": "checkout.session",
+            "after_expiration": null,
+            "allow_promotion_codes": null,
+            "amount_subtotal": null,
+            "amount_total": null,
+            "automatic_tax": { "enabled": false, "liability": null, "status": null },
+            "billing_address_collection": null,
+            "cancel_url": "http://localhost/cancel",
+            "client_reference_id": null,
+            "consent": null,
+            "consent_collection": null,
+            "created": 1_700_000_000,
+            "currency": null,
+            "custom_fields": [],
+            "custom_text": {
+                "after_submit": null,
+                "shipping_address": null,
+                "submit": null,
+                "terms_of_service_acceptance": null
+            },
+            "customer": "cus_test",
+            "customer_creation": null,
+            "customer_details": null,
+            "customer_email": null,
+            "expires_at": 1_700_086_400,
+            "invoice": null,
+            "invoice_creation": null,
+            "livemode": false,
+            "locale": null,
+            "metadata": { "price_id": "price_123" },
+            "mode": "setup",
+            "payment_intent": null,
+            "payment_link": null,
+            "payment_method_collection": null,
+            "payment_method_options": null,
+            "payment_method_types": ["card"],
+            "payment_status": "no_payment_required",
+            "phone_number_collection": { "enabled": false },
+            "recovered_from": null,
+            "setup_intent": "seti_test",
+            "shipping_address_collection": null,
+            "shipping_cost": null,
+            "shipping_details": null,
+            "shipping_options": [],
+            "status": "open",
+            "submit_type": null,
+            "subscription": null,
+            "success_url": "http://localhost/checkout/success",
+            "total_details": null,
+            "ui_mode": "hosted",
+            "url": "https://checkout.stripe.com/c/pay/cs_test_setup"
+        })
+    }
+
+    #[actix_rt::test]
+    async fn subscription_mode_session_posts_the_price_and_user() {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let mut session = setup_session_fixture();
+        session["id"] = serde_json::json!("cs_test_sub");
+        session["mode"] = serde_json::json!("subscription");
+        Mock::given(method("POST"))
+            .and(path("/v1/checkout/sessions"))
+            .and(body_string_contains("mode=subscription"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(session))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let user_id = Uuid::new_v4();
+        let (session_id, url) = mock_service(&server)
+            .create_checkout_session(
+                "cus_test",
+                user_id,
+                "price_123",
+                None,
+                CheckoutMode::Subscription,
+            )
+            .await
+            .unwrap();
+        assert_eq!(session_id, "cs_test_sub");
+        assert!(url.starts_with("https://checkout.stripe.com/"));
+
+        let requests = server.received_requests().await.unwrap();
+        let body = String::from_utf8_lossy(&requests[0].body).to_string();
+        for expected in [
+            "customer=cus_test",
+            "line_items[0][price]=price_123",
+            "line_items[0][quantity]=1",
+            &format!("metadata[user_id]={}", user_id),
+            "success_url=",
+            "cancel_url=",
+        ] {
+            let encoded = expected.replace('[', "%5B").replace(']', "%5D");
+            assert!(
+                body.contains(expected) || body.contains(&encoded),
+                "missing {} in {}",
+                expected,
+                body
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn checkout_session_surfaces_the_stripe_error_message() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/checkout/sessions"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": "No such price: 'price_missing'",
+                    "param": "line_items[0][price]"
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        let err = mock_service(&server)
+            .create_checkout_session(
+                "cus_test",
+                Uuid::new_v4(),
+                "price_missing",
+                None,
+                CheckoutMode::Subscription,
+            )
+            .await
+            .unwrap_err();
+        match err {
+            AppError::Upstream { service, message } => {
+                assert_eq!(service, Some("stripe"));
+                assert!(message.contains("No such price"), "{}", message);
+            }
+            other => panic!("expected a Stripe error, got {:?}", other),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn setup_mode_session_collects_a_payment_method_only() {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/checkout/sessions"))
+            .and(body_string_contains("mode=setup"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(setup_session_fixture()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let user_id = Uuid::new_v4();
+        let (session_id, url) = mock_service(&server)
+            .create_checkout_session(
+                "cus_test",
+                user_id,
+                "price_123",
+                Some(7),
+                CheckoutMode::Setup,
+            )
+            .await
+            .unwrap();
+        assert_eq!(session_id, "cs_test_setup");
+        assert!(url.starts_with("https://checkout.stripe.com/"));
+
+        let requests = server.received_requests().await.unwrap();
+        let body = String::from_utf8_lossy(&requests[0].body).to_string();
+        assert!(body.contains("price_123"), "{}", body);
+        assert!(body.contains(&user_id.to_string()), "{}", body);
+        assert!(!body.contains("line_items"), "{}", body);
+        assert!(!body.contains("subscription_data"), "{}", body);
+    }
+
+    #[test]
+    fn test_clock_is_only_allowed_outside_production() {
+        let production = test_config();
+        let staging = StripeConfig {
+            allow_test_clocks: true,
+            ..test_config()
+        };
+        assert_eq!(production.test_clock(Some("clock_abc")), None);
+        assert_eq!(staging.test_clock(Some("clock_abc")), Some("clock_abc"));
+        assert_eq!(staging.test_clock(Some("cus_abc")), None);
+        assert_eq!(staging.test_clock(None), None);
+    }
+
+    /// Create a customer against a mock backend and return the request body
+    async fn created_customer_body(allow_test_clocks: bool) -> String {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/customers"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "id": "cus_clock", "object": "customer" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let service = StripeService::new(StripeConfig {
+            api_base: server.uri(),
+            allow_test_clocks,
+            ..test_config()
+        });
+        let customer_id = service
+            .create_customer_on_test_clock("qa@example.com", Uuid::new_v4(), Some("clock_abc"))
+            .await
+            .unwrap();
+        assert_eq!(customer_id, "cus_clock");
+
+        let requests = server.received_requests().await.unwrap();
+        String::from_utf8_lossy(&requests[0].body).to_string()
+    }
+
+    #[actix_rt::test]
+    async fn test_clock_is_forwarded_outside_production() {
+        let body = created_customer_body(true).await;
+        assert!(body.contains("test_clock=clock_abc"), "{}", body);
+    }
+
+    #[actix_rt::test]
+    async fn test_clock_is_dropped_in_production() {
+        let body = created_customer_body(false).await;
+        assert!(!body.contains("test_clock"), "{}", body);
+    }
+
+    #[actix_rt::test]
+    async fn setup_intent_payment_method_is_attached_then_subscribed() {
+        use wiremock::matchers::{body_string_contains, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/setup_intents/seti_test"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "seti_test",
+                "object": "setup_intent",
+                "payment_method": "pm_test",
+                "status": "succeeded"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/payment_methods/pm_test/attach"))
+            .and(body_string_contains("customer=cus_test"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "id": "pm_test", "customer": "cus_test" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/customers/cus_test"))
+            .and(body_string_contains(
+                "invoice_settings%5Bdefault_payment_method%5D=pm_test",
+            ))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": "cus_test" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/subscriptions"))
+            .and(header(IDEMPOTENCY_KEY_HEADER, "setup-subscribe-seti_test"))
+            .and(body_string_contains("default_payment_method=pm_test"))
+            .and(body_string_contains("price%5D=price_123"))
+            .and(body_string_contains("trial_period_days=7"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "id": "sub_test", "status": "trialing" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let subscription_id = mock_service(&server)
+            .subscribe_with_setup_intent(
+                "cus_test",
+                "seti_test",
+                "price_123",
+                Some(7),
+                Uuid::new_v4(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(subscription_id, "sub_test");
+    }
+
+    #[actix_rt::test]
+    async fn attach_failure_stops_before_subscribing() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/setup_intents/seti_test"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({ "id": "seti_test", "payment_method": "pm_test" }),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/payment_methods/pm_test/attach"))
+            .respond_with(ResponseTemplate::new(402).set_body_json(serde_json::json!({
+                "error": { "type": "card_error", "code": "card_declined" }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/subscriptions"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let result = mock_service(&server)
+            .subscribe_with_setup_intent("cus_test", "seti_test", "price_123", None, Uuid::new_v4())
+            .await;
+        assert!(result.is_err());
+    }
+
+    // -- Invoice PDF links --
+
+    async fn mock_invoice(server: &wiremock::MockServer, id: &str, invoice: serde_json::Value) {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        Mock::given(method("GET"))
+            .and(path(format!("/v1/invoices/{}", id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(invoice))
+            .mount(server)
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn invoice_pdf_url_resolves_for_the_owning_customer() {
+        let server = wiremock::MockServer::start().await;
+        mock_invoice(
+            &server,
+            "in_owned",
+            serde_json::json!({
+                "id": "in_owned",
+                "customer": "cus_owner",
+                "invoice_pdf": "https://pay.stripe.com/invoice/acct_1/in_owned/pdf",
+            }),
+        )
+        .await;
+        mock_invoice(
+            &server,
+            "in_expanded",
+            serde_json::json!({
+                "id": "in_expanded",
+                "customer": { "id": "cus_owner" },
+                "invoice_pdf": "https://pay.stripe.com/invoice/acct_1/in_expanded/pdf",
+            }),
+        )
+        .await;
+
+        let service = mock_service(&server);
+        assert_eq!(
+            service
+                .invoice_pdf_url("in_owned", "cus_owner")
+                .await
+                .unwrap(),
+            "https://pay.stripe.com/invoice/acct_1/in_owned/pdf"
+        );
+        assert_eq!(
+            service
+                .invoice_pdf_url("in_expanded", "cus_owner")
+                .await
+                .unwrap(),
+            "https://pay.stripe.com/invoice/acct_1/in_expanded/pdf"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn invoice_pdf_url_hides_other_customers_and_unknown_invoices() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, ResponseTemplate};
+
+        let server = wiremock::MockServer::start().await;
+        mock_invoice(
+            &server,
+            "in_other",
+            serde_json::json!({
+                "id": "in_other",
+                "customer": "cus_someone_else",
+                "invoice_pdf": "https://pay.stripe.com/invoice/acct_1/in_other/pdf",
+            }),
+        )
+        .await;
+        mock_invoice(
+            &server,
+            "in_draft",
+            serde_json::json!({ "id": "in_draft", "customer": "cus_owner", "invoice_pdf": null }),
+        )
+        .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "error": { "type": "invalid_request_error", "code": "resource_missing" }
+            })))
+            .mount(&server)
+            .await;
+
+        let service = mock_service(&server);
+        for invoice_id in [
+            "in_other",
+            "in_draft",
+            "in_missing",
+            "../customers/cus_owner",
+        ] {
+            let err = service
+                .invoice_pdf_url(invoice_id, "cus_owner")
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, AppError::NotFound { .. }),
+                "{}: {:?}",
+                invoice_id,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn checkout_mode_defaults_to_subscription() {
+        assert_eq!(CheckoutMode::default(), CheckoutMode::Subscription);
+        let mode: CheckoutMode = serde_json::from_str("\"setup\"").unwrap();
+        assert_eq!(mode, CheckoutMode::Setup);
+    }
+
+    // -- Proration preview --
+
+    fn upcoming_invoice_fixture() -> serde_json::Value {
+        serde_json::json!({
+            "object": "invoice",
+            "currency": "usd",
+            "next_payment_attempt": 1_700_086_400,
+            "lines": { "data": [
+                { "amount": -500, "proration": true, "description": "Unused time on Standard" },
+                { "amount": 1500, "proration": true, "description": "Remaining time on Pro" },
+                { "amount": 2000, "proration": false, "description": "1 x Pro" }
+            ]}
+        })
+    }
+
+    #[test]
+    fn proration_lines_are_the_immediate_charge() {
+        assert_eq!(
+            proration_preview_from_invoice(&upcoming_invoice_fixture()),
+            StripeProrationPreview {
+                immediate_amount: 1000,
+                next_cycle_amount: 2000,
+                currency: "usd".to_string(),
+                next_payment_at: Some(1_700_086_400),
+            }
+        );
+    }
+
+    #[actix_rt::test]
+    async fn proration_preview_swaps_the_subscription_item_price() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/subscriptions/sub_test"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "sub_test",
+                "items": { "data": [{ "id": "si_test", "price": { "id": "price_old" } }] }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/invoices/upcoming"))
+            .and(query_param("subscription", "sub_test"))
+            .and(query_param("subscription_items[0][id]", "si_test"))
+            .and(query_param("subscription_items[0][price]", "price_new"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(upcoming_invoice_fixture()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let preview = mock_service(&server)
+            .preview_proration("sub_test", "price_new")
+            .await
+            .unwrap();
+        assert_eq!(preview.immediate_amount, 1000);
+        assert_eq!(preview.next_cycle_amount, 2000);
+
+        // Only reads: nothing may be posted to Stripe
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|r| r.method.as_str() == "GET"));
+    }
+
+    // -- Live subscription lookup (drift reconciliation) --
+
+    #[actix_rt::test]
+    async fn customer_subscription_reports_status_period_and_trial() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/subscriptions"))
+            .and(query_param("customer", "cus_drift"))
+            .and(query_param("status", "all"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "object": "list",
+                "url": "/v1/subscriptions",
+                "has_more": false,
+                "data": [{
+                    "id": "sub_drift",
+                    "object": "subscription",
+                    "automatic_tax": { "enabled": false },
+                    "billing_cycle_anchor": 1_700_000_000,
+                    "cancel_at_period_end": false,
+                    "collection_method": "charge_automatically",
+                    "created": 1_700_000_000,
+                    "currency": "usd",
+                    "current_period_start": 1_700_000_000,
+                    "current_period_end": 1_702_592_000,
+                    "customer": "cus_drift",
+                    "livemode": false,
+                    "metadata": {},
+                    "start_date": 1_700_000_000,
+                    "status": "trialing",
+                    "trial_end": 1_701_209_600,
+                    "items": {
+                        "object": "list",
+                        "url": "/v1/subscription_items?subscription=sub_drift",
+                        "has_more": false,
+                        "data": [{
+                            "id": "si_drift",
+                            "object": "subscription_item",
+                            "created": 1_700_000_000,
+                            "metadata": {},
+                            "quantity": 1,
+                            "price": {
+                                "id": "price_standard",
+                                "object": "price",
+                                "active": true,
+                                "currency": "usd",
+                                "product": "prod_standard",
+                                "type": "recurring",
+                                "unit_amount": 900
+                            }
+                        }]
+                    }
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let sub = mock_service(&server)
+            .get_customer_subscription("cus_drift")
+            .await
+            .unwrap()
+            .expect("live subscription");
+        assert_eq!(sub.id, "sub_drift");
+        assert_eq!(sub.status, "trialing");
+        assert_eq!(sub.current_period_end, 1_702_592_000);
+        assert_eq!(sub.trial_end, Some(1_701_209_600));
+        assert_eq!(sub.items[0].product_id, "prod_standard");
+    }
+
+    fn listed(id: &str, status: &str) -> StripeSubscriptionResponse {
+        StripeSubscriptionResponse {
+            id: id.to_string(),
+            status: status.to_string(),
+            current_period_start: 0,
+            current_period_end: 0,
+            cancel_at_period_end: false,
+            trial_end: None,
+            items: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn live_subscription_wins_over_a_newer_incomplete_one() {
+        let picked = preferred_subscription(vec![
+            (2_000, listed("sub_retry", "incomplete")),
+            (1_000, listed("sub_live", "active")),
+            (3_000, listed("sub_old", "canceled")),
+        ])
+        .unwrap();
+        assert_eq!(picked.id, "sub_live");
+    }
+
+    #[test]
+    fn most_recent_subscription_wins_when_none_is_live() {
+        let picked = preferred_subscription(vec![
+            (1_000, listed("sub_first", "canceled")),
+            (2_000, listed("sub_second", "canceled")),
+        ])
+        .unwrap();
+        assert_eq!(picked.id, "sub_second");
+        assert!(preferred_subscription(Vec::new()).is_none());
     }
 }
@@ -79,7 +79,11 @@ impl TotpService {
             totp_record.key_version,
         )?;
         if !self.check_code(&secret, code)? {
-            return Err(AppError::validation("code", "Invalid verification code"));
+            return Err(AppError::validation_coded(
+                "code",
+                "totp_code_invalid",
+                "Invalid verification code",
+            ));
         }
 
         // Mark as verified
@@ -118,8 +122,9 @@ impl TotpService {
 
         for recovery_code in unused_codes {
             if Self::verify_code_against_hash(&normalized, &recovery_code.code_hash)? {
-                TotpRepository::mark_recovery_code_used(&self.pool, recovery_code.id).await?;
-                return Ok(true);
+                let claimed =
+                    TotpRepository::mark_recovery_code_used(&self.pool, recovery_code.id).await?;
+                return Ok(claimed);
             }
         }
 
@@ -472,4 +477,58 @@ mod tests {
         assert!(TotpService::verify_code_against_hash(code, &hash).unwrap());
         assert!(!TotpService::verify_code_against_hash("WRONG123", &hash).unwrap());
     }
+
+    async fn maybe_pool() -> Option<PgPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        PgPool::connect(&url).await.ok()
+    }
+
+    #[actix_rt::test]
+    async fn recovery_codes_are_single_use() {
+        let Some(pool) = maybe_pool().await else {
+            return;
+        };
+
+        let user = crate::repositories::UserRepository::create(
+            &pool,
+            crate::models::CreateUser {
+                email: format!("recovery-{}@example.com", Uuid::new_v4()),
+                password_hash: None,
+                role: crate::models::UserRole::Subscriber,
+            },
+        )
+        .await
+        .unwrap();
+        let service = TotpService::new(test_key_set(), "a8n".to_string(), pool.clone());
+        let codes = service
+            .generate_and_store_recovery_codes(user.id)
+            .await
+            .unwrap();
+
+        let first = service
+            .verify_recovery_code(user.id, &codes[0])
+            .await
+            .unwrap();
+        let replay = service
+            .verify_recovery_code(user.id, &codes[0])
+            .await
+            .unwrap();
+        // Two logins racing with the same code: only one may spend it
+        let (a, b) = tokio::join!(
+            service.verify_recovery_code(user.id, &codes[1]),
+            service.verify_recovery_code(user.id, &codes[1]),
+        );
+        let remaining = service.recovery_codes_remaining(user.id).await.unwrap();
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(first);
+        assert!(!replay);
+        assert_eq!([a.unwrap(), b.unwrap()].iter().filter(|ok| **ok).count(), 1);
+        assert_eq!(remaining, codes.len() as i64 - 2);
+    }
 }
@@ -1,5 +1,9 @@
 //! Request validation utilities
 
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::config::SignupPolicyConfig;
 use crate::errors::AppError;
 use validator::ValidationError;
 
@@ -11,6 +15,8 @@ impl ValidationRules {
     pub const PASSWORD_MIN_LENGTH: usize = 12;
     pub const PASSWORD_MAX_LENGTH: usize = 128;
     pub const SLUG_PATTERN: &'static str = r"^[a-z0-9-]+$";
+    /// Slugs taken by fixed routes under `/applications` (see `routes::application`)
+    pub const RESERVED_APPLICATION_SLUGS: &'static [&'static str] = &["status"];
 }
 
 /// Common password list for strength validation
@@ -21,6 +27,31 @@ const COMMON_PASSWORDS: &[&str] = &[
     "administrator",
 ];
 
+/// Read a banned-password list: one password per line, blank lines and
+/// `#` comments skipped. Entries are lowercased, like the lookup, and the
+/// embedded common passwords are always included.
+pub fn load_banned_passwords(path: &Path) -> std::io::Result<HashSet<String>> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut banned: HashSet<String> = COMMON_PASSWORDS.iter().map(|p| p.to_string()).collect();
+    banned.extend(
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase),
+    );
+    Ok(banned)
+}
+
+/// Whether `password` is on `banned`, or on the embedded list if none was loaded
+fn is_banned_password(password: &str, banned: Option<&HashSet<String>>) -> bool {
+    let password = password.to_lowercase();
+    match banned {
+        Some(banned) => banned.contains(&password),
+        None => COMMON_PASSWORDS.contains(&password.as_str()),
+    }
+}
+
 /// Validate email format (returns ValidationError)
 pub fn validate_email_format(email: &str) -> Result<(), ValidationError> {
     if email.is_empty() {
@@ -54,12 +85,66 @@ pub fn validate_email(email: &str) -> Result<(), AppError> {
             "invalid_email_format" => "Invalid email format",
             _ => "Invalid email",
         };
-        AppError::validation("email", message)
+        AppError::validation_coded("email", e.code, message)
     })
 }
 
-/// Validate password strength
+/// Reject signups for reserved addresses or denied domains.
+///
+/// Plus-addressing is ignored when matching reserved local parts, so
+/// `support+x@` is treated as `support@`.
+pub fn validate_signup_email(email: &str, policy: &SignupPolicyConfig) -> Result<(), AppError> {
+    let email = email.trim().to_lowercase();
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return Ok(());
+    };
+    let base_local = local.split('+').next().unwrap_or(local);
+    let base_email = format!("{}@{}", base_local, domain);
+
+    let reserved = policy
+        .reserved_emails
+        .iter()
+        .any(|entry| match entry.strip_suffix('@') {
+            Some(reserved_local) => reserved_local == base_local,
+            None => *entry == email || *entry == base_email,
+        });
+    if reserved {
+        return Err(AppError::validation_coded(
+            "email",
+            "email_reserved",
+            "This email address is reserved",
+        ));
+    }
+
+    let denied = policy
+        .denied_domains
+        .iter()
+        .any(|entry| match entry.strip_prefix("*.") {
+            Some(parent) => domain == parent || domain.ends_with(&format!(".{}", parent)),
+            None => *entry == domain,
+        });
+    if denied {
+        return Err(AppError::validation_coded(
+            "email",
+            "email_domain_denied",
+            "Signups from this email domain are not allowed",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validate password strength against the embedded common-password list
 pub fn validate_password_strength(password: &str) -> Result<(), ValidationError> {
+    check_password_strength(password, None)
+}
+
+/// [`validate_password_strength`] against a list from [`load_banned_passwords`];
+/// `None` falls back to the embedded list
+pub fn check_password_strength(
+    password: &str,
+    banned: Option<&HashSet<String>>,
+) -> Result<(), ValidationError> {
     if password.len() < ValidationRules::PASSWORD_MIN_LENGTH {
         let mut err = ValidationError::new("password_too_short");
         err.message = Some(
@@ -112,8 +197,8 @@ pub fn validate_password_strength(password: &str) -> Result<(), ValidationError>
         return Err(err);
     }
 
-    // Check against common passwords
-    if COMMON_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+    // Check against banned passwords
+    if is_banned_password(password, banned) {
         let mut err = ValidationError::new("password_too_common");
         err.message = Some("Password is too common".into());
         return Err(err);
@@ -148,6 +233,56 @@ pub fn validate_slug(slug: &str) -> Result<(), ValidationError> {
 mod tests {
     use super::*;
 
+    fn signup_policy() -> SignupPolicyConfig {
+        SignupPolicyConfig {
+            reserved_emails: vec!["support@".into(), "ceo@example.org".into()],
+            denied_domains: vec!["a8n.tools".into(), "*.corp.example".into()],
+        }
+    }
+
+    #[test]
+    fn test_validate_signup_email_reserved_address() {
+        let policy = signup_policy();
+        assert!(validate_signup_email("support@gmail.com", &policy).is_err());
+        assert!(validate_signup_email("Support+billing@gmail.com", &policy).is_err());
+        assert!(validate_signup_email("ceo@example.org", &policy).is_err());
+        assert!(validate_signup_email("ceo@example.com", &policy).is_ok());
+        assert!(validate_signup_email("supporter@gmail.com", &policy).is_ok());
+    }
+
+    #[test]
+    fn test_validate_signup_email_denied_domain() {
+        let policy = signup_policy();
+        let err = validate_signup_email("dev@A8N.tools", &policy).unwrap_err();
+        assert_eq!(err.error_code(), "VALIDATION_ERROR");
+        assert!(validate_signup_email("dev@mail.a8n.tools", &policy).is_ok());
+        assert!(validate_signup_email("dev@corp.example", &policy).is_err());
+        assert!(validate_signup_email("dev@eu.corp.example", &policy).is_err());
+        assert!(validate_signup_email("dev@notcorp.example", &policy).is_ok());
+    }
+
+    #[test]
+    fn test_validate_signup_email_codes() {
+        let policy = signup_policy();
+        for (email, expected) in [
+            ("support@gmail.com", "email_reserved"),
+            ("dev@a8n.tools", "email_domain_denied"),
+        ] {
+            match validate_signup_email(email, &policy).unwrap_err() {
+                AppError::ValidationError { field, code, .. } => {
+                    assert_eq!((field.as_str(), code.as_str()), ("email", expected));
+                }
+                other => panic!("Expected ValidationError, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_signup_email_empty_policy_allows_all() {
+        let policy = SignupPolicyConfig::default();
+        assert!(validate_signup_email("support@a8n.tools", &policy).is_ok());
+    }
+
     #[test]
     fn test_validate_email() {
         assert!(validate_email_format("user@example.com").is_ok());
@@ -182,6 +317,30 @@ mod tests {
         assert!(validate_password_strength("NoSpecial123").is_err());
     }
 
+    #[test]
+    fn test_password_in_loaded_banned_list_is_rejected() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("banned.txt");
+        std::fs::write(
+            &path,
+            "# top passwords\n\nCorrectHorse1!\n  Tr0ub4dor&3xyz  \n",
+        )
+        .unwrap();
+        let banned = load_banned_passwords(&path).unwrap();
+
+        // Matching ignores case and the whitespace around entries in the file
+        for pw in ["CorrectHorse1!", "cORRECThORSE1!", "Tr0ub4dor&3xyz"] {
+            let result = check_password_strength(pw, Some(&banned));
+            assert_eq!(result.unwrap_err().code.as_ref(), "password_too_common");
+        }
+
+        // The embedded defaults stay banned alongside the file
+        assert!(banned.contains("administrator"));
+        assert!(!banned.iter().any(|p| p.starts_with('#')));
+
+        assert!(check_password_strength("SecurePass123!", Some(&banned)).is_ok());
+    }
+
     #[test]
     fn test_password_too_long() {
         // 129 chars total: 100 uppercase + 21 lowercase + "12345678" (8 digits)
@@ -222,8 +381,13 @@ mod tests {
         let result = validate_email("invalid");
         assert!(result.is_err());
         match result.unwrap_err() {
-            crate::errors::AppError::ValidationError { field, message } => {
+            crate::errors::AppError::ValidationError {
+                field,
+                code,
+                message,
+            } => {
                 assert_eq!(field, "email");
+                assert_eq!(code, "invalid_email_format");
                 assert_eq!(message, "Invalid email format");
             }
             other => panic!("Expected ValidationError, got {:?}", other),
@@ -50,9 +50,10 @@ import { AdminFeedbackPage } from '@/pages/admin/AdminFeedbackPage'
 import { AdminStripePage } from '@/pages/admin/AdminStripePage'
 import AdminTierSettingsPage from '@/pages/admin/AdminTierSettingsPage'
 
-// Settings pages (public, token-based)
+// Settings pages (token-based)
 import { ConfirmEmailPage } from '@/pages/settings/ConfirmEmailPage'
 import { VerifyEmailPage } from '@/pages/settings/VerifyEmailPage'
+import { SetPasswordPage } from '@/pages/settings/SetPasswordPage'
 
 // Error pages
 import { NotFoundPage } from '@/pages/errors/NotFoundPage'
@@ -235,6 +236,7 @@ export default function App() {
           <Route path="/downloads" element={<DownloadsPage />} />
           <Route path="/settings" element={<SettingsPage />} />
           <Route path="/settings/2fa/setup" element={<TwoFactorSetupPage />} />
+          <Route path="/settings/set-password" element={<SetPasswordPage />} />
           <Route path="/membership-required" element={<MembershipRequiredPage />} />
         </Route>
 
@@ -33,6 +33,8 @@ export interface AdminMembership {
   status: string
   subscription_tier: string
   subscription_override_by: string | null
+  cancellation_reason: string | null
+  cancellation_feedback: string | null
   created_at: string
 }
 
@@ -315,8 +317,8 @@ export const adminApi = {
 
   // Applications
   getApplications: async (): Promise<AdminApplication[]> => {
-    const response = await apiClient.get<{ applications: AdminApplication[] }>('/admin/applications')
-    return response.applications
+    const response = await apiClient.get<PaginatedResponse<AdminApplication>>('/admin/applications?per_page=100')
+    return response.items
   },
 
   updateApplication: (appId: string, data: UpdateApplicationRequest): Promise<AdminApplication> =>
@@ -39,6 +39,9 @@ export const authApi = {
   confirmPasswordReset: (data: PasswordResetConfirmRequest): Promise<{ message: string }> =>
     apiClient.post('/auth/password-reset/confirm', data),
 
+  setPassword: (data: { token: string; new_password: string }): Promise<void> =>
+    apiClient.post('/auth/set-password', data),
+
   changePassword: (data: { current_password: string; new_password: string }): Promise<void> =>
     apiClient.put('/users/me/password', data),
 
@@ -61,7 +64,7 @@ export const authApi = {
   confirm2FA: (data: { code: string }): Promise<RecoveryCodesResponse> =>
     apiClient.post('/auth/2fa/confirm', data),
 
-  verify2FA: (data: { challenge_token: string; code: string }): Promise<AuthResponse> =>
+  verify2FA: (data: { challenge_token?: string; code: string }): Promise<AuthResponse> =>
     apiClient.post('/auth/2fa/verify', data),
 
   disable2FA: (data: { password: string }): Promise<void> =>
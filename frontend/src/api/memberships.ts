@@ -10,6 +10,19 @@ export interface SubscribeResponse {
   membership_status: string
 }
 
+export type CancellationReason =
+  | 'too_expensive'
+  | 'not_using'
+  | 'missing_features'
+  | 'technical_issues'
+  | 'switching_service'
+  | 'other'
+
+export interface CancelMembershipRequest {
+  reason?: CancellationReason
+  feedback?: string
+}
+
 export const membershipApi = {
   getCurrent: (): Promise<Membership | null> =>
     apiClient.get('/memberships/me'),
@@ -20,8 +33,8 @@ export const membershipApi = {
   subscribe: (): Promise<SubscribeResponse> =>
     apiClient.post('/memberships/subscribe'),
 
-  cancel: (): Promise<void> =>
-    apiClient.post('/memberships/cancel'),
+  cancel: (data: CancelMembershipRequest = {}): Promise<void> =>
+    apiClient.post('/memberships/cancel', data),
 
   cancelNow: (): Promise<void> =>
     apiClient.post('/memberships/cancel-now'),
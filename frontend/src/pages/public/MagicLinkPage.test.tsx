@@ -47,6 +47,27 @@ describe('MagicLinkPage', () => {
     })
   })
 
+  describe('click-through errors', () => {
+    it('explains an error code from the email link redirect', () => {
+      window.history.pushState({}, '', '/magic-link?error=RATE_LIMITED')
+
+      render(<MagicLinkPage />)
+
+      expect(screen.getByText(/too many sign-in attempts/i)).toBeInTheDocument()
+      expect(screen.getByRole('button', { name: /send magic link/i })).toBeInTheDocument()
+      window.history.pushState({}, '', '/')
+    })
+
+    it('treats unknown codes as an invalid link', () => {
+      window.history.pushState({}, '', '/magic-link?error=INVALID_CREDENTIALS')
+
+      render(<MagicLinkPage />)
+
+      expect(screen.getByText(/invalid or has already been used/i)).toBeInTheDocument()
+      window.history.pushState({}, '', '/')
+    })
+  })
+
   describe('verification', () => {
     it('shows verifying state when token is in URL', async () => {
       render(<MagicLinkPage />, {
@@ -21,12 +21,28 @@ const magicLinkSchema = z.object({
 
 type MagicLinkFormData = z.infer<typeof magicLinkSchema>
 
+/** Message for the `error` code the API's email click-through redirects with */
+function linkErrorMessage(code: string | null): string | null {
+  switch (code) {
+    case null:
+      return null
+    case 'RATE_LIMITED':
+      return 'Too many sign-in attempts. Please wait a few minutes and try again.'
+    case 'TOKEN_EXPIRED':
+      return 'That magic link has expired. Request a new one below.'
+    default:
+      return 'That magic link is invalid or has already been used. Request a new one below.'
+  }
+}
+
 export function MagicLinkPage() {
   const emailEnabled = useEmailConfigStore((s) => s.emailEnabled)
   const [searchParams] = useSearchParams()
   const token = searchParams.get('token')
   const [isLoading, setIsLoading] = useState(false)
-  const [error, setError] = useState<string | null>(null)
+  const [error, setError] = useState<string | null>(() =>
+    linkErrorMessage(searchParams.get('error'))
+  )
   const [success, setSuccess] = useState(false)
 
   const {
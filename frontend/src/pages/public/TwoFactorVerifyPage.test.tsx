@@ -64,6 +64,16 @@ describe('TwoFactorVerifyPage', () => {
     expect(screen.getByText(/use authenticator app instead/i)).toBeInTheDocument()
   })
 
+  it('shows 2FA form for a challenge handed off in a cookie', () => {
+    window.history.pushState({}, '', '/login/2fa?challenge=cookie')
+
+    render(<TwoFactorVerifyPage />)
+
+    expect(screen.getByText('Two-Factor Authentication')).toBeInTheDocument()
+    expect(useAuthStore.getState().pendingChallenge).toEqual({ challenge_token: null })
+    window.history.pushState({}, '', '/')
+  })
+
   it('shows back to login link', () => {
     // TODO: as never cast needed because pendingChallenge type isn't exported from the store — export the type
     useAuthStore.setState({
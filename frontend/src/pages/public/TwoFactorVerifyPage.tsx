@@ -1,4 +1,4 @@
-import { useState, useRef } from 'react'
+import { useState, useRef, useEffect } from 'react'
 import { Link, useNavigate, useSearchParams } from 'react-router-dom'
 import { useAuthStore } from '@/stores/authStore'
 import { Button } from '@/components/ui/button'
@@ -28,6 +28,14 @@ export function TwoFactorVerifyPage() {
   const [useRecovery, setUseRecovery] = useState(false)
   const redirectUrl = getRedirectUrl(searchParams)
   const redirectingRef = useRef(false)
+  // A magic link click-through leaves the challenge in an httpOnly cookie
+  const cookieChallenge = searchParams.get('challenge') === 'cookie'
+
+  useEffect(() => {
+    if (cookieChallenge && !useAuthStore.getState().pendingChallenge) {
+      useAuthStore.setState({ pendingChallenge: { challenge_token: null } })
+    }
+  }, [cookieChallenge])
 
   const loginPath = redirectUrl !== '/dashboard'
     ? `/login?redirect=${encodeURIComponent(redirectUrl)}`
@@ -44,7 +52,7 @@ export function TwoFactorVerifyPage() {
   }
 
   // Redirect if no pending challenge (but not if we're mid-redirect after successful 2FA)
-  if (!pendingChallenge && !redirectingRef.current) {
+  if (!pendingChallenge && !cookieChallenge && !redirectingRef.current) {
     return (
       <div className="flex min-h-[calc(100vh-8rem)] items-center justify-center py-12">
         <Card className="w-full max-w-md">
@@ -0,0 +1,70 @@
+import { describe, it, expect, beforeEach } from 'vitest'
+import { screen, waitFor } from '@testing-library/react'
+import userEvent from '@testing-library/user-event'
+import { MemoryRouter, Route, Routes } from 'react-router-dom'
+import { render as rtlRender } from '@testing-library/react'
+import { QueryClient, QueryClientProvider } from '@tanstack/react-query'
+import { SetPasswordPage } from './SetPasswordPage'
+import { setupAuthUser } from '@/test/utils'
+
+function renderWithToken(search = '') {
+  const queryClient = new QueryClient({ defaultOptions: { queries: { retry: false } } })
+  return rtlRender(
+    <QueryClientProvider client={queryClient}>
+      <MemoryRouter initialEntries={[`/settings/set-password${search}`]}>
+        <Routes>
+          <Route path="/settings/set-password" element={<SetPasswordPage />} />
+          <Route path="/settings" element={<div>Settings Page</div>} />
+        </Routes>
+      </MemoryRouter>
+    </QueryClientProvider>
+  )
+}
+
+beforeEach(() => {
+  setupAuthUser()
+})
+
+describe('SetPasswordPage', () => {
+  it('shows invalid link state when no token', () => {
+    renderWithToken()
+
+    expect(screen.getByText('Invalid Link')).toBeInTheDocument()
+    expect(screen.getByRole('button', { name: /go to settings/i })).toBeInTheDocument()
+  })
+
+  it('shows password form when token is present', () => {
+    renderWithToken('?token=some-token')
+
+    expect(screen.getByText('Set a password')).toBeInTheDocument()
+    expect(screen.getByLabelText('New Password')).toBeInTheDocument()
+    expect(screen.getByLabelText('Confirm Password')).toBeInTheDocument()
+    expect(screen.getByText('At least 12 characters')).toBeInTheDocument()
+  })
+
+  it('shows success state after setting the password', async () => {
+    const user = userEvent.setup()
+    renderWithToken('?token=valid-set-password-token')
+
+    await user.type(screen.getByLabelText('New Password'), 'ValidPass123!')
+    await user.type(screen.getByLabelText('Confirm Password'), 'ValidPass123!')
+    await user.click(screen.getByRole('button', { name: /set password/i }))
+
+    await waitFor(() => {
+      expect(screen.getByText('Password Set')).toBeInTheDocument()
+    })
+  })
+
+  it('shows the API error for an invalid token', async () => {
+    const user = userEvent.setup()
+    renderWithToken('?token=expired-token')
+
+    await user.type(screen.getByLabelText('New Password'), 'ValidPass123!')
+    await user.type(screen.getByLabelText('Confirm Password'), 'ValidPass123!')
+    await user.click(screen.getByRole('button', { name: /set password/i }))
+
+    await waitFor(() => {
+      expect(screen.getByText('Invalid or expired token')).toBeInTheDocument()
+    })
+  })
+})
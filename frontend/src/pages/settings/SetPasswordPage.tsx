@@ -0,0 +1,191 @@
+import { useState } from 'react'
+import { Link, useSearchParams } from 'react-router-dom'
+import { useForm } from 'react-hook-form'
+import { zodResolver } from '@hookform/resolvers/zod'
+import { z } from 'zod'
+import { authApi } from '@/api'
+import { useAuthStore } from '@/stores/authStore'
+import { Button } from '@/components/ui/button'
+import { Input } from '@/components/ui/input'
+import { Label } from '@/components/ui/label'
+import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
+import { Alert, AlertDescription } from '@/components/ui/alert'
+import { AlertCircle, Loader2, KeyRound, Check } from 'lucide-react'
+
+const setPasswordSchema = z.object({
+  password: z
+    .string()
+    .min(12, 'Password must be at least 12 characters')
+    .regex(/[a-z]/, 'Password must contain a lowercase letter')
+    .regex(/[A-Z]/, 'Password must contain an uppercase letter')
+    .regex(/[0-9]/, 'Password must contain a number')
+    .regex(/[^a-zA-Z0-9]/, 'Password must contain a special character'),
+  confirmPassword: z.string(),
+}).refine((data) => data.password === data.confirmPassword, {
+  message: "Passwords don't match",
+  path: ['confirmPassword'],
+})
+
+type SetPasswordFormData = z.infer<typeof setPasswordSchema>
+
+const passwordRequirements = [
+  { label: 'At least 12 characters', test: (p: string) => p.length >= 12 },
+  { label: 'One lowercase letter', test: (p: string) => /[a-z]/.test(p) },
+  { label: 'One uppercase letter', test: (p: string) => /[A-Z]/.test(p) },
+  { label: 'One number', test: (p: string) => /[0-9]/.test(p) },
+  { label: 'One special character', test: (p: string) => /[^a-zA-Z0-9]/.test(p) },
+]
+
+export function SetPasswordPage() {
+  const [searchParams] = useSearchParams()
+  const token = searchParams.get('token')
+  const [isLoading, setIsLoading] = useState(false)
+  const [error, setError] = useState<string | null>(null)
+  const [isDone, setIsDone] = useState(false)
+
+  const {
+    register,
+    handleSubmit,
+    watch,
+    formState: { errors },
+  } = useForm<SetPasswordFormData>({
+    resolver: zodResolver(setPasswordSchema),
+  })
+
+  const password = watch('password', '')
+
+  const onSubmit = async (data: SetPasswordFormData) => {
+    if (!token) return
+
+    setIsLoading(true)
+    setError(null)
+    try {
+      await authApi.setPassword({ token, new_password: data.password })
+      setIsDone(true)
+      useAuthStore.getState().refreshUser()
+    } catch (err) {
+      const apiError = err as { error?: { message?: string } }
+      setError(apiError.error?.message || 'Failed to set password')
+    } finally {
+      setIsLoading(false)
+    }
+  }
+
+  if (!token) {
+    return (
+      <div className="flex min-h-[calc(100vh-8rem)] items-center justify-center py-12">
+        <Card className="w-full max-w-md">
+          <CardHeader className="text-center">
+            <div className="mx-auto mb-4 flex h-12 w-12 items-center justify-center rounded-full bg-destructive/10">
+              <AlertCircle className="h-6 w-6 text-destructive" />
+            </div>
+            <CardTitle className="text-2xl">Invalid Link</CardTitle>
+            <CardDescription>
+              This set password link is invalid or has expired.
+            </CardDescription>
+          </CardHeader>
+          <CardContent>
+            <Link to="/settings" className="block">
+              <Button className="w-full">Go to Settings</Button>
+            </Link>
+          </CardContent>
+        </Card>
+      </div>
+    )
+  }
+
+  if (isDone) {
+    return (
+      <div className="flex min-h-[calc(100vh-8rem)] items-center justify-center py-12">
+        <Card className="w-full max-w-md">
+          <CardHeader className="text-center">
+            <div className="mx-auto mb-4 flex h-12 w-12 items-center justify-center rounded-full bg-teal-500/10">
+              <Check className="h-6 w-6 text-teal-600 dark:text-teal-400" />
+            </div>
+            <CardTitle className="text-2xl">Password Set</CardTitle>
+            <CardDescription>
+              You can now sign in with your email and password.
+            </CardDescription>
+          </CardHeader>
+          <CardContent>
+            <Link to="/settings" className="block">
+              <Button className="w-full">Go to Settings</Button>
+            </Link>
+          </CardContent>
+        </Card>
+      </div>
+    )
+  }
+
+  return (
+    <div className="flex min-h-[calc(100vh-8rem)] items-center justify-center py-12">
+      <Card className="w-full max-w-md">
+        <CardHeader className="text-center">
+          <div className="mx-auto mb-4 flex h-12 w-12 items-center justify-center rounded-full bg-primary/10">
+            <KeyRound className="h-6 w-6 text-primary" />
+          </div>
+          <CardTitle className="text-2xl">Set a password</CardTitle>
+          <CardDescription>
+            Add a password so you can also sign in without an email link.
+          </CardDescription>
+        </CardHeader>
+        <CardContent>
+          <form onSubmit={handleSubmit(onSubmit)} className="space-y-4">
+            {error && (
+              <Alert variant="destructive">
+                <AlertCircle className="h-4 w-4" />
+                <AlertDescription>{error}</AlertDescription>
+              </Alert>
+            )}
+
+            <div className="space-y-2">
+              <Label htmlFor="password">New Password</Label>
+              <Input
+                id="password"
+                type="password"
+                {...register('password')}
+              />
+              {errors.password && (
+                <p className="text-sm text-destructive">{errors.password.message}</p>
+              )}
+              <div className="mt-2 space-y-1">
+                {passwordRequirements.map((req) => (
+                  <div
+                    key={req.label}
+                    className={`flex items-center gap-2 text-xs ${
+                      req.test(password)
+                        ? 'text-green-600'
+                        : 'text-muted-foreground'
+                    }`}
+                  >
+                    <Check className={`h-3 w-3 ${req.test(password) ? 'opacity-100' : 'opacity-30'}`} />
+                    {req.label}
+                  </div>
+                ))}
+              </div>
+            </div>
+
+            <div className="space-y-2">
+              <Label htmlFor="confirmPassword">Confirm Password</Label>
+              <Input
+                id="confirmPassword"
+                type="password"
+                {...register('confirmPassword')}
+              />
+              {errors.confirmPassword && (
+                <p className="text-sm text-destructive">
+                  {errors.confirmPassword.message}
+                </p>
+              )}
+            </div>
+
+            <Button type="submit" className="w-full" disabled={isLoading}>
+              {isLoading && <Loader2 className="mr-2 h-4 w-4 animate-spin" />}
+              Set Password
+            </Button>
+          </form>
+        </CardContent>
+      </Card>
+    </div>
+  )
+}
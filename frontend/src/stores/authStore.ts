@@ -61,7 +61,8 @@ interface AuthState {
   isAuthenticated: boolean
   isLoading: boolean
   error: string | null
-  pendingChallenge: { challenge_token: string } | null
+  /** `challenge_token` is null when the API holds it in an httpOnly cookie (magic link click-through) */
+  pendingChallenge: { challenge_token: string | null } | null
 
   // Actions
   setUser: (user: User | null) => void
@@ -145,7 +146,7 @@ export const useAuthStore = create<AuthState>()(
         set({ isLoading: true, error: null })
         try {
           const response = await authApi.verify2FA({
-            challenge_token: pendingChallenge.challenge_token,
+            challenge_token: pendingChallenge.challenge_token ?? undefined,
             code,
           })
           set({
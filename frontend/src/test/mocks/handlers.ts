@@ -93,6 +93,8 @@ export const mockAdminMembership = {
   status: 'active',
   subscription_tier: 'standard',
   subscription_override_by: null,
+  cancellation_reason: null,
+  cancellation_feedback: null,
   created_at: '2024-01-01T00:00:00Z',
 }
 
@@ -342,6 +344,25 @@ export const handlers = [
   }),
 
   // Confirm password reset
+  http.post(`${API_BASE}/auth/set-password`, async ({ request }) => {
+    const body = await request.json() as { token: string; new_password: string }
+
+    if (body.token === 'valid-set-password-token') {
+      return HttpResponse.json({ success: true, data: null })
+    }
+
+    return HttpResponse.json(
+      {
+        success: false,
+        error: {
+          code: 'INVALID_TOKEN',
+          message: 'Invalid or expired token',
+        },
+      },
+      { status: 400 }
+    )
+  }),
+
   http.post(`${API_BASE}/auth/password-reset/confirm`, async ({ request }) => {
     const body = await request.json() as { token: string; password: string }
 
@@ -493,7 +514,7 @@ export const handlers = [
   http.get(`${API_BASE}/admin/applications`, () => {
     return HttpResponse.json({
       success: true,
-      data: { applications: [mockAdminApplication] },
+      data: { items: [mockAdminApplication], total: 1, page: 1, per_page: 100, total_pages: 1 },
     })
   }),
 
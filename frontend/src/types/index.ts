@@ -26,6 +26,11 @@ export interface User {
   subscription_tier: SubscriptionTier
   trial_ends_at: string | null
   lifetime_member: boolean
+  /** Email of the admin impersonating this user, when applicable */
+  impersonated_by?: string
+  /** Set on deactivated accounts in admin views */
+  deactivated_at?: string
+  deactivation_reason?: string
 }
 
 // Auth types